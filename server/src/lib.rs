@@ -0,0 +1,2110 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::{self, File},
+    io::Write,
+    net::SocketAddr,
+    path::{Path as FilePath, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::{
+    extract::{Json, Path, Query, State},
+    http::{header::AUTHORIZATION, HeaderMap, Request},
+    middleware::{self, Next},
+    response::Response,
+    routing::{get, post},
+    Router,
+};
+use dashmap::{mapref::entry::Entry, DashMap, DashSet};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use shared::{
+    balance, hmac_sha256, AchievementTracker, ArenaGeometry, ArenaMap, BalanceConfig, Bracket,
+    BracketPhase, ChatContent, Lobby, LobbyError, LobbyErrorKind, LobbySettings, LobbySort,
+    MapGallery, Message, Nickname, Player, Profile, Replay, Result as GameResult, ServerStatus,
+    SessionBugReport, SessionGrant, SessionMessage, SessionNewLobby, SessionReplay, SessionRequest,
+    SessionSharedMap, SharedMap, Team, Turn, TurnSync, ZoneBot, BRACKET_CODE_LENGTH,
+};
+use tower_http::services::{ServeDir, ServeFile};
+
+/// Most lobbies hosted at once, beyond which new lobbies are refused.
+const MAX_LOBBIES: usize = 1024;
+
+/// Longest replay accepted for verification, bounding the cost of re-simulating it.
+const MAX_VERIFY_TURNS: usize = 256;
+
+/// Length of issued session IDs, long enough that two sessions never draw the same one.
+const SESSION_ID_LENGTH: usize = 16;
+
+/// Length of the key session tokens are signed with when `SESSION_SECRET` is not set.
+const SESSION_SECRET_LENGTH: usize = 32;
+
+/// Region reported when the `REGION` environment variable is not set.
+const DEFAULT_REGION: &str = "local";
+
+/// Period at which each lobby's clock checks whether its next turn is due.
+const LOBBY_CLOCK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Balance config shared with the client, served from the static directory.
+const BALANCE_PATH: &str = "static/balance.json";
+
+/// File the map gallery is persisted to, reloaded on restart.
+const MAPS_PATH: &str = "maps.json";
+
+/// File the verified profiles are persisted to, reloaded on restart.
+const PROFILES_PATH: &str = "profiles.json";
+
+/// Seconds a session waits between replay verifications, each re-simulating a whole game.
+const VERIFY_REPLAY_INTERVAL: f64 = 30.0;
+
+/// Window over which [`AppState::VERIFY_REPLAYS_PER_WINDOW`] are served across all sessions.
+const VERIFY_REPLAY_WINDOW: f64 = 60.0;
+
+/// Seconds a level seed remains valid for verifying a replay after it was issued.
+const LEVEL_SEED_SECONDS: f64 = 4.0 * 3600.0;
+
+/// Most level seeds awaiting a replay at once, beyond which new ones are refused.
+const MAX_LEVEL_SEEDS: usize = 65536;
+
+/// Most levels a profile counts verified wins on, beyond which further wins are not counted.
+const MAX_PROFILE_LEVELS: usize = 256;
+
+/// Path of the words masked in chat, one per line, relative to the working directory.
+const CHAT_BLOCKLIST_PATH: &str = "chat_blocklist.txt";
+
+/// Period at which brackets check their matches for results and host the next ones.
+const BRACKET_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Seconds a bracket match may wait for its players before whoever showed up advances.
+const BRACKET_NO_SHOW_SECONDS: f64 = 600.0;
+
+/// Seconds a finished bracket remains viewable before it is dropped.
+const BRACKET_RETENTION_SECONDS: f64 = 3600.0;
+
+/// Most brackets hosted at once, beyond which new brackets are refused.
+const MAX_BRACKETS: usize = 256;
+
+/// Maps listed on each page of the gallery.
+const MAPS_PAGE_SIZE: usize = 4;
+
+/// Longest title a shared map may be listed under.
+const MAX_MAP_TITLE: usize = 24;
+
+/// Most maps the gallery holds, beyond which new shares are refused.
+const MAX_SHARED_MAPS: usize = 4096;
+
+/// Distinct reporters after which a map is queued for moderators to review, counting only sessions
+/// which played the map and have a verified win. Reports alone never hide a map, since sessions are
+/// free to obtain.
+const MAP_REPORT_THRESHOLD: usize = 3;
+
+/// File bug reports are appended to, one JSON object per line.
+const BUG_REPORTS_PATH: &str = "bug_reports.jsonl";
+
+/// Longest description a bug report may carry, beyond which it is cut.
+const MAX_BUG_DESCRIPTION: usize = 1024;
+
+/// Most log lines a bug report may carry, the latest being kept.
+const MAX_BUG_LOG_LINES: usize = 256;
+
+/// Longest log line a bug report may carry, beyond which it is cut.
+const MAX_BUG_LOG_LINE: usize = 256;
+
+/// Seconds a session waits between bug reports.
+const BUG_REPORT_INTERVAL: f64 = 60.0;
+
+/// Window over which [`AppState::BUG_REPORTS_PER_WINDOW`] are accepted across all sessions.
+const BUG_REPORT_WINDOW: f64 = 3600.0;
+
+/// Size in bytes of the reports file beyond which further bug reports are refused.
+const MAX_BUG_REPORTS_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Directory finished lobbies are archived to along with their replays, gzipped.
+const ARCHIVE_DIR: &str = "lobbies/archive";
+
+/// Seconds between archiving finished lobbies when `LOBBY_FLUSH_SECONDS` is not set.
+const DEFAULT_FLUSH_SECONDS: u64 = 5;
+
+/// Period at which the retention policy prunes the archive.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Seconds without heartbeats after which clients are warned that their lobby is about to be
+/// dropped, when `LOBBY_IDLE_WARNING_SECONDS` is not set.
+const DEFAULT_IDLE_WARNING_SECONDS: f64 = 10.0;
+
+/// When lobbies whose players stopped sending heartbeats are dropped, and when their clients are
+/// warned beforehand with a [`Message::LobbyExpiryWarning`].
+///
+/// Configured by `LOBBY_IDLE_SECONDS` and `LOBBY_IDLE_WARNING_SECONDS`, which default to
+/// [`Player::CONNECTION_TIMEOUT`] and [`DEFAULT_IDLE_WARNING_SECONDS`].
+#[derive(Clone, Copy)]
+struct IdleConfig {
+    timeout: f64,
+    warning: f64,
+}
+
+impl Default for IdleConfig {
+    fn default() -> IdleConfig {
+        IdleConfig {
+            timeout: Player::CONNECTION_TIMEOUT,
+            warning: DEFAULT_IDLE_WARNING_SECONDS,
+        }
+    }
+}
+
+impl IdleConfig {
+    fn from_env() -> IdleConfig {
+        let read = |key| {
+            std::env::var(key)
+                .ok()
+                .and_then(|value| value.parse::<f64>().ok())
+                .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+        };
+
+        let default = IdleConfig::default();
+        let timeout = read("LOBBY_IDLE_SECONDS").unwrap_or(default.timeout);
+
+        IdleConfig {
+            timeout,
+            warning: read("LOBBY_IDLE_WARNING_SECONDS")
+                .unwrap_or(default.warning)
+                .min(timeout),
+        }
+    }
+
+    /// Returns the seconds left before a lobby idle for the given seconds is dropped, once its
+    /// clients are to be warned.
+    fn seconds_left(&self, idle: f64) -> Option<f64> {
+        (self.warning..self.timeout)
+            .contains(&idle)
+            .then_some(self.timeout - idle)
+    }
+}
+
+/// How often finished lobbies are archived to disk and how long their archives are kept.
+///
+/// Configured by `LOBBY_FLUSH_SECONDS`, and by `REPLAY_RETENTION_DAYS` and
+/// `REPLAY_RETENTION_PER_PLAYER`; an archive is kept while either policy keeps it, and forever when
+/// neither is set.
+#[derive(Clone, Copy)]
+struct PersistenceConfig {
+    flush_interval: Duration,
+    retention_age: Option<Duration>,
+    retention_per_player: Option<usize>,
+}
+
+impl PersistenceConfig {
+    fn from_env() -> PersistenceConfig {
+        let read = |key| {
+            std::env::var(key)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+        };
+
+        PersistenceConfig {
+            flush_interval: Duration::from_secs(
+                read("LOBBY_FLUSH_SECONDS")
+                    .unwrap_or(DEFAULT_FLUSH_SECONDS)
+                    .max(1),
+            ),
+            retention_age: read("REPLAY_RETENTION_DAYS")
+                .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+            retention_per_player: read("REPLAY_RETENTION_PER_PLAYER").map(|count| count as usize),
+        }
+    }
+
+    fn retains_all(&self) -> bool {
+        self.retention_age.is_none() && self.retention_per_player.is_none()
+    }
+}
+
+/// Delay before the first retry of a failed webhook delivery, doubled for every further retry.
+const WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Attempts at delivering a webhook before it is given up on.
+const WEBHOOK_ATTEMPTS: u32 = 5;
+
+/// Longest a single webhook delivery may take before it counts as failed.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Header carrying the hex-encoded HMAC-SHA256 of a webhook's body under `WEBHOOK_SECRET`.
+const WEBHOOK_SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// Endpoints told about every finished lobby, for community bots and stat sites.
+///
+/// Configured by `WEBHOOK_URLS`, comma-separated, and optionally `WEBHOOK_SECRET` to sign the
+/// deliveries with. Only plain `http://` URLs can be delivered to, so HTTPS endpoints such as
+/// Discord's have to sit behind a relay.
+#[derive(Clone)]
+struct WebhookConfig {
+    urls: Vec<hyper::Uri>,
+    secret: Option<String>,
+}
+
+impl WebhookConfig {
+    fn from_env() -> Option<WebhookConfig> {
+        let urls: Vec<hyper::Uri> = std::env::var("WEBHOOK_URLS")
+            .ok()?
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .filter_map(|url| match url.parse::<hyper::Uri>() {
+                Ok(uri) if uri.scheme_str() == Some("http") => Some(uri),
+                _ => {
+                    eprintln!("ignoring webhook {url}, only http:// URLs are supported");
+                    None
+                }
+            })
+            .collect();
+
+        if urls.is_empty() {
+            return None;
+        }
+
+        Some(WebhookConfig {
+            urls,
+            secret: std::env::var("WEBHOOK_SECRET").ok(),
+        })
+    }
+}
+
+/// Summary of a finished lobby as posted to webhooks.
+#[derive(Serialize)]
+struct FinishedLobby {
+    lobby: u16,
+    region: String,
+    players: Vec<FinishedLobbyPlayer>,
+    result: GameResult,
+    /// Seconds from the start of the game to its result.
+    duration: f64,
+    turns: usize,
+    finished_at: f64,
+}
+
+/// A player of a [`FinishedLobby`], by nickname so that session IDs are never sent out.
+#[derive(Serialize)]
+struct FinishedLobbyPlayer {
+    nickname: String,
+    team: Team,
+}
+
+impl FinishedLobby {
+    fn new(id: u16, region: &str, lobby: &Lobby) -> Option<FinishedLobby> {
+        let finished_at = timestamp();
+        let started_at = lobby.warmup_until.unwrap_or(lobby.first_heartbeat);
+
+        let mut players: Vec<FinishedLobbyPlayer> = lobby
+            .players()
+            .iter()
+            .map(|(session_id, player)| FinishedLobbyPlayer {
+                nickname: Nickname::from_session(session_id).to_string(),
+                team: player.team,
+            })
+            .collect();
+        players.sort_by(|a, b| a.nickname.cmp(&b.nickname));
+
+        Some(FinishedLobby {
+            lobby: id,
+            region: region.to_string(),
+            players,
+            result: lobby.game.result()?,
+            duration: (finished_at - started_at).max(0.0),
+            turns: lobby.game.turns_count(),
+            finished_at,
+        })
+    }
+}
+
+/// A finished lobby as archived, with the replay of its game which the lobby itself leaves out,
+/// unless the game outlasted its turn history.
+#[derive(Serialize)]
+struct ArchivedLobby<'a> {
+    lobby: &'a Lobby,
+    replay: Option<Replay>,
+}
+
+/// Artificial delay added to every response, a test mode for netcode under latency.
+///
+/// Enabled by setting `NET_DELAY_MS` and optionally `NET_JITTER_MS` in the environment.
+#[derive(Clone, Copy)]
+struct NetworkDelay {
+    delay: u64,
+    jitter: u64,
+}
+
+impl NetworkDelay {
+    fn from_env() -> Option<NetworkDelay> {
+        let read = |key| {
+            std::env::var(key)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+        };
+
+        let delay = read("NET_DELAY_MS");
+        let jitter = read("NET_JITTER_MS");
+
+        if delay.is_none() && jitter.is_none() {
+            return None;
+        }
+
+        Some(NetworkDelay {
+            delay: delay.unwrap_or_default(),
+            jitter: jitter.unwrap_or_default(),
+        })
+    }
+}
+
+async fn delay_response<B>(
+    State(network_delay): State<NetworkDelay>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let delay = network_delay.delay + rand::thread_rng().gen_range(0..=network_delay.jitter);
+
+    let response = next.run(request).await;
+
+    tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+
+    response
+}
+
+#[derive(Deserialize)]
+struct TurnsQuery {
+    session_id: Option<String>,
+}
+
+/// A map in the gallery, with its moderation state.
+#[derive(Serialize, Deserialize)]
+struct StoredMap {
+    map: SharedMap,
+    /// Session ID that shared the map.
+    author: String,
+    /// Hidden maps are left out of the gallery.
+    hidden: bool,
+    /// Session IDs that reported the map.
+    reporters: HashSet<String>,
+    /// Session IDs that played the map, each counted once in its plays.
+    #[serde(default)]
+    players: HashSet<String>,
+}
+
+/// A session's verified results, with the levels it was verified to have won.
+#[derive(Serialize, Deserialize, Default)]
+struct StoredProfile {
+    profile: Profile,
+    /// Levels won, see [`level_key`], so winning the same level again counts once.
+    levels: HashSet<String>,
+}
+
+/// Writes of a file rewritten as a whole on every change, kept in order so that an older snapshot
+/// written late never overwrites a newer one.
+#[derive(Clone, Default)]
+struct FileWrites {
+    /// Number of snapshots taken so far.
+    taken: Arc<AtomicU64>,
+    /// Latest snapshot written, locked while it is being written.
+    written: Arc<Mutex<u64>>,
+}
+
+/// Limits how often a costly request is served, both per session and across all sessions, since
+/// new sessions are free to obtain.
+#[derive(Clone)]
+struct RateLimit {
+    /// Seconds a session waits between its requests.
+    session_interval: f64,
+    /// Requests served across all sessions within each `window`.
+    requests_per_window: usize,
+    /// Seconds over which `requests_per_window` are counted.
+    window: f64,
+    served: Arc<Mutex<ServedRequests>>,
+}
+
+#[derive(Default)]
+struct ServedRequests {
+    /// Time of each session's latest request within its interval.
+    by_session: HashMap<String, f64>,
+    /// Times of the requests served within the window, oldest first.
+    recent: VecDeque<f64>,
+}
+
+impl RateLimit {
+    fn new(session_interval: f64, requests_per_window: usize, window: f64) -> RateLimit {
+        RateLimit {
+            session_interval,
+            requests_per_window,
+            window,
+            served: Arc::new(Mutex::new(ServedRequests::default())),
+        }
+    }
+
+    /// Counts a request of the session made at `now`, unless the session or all sessions together
+    /// made too many already.
+    fn admit(&self, session_id: &str, now: f64) -> bool {
+        let mut served = self.served.lock().unwrap();
+
+        served
+            .by_session
+            .retain(|_, served_at| now - *served_at < self.session_interval);
+
+        while served
+            .recent
+            .front()
+            .is_some_and(|served_at| now - served_at >= self.window)
+        {
+            served.recent.pop_front();
+        }
+
+        if served.by_session.contains_key(session_id)
+            || served.recent.len() >= self.requests_per_window
+        {
+            return false;
+        }
+
+        served.by_session.insert(session_id.to_string(), now);
+        served.recent.push_back(now);
+
+        true
+    }
+}
+
+/// Hook every free text line of lobby chats passes through before it is relayed.
+pub trait ChatFilter: Send + Sync {
+    /// Returns the text relayed in place of the given line, which is dropped if blank.
+    fn filter(&self, text: &str) -> String;
+}
+
+/// A [`ChatFilter`] masking blocked words with asterisks, wherever they appear and in any case.
+#[derive(Default)]
+pub struct Blocklist {
+    words: Vec<Vec<char>>,
+}
+
+impl Blocklist {
+    /// Creates a blocklist of the given words.
+    pub fn new<'a>(words: impl IntoIterator<Item = &'a str>) -> Blocklist {
+        Blocklist {
+            words: words
+                .into_iter()
+                .map(|word| word.trim().chars().flat_map(char::to_lowercase).collect())
+                .filter(|word: &Vec<char>| !word.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Loads the blocklist at [`CHAT_BLOCKLIST_PATH`], skipping lines starting with `#`, or masks
+    /// nothing if there is none.
+    fn load() -> Blocklist {
+        match fs::read_to_string(CHAT_BLOCKLIST_PATH) {
+            Ok(list) => Blocklist::new(list.lines().filter(|line| !line.starts_with('#'))),
+            Err(_) => Blocklist::default(),
+        }
+    }
+}
+
+impl ChatFilter for Blocklist {
+    fn filter(&self, text: &str) -> String {
+        let mut chars: Vec<char> = text.chars().collect();
+
+        // Lowercased like the words, each lowercase character noting the character it came from
+        let (lowercase, origins): (Vec<char>, Vec<usize>) = chars
+            .iter()
+            .enumerate()
+            .flat_map(|(index, char)| char.to_lowercase().map(move |lower| (lower, index)))
+            .unzip();
+
+        for word in &self.words {
+            for start in 0..lowercase.len().saturating_sub(word.len() - 1) {
+                if lowercase[start..].starts_with(word) {
+                    chars[origins[start]..=origins[start + word.len() - 1]].fill('*');
+                }
+            }
+        }
+
+        chars.into_iter().collect()
+    }
+}
+
+/// State shared by the handlers of the [`router`].
+#[derive(Clone)]
+pub struct AppState {
+    /// Lobbies by ID, sharded so requests to different lobbies do not contend.
+    lobbies: Arc<DashMap<u16, Lobby>>,
+    /// Key the tokens of sessions are signed with, so they are checked without storing them.
+    session_secret: Arc<Vec<u8>>,
+    /// Verified results by session ID.
+    profiles: Arc<Mutex<HashMap<String, StoredProfile>>>,
+    /// Writes of the profiles to [`PROFILES_PATH`].
+    profile_writes: FileWrites,
+    /// Replay verifications served, to rate limit them.
+    verify_limit: RateLimit,
+    /// Level seeds awaiting a replay, with the session each was issued to and when.
+    level_seeds: Arc<Mutex<HashMap<u64, (String, f64)>>>,
+    /// Bug reports accepted, to rate limit them.
+    bug_report_limit: RateLimit,
+    /// Region this server is deployed in, tagged onto its lobbies.
+    region: Arc<String>,
+    /// Community map gallery, in order of sharing.
+    maps: Arc<Mutex<Vec<StoredMap>>>,
+    /// Writes of the map gallery to [`MAPS_PATH`].
+    map_writes: FileWrites,
+    /// Bearer token allowing reported maps to be reviewed, from the `MODERATOR_TOKEN` environment
+    /// variable.
+    moderator_token: Arc<Option<String>>,
+    /// Lobbies changed since the last flush, archived if they finished.
+    dirty_lobbies: Arc<DashSet<u16>>,
+    /// Tournament brackets by join code.
+    brackets: Arc<Mutex<HashMap<String, HostedBracket>>>,
+    /// Filter of free text said in lobby chats.
+    chat_filter: Arc<dyn ChatFilter>,
+    /// Endpoints finished lobbies are posted to, if any are configured.
+    webhooks: Arc<Option<WebhookConfig>>,
+    /// When idle lobbies are dropped.
+    idle: IdleConfig,
+}
+
+/// A bracket and the time its final was decided, after which it is eventually dropped.
+struct HostedBracket {
+    bracket: Bracket,
+    finished_at: Option<f64>,
+}
+
+impl AppState {
+    /// Replays verified across all sessions within a minute, beyond which verification is refused.
+    pub const VERIFY_REPLAYS_PER_WINDOW: usize = 10;
+
+    /// Bug reports accepted across all sessions within an hour, beyond which reports are refused.
+    pub const BUG_REPORTS_PER_WINDOW: usize = 60;
+
+    /// Creates the state of a server without lobbies, sessions or shared maps.
+    pub fn new(region: String, moderator_token: Option<String>) -> AppState {
+        AppState {
+            lobbies: Arc::new(DashMap::new()),
+            session_secret: Arc::new(generate_alphanumeric(SESSION_SECRET_LENGTH).into_bytes()),
+            profiles: Arc::new(Mutex::new(HashMap::new())),
+            profile_writes: FileWrites::default(),
+            verify_limit: RateLimit::new(
+                VERIFY_REPLAY_INTERVAL,
+                AppState::VERIFY_REPLAYS_PER_WINDOW,
+                VERIFY_REPLAY_WINDOW,
+            ),
+            level_seeds: Arc::new(Mutex::new(HashMap::new())),
+            bug_report_limit: RateLimit::new(
+                BUG_REPORT_INTERVAL,
+                AppState::BUG_REPORTS_PER_WINDOW,
+                BUG_REPORT_WINDOW,
+            ),
+            region: Arc::new(region),
+            maps: Arc::new(Mutex::new(Vec::new())),
+            map_writes: FileWrites::default(),
+            moderator_token: Arc::new(moderator_token),
+            dirty_lobbies: Arc::new(DashSet::new()),
+            brackets: Arc::new(Mutex::new(HashMap::new())),
+            webhooks: Arc::new(None),
+            idle: IdleConfig::default(),
+            chat_filter: Arc::new(Blocklist::default()),
+        }
+    }
+
+    /// Filters free text said in lobby chats with the given [`ChatFilter`].
+    pub fn with_chat_filter(self, chat_filter: impl ChatFilter + 'static) -> AppState {
+        AppState {
+            chat_filter: Arc::new(chat_filter),
+            ..self
+        }
+    }
+
+    /// Signs a session ID into the token issued for it.
+    fn session_token(&self, session_id: &str) -> String {
+        to_hex(&hmac_sha256(&self.session_secret, session_id.as_bytes()))
+    }
+
+    /// Checks the bearer token in the `Authorization` header against the one issued for the session ID.
+    fn authorized(&self, headers: &HeaderMap, session_id: &str) -> bool {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match token {
+            Some(token) => constant_time_eq(token, &self.session_token(session_id)),
+            None => false,
+        }
+    }
+
+    /// Checks the bearer token in the `Authorization` header against the moderator token.
+    fn moderator(&self, headers: &HeaderMap) -> bool {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match (token, self.moderator_token.as_deref()) {
+            (Some(token), Some(moderator_token)) => constant_time_eq(token, moderator_token),
+            _ => false,
+        }
+    }
+}
+
+fn unauthorized() -> Message {
+    Message::LobbyError(LobbyError::new(
+        LobbyErrorKind::Unauthorized,
+        "unauthorized session",
+    ))
+}
+
+fn bracket_not_found() -> Message {
+    Message::LobbyError(LobbyError::new(
+        LobbyErrorKind::NotFound,
+        "bracket does not exist",
+    ))
+}
+
+fn map_not_found() -> Message {
+    Message::LobbyError(LobbyError::new(
+        LobbyErrorKind::NotFound,
+        "map does not exist",
+    ))
+}
+
+/// Loads the balance config and the map gallery, starts the background tasks and serves the
+/// [`router`] until the server is stopped.
+pub async fn run() {
+    // Balance patches are picked up on restart, from the same asset the client loads
+    match std::fs::read_to_string(BALANCE_PATH) {
+        Ok(json) => match BalanceConfig::from_json(&json) {
+            Ok(config) => {
+                let _ = config.install();
+            }
+            Err(err) => eprintln!("invalid {BALANCE_PATH}, using defaults: {err}"),
+        },
+        Err(_) => eprintln!("no {BALANCE_PATH}, using defaults"),
+    }
+
+    let webhooks = WebhookConfig::from_env();
+
+    if let Some(webhooks) = &webhooks {
+        eprintln!(
+            "posting finished lobbies to {} webhook(s){}",
+            webhooks.urls.len(),
+            if webhooks.secret.is_some() {
+                ", signed"
+            } else {
+                ""
+            }
+        );
+    }
+
+    let mut state = AppState {
+        maps: Arc::new(Mutex::new(load_maps())),
+        profiles: Arc::new(Mutex::new(load_profiles())),
+        chat_filter: Arc::new(Blocklist::load()),
+        webhooks: Arc::new(webhooks),
+        idle: IdleConfig::from_env(),
+        ..AppState::new(
+            std::env::var("REGION").unwrap_or_else(|_| DEFAULT_REGION.to_string()),
+            std::env::var("MODERATOR_TOKEN").ok(),
+        )
+    };
+
+    // Without a configured secret, sessions issued before a restart are no longer accepted
+    if let Ok(secret) = std::env::var("SESSION_SECRET") {
+        state.session_secret = Arc::new(secret.into_bytes());
+    }
+
+    let persistence_config = PersistenceConfig::from_env();
+
+    tokio::spawn(run_lobby_flush(state.clone(), persistence_config));
+    tokio::spawn(run_archive_retention(persistence_config));
+    tokio::spawn(run_brackets(state.clone()));
+
+    let app = router(state);
+
+    let app = match NetworkDelay::from_env() {
+        Some(network_delay) => {
+            eprintln!(
+                "delaying responses by {}+{}ms",
+                network_delay.delay, network_delay.jitter
+            );
+            app.layer(middleware::from_fn_with_state(
+                network_delay,
+                delay_response,
+            ))
+        }
+        None => app,
+    };
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], 8001));
+
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
+
+/// Routes the static pages and the lobby, session, map and tournament endpoints to their handlers.
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .nest_service("/static", ServeDir::new("static"))
+        .route_service("/", ServeFile::new("html/game.html"))
+        .route_service("/about", ServeFile::new("html/index.html"))
+        .route("/lobbies/create", post(create_lobby))
+        .route("/lobbies/", get(get_lobbies))
+        .route("/lobbies/:id/turns/:since", get(get_turns_since))
+        .route("/lobbies/:id/act", post(process_inbound))
+        .route("/lobbies/:id/ready", post(post_ready))
+        // .route("/lobbies/:id/rematch", post(post_rematch))
+        .route("/lobbies/:id/state", get(get_state))
+        .route("/session", get(obtain_session))
+        .route("/status", get(get_status))
+        .route("/ping", get(get_ping))
+        .route("/verify", post(verify_replay))
+        .route("/verify/seed", post(issue_level_seed))
+        .route("/maps", post(share_map))
+        // Named like the parameter of the map routes below, as the router requires
+        .route("/maps/:id", get(get_maps))
+        .route("/maps/:id/play", post(play_map))
+        .route("/maps/:id/report", post(report_map))
+        .route("/maps/:id/hide", post(hide_map))
+        .route("/maps/:id/keep", post(keep_map))
+        .route("/moderation/maps", get(get_reported_maps))
+        .route("/bugs", post(report_bug))
+        .route("/tournaments/create", post(create_bracket))
+        .route("/tournaments/:code", get(get_bracket))
+        .route("/tournaments/:code/join", post(join_bracket))
+        .route("/tournaments/:code/start", post(start_bracket))
+        .with_state(state)
+}
+
+async fn create_lobby(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(mut session_message): Json<SessionNewLobby>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &session_message.session_id) {
+        return Json(unauthorized());
+    }
+
+    if server_status(&state).busy() {
+        return Json(Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::RateLimited,
+            "server busy",
+        )));
+    }
+
+    if let Some(Err(err)) = session_message
+        .lobby_settings
+        .map()
+        .map(|map| map.validate(&ArenaGeometry::default()))
+    {
+        return Json(Message::LobbyError(err));
+    }
+
+    // Re-applied so the limits are clamped regardless of what the client sent
+    let max_turns = session_message.lobby_settings.max_turns();
+    session_message.lobby_settings.set_max_turns(max_turns);
+    let turn_duration = session_message.lobby_settings.turn_duration();
+    session_message
+        .lobby_settings
+        .set_turn_duration(turn_duration);
+    let best_of = session_message.lobby_settings.best_of();
+    session_message.lobby_settings.set_best_of(best_of);
+    let players_per_team = session_message.lobby_settings.players_per_team();
+    session_message
+        .lobby_settings
+        .set_players_per_team(players_per_team);
+
+    let mut lobby = Lobby::new(session_message.lobby_settings, timestamp());
+
+    if let Err(err) = lobby.join_player(session_message.session_id.clone(), timestamp()) {
+        return Json(Message::LobbyError(err));
+    }
+
+    let lobby_id = host_lobby(&state, lobby.clone());
+    lobby.settings.set_sort(LobbySort::Online(lobby_id));
+
+    Json(Message::Lobby(Box::new(
+        lobby.view_for(Some(&session_message.session_id)),
+    )))
+}
+
+async fn get_lobbies(State(state): State<AppState>) -> Json<Message> {
+    let now = timestamp();
+
+    // Bracket lobbies wait for their players, and are dropped by the bracket once decided
+    state.lobbies.retain(|_, v| {
+        v.idle_seconds(now) < state.idle.timeout || (!v.reserved.is_empty() && !v.finished())
+    });
+
+    Json(Message::Lobbies(
+        state
+            .lobbies
+            .iter()
+            // Private lobbies are joined by their ID only
+            .filter(|entry| !entry.value().settings.private())
+            .map(|entry| {
+                let mut summary = entry.value().summary();
+                summary.region = state.region.to_string();
+
+                (*entry.key(), summary)
+            })
+            .collect(),
+    ))
+}
+
+fn server_status(state: &AppState) -> ServerStatus {
+    ServerStatus {
+        active_lobbies: state.lobbies.len(),
+        capacity: MAX_LOBBIES,
+        balance_hash: balance().hash(),
+    }
+}
+
+/// Answers as cheaply as possible, so clients can measure their round trip to this region.
+async fn get_ping() -> Json<Message> {
+    Json(Message::Ok)
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<Message> {
+    Json(Message::Status(server_status(&state)))
+}
+
+async fn get_turns_since(
+    State(state): State<AppState>,
+    Path((id, since)): Path<(u16, usize)>,
+    Query(query): Query<TurnsQuery>,
+    headers: HeaderMap,
+) -> Json<Message> {
+    // Unauthorized session IDs are treated as spectators
+    let session_id = query
+        .session_id
+        .filter(|session_id| state.authorized(&headers, session_id));
+
+    if let Some(mut lobby) = state.lobbies.get_mut(&id) {
+        let now = timestamp();
+
+        // Players are warned of their own silence, spectators of everyone's
+        let idle = match session_id
+            .as_ref()
+            .and_then(|session_id| lobby.players().get(session_id))
+        {
+            Some(player) => now - player.last_heartbeat,
+            None => lobby.idle_seconds(now),
+        };
+
+        // Polling keeps players connected, also while they think over a turn for long
+        if let Some(session_id) = &session_id {
+            lobby.heartbeat(session_id, now);
+        }
+
+        if let Some(seconds_left) = state.idle.seconds_left(idle) {
+            return Json(Message::LobbyExpiryWarning(seconds_left));
+        }
+
+        if lobby.all_ready() {
+            let spectator = lobby.is_spectator(session_id.as_ref());
+
+            Json(Message::TurnSync(TurnSync {
+                turns: lobby.visible_turns_since(session_id.as_ref(), since),
+                turn_count: lobby.visible_turns_count(session_id.as_ref()),
+                capture_progress: (!spectator).then(|| lobby.game.capture_progress()),
+                checksum: (!spectator).then(|| lobby.game.checksum()),
+                server_time: timestamp(),
+                // The lobby clock advances the turn on its first tick past this deadline
+                turn_deadline: (!spectator)
+                    .then(|| lobby.last_beat() + lobby.game.turn_duration() as f64),
+                warmup_until: lobby.warmup_until,
+                surrender_vote: session_id
+                    .as_ref()
+                    .and_then(|session_id| lobby.players().get(session_id))
+                    .and_then(|player| lobby.surrender_vote(player.team))
+                    .cloned(),
+            }))
+        } else {
+            Json(Message::Lobby(Box::new(
+                lobby.view_for(session_id.as_ref()),
+            )))
+        }
+    } else {
+        Json(Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::NotFound,
+            "lobby does not exist",
+        )))
+    }
+}
+
+async fn get_state(State(state): State<AppState>, Path(id): Path<u16>) -> Json<Message> {
+    match state.lobbies.get(&id) {
+        Some(lobby) => Json(Message::Lobby(Box::new(lobby.view_for(None)))),
+        None => Json(Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::NotFound,
+            "lobby does not exist",
+        ))),
+    }
+}
+
+async fn process_inbound(
+    State(state): State<AppState>,
+    Path(id): Path<u16>,
+    headers: HeaderMap,
+    Json(session_message): Json<SessionMessage>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &session_message.session_id) {
+        return Json(unauthorized());
+    }
+
+    // Filtered once sanitized, so characters dropped by sanitizing cannot break up blocked words
+    let message = match session_message.message {
+        Message::Chat(content) => Message::Chat(match content.clone().sanitized() {
+            Some(ChatContent::Text(text)) => ChatContent::Text(state.chat_filter.filter(&text)),
+            _ => content,
+        }),
+        message => message,
+    };
+
+    Json(match state.lobbies.get_mut(&id) {
+        Some(mut lobby) => {
+            let result: Message = lobby.act_player(session_message.session_id, message).into();
+            state.dirty_lobbies.insert(id);
+
+            match (result, session_message.message_id) {
+                (Message::Ok, Some(message_id)) => Message::Ack(message_id),
+                (result, _) => result,
+            }
+        }
+        None => Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::NotFound,
+            "lobby does not exist",
+        )),
+    })
+}
+
+async fn post_ready(
+    State(state): State<AppState>,
+    Path(id): Path<u16>,
+    headers: HeaderMap,
+    Json(session_request): Json<SessionRequest>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &session_request.session_id) {
+        return Json(unauthorized());
+    }
+
+    Json(match state.lobbies.get_mut(&id) {
+        Some(mut lobby) => match lobby.join_player(session_request.session_id.clone(), timestamp())
+        {
+            Ok(_) => {
+                // lobby.game.execute_turn(&Turn {
+                //     timestamp: timestamp(),
+                //     ..Default::default()
+                // });
+
+                lobby.first_heartbeat = timestamp();
+                state.dirty_lobbies.insert(id);
+
+                Message::Lobby(Box::new(lobby.view_for(Some(&session_request.session_id))))
+            }
+            Err(err) => Message::LobbyError(err),
+        },
+        None => Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::NotFound,
+            "lobby does not exist",
+        )),
+    })
+}
+
+// async fn post_rematch(
+//     State(state): State<AppState>,
+//     Path(id): Path<u16>,
+//     Json(session_request): Json<SessionRequest>,
+// ) -> Json<Message> {
+//     let mut lobbies = state.lobbies.lock().unwrap();
+
+//     Json(match lobbies.get_mut(&id) {
+//         Some(lobby) => {
+//             let result = lobby.request_rematch(session_request.session_id);
+
+//             if let Ok(true) = result {
+//                 lobby.remake();
+//             }
+
+//             result.into()
+//         }
+//         None => Message::LobbyError(LobbyError::new(LobbyErrorKind::NotFound, "lobby does not exist")),
+//     })
+// }
+
+/// Issues a new session, whose token is its ID signed with the server's secret, so nothing is
+/// stored per session.
+async fn obtain_session(State(state): State<AppState>) -> Json<SessionGrant> {
+    let session_id = generate_alphanumeric(SESSION_ID_LENGTH);
+    let token = state.session_token(&session_id);
+
+    Json(SessionGrant {
+        session_id,
+        token,
+        balance_hash: balance().hash(),
+    })
+}
+
+/// Issues a seed for the session's next game against the built-in bot, which its replay must be
+/// played with to be verified, see [`LobbySettings::level_seed`].
+async fn issue_level_seed(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(session_request): Json<SessionRequest>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &session_request.session_id) {
+        return Json(unauthorized());
+    }
+
+    let now = timestamp();
+    let mut level_seeds = state.level_seeds.lock().unwrap();
+
+    level_seeds.retain(|_, (_, issued_at)| now - *issued_at < LEVEL_SEED_SECONDS);
+
+    if level_seeds.len() >= MAX_LEVEL_SEEDS {
+        return Json(Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::RateLimited,
+            "too many levels in play, try again later",
+        )));
+    }
+
+    // Zero stands for no seed at all
+    let level_seed = loop {
+        let level_seed = rand::thread_rng().gen::<u64>();
+
+        if level_seed != 0 && !level_seeds.contains_key(&level_seed) {
+            break level_seed;
+        }
+    };
+
+    level_seeds.insert(level_seed, (session_request.session_id, now));
+
+    Json(Message::LevelSeed(level_seed))
+}
+
+/// Re-simulates a claimed win against the built-in bot and records it on the session's profile.
+///
+/// The replay must be played with a level seed issued to the session, which it uses up.
+async fn verify_replay(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(session_replay): Json<SessionReplay>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &session_replay.session_id) {
+        return Json(unauthorized());
+    }
+
+    let replay = session_replay.replay;
+
+    if replay.turns.len() > MAX_VERIFY_TURNS {
+        return Json(Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::InvalidReplay,
+            "replay too long",
+        )));
+    }
+
+    // Checked before re-simulating, which is the expensive part
+    {
+        let level_seed = replay.settings.level_seed();
+        let mut level_seeds = state.level_seeds.lock().unwrap();
+
+        // Replays shared as codes reveal their seed, so only the session it was issued to uses it
+        if !matches!(
+            level_seeds.get(&level_seed),
+            Some((session_id, _)) if *session_id == session_replay.session_id
+        ) {
+            return Json(Message::LobbyError(LobbyError::new(
+                LobbyErrorKind::InvalidReplay,
+                "replay not played on a level issued to this session",
+            )));
+        }
+
+        if !state
+            .verify_limit
+            .admit(&session_replay.session_id, timestamp())
+        {
+            return Json(Message::LobbyError(LobbyError::new(
+                LobbyErrorKind::InvalidReplay,
+                "verifying too often, wait a moment",
+            )));
+        }
+
+        level_seeds.remove(&level_seed);
+    }
+
+    let level = level_key(&replay.settings);
+
+    let verification = tokio::task::spawn_blocking(move || {
+        let mut tracker = AchievementTracker::new(Team::Red);
+        let result = replay.verify(
+            Team::Blue,
+            &mut ZoneBot::for_settings(&replay.settings),
+            &mut tracker,
+        );
+
+        (result, tracker)
+    })
+    .await
+    .ok();
+
+    let Some((Some(GameResult::Win(Team::Red)), tracker)) = verification else {
+        return Json(Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::InvalidReplay,
+            "replay could not be verified",
+        )));
+    };
+
+    let mut profiles = state.profiles.lock().unwrap();
+    let stored_profile = profiles.entry(session_replay.session_id).or_default();
+
+    // Replays of the same level differ in as little as a timestamp, so wins are counted by level
+    if stored_profile.levels.len() < MAX_PROFILE_LEVELS && stored_profile.levels.insert(level) {
+        stored_profile.profile.verified_wins += 1;
+    }
+
+    for achievement in tracker.unlocked() {
+        if !stored_profile.profile.achievements.contains(achievement) {
+            stored_profile.profile.achievements.push(*achievement);
+        }
+    }
+
+    let profile = stored_profile.profile.clone();
+    save_profiles(&state, &profiles);
+
+    Json(Message::Profile(profile))
+}
+
+/// Identifies the level a replay was played on by the settings the bot and arena depend on.
+fn level_key(settings: &LobbySettings) -> String {
+    let mut mutators: Vec<String> = settings
+        .mutators()
+        .iter()
+        .map(|mutator| format!("{mutator:?}"))
+        .collect();
+    mutators.sort();
+
+    format!(
+        "{:?}/{:?}/{:?}/{}/{}",
+        settings.mode(),
+        settings.force_field(),
+        settings.bot_difficulty(),
+        mutators.join(","),
+        settings.map().map(ArenaMap::encode).unwrap_or_default(),
+    )
+}
+
+/// Lists a validated map in the gallery, returning the existing listing if its code was already shared.
+async fn share_map(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(session_map): Json<SessionSharedMap>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &session_map.session_id) {
+        return Json(unauthorized());
+    }
+
+    let title = session_map.title.trim();
+
+    if title.is_empty() || title.chars().count() > MAX_MAP_TITLE {
+        return Json(Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::InvalidMap,
+            "invalid map title",
+        )));
+    }
+
+    let code = match ArenaMap::decode(&session_map.code) {
+        // Re-encoded so the same map always shares under the same code
+        Ok(map) => map.encode(),
+        Err(err) => return Json(Message::LobbyError(err)),
+    };
+
+    let mut maps = state.maps.lock().unwrap();
+
+    if let Some(stored_map) = maps.iter().find(|stored_map| stored_map.map.code == code) {
+        return Json(Message::Maps(MapGallery {
+            maps: vec![stored_map.map.clone()],
+            page: 0,
+            pages: 1,
+        }));
+    }
+
+    if maps.len() >= MAX_SHARED_MAPS {
+        return Json(Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::RateLimited,
+            "map gallery full",
+        )));
+    }
+
+    let shared_map = SharedMap {
+        id: maps.len() as u32,
+        title: title.to_string(),
+        code,
+        plays: 0,
+    };
+
+    maps.push(StoredMap {
+        map: shared_map.clone(),
+        author: session_map.session_id,
+        hidden: false,
+        reporters: HashSet::new(),
+        players: HashSet::new(),
+    });
+    save_maps(&state, &maps);
+
+    Json(Message::Maps(MapGallery {
+        maps: vec![shared_map],
+        page: 0,
+        pages: 1,
+    }))
+}
+
+/// Lists a page of the visible maps in the gallery, most played first.
+async fn get_maps(State(state): State<AppState>, Path(page): Path<usize>) -> Json<Message> {
+    let maps = state.maps.lock().unwrap();
+
+    let mut visible: Vec<&SharedMap> = maps
+        .iter()
+        .filter(|stored_map| !stored_map.hidden)
+        .map(|stored_map| &stored_map.map)
+        .collect();
+    visible.sort_by_key(|map| (std::cmp::Reverse(map.plays), map.id));
+
+    let pages = visible.len().div_ceil(MAPS_PAGE_SIZE).max(1);
+    let page = page.min(pages - 1);
+
+    Json(Message::Maps(MapGallery {
+        maps: visible
+            .into_iter()
+            .skip(page * MAPS_PAGE_SIZE)
+            .take(MAPS_PAGE_SIZE)
+            .cloned()
+            .collect(),
+        page,
+        pages,
+    }))
+}
+
+/// Counts a session's first match started on a map, ranking it higher in the gallery.
+async fn play_map(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    headers: HeaderMap,
+    Json(session_request): Json<SessionRequest>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &session_request.session_id) {
+        return Json(unauthorized());
+    }
+
+    let mut maps = state.maps.lock().unwrap();
+
+    match maps.get_mut(id as usize) {
+        Some(stored_map) if !stored_map.hidden => {
+            if stored_map.players.insert(session_request.session_id) {
+                stored_map.map.plays = stored_map.map.plays.saturating_add(1);
+                save_maps(&state, &maps);
+            }
+
+            Json(Message::Ok)
+        }
+        _ => Json(map_not_found()),
+    }
+}
+
+/// Records a session's report of a map, queueing it for review once enough distinct sessions
+/// reported it, see [`MAP_REPORT_THRESHOLD`].
+async fn report_map(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    headers: HeaderMap,
+    Json(session_request): Json<SessionRequest>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &session_request.session_id) {
+        return Json(unauthorized());
+    }
+
+    let verified = state
+        .profiles
+        .lock()
+        .unwrap()
+        .get(&session_request.session_id)
+        .is_some_and(|stored_profile| stored_profile.profile.verified_wins > 0);
+
+    let mut maps = state.maps.lock().unwrap();
+
+    match maps.get_mut(id as usize) {
+        Some(stored_map) if !stored_map.hidden => {
+            // Other reports are accepted all the same, so they tell nothing about the threshold
+            if verified
+                && stored_map.author != session_request.session_id
+                && stored_map.players.contains(&session_request.session_id)
+                && stored_map.reporters.insert(session_request.session_id)
+            {
+                save_maps(&state, &maps);
+            }
+
+            Json(Message::Ok)
+        }
+        _ => Json(map_not_found()),
+    }
+}
+
+/// Appends a player's bug report to the reports file, cutting oversized descriptions and logs.
+///
+/// Reports are rate limited and refused once the file is full, so that they cannot fill the disk.
+async fn report_bug(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(bug_report): Json<SessionBugReport>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &bug_report.session_id) {
+        return Json(unauthorized());
+    }
+
+    if !state
+        .bug_report_limit
+        .admit(&bug_report.session_id, timestamp())
+    {
+        return Json(Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::RateLimited,
+            "reporting too often, wait a moment",
+        )));
+    }
+
+    let description: String = bug_report
+        .description
+        .trim()
+        .chars()
+        .take(MAX_BUG_DESCRIPTION)
+        .collect();
+
+    if description.is_empty() {
+        return Json(Message::Ok);
+    }
+
+    let skipped = bug_report.logs.len().saturating_sub(MAX_BUG_LOG_LINES);
+    let logs: Vec<String> = bug_report
+        .logs
+        .into_iter()
+        .skip(skipped)
+        .map(|line| line.chars().take(MAX_BUG_LOG_LINE).collect())
+        .collect();
+
+    let line = serde_json::json!({
+        "received_at": timestamp(),
+        // Players only see each other's nicknames, which reports about them refer to
+        "nickname": Nickname::from_session(&bug_report.session_id).to_string(),
+        "session_id": bug_report.session_id,
+        "description": description,
+        "logs": logs,
+    });
+
+    let size = fs::metadata(BUG_REPORTS_PATH).map_or(0, |metadata| metadata.len());
+
+    if size >= MAX_BUG_REPORTS_BYTES {
+        eprintln!("{BUG_REPORTS_PATH} is full, refusing bug reports");
+
+        return Json(Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::RateLimited,
+            "too many bug reports, try again later",
+        )));
+    }
+
+    match fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(BUG_REPORTS_PATH)
+    {
+        Ok(mut file) => {
+            if let Err(err) = writeln!(file, "{line}") {
+                eprintln!("could not write {BUG_REPORTS_PATH}: {err}");
+            }
+        }
+        Err(err) => eprintln!("could not open {BUG_REPORTS_PATH}: {err}"),
+    }
+
+    Json(Message::Ok)
+}
+
+/// Lists the visible maps reported often enough to be reviewed, most reported first, for
+/// moderators only. Each is then hidden or kept.
+async fn get_reported_maps(State(state): State<AppState>, headers: HeaderMap) -> Json<Message> {
+    if !state.moderator(&headers) {
+        return Json(unauthorized());
+    }
+
+    let maps = state.maps.lock().unwrap();
+
+    let mut reported: Vec<&StoredMap> = maps
+        .iter()
+        .filter(|stored_map| {
+            !stored_map.hidden && stored_map.reporters.len() >= MAP_REPORT_THRESHOLD
+        })
+        .collect();
+    reported.sort_by_key(|stored_map| {
+        (
+            std::cmp::Reverse(stored_map.reporters.len()),
+            stored_map.map.id,
+        )
+    });
+
+    Json(Message::Maps(MapGallery {
+        maps: reported
+            .into_iter()
+            .map(|stored_map| stored_map.map.clone())
+            .collect(),
+        page: 0,
+        pages: 1,
+    }))
+}
+
+/// Keeps a reported map in the gallery, clearing its reports, for moderators only.
+async fn keep_map(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    headers: HeaderMap,
+) -> Json<Message> {
+    if !state.moderator(&headers) {
+        return Json(unauthorized());
+    }
+
+    let mut maps = state.maps.lock().unwrap();
+
+    match maps.get_mut(id as usize) {
+        Some(stored_map) => {
+            stored_map.reporters.clear();
+            save_maps(&state, &maps);
+
+            Json(Message::Ok)
+        }
+        None => Json(map_not_found()),
+    }
+}
+
+/// Hides a map from the gallery, for moderators only.
+async fn hide_map(
+    State(state): State<AppState>,
+    Path(id): Path<u32>,
+    headers: HeaderMap,
+) -> Json<Message> {
+    if !state.moderator(&headers) {
+        return Json(unauthorized());
+    }
+
+    let mut maps = state.maps.lock().unwrap();
+
+    match maps.get_mut(id as usize) {
+        Some(stored_map) => {
+            stored_map.hidden = true;
+            save_maps(&state, &maps);
+
+            Json(Message::Ok)
+        }
+        None => Json(map_not_found()),
+    }
+}
+
+fn load_maps() -> Vec<StoredMap> {
+    match fs::read_to_string(MAPS_PATH) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|err| {
+            eprintln!("invalid {MAPS_PATH}, starting an empty gallery: {err}");
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn save_maps(state: &AppState, maps: &[StoredMap]) {
+    save_json(MAPS_PATH, &state.map_writes, maps);
+}
+
+fn load_profiles() -> HashMap<String, StoredProfile> {
+    match fs::read_to_string(PROFILES_PATH) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|err| {
+            eprintln!("invalid {PROFILES_PATH}, starting without profiles: {err}");
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn save_profiles(state: &AppState, profiles: &HashMap<String, StoredProfile>) {
+    save_json(PROFILES_PATH, &state.profile_writes, profiles);
+}
+
+/// Serializes a snapshot right away, under the lock of whatever it is a snapshot of, and writes it
+/// on a blocking thread through a temporary file, so that a crash never leaves it half-written.
+fn save_json(path: &'static str, writes: &FileWrites, value: &(impl Serialize + ?Sized)) {
+    let json = match serde_json::to_vec(value) {
+        Ok(json) => json,
+        Err(err) => {
+            eprintln!("could not serialize {path}: {err}");
+            return;
+        }
+    };
+
+    let snapshot = writes.taken.fetch_add(1, Ordering::SeqCst) + 1;
+    let written = writes.written.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut written = written.lock().unwrap();
+
+        if *written > snapshot {
+            return;
+        }
+
+        let temporary = format!("{path}.tmp");
+
+        match fs::write(&temporary, json).and_then(|_| fs::rename(&temporary, path)) {
+            Ok(()) => *written = snapshot,
+            Err(err) => eprintln!("could not write {path}: {err}"),
+        }
+    });
+}
+
+async fn create_bracket(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(session_request): Json<SessionRequest>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &session_request.session_id) {
+        return Json(unauthorized());
+    }
+
+    let mut brackets = state.brackets.lock().unwrap();
+
+    if brackets.len() >= MAX_BRACKETS {
+        return Json(Message::LobbyError(LobbyError::new(
+            LobbyErrorKind::RateLimited,
+            "server busy",
+        )));
+    }
+
+    let code = loop {
+        let code = generate_alphanumeric(BRACKET_CODE_LENGTH).to_uppercase();
+
+        if !brackets.contains_key(&code) {
+            break code;
+        }
+    };
+
+    let bracket = Bracket::new(code.clone(), session_request.session_id);
+
+    brackets.insert(
+        code,
+        HostedBracket {
+            bracket: bracket.clone(),
+            finished_at: None,
+        },
+    );
+
+    Json(Message::Bracket(Box::new(bracket)))
+}
+
+async fn get_bracket(State(state): State<AppState>, Path(code): Path<String>) -> Json<Message> {
+    match state.brackets.lock().unwrap().get(&code.to_uppercase()) {
+        Some(hosted) => Json(Message::Bracket(Box::new(hosted.bracket.clone()))),
+        None => Json(bracket_not_found()),
+    }
+}
+
+async fn join_bracket(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+    Json(session_request): Json<SessionRequest>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &session_request.session_id) {
+        return Json(unauthorized());
+    }
+
+    match state.brackets.lock().unwrap().get_mut(&code.to_uppercase()) {
+        Some(hosted) => match hosted.bracket.join(session_request.session_id) {
+            Ok(()) => Json(Message::Bracket(Box::new(hosted.bracket.clone()))),
+            Err(err) => Json(Message::LobbyError(err)),
+        },
+        None => Json(bracket_not_found()),
+    }
+}
+
+/// Closes registration and seeds the bracket, for its host only.
+async fn start_bracket(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+    Json(session_request): Json<SessionRequest>,
+) -> Json<Message> {
+    if !state.authorized(&headers, &session_request.session_id) {
+        return Json(unauthorized());
+    }
+
+    match state.brackets.lock().unwrap().get_mut(&code.to_uppercase()) {
+        Some(hosted) if hosted.bracket.host != session_request.session_id => Json(unauthorized()),
+        Some(hosted) => match hosted.bracket.start() {
+            Ok(()) => Json(Message::Bracket(Box::new(hosted.bracket.clone()))),
+            Err(err) => Json(Message::LobbyError(err)),
+        },
+        None => Json(bracket_not_found()),
+    }
+}
+
+/// Advances the winners of finished bracket matches and hosts a lobby for every pairing whose
+/// players are known, dropping brackets some time after their final.
+async fn run_brackets(state: AppState) {
+    let mut interval = tokio::time::interval(BRACKET_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let mut brackets = state.brackets.lock().unwrap();
+
+        brackets.retain(|_, hosted| {
+            hosted
+                .finished_at
+                .is_none_or(|finished_at| timestamp() - finished_at < BRACKET_RETENTION_SECONDS)
+        });
+
+        for hosted in brackets.values_mut() {
+            let bracket = &mut hosted.bracket;
+
+            for (round, index, lobby_id) in bracket.hosted_matches() {
+                if let Some(winner) = bracket_match_winner(&state, bracket, round, index, lobby_id)
+                {
+                    bracket.report(round, index, winner);
+                }
+            }
+
+            for (round, index) in bracket.unhosted_matches() {
+                let Some(bracket_match) = bracket.get(round, index) else {
+                    continue;
+                };
+
+                let players = bracket_match.players.iter().flatten().cloned().collect();
+
+                match host_bracket_match(&state, players) {
+                    Some(lobby_id) => bracket.host(round, index, lobby_id),
+                    // Retried on the next tick, once lobbies have freed up
+                    None => break,
+                }
+            }
+
+            if bracket.phase == BracketPhase::Finished && hosted.finished_at.is_none() {
+                hosted.finished_at = Some(timestamp());
+            }
+        }
+    }
+}
+
+/// Decides who advances from a bracket match, once its lobby finished or its players failed to
+/// show up. Draws, and matches nobody showed up to, go to the higher seed.
+fn bracket_match_winner(
+    state: &AppState,
+    bracket: &Bracket,
+    round: usize,
+    index: usize,
+    lobby_id: u16,
+) -> Option<String> {
+    let players: Vec<String> = bracket
+        .get(round, index)?
+        .players
+        .iter()
+        .flatten()
+        .cloned()
+        .collect();
+
+    let higher_seed = players
+        .iter()
+        .min_by_key(|player| bracket.seed(player))
+        .cloned();
+
+    let mut no_show = false;
+
+    let winner = match state.lobbies.get(&lobby_id) {
+        Some(lobby) if lobby.finished() => match lobby.game.result() {
+            Some(GameResult::Win(team)) => lobby
+                .players()
+                .iter()
+                .find(|(_, player)| player.team == team)
+                .map(|(session_id, _)| session_id.clone()),
+            _ => higher_seed.clone(),
+        },
+        Some(lobby)
+            if !lobby.all_ready()
+                && timestamp() - lobby.first_heartbeat > BRACKET_NO_SHOW_SECONDS =>
+        {
+            no_show = true;
+
+            // A player who readied up showed up, over one who only joined
+            lobby
+                .players()
+                .iter()
+                .max_by_key(|(_, player)| player.ready)
+                .map(|(session_id, _)| session_id.clone())
+        }
+        Some(_) => return None,
+        None => None,
+    };
+
+    if no_show {
+        state.lobbies.remove(&lobby_id);
+    }
+
+    winner
+        .filter(|winner| players.contains(winner))
+        .or(higher_seed)
+}
+
+/// Hosts a lobby reserved for the players of a bracket match, unless the server is busy.
+fn host_bracket_match(state: &AppState, players: Vec<String>) -> Option<u16> {
+    if server_status(state).busy() {
+        return None;
+    }
+
+    let mut lobby_settings = LobbySettings::new(LobbySort::Online(0));
+    lobby_settings.set_max_turns(Some(LobbySettings::DEFAULT_MAX_TURNS));
+
+    let mut lobby = Lobby::new(lobby_settings, timestamp());
+    lobby.reserved = players;
+
+    Some(host_lobby(state, lobby))
+}
+
+/// Executes the lobby's turns on schedule for as long as it is hosted, independently of polling.
+///
+/// Turns are fully simulated, so the results and checksums reported to clients are authoritative.
+/// The simulation runs on a copy of the game away from the lobby's lock, which requests to the
+/// lobby would otherwise wait on.
+async fn run_lobby_clock(state: AppState, id: u16) {
+    let mut interval = tokio::time::interval(LOBBY_CLOCK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let due = match state.lobbies.get(&id) {
+            Some(lobby) => {
+                due_turn(&lobby).map(|turn| (turn, lobby.game.clone(), lobby.revision()))
+            }
+            None => break,
+        };
+
+        if let Some((turn, mut game, revision)) = due {
+            let Ok(game) = tokio::task::spawn_blocking(move || {
+                game.resolve_turn(turn);
+                game.drain_events();
+
+                game
+            })
+            .await
+            else {
+                continue;
+            };
+
+            let Some(mut lobby) = state.lobbies.get_mut(&id) else {
+                break;
+            };
+
+            // Messages that arrived meanwhile, e.g. late reveals, are resolved with the turn on the
+            // next tick instead of being overwritten
+            if lobby.revision() == revision {
+                lobby.adopt_resolved(game);
+                state.dirty_lobbies.insert(id);
+            }
+        }
+
+        let Some(lobby) = state.lobbies.get(&id) else {
+            break;
+        };
+
+        if lobby.finished() {
+            if let Some(webhooks) = state.webhooks.as_ref() {
+                if let Some(summary) = FinishedLobby::new(id, &state.region, &lobby) {
+                    tokio::spawn(post_webhooks(webhooks.clone(), summary));
+                }
+            }
+
+            break;
+        }
+    }
+}
+
+/// Posts the summary of a finished lobby to every webhook, each retried with backoff until it is
+/// accepted or runs out of attempts.
+async fn post_webhooks(config: WebhookConfig, summary: FinishedLobby) {
+    let Ok(body) = serde_json::to_vec(&summary) else {
+        return;
+    };
+
+    let signature = config
+        .secret
+        .as_ref()
+        .map(|secret| to_hex(&hmac_sha256(secret.as_bytes(), &body)));
+
+    let lobby = summary.lobby;
+    let client = hyper::Client::new();
+
+    for uri in config.urls {
+        let client = client.clone();
+        let body = body.clone();
+        let signature = signature.clone();
+
+        tokio::spawn(async move {
+            let mut delay = WEBHOOK_RETRY_DELAY;
+
+            for attempt in 1..=WEBHOOK_ATTEMPTS {
+                let mut request = hyper::Request::post(uri.clone())
+                    .header(hyper::header::CONTENT_TYPE, "application/json");
+
+                if let Some(signature) = &signature {
+                    request =
+                        request.header(WEBHOOK_SIGNATURE_HEADER, format!("sha256={signature}"));
+                }
+
+                let Ok(request) = request.body(hyper::Body::from(body.clone())) else {
+                    return;
+                };
+
+                let error =
+                    match tokio::time::timeout(WEBHOOK_TIMEOUT, client.request(request)).await {
+                        Ok(Ok(response)) if response.status().is_success() => return,
+                        Ok(Ok(response)) => format!("status {}", response.status()),
+                        Ok(Err(err)) => err.to_string(),
+                        Err(_) => "timed out".to_string(),
+                    };
+
+                eprintln!(
+                    "webhook {uri} failed for lobby {lobby} ({attempt}/{WEBHOOK_ATTEMPTS}): {error}"
+                );
+
+                if attempt < WEBHOOK_ATTEMPTS {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        });
+    }
+}
+
+/// Aggregates the lobby's next turn once its players have had the full turn duration to plan, or
+/// right away once a team surrendered.
+fn due_turn(lobby: &Lobby) -> Option<Turn> {
+    if !lobby.all_ready() || lobby.finished() {
+        return None;
+    }
+
+    let since_last_beat = timestamp() - lobby.last_beat();
+
+    if since_last_beat <= lobby.game.turn_duration() as f64
+        && lobby.game.pending_surrender().is_none()
+    {
+        return None;
+    }
+
+    let mut turn = lobby.game.aggregate_turn();
+    turn.timestamp = timestamp();
+
+    // Teams that never committed a move used up the whole turn
+    for team in [Team::Red, Team::Blue] {
+        turn.think_times
+            .entry(team)
+            .or_insert(lobby.game.turn_duration() as f32);
+    }
+
+    Some(turn)
+}
+
+/// Archives the lobbies which finished since the last flush, batching the writes of busy lobbies.
+///
+/// Lobbies in progress are not written, as they are not restored once the server restarts.
+async fn run_lobby_flush(state: AppState, config: PersistenceConfig) {
+    let mut interval = tokio::time::interval(config.flush_interval);
+
+    loop {
+        interval.tick().await;
+
+        let ids: Vec<u16> = state.dirty_lobbies.iter().map(|id| *id).collect();
+
+        // Lobbies are serialized under their lock, and written once it is released
+        let records: Vec<(u16, u64, Vec<u8>)> = ids
+            .into_iter()
+            .filter_map(|id| {
+                state.dirty_lobbies.remove(&id);
+
+                let lobby = state.lobbies.get(&id).filter(|lobby| lobby.finished())?;
+                let json = serde_json::to_vec(&ArchivedLobby {
+                    lobby: &lobby,
+                    replay: Replay::new(&lobby),
+                });
+
+                Some((id, lobby.first_heartbeat as u64, json.ok()?))
+            })
+            .collect();
+
+        if records.is_empty() {
+            continue;
+        }
+
+        let _ = tokio::task::spawn_blocking(move || {
+            for (id, started_at, json) in records {
+                if let Err(err) = archive_lobby(id, started_at, &json) {
+                    eprintln!("could not write lobby {id}: {err}");
+                }
+            }
+        })
+        .await;
+    }
+}
+
+/// Compresses a finished lobby into the archive.
+fn archive_lobby(id: u16, started_at: u64, json: &[u8]) -> std::io::Result<()> {
+    fs::create_dir_all(ARCHIVE_DIR)?;
+
+    let file = File::create(format!("{ARCHIVE_DIR}/{id}-{started_at}.json.gz"))?;
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    encoder.write_all(json)?;
+    encoder.finish()?;
+
+    Ok(())
+}
+
+/// Session IDs of the players of an archived lobby.
+fn archived_players(path: &FilePath) -> Option<Vec<String>> {
+    let archive: serde_json::Value =
+        serde_json::from_reader(GzDecoder::new(File::open(path).ok()?)).ok()?;
+
+    Some(
+        archive["lobby"]["players"]
+            .as_object()?
+            .keys()
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Deletes the archives which neither retention policy keeps, on a schedule.
+async fn run_archive_retention(config: PersistenceConfig) {
+    if config.retains_all() {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let _ = tokio::task::spawn_blocking(move || prune_archive(config)).await;
+    }
+}
+
+fn prune_archive(config: PersistenceConfig) {
+    let Ok(entries) = fs::read_dir(ARCHIVE_DIR) else {
+        return;
+    };
+
+    let mut archives: Vec<(PathBuf, std::time::SystemTime)> = entries
+        .flatten()
+        .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?.modified().ok()?)))
+        .collect();
+
+    // Newest first, so each player's most recent archives are counted first
+    archives.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+
+    let mut kept_per_player: HashMap<String, usize> = HashMap::new();
+
+    for (path, modified) in archives {
+        let recent = config.retention_age.is_some_and(|retention_age| {
+            modified
+                .elapsed()
+                .is_ok_and(|elapsed| elapsed < retention_age)
+        });
+
+        let among_latest = config.retention_per_player.is_some_and(|per_player| {
+            let mut among_latest = false;
+
+            for player in archived_players(&path).unwrap_or_default() {
+                let kept = kept_per_player.entry(player).or_default();
+
+                if *kept < per_player {
+                    *kept += 1;
+                    among_latest = true;
+                }
+            }
+
+            among_latest
+        });
+
+        if !recent && !among_latest {
+            if let Err(err) = fs::remove_file(&path) {
+                eprintln!("could not prune {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+/// Compares two secrets without stopping at the first difference, so timing leaks nothing but
+/// their length.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn generate_alphanumeric(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+/// Hosts a lobby under an ID not taken by any hosted lobby, running its clock, and returns the ID.
+///
+/// The ID is taken in the same step as it is found free, so that lobbies created at the same time
+/// cannot end up under the same ID.
+fn host_lobby(state: &AppState, mut lobby: Lobby) -> u16 {
+    let lobby_id = loop {
+        let lobby_id = generate_lobby_id();
+
+        if let Entry::Vacant(entry) = state.lobbies.entry(lobby_id) {
+            lobby.settings.set_sort(LobbySort::Online(lobby_id));
+            entry.insert(lobby);
+
+            break lobby_id;
+        }
+    };
+
+    state.dirty_lobbies.insert(lobby_id);
+
+    tokio::spawn(run_lobby_clock(state.clone(), lobby_id));
+
+    lobby_id
+}
+
+fn generate_lobby_id() -> u16 {
+    loop {
+        let res = rand::thread_rng().gen_range(u16::MIN..=u16::MAX);
+
+        if res.count_ones() >= 4 {
+            return res;
+        }
+    }
+}
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn timestamp() -> f64 {
+    let start = SystemTime::now();
+    let since_the_epoch = start
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards");
+
+    since_the_epoch.as_secs_f64()
+}
@@ -0,0 +1,50 @@
+//! Reports bugs, checking that neither a session nor many fresh sessions can report back to back.
+//! The reports are blank, so nothing is written to the reports file.
+
+mod common;
+
+use axum::{http::Method, Router};
+use common::{obtain_session, send};
+use server::{router, AppState};
+use shared::{LobbyError, LobbyErrorKind, Message, SessionBugReport, SessionGrant};
+
+/// Reports a blank bug, which is accepted without being written unless it is rate limited.
+async fn report(app: &Router, grant: &SessionGrant) -> Message {
+    let body = serde_json::to_vec(&SessionBugReport {
+        session_id: grant.session_id.clone(),
+        description: " ".to_string(),
+        logs: Vec::new(),
+    })
+    .unwrap();
+
+    send(app, Method::POST, "/bugs", Some(&grant.token), body).await
+}
+
+fn rate_limited(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::LobbyError(LobbyError {
+            kind: LobbyErrorKind::RateLimited,
+            ..
+        })
+    )
+}
+
+#[tokio::test]
+async fn bug_report_rate_limit() {
+    let app = router(AppState::new("bugs".to_string(), None));
+
+    let grant = obtain_session(&app).await;
+
+    assert!(matches!(report(&app, &grant).await, Message::Ok));
+    assert!(rate_limited(&report(&app, &grant).await));
+
+    // Other sessions are limited on their own, until all of them together reach the global limit
+    for _ in 1..AppState::BUG_REPORTS_PER_WINDOW {
+        let fresh = obtain_session(&app).await;
+        assert!(matches!(report(&app, &fresh).await, Message::Ok));
+    }
+
+    let fresh = obtain_session(&app).await;
+    assert!(rate_limited(&report(&app, &fresh).await));
+}
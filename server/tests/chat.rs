@@ -0,0 +1,139 @@
+//! Chats in a waiting room, checking free text passes through the server's chat filter, that
+//! chatting too fast is refused and that muted players' lines are no longer relayed to whoever
+//! muted them. Also checks the lobby's public state reveals neither sessions nor mute lists, and
+//! that the blocklist matches words in any case, beyond ASCII too.
+
+mod common;
+
+use axum::{http::Method, Router};
+use common::{act, create_lobby, join, obtain_session, send};
+use server::{router, AppState, Blocklist, ChatFilter};
+use shared::{ChatContent, Emote, Lobby, LobbyError, LobbyErrorKind, Message, SessionGrant};
+
+/// Polls the waiting room as the given session sees it.
+async fn poll(app: &Router, lobby_id: u16, grant: &SessionGrant) -> Lobby {
+    let uri = format!(
+        "/lobbies/{lobby_id}/turns/0?session_id={}",
+        grant.session_id
+    );
+
+    match send(app, Method::GET, &uri, Some(&grant.token), Vec::new()).await {
+        Message::Lobby(lobby) => *lobby,
+        _ => panic!("the lobby is not waiting for its players"),
+    }
+}
+
+#[tokio::test]
+async fn moderated_chat() {
+    let app =
+        router(AppState::new("chat".to_string(), None).with_chat_filter(Blocklist::new(["darn"])));
+
+    let host = obtain_session(&app).await;
+    let guest = obtain_session(&app).await;
+
+    let lobby_id = create_lobby(&app, &host).await;
+    assert!(matches!(
+        join(&app, lobby_id, &guest).await,
+        Message::Lobby(_)
+    ));
+
+    // Blocked words are masked in any case, even when sanitizing would have joined them up
+    act(
+        &app,
+        lobby_id,
+        &host,
+        Message::Chat(ChatContent::Text("oh DA\u{0}RN it".to_string())),
+    )
+    .await;
+
+    assert_eq!(
+        poll(&app, lobby_id, &guest).await.chat[0].content,
+        ChatContent::Text("oh **** it".to_string())
+    );
+
+    // Emotes count towards the rate limit as well
+    for _ in 0..Lobby::CHAT_RATE_LINES {
+        assert!(matches!(
+            act(
+                &app,
+                lobby_id,
+                &guest,
+                Message::Chat(ChatContent::Emote(Emote::Hello))
+            )
+            .await,
+            Message::Ok
+        ));
+    }
+
+    assert!(matches!(
+        act(
+            &app,
+            lobby_id,
+            &guest,
+            Message::Chat(ChatContent::Emote(Emote::Hello))
+        )
+        .await,
+        Message::LobbyError(LobbyError {
+            kind: LobbyErrorKind::RateLimited,
+            ..
+        })
+    ));
+
+    // Muting goes by seat and hides the guest's lines from the host only, who alone knows whom
+    // they muted
+    let guest_seat = poll(&app, lobby_id, &guest).await.players()[&guest.session_id].seat;
+
+    assert!(matches!(
+        act(&app, lobby_id, &host, Message::Mute(vec![guest_seat, 99])).await,
+        Message::Ok
+    ));
+
+    let host_view = poll(&app, lobby_id, &host).await;
+    assert_eq!(host_view.chat.len(), 1);
+    assert_eq!(
+        host_view.players()[&host.session_id].muted,
+        vec![guest_seat]
+    );
+
+    let guest_view = poll(&app, lobby_id, &guest).await;
+    assert_eq!(guest_view.chat.len(), 1 + Lobby::CHAT_RATE_LINES);
+    assert!(guest_view
+        .players()
+        .values()
+        .all(|player| player.muted.is_empty()));
+
+    // The public state is seen by no one in particular, so every session and mute list is redacted
+    let Message::Lobby(state) = send(
+        &app,
+        Method::GET,
+        &format!("/lobbies/{lobby_id}/state"),
+        None,
+        Vec::new(),
+    )
+    .await
+    else {
+        panic!("lobby state not found");
+    };
+
+    assert!(!state.players().contains_key(&host.session_id));
+    assert!(!state.players().contains_key(&guest.session_id));
+    assert!(state
+        .players()
+        .values()
+        .all(|player| player.muted.is_empty()));
+    assert_eq!(state.chat.len(), 1 + Lobby::CHAT_RATE_LINES);
+}
+
+#[test]
+fn blocklist_casing() {
+    let blocklist = Blocklist::new(["Ärger", "darn"]);
+
+    // Lowercased the same way on both sides, beyond ASCII too
+    assert_eq!(
+        blocklist.filter("So ein ÄRGER, darn ärger!"),
+        "So ein *****, **** *****!"
+    );
+
+    // A character lowercasing to two is masked whole
+    assert_eq!(Blocklist::new(["i\u{307}x"]).filter("İX!"), "**!");
+}
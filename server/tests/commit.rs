@@ -0,0 +1,149 @@
+//! Commits to moves in a started game, checking a player keeps a single commitment per turn which
+//! they may resend or replace with their whole plan until reveals open, and that only accepted
+//! messages which change the game hold back turns resolved away from the lobby.
+
+mod common;
+
+use common::{act, create_lobby, join, obtain_session};
+use server::{router, AppState};
+use shared::{
+    Lobby, LobbyError, LobbyErrorKind, LobbySettings, LobbySort, Message, Turn, TurnCommitment,
+};
+
+/// Starts a lobby of two players, the host and the guest.
+fn started_lobby() -> Lobby {
+    let mut lobby = Lobby::new(LobbySettings::new(LobbySort::Online(0)), 0.0);
+
+    for session_id in ["host", "guest"] {
+        lobby.join_player(session_id.to_string(), 0.0).unwrap();
+        lobby
+            .act_player(session_id.to_string(), Message::Ready(true))
+            .unwrap();
+    }
+
+    lobby
+}
+
+#[tokio::test]
+async fn one_commitment_per_turn() {
+    let app = router(AppState::new("commit".to_string(), None));
+
+    let host = obtain_session(&app).await;
+    let guest = obtain_session(&app).await;
+
+    let lobby_id = create_lobby(&app, &host).await;
+    join(&app, lobby_id, &guest).await;
+
+    for grant in [&host, &guest] {
+        act(&app, lobby_id, grant, Message::Ready(true)).await;
+    }
+
+    let turn = Turn::default();
+
+    assert!(matches!(
+        act(&app, lobby_id, &host, Message::Commit(turn.commitment(1))).await,
+        Message::Ok
+    ));
+
+    // Resent as is, e.g. when its acknowledgement was lost
+    assert!(matches!(
+        act(&app, lobby_id, &host, Message::Commit(turn.commitment(1))).await,
+        Message::Ok
+    ));
+
+    // Replaced while planning
+    assert!(matches!(
+        act(&app, lobby_id, &host, Message::Commit(turn.commitment(2))).await,
+        Message::Ok
+    ));
+
+    // The other player's commitment is their own
+    assert!(matches!(
+        act(&app, lobby_id, &guest, Message::Commit(turn.commitment(2))).await,
+        Message::Ok
+    ));
+}
+
+#[test]
+fn plan_two_bugs() {
+    let mut lobby = started_lobby();
+
+    let team = lobby.players()["host"].team;
+    let bug_indices = lobby.game.team_bug_indices(team);
+    let impulse_intent = nalgebra::vector![1.0, 0.0];
+
+    // Each flick commits to the whole plan so far
+    let first = Turn {
+        impulse_intents: [(bug_indices[0], impulse_intent)].into_iter().collect(),
+        ..Default::default()
+    };
+    let second = Turn {
+        impulse_intents: [
+            (bug_indices[0], impulse_intent),
+            (bug_indices[1], impulse_intent),
+        ]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+
+    for turn in [&first, &second] {
+        lobby
+            .act_player("host".to_string(), Message::Commit(turn.commitment(1)))
+            .unwrap();
+    }
+
+    // Opens the reveal window, which closes commitments
+    lobby.first_heartbeat -= lobby.game.turn_duration() as f64 - TurnCommitment::REVEAL_SECONDS;
+
+    assert!(matches!(
+        lobby.act_player("host".to_string(), Message::Commit(first.commitment(1))),
+        Err(LobbyError {
+            kind: LobbyErrorKind::InvalidTurn,
+            ..
+        })
+    ));
+    assert!(lobby
+        .act_player("host".to_string(), Message::Reveal(first.clone(), 1))
+        .is_err());
+
+    lobby
+        .act_player("host".to_string(), Message::Reveal(second, 1))
+        .unwrap();
+
+    for bug_index in &bug_indices[..2] {
+        let (_, bug_data) = lobby.game.get_bug(*bug_index).unwrap();
+        assert!(bug_data.impulse_intent().magnitude() > 0.0);
+    }
+}
+
+#[test]
+fn revision_follows_the_game() {
+    let mut lobby = started_lobby();
+
+    let turn = Turn::default();
+    let revision = lobby.revision();
+
+    // Refused messages, e.g. a spectator's, leave the revision be however often they are sent
+    for _ in 0..8 {
+        assert!(lobby
+            .act_player("spectator".to_string(), Message::Commit(turn.commitment(1)))
+            .is_err());
+        assert!(lobby
+            .act_player("host".to_string(), Message::Move(turn.clone()))
+            .is_err());
+    }
+
+    assert_eq!(lobby.revision(), revision);
+
+    lobby
+        .act_player("host".to_string(), Message::Commit(turn.commitment(1)))
+        .unwrap();
+    assert_eq!(lobby.revision(), revision + 1);
+
+    // Neither does a resent commitment
+    lobby
+        .act_player("host".to_string(), Message::Commit(turn.commitment(1)))
+        .unwrap();
+    assert_eq!(lobby.revision(), revision + 1);
+}
@@ -0,0 +1,113 @@
+//! Requests shared by the integration tests, sent straight to the router without a socket.
+
+// Each test crate only uses some of them
+#![allow(dead_code)]
+
+use axum::{
+    body::Body,
+    http::{header::AUTHORIZATION, Method, Request},
+    Router,
+};
+use serde::de::DeserializeOwned;
+use shared::{
+    LobbyErrorKind, LobbySettings, LobbySort, Message, SessionGrant, SessionMessage,
+    SessionNewLobby, SessionRequest,
+};
+use tower::ServiceExt;
+
+/// Sends a request to the router, authorized with the token if given, and decodes its JSON answer.
+pub async fn send<T: DeserializeOwned>(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    token: Option<&str>,
+    body: Vec<u8>,
+) -> T {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+
+    if let Some(token) = token {
+        request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+    }
+
+    let response = app
+        .clone()
+        .oneshot(request.body(Body::from(body)).unwrap())
+        .await
+        .unwrap();
+
+    serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap()).unwrap()
+}
+
+pub async fn obtain_session(app: &Router) -> SessionGrant {
+    send(app, Method::GET, "/session", None, Vec::new()).await
+}
+
+pub async fn act(app: &Router, lobby_id: u16, grant: &SessionGrant, message: Message) -> Message {
+    let body = serde_json::to_vec(&SessionMessage {
+        session_id: grant.session_id.clone(),
+        message,
+        message_id: None,
+    })
+    .unwrap();
+
+    send(
+        app,
+        Method::POST,
+        &format!("/lobbies/{lobby_id}/act"),
+        Some(&grant.token),
+        body,
+    )
+    .await
+}
+
+pub async fn join(app: &Router, lobby_id: u16, grant: &SessionGrant) -> Message {
+    let body = serde_json::to_vec(&SessionRequest {
+        session_id: grant.session_id.clone(),
+    })
+    .unwrap();
+
+    send(
+        app,
+        Method::POST,
+        &format!("/lobbies/{lobby_id}/ready"),
+        Some(&grant.token),
+        body,
+    )
+    .await
+}
+
+/// Creates a 1v1 online lobby hosted by the session, returning its ID.
+pub async fn create_lobby(app: &Router, host: &SessionGrant) -> u16 {
+    let body = serde_json::to_vec(&SessionNewLobby {
+        session_id: host.session_id.clone(),
+        lobby_settings: LobbySettings::new(LobbySort::Online(0)),
+    })
+    .unwrap();
+    let created: Message = send(
+        app,
+        Method::POST,
+        "/lobbies/create",
+        Some(&host.token),
+        body,
+    )
+    .await;
+
+    let Message::Lobby(lobby) = created else {
+        panic!("lobby not created: {:?}", error_kind(&created));
+    };
+    let LobbySort::Online(lobby_id) = *lobby.settings.sort() else {
+        panic!("lobby is not online");
+    };
+
+    lobby_id
+}
+
+pub fn error_kind(message: &Message) -> Option<LobbyErrorKind> {
+    match message {
+        Message::LobbyError(error) => Some(error.kind),
+        _ => None,
+    }
+}
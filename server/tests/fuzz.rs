@@ -0,0 +1,547 @@
+//! Sends random sequences of valid and malformed requests to the router, checking that the server
+//! never fails and that its lobbies stay consistent, also while their clocks execute turns.
+
+use axum::{
+    body::Body,
+    http::{header::AUTHORIZATION, Method, Request, StatusCode},
+    Router,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use server::{router, AppState};
+use shared::{
+    ChatContent, Lobby, LobbySettings, LobbySort, Message, SessionGrant, SessionMessage,
+    SessionNewLobby, SessionRequest, Turn,
+};
+use tower::ServiceExt;
+
+/// Sequences sent, each from its own seed so a failure can be replayed.
+const SEEDS: u64 = 8;
+
+/// Requests in each sequence.
+const STEPS: usize = 160;
+
+/// Turns a started lobby is played for by random moves, in real time as the lobby clock runs on it.
+const TURNS: usize = 2;
+
+/// Size of the oversized payloads, beyond the default body limit.
+const HUGE_PAYLOAD: usize = 3 * 1024 * 1024;
+
+/// Endpoints taking a JSON body, fed with malformed and oversized ones.
+const POST_ENDPOINTS: [&str; 9] = [
+    "/lobbies/create",
+    "/lobbies/{id}/act",
+    "/lobbies/{id}/ready",
+    "/verify",
+    "/verify/seed",
+    "/maps",
+    "/bugs",
+    "/tournaments/create",
+    "/tournaments/AAAA/join",
+];
+
+/// Session issued by the server.
+struct Client {
+    session_id: String,
+    token: String,
+}
+
+struct Fuzzer {
+    app: Router,
+    rng: StdRng,
+    seed: u64,
+    clients: Vec<Client>,
+    lobby_ids: Vec<u16>,
+}
+
+impl Fuzzer {
+    fn new(seed: u64) -> Fuzzer {
+        Fuzzer {
+            app: router(AppState::new("fuzz".to_string(), None)),
+            rng: StdRng::seed_from_u64(seed),
+            seed,
+            clients: Vec::new(),
+            lobby_ids: Vec::new(),
+        }
+    }
+
+    /// Sends a request, asserting the server answers it without failing.
+    async fn send(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Vec<u8>,
+    ) -> (StatusCode, Vec<u8>) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+
+        let response = self
+            .app
+            .clone()
+            .oneshot(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap()
+            .to_vec();
+
+        assert!(
+            !status.is_server_error(),
+            "seed {}: {uri} answered {status}",
+            self.seed
+        );
+
+        (status, body)
+    }
+
+    /// Sends a request expected to be answered with a [`Message`].
+    async fn send_message(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Vec<u8>,
+    ) -> Option<Message> {
+        let (status, body) = self.send(method, uri, token, body).await;
+
+        (status == StatusCode::OK).then(|| {
+            serde_json::from_slice(&body).unwrap_or_else(|err| {
+                panic!(
+                    "seed {}: {uri} answered an invalid message: {err}",
+                    self.seed
+                )
+            })
+        })
+    }
+
+    /// Picks a known lobby ID most of the time, and any other ID otherwise.
+    fn lobby_id(&mut self) -> u16 {
+        match self.lobby_ids.choose(&mut self.rng) {
+            Some(lobby_id) if self.rng.gen_bool(0.8) => *lobby_id,
+            _ => self.rng.gen(),
+        }
+    }
+
+    /// Picks a client, returning its session ID and a token which is sometimes not its own.
+    fn client(&mut self) -> Option<(String, Option<String>)> {
+        let client = self.clients.choose(&mut self.rng)?;
+
+        let token = match self.rng.gen_range(0..8) {
+            0 => None,
+            1 => Some("forged".to_string()),
+            _ => Some(client.token.clone()),
+        };
+
+        Some((client.session_id.clone(), token))
+    }
+
+    async fn obtain_session(&mut self) {
+        if let (StatusCode::OK, body) = self.send(Method::GET, "/session", None, Vec::new()).await {
+            let grant: SessionGrant = serde_json::from_slice(&body).unwrap();
+
+            self.clients.push(Client {
+                session_id: grant.session_id,
+                token: grant.token,
+            });
+        }
+    }
+
+    async fn create_lobby(&mut self) {
+        let Some((session_id, token)) = self.client() else {
+            return;
+        };
+
+        // Written past the setters, which would clamp them on this side
+        let mut body = serde_json::to_value(SessionNewLobby {
+            session_id,
+            lobby_settings: LobbySettings::new(LobbySort::Online(0)),
+        })
+        .unwrap();
+        let lobby_settings = &mut body["lobby_settings"];
+        lobby_settings["players_per_team"] = self.rng.gen_range(0..4).into();
+        lobby_settings["turn_duration"] = self.rng.gen::<u64>().into();
+        lobby_settings["best_of"] = self.rng.gen_range(0..16).into();
+
+        let body = serde_json::to_vec(&body).unwrap();
+
+        let message = self
+            .send_message(Method::POST, "/lobbies/create", token.as_deref(), body)
+            .await;
+
+        if let Some(Message::Lobby(lobby)) = message {
+            if let LobbySort::Online(lobby_id) = lobby.settings.sort() {
+                self.lobby_ids.push(*lobby_id);
+            }
+        }
+    }
+
+    async fn join_lobby(&mut self) {
+        let Some((session_id, token)) = self.client() else {
+            return;
+        };
+
+        let uri = format!("/lobbies/{}/ready", self.lobby_id());
+        let body = serde_json::to_vec(&SessionRequest { session_id }).unwrap();
+
+        self.send_message(Method::POST, &uri, token.as_deref(), body)
+            .await;
+    }
+
+    /// Acts with moves for any bug, with any impulse, for any turn, committed, revealed with the
+    /// right or a wrong salt, or sent in plain, readies up, chats or surrenders.
+    async fn act(&mut self) {
+        let Some((session_id, token)) = self.client() else {
+            return;
+        };
+
+        let message = if self.rng.gen_bool(0.2) {
+            Message::Surrender
+        } else if self.rng.gen_bool(0.3) {
+            Message::Ready(self.rng.gen_bool(0.8))
+        } else if self.rng.gen_bool(0.2) {
+            let text = (0..self.rng.gen_range(0..80))
+                .map(|_| char::from_u32(self.rng.gen_range(0..0x300)).unwrap_or(' '))
+                .collect();
+
+            Message::Chat(ChatContent::Text(text))
+        } else {
+            let mut turn = Turn {
+                index: match self.rng.gen_bool(0.5) {
+                    true => self.rng.gen_range(0..4),
+                    false => self.rng.gen(),
+                },
+                ..Default::default()
+            };
+
+            for _ in 0..self.rng.gen_range(0..8) {
+                let bug_index = match self.rng.gen_bool(0.5) {
+                    true => self.rng.gen_range(0..8),
+                    false => self.rng.gen(),
+                };
+                let scale = [1.0, 1e6, f32::MAX].choose(&mut self.rng).unwrap();
+                let impulse_intent = nalgebra::vector![
+                    self.rng.gen_range(-1.0..1.0) * scale,
+                    self.rng.gen_range(-1.0..1.0) * scale
+                ];
+
+                turn.impulse_intents.insert(bug_index, impulse_intent);
+            }
+
+            let salt = self.rng.gen_range(0..4);
+
+            match self.rng.gen_range(0..3) {
+                0 => Message::Commit(turn.commitment(salt)),
+                1 => Message::Reveal(turn, salt),
+                _ => Message::Move(turn),
+            }
+        };
+
+        let uri = format!("/lobbies/{}/act", self.lobby_id());
+        let body = serde_json::to_vec(&SessionMessage {
+            session_id,
+            message,
+            message_id: self.rng.gen(),
+        })
+        .unwrap();
+
+        self.send_message(Method::POST, &uri, token.as_deref(), body)
+            .await;
+    }
+
+    async fn poll(&mut self) {
+        let since = match self.rng.gen_bool(0.5) {
+            true => self.rng.gen_range(0..4),
+            false => self.rng.gen::<usize>(),
+        };
+
+        let uri = match self.client() {
+            Some((session_id, _)) => format!(
+                "/lobbies/{}/turns/{since}?session_id={session_id}",
+                self.lobby_id()
+            ),
+            None => format!("/lobbies/{}/turns/{since}", self.lobby_id()),
+        };
+        let token = self.client().and_then(|(_, token)| token);
+
+        self.send(Method::GET, &uri, token.as_deref(), Vec::new())
+            .await;
+    }
+
+    /// Sends truncated, mistyped or oversized bodies to the endpoints taking JSON.
+    async fn malformed(&mut self) {
+        let endpoint = POST_ENDPOINTS.choose(&mut self.rng).unwrap();
+        let uri = endpoint.replace("{id}", &self.lobby_id().to_string());
+        let token = self.client().and_then(|(_, token)| token);
+
+        let body = match self.rng.gen_range(0..5) {
+            0 => Vec::new(),
+            1 => b"{\"session_id\":".to_vec(),
+            2 => b"{\"session_id\":7,\"message\":[]}".to_vec(),
+            3 => (0..self.rng.gen_range(1..256))
+                .map(|_| self.rng.gen())
+                .collect(),
+            _ => {
+                let mut body = b"{\"session_id\":\"".to_vec();
+                body.resize(HUGE_PAYLOAD, b'a');
+                body.extend(b"\"}");
+                body
+            }
+        };
+
+        let (status, _) = self.send(Method::POST, &uri, token.as_deref(), body).await;
+
+        assert!(
+            status.is_client_error(),
+            "seed {}: malformed request to {uri} answered {status}",
+            self.seed
+        );
+    }
+
+    /// Requests paths the router does not expect, or parameters it cannot parse.
+    async fn stray(&mut self) {
+        let uri = [
+            "/lobbies/-1/state",
+            "/lobbies/65536/state",
+            "/lobbies/abc/turns/0",
+            "/lobbies/1/turns/-1",
+            "/lobbies/1/turns/99999999999999999999999",
+            "/maps/-1",
+            "/maps/99999999999999999999999",
+            "/tournaments/%FF%FE",
+            "/nowhere",
+        ]
+        .choose(&mut self.rng)
+        .unwrap();
+
+        self.send(Method::GET, uri, None, Vec::new()).await;
+    }
+
+    async fn step(&mut self) {
+        match self.rng.gen_range(0..10) {
+            0 => self.obtain_session().await,
+            1 => self.create_lobby().await,
+            2 => self.join_lobby().await,
+            3 | 4 => self.act().await,
+            5 => self.poll().await,
+            6 | 7 => self.malformed().await,
+            8 => self.stray().await,
+            _ => {
+                for uri in ["/lobbies/", "/status", "/ping", "/maps/0"] {
+                    self.send(Method::GET, uri, None, Vec::new()).await;
+                }
+            }
+        }
+    }
+
+    /// Checks that every hosted lobby is listed and consistent.
+    async fn check_lobbies(&self) {
+        let listed = match self
+            .send_message(Method::GET, "/lobbies/", None, Vec::new())
+            .await
+        {
+            Some(Message::Lobbies(lobbies)) => lobbies,
+            _ => panic!("seed {}: lobbies are not listed", self.seed),
+        };
+
+        for lobby_id in &self.lobby_ids {
+            let uri = format!("/lobbies/{lobby_id}/state");
+
+            match self.send_message(Method::GET, &uri, None, Vec::new()).await {
+                Some(Message::Lobby(lobby)) => {
+                    self.check_lobby(*lobby_id, &lobby);
+                    self.check_turns(*lobby_id).await;
+                }
+                Some(Message::LobbyError(_)) => {
+                    assert!(!listed.contains_key(lobby_id), "seed {}", self.seed)
+                }
+                _ => panic!("seed {}: lobby {lobby_id} has no state", self.seed),
+            }
+        }
+    }
+
+    fn check_lobby(&self, lobby_id: u16, lobby: &Lobby) {
+        let seed = self.seed;
+        let players_per_team = lobby.settings.players_per_team();
+
+        assert!(
+            matches!(lobby.settings.sort(), LobbySort::Online(id) if *id == lobby_id),
+            "seed {seed}: lobby {lobby_id} is filed under another ID"
+        );
+        assert!(
+            (1..=2).contains(&players_per_team),
+            "seed {seed}: lobby {lobby_id} has {players_per_team} players per team"
+        );
+        assert!(
+            (1..=LobbySettings::MAX_BEST_OF).contains(&lobby.settings.best_of())
+                && lobby.settings.best_of() % 2 == 1,
+            "seed {seed}: lobby {lobby_id} plays a series of {}",
+            lobby.settings.best_of()
+        );
+        assert!(
+            (LobbySettings::MIN_TURN_DURATION..=LobbySettings::MAX_TURN_DURATION)
+                .contains(&lobby.settings.turn_duration()),
+            "seed {seed}: lobby {lobby_id} has turns of {}s",
+            lobby.settings.turn_duration()
+        );
+        assert!(
+            !lobby.players().is_empty(),
+            "seed {seed}: lobby {lobby_id} has no host"
+        );
+        assert_eq!(
+            lobby.players().len() + lobby.open_slots(),
+            players_per_team * 2,
+            "seed {seed}: lobby {lobby_id} lost track of its slots"
+        );
+
+        assert!(
+            lobby.chat.len() <= Lobby::CHAT_LINES,
+            "seed {seed}: lobby {lobby_id} keeps {} chat lines",
+            lobby.chat.len()
+        );
+
+        for line in &lobby.chat {
+            let text = line.content.text();
+
+            assert!(
+                !text.is_empty()
+                    && text.len() <= ChatContent::MAX_LENGTH
+                    && text
+                        .chars()
+                        .all(|char| char.is_ascii_graphic() || char == ' '),
+                "seed {seed}: lobby {lobby_id} has unsanitized chat {text:?}"
+            );
+        }
+    }
+
+    /// Checks that the turns a started lobby executed follow each other, returning how many it
+    /// executed.
+    async fn check_turns(&self, lobby_id: u16) -> usize {
+        let seed = self.seed;
+        let uri = format!("/lobbies/{lobby_id}/turns/0");
+
+        let Some(Message::TurnSync(turn_sync)) =
+            self.send_message(Method::GET, &uri, None, Vec::new()).await
+        else {
+            return 0;
+        };
+
+        let first_index = turn_sync.turns.first().map_or(0, |turn| turn.index);
+
+        for (offset, turn) in turn_sync.turns.iter().enumerate() {
+            assert_eq!(
+                turn.index,
+                first_index + offset,
+                "seed {seed}: lobby {lobby_id} has turns out of order"
+            );
+        }
+
+        assert!(
+            first_index + turn_sync.turns.len() <= turn_sync.turn_count,
+            "seed {seed}: lobby {lobby_id} sent turns it did not execute"
+        );
+
+        turn_sync.turn_count
+    }
+}
+
+#[tokio::test]
+async fn fuzz_router() {
+    for seed in 0..SEEDS {
+        let mut fuzzer = Fuzzer::new(seed);
+
+        for _ in 0..STEPS {
+            fuzzer.step().await;
+            fuzzer.check_lobbies().await;
+        }
+    }
+}
+
+#[tokio::test]
+async fn fuzz_turns() {
+    let mut fuzzer = Fuzzer::new(SEEDS);
+
+    for _ in 0..2 {
+        fuzzer.obtain_session().await;
+    }
+
+    let [host, guest] = &fuzzer.clients[..] else {
+        panic!("no sessions were issued");
+    };
+
+    let mut lobby_settings = LobbySettings::new(LobbySort::Online(0));
+    lobby_settings.set_turn_duration(LobbySettings::MIN_TURN_DURATION);
+    // Turns are checked as spectators see them
+    lobby_settings.set_spectator_delay(0);
+
+    let body = serde_json::to_vec(&SessionNewLobby {
+        session_id: host.session_id.clone(),
+        lobby_settings,
+    })
+    .unwrap();
+
+    let lobby_id = match fuzzer
+        .send_message(Method::POST, "/lobbies/create", Some(&host.token), body)
+        .await
+    {
+        Some(Message::Lobby(lobby)) => match lobby.settings.sort() {
+            LobbySort::Online(lobby_id) => *lobby_id,
+            _ => panic!("lobby is not online"),
+        },
+        _ => panic!("lobby not created"),
+    };
+
+    let uri = format!("/lobbies/{lobby_id}/ready");
+    let body = serde_json::to_vec(&SessionRequest {
+        session_id: guest.session_id.clone(),
+    })
+    .unwrap();
+    fuzzer
+        .send_message(Method::POST, &uri, Some(&guest.token), body)
+        .await;
+
+    let uri = format!("/lobbies/{lobby_id}/act");
+
+    for client in [host, guest] {
+        let body = serde_json::to_vec(&SessionMessage {
+            session_id: client.session_id.clone(),
+            message: Message::Ready(true),
+            message_id: None,
+        })
+        .unwrap();
+
+        fuzzer
+            .send_message(Method::POST, &uri, Some(&client.token), body)
+            .await;
+    }
+
+    fuzzer.lobby_ids.push(lobby_id);
+
+    // Players act and poll at random, but only the clock advances the turns
+    let deadline = tokio::time::Instant::now()
+        + std::time::Duration::from_secs(LobbySettings::MIN_TURN_DURATION * TURNS as u64 + 5);
+
+    while fuzzer.check_turns(lobby_id).await < TURNS {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "lobby {lobby_id} executed no turns on schedule"
+        );
+
+        match fuzzer.rng.gen_range(0..4) {
+            0 => fuzzer.poll().await,
+            _ => fuzzer.act().await,
+        }
+
+        fuzzer.check_lobbies().await;
+
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
@@ -0,0 +1,94 @@
+//! Races sessions for the last slot of a lobby, checking the loser is told the slot was taken
+//! while the lobby waits for its players, and that the lobby is full once its game started.
+//! Also checks that a player leaving the waiting room frees their slot for someone else.
+
+mod common;
+
+use axum::Router;
+use common::{act, create_lobby, error_kind, join, obtain_session};
+use server::{router, AppState};
+use shared::{LobbyErrorKind, Message, SessionGrant};
+
+async fn ready(app: &Router, lobby_id: u16, grant: &SessionGrant) {
+    act(app, lobby_id, grant, Message::Ready(true)).await;
+}
+
+#[tokio::test]
+async fn join_race() {
+    let app = router(AppState::new("join".to_string(), None));
+
+    let host = obtain_session(&app).await;
+    let first = obtain_session(&app).await;
+    let second = obtain_session(&app).await;
+    let late = obtain_session(&app).await;
+
+    let lobby_id = create_lobby(&app, &host).await;
+
+    let (a, b) = tokio::join!(join(&app, lobby_id, &first), join(&app, lobby_id, &second));
+
+    let (winner, loser) = match (&a, &b) {
+        (Message::Lobby(_), _) => ((&first, &a), (&second, &b)),
+        _ => ((&second, &b), (&first, &a)),
+    };
+
+    assert!(
+        matches!(winner.1, Message::Lobby(_)),
+        "no one joined: {:?} {:?}",
+        error_kind(&a),
+        error_kind(&b)
+    );
+    assert_eq!(error_kind(loser.1), Some(LobbyErrorKind::SlotTaken));
+
+    // Joining again is harmless for the winner, and still a lost race for the loser
+    assert_eq!(
+        error_kind(&join(&app, lobby_id, winner.0).await),
+        Some(LobbyErrorKind::AlreadyJoined)
+    );
+    assert_eq!(
+        error_kind(&join(&app, lobby_id, loser.0).await),
+        Some(LobbyErrorKind::SlotTaken)
+    );
+
+    ready(&app, lobby_id, &host).await;
+    ready(&app, lobby_id, winner.0).await;
+
+    assert_eq!(
+        error_kind(&join(&app, lobby_id, &late).await),
+        Some(LobbyErrorKind::Full)
+    );
+}
+
+#[tokio::test]
+async fn leave_frees_slot() {
+    let app = router(AppState::new("leave".to_string(), None));
+
+    let host = obtain_session(&app).await;
+    let guest = obtain_session(&app).await;
+    let other = obtain_session(&app).await;
+
+    let lobby_id = create_lobby(&app, &host).await;
+
+    assert!(matches!(
+        join(&app, lobby_id, &guest).await,
+        Message::Lobby(_)
+    ));
+    assert_eq!(
+        error_kind(&join(&app, lobby_id, &other).await),
+        Some(LobbyErrorKind::SlotTaken)
+    );
+
+    assert!(matches!(
+        act(&app, lobby_id, &guest, Message::Leave).await,
+        Message::Ok
+    ));
+    assert_eq!(
+        error_kind(&act(&app, lobby_id, &guest, Message::Leave).await),
+        Some(LobbyErrorKind::NotJoined)
+    );
+
+    let Message::Lobby(lobby) = join(&app, lobby_id, &other).await else {
+        panic!("the freed slot could not be joined");
+    };
+    assert!(lobby.all_joined());
+    assert!(!lobby.players().contains_key(&guest.session_id));
+}
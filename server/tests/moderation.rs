@@ -0,0 +1,43 @@
+//! Reviews reported maps, checking that only the moderator token opens the review queue and that
+//! nothing is queued before enough players reported a map.
+
+mod common;
+
+use axum::http::Method;
+use common::{obtain_session, send};
+use server::{router, AppState};
+use shared::{LobbyError, LobbyErrorKind, Message};
+
+#[tokio::test]
+async fn review_queue() {
+    let app = router(AppState::new(
+        "moderation".to_string(),
+        Some("moderator".to_string()),
+    ));
+
+    // Neither sessions nor anything else than the moderator token open the queue
+    let grant = obtain_session(&app).await;
+
+    for token in [None, Some(grant.token.as_str()), Some("moderatoR")] {
+        assert!(matches!(
+            send(&app, Method::GET, "/moderation/maps", token, Vec::new()).await,
+            Message::LobbyError(LobbyError {
+                kind: LobbyErrorKind::Unauthorized,
+                ..
+            })
+        ));
+    }
+
+    match send(
+        &app,
+        Method::GET,
+        "/moderation/maps",
+        Some("moderator"),
+        Vec::new(),
+    )
+    .await
+    {
+        Message::Maps(gallery) => assert!(gallery.maps.is_empty()),
+        _ => panic!("the review queue is not listed"),
+    }
+}
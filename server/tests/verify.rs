@@ -0,0 +1,117 @@
+//! Submits replays for verification, checking that each is bound to a level seed issued to its
+//! session and used once, and that neither a session nor many fresh sessions can have the server
+//! re-simulate games back to back.
+
+mod common;
+
+use axum::{http::Method, Router};
+use common::{obtain_session, send};
+use server::{router, AppState};
+use shared::{
+    LobbyError, LobbySettings, LobbySort, Message, Replay, SessionGrant, SessionReplay,
+    SessionRequest,
+};
+
+/// Has the server issue a level seed to the session.
+async fn level_seed(app: &Router, grant: &SessionGrant) -> u64 {
+    let body = serde_json::to_vec(&SessionRequest {
+        session_id: grant.session_id.clone(),
+    })
+    .unwrap();
+
+    match send(app, Method::POST, "/verify/seed", Some(&grant.token), body).await {
+        Message::LevelSeed(level_seed) => level_seed,
+        _ => panic!("no level seed was issued"),
+    }
+}
+
+/// Submits a replay without turns played on the given level, returning why it was refused.
+async fn verify(app: &Router, grant: &SessionGrant, level_seed: u64) -> String {
+    let mut settings = LobbySettings::new(LobbySort::Local);
+    settings.set_level_seed(level_seed);
+
+    let body = serde_json::to_vec(&SessionReplay {
+        session_id: grant.session_id.clone(),
+        replay: Replay {
+            settings,
+            turns: Vec::new(),
+        },
+    })
+    .unwrap();
+
+    match send(app, Method::POST, "/verify", Some(&grant.token), body).await {
+        Message::LobbyError(LobbyError { reason, .. }) => reason,
+        _ => panic!("a replay without turns was verified"),
+    }
+}
+
+#[tokio::test]
+async fn level_seeds() {
+    let app = router(AppState::new("verify".to_string(), None));
+
+    let grant = obtain_session(&app).await;
+    let other = obtain_session(&app).await;
+
+    let not_issued = "replay not played on a level issued to this session";
+
+    // Replays need a seed, and one issued to another session does not count
+    assert_eq!(verify(&app, &grant, 0).await, not_issued);
+
+    let level_seed = level_seed(&app, &grant).await;
+    assert_ne!(level_seed, 0);
+    assert_eq!(verify(&app, &other, level_seed).await, not_issued);
+
+    // Each seed verifies a single replay
+    assert_eq!(
+        verify(&app, &grant, level_seed).await,
+        "replay could not be verified"
+    );
+    assert_eq!(verify(&app, &grant, level_seed).await, not_issued);
+}
+
+#[tokio::test]
+async fn verification_rate_limit() {
+    let app = router(AppState::new("verify".to_string(), None));
+
+    let grant = obtain_session(&app).await;
+    let other = obtain_session(&app).await;
+
+    let seeds = [
+        level_seed(&app, &grant).await,
+        level_seed(&app, &grant).await,
+    ];
+
+    assert_eq!(
+        verify(&app, &grant, seeds[0]).await,
+        "replay could not be verified"
+    );
+    assert_eq!(
+        verify(&app, &grant, seeds[1]).await,
+        "verifying too often, wait a moment"
+    );
+
+    // Other sessions are limited on their own, until all of them together reach the global limit
+    let seed = level_seed(&app, &other).await;
+    assert_eq!(
+        verify(&app, &other, seed).await,
+        "replay could not be verified"
+    );
+
+    for _ in 2..AppState::VERIFY_REPLAYS_PER_WINDOW {
+        let fresh = obtain_session(&app).await;
+        let seed = level_seed(&app, &fresh).await;
+
+        assert_eq!(
+            verify(&app, &fresh, seed).await,
+            "replay could not be verified"
+        );
+    }
+
+    let fresh = obtain_session(&app).await;
+    let seed = level_seed(&app, &fresh).await;
+
+    assert_eq!(
+        verify(&app, &fresh, seed).await,
+        "verifying too often, wait a moment"
+    );
+}
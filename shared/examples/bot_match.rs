@@ -0,0 +1,51 @@
+//! Pits two [`BotController`]s against each other headlessly and prints the capture progress each turn.
+//!
+//! Run with `cargo run -p shared --example bot_match -- [turns]`.
+
+use nalgebra::Vector2;
+use shared::{BotController, Game, GameView, Team, VecMap, ZoneBot};
+
+/// Flicks every bug at the closest enemy bug.
+struct HunterBot;
+
+impl BotController for HunterBot {
+    fn plan(&mut self, view: &GameView) -> VecMap<usize, Vector2<f32>> {
+        view.own_bugs()
+            .filter_map(|bug| {
+                view.bugs
+                    .iter()
+                    .filter(|other| other.team != view.team && other.health > 1)
+                    .min_by(|a, b| {
+                        (a.position - bug.position)
+                            .magnitude()
+                            .total_cmp(&(b.position - bug.position).magnitude())
+                    })
+                    .map(|target| (bug.index, (target.position - bug.position) * 0.5))
+            })
+            .collect()
+    }
+}
+
+fn main() {
+    let turns: usize = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(20);
+
+    let mut game = Game::default();
+    let mut red = ZoneBot::default();
+    let mut blue = HunterBot;
+
+    for turn in 0..turns {
+        while !game.awaiting_turn() {
+            game.tick();
+        }
+
+        game.plan_bot(Team::Red, &mut red);
+        game.plan_bot(Team::Blue, &mut blue);
+        game.queue_turns(vec![game.aggregate_turn()]).unwrap();
+        game.tick();
+
+        println!("turn {turn:>3}: capture {:+.2}", game.capture_progress());
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Game, GameEvent, GamePhase, Result, Team};
+
+/// Bug impacts a team has to land within one turn for [`Achievement::TripleHit`].
+const TRIPLE_HIT_IMPACTS: usize = 3;
+
+/// Capture progress of the rival from which a win counts as an [`Achievement::Comeback`].
+const COMEBACK_PROGRESS: f32 = 0.8;
+
+/// Feats players unlock over their games, tracked with an [`AchievementTracker`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Achievement {
+    /// Win without any of the team's bugs being knocked out.
+    Flawless,
+    /// Land three hits on rival bugs within a single turn.
+    TripleHit,
+    /// Win after the rival had captured most of the zone.
+    Comeback,
+}
+
+impl Achievement {
+    /// All achievements, in the order of the gallery.
+    pub const ALL: [Achievement; 3] = [
+        Achievement::Flawless,
+        Achievement::TripleHit,
+        Achievement::Comeback,
+    ];
+
+    /// Display name of the achievement.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Achievement::Flawless => "Flawless",
+            Achievement::TripleHit => "Triple Hit",
+            Achievement::Comeback => "Comeback",
+        }
+    }
+
+    /// Short explanation of how the achievement is unlocked.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Achievement::Flawless => "Win without losing a bug.",
+            Achievement::TripleHit => "Land 3 hits in one turn.",
+            Achievement::Comeback => "Win from -80% capture.",
+        }
+    }
+
+    /// Stable identifier of the achievement, used to persist it.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Achievement::Flawless => "flawless",
+            Achievement::TripleHit => "triple_hit",
+            Achievement::Comeback => "comeback",
+        }
+    }
+
+    /// Returns the achievement with the given [`Achievement::key`].
+    pub fn from_key(key: &str) -> Option<Achievement> {
+        Achievement::ALL
+            .into_iter()
+            .find(|achievement| achievement.key() == key)
+    }
+}
+
+/// Follows a [`Team`] through a [`Game`], unlocking [`Achievement`]s from its [`GameEvent`]s.
+#[derive(Debug, Clone)]
+pub struct AchievementTracker {
+    team: Team,
+    unlocked: Vec<Achievement>,
+    lost_bug: bool,
+    turn_hits: usize,
+    worst_progress: f32,
+}
+
+impl AchievementTracker {
+    /// Creates a tracker for the given [`Team`].
+    pub fn new(team: Team) -> AchievementTracker {
+        AchievementTracker {
+            team,
+            unlocked: Vec::new(),
+            lost_bug: false,
+            turn_hits: 0,
+            worst_progress: 0.0,
+        }
+    }
+
+    /// Observes the [`Game`] along with the events it emitted since the last observation, returning
+    /// the achievements unlocked by them.
+    pub fn observe(&mut self, game: &Game, events: &[GameEvent]) -> Vec<Achievement> {
+        // Knocked out bugs recover over turns, so the team is checked on every observation
+        if game
+            .iter_bugdata()
+            .any(|bug_data| *bug_data.team() == self.team && bug_data.health() <= 1)
+        {
+            self.lost_bug = true;
+        }
+
+        let mut unlocked = Vec::new();
+
+        for event in events {
+            match event {
+                GameEvent::PhaseChanged(GamePhase::Resolving) => {
+                    self.turn_hits = 0;
+                }
+                GameEvent::BugImpact { attacker, .. } => {
+                    let own = game
+                        .get_bug(*attacker)
+                        .is_some_and(|(_, bug_data)| *bug_data.team() == self.team);
+
+                    if own {
+                        self.turn_hits += 1;
+
+                        if self.turn_hits >= TRIPLE_HIT_IMPACTS {
+                            unlocked.push(Achievement::TripleHit);
+                        }
+                    }
+                }
+                GameEvent::ZoneScored { progress, .. } => {
+                    let progress = match self.team {
+                        Team::Red => *progress,
+                        Team::Blue => -*progress,
+                    };
+
+                    self.worst_progress = self.worst_progress.min(progress);
+                }
+                GameEvent::PhaseChanged(GamePhase::Finished)
+                    if game.result() == Some(Result::Win(self.team)) =>
+                {
+                    if !self.lost_bug {
+                        unlocked.push(Achievement::Flawless);
+                    }
+
+                    if self.worst_progress <= -COMEBACK_PROGRESS {
+                        unlocked.push(Achievement::Comeback);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut newly_unlocked = Vec::new();
+
+        for achievement in unlocked {
+            if !self.unlocked.contains(&achievement) {
+                self.unlocked.push(achievement);
+                newly_unlocked.push(achievement);
+            }
+        }
+
+        newly_unlocked
+    }
+
+    /// Returns the achievements unlocked so far, in order.
+    pub fn unlocked(&self) -> &[Achievement] {
+        &self.unlocked
+    }
+}
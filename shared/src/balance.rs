@@ -0,0 +1,116 @@
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::BugSort;
+
+static BALANCE: OnceLock<BalanceConfig> = OnceLock::new();
+
+/// Returns the installed [`BalanceConfig`], or the built-in defaults if none was installed.
+pub fn balance() -> &'static BalanceConfig {
+    BALANCE.get_or_init(BalanceConfig::default)
+}
+
+/// Physical properties of a [`BugSort`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct BugBalance {
+    /// Mass of the bug's collider.
+    pub mass: f32,
+    /// Restitution of the bug's collider.
+    pub restitution: f32,
+}
+
+/// Physics parameters shared by the client and the server, loaded at runtime so balance patches
+/// ship without rebuilding.
+///
+/// Both sides must run the same config to simulate identical games, compare with [`BalanceConfig::hash`].
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct BalanceConfig {
+    /// Balance of [`BugSort::Beetle`].
+    pub beetle: BugBalance,
+    /// Balance of [`BugSort::Ladybug`].
+    pub ladybug: BugBalance,
+    /// Balance of [`BugSort::Ant`].
+    pub ant: BugBalance,
+    /// Multiplier from a bug's impulse intent to the impulse applied when a turn executes.
+    pub impulse_multiplier: f32,
+    /// Linear damping of bodies outside of any terrain patch.
+    pub base_linear_damping: f32,
+    /// Linear damping of bodies in [`crate::TerrainSort::Mud`].
+    pub mud_linear_damping: f32,
+    /// Linear damping of bodies on [`crate::TerrainSort::Ice`].
+    pub ice_linear_damping: f32,
+    /// Mass of a [`crate::PropSort::Boulder`].
+    pub boulder_mass: f32,
+    /// Restitution of a [`crate::PropSort::Boulder`].
+    pub boulder_restitution: f32,
+    /// Angular damping of a [`crate::PropSort::Boulder`].
+    pub boulder_angular_damping: f32,
+    /// Restitution of immovable props.
+    pub prop_restitution: f32,
+    /// Speed below which a body may fall asleep, no longer simulated until something hits it.
+    pub sleep_linear_threshold: f32,
+    /// Seconds a body has to stay below [`BalanceConfig::sleep_linear_threshold`] to fall asleep.
+    pub sleep_delay: f32,
+}
+
+impl Default for BalanceConfig {
+    fn default() -> Self {
+        BalanceConfig {
+            beetle: BugBalance {
+                mass: 1.0,
+                restitution: 0.7,
+            },
+            ladybug: BugBalance {
+                mass: 0.9,
+                restitution: 0.75,
+            },
+            ant: BugBalance {
+                mass: 0.6,
+                restitution: 0.95,
+            },
+            impulse_multiplier: 2.0,
+            base_linear_damping: 1.5,
+            mud_linear_damping: 6.0,
+            ice_linear_damping: 0.1,
+            boulder_mass: 4.0,
+            boulder_restitution: 0.3,
+            boulder_angular_damping: 2.0,
+            prop_restitution: 0.7,
+            sleep_linear_threshold: 0.4,
+            sleep_delay: 0.25,
+        }
+    }
+}
+
+impl BalanceConfig {
+    /// Parses a [`BalanceConfig`] from JSON, missing fields take their default values.
+    pub fn from_json(json: &str) -> Result<BalanceConfig, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Installs this config for the rest of the process, fails if a config is already in use.
+    pub fn install(self) -> Result<(), BalanceConfig> {
+        BALANCE.set(self)
+    }
+
+    /// Returns the [`BugBalance`] of the given [`BugSort`].
+    pub fn bug(&self, bug_sort: &BugSort) -> &BugBalance {
+        match bug_sort {
+            BugSort::Beetle => &self.beetle,
+            BugSort::Ladybug => &self.ladybug,
+            BugSort::Ant => &self.ant,
+        }
+    }
+
+    /// Stable FNV-1a hash of the config, identical across platforms.
+    pub fn hash(&self) -> u64 {
+        serde_json::to_vec(self)
+            .unwrap_or_default()
+            .iter()
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+}
@@ -0,0 +1,342 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{LobbyError, LobbyErrorKind, LobbyID};
+
+/// Length of the codes players join a [`Bracket`] with.
+pub const BRACKET_CODE_LENGTH: usize = 6;
+
+/// Most players a [`Bracket`] takes.
+pub const MAX_BRACKET_PLAYERS: usize = 16;
+
+/// Stage a [`Bracket`] is in.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Copy, Clone, Default)]
+pub enum BracketPhase {
+    /// Players are registering with the join code.
+    #[default]
+    Registering,
+    /// Matches are being played.
+    Running,
+    /// The final was decided.
+    Finished,
+}
+
+/// A pairing in a round of a [`Bracket`].
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BracketMatch {
+    /// Session IDs of the Red and Blue player, `None` while undecided or for a bye.
+    pub players: [Option<String>; 2],
+    /// Lobby the match is played in, once both players are known.
+    pub lobby_id: Option<LobbyID>,
+    /// Session ID of the player who advanced.
+    pub winner: Option<String>,
+}
+
+/// A single-elimination tournament hosted by the server, which players register for with its
+/// join code. The server creates a lobby for every pairing and advances winners as lobbies
+/// finish.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Bracket {
+    /// Code players join the bracket with.
+    pub code: String,
+    /// Session ID of the player who created the bracket, the only one who may start it.
+    pub host: String,
+    /// Session IDs of the registered players in order of registration, which seeds the bracket.
+    pub players: Vec<String>,
+    /// Matches of every round, the first round first and the final last.
+    pub rounds: Vec<Vec<BracketMatch>>,
+    /// Stage the bracket is in.
+    pub phase: BracketPhase,
+}
+
+impl Bracket {
+    /// Opens a bracket for registration, with its host as the first player.
+    pub fn new(code: String, host: String) -> Bracket {
+        Bracket {
+            code,
+            players: vec![host.clone()],
+            host,
+            rounds: Vec::new(),
+            phase: BracketPhase::Registering,
+        }
+    }
+
+    /// Registers a player, who may register again harmlessly.
+    pub fn join(&mut self, session_id: String) -> Result<(), LobbyError> {
+        if self.players.contains(&session_id) {
+            Ok(())
+        } else if self.phase != BracketPhase::Registering {
+            Err(LobbyError::new(
+                LobbyErrorKind::Full,
+                "bracket already started",
+            ))
+        } else if self.players.len() >= MAX_BRACKET_PLAYERS {
+            Err(LobbyError::new(LobbyErrorKind::Full, "bracket is full"))
+        } else {
+            self.players.push(session_id);
+
+            Ok(())
+        }
+    }
+
+    /// Seeds the registered players into rounds, the top seeds getting byes when the players do
+    /// not fill the bracket.
+    pub fn start(&mut self) -> Result<(), LobbyError> {
+        if self.phase != BracketPhase::Registering {
+            return Err(LobbyError::new(
+                LobbyErrorKind::AlreadyJoined,
+                "bracket already started",
+            ));
+        }
+
+        if self.players.len() < 2 {
+            return Err(LobbyError::new(
+                LobbyErrorKind::NotStarted,
+                "bracket needs at least two players",
+            ));
+        }
+
+        let size = self.players.len().next_power_of_two();
+
+        // The top seed meets the bottom one, so byes fall to the top seeds
+        self.rounds = vec![(0..size / 2)
+            .map(|i| BracketMatch {
+                players: [
+                    self.players.get(i).cloned(),
+                    self.players.get(size - 1 - i).cloned(),
+                ],
+                ..Default::default()
+            })
+            .collect()];
+
+        while self.rounds.last().map_or(0, Vec::len) > 1 {
+            let matches = self.rounds.last().map_or(0, Vec::len) / 2;
+            self.rounds.push(vec![BracketMatch::default(); matches]);
+        }
+
+        self.phase = BracketPhase::Running;
+        self.advance();
+
+        Ok(())
+    }
+
+    /// Records the winner of a match and moves them on.
+    pub fn report(&mut self, round: usize, index: usize, winner: String) {
+        if let Some(bracket_match) = self
+            .rounds
+            .get_mut(round)
+            .and_then(|matches| matches.get_mut(index))
+        {
+            if bracket_match.winner.is_none()
+                && bracket_match.players.contains(&Some(winner.clone()))
+            {
+                bracket_match.winner = Some(winner);
+                self.advance();
+            }
+        }
+    }
+
+    /// Moves winners into their next match, deciding first-round byes, and finishes the bracket
+    /// once the final is decided.
+    fn advance(&mut self) {
+        if let Some(first_round) = self.rounds.first_mut() {
+            for bracket_match in first_round {
+                if let [Some(player), None] | [None, Some(player)] = &bracket_match.players {
+                    bracket_match.winner = Some(player.clone());
+                }
+            }
+        }
+
+        for round in 1..self.rounds.len() {
+            for index in 0..self.rounds[round - 1].len() {
+                if let Some(winner) = self.rounds[round - 1][index].winner.clone() {
+                    self.rounds[round][index / 2].players[index % 2] = Some(winner);
+                }
+            }
+        }
+
+        if self.champion().is_some() {
+            self.phase = BracketPhase::Finished;
+        }
+    }
+
+    /// Matches whose players are both known but which have no lobby yet, by round and index.
+    pub fn unhosted_matches(&self) -> Vec<(usize, usize)> {
+        self.matches()
+            .filter(|(_, _, bracket_match)| {
+                bracket_match.lobby_id.is_none()
+                    && bracket_match.winner.is_none()
+                    && bracket_match.players.iter().all(Option::is_some)
+            })
+            .map(|(round, index, _)| (round, index))
+            .collect()
+    }
+
+    /// Matches being played in a lobby, by round and index.
+    pub fn hosted_matches(&self) -> Vec<(usize, usize, LobbyID)> {
+        self.matches()
+            .filter(|(_, _, bracket_match)| bracket_match.winner.is_none())
+            .filter_map(|(round, index, bracket_match)| {
+                bracket_match
+                    .lobby_id
+                    .map(|lobby_id| (round, index, lobby_id))
+            })
+            .collect()
+    }
+
+    /// Assigns the lobby a match is played in.
+    pub fn host(&mut self, round: usize, index: usize, lobby_id: LobbyID) {
+        if let Some(bracket_match) = self
+            .rounds
+            .get_mut(round)
+            .and_then(|matches| matches.get_mut(index))
+        {
+            bracket_match.lobby_id = Some(lobby_id);
+        }
+    }
+
+    /// Returns a match by round and index.
+    pub fn get(&self, round: usize, index: usize) -> Option<&BracketMatch> {
+        self.rounds
+            .get(round)
+            .and_then(|matches| matches.get(index))
+    }
+
+    /// The match a player has yet to finish, if they are still in the bracket.
+    pub fn current_match(&self, session_id: &str) -> Option<&BracketMatch> {
+        self.matches()
+            .map(|(_, _, bracket_match)| bracket_match)
+            .find(|bracket_match| {
+                bracket_match.winner.is_none()
+                    && bracket_match
+                        .players
+                        .iter()
+                        .any(|player| player.as_deref() == Some(session_id))
+            })
+    }
+
+    /// The winner of the final, once decided.
+    pub fn champion(&self) -> Option<&String> {
+        self.rounds
+            .last()
+            .and_then(|matches| matches.first())
+            .and_then(|bracket_match| bracket_match.winner.as_ref())
+    }
+
+    /// Seed of a player, counting from one in order of registration.
+    pub fn seed(&self, session_id: &str) -> Option<usize> {
+        self.players
+            .iter()
+            .position(|player| player == session_id)
+            .map(|position| position + 1)
+    }
+
+    fn matches(&self) -> impl Iterator<Item = (usize, usize, &BracketMatch)> {
+        self.rounds.iter().enumerate().flat_map(|(round, matches)| {
+            matches
+                .iter()
+                .enumerate()
+                .map(move |(index, bracket_match)| (round, index, bracket_match))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bracket(players: usize) -> Bracket {
+        let mut bracket = Bracket::new("ABCDEF".to_string(), "p1".to_string());
+
+        for player in 2..=players {
+            bracket.join(format!("p{player}")).unwrap();
+        }
+
+        bracket
+    }
+
+    #[test]
+    fn registration() {
+        let mut bracket = bracket(MAX_BRACKET_PLAYERS);
+
+        // Registering again is harmless, even once full
+        assert!(bracket.join("p2".to_string()).is_ok());
+        assert_eq!(
+            bracket.join("late".to_string()).unwrap_err().kind,
+            LobbyErrorKind::Full
+        );
+
+        let mut bracket = self::bracket(1);
+        assert_eq!(
+            bracket.start().unwrap_err().kind,
+            LobbyErrorKind::NotStarted
+        );
+
+        bracket.join("p2".to_string()).unwrap();
+        bracket.start().unwrap();
+
+        assert_eq!(bracket.phase, BracketPhase::Running);
+        assert_eq!(
+            bracket.join("p3".to_string()).unwrap_err().kind,
+            LobbyErrorKind::Full
+        );
+        assert_eq!(
+            bracket.start().unwrap_err().kind,
+            LobbyErrorKind::AlreadyJoined
+        );
+    }
+
+    #[test]
+    fn top_seeds_get_byes() {
+        let mut bracket = bracket(5);
+        bracket.start().unwrap();
+
+        assert_eq!(bracket.rounds.len(), 3);
+        assert_eq!(bracket.rounds[0].len(), 4);
+
+        // Only the fourth and fifth seeds play in the first round
+        assert_eq!(bracket.unhosted_matches(), vec![(0, 3), (1, 0)]);
+        assert_eq!(
+            bracket.rounds[0][3].players,
+            [Some("p4".to_string()), Some("p5".to_string())]
+        );
+        assert_eq!(
+            bracket.rounds[1][0].players,
+            [Some("p1".to_string()), Some("p2".to_string())]
+        );
+        assert_eq!(bracket.rounds[1][1].players, [Some("p3".to_string()), None]);
+    }
+
+    #[test]
+    fn winners_advance_to_the_final() {
+        let mut bracket = bracket(4);
+        bracket.start().unwrap();
+
+        assert_eq!(bracket.unhosted_matches(), vec![(0, 0), (0, 1)]);
+
+        bracket.host(0, 0, 1);
+        assert_eq!(bracket.hosted_matches(), vec![(0, 0, 1)]);
+        assert_eq!(bracket.current_match("p4").unwrap().lobby_id, Some(1));
+
+        // Only a player of the match may win it, and only once
+        bracket.report(0, 0, "p2".to_string());
+        assert!(bracket.get(0, 0).unwrap().winner.is_none());
+
+        bracket.report(0, 0, "p4".to_string());
+        bracket.report(0, 0, "p1".to_string());
+        assert_eq!(bracket.get(0, 0).unwrap().winner.as_deref(), Some("p4"));
+        assert!(bracket.hosted_matches().is_empty());
+        assert!(bracket.current_match("p1").is_none());
+
+        bracket.report(0, 1, "p2".to_string());
+        assert_eq!(
+            bracket.get(1, 0).unwrap().players,
+            [Some("p4".to_string()), Some("p2".to_string())]
+        );
+        assert!(bracket.champion().is_none());
+
+        bracket.report(1, 0, "p2".to_string());
+        assert_eq!(bracket.champion().map(String::as_str), Some("p2"));
+        assert_eq!(bracket.phase, BracketPhase::Finished);
+        assert_eq!(bracket.seed("p2"), Some(2));
+    }
+}
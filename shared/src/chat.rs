@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Team;
+
+/// Canned messages players send with a single click.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Emote {
+    /// Greets the other players.
+    Hello,
+    /// Wishes the other players luck.
+    GoodLuck,
+    /// Asks the other players to get ready.
+    Ready,
+    /// Thanks the other players.
+    Thanks,
+}
+
+impl Emote {
+    /// Every emote, in the order they are offered.
+    pub const ALL: [Emote; 4] = [Emote::Hello, Emote::GoodLuck, Emote::Ready, Emote::Thanks];
+
+    /// Text shown for the emote.
+    pub fn text(&self) -> &'static str {
+        match self {
+            Emote::Hello => "Hello!",
+            Emote::GoodLuck => "Good luck!",
+            Emote::Ready => "Ready up!",
+            Emote::Thanks => "Thanks!",
+        }
+    }
+}
+
+/// What a player said in the lobby chat.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum ChatContent {
+    /// Free text, see [`ChatContent::sanitized`].
+    Text(String),
+    /// An [`Emote`].
+    Emote(Emote),
+}
+
+impl ChatContent {
+    /// Longest text message, which still fits a line of the chat.
+    pub const MAX_LENGTH: usize = 32;
+
+    /// Keeps the characters the game's font can draw, up to [`ChatContent::MAX_LENGTH`] of them,
+    /// or `None` if nothing is left to say.
+    pub fn sanitized(self) -> Option<ChatContent> {
+        match self {
+            ChatContent::Text(text) => {
+                let text: String = text
+                    .chars()
+                    .filter(|char| char.is_ascii_graphic() || *char == ' ')
+                    .take(Self::MAX_LENGTH)
+                    .collect();
+
+                (!text.trim().is_empty()).then(|| ChatContent::Text(text.trim().to_string()))
+            }
+            emote => Some(emote),
+        }
+    }
+
+    /// Text shown for the message.
+    pub fn text(&self) -> &str {
+        match self {
+            ChatContent::Text(text) => text,
+            ChatContent::Emote(emote) => emote.text(),
+        }
+    }
+}
+
+/// A line of the lobby chat.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChatLine {
+    /// [`crate::Player::seat`] of the player who said it.
+    pub seat: usize,
+    /// Team of the player who said it.
+    pub team: Team,
+    /// What was said.
+    pub content: ChatContent,
+    /// Server time at which it was said.
+    pub timestamp: f64,
+}
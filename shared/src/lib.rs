@@ -2,12 +2,26 @@
 
 //! The `shared` crate contains all the components which are used by both the client and the server, which includes the entire game logic too.
 
+mod achievement;
+mod balance;
+mod bracket;
+mod chat;
 mod lobby;
 mod logic;
 mod net;
+mod nickname;
+mod replay;
+mod sha256;
 mod vecmap;
 
+pub use achievement::*;
+pub use balance::*;
+pub use bracket::*;
+pub use chat::*;
 pub use lobby::*;
 pub use logic::*;
 pub use net::*;
+pub use nickname::*;
+pub use replay::*;
+pub use sha256::hmac_sha256;
 pub use vecmap::*;
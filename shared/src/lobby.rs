@@ -2,7 +2,15 @@ use std::collections::{HashMap, VecDeque};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{Game, Message, Team, Turn};
+use nalgebra::{vector, Vector2};
+
+use crate::{
+    ArenaMap, BotDifficulty, BugSort, ChatLine, ForceField, Game, GameMode, Message, Mutator,
+    Nickname, Result as GameResult, Team, Turn, TurnCommitment, MAX_QUEUED_TURNS,
+};
+
+#[cfg(feature = "server")]
+use crate::ChatContent;
 
 // #[cfg(feature = "server")]
 // use crate::Turn;
@@ -11,9 +19,59 @@ use crate::{Game, Message, Team, Turn};
 /// A identifier for a lobby, shared by the client and the server.
 pub type LobbyID = u16;
 
+/// Kinds of [`LobbyError`], which clients use to decide how to react.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum LobbyErrorKind {
+    /// The lobby does not exist.
+    NotFound,
+    /// The lobby has no free player slots left.
+    Full,
+    /// The last free player slot was taken by another player between listing the lobby and
+    /// joining it, while the lobby is still waiting for its players to ready up.
+    SlotTaken,
+    /// The session has already joined the lobby.
+    AlreadyJoined,
+    /// The session is not a player of the lobby.
+    NotJoined,
+    /// The game has not started yet.
+    NotStarted,
+    /// The submitted turn does not belong to the current turn.
+    InvalidTurn,
+    /// The submitted replay is malformed or could not be verified.
+    InvalidReplay,
+    /// The submitted map is malformed or does not fit the arena.
+    InvalidMap,
+    /// The session is not authorized.
+    Unauthorized,
+    /// The server is too busy to accept the request.
+    RateLimited,
+    /// The team cannot vote to surrender, outside team games or while its last vote cools down.
+    SurrenderUnavailable,
+    /// The turn does not accept reveals yet, see [`TurnCommitment::REVEAL_SECONDS`].
+    NotRevealing,
+    /// The game is at one of its budgets, such as [`crate::MAX_BUGS`] or
+    /// [`crate::MAX_QUEUED_TURNS`].
+    OverBudget,
+}
+
 /// Errors concerning the [`Lobby`].
 #[derive(Debug, Serialize, Deserialize)]
-pub struct LobbyError(pub String);
+pub struct LobbyError {
+    /// The kind of error.
+    pub kind: LobbyErrorKind,
+    /// A human-readable reason.
+    pub reason: String,
+}
+
+impl LobbyError {
+    /// Instantiates a new [`LobbyError`].
+    pub fn new(kind: LobbyErrorKind, reason: &str) -> LobbyError {
+        LobbyError {
+            kind,
+            reason: reason.to_string(),
+        }
+    }
+}
 
 impl<T> From<Result<T, LobbyError>> for Message {
     fn from(result: Result<T, LobbyError>) -> Self {
@@ -33,16 +91,71 @@ pub struct Player {
     pub rematch: bool,
     /// Last heartbeat.
     pub last_heartbeat: f64,
+    /// Bug indices this player controls, the whole team if empty.
+    #[serde(default)]
+    pub squad: Vec<usize>,
+    /// Index of the player's slot, which tells players apart for the whole lobby without giving
+    /// away their session IDs.
+    #[serde(default)]
+    pub seat: usize,
+    /// [`Player::seat`]s of the players whose chat lines are hidden from this player.
+    #[serde(default)]
+    pub muted: Vec<usize>,
+    /// Times of the player's latest chat lines, kept by the server only to rate limit them.
+    #[cfg(feature = "server")]
+    #[serde(skip)]
+    said_at: Vec<f64>,
+    /// Whether the player is ready for the game to begin.
+    #[serde(default)]
+    pub ready: bool,
+    /// Commitment to the player's move of the current turn, kept by the server only.
+    #[serde(skip)]
+    pub commitment: Option<TurnCommitment>,
+    /// Whether the player revealed the move of their commitment, kept by the server only.
+    #[serde(skip)]
+    pub revealed: bool,
+    /// Name the player is shown by, derived from their session ID when they join.
+    #[serde(default)]
+    pub nickname: Nickname,
 }
 
 impl Player {
-    fn new(team: Team, heartbeat: f64) -> Player {
+    fn new(team: Team, seat: usize) -> Player {
         Player {
             team,
             rematch: false,
-            last_heartbeat: heartbeat,
+            last_heartbeat: 0.0,
+            squad: Vec::new(),
+            seat,
+            muted: Vec::new(),
+            #[cfg(feature = "server")]
+            said_at: Vec::new(),
+            ready: false,
+            commitment: None,
+            revealed: false,
+            nickname: Nickname::default(),
         }
     }
+
+    fn with_squad(team: Team, seat: usize, squad: Vec<usize>) -> Player {
+        Player {
+            squad,
+            ..Player::new(team, seat)
+        }
+    }
+
+    /// Seconds without a heartbeat after which a player counts as disconnected.
+    pub const CONNECTION_TIMEOUT: f64 = 15.0;
+
+    /// Determines whether the player sent a heartbeat recently at the given time.
+    pub fn connected(&self, timestamp: f64) -> bool {
+        timestamp - self.last_heartbeat < Self::CONNECTION_TIMEOUT
+    }
+
+    /// Determines whether this player controls the given bug.
+    pub fn controls(&self, bug_index: usize) -> bool {
+        self.squad.is_empty() || self.squad.contains(&bug_index)
+    }
 }
 
 impl PartialEq for Player {
@@ -55,12 +168,93 @@ impl PartialEq for Player {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LobbySettings {
     sort: LobbySort,
+    spectator_delay: usize,
+    force_field: ForceField,
+    #[serde(default = "LobbySettings::default_players_per_team")]
+    players_per_team: usize,
+    #[serde(default)]
+    think_time_bank: Option<f32>,
+    #[serde(default)]
+    max_turns: Option<usize>,
+    #[serde(default)]
+    map: Option<ArenaMap>,
+    #[serde(default)]
+    mutators: Vec<Mutator>,
+    #[serde(default)]
+    warmup: bool,
+    #[serde(default)]
+    mode: GameMode,
+    #[serde(default = "LobbySettings::default_turn_duration")]
+    turn_duration: u64,
+    #[serde(default)]
+    private: bool,
+    #[serde(default = "LobbySettings::default_best_of")]
+    best_of: usize,
+    #[serde(default)]
+    series: Vec<GameResult>,
+    #[serde(default)]
+    bot_difficulty: BotDifficulty,
+    #[serde(default)]
+    level_seed: u64,
 }
 
 impl LobbySettings {
+    /// Number of most recent turns withheld from spectators by default.
+    pub const DEFAULT_SPECTATOR_DELAY: usize = 2;
+
+    /// Turn limit of online lobbies created from the menu.
+    pub const DEFAULT_MAX_TURNS: usize = 20;
+
+    /// Highest turn limit a lobby accepts.
+    pub const MAX_TURNS_LIMIT: usize = 100;
+
+    /// Seconds of warmup once a lobby with [`LobbySettings::warmup`] fills.
+    pub const WARMUP_SECONDS: f64 = 30.0;
+
+    /// Seconds a turn lasts unless chosen otherwise.
+    pub const DEFAULT_TURN_DURATION: u64 = 16;
+
+    /// Shortest turn a lobby accepts, leaving room to plan after the resolution.
+    pub const MIN_TURN_DURATION: u64 = 8;
+
+    /// Longest turn a lobby accepts.
+    pub const MAX_TURN_DURATION: u64 = 30;
+
+    /// Longest series a lobby accepts.
+    pub const MAX_BEST_OF: usize = 7;
+
     /// Create a new instance of [`LobbySettings`].
     pub fn new(sort: LobbySort) -> LobbySettings {
-        LobbySettings { sort }
+        LobbySettings {
+            sort,
+            spectator_delay: Self::DEFAULT_SPECTATOR_DELAY,
+            force_field: ForceField::default(),
+            players_per_team: 1,
+            think_time_bank: None,
+            max_turns: None,
+            map: None,
+            mutators: Vec::new(),
+            warmup: false,
+            mode: GameMode::default(),
+            turn_duration: Self::DEFAULT_TURN_DURATION,
+            private: false,
+            best_of: 1,
+            series: Vec::new(),
+            bot_difficulty: BotDifficulty::default(),
+            level_seed: 0,
+        }
+    }
+
+    fn default_players_per_team() -> usize {
+        1
+    }
+
+    fn default_turn_duration() -> u64 {
+        Self::DEFAULT_TURN_DURATION
+    }
+
+    fn default_best_of() -> usize {
+        1
     }
 
     /// Returns the [`LobbySort`].
@@ -72,6 +266,232 @@ impl LobbySettings {
     pub fn set_sort(&mut self, sort: LobbySort) {
         self.sort = sort;
     }
+
+    /// Returns the number of most recent turns withheld from spectators.
+    pub fn spectator_delay(&self) -> usize {
+        self.spectator_delay
+    }
+
+    /// Sets the number of most recent turns withheld from spectators.
+    pub fn set_spectator_delay(&mut self, spectator_delay: usize) {
+        self.spectator_delay = spectator_delay;
+    }
+
+    /// Returns the [`ForceField`] games in this lobby are played under.
+    pub fn force_field(&self) -> &ForceField {
+        &self.force_field
+    }
+
+    /// Sets the [`ForceField`] games in this lobby are played under.
+    pub fn set_force_field(&mut self, force_field: ForceField) {
+        self.force_field = force_field;
+    }
+
+    /// Returns the number of players on each team, `2` for 2v2 lobbies.
+    pub fn players_per_team(&self) -> usize {
+        self.players_per_team
+    }
+
+    /// Sets the number of players on each team, each controlling an equal share of the squad.
+    pub fn set_players_per_team(&mut self, players_per_team: usize) {
+        self.players_per_team = players_per_team.clamp(1, 2);
+    }
+
+    /// Returns the total seconds each team may spend planning, if limited.
+    pub fn think_time_bank(&self) -> Option<f32> {
+        self.think_time_bank
+    }
+
+    /// Limits the total seconds each team may spend planning, losing the game once exhausted.
+    pub fn set_think_time_bank(&mut self, think_time_bank: Option<f32>) {
+        self.think_time_bank = think_time_bank;
+    }
+
+    /// Returns the number of turns after which the game is decided on points, if limited.
+    pub fn max_turns(&self) -> Option<usize> {
+        self.max_turns
+    }
+
+    /// Limits the game to a number of turns, clamped to [`LobbySettings::MAX_TURNS_LIMIT`].
+    pub fn set_max_turns(&mut self, max_turns: Option<usize>) {
+        self.max_turns = max_turns.map(|max_turns| max_turns.clamp(1, Self::MAX_TURNS_LIMIT));
+    }
+
+    /// Returns the [`ArenaMap`] games in this lobby are played on, if not the default arena.
+    pub fn map(&self) -> Option<&ArenaMap> {
+        self.map.as_ref()
+    }
+
+    /// Sets the [`ArenaMap`] games in this lobby are played on, `None` for the default arena.
+    pub fn set_map(&mut self, map: Option<ArenaMap>) {
+        self.map = map;
+    }
+
+    /// Returns the [`Mutator`]s games in this lobby are played under.
+    pub fn mutators(&self) -> &[Mutator] {
+        &self.mutators
+    }
+
+    /// Turns a [`Mutator`] on or off, keeping them in the order of [`Mutator::ALL`].
+    pub fn set_mutator(&mut self, mutator: Mutator, enabled: bool) {
+        self.mutators = Mutator::ALL
+            .into_iter()
+            .filter(|other| {
+                if *other == mutator {
+                    enabled
+                } else {
+                    self.mutators.contains(other)
+                }
+            })
+            .collect();
+    }
+
+    /// Returns the [`BotDifficulty`] of the AI opponent in [`LobbySort::LocalAI`] lobbies.
+    pub fn bot_difficulty(&self) -> BotDifficulty {
+        self.bot_difficulty
+    }
+
+    /// Sets the [`BotDifficulty`] of the AI opponent in [`LobbySort::LocalAI`] lobbies.
+    pub fn set_bot_difficulty(&mut self, bot_difficulty: BotDifficulty) {
+        self.bot_difficulty = bot_difficulty;
+    }
+
+    /// Returns the seed the server issued for this level, which varies the built-in bot's aim so
+    /// that a replay only verifies for the session it was issued to. Zero if none was issued.
+    pub fn level_seed(&self) -> u64 {
+        self.level_seed
+    }
+
+    /// Sets the seed the server issued for this level.
+    pub fn set_level_seed(&mut self, level_seed: u64) {
+        self.level_seed = level_seed;
+    }
+
+    /// Determines if players warm up in a sandbox once the lobby fills, before the game begins.
+    pub fn warmup(&self) -> bool {
+        self.warmup
+    }
+
+    /// Sets whether players warm up in a sandbox once the lobby fills.
+    pub fn set_warmup(&mut self, warmup: bool) {
+        self.warmup = warmup;
+    }
+
+    /// Returns the [`GameMode`] games in this lobby are played in.
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    /// Sets the [`GameMode`] games in this lobby are played in.
+    pub fn set_mode(&mut self, mode: GameMode) {
+        self.mode = mode;
+    }
+
+    /// Returns the seconds every turn lasts.
+    pub fn turn_duration(&self) -> u64 {
+        self.turn_duration
+    }
+
+    /// Sets the seconds every turn lasts, clamped to the accepted range.
+    pub fn set_turn_duration(&mut self, turn_duration: u64) {
+        self.turn_duration = turn_duration.clamp(Self::MIN_TURN_DURATION, Self::MAX_TURN_DURATION);
+    }
+
+    /// Determines if the lobby is left out of the lobby list, joined by its ID only.
+    pub fn private(&self) -> bool {
+        self.private
+    }
+
+    /// Sets whether the lobby is left out of the lobby list.
+    pub fn set_private(&mut self, private: bool) {
+        self.private = private;
+    }
+
+    /// Returns the number of games of the series this lobby is part of.
+    pub fn best_of(&self) -> usize {
+        self.best_of
+    }
+
+    /// Sets the number of games of the series, rounded up to an odd number so it cannot tie on
+    /// wins.
+    pub fn set_best_of(&mut self, best_of: usize) {
+        self.best_of = best_of.clamp(1, Self::MAX_BEST_OF) | 1;
+    }
+
+    /// Returns the results of the games played earlier in the series.
+    pub fn series(&self) -> &[GameResult] {
+        &self.series
+    }
+
+    /// Adds the result of a game to the series, starting a fresh one if the last was over.
+    pub fn record_series_game(&mut self, result: GameResult) {
+        if self.series_over() {
+            self.series.clear();
+        }
+
+        self.series.push(result);
+    }
+
+    /// Returns the games won by Red and by Blue in the series so far.
+    pub fn series_wins(&self) -> (usize, usize) {
+        self.series
+            .iter()
+            .fold((0, 0), |(red, blue), result| match result {
+                GameResult::Win(Team::Red) => (red + 1, blue),
+                GameResult::Win(Team::Blue) => (red, blue + 1),
+                GameResult::Tie => (red, blue),
+            })
+    }
+
+    /// Returns the team which won the majority of the series, or the most games once all were
+    /// played, as ties count towards the length of the series.
+    pub fn series_winner(&self) -> Option<Team> {
+        let (red, blue) = self.series_wins();
+        let played_out = self.series.len() >= self.best_of;
+
+        if red * 2 > self.best_of || (played_out && red > blue) {
+            Some(Team::Red)
+        } else if blue * 2 > self.best_of || (played_out && blue > red) {
+            Some(Team::Blue)
+        } else {
+            None
+        }
+    }
+
+    /// Determines if the series is decided or all its games were played.
+    pub fn series_over(&self) -> bool {
+        self.series_winner().is_some() || self.series.len() >= self.best_of
+    }
+}
+
+/// A vote of a team to surrender, which passes once every player of the team has voted before it
+/// expires.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SurrenderVote {
+    /// Team voting to surrender.
+    pub team: Team,
+    /// Session IDs of the players who voted, the one who started the vote first.
+    pub voters: Vec<String>,
+    /// Server time at which the vote expires.
+    pub expires_at: f64,
+}
+
+impl SurrenderVote {
+    /// Seconds teammates have to confirm a surrender vote.
+    pub const DURATION: f64 = 15.0;
+
+    /// Seconds after a vote expires before the team may start another.
+    pub const COOLDOWN: f64 = 60.0;
+
+    /// Determines if the vote is still awaiting the confirmation of teammates.
+    pub fn open(&self, timestamp: f64) -> bool {
+        timestamp < self.expires_at
+    }
+
+    /// Determines if the team has to wait before starting another vote.
+    pub fn cooling_down(&self, timestamp: f64) -> bool {
+        timestamp < self.expires_at + Self::COOLDOWN
+    }
 }
 
 /// [`Lobby`] is a `struct` which contains all the information necessary for executing a game.
@@ -86,67 +506,242 @@ pub struct Lobby {
     pub first_heartbeat: f64,
     /// The [`Lobby`]s sort.
     pub settings: LobbySettings,
+    /// Time at which the warmup ends and the game begins, once the lobby filled.
+    #[serde(default)]
+    pub warmup_until: Option<f64>,
+    /// Session IDs the lobby's slots are held for, e.g. the pairing of a [`crate::Bracket`]
+    /// match; anyone may join if empty.
+    #[serde(default)]
+    pub reserved: Vec<String>,
+    /// Latest lines of the lobby chat, oldest first.
+    #[serde(default)]
+    pub chat: VecDeque<ChatLine>,
+    /// Latest surrender vote of each team, kept after expiring for its cooldown.
+    #[serde(default)]
+    pub surrender_votes: Vec<SurrenderVote>,
+    /// Glances at the game after each of its latest turns, oldest first, so that
+    /// [`Lobby::summary`] shows what spectators see rather than the live game.
+    #[serde(skip)]
+    glances: VecDeque<GameGlance>,
+    /// Number of accepted messages which changed the game, so that a turn resolved away from the
+    /// lobby is only applied if the game did not change in the meantime.
+    #[cfg(feature = "server")]
+    #[serde(skip)]
+    revision: u64,
+}
+
+/// The bugs and capture progress of a [`Game`] right after one of its turns.
+#[derive(Debug, Clone)]
+struct GameGlance {
+    turn: usize,
+    capture_progress: f32,
+    bugs: Vec<BugSummary>,
 }
 
 impl Lobby {
+    /// Number of chat lines kept by the lobby.
+    pub const CHAT_LINES: usize = 8;
+
+    /// Chat lines a player may say within [`Lobby::CHAT_RATE_SECONDS`].
+    pub const CHAT_RATE_LINES: usize = 3;
+
+    /// Window of the chat rate limit, see [`Lobby::CHAT_RATE_LINES`].
+    pub const CHAT_RATE_SECONDS: f64 = 5.0;
+
     /// Instantiates the [`Lobby`] `struct` with a given [`LobbySort`].
     pub fn new(settings: LobbySettings, first_heartbeat: f64) -> Lobby {
         // let mut rng = ChaCha8Rng::seed_from_u64(settings.seed);
 
+        let game = Game::with_settings(&settings);
+        let player_slots = Lobby::player_slots(&game, settings.players_per_team);
+
         Lobby {
-            game: Game::default(),
+            game,
             players: HashMap::new(),
-            player_slots: VecDeque::from([
-                Player::new(Team::Red, 0.0),
-                Player::new(Team::Blue, 0.0),
-            ]),
+            player_slots,
             first_heartbeat,
             settings,
+            warmup_until: None,
+            reserved: Vec::new(),
+            chat: VecDeque::new(),
+            surrender_votes: Vec::new(),
+            glances: VecDeque::new(),
+            #[cfg(feature = "server")]
+            revision: 0,
+        }
+    }
+
+    /// Splits each team's bugs between its players, alternating slots between teams.
+    fn player_slots(game: &Game, players_per_team: usize) -> VecDeque<Player> {
+        if players_per_team <= 1 {
+            return VecDeque::from([Player::new(Team::Red, 0), Player::new(Team::Blue, 1)]);
+        }
+
+        let squads = |team: Team| -> Vec<Vec<usize>> {
+            let bug_indices = game.team_bug_indices(team);
+            let squad_size = bug_indices.len().div_ceil(players_per_team).max(1);
+
+            bug_indices
+                .chunks(squad_size)
+                .map(|squad| squad.to_vec())
+                .collect()
+        };
+
+        squads(Team::Red)
+            .into_iter()
+            .zip(squads(Team::Blue))
+            .enumerate()
+            .flat_map(|(index, (red, blue))| {
+                [
+                    Player::with_squad(Team::Red, 2 * index, red),
+                    Player::with_squad(Team::Blue, 2 * index + 1, blue),
+                ]
+            })
+            .collect()
+    }
+
+    /// Rebuilds the [`Game`] from the lobby settings, as it is not part of the serialized lobby.
+    pub fn reset_game(&mut self) {
+        self.game = Game::with_settings(&self.settings);
+        self.glances.clear();
+    }
+
+    /// Resolves the game's next turn, remembering a glance at its outcome for as long as
+    /// spectators are kept from seeing it.
+    pub fn resolve_turn(&mut self, turn: Turn) {
+        self.game.resolve_turn(turn);
+
+        self.record_glance();
+    }
+
+    #[cfg(feature = "server")]
+    /// Replaces the game by a copy of it which resolved its next turn elsewhere, e.g. on a
+    /// blocking thread, remembering a glance like [`Lobby::resolve_turn`].
+    pub fn adopt_resolved(&mut self, game: Game) {
+        self.game = game;
+
+        self.record_glance();
+    }
+
+    #[cfg(feature = "server")]
+    /// Returns the lobby's revision, which changes with every accepted message that changes the
+    /// game.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    fn record_glance(&mut self) {
+        self.glances.push_back(self.glance());
+
+        while self.glances.len() > self.settings.spectator_delay + 1 {
+            self.glances.pop_front();
+        }
+    }
+
+    /// Glances at the live game.
+    fn glance(&self) -> GameGlance {
+        let bugs = [Team::Red, Team::Blue]
+            .into_iter()
+            .flat_map(|team| self.game.team_bug_indices(team))
+            .filter_map(|bug_index| self.game.get_bug(bug_index))
+            .map(|(rigid_body, bug_data)| BugSummary {
+                sort: *bug_data.sort(),
+                team: *bug_data.team(),
+                health: bug_data.health().min(u8::MAX as usize) as u8,
+                position: [
+                    BugSummary::quantize(rigid_body.translation().x),
+                    BugSummary::quantize(rigid_body.translation().y),
+                ],
+            })
+            .collect();
+
+        GameGlance {
+            turn: self.game.turns_count(),
+            capture_progress: self.game.capture_progress(),
+            bugs,
         }
     }
 
     /// Determines if all players slots are taken.
-    pub fn all_ready(&self) -> bool {
+    pub fn all_joined(&self) -> bool {
         self.player_slots.is_empty()
     }
 
+    /// Determines if all player slots are taken by players who are ready, which starts the game.
+    pub fn all_ready(&self) -> bool {
+        self.all_joined() && self.players.values().all(|player| player.ready)
+    }
+
     #[cfg(feature = "server")]
     /// Includes a new session ID into the lobby, and assigns a player index to it.
     pub fn join_player(&mut self, session_id: String, timestamp: f64) -> Result<(), LobbyError> {
         if self.all_ready() {
-            Err(LobbyError("cannot join an active game".to_string()))
+            Err(LobbyError::new(
+                LobbyErrorKind::Full,
+                "cannot join an active game",
+            ))
         } else if self.players.contains_key(&session_id) {
-            Err(LobbyError("already in lobby".to_string()))
+            Err(LobbyError::new(
+                LobbyErrorKind::AlreadyJoined,
+                "already in lobby",
+            ))
+        } else if !self.reserved.is_empty() && !self.reserved.contains(&session_id) {
+            Err(LobbyError::new(
+                LobbyErrorKind::Full,
+                "lobby is reserved for other players",
+            ))
         } else if let Some(mut player) = self.player_slots.pop_front() {
             player.last_heartbeat = timestamp;
+            player.nickname = Nickname::from_session(&session_id);
 
             self.players.insert(session_id.clone(), player);
 
             Ok(())
         } else {
-            Err(LobbyError("no available slots in lobby".to_string()))
+            // Still in the waiting room, so the last slot was only just taken
+            Err(LobbyError::new(
+                LobbyErrorKind::SlotTaken,
+                "the last slot was just taken by another player",
+            ))
         }
     }
 
-    // #[cfg(feature = "server")]
-    // pub fn leave_player(&mut self, session_id: String) -> Result<String, LobbyError> {
-    //     if self.state == LobbyState::Finished {
-    //         Err(LobbyError("cannot leave a finished game".to_string()))
-    //     } else {
-    //         match self.players.remove(&session_id) {
-    //             Some(player) => {
-    //                 self.player_slots.push_back(player.index);
+    #[cfg(feature = "server")]
+    /// Takes a player out of the lobby at their request, unlike a player who stopped sending
+    /// heartbeats.
+    ///
+    /// Before the game starts their slot is freed for the next player to join. Afterwards they stop
+    /// counting as connected right away, so that the lobby closes as soon as everyone left.
+    fn leave_player(&mut self, session_id: &str) -> Result<(), LobbyError> {
+        if self.all_ready() {
+            let player = self
+                .players
+                .get_mut(session_id)
+                .ok_or_else(|| LobbyError::new(LobbyErrorKind::NotJoined, "player not in lobby"))?;
 
-    //                 self.players.remove(&session_id);
+            player.last_heartbeat = 0.0;
+        } else {
+            let player = self
+                .players
+                .remove(session_id)
+                .ok_or_else(|| LobbyError::new(LobbyErrorKind::NotJoined, "player not in lobby"))?;
 
-    //                 self.tick();
+            // The seat goes to the next player to join, who must not inherit this one's chat
+            self.chat.retain(|line| line.seat != player.seat);
 
-    //                 Ok(session_id)
-    //             }
-    //             None => Err(LobbyError("player not in lobby".to_string())),
-    //         }
-    //     }
-    // }
+            for other in self.players.values_mut() {
+                other.muted.retain(|seat| *seat != player.seat);
+            }
+
+            self.player_slots.push_front(Player::with_squad(
+                player.team,
+                player.seat,
+                player.squad,
+            ));
+        }
+
+        Ok(())
+    }
 
     #[cfg(feature = "server")]
     /// Executes a certain [`Message`] for the player.
@@ -162,27 +757,316 @@ impl Lobby {
             since_the_epoch.as_secs_f64()
         }
 
+        // Readiness and chat are handled whether the game started or not
+        let message = match message {
+            Message::Ready(ready) => return self.set_ready(&session_id, ready, timestamp()),
+            Message::Chat(content) => return self.say(&session_id, content, timestamp()),
+            Message::Mute(muted) => return self.mute(&session_id, muted),
+            Message::Leave => return self.leave_player(&session_id),
+            message => message,
+        };
+
         if !self.all_ready() {
-            Err(LobbyError("game not yet started".to_string()))
+            Err(LobbyError::new(
+                LobbyErrorKind::NotStarted,
+                "game not yet started",
+            ))
+        } else if self.warming_up(timestamp()) {
+            Err(LobbyError::new(
+                LobbyErrorKind::NotStarted,
+                "players are warming up",
+            ))
         } else {
+            let think_time = (timestamp() - self.last_beat()) as f32;
+            let revealing = self.revealing(timestamp());
+            let turn_index = self.game.turns_count();
+
+            match &message {
+                Message::Commit(TurnCommitment { index, .. })
+                | Message::Reveal(Turn { index, .. }, _)
+                    if *index != turn_index =>
+                {
+                    return Err(LobbyError::new(
+                        LobbyErrorKind::InvalidTurn,
+                        "move is not for the current turn",
+                    ));
+                }
+                Message::Commit(_) if revealing => {
+                    return Err(LobbyError::new(
+                        LobbyErrorKind::InvalidTurn,
+                        "commitments for this turn are closed",
+                    ));
+                }
+                Message::Reveal(..) if !revealing => {
+                    return Err(LobbyError::new(
+                        LobbyErrorKind::NotRevealing,
+                        "reveals for this turn are not open yet",
+                    ));
+                }
+                // Plain moves would leak their intents before the turn resolves
+                Message::Move(_) => {
+                    return Err(LobbyError::new(
+                        LobbyErrorKind::InvalidTurn,
+                        "moves must be committed and revealed",
+                    ));
+                }
+                Message::Surrender => return self.vote_surrender(session_id, timestamp()),
+                _ => (),
+            }
+
             match self.players.get_mut(&session_id) {
                 Some(player) => {
-                    self.game.act_player(player, message);
-
                     player.last_heartbeat = timestamp();
 
+                    match message {
+                        Message::Commit(commitment) => {
+                            // A resent commitment is accepted again, a different one replaces
+                            // it as the player plans more bugs. Commitments close once reveals
+                            // open, so nobody picks their move after others revealed theirs
+                            if player.commitment == Some(commitment) {
+                                return Ok(());
+                            }
+
+                            player.commitment = Some(commitment);
+                            player.revealed = false;
+
+                            self.game.record_think_time(player.team, think_time);
+                        }
+                        Message::Reveal(turn, salt) => {
+                            // The commitment is kept so a resent reveal is accepted again
+                            if player.commitment != Some(turn.commitment(salt)) {
+                                return Err(LobbyError::new(
+                                    LobbyErrorKind::InvalidTurn,
+                                    "move does not match the commitment",
+                                ));
+                            } else if player.revealed {
+                                return Ok(());
+                            }
+
+                            player.revealed = true;
+                            self.game.act_player(player, Message::Move(turn));
+                        }
+                        // Nothing else is played by the player
+                        _ => return Ok(()),
+                    }
+
+                    // Only accepted messages that change the game invalidate turns resolved away
+                    // from the lobby, so that nobody can hold back a turn by spamming messages
+                    self.revision += 1;
+
                     Ok(())
                 }
-                None => Err(LobbyError("player not in lobby".to_string())),
+                None => Err(LobbyError::new(
+                    LobbyErrorKind::NotJoined,
+                    "player not in lobby",
+                )),
+            }
+        }
+    }
+
+    /// Determines if the current turn accepts reveals rather than commitments at the given time,
+    /// see [`TurnCommitment::REVEAL_SECONDS`].
+    pub fn revealing(&self, timestamp: f64) -> bool {
+        timestamp
+            >= self.last_beat() + self.game.turn_duration() as f64 - TurnCommitment::REVEAL_SECONDS
+    }
+
+    #[cfg(feature = "server")]
+    /// Marks a player ready or not, starting the game once every slot is taken by a ready player.
+    fn set_ready(
+        &mut self,
+        session_id: &str,
+        ready: bool,
+        timestamp: f64,
+    ) -> Result<(), LobbyError> {
+        // Readiness no longer matters once the game started
+        if self.all_ready() {
+            return Ok(());
+        }
+
+        let Some(player) = self.players.get_mut(session_id) else {
+            return Err(LobbyError::new(
+                LobbyErrorKind::NotJoined,
+                "player not in lobby",
+            ));
+        };
+
+        player.ready = ready;
+        player.last_heartbeat = timestamp;
+
+        if self.all_ready() {
+            // The first turn is timed from the moment everyone is ready
+            self.first_heartbeat = timestamp;
+
+            if self.settings.warmup() {
+                self.warmup_until = Some(timestamp + LobbySettings::WARMUP_SECONDS);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "server")]
+    /// Adds a line to the lobby chat, dropping the oldest beyond [`Lobby::CHAT_LINES`].
+    ///
+    /// Players may say up to [`Lobby::CHAT_RATE_LINES`] lines within
+    /// [`Lobby::CHAT_RATE_SECONDS`], emotes included. Free text is expected to have passed the
+    /// server's filter already.
+    fn say(
+        &mut self,
+        session_id: &str,
+        content: ChatContent,
+        timestamp: f64,
+    ) -> Result<(), LobbyError> {
+        let Some(player) = self.players.get_mut(session_id) else {
+            return Err(LobbyError::new(
+                LobbyErrorKind::NotJoined,
+                "player not in lobby",
+            ));
+        };
+
+        player.last_heartbeat = timestamp;
+
+        player
+            .said_at
+            .retain(|said_at| timestamp - said_at < Self::CHAT_RATE_SECONDS);
+
+        if player.said_at.len() >= Self::CHAT_RATE_LINES {
+            return Err(LobbyError::new(
+                LobbyErrorKind::RateLimited,
+                "chatting too fast, wait a moment",
+            ));
+        }
+
+        if let Some(content) = content.sanitized() {
+            player.said_at.push(timestamp);
+
+            self.chat.push_back(ChatLine {
+                seat: player.seat,
+                team: player.team,
+                content,
+                timestamp,
+            });
+
+            while self.chat.len() > Self::CHAT_LINES {
+                self.chat.pop_front();
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "server")]
+    /// Replaces the [`Player::seat`]s a player muted, keeping only those of the other players.
+    fn mute(&mut self, session_id: &str, mut muted: Vec<usize>) -> Result<(), LobbyError> {
+        let Some(seat) = self.players.get(session_id).map(|player| player.seat) else {
+            return Err(LobbyError::new(
+                LobbyErrorKind::NotJoined,
+                "player not in lobby",
+            ));
+        };
+
+        let seats: Vec<usize> = self
+            .players
+            .values()
+            .map(|player| player.seat)
+            .filter(|other| *other != seat)
+            .collect();
+
+        muted.retain(|seat| seats.contains(seat));
+        muted.sort();
+        muted.dedup();
+
+        if let Some(player) = self.players.get_mut(session_id) {
+            player.muted = muted;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "server")]
+    /// Records that a player is still connected, e.g. while polling the lobby.
+    pub fn heartbeat(&mut self, session_id: &str, timestamp: f64) {
+        if let Some(player) = self.players.get_mut(session_id) {
+            player.last_heartbeat = timestamp;
+        }
+    }
+
+    #[cfg(feature = "server")]
+    /// Starts or joins the surrender vote of a player's team in team games, surrendering once
+    /// every player of the team has voted.
+    fn vote_surrender(&mut self, session_id: String, timestamp: f64) -> Result<(), LobbyError> {
+        let Some(team) = self.players.get(&session_id).map(|player| player.team) else {
+            return Err(LobbyError::new(
+                LobbyErrorKind::NotJoined,
+                "player not in lobby",
+            ));
+        };
+
+        if self.settings.players_per_team <= 1 || self.finished() {
+            return Err(LobbyError::new(
+                LobbyErrorKind::SurrenderUnavailable,
+                "surrender votes are for team games in progress",
+            ));
+        }
+
+        let position = self
+            .surrender_votes
+            .iter()
+            .position(|vote| vote.team == team);
+
+        match position.map(|position| &mut self.surrender_votes[position]) {
+            Some(vote) if vote.open(timestamp) => {
+                if !vote.voters.contains(&session_id) {
+                    vote.voters.push(session_id);
+                }
+            }
+            Some(vote) if vote.cooling_down(timestamp) => {
+                return Err(LobbyError::new(
+                    LobbyErrorKind::SurrenderUnavailable,
+                    "surrender vote on cooldown",
+                ));
+            }
+            _ => {
+                self.surrender_votes.retain(|vote| vote.team != team);
+                self.surrender_votes.push(SurrenderVote {
+                    team,
+                    voters: vec![session_id],
+                    expires_at: timestamp + SurrenderVote::DURATION,
+                });
             }
         }
+
+        let team_size = self
+            .players
+            .values()
+            .filter(|player| player.team == team)
+            .count();
+
+        if self
+            .surrender_vote(team)
+            .is_some_and(|vote| vote.voters.len() >= team_size)
+        {
+            self.game.surrender(team);
+            self.revision += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the latest surrender vote of a [`Team`], open or cooling down.
+    pub fn surrender_vote(&self, team: Team) -> Option<&SurrenderVote> {
+        self.surrender_votes.iter().find(|vote| vote.team == team)
     }
 
     #[cfg(feature = "server")]
     /// Requests a rematch for the active game.
     pub fn request_rematch(&mut self, session_id: String) -> Result<bool, LobbyError> {
         if !self.all_ready() {
-            Err(LobbyError("game not yet started".to_string()))
+            Err(LobbyError::new(
+                LobbyErrorKind::NotStarted,
+                "game not yet started",
+            ))
         } else {
             match self.players.get_mut(&session_id) {
                 Some(player) => {
@@ -193,7 +1077,10 @@ impl Lobby {
                         .values()
                         .fold(true, |acc, player| acc & player.rematch))
                 }
-                None => Err(LobbyError("player not in lobby".to_string())),
+                None => Err(LobbyError::new(
+                    LobbyErrorKind::NotJoined,
+                    "player not in lobby",
+                )),
             }
         }
         // else if !self.finished() {
@@ -229,11 +1116,92 @@ impl Lobby {
         }
     }
 
+    /// Determines whether the given session ID is only watching this lobby.
+    pub fn is_spectator(&self, session_id: Option<&String>) -> bool {
+        !self.has_session_id(session_id)
+    }
+
+    /// Returns the [`Turn`]s skipping the first `since` turns, withholding the most recent ones from spectators.
+    ///
+    /// At most [`MAX_QUEUED_TURNS`] are returned at once, so that catching up never
+    /// overflows a client's queue.
+    pub fn visible_turns_since(&self, session_id: Option<&String>, since: usize) -> Vec<Turn> {
+        let visible = self.visible_turns_count(session_id);
+
+        self.game
+            .turns_since(since)
+            .into_iter()
+            .take(visible.saturating_sub(since).min(MAX_QUEUED_TURNS))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the lobby as sent to the given session ID, hiding the session IDs of every other
+    /// player so they can't be used to pass as a player, e.g. to skip the spectator delay.
+    ///
+    /// Whom the other players muted is hidden as well, and the chat lines of the players the
+    /// session muted are left out.
+    pub fn view_for(&self, session_id: Option<&String>) -> Lobby {
+        let mut lobby = self.clone();
+
+        lobby.players = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(index, (player_session_id, player))| {
+                if Some(player_session_id) == session_id {
+                    (player_session_id.clone(), player.clone())
+                } else {
+                    let player = Player {
+                        muted: Vec::new(),
+                        ..player.clone()
+                    };
+
+                    (format!("player {index}"), player)
+                }
+            })
+            .collect();
+
+        if let Some(player) = session_id.and_then(|session_id| self.players.get(session_id)) {
+            lobby.chat.retain(|line| !player.muted.contains(&line.seat));
+        }
+
+        lobby
+    }
+
+    /// Returns the number of turns visible to a session, withholding the most recent ones from spectators.
+    pub fn visible_turns_count(&self, session_id: Option<&String>) -> usize {
+        if self.is_spectator(session_id) {
+            self.game
+                .turns_count()
+                .saturating_sub(self.settings.spectator_delay)
+        } else {
+            self.game.turns_count()
+        }
+    }
+
     /// Returns the players.
     pub fn players(&self) -> &HashMap<String, Player> {
         &self.players
     }
 
+    /// Returns the [`Nickname`]s of the players, sorted alphabetically.
+    pub fn nicknames(&self) -> Vec<String> {
+        let mut nicknames: Vec<String> = self
+            .players
+            .values()
+            .map(|player| player.nickname.to_string())
+            .collect();
+        nicknames.sort();
+
+        nicknames
+    }
+
+    /// Returns the number of open player slots.
+    pub fn open_slots(&self) -> usize {
+        self.player_slots.len()
+    }
+
     /// turns
     pub fn turns(&self) -> &Vec<Turn> {
         &self.game.turns()
@@ -243,7 +1211,26 @@ impl Lobby {
     pub fn any_connected(&self, timestamp: f64) -> bool {
         self.players
             .iter()
-            .any(|(_, player)| timestamp - player.last_heartbeat < 15.0)
+            .any(|(_, player)| player.connected(timestamp))
+    }
+
+    /// Returns the seconds since any player last sent a heartbeat, infinite without players.
+    pub fn idle_seconds(&self, timestamp: f64) -> f64 {
+        timestamp
+            - self
+                .players
+                .values()
+                .map(|player| player.last_heartbeat)
+                .fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    /// Returns the most recent heartbeat of any player, which clients compare the others against
+    /// as they lack the server's clock.
+    pub fn latest_heartbeat(&self) -> f64 {
+        self.players
+            .values()
+            .map(|player| player.last_heartbeat)
+            .fold(self.first_heartbeat, f64::max)
     }
 
     /// last bewat
@@ -251,11 +1238,118 @@ impl Lobby {
         if let Some(turn) = self.game.last_turn() {
             turn.timestamp
         } else {
-            self.first_heartbeat
+            // The first turn is timed from the end of the warmup
+            self.warmup_until
+                .map_or(self.first_heartbeat, |warmup_until| {
+                    warmup_until.max(self.first_heartbeat)
+                })
+        }
+    }
+
+    /// Determines if the players are still warming up at the given time.
+    pub fn warming_up(&self, timestamp: f64) -> bool {
+        self.warmup_until
+            .is_some_and(|warmup_until| timestamp < warmup_until)
+    }
+
+    /// Summarises the [`Lobby`]'s game for previews in the lobby browser.
+    ///
+    /// The listing is public, so the game is summarised as spectators see it, turns withheld by
+    /// the spectator delay included. Bugs are left out when no glance at that turn is kept, e.g.
+    /// after the lobby was reloaded from disk.
+    pub fn summary(&self) -> LobbySummary {
+        let turn = self.visible_turns_count(None);
+
+        let glance = if turn == self.game.turns_count() {
+            Some(self.glance())
+        } else {
+            self.glances
+                .iter()
+                .find(|glance| glance.turn == turn)
+                .cloned()
+        };
+
+        let (capture_progress, bugs) = match glance {
+            Some(glance) => (glance.capture_progress, glance.bugs),
+            None => (0.0, Vec::new()),
+        };
+
+        LobbySummary {
+            players: self.players.len(),
+            names: self.nicknames(),
+            players_per_team: self.settings.players_per_team(),
+            created_at: self.first_heartbeat,
+            custom_map: self.settings.map().is_some(),
+            mutators: self.settings.mutators().to_vec(),
+            turn,
+            max_turns: self.game.max_turns(),
+            capture_progress,
+            bugs,
+            region: String::new(),
         }
     }
 }
 
+/// A lightweight glance at a [`Lobby`] and its game, listed in [`Message::Lobbies`] in place of
+/// the full lobby.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LobbySummary {
+    /// Number of players who joined.
+    pub players: usize,
+    /// [`Nickname`]s of the players who joined, sorted alphabetically.
+    #[serde(default)]
+    pub names: Vec<String>,
+    /// Players on each team once the lobby is full.
+    pub players_per_team: usize,
+    /// Timestamp of the lobby's creation.
+    pub created_at: f64,
+    /// Whether the lobby plays on a shared [`crate::ArenaMap`] rather than the default arena.
+    pub custom_map: bool,
+    /// [`Mutator`]s the lobby is played under.
+    #[serde(default)]
+    pub mutators: Vec<Mutator>,
+    /// Number of turns played.
+    pub turn: usize,
+    /// Turn limit of the game, if any.
+    pub max_turns: Option<usize>,
+    /// Capture progress, see [`Game::capture_progress`].
+    pub capture_progress: f32,
+    /// All bugs, ordered by their index.
+    pub bugs: Vec<BugSummary>,
+    /// Region of the server hosting the lobby, filled in by the server.
+    #[serde(default)]
+    pub region: String,
+}
+
+/// A bug of a [`LobbySummary`], its position quantized to a byte per axis.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct BugSummary {
+    /// Sort of the bug.
+    pub sort: BugSort,
+    /// Team of the bug.
+    pub team: Team,
+    /// Health of the bug.
+    pub health: u8,
+    /// Quantized position of the bug.
+    pub position: [u8; 2],
+}
+
+impl BugSummary {
+    /// Extent of either axis covered by quantized positions, centred on the arena.
+    const EXTENT: f32 = 25.6;
+
+    fn quantize(coordinate: f32) -> u8 {
+        ((coordinate / Self::EXTENT + 0.5) * 256.0).clamp(0.0, 255.0) as u8
+    }
+
+    /// Position of the bug in arena units, accurate to a tenth of a unit.
+    pub fn position(&self) -> Vector2<f32> {
+        let dequantize = |byte: u8| (byte as f32 + 0.5) / 256.0 * Self::EXTENT - Self::EXTENT / 2.0;
+
+        vector![dequantize(self.position[0]), dequantize(self.position[1])]
+    }
+}
+
 /// Loadout methods.
 #[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Default)]
 pub enum LobbySort {
@@ -0,0 +1,64 @@
+use nalgebra::Vector2;
+
+/// Dimensions of an arena and how its local units map onto the screen, owned by the [`crate::Game`]
+/// so maps of any size are simulated and drawn consistently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArenaGeometry {
+    /// Width of the walled arena, in local units.
+    pub width: f32,
+    /// Height of the walled arena, in local units.
+    pub height: f32,
+    /// Radius of the capture zone around the arena centre, in local units.
+    pub capture_radius: f32,
+    /// Screen pixels per local unit.
+    pub scale: f32,
+    /// Width of the view the arena is centred in, in pixels.
+    pub view_width: f32,
+    /// Height of the view the arena is centred in, in pixels.
+    pub view_height: f32,
+}
+
+impl Default for ArenaGeometry {
+    fn default() -> Self {
+        ArenaGeometry {
+            width: 23.0,
+            height: 23.0,
+            capture_radius: 4.0,
+            scale: 16.0,
+            view_width: 384.0,
+            view_height: 360.0,
+        }
+    }
+}
+
+impl ArenaGeometry {
+    /// Screen position of the arena centre.
+    pub fn view_centre(&self) -> (f64, f64) {
+        (self.view_width as f64 / 2.0, self.view_height as f64 / 2.0)
+    }
+
+    /// Converts a position in local units to screen pixels.
+    pub fn local_to_screen(&self, local: &Vector2<f32>) -> (f64, f64) {
+        let (cx, cy) = self.view_centre();
+
+        (
+            local.x as f64 * self.scale as f64 + cx,
+            local.y as f64 * self.scale as f64 + cy,
+        )
+    }
+
+    /// Converts a position in screen pixels to local units.
+    pub fn screen_to_local(&self, screen: (f64, f64)) -> (f64, f64) {
+        let (cx, cy) = self.view_centre();
+
+        (
+            (screen.0 - cx) / self.scale as f64,
+            (screen.1 - cy) / self.scale as f64,
+        )
+    }
+
+    /// Converts a length in local units to screen pixels.
+    pub fn to_screen_length(&self, length: f32) -> f64 {
+        length as f64 * self.scale as f64
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+
+use nalgebra::{Rotation2, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::{BugSort, Game, LobbySettings, Team, VecMap};
+
+/// A read-only snapshot of a single bug, as seen by a [`BotController`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BugView {
+    /// Index of the bug, used as the key of the returned impulse intents.
+    pub index: usize,
+    /// The bug's [`BugSort`].
+    pub sort: BugSort,
+    /// The bug's [`Team`].
+    pub team: Team,
+    /// Remaining health.
+    pub health: usize,
+    /// Remaining stamina, capping the magnitude of the next impulse.
+    pub stamina: f32,
+    /// Position in arena units.
+    pub position: Vector2<f32>,
+    /// Linear velocity in arena units per second.
+    pub velocity: Vector2<f32>,
+}
+
+/// A read-only snapshot of a [`Game`] handed to [`BotController`]s.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GameView {
+    /// The team the bot is playing for.
+    pub team: Team,
+    /// Number of executed turns.
+    pub turn: usize,
+    /// Radius of the capture zone around the arena centre.
+    pub capture_radius: f32,
+    /// Capture progress, positive for red and negative for blue.
+    pub capture_progress: f32,
+    /// All bugs in the arena.
+    pub bugs: Vec<BugView>,
+    /// Positions of all props in the arena.
+    pub props: Vec<Vector2<f32>>,
+}
+
+impl GameView {
+    /// Creates a [`GameView`] of the given [`Game`] for a [`Team`].
+    pub fn new(game: &Game, team: Team) -> GameView {
+        GameView {
+            team,
+            turn: game.turns_count(),
+            capture_radius: game.capture_radius(),
+            capture_progress: game.capture_progress(),
+            bugs: game
+                .iter_bugs()
+                .map(|(rigid_body, bug_data)| BugView {
+                    index: rigid_body.user_data as usize,
+                    sort: *bug_data.sort(),
+                    team: *bug_data.team(),
+                    health: bug_data.health(),
+                    stamina: bug_data.stamina(),
+                    position: *rigid_body.translation(),
+                    velocity: *rigid_body.linvel(),
+                })
+                .collect(),
+            props: game
+                .iter_props()
+                .map(|(collider, _)| *collider.translation())
+                .collect(),
+        }
+    }
+
+    /// Returns the bugs of the bot's own team which can still act.
+    pub fn own_bugs(&self) -> impl Iterator<Item = &BugView> {
+        self.bugs
+            .iter()
+            .filter(move |bug| bug.team == self.team && bug.health > 1)
+    }
+}
+
+/// Plans the impulse intents for one [`Team`] each turn.
+pub trait BotController {
+    /// Returns the impulse intents keyed by bug index. Intents for bugs of other teams are ignored.
+    fn plan(&mut self, view: &GameView) -> VecMap<usize, Vector2<f32>>;
+}
+
+/// Strongest impulse the built-in bot gives.
+const BOT_MAX_STRENGTH: f32 = 4.0;
+
+/// Largest angle in radians by which a [`BotDifficulty::Easy`] bot misaims.
+const EASY_MAX_MISAIM: f32 = 0.3;
+
+/// Largest angle in radians by which a level seed turns the built-in bot's flicks.
+const LEVEL_SEED_MAX_TURN: f32 = 0.05;
+
+/// How well the built-in bot plays.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Copy, Clone, Default)]
+pub enum BotDifficulty {
+    /// Moves only half of its bugs, with weaker and less accurate flicks.
+    Easy,
+    /// Moves every bug by its [`Personality`].
+    #[default]
+    Normal,
+    /// Moves every bug by its [`Personality`], with stronger flicks.
+    Hard,
+}
+
+impl BotDifficulty {
+    /// All difficulties, from the easiest.
+    pub const ALL: [BotDifficulty; 3] = [
+        BotDifficulty::Easy,
+        BotDifficulty::Normal,
+        BotDifficulty::Hard,
+    ];
+
+    /// Name of the difficulty for menus.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BotDifficulty::Easy => "Easy",
+            BotDifficulty::Normal => "Normal",
+            BotDifficulty::Hard => "Hard",
+        }
+    }
+
+    /// Factor the strength of every flick is scaled by.
+    fn strength(&self) -> f32 {
+        match self {
+            BotDifficulty::Easy => 0.7,
+            BotDifficulty::Normal => 1.0,
+            BotDifficulty::Hard => 1.25,
+        }
+    }
+}
+
+/// How much a bug controlled by the built-in bot favours each of its candidate moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PersonalityWeights {
+    /// Weight of heading into the capture zone.
+    pub zone: f32,
+    /// Weight of charging the nearest rival bug.
+    pub attack: f32,
+    /// Weight of stepping between an ally and the rival bug closest to it.
+    pub guard: f32,
+}
+
+/// Temperament of a bug controlled by the built-in bot, set per bug by an [`crate::ArenaMap`].
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Copy, Clone, Default)]
+pub enum Personality {
+    /// Heads for the capture zone and stays there.
+    #[default]
+    Camper,
+    /// Charges the nearest rival bug.
+    Rusher,
+    /// Body-blocks rival bugs closing in on its allies.
+    Protector,
+}
+
+impl Personality {
+    /// Returns the [`PersonalityWeights`] of the personality.
+    pub fn weights(&self) -> PersonalityWeights {
+        match self {
+            Personality::Camper => PersonalityWeights {
+                zone: 1.0,
+                attack: 0.0,
+                guard: 0.0,
+            },
+            Personality::Rusher => PersonalityWeights {
+                zone: 0.5,
+                attack: 1.0,
+                guard: 0.0,
+            },
+            Personality::Protector => PersonalityWeights {
+                zone: 0.5,
+                attack: 0.25,
+                guard: 1.0,
+            },
+        }
+    }
+}
+
+/// The built-in bot, scoring a few candidate moves for every bug by its [`Personality`] and
+/// flicking it towards the best one.
+#[derive(Debug, Default, Clone)]
+pub struct ZoneBot {
+    personalities: HashMap<usize, Personality>,
+    difficulty: BotDifficulty,
+    level_seed: u64,
+}
+
+impl ZoneBot {
+    /// Creates a bot giving bugs the personalities keyed by their index, [`Personality::Camper`]
+    /// for the rest.
+    pub fn new(personalities: HashMap<usize, Personality>) -> ZoneBot {
+        ZoneBot {
+            personalities,
+            difficulty: BotDifficulty::default(),
+            level_seed: 0,
+        }
+    }
+
+    /// Creates a bot with the personalities of the lobby's [`crate::ArenaMap`], if any, playing at
+    /// the lobby's [`BotDifficulty`] and aiming by its [`LobbySettings::level_seed`].
+    pub fn for_settings(settings: &LobbySettings) -> ZoneBot {
+        ZoneBot::new(
+            settings
+                .map()
+                .map(|map| map.personalities.iter().copied().collect())
+                .unwrap_or_default(),
+        )
+        .with_difficulty(settings.bot_difficulty())
+        .with_level_seed(settings.level_seed())
+    }
+
+    /// Sets the [`BotDifficulty`] the bot plays at.
+    pub fn with_difficulty(mut self, difficulty: BotDifficulty) -> ZoneBot {
+        self.difficulty = difficulty;
+        self
+    }
+
+    /// Sets the level seed turning the bot's flicks, see [`LobbySettings::level_seed`].
+    pub fn with_level_seed(mut self, level_seed: u64) -> ZoneBot {
+        self.level_seed = level_seed;
+        self
+    }
+
+    /// Angle by which the level seed turns a bug's flick in a turn, between
+    /// ±[`LEVEL_SEED_MAX_TURN`], or none without a seed.
+    fn level_turn(&self, turn: usize, bug_index: usize) -> f32 {
+        if self.level_seed == 0 {
+            return 0.0;
+        }
+
+        // SplitMix64, mixing the turn and bug into the seed
+        let mut hash = self.level_seed ^ ((turn as u64) << 32) ^ bug_index as u64;
+        hash = hash.wrapping_add(0x9e3779b97f4a7c15);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+        hash ^= hash >> 31;
+
+        ((hash >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0) * LEVEL_SEED_MAX_TURN
+    }
+
+    /// Scores the candidate moves of a bug, returning the score and target of the best and its
+    /// overshoot.
+    fn best_target(
+        view: &GameView,
+        bug: &BugView,
+        weights: PersonalityWeights,
+    ) -> Option<(f32, Vector2<f32>, f32)> {
+        let rivals: Vec<&BugView> = view
+            .bugs
+            .iter()
+            .filter(|other| other.team != view.team && other.health > 1)
+            .collect();
+
+        let nearest_rival = |position: Vector2<f32>| {
+            rivals.iter().copied().min_by(|a, b| {
+                (a.position - position)
+                    .magnitude()
+                    .total_cmp(&(b.position - position).magnitude())
+            })
+        };
+
+        let mut candidates: Vec<(f32, Vector2<f32>, f32)> = Vec::new();
+
+        let from_centre = bug.position.magnitude();
+
+        if from_centre > view.capture_radius * 0.5 {
+            candidates.push((
+                weights.zone * from_centre / view.capture_radius,
+                Vector2::zeros(),
+                0.0,
+            ));
+        }
+
+        if let Some(rival) = nearest_rival(bug.position) {
+            let distance = (rival.position - bug.position).magnitude();
+
+            candidates.push((
+                weights.attack * (1.0 + 4.0 / (1.0 + distance)),
+                rival.position,
+                1.0,
+            ));
+        }
+
+        for ally in view.own_bugs().filter(|ally| ally.index != bug.index) {
+            if let Some(rival) = nearest_rival(ally.position) {
+                let threat = 4.0 / (1.0 + (rival.position - ally.position).magnitude());
+
+                candidates.push((
+                    weights.guard * threat,
+                    (ally.position + rival.position) / 2.0,
+                    0.0,
+                ));
+            }
+        }
+
+        candidates
+            .into_iter()
+            .filter(|(score, target, _)| *score > 0.0 && (target - bug.position).magnitude() > 0.01)
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    /// Scores the best candidate move of each of the bot's bugs, returning their scores and
+    /// impulse intents.
+    fn scored_moves(&self, view: &GameView) -> Vec<(f32, usize, Vector2<f32>)> {
+        view.own_bugs()
+            .filter_map(|bug| {
+                let personality = self
+                    .personalities
+                    .get(&bug.index)
+                    .copied()
+                    .unwrap_or_default();
+                let (score, target, overshoot) =
+                    ZoneBot::best_target(view, bug, personality.weights())?;
+
+                let towards = target - bug.position;
+                let strength = (towards.magnitude() * 0.5 + overshoot).min(BOT_MAX_STRENGTH);
+
+                Some((score, bug.index, towards.normalize() * strength))
+            })
+            .collect()
+    }
+
+    /// Suggests moves for the team of the [`GameView`], keeping only the `budget` most valuable
+    /// ones so a player learning the game is not shown a move for every bug.
+    pub fn suggest(&self, view: &GameView, budget: usize) -> VecMap<usize, Vector2<f32>> {
+        let mut moves = self.scored_moves(view);
+
+        moves.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        moves
+            .into_iter()
+            .take(budget)
+            .map(|(_, bug_index, impulse_intent)| (bug_index, impulse_intent))
+            .collect()
+    }
+}
+
+impl BotController for ZoneBot {
+    fn plan(&mut self, view: &GameView) -> VecMap<usize, Vector2<f32>> {
+        let mut moves = self.scored_moves(view);
+
+        if self.difficulty == BotDifficulty::Easy {
+            moves.sort_by(|a, b| b.0.total_cmp(&a.0));
+            moves.truncate(moves.len().div_ceil(2));
+        }
+
+        moves
+            .into_iter()
+            .map(|(_, bug_index, impulse_intent)| {
+                let impulse_intent = Rotation2::new(self.level_turn(view.turn, bug_index))
+                    * impulse_intent
+                    * self.difficulty.strength();
+
+                if self.difficulty != BotDifficulty::Easy {
+                    return (bug_index, impulse_intent);
+                }
+
+                // Misaimed by the turn and bug alone, so replays of the game plan alike
+                let misaim = ((view.turn * 7 + bug_index * 13) % 5) as f32 / 2.0 - 1.0;
+
+                (
+                    bug_index,
+                    Rotation2::new(misaim * EASY_MAX_MISAIM) * impulse_intent,
+                )
+            })
+            .collect()
+    }
+}
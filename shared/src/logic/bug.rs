@@ -1,7 +1,27 @@
 use nalgebra::{vector, Vector2};
 use serde::{Deserialize, Serialize};
 
-use crate::Team;
+use crate::{balance, Team};
+
+/// Strongest impulse intent a bug can be given, further limited by its stamina.
+pub const MAX_IMPULSE_INTENT: f32 = 4.0;
+
+/// Speed above which a bug turns its shell to face where it is heading.
+const FACING_SPEED: f32 = 0.5;
+
+/// Cosine of the half-angle of the arcs counting as the front and the rear of a shell.
+const SHELL_ARC_COS: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Side of a bug's shell struck by an impact, relative to where it faces.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum ImpactSide {
+    /// Struck head-on, where the shell is thickest.
+    Front,
+    /// Struck from the side.
+    Flank,
+    /// Struck from behind, where the shell is weakest.
+    Rear,
+}
 
 /// Sort of a bug
 #[derive(PartialEq, Eq, Hash, Debug, Serialize, Deserialize, Copy, Clone, Default)]
@@ -15,13 +35,118 @@ pub enum BugSort {
     Ant,
 }
 impl BugSort {
-    fn max_health(&self) -> usize {
+    /// All sorts of bugs, in roster order.
+    pub const ALL: [BugSort; 3] = [BugSort::Beetle, BugSort::Ladybug, BugSort::Ant];
+
+    /// Mass of the bug's collider.
+    pub fn mass(&self) -> f32 {
+        balance().bug(self).mass
+    }
+
+    /// Restitution of the bug's collider, i.e. how bouncy it is.
+    pub fn restitution(&self) -> f32 {
+        balance().bug(self).restitution
+    }
+
+    /// Extra damage dealt on top of the base point when this bug is the attacker.
+    pub fn bonus_damage(&self) -> isize {
+        match self {
+            BugSort::Beetle => 0,
+            BugSort::Ladybug => 0,
+            BugSort::Ant => 1,
+        }
+    }
+
+    /// Health a bug of this sort starts with.
+    pub fn max_health(&self) -> usize {
         match self {
             BugSort::Beetle => 5,
             BugSort::Ladybug => 4,
             BugSort::Ant => 3,
         }
     }
+
+    /// Weight class, heavier bugs shrug off damage from lighter attackers.
+    pub fn weight_class(&self) -> u8 {
+        match self {
+            BugSort::Beetle => 3,
+            BugSort::Ladybug => 2,
+            BugSort::Ant => 1,
+        }
+    }
+
+    /// Fraction of knockback taken while braced (stationary), `1.0` if this sort cannot brace.
+    pub fn brace_knockback(&self) -> f32 {
+        match self {
+            BugSort::Beetle => 0.5,
+            BugSort::Ladybug => 1.0,
+            BugSort::Ant => 1.0,
+        }
+    }
+
+    /// Maximum stamina a bug of this sort can hold.
+    pub fn max_stamina(&self) -> f32 {
+        match self {
+            BugSort::Beetle => 8.0,
+            BugSort::Ladybug => 7.0,
+            BugSort::Ant => 6.0,
+        }
+    }
+
+    /// Stamina regenerated at the end of each turn.
+    pub fn stamina_regen(&self) -> f32 {
+        match self {
+            BugSort::Beetle => 2.5,
+            BugSort::Ladybug => 3.0,
+            BugSort::Ant => 3.5,
+        }
+    }
+}
+
+/// Titles a [`BugName`] is drawn from.
+const NAME_TITLES: [&str; 6] = ["Sgt.", "Lady", "Sir", "Cpl.", "Dr.", "Pvt."];
+
+/// Given names a [`BugName`] is drawn from, at least as many as bugs in a game so none repeat.
+const NAME_GIVEN: [&str; 16] = [
+    "Crumble", "Dot", "Nibbles", "Pip", "Bramble", "Fizz", "Thistle", "Moss", "Clover", "Pebble",
+    "Sprout", "Twig", "Acorn", "Button", "Fern", "Biscuit",
+];
+
+/// Nickname and number a bug keeps for the whole match, such as "Sgt. Crumble No. 3".
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Copy, Clone, Default)]
+pub struct BugName {
+    title: u8,
+    given: u8,
+    number: u8,
+}
+
+impl BugName {
+    /// Generates the name of the bug at the given index, numbered by its place in its team.
+    ///
+    /// Names only depend on their arguments so every client and the server agree on them.
+    pub fn generate(bug_index: usize, number: usize) -> BugName {
+        BugName {
+            title: ((bug_index * 5 + number) % NAME_TITLES.len()) as u8,
+            given: ((bug_index * 7) % NAME_GIVEN.len()) as u8,
+            number: number as u8,
+        }
+    }
+
+    /// Returns the number of the bug within its team, starting at 1.
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+}
+
+impl std::fmt::Display for BugName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            NAME_TITLES[self.title as usize % NAME_TITLES.len()],
+            NAME_GIVEN[self.given as usize % NAME_GIVEN.len()]
+        )
+    }
 }
 
 /// A bug
@@ -31,6 +156,11 @@ pub struct BugData {
     team: Team,
     impulse_intent: Vector2<f32>,
     health: usize,
+    stamina: f32,
+    #[serde(default)]
+    name: BugName,
+    #[serde(default)]
+    facing: Vector2<f32>,
 }
 
 impl BugData {
@@ -41,8 +171,22 @@ impl BugData {
             team,
             impulse_intent: Vector2::zeros(),
             health: sort.max_health(),
+            stamina: sort.max_stamina(),
+            name: BugName::default(),
+            facing: Vector2::zeros(),
         }
     }
+
+    /// Gives the bug a [`BugName`].
+    pub fn with_name(mut self, name: BugName) -> BugData {
+        self.name = name;
+        self
+    }
+
+    /// Returns the [`BugName`] of this [`Bug`].
+    pub fn name(&self) -> &BugName {
+        &self.name
+    }
     /// Returns the [`BugSort`] for this [`Bug`].
     pub fn sort(&self) -> &BugSort {
         &self.sort
@@ -60,7 +204,10 @@ impl BugData {
 
     /// TODO docs
     pub fn set_impulse_intent(&mut self, impulse_intent: Vector2<f32>) {
-        let magnitude = impulse_intent.magnitude().min(4.0);
+        let magnitude = impulse_intent
+            .magnitude()
+            .min(MAX_IMPULSE_INTENT)
+            .min(self.stamina);
 
         self.impulse_intent = if impulse_intent.magnitude() > 0.05 {
             impulse_intent.normalize() * magnitude
@@ -80,8 +227,71 @@ impl BugData {
             (self.health as isize + delta).clamp(0, self.sort.max_health() as isize) as usize;
     }
 
+    /// Remaining stamina, spent on the magnitude of impulses.
+    pub fn stamina(&self) -> f32 {
+        self.stamina
+    }
+
+    /// Spends stamina for the current impulse intent.
+    pub fn spend_stamina(&mut self) {
+        self.stamina = (self.stamina - self.impulse_intent.magnitude()).max(0.0);
+    }
+
+    /// Regenerates stamina at the end of a turn.
+    pub fn regen_stamina(&mut self) {
+        self.stamina = (self.stamina + self.sort.stamina_regen()).min(self.sort.max_stamina());
+    }
+
     /// TODO docs
     pub fn reset_impulse_intent(&mut self) {
         self.impulse_intent = Vector2::zeros();
     }
+
+    /// Unit vector of where the bug's shell faces, zero until it first moves.
+    pub fn facing(&self) -> &Vector2<f32> {
+        &self.facing
+    }
+
+    /// Turns the shell towards a heading, unless the bug is too slow to turn.
+    pub fn face(&mut self, heading: &Vector2<f32>) {
+        if heading.magnitude() > FACING_SPEED {
+            self.facing = heading.normalize();
+        }
+    }
+
+    /// Returns the side of the shell an impact coming from `direction`, relative to the bug,
+    /// strikes. Bugs which have yet to face anywhere are struck on the flank.
+    pub fn impact_side(&self, direction: &Vector2<f32>) -> ImpactSide {
+        if self.facing == Vector2::zeros() || direction.magnitude() == 0.0 {
+            return ImpactSide::Flank;
+        }
+
+        let alignment = self.facing.dot(&direction.normalize());
+
+        if alignment >= SHELL_ARC_COS {
+            ImpactSide::Front
+        } else if alignment <= -SHELL_ARC_COS {
+            ImpactSide::Rear
+        } else {
+            ImpactSide::Flank
+        }
+    }
+}
+
+/// Turns a [`Corpse`] stays in the arena before it is cleared.
+pub const CORPSE_TURNS: usize = 3;
+
+/// Decal left where a bug was knocked out, purely visual: it has no collider and no health.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+pub struct Corpse {
+    /// Position the bug was knocked out at.
+    pub position: Vector2<f32>,
+    /// Sort of the knocked out bug.
+    pub sort: BugSort,
+    /// Team of the knocked out bug.
+    pub team: Team,
+    /// Game tick at which the bug was knocked out.
+    pub ticks: u64,
+    /// Number of executed turns when the bug was knocked out.
+    pub turn: usize,
 }
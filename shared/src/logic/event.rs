@@ -0,0 +1,86 @@
+use nalgebra::Point2;
+
+use crate::{GamePhase, Team};
+
+/// A one-shot occurrence in the simulation, emitted once by [`crate::Game`] for presentation.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum GameEvent {
+    /// Two opposing bugs collided hard enough to deal damage.
+    BugImpact {
+        /// Index of the faster, attacking bug
+        attacker: usize,
+        /// Index of the slower, defending bug
+        defender: usize,
+        /// Contact point of the impact
+        position: Point2<f32>,
+        /// Speed of the attacking bug
+        speed: f32,
+    },
+    /// A bug dropped to its last health point and was knocked out.
+    BugKnockedOut {
+        /// Index of the knocked out bug
+        bug: usize,
+        /// Index of the rival bug which struck the final blow, `None` if it was not a bug
+        attacker: Option<usize>,
+    },
+    /// A bug struck a prop hard enough to matter.
+    PropImpact {
+        /// Index of the bug
+        bug: usize,
+        /// Index of the prop
+        prop: usize,
+        /// Contact point of the impact
+        position: Point2<f32>,
+        /// Speed at which the bug and the prop closed in on each other
+        speed: f32,
+    },
+    /// A bug ran into one of the walls of the arena.
+    WallImpact {
+        /// Index of the bug
+        bug: usize,
+        /// Contact point of the impact
+        position: Point2<f32>,
+        /// Speed at which the bug closed in on the wall
+        speed: f32,
+    },
+    /// A prop shattered and was removed from the arena.
+    PropBreak {
+        /// Index of the prop
+        prop: usize,
+        /// Contact point of the final blow
+        position: Point2<f32>,
+    },
+    /// The capture zone was scored at the end of a turn.
+    ZoneScored {
+        /// Team which gained ground, `None` if the zone was contested or empty
+        team: Option<Team>,
+        /// Number of bugs the scoring team outnumbered its rival by
+        amount: u32,
+        /// Capture progress after scoring
+        progress: f32,
+    },
+    /// The walls closed in at the end of a turn under [`crate::GameMode::Sumo`].
+    RingShrunk {
+        /// Distance the walls have closed in by from the edges of the arena
+        inset: f32,
+    },
+    /// A bug was shoved out of the outer ring under [`crate::Mutator::RingOut`], scoring a point
+    /// for its rival team.
+    RingOut {
+        /// Index of the bug, which sits out until it returns
+        bug: usize,
+        /// Team which scored the point
+        team: Team,
+        /// Points the team has scored so far
+        points: u32,
+    },
+    /// The ball crossed into a goal under [`crate::GameMode::Soccer`] and was reset to the centre.
+    GoalScored {
+        /// Team which scored the goal
+        team: Team,
+        /// Goals the team has scored so far
+        goals: u32,
+    },
+    /// The game entered a new [`GamePhase`].
+    PhaseChanged(GamePhase),
+}
@@ -0,0 +1,39 @@
+use nalgebra::{vector, Vector2};
+use serde::{Deserialize, Serialize};
+
+/// A global force field acting on all moving bodies in the arena
+#[derive(PartialEq, Debug, Serialize, Deserialize, Copy, Clone, Default)]
+pub enum ForceField {
+    /// No force field
+    #[default]
+    Calm,
+    /// A constant wind blowing in one direction
+    Wind(Vector2<f32>),
+    /// A current pulling towards the center (or pushing away if negative), swirling around it
+    Current {
+        /// Strength of the pull towards the center
+        pull: f32,
+        /// Strength of the counter-clockwise swirl around the center
+        swirl: f32,
+    },
+}
+
+impl ForceField {
+    /// Acceleration exerted by the field at a given position.
+    pub fn acceleration_at(&self, position: &Vector2<f32>) -> Vector2<f32> {
+        match self {
+            ForceField::Calm => Vector2::zeros(),
+            ForceField::Wind(wind) => *wind,
+            ForceField::Current { pull, swirl } => {
+                if position.magnitude() < 0.01 {
+                    return Vector2::zeros();
+                }
+
+                let inward = -position.normalize();
+                let around = vector![-inward.y, inward.x];
+
+                inward * *pull + around * *swirl
+            }
+        }
+    }
+}
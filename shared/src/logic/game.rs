@@ -1,118 +1,594 @@
-use std::{
-    collections::{HashMap, VecDeque},
-    f64::consts::{PI, TAU},
-};
+use std::collections::{HashMap, VecDeque};
 
-use nalgebra::{vector, Point2, Vector2};
+use nalgebra::{Point2, Vector2};
 use rapier2d::{
     dynamics::{RigidBody, RigidBodyHandle},
     geometry::{Collider, ColliderHandle, ContactData},
 };
 
-use crate::{BugData, BugSort, Message, Physics, Player, PropData, Result, Team, Turn};
+use crate::{
+    balance, ArenaGeometry, ArenaMap, BotController, BugData, BugName, BugSort, CaptureHeatmap,
+    Corpse, ForceField, GameEvent, GameMode, GameView, ImpactSide, LobbyError, LobbyErrorKind,
+    LobbySettings, Message, Mutator, Physics, Player, PropData, PropSort, Result, Team,
+    TerrainData, Turn, VecMap, BALL_USER_DATA, CORPSE_TURNS, FOG_OF_WAR_RADIUS, LOW_GRAVITY_PULL,
+    RING_OUT_RADIUS, RING_OUT_TURNS, SOCCER_GOALS_TO_WIN, SOCCER_GOAL_HALF_WIDTH, SUMO_MAX_INSET,
+    SUMO_RING_SHRINK, TEAM_SIZE,
+};
+
+/// Speed below which a bug counts as stationary and can brace against impacts.
+const BRACE_SPEED: f32 = 0.25;
+
+/// Speed towards a wall above which a bug running into it is reported as a
+/// [`GameEvent::WallImpact`], the same as the speed bugs need to hurt each other.
+const WALL_IMPACT_SPEED: f32 = 2.0;
+
+/// Distance from a wall's centre line within which a bug is crushed as the wall closes in, half
+/// the wall's thickness and the bug's radius.
+const CRUSH_DISTANCE: f32 = 1.0;
+
+/// Ticks the scoring phase lasts after the zone is scored.
+const SCORING_TICKS: u64 = 60;
+
+/// Distance inside the outer ring at which bugs shoved out of it return under
+/// [`Mutator::RingOut`], the same as the default formation.
+const RETURN_INSET: f32 = 1.5;
+
+/// Angle between the spots along its team's edge a returning bug is tried at.
+const RETURN_SPACING: f32 = 0.3;
+
+/// Most bugs a game holds at once, benched ones included.
+pub const MAX_BUGS: usize = 64;
+
+/// Most props a game holds, keeping their indices clear of the terrain's.
+pub const MAX_PROPS: usize = 64;
+
+/// Most [`Turn`]s queued for execution at once, beyond which further turns are refused.
+pub const MAX_QUEUED_TURNS: usize = 256;
+
+/// Most executed [`Turn`]s a game remembers, the oldest being forgotten as new ones execute.
+pub const MAX_TURN_HISTORY: usize = 4096;
+
+/// Bug indices run below the props', which start here.
+const PROP_INDEX_START: usize = 0xff;
+
+/// What the [`Turn`]s forgotten beyond [`MAX_TURN_HISTORY`] still count towards.
+#[derive(Clone, Default)]
+struct TurnHistorySnapshot {
+    turns: usize,
+    think_times: HashMap<Team, f32>,
+    surrendered: Option<Team>,
+}
+
+impl TurnHistorySnapshot {
+    fn forget(&mut self, turn: &Turn) {
+        self.turns += 1;
+
+        for (team, think_time) in &turn.think_times {
+            *self.think_times.entry(*team).or_default() += think_time;
+        }
+
+        self.surrendered = self.surrendered.or(turn.surrendered);
+    }
+}
+
+/// Phase of the current turn.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum GamePhase {
+    /// Waiting for the next turn, players plan their moves.
+    #[default]
+    Planning,
+    /// The last turn's impulses are being simulated.
+    Resolving,
+    /// The capture zone has just been scored.
+    Scoring,
+    /// A team has fully captured the zone.
+    Finished,
+}
+
+/// What a ray cast along a bug's aim runs into first.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Obstruction {
+    /// A bug of the aiming bug's team.
+    Ally(usize),
+    /// A bug of the rival team.
+    Enemy(usize),
+    /// A prop.
+    Prop(usize),
+    /// One of the arena walls.
+    Wall,
+    /// The ball, under [`GameMode::Soccer`].
+    Ball,
+}
 
 /// Game structure.
 #[derive(Clone)]
 pub struct Game {
     physics: Physics,
-    bugs: HashMap<usize, BugData>,
-    bug_handles: HashMap<usize, RigidBodyHandle>,
-    props: HashMap<usize, PropData>,
+    /// Bugs by index, kept in a [`VecMap`] so they are walked in the same order everywhere.
+    bugs: VecMap<usize, BugData>,
+    bug_handles: VecMap<usize, RigidBodyHandle>,
+    props: VecMap<usize, PropData>,
+    prop_handles: VecMap<usize, ColliderHandle>,
+    terrain: HashMap<usize, TerrainData>,
+    force_field: ForceField,
     ticks: u64,
     turns: Vec<Turn>,
+    /// Turns executed before the remembered ones, see [`MAX_TURN_HISTORY`].
+    forgotten_turns: TurnHistorySnapshot,
     queued_turns: VecDeque<Turn>,
-    capture_radius: f32,
+    geometry: ArenaGeometry,
     capture_progress: i32,
     bug_collisions: Vec<((u128, u128), Point2<f32>)>,
     bug_impacts: Vec<((u128, u128), Point2<f32>)>,
+    prop_impacts: Vec<((u128, u128), Point2<f32>)>,
+    prop_breaks: Vec<(usize, Point2<f32>)>,
+    phase: GamePhase,
+    scoring_history: Vec<i32>,
+    events: Vec<GameEvent>,
+    think_times: HashMap<Team, f32>,
+    surrender: Option<Team>,
+    think_time_bank: Option<f32>,
+    max_turns: Option<usize>,
+    turn_duration: u64,
+    corpses: Vec<Corpse>,
+    capture_heatmap: CaptureHeatmap,
+    mutators: Vec<Mutator>,
+    mode: GameMode,
+    /// Distance the walls have closed in by under [`GameMode::Sumo`].
+    ring_inset: f32,
+    ball: Option<RigidBodyHandle>,
+    /// Goal sensors under [`GameMode::Soccer`], by the team scoring into them.
+    goal_sensors: Vec<(Team, ColliderHandle)>,
+    goals: HashMap<Team, u32>,
+    /// Bugs sitting out after being shoved out of the ring under [`Mutator::RingOut`], with the
+    /// number of turns played when they were.
+    benched: VecMap<usize, (BugData, usize)>,
+    ring_out_points: HashMap<Team, u32>,
+    sandbox: bool,
+    /// Ticks of physics skipped since the game began, as every body was asleep.
+    resting_ticks: u64,
 }
 
 impl Default for Game {
     fn default() -> Self {
+        Game::with_map(&ArenaMap::default())
+    }
+}
+
+impl Game {
+    /// Creates an arena with the given props and terrain, and both teams' bugs lined up.
+    pub fn with_map(map: &ArenaMap) -> Game {
+        let geometry = ArenaGeometry::default();
+
         let mut game = Game {
-            physics: Physics::default(),
-            bugs: HashMap::new(),
-            bug_handles: HashMap::new(),
-            props: HashMap::new(),
+            physics: Physics::new(&geometry),
+            bugs: VecMap::new(),
+            bug_handles: VecMap::new(),
+            props: VecMap::new(),
+            prop_handles: VecMap::new(),
+            terrain: HashMap::new(),
+            force_field: ForceField::default(),
             turns: Vec::new(),
+            forgotten_turns: TurnHistorySnapshot::default(),
             queued_turns: VecDeque::new(),
             ticks: 0,
-            capture_radius: 4.0,
+            geometry,
             capture_progress: 0,
             bug_collisions: Vec::new(),
             bug_impacts: Vec::new(),
+            prop_impacts: Vec::new(),
+            prop_breaks: Vec::new(),
+            phase: GamePhase::default(),
+            scoring_history: Vec::new(),
+            events: Vec::new(),
+            think_times: HashMap::new(),
+            surrender: None,
+            think_time_bank: None,
+            max_turns: None,
+            turn_duration: LobbySettings::DEFAULT_TURN_DURATION,
+            corpses: Vec::new(),
+            capture_heatmap: CaptureHeatmap::default(),
+            mutators: Vec::new(),
+            mode: GameMode::default(),
+            ring_inset: 0.0,
+            ball: None,
+            goal_sensors: Vec::new(),
+            goals: HashMap::new(),
+            benched: VecMap::new(),
+            ring_out_points: HashMap::new(),
+            sandbox: false,
+            resting_ticks: 0,
         };
 
-        let team_size = 6;
-        let num_bugs = team_size * 2;
+        for (i, translation) in map.formation().into_iter().enumerate() {
+            let offset = i % TEAM_SIZE;
+            let team = if i < TEAM_SIZE { Team::Red } else { Team::Blue };
 
-        for i in 0..num_bugs {
-            let offset = i % team_size;
-            let arc_size = 0.3;
-            let team_arc = arc_size * (team_size - 1) as f32;
-            let arc_offset = team_arc / 2.0;
-            let team_offset = if i < team_size {
-                -arc_offset
-            } else {
-                std::f32::consts::PI - arc_offset
-            };
-            let net_offset = team_offset + arc_size * offset as f32;
+            let mut bug_data = match i % 3 {
+                0 => BugData::new(BugSort::Beetle, team),
+                1 => BugData::new(BugSort::Ladybug, team),
+                _ => BugData::new(BugSort::Ant, team),
+            }
+            .with_name(BugName::generate(i, offset + 1));
 
-            let team = if i < team_size { Team::Red } else { Team::Blue };
+            // Bugs start out facing the centre of the arena
+            bug_data.face(&-translation);
 
-            game.insert_bug(
-                vector![
-                    0.0 + (net_offset).cos() * 8.0,
-                    0.0 + (net_offset).sin() * 8.0
-                ],
-                match i % 3 {
-                    0 => BugData::new(BugSort::Beetle, team),
-                    1 => BugData::new(BugSort::Ladybug, team),
-                    _ => BugData::new(BugSort::Ant, team),
-                },
-            );
+            // The formation is well within the budget
+            let _ = game.insert_bug(translation, bug_data);
         }
 
-        for i in 0..24 {
-            let offset = i;
-            let arc_size = TAU / 16 as f64;
-            let arc: f32 = arc_size as f32 * offset as f32;
+        // Props beyond the budget are left out
+        for (translation, prop_sort) in &map.props {
+            let _ = game.insert_prop(*translation, PropData::new(*prop_sort));
+        }
 
-            game.insert_prop(vector![
-                0.0 + (arc * 1.0).cos() * 10.0,
-                0.0 + (arc * 6.0).sin() * 10.0
-            ]);
+        for (translation, terrain_data) in &map.terrain {
+            game.insert_terrain(*translation, *terrain_data);
         }
 
-        for i in 0..6 {
-            let offset = i;
-            let arc_size = TAU / 6 as f64;
-            let arc: f32 = arc_size as f32 * offset as f32 + 3.141592653589793 / 6.0;
+        game
+    }
+
+    /// Creates the default arena under a given [`ForceField`].
+    pub fn with_force_field(force_field: ForceField) -> Game {
+        let mut game = Game::default();
+
+        game.set_force_field(force_field);
+
+        game
+    }
 
-            game.insert_prop(vector![
-                0.0 + (arc * 1.0).cos() * 6.0,
-                0.0 + (arc * 1.0).sin() * 6.0
-            ]);
+    /// Creates the arena for a lobby's [`LobbySettings`].
+    pub fn with_settings(settings: &LobbySettings) -> Game {
+        let mut map = settings.map().cloned().unwrap_or_default();
+
+        if settings.mutators().contains(&Mutator::NoProps) {
+            map.props.clear();
         }
 
-        for i in 0..4 {
-            let offset = i;
-            let arc_size = TAU / 4.0;
-            let arc: f32 = arc_size as f32 * offset as f32 + 3.141592653589793 / 8.0;
+        let mut game = Game::with_map(&map);
+
+        game.set_force_field(*settings.force_field());
+        game.set_mutators(settings.mutators());
+        game.mode = settings.mode();
 
-            game.insert_prop(vector![
-                0.0 + (arc * 1.0).cos() * 3.0,
-                0.0 + (arc * 1.0).sin() * 3.0
-            ]);
+        if game.mode == GameMode::Soccer {
+            game.set_up_soccer();
         }
 
+        game.think_time_bank = settings.think_time_bank();
+        game.max_turns = settings.max_turns();
+        game.turn_duration = settings.turn_duration();
+
         game
     }
-}
-impl Game {
+
+    /// Creates a sandboxed copy of a lobby's arena for warming up, in which flicks resolve
+    /// instantly and bugs take no damage, nor does the zone score.
+    pub fn sandbox(settings: &LobbySettings) -> Game {
+        let mut game = Game::with_settings(settings);
+
+        game.sandbox = true;
+
+        game
+    }
+
+    /// Creates an empty sandbox with only Red's bugs lined up, for [`crate::TargetPractice`].
+    pub(crate) fn practice_arena() -> Game {
+        let mut game = Game::with_map(&ArenaMap {
+            props: Vec::new(),
+            terrain: Vec::new(),
+            ..ArenaMap::default()
+        });
+
+        for bug_index in game.team_bug_indices(Team::Blue) {
+            if let Some(rigid_body_handle) = game.bug_handles.remove(&bug_index) {
+                game.physics.remove_bug(rigid_body_handle);
+            }
+
+            game.bugs.remove(&bug_index);
+        }
+
+        game.sandbox = true;
+
+        game
+    }
+
+    /// Determines if the game is a sandbox, see [`Game::sandbox`].
+    pub fn is_sandbox(&self) -> bool {
+        self.sandbox
+    }
+
+    /// Launches a bug right away, outside of any turn and without spending stamina, in sandboxes.
+    pub fn flick(&mut self, bug_index: usize, impulse_intent: Vector2<f32>) {
+        if !self.sandbox {
+            return;
+        }
+
+        let impulse_multiplier = self.impulse_multiplier();
+
+        if let Some((rigid_body, bug_data)) = self.get_bug_mut(bug_index) {
+            bug_data.set_impulse_intent(impulse_intent);
+            rigid_body.apply_impulse(*bug_data.impulse_intent() * impulse_multiplier, true);
+            bug_data.set_impulse_intent(Vector2::zeros());
+        }
+    }
+
+    /// Scale from impulse intents to the impulses applied to bugs.
+    fn impulse_multiplier(&self) -> f32 {
+        if self.has_mutator(Mutator::DoubleImpulse) {
+            balance().impulse_multiplier * 2.0
+        } else {
+            balance().impulse_multiplier
+        }
+    }
+
+    fn set_force_field(&mut self, force_field: ForceField) {
+        self.force_field = force_field;
+        self.physics.set_force_field(force_field);
+    }
+
+    /// Returns the [`ForceField`] acting on the arena.
+    pub fn force_field(&self) -> &ForceField {
+        &self.force_field
+    }
+
+    /// Applies the [`Mutator`]s which change the setup of the arena, props aside.
+    fn set_mutators(&mut self, mutators: &[Mutator]) {
+        self.mutators = mutators.to_vec();
+
+        if self.has_mutator(Mutator::LowGravity) {
+            self.physics.set_central_pull(LOW_GRAVITY_PULL);
+        }
+
+        // Bugs are knocked out at one health, so two leaves them a single hit
+        if self.has_mutator(Mutator::OneHealth) {
+            for bug_data in self.bugs.values_mut() {
+                bug_data.add_health(2 - bug_data.health() as isize);
+            }
+        }
+    }
+
+    /// Returns the [`Mutator`]s the game is played under.
+    pub fn mutators(&self) -> &[Mutator] {
+        &self.mutators
+    }
+
+    /// Determines if the game is played under a [`Mutator`].
+    pub fn has_mutator(&self, mutator: Mutator) -> bool {
+        self.mutators.contains(&mutator)
+    }
+
+    /// Returns the [`GameMode`] the game is played in.
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    /// Distance the walls have closed in by from the edges of the arena, only ever non-zero under
+    /// [`GameMode::Sumo`].
+    pub fn ring_inset(&self) -> f32 {
+        self.ring_inset
+    }
+
+    /// Places the ball at the centre of the arena and a goal against the middle of the left and
+    /// right walls.
+    fn set_up_soccer(&mut self) {
+        self.ball = Some(self.physics.insert_ball(Vector2::zeros()));
+
+        let goal_x = self.geometry.width / 2.0 - 1.0;
+        let half_extents = Vector2::new(0.5, SOCCER_GOAL_HALF_WIDTH);
+
+        // Red lines up on the right of the default formation, so it attacks the left goal
+        self.goal_sensors = vec![
+            (
+                Team::Red,
+                self.physics
+                    .insert_goal(Vector2::new(-goal_x, 0.0), half_extents),
+            ),
+            (
+                Team::Blue,
+                self.physics
+                    .insert_goal(Vector2::new(goal_x, 0.0), half_extents),
+            ),
+        ];
+    }
+
+    /// Returns the ball's [`RigidBody`] under [`GameMode::Soccer`].
+    pub fn ball(&self) -> Option<&RigidBody> {
+        self.ball
+            .and_then(|ball| self.physics.rigid_body_set.get(ball))
+    }
+
+    /// Centre of the goal a team scores into under [`GameMode::Soccer`].
+    pub fn goal(&self, team: Team) -> Option<Vector2<f32>> {
+        self.goal_sensors
+            .iter()
+            .find(|(scorer, _)| *scorer == team)
+            .and_then(|(_, sensor_handle)| self.physics.collider_set.get(*sensor_handle))
+            .map(|collider| *collider.translation())
+    }
+
+    /// Number of goals a team has scored under [`GameMode::Soccer`].
+    pub fn goals(&self, team: Team) -> u32 {
+        self.goals.get(&team).copied().unwrap_or_default()
+    }
+
+    /// Counts a goal when the ball crosses into one, putting the ball back at the centre.
+    fn tick_goals(&mut self) {
+        let Some(ball) = self.ball else {
+            return;
+        };
+
+        let Some(team) = self
+            .goal_sensors
+            .iter()
+            .find(|(_, sensor_handle)| self.physics.overlaps(*sensor_handle, ball))
+            .map(|(team, _)| *team)
+        else {
+            return;
+        };
+
+        self.physics.reset_body(ball, Vector2::zeros());
+
+        if self.sandbox {
+            return;
+        }
+
+        let goals = self.goals.entry(team).or_default();
+        *goals += 1;
+
+        self.events.push(GameEvent::GoalScored {
+            team,
+            goals: *goals,
+        });
+    }
+
+    /// Number of a team's bugs still in the arena, knocked out or not, counting those sitting out
+    /// after being shoved out of the ring as they return.
+    pub fn bugs_in_ring(&self, team: Team) -> usize {
+        self.iter_bugs()
+            .map(|(_, bug_data)| bug_data)
+            .chain(self.benched.values().map(|(bug_data, _)| bug_data))
+            .filter(|bug_data| *bug_data.team() == team)
+            .count()
+    }
+
+    /// Number of points a team has scored by shoving rival bugs out of the ring under
+    /// [`Mutator::RingOut`].
+    pub fn ring_out_points(&self, team: Team) -> u32 {
+        self.ring_out_points.get(&team).copied().unwrap_or_default()
+    }
+
+    /// Returns the [`BugData`] of a bug sitting out after being shoved out of the ring.
+    pub fn get_benched_bug(&self, bug_index: usize) -> Option<&BugData> {
+        self.benched.get(&bug_index).map(|(bug_data, _)| bug_data)
+    }
+
+    /// Scores a point for the rival team of each bug which crossed out of the ring this tick,
+    /// taking the bug out of the arena until it returns.
+    fn tick_ring_outs(&mut self, inside: Vec<usize>) {
+        for bug_index in inside {
+            let Some((rigid_body, bug_data)) = self.get_bug(bug_index) else {
+                continue;
+            };
+
+            if rigid_body.translation().magnitude() <= RING_OUT_RADIUS {
+                continue;
+            }
+
+            let team = bug_data.team().enemy();
+
+            if let Some(rigid_body_handle) = self.bug_handles.remove(&bug_index) {
+                self.physics.remove_bug(rigid_body_handle);
+            }
+
+            if let Some(mut bug_data) = self.bugs.remove(&bug_index) {
+                bug_data.reset_impulse_intent();
+                self.benched
+                    .insert(bug_index, (bug_data, self.turns_count()));
+            }
+
+            let points = self.ring_out_points.entry(team).or_default();
+            *points += 1;
+            let points = *points;
+
+            // Each point tips the zone as much as a bug holding it for a turn
+            if self.mode == GameMode::Capture {
+                self.capture_progress += match team {
+                    Team::Red => 1,
+                    Team::Blue => -1,
+                };
+            }
+
+            self.events.push(GameEvent::RingOut {
+                bug: bug_index,
+                team,
+                points,
+            });
+        }
+    }
+
+    /// Puts the bugs which have sat out long enough back along their team's edge of the ring.
+    fn return_benched(&mut self) {
+        let turns_count = self.turns_count();
+        let returning: Vec<usize> = self
+            .benched
+            .iter()
+            .filter(|(_, (_, turn))| turns_count >= turn + RING_OUT_TURNS)
+            .map(|(bug_index, _)| *bug_index)
+            .collect();
+
+        for bug_index in returning {
+            let Some((bug_data, _)) = self.benched.remove(&bug_index) else {
+                continue;
+            };
+
+            let translation = self.return_spot(*bug_data.team());
+            let rigid_body_handle =
+                self.physics
+                    .insert_bug(translation, bug_index, *bug_data.sort());
+
+            self.bugs.insert(bug_index, bug_data);
+            self.bug_handles.insert(bug_index, rigid_body_handle);
+        }
+    }
+
+    /// Picks the free spot closest to the middle of a team's edge of the ring, on the side it
+    /// lines up on in the default formation.
+    fn return_spot(&self, team: Team) -> Vector2<f32> {
+        let middle = match team {
+            Team::Red => 0.0,
+            Team::Blue => std::f32::consts::PI,
+        };
+        let radius = RING_OUT_RADIUS - RETURN_INSET;
+
+        let spots: Vec<Vector2<f32>> = (0..TEAM_SIZE * 2)
+            .map(|i| {
+                let side = if i % 2 == 0 { 1.0 } else { -1.0 };
+                let angle = middle + side * i.div_ceil(2) as f32 * RETURN_SPACING;
+
+                Vector2::new(angle.cos(), angle.sin()) * radius
+            })
+            .collect();
+
+        let free = |spot: &&Vector2<f32>| {
+            self.iter_bugs()
+                .all(|(rigid_body, _)| (rigid_body.translation() - *spot).magnitude() > 1.0)
+                && self
+                    .ball()
+                    .is_none_or(|ball| (ball.translation() - *spot).magnitude() > 1.0)
+                && self.intersecting_prop(Point2::from(**spot)).is_none()
+        };
+
+        spots.iter().find(free).copied().unwrap_or(spots[0])
+    }
+
+    /// Determines if a position can be seen by a team, which under [`Mutator::FogOfWar`] requires
+    /// one of its standing bugs nearby.
+    pub fn visible_to(&self, team: Team, position: &Vector2<f32>) -> bool {
+        !self.has_mutator(Mutator::FogOfWar)
+            || self.iter_bugs().any(|(rigid_body, bug_data)| {
+                *bug_data.team() == team
+                    && bug_data.health() > 1
+                    && (rigid_body.translation() - position).magnitude() < FOG_OF_WAR_RADIUS
+            })
+    }
+
     /// Returns a list of [`Turn`]s skipping the first `since` turns.
+    ///
+    /// Forgotten turns are left out, see [`MAX_TURN_HISTORY`].
     pub fn turns_since(&self, since: usize) -> Vec<&Turn> {
-        self.turns.iter().skip(since).collect()
+        self.turns
+            .iter()
+            .skip(since.saturating_sub(self.forgotten_turns.turns))
+            .collect()
+    }
+
+    /// Returns the executed [`Turn`] of the given index, unless it was forgotten.
+    pub fn turn(&self, index: usize) -> Option<&Turn> {
+        self.turns
+            .get(index.checked_sub(self.forgotten_turns.turns)?)
     }
 
     /// Returns the latest [`Turn`].
@@ -121,19 +597,177 @@ impl Game {
     }
 
     /// hypothetical turn
+    ///
+    /// Only bugs with a planned move are included, as [`Game::execute_turn`] treats missing intents as standing still.
     pub fn aggregate_turn(&self) -> Turn {
         Turn {
-            impulse_intents: HashMap::from_iter(
-                self.bugs.iter().map(|(i, bug)| (*i, *bug.impulse_intent())),
+            impulse_intents: VecMap::from_iter(
+                self.bugs
+                    .iter()
+                    .filter(|(_, bug)| bug.impulse_intent().magnitude() > 0.0)
+                    .map(|(i, bug)| (*i, *bug.impulse_intent())),
             ),
             timestamp: 0.0,
             index: self.turns_count(),
+            think_times: self.think_times.clone(),
+            surrendered: self.surrender,
         }
     }
 
+    /// Marks a [`Team`] as surrendering, which ends the game as its loss with the upcoming turn.
+    pub fn surrender(&mut self, team: Team) {
+        self.surrender = Some(team);
+    }
+
+    /// Returns the [`Team`] surrendering with the upcoming turn, if any.
+    pub fn pending_surrender(&self) -> Option<Team> {
+        self.surrender
+    }
+
+    /// Returns the [`Team`] which surrendered in an executed turn, if any.
+    pub fn surrendered(&self) -> Option<Team> {
+        self.forgotten_turns
+            .surrendered
+            .or_else(|| self.turns.iter().find_map(|turn| turn.surrendered))
+    }
+
+    /// Records the seconds a [`Team`] has spent planning the upcoming turn so far.
+    pub fn record_think_time(&mut self, team: Team, think_time: f32) {
+        self.think_times.insert(team, think_time.max(0.0));
+    }
+
+    /// Returns the total seconds a [`Team`] has spent planning over all executed turns.
+    pub fn think_time(&self, team: Team) -> f32 {
+        self.forgotten_turns
+            .think_times
+            .get(&team)
+            .copied()
+            .unwrap_or_default()
+            + self
+                .turns
+                .iter()
+                .filter_map(|turn| turn.think_times.get(&team))
+                .sum::<f32>()
+    }
+
+    /// Returns the seconds a [`Team`] has left in its think time bank, if limited.
+    pub fn think_time_left(&self, team: Team) -> Option<f32> {
+        self.think_time_bank
+            .map(|think_time_bank| (think_time_bank - self.think_time(team)).max(0.0))
+    }
+
     /// Returns the result of the [`Game`].
     pub fn result(&self) -> Option<Result> {
-        None
+        if let Some(team) = self.surrendered() {
+            return Some(Result::Win(team.enemy()));
+        }
+
+        let exhausted = |team| self.think_time_left(team) == Some(0.0);
+
+        match (exhausted(Team::Red), exhausted(Team::Blue)) {
+            (true, true) => return Some(Result::Tie),
+            (true, false) => return Some(Result::Win(Team::Blue)),
+            (false, true) => return Some(Result::Win(Team::Red)),
+            (false, false) => (),
+        }
+
+        if self.mode == GameMode::Sumo {
+            return match (self.bugs_in_ring(Team::Red), self.bugs_in_ring(Team::Blue)) {
+                (0, 0) => Some(Result::Tie),
+                (0, _) => Some(Result::Win(Team::Blue)),
+                (_, 0) => Some(Result::Win(Team::Red)),
+                _ if self.turn_limit_reached() => Some(self.tiebreak()),
+                _ => None,
+            };
+        }
+
+        if self.mode == GameMode::Soccer {
+            return if self.goals(Team::Red) >= SOCCER_GOALS_TO_WIN {
+                Some(Result::Win(Team::Red))
+            } else if self.goals(Team::Blue) >= SOCCER_GOALS_TO_WIN {
+                Some(Result::Win(Team::Blue))
+            } else if self.turn_limit_reached() {
+                Some(self.tiebreak())
+            } else {
+                None
+            };
+        }
+
+        let capture_progress = self.capture_progress();
+
+        if capture_progress >= 1.0 {
+            Some(Result::Win(Team::Red))
+        } else if capture_progress <= -1.0 {
+            Some(Result::Win(Team::Blue))
+        } else if self.turn_limit_reached() {
+            Some(self.tiebreak())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the maximum number of turns, if limited.
+    pub fn max_turns(&self) -> Option<usize> {
+        self.max_turns
+    }
+
+    /// Determines if the current turn is the last one before the game is decided on points.
+    pub fn final_turn(&self) -> bool {
+        self.max_turns == Some(self.turns_count() + 1)
+    }
+
+    /// Determines if the last allowed turn has been scored.
+    fn turn_limit_reached(&self) -> bool {
+        self.max_turns.is_some_and(|max_turns| {
+            self.turns_count() >= max_turns
+                && (self.turn_ticks() >= self.turn_tick_count_half()
+                    || self.phase == GamePhase::Finished)
+        })
+    }
+
+    /// Decides a game which ran out of turns by capture progress, bugs left in the ring under
+    /// [`GameMode::Sumo`] or goals under [`GameMode::Soccer`], then total remaining health.
+    fn tiebreak(&self) -> Result {
+        let lead = match self.mode {
+            GameMode::Capture => std::cmp::Ordering::Equal,
+            GameMode::Sumo => self
+                .bugs_in_ring(Team::Red)
+                .cmp(&self.bugs_in_ring(Team::Blue)),
+            GameMode::Soccer => self.goals(Team::Red).cmp(&self.goals(Team::Blue)),
+        }
+        .then(
+            self.ring_out_points(Team::Red)
+                .cmp(&self.ring_out_points(Team::Blue)),
+        );
+
+        match lead {
+            std::cmp::Ordering::Greater => return Result::Win(Team::Red),
+            std::cmp::Ordering::Less => return Result::Win(Team::Blue),
+            std::cmp::Ordering::Equal => (),
+        }
+
+        let capture_progress = self.capture_progress();
+
+        if capture_progress > 0.0 {
+            return Result::Win(Team::Red);
+        } else if capture_progress < 0.0 {
+            return Result::Win(Team::Blue);
+        }
+
+        let health = |team| {
+            self.iter_bugs()
+                .map(|(_, bug_data)| bug_data)
+                .chain(self.benched.values().map(|(bug_data, _)| bug_data))
+                .filter(|bug_data| *bug_data.team() == team)
+                .map(|bug_data| bug_data.health())
+                .sum::<usize>()
+        };
+
+        match health(Team::Red).cmp(&health(Team::Blue)) {
+            std::cmp::Ordering::Greater => Result::Win(Team::Red),
+            std::cmp::Ordering::Less => Result::Win(Team::Blue),
+            std::cmp::Ordering::Equal => Result::Tie,
+        }
     }
 
     /// num ticks
@@ -146,6 +780,12 @@ impl Game {
     pub fn tick(&mut self) {
         self.ticks += 1;
 
+        // Sandboxes have no turns, their bugs move whenever flicked
+        if self.sandbox {
+            self.tick_physics();
+            return;
+        }
+
         let turn_ticks = self.turn_ticks();
         let turn_tick_count = self.turn_tick_count();
         let turn_tick_count_half = self.turn_tick_count_half();
@@ -154,6 +794,7 @@ impl Game {
             // At each N second interval, check for queued turns (which are sent from the server
             if let Some(queued_turn) = self.queued_turns.pop_front() {
                 if self.execute_turn(&queued_turn) {
+                    self.set_phase(GamePhase::Resolving);
                     self.tick_physics();
                 }
             } else {
@@ -166,6 +807,16 @@ impl Game {
         }
         if turn_ticks == turn_tick_count_half {
             self.tick_turn();
+
+            self.set_phase(if self.result().is_some() {
+                GamePhase::Finished
+            } else {
+                GamePhase::Scoring
+            });
+        } else if self.phase == GamePhase::Scoring
+            && turn_ticks >= turn_tick_count_half + SCORING_TICKS
+        {
+            self.set_phase(GamePhase::Planning);
         }
 
         // Tick until we reach the next target
@@ -186,7 +837,7 @@ impl Game {
 
     /// Duration of the turn in seconds
     pub fn turn_duration(&self) -> u64 {
-        16
+        self.turn_duration
     }
 
     /// num turn turn_tick_count
@@ -212,10 +863,27 @@ impl Game {
     /// force a subtick
     ///
     pub fn tick_turn(&mut self) {
+        self.return_benched();
+
+        match self.mode {
+            GameMode::Capture => (),
+            GameMode::Sumo => {
+                self.tick_ring();
+                return;
+            }
+            GameMode::Soccer => {
+                self.recover();
+                self.scoring_history.push(0);
+                return;
+            }
+        }
+
         let mut tip = 0;
 
         for (rigid_body, bug_data) in self.iter_bugs() {
-            if rigid_body.translation().magnitude() < self.capture_radius && bug_data.health() > 1 {
+            if rigid_body.translation().magnitude() < self.geometry.capture_radius
+                && bug_data.health() > 1
+            {
                 match bug_data.team() {
                     Team::Red => tip += 1,
                     Team::Blue => tip -= 1,
@@ -223,21 +891,235 @@ impl Game {
             }
         }
 
+        let occupants: Vec<(Vector2<f32>, Team)> = self
+            .iter_bugs()
+            .filter(|(_, bug_data)| bug_data.health() > 1)
+            .map(|(rigid_body, bug_data)| (*rigid_body.translation(), *bug_data.team()))
+            .collect();
+        self.capture_heatmap
+            .record(self.geometry.capture_radius, occupants);
+
+        self.recover();
+
+        self.capture_progress += tip;
+        self.scoring_history.push(tip);
+        self.events.push(GameEvent::ZoneScored {
+            team: match tip.signum() {
+                1 => Some(Team::Red),
+                -1 => Some(Team::Blue),
+                _ => None,
+            },
+            amount: tip.unsigned_abs(),
+            progress: self.capture_progress(),
+        });
+    }
+
+    /// Closes the walls in under [`GameMode::Sumo`], crushing the bugs caught against them, in
+    /// place of scoring the zone.
+    fn tick_ring(&mut self) {
+        if self.ring_inset < SUMO_MAX_INSET {
+            self.ring_inset = (self.ring_inset + SUMO_RING_SHRINK).min(SUMO_MAX_INSET);
+            self.physics.set_wall_inset(&self.geometry, self.ring_inset);
+
+            self.events.push(GameEvent::RingShrunk {
+                inset: self.ring_inset,
+            });
+        }
+
+        let limit_x = self.geometry.width / 2.0 - self.ring_inset - CRUSH_DISTANCE;
+        let limit_y = self.geometry.height / 2.0 - self.ring_inset - CRUSH_DISTANCE;
+
+        let crushed: Vec<usize> = self
+            .iter_bugs()
+            .filter(|(rigid_body, _)| {
+                rigid_body.translation().x.abs() > limit_x
+                    || rigid_body.translation().y.abs() > limit_y
+            })
+            .map(|(rigid_body, _)| rigid_body.user_data as usize)
+            .collect();
+
+        for bug_index in crushed {
+            self.remove_bug(bug_index);
+        }
+
+        self.recover();
+
+        // Nothing is captured, but every turn is still recorded
+        self.scoring_history.push(0);
+    }
+
+    /// Takes a bug out of the arena for good, leaving a corpse behind.
+    pub(crate) fn remove_bug(&mut self, bug_index: usize) {
+        let Some((rigid_body, bug_data)) = self.get_bug(bug_index) else {
+            return;
+        };
+
+        self.corpses.push(Corpse {
+            position: *rigid_body.translation(),
+            sort: *bug_data.sort(),
+            team: *bug_data.team(),
+            ticks: self.ticks,
+            turn: self.turns_count(),
+        });
+
+        if let Some(rigid_body_handle) = self.bug_handles.remove(&bug_index) {
+            self.physics.remove_bug(rigid_body_handle);
+        }
+
+        self.bugs.remove(&bug_index);
+
+        self.events.push(GameEvent::BugKnockedOut {
+            bug: bug_index,
+            attacker: None,
+        });
+    }
+
+    /// Restores a point of health to every bug and clears out old corpses at the end of a turn.
+    fn recover(&mut self) {
         for (_, bug_data) in self.bugs.iter_mut() {
             bug_data.add_health(1);
         }
 
-        self.capture_progress += tip;
+        let turns_count = self.turns_count();
+        self.corpses
+            .retain(|corpse| turns_count < corpse.turn + CORPSE_TURNS);
+    }
+
+    fn set_phase(&mut self, phase: GamePhase) {
+        if self.phase != phase {
+            self.phase = phase;
+            self.events.push(GameEvent::PhaseChanged(phase));
+        }
+    }
+
+    /// Takes the [`GameEvent`]s emitted since the last call, so each is only handled once.
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Determines if every bug, prop and ball has come to rest.
+    pub fn at_rest(&self) -> bool {
+        self.physics.all_asleep()
+    }
+
+    /// Returns the number of ticks whose physics were skipped as every body was asleep.
+    pub fn resting_ticks(&self) -> u64 {
+        self.resting_ticks
+    }
+
+    /// Returns the current [`GamePhase`].
+    pub fn phase(&self) -> GamePhase {
+        self.phase
+    }
+
+    /// Capture progress gained by Red (positive) or Blue (negative) at the last scoring.
+    pub fn capture_tip(&self) -> i32 {
+        self.scoring_history.last().copied().unwrap_or_default()
+    }
+
+    /// Capture progress gained by Red (positive) or Blue (negative) in each scored turn.
+    pub fn scoring_history(&self) -> &[i32] {
+        &self.scoring_history
+    }
+
+    /// Capture progress gained by Red (positive) or Blue (negative) at the last scoring, as a
+    /// fraction of [`Game::capture_progress`].
+    pub fn capture_momentum(&self) -> f32 {
+        self.capture_tip() as f32 / (self.bugs.len() + self.benched.len()) as f32
+    }
+
+    /// Determines if a [`Team`] would capture the zone by repeating its best scored turn so far,
+    /// only ever in [`GameMode::Capture`].
+    pub fn capture_within_reach(&self, team: Team) -> bool {
+        if self.mode != GameMode::Capture {
+            return false;
+        }
+
+        let sign = match team {
+            Team::Red => 1,
+            Team::Blue => -1,
+        };
+        let best_gain = self
+            .scoring_history
+            .iter()
+            .map(|tip| tip * sign)
+            .max()
+            .unwrap_or_default();
+
+        best_gain > 0
+            && self.capture_progress * sign + best_gain
+                >= (self.bugs.len() + self.benched.len()) as i32
     }
 
     /// force a subtick
     pub fn tick_physics(&mut self) {
+        // Nothing moves, collides or scores until an executed turn or a flick wakes a body up
+        if self.physics.all_asleep() {
+            self.resting_ticks += 1;
+            self.bug_collisions.clear();
+            self.bug_impacts.clear();
+            self.prop_impacts.clear();
+            self.prop_breaks.clear();
+
+            return;
+        }
+
+        let braced: Vec<(usize, Vector2<f32>)> = self
+            .iter_bugs()
+            .filter(|(rigid_body, bug_data)| {
+                bug_data.sort().brace_knockback() < 1.0
+                    && bug_data.health() > 1
+                    && rigid_body.linvel().magnitude() < BRACE_SPEED
+            })
+            .map(|(rigid_body, _)| (rigid_body.user_data as usize, *rigid_body.linvel()))
+            .collect();
+
+        // Only bugs crossing the ring's edge are shoved out, not those which started beyond it
+        let inside: Vec<usize> = if self.has_mutator(Mutator::RingOut) && !self.sandbox {
+            self.iter_bugs()
+                .filter(|(rigid_body, _)| rigid_body.translation().magnitude() <= RING_OUT_RADIUS)
+                .map(|(rigid_body, _)| rigid_body.user_data as usize)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // Velocities going into the tick, as collisions have already bounced bugs off after it
+        let approaches: HashMap<usize, Vector2<f32>> = self
+            .iter_bugs()
+            .map(|(rigid_body, _)| (rigid_body.user_data as usize, *rigid_body.linvel()))
+            .collect();
+
         self.physics.tick();
 
         self.bug_collisions = self.physics.bug_collisions();
 
+        // Braced bugs only take part of the velocity change from collisions
+        for (bug_index, linvel) in braced {
+            let collided = self
+                .bug_collisions
+                .iter()
+                .any(|((a, b), _)| *a as usize == bug_index || *b as usize == bug_index);
+
+            if collided {
+                if let Some((rigid_body, bug_data)) = self.get_bug_mut(bug_index) {
+                    let factor = bug_data.sort().brace_knockback();
+                    let knockback = rigid_body.linvel() - linvel;
+
+                    rigid_body.set_linvel(linvel + knockback * factor, true);
+                }
+            }
+        }
+
         self.bug_impacts = Vec::new();
 
+        let standing: Vec<usize> = self
+            .bugs
+            .iter()
+            .filter(|(_, bug_data)| bug_data.health() > 1)
+            .map(|(bug_index, _)| *bug_index)
+            .collect();
+
         for ((a, b), position) in self.bug_collisions.clone() {
             let (rb_a, bug_a) = self.get_bug(a as usize).unwrap();
             let (rb_b, bug_b) = self.get_bug(b as usize).unwrap();
@@ -254,16 +1136,213 @@ impl Game {
         }
 
         for ((a, b), position) in self.bug_impacts.clone() {
+            let sandbox = self.sandbox;
+            let speed = self
+                .get_bug(a as usize)
+                .map_or(0.0, |(rigid_body, _)| rigid_body.linvel().magnitude());
             let (rb_a, bug_a) = self.get_bug_mut(a as usize).unwrap();
-            bug_a.add_health(-1);
+
+            if !sandbox {
+                bug_a.add_health(-1);
+            }
 
             let attacker_sort = *bug_a.sort();
 
             let (rb_b, bug_b) = self.get_bug_mut(b as usize).unwrap();
-            bug_b.add_health(-1);
 
-            if attacker_sort == BugSort::Ant {
-                bug_b.add_health(-1);
+            let side = bug_b.impact_side(&(position.coords - rb_b.translation()));
+
+            // Heavier defenders resist one point of damage from lighter attackers, as does the
+            // front of any shell
+            let resisted = bug_b.sort().weight_class() > attacker_sort.weight_class()
+                || side == ImpactSide::Front;
+
+            if !sandbox {
+                if !resisted {
+                    bug_b.add_health(-1);
+                }
+
+                if side == ImpactSide::Rear {
+                    bug_b.add_health(-1);
+                }
+
+                bug_b.add_health(-attacker_sort.bonus_damage());
+            }
+
+            self.events.push(GameEvent::BugImpact {
+                attacker: a as usize,
+                defender: b as usize,
+                position,
+                speed,
+            });
+        }
+
+        self.tick_prop_impacts();
+        self.tick_wall_impacts(&approaches);
+        self.tick_goals();
+
+        // Shells turn once the tick's impacts are resolved, so impacts meet the facing bugs had
+        // coming into the tick
+        let headings: Vec<(usize, Vector2<f32>)> = self
+            .iter_bugs()
+            .map(|(rigid_body, _)| (rigid_body.user_data as usize, *rigid_body.linvel()))
+            .collect();
+
+        for (bug_index, heading) in headings {
+            if let Some((_, bug_data)) = self.get_bug_mut(bug_index) {
+                bug_data.face(&heading);
+            }
+        }
+
+        for bug_index in standing {
+            if let Some((rigid_body, bug_data)) = self.get_bug(bug_index) {
+                if bug_data.health() <= 1 {
+                    let corpse = Corpse {
+                        position: *rigid_body.translation(),
+                        sort: *bug_data.sort(),
+                        team: *bug_data.team(),
+                        ticks: self.ticks,
+                        turn: self.turns_count(),
+                    };
+
+                    self.corpses.push(corpse);
+
+                    // The last rival to strike the bug this tick takes the credit
+                    let attacker = self
+                        .bug_impacts
+                        .iter()
+                        .rev()
+                        .find(|((_, defender), _)| *defender as usize == bug_index)
+                        .map(|((attacker, _), _)| *attacker as usize);
+
+                    self.events.push(GameEvent::BugKnockedOut {
+                        bug: bug_index,
+                        attacker,
+                    });
+                }
+            }
+        }
+
+        self.tick_ring_outs(inside);
+    }
+
+    /// Returns where each team's bugs have held the capture zone over the past turns.
+    pub fn capture_heatmap(&self) -> &CaptureHeatmap {
+        &self.capture_heatmap
+    }
+
+    /// Returns the corpses left where bugs were knocked out over the last [`CORPSE_TURNS`] turns.
+    pub fn corpses(&self) -> &[Corpse] {
+        &self.corpses
+    }
+
+    /// Damages rocks hit hard enough and lets moving boulders crush weakened bugs.
+    fn tick_prop_impacts(&mut self) {
+        self.prop_impacts = Vec::new();
+        self.prop_breaks = Vec::new();
+
+        let mut impact_speeds = Vec::new();
+
+        for ((a, b), position) in self.physics.prop_collisions() {
+            let Some(prop_data) = self.props.get(&(b as usize)) else {
+                continue;
+            };
+            let Some((rb_bug, _)) = self.get_bug(a as usize) else {
+                continue;
+            };
+
+            match prop_data.sort() {
+                PropSort::Rock => {
+                    let towards = position.coords - rb_bug.translation();
+                    let closing_speed = rb_bug.linvel().dot(&towards.normalize()).max(0.0);
+                    let energy = 0.5 * rb_bug.mass() * closing_speed * closing_speed;
+
+                    if energy > 4.0 {
+                        self.prop_impacts.push(((a, b), position));
+                        impact_speeds.push(closing_speed);
+
+                        if let Some(prop_data) = self.props.get_mut(&(b as usize)) {
+                            if prop_data.absorb(energy) {
+                                self.prop_breaks.push((b as usize, position));
+                            }
+                        }
+                    }
+                }
+                PropSort::Boulder => {
+                    let boulder_speed = self
+                        .prop_handles
+                        .get(&(b as usize))
+                        .and_then(|handle| self.physics.collider_set.get(*handle))
+                        .and_then(|collider| collider.parent())
+                        .and_then(|handle| self.physics.rigid_body_set.get(handle))
+                        .map_or(0.0, |rigid_body| rigid_body.linvel().magnitude());
+
+                    if boulder_speed > 2.0 {
+                        self.prop_impacts.push(((a, b), position));
+                        impact_speeds.push(boulder_speed);
+
+                        if self.sandbox {
+                            continue;
+                        }
+
+                        if let Some((_, bug_data)) = self.get_bug_mut(a as usize) {
+                            if bug_data.health() <= 2 {
+                                bug_data.add_health(-(bug_data.health() as isize));
+                            }
+                        }
+                    }
+                }
+                PropSort::Pillar => (),
+            }
+        }
+
+        for (((a, b), position), speed) in self.prop_impacts.iter().zip(impact_speeds) {
+            self.events.push(GameEvent::PropImpact {
+                bug: *a as usize,
+                prop: *b as usize,
+                position: *position,
+                speed,
+            });
+        }
+
+        for (prop_index, position) in &self.prop_breaks {
+            if let Some(collider_handle) = self.prop_handles.remove(prop_index) {
+                self.physics.remove_prop(collider_handle);
+            }
+
+            self.events.push(GameEvent::PropBreak {
+                prop: *prop_index,
+                position: *position,
+            });
+        }
+    }
+
+    /// Reports bugs running into the walls fast enough to be heard, from their velocities going
+    /// into the tick so bugs resting against a wall stay quiet.
+    fn tick_wall_impacts(&mut self, approaches: &HashMap<usize, Vector2<f32>>) {
+        for (bug_index, position) in self.physics.wall_collisions() {
+            let bug_index = bug_index as usize;
+
+            let (Some((rigid_body, _)), Some(approach)) =
+                (self.get_bug(bug_index), approaches.get(&bug_index))
+            else {
+                continue;
+            };
+
+            let towards = position.coords - rigid_body.translation();
+
+            if towards.magnitude() == 0.0 {
+                continue;
+            }
+
+            let speed = approach.dot(&towards.normalize()).max(0.0);
+
+            if speed > WALL_IMPACT_SPEED {
+                self.events.push(GameEvent::WallImpact {
+                    bug: bug_index,
+                    position,
+                    speed,
+                });
             }
         }
     }
@@ -278,6 +1357,110 @@ impl Game {
         self.bug_impacts.clone()
     }
 
+    /// prop impacts, bug first
+    pub fn prop_impacts(&self) -> Vec<((u128, u128), Point2<f32>)> {
+        self.prop_impacts.clone()
+    }
+
+    /// props shattered during the last physics tick
+    pub fn prop_breaks(&self) -> Vec<(usize, Point2<f32>)> {
+        self.prop_breaks.clone()
+    }
+
+    /// Find the prop under the given [`Point2`].
+    pub fn intersecting_prop(&self, point: Point2<f32>) -> Option<(usize, &Collider, &PropData)> {
+        let (collider_handle, _) = self.physics.intersecting_collider(point)?;
+        let collider = self.physics.collider_set.get(collider_handle)?;
+        let prop_index = collider.user_data as usize;
+
+        self.props
+            .get(&prop_index)
+            .map(|prop_data| (prop_index, collider, prop_data))
+    }
+
+    /// Projects a bug moving along a direction, returning the index of the first bug or prop it
+    /// would collide with and the distance to it.
+    pub fn first_hit(&self, bug_index: usize, direction: Vector2<f32>) -> Option<(usize, f32)> {
+        if direction.magnitude() == 0.0 {
+            return None;
+        }
+
+        let rigid_body_handle = *self.bug_handles.get(&bug_index)?;
+        let rigid_body = self.physics.rigid_body_set.get(rigid_body_handle)?;
+
+        let (collider_handle, distance) = self.physics.first_hit(
+            *rigid_body.translation(),
+            direction,
+            0.5,
+            self.geometry.capture_radius * 6.0,
+            rigid_body_handle,
+        )?;
+
+        let collider = self.physics.collider_set.get(collider_handle)?;
+
+        Some((collider.user_data as usize, distance))
+    }
+
+    /// Estimates how far a bug travels when launched with an impulse intent, ignoring collisions,
+    /// from the closed-form decay of its velocity under its current linear damping.
+    pub fn estimated_travel(&self, bug_index: usize, impulse_intent: Vector2<f32>) -> Option<f32> {
+        let (rigid_body, _) = self.get_bug(bug_index)?;
+
+        if rigid_body.linear_damping() <= 0.0 || rigid_body.mass() <= 0.0 {
+            return None;
+        }
+
+        let speed = (impulse_intent * self.impulse_multiplier()).magnitude() / rigid_body.mass();
+
+        Some(speed / rigid_body.linear_damping())
+    }
+
+    /// Casts a ray from a bug along a direction, returning the first [`Obstruction`] it meets and
+    /// where.
+    pub fn raycast_obstruction(
+        &self,
+        bug_index: usize,
+        direction: Vector2<f32>,
+    ) -> Option<(Obstruction, Point2<f32>)> {
+        if direction.magnitude() == 0.0 {
+            return None;
+        }
+
+        let rigid_body_handle = *self.bug_handles.get(&bug_index)?;
+        let rigid_body = self.physics.rigid_body_set.get(rigid_body_handle)?;
+        let team = *self.bugs.get(&bug_index)?.team();
+
+        let (collider_handle, point) = self.physics.raycast_first(
+            Point2::from(*rigid_body.translation()),
+            direction,
+            Some(rigid_body_handle),
+        )?;
+
+        let index = self.physics.collider_set.get(collider_handle)?.user_data as usize;
+
+        let obstruction = match (self.bugs.get(&index), self.props.contains_key(&index)) {
+            (Some(bug_data), _) if *bug_data.team() == team => Obstruction::Ally(index),
+            (Some(_), _) => Obstruction::Enemy(index),
+            (None, true) => Obstruction::Prop(index),
+            (None, false) if index == BALL_USER_DATA as usize => Obstruction::Ball,
+            (None, false) => Obstruction::Wall,
+        };
+
+        Some((obstruction, point))
+    }
+
+    /// Returns the [`PropData`] of a prop still in the arena.
+    pub fn get_prop(&self, prop_index: usize) -> Option<(&Collider, &PropData)> {
+        let collider = self
+            .physics
+            .collider_set
+            .get(*self.prop_handles.get(&prop_index)?)?;
+
+        self.props
+            .get(&prop_index)
+            .map(|prop_data| (collider, prop_data))
+    }
+
     /// Find the [`Bug`] that's the closest to the given [`Point2`].
     pub fn intersecting_bug(&self, point: Point2<f32>) -> Option<(usize, &RigidBody, &BugData)> {
         if let Some((collider_handle, _)) = self.physics.intersecting_collider(point) {
@@ -335,6 +1518,38 @@ impl Game {
         }
     }
 
+    /// Returns the sorted indices of the bugs overlapping the rectangle spanned by two corners.
+    pub fn bugs_in_rect(&self, a: Point2<f32>, b: Point2<f32>) -> Vec<usize> {
+        let mut bug_indices: Vec<usize> = self
+            .physics
+            .colliders_in_rect(a, b)
+            .into_iter()
+            .filter_map(|collider_handle| self.physics.collider_set.get(collider_handle)?.parent())
+            .filter_map(|rigid_body_handle| self.physics.rigid_body_set.get(rigid_body_handle))
+            .map(|rigid_body| rigid_body.user_data as usize)
+            .filter(|bug_index| self.bugs.contains_key(bug_index))
+            .collect();
+
+        bug_indices.sort();
+        bug_indices.dedup();
+
+        bug_indices
+    }
+
+    /// Returns the sorted indices of a [`Team`]'s bugs.
+    pub fn team_bug_indices(&self, team: Team) -> Vec<usize> {
+        let mut bug_indices: Vec<usize> = self
+            .bugs
+            .iter()
+            .filter(|(_, bug_data)| *bug_data.team() == team)
+            .map(|(bug_index, _)| *bug_index)
+            .collect();
+
+        bug_indices.sort();
+
+        bug_indices
+    }
+
     /// Returns an iterator over all active [`Bugs`].
     pub fn iter_bugdata(&self) -> impl Iterator<Item = &BugData> {
         self.bugs.values()
@@ -388,23 +1603,85 @@ impl Game {
             })
     }
 
-    /// Inserts a new [`Bug`].
-    pub fn insert_prop(&mut self, translation: Vector2<f32>) -> (usize, ColliderHandle) {
-        let prop_index = self.props.len() + 0xff;
-        let collider_handle = self.physics.insert_prop(translation, prop_index);
+    /// Inserts a new prop, unless the game already holds [`MAX_PROPS`].
+    pub fn insert_prop(
+        &mut self,
+        translation: Vector2<f32>,
+        prop_data: PropData,
+    ) -> std::result::Result<(usize, ColliderHandle), LobbyError> {
+        if self.props.len() >= MAX_PROPS {
+            return Err(LobbyError::new(
+                LobbyErrorKind::OverBudget,
+                &format!("too many props, at most {MAX_PROPS}"),
+            ));
+        }
+
+        let prop_index = self.props.len() + PROP_INDEX_START;
+        let collider_handle = self
+            .physics
+            .insert_prop(translation, prop_index, *prop_data.sort());
 
-        self.props.insert(prop_index, PropData {});
+        self.props.insert(prop_index, prop_data);
+        self.prop_handles.insert(prop_index, collider_handle);
 
-        (prop_index, collider_handle)
+        Ok((prop_index, collider_handle))
+    }
+
+    /// Returns an iterator over all terrain patches.
+    pub fn iter_terrain(&self) -> impl Iterator<Item = (&Collider, &TerrainData)> {
+        self.physics
+            .collider_set
+            .iter()
+            .filter_map(|(_collider_handle, collider)| {
+                self.terrain
+                    .get(&(collider.user_data as usize))
+                    .map(|data| (collider, data))
+            })
+    }
+
+    /// Inserts a new terrain patch.
+    pub fn insert_terrain(
+        &mut self,
+        translation: Vector2<f32>,
+        terrain_data: TerrainData,
+    ) -> (usize, ColliderHandle) {
+        let terrain_index = self.terrain.len() + 0x200;
+        let collider_handle = self.physics.insert_terrain(
+            translation,
+            terrain_index,
+            terrain_data.radius(),
+            *terrain_data.sort(),
+        );
+
+        self.terrain.insert(terrain_index, terrain_data);
+
+        (terrain_index, collider_handle)
     }
 
-    /// Inserts a new [`Bug`].
+    /// Inserts a new [`Bug`], unless the game already holds [`MAX_BUGS`] or has run out of bug
+    /// indices.
     pub fn insert_bug(
         &mut self,
         translation: Vector2<f32>,
         bug_data: BugData,
-    ) -> (usize, RigidBodyHandle) {
-        let bug_index = self.bugs.len() + 0x01;
+    ) -> std::result::Result<(usize, RigidBodyHandle), LobbyError> {
+        let over_budget = |reason: &str| Err(LobbyError::new(LobbyErrorKind::OverBudget, reason));
+
+        if self.bugs.len() + self.benched.len() >= MAX_BUGS {
+            return over_budget(&format!("too many bugs, at most {MAX_BUGS}"));
+        }
+
+        // Indices follow the highest in use, as bugs taken out of the arena leave gaps
+        let bug_index = self
+            .bugs
+            .keys()
+            .chain(self.benched.keys())
+            .max()
+            .map_or(0x01, |bug_index| bug_index + 1);
+
+        if bug_index >= PROP_INDEX_START {
+            return over_budget("out of bug indices");
+        }
         let rigid_body_handle = self
             .physics
             .insert_bug(translation, bug_index, *bug_data.sort());
@@ -412,12 +1689,39 @@ impl Game {
         self.bugs.insert(bug_index, bug_data);
         self.bug_handles.insert(bug_index, rigid_body_handle);
 
-        (bug_index, rigid_body_handle)
+        Ok((bug_index, rigid_body_handle))
+    }
+
+    /// Replaces the record of an executed [`Turn`] with one of the same index, e.g. the authoritative
+    /// copy of a turn which was predicted locally.
+    pub fn amend_turn(&mut self, turn: Turn) {
+        if let Some(recorded) = self
+            .turns
+            .iter_mut()
+            .rfind(|recorded| recorded.index == turn.index)
+        {
+            *recorded = turn;
+        }
     }
 
-    /// records turns
-    pub fn queue_turns(&mut self, turns: Vec<Turn>) {
-        self.queued_turns.append(&mut VecDeque::from(turns));
+    /// Queues [`Turn`]s for execution, up to [`MAX_QUEUED_TURNS`] at once.
+    ///
+    /// Turns beyond the budget are refused while the ones before them are still queued, so a
+    /// client catching up on a long game can pick up where the queue left off.
+    pub fn queue_turns(&mut self, turns: Vec<Turn>) -> std::result::Result<(), LobbyError> {
+        let room = MAX_QUEUED_TURNS.saturating_sub(self.queued_turns.len());
+        let refused = turns.len().saturating_sub(room);
+
+        self.queued_turns.extend(turns.into_iter().take(room));
+
+        if refused > 0 {
+            return Err(LobbyError::new(
+                LobbyErrorKind::OverBudget,
+                &format!("{refused} turns refused, at most {MAX_QUEUED_TURNS} may be queued"),
+            ));
+        }
+
+        Ok(())
     }
 
     /// Shoots all [`Bug`]s forward based on their impulses.
@@ -430,18 +1734,33 @@ impl Game {
 
         if pass {
             for (i, bug_data) in &mut self.bugs {
-                if let Some(impulse_intent) = turn.impulse_intents.get(i) {
-                    bug_data.set_impulse_intent(impulse_intent.clone());
-                }
+                bug_data
+                    .set_impulse_intent(turn.impulse_intents.get(i).copied().unwrap_or_default());
             }
 
+            let impulse_multiplier = self.impulse_multiplier();
+
             for (rigid_body, data) in self.iter_bugmuts() {
-                rigid_body.apply_impulse(*data.impulse_intent() * 2.0, true)
+                rigid_body.apply_impulse(*data.impulse_intent() * impulse_multiplier, true)
+            }
+
+            // Settled once per turn while the intents are still set, as every peer applies turns
+            // here whether it ticks in real time or resolves them headlessly
+            for bug_data in self.bugs.values_mut() {
+                bug_data.spend_stamina();
+                bug_data.regen_stamina();
             }
 
             self.reset_impulses();
+            self.think_times.clear();
+            self.surrender = None;
 
             self.turns.push(turn.clone());
+
+            if self.turns.len() > MAX_TURN_HISTORY {
+                let forgotten = self.turns.remove(0);
+                self.forgotten_turns.forget(&forgotten);
+            }
         }
 
         pass
@@ -526,29 +1845,85 @@ impl Game {
         match message {
             Message::Ok => (),
             Message::Move(turn) => {
-                for (bug_index, impulse_intent) in turn.impulse_intents {
-                    if let Some(bug_data) = self.bugs.get_mut(&bug_index) {
-                        if bug_data.team() == &player.team && bug_data.health() > 1 {
-                            bug_data.set_impulse_intent(impulse_intent);
-                        }
-                    }
-                }
+                let impulse_intents = turn
+                    .impulse_intents
+                    .into_iter()
+                    .filter(|(bug_index, _)| player.controls(*bug_index))
+                    .collect();
+
+                self.set_impulse_intents(player.team, impulse_intents);
             }
             Message::TurnSync(_) => (),
             Message::Lobby(_) => (),
             Message::Lobbies(_) => (),
             Message::LobbyError(_) => (),
+            Message::Profile(_) => (),
+            Message::Status(_) => (),
+            Message::Ack(_) => (),
+            Message::Maps(_) => (),
+            Message::Bracket(_) => (),
+            Message::Chat(_) => (),
+            Message::Mute(_) => (),
+            Message::Surrender => (),
+            Message::Ready(_) => (),
+            Message::Commit(_) => (),
+            Message::Reveal(..) => (),
+            Message::Leave => (),
+            Message::LobbyExpiryWarning(_) => (),
+            Message::LevelSeed(_) => (),
         }
     }
 
-    /// num turns
+    /// Sets the impulse intents of the given [`Team`]'s bugs, ignoring other teams' and knocked out bugs.
+    pub fn set_impulse_intents(
+        &mut self,
+        team: Team,
+        impulse_intents: VecMap<usize, Vector2<f32>>,
+    ) {
+        for (bug_index, impulse_intent) in impulse_intents {
+            if let Some(bug_data) = self.bugs.get_mut(&bug_index) {
+                if bug_data.team() == &team && bug_data.health() > 1 {
+                    bug_data.set_impulse_intent(impulse_intent);
+                }
+            }
+        }
+    }
+
+    /// Lets a [`BotController`] plan the impulse intents for a [`Team`].
+    pub fn plan_bot(&mut self, team: Team, bot: &mut dyn BotController) {
+        let impulse_intents = bot.plan(&GameView::new(self, team));
+
+        self.set_impulse_intents(team, impulse_intents);
+    }
+
+    /// Fully simulates a [`Turn`] headlessly, up to the boundary where the next one is awaited.
+    pub fn resolve_turn(&mut self, turn: Turn) {
+        while !self.awaiting_turn() {
+            self.tick();
+        }
+
+        // Nothing is queued while a turn is awaited
+        let _ = self.queue_turns(vec![turn]);
+        self.tick();
+
+        while !self.awaiting_turn() {
+            self.tick();
+        }
+    }
+
+    /// Determines if the simulation is stalled at a turn boundary waiting for the next [`Turn`].
+    pub fn awaiting_turn(&self) -> bool {
+        self.queued_turns.is_empty() && self.turn_ticks() == self.turn_tick_count() - 1
+    }
+
+    /// Returns the remembered turns, at most the latest [`MAX_TURN_HISTORY`].
     pub fn turns(&self) -> &Vec<Turn> {
         &self.turns
     }
 
-    /// num turns
+    /// num turns, forgotten ones included
     pub fn turns_count(&self) -> usize {
-        self.turns.len()
+        self.forgotten_turns.turns + self.turns.len()
     }
 
     /// num turns plus queued
@@ -558,11 +1933,163 @@ impl Game {
 
     /// diameter of the capture zone
     pub fn capture_progress(&self) -> f32 {
-        self.capture_progress as f32 / self.bugs.len() as f32
+        self.capture_progress as f32 / (self.bugs.len() + self.benched.len()) as f32
+    }
+
+    /// FNV-1a hash of the turn count, capture progress and every bug's health and position, rounded
+    /// so that simulations on different platforms only differ on genuine divergence.
+    pub fn checksum(&self) -> u64 {
+        let mut values = vec![self.turns_count() as i64, self.capture_progress as i64];
+
+        if self.has_mutator(Mutator::RingOut) {
+            values.push(self.ring_out_points(Team::Red) as i64);
+            values.push(self.ring_out_points(Team::Blue) as i64);
+        }
+
+        for team in [Team::Red, Team::Blue] {
+            for bug_index in self.team_bug_indices(team) {
+                if let Some((rigid_body, bug_data)) = self.get_bug(bug_index) {
+                    values.push(bug_data.health() as i64);
+                    values.push((rigid_body.translation().x * 100.0).round() as i64);
+                    values.push((rigid_body.translation().y * 100.0).round() as i64);
+                }
+            }
+        }
+
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    /// Overrides the capture progress with an authoritative one, correcting drift.
+    pub fn sync_capture_progress(&mut self, capture_progress: f32) {
+        self.capture_progress =
+            (capture_progress * (self.bugs.len() + self.benched.len()) as f32).round() as i32;
     }
 
     /// cap rad
     pub fn capture_radius(&self) -> f32 {
-        self.capture_radius
+        self.geometry.capture_radius
+    }
+
+    /// Returns the [`ArenaGeometry`] the game is played in.
+    pub fn geometry(&self) -> &ArenaGeometry {
+        &self.geometry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{vector, Vector2};
+
+    use super::*;
+
+    fn turn(index: usize) -> Turn {
+        Turn {
+            index,
+            think_times: [(Team::Red, 1.0)].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bug_budget() {
+        let mut game = Game::default();
+
+        while game.iter_bugdata().count() < MAX_BUGS {
+            game.insert_bug(Vector2::zeros(), BugData::new(BugSort::Beetle, Team::Red))
+                .unwrap();
+        }
+
+        let error = game
+            .insert_bug(Vector2::zeros(), BugData::new(BugSort::Beetle, Team::Red))
+            .unwrap_err();
+
+        assert_eq!(error.kind, LobbyErrorKind::OverBudget);
+        assert_eq!(error.reason, format!("too many bugs, at most {MAX_BUGS}"));
+    }
+
+    #[test]
+    fn prop_budget() {
+        let mut game = Game::default();
+
+        while game.iter_props().count() < MAX_PROPS {
+            game.insert_prop(vector![0.0, 0.0], PropData::new(PropSort::Pillar))
+                .unwrap();
+        }
+
+        assert_eq!(
+            game.insert_prop(vector![0.0, 0.0], PropData::new(PropSort::Pillar))
+                .unwrap_err()
+                .kind,
+            LobbyErrorKind::OverBudget
+        );
+    }
+
+    #[test]
+    fn queued_turn_budget() {
+        let mut game = Game::default();
+
+        let error = game
+            .queue_turns((1..=MAX_QUEUED_TURNS + 3).map(turn).collect())
+            .unwrap_err();
+
+        // The turns within the budget are still queued
+        assert_eq!(error.kind, LobbyErrorKind::OverBudget);
+        assert!(error.reason.starts_with("3 turns refused"));
+        assert_eq!(game.all_turns_count(), MAX_QUEUED_TURNS);
+
+        assert!(game.queue_turns(vec![turn(MAX_QUEUED_TURNS + 1)]).is_err());
+    }
+
+    #[test]
+    fn forgotten_turn_history() {
+        let mut game = Game::default();
+        let executed = MAX_TURN_HISTORY + 10;
+
+        for index in 1..=executed {
+            assert!(game.execute_turn(&turn(index)));
+        }
+
+        assert_eq!(game.turns().len(), MAX_TURN_HISTORY);
+        assert_eq!(game.turns_count(), executed);
+        assert_eq!(game.think_time(Team::Red), executed as f32);
+
+        // Turns are looked up by their position in the whole game, forgotten ones included
+        assert!(game.turn(9).is_none());
+        assert_eq!(game.turn(10).map(|turn| turn.index), Some(11));
+        assert_eq!(
+            game.turn(executed - 1).map(|turn| turn.index),
+            Some(executed)
+        );
+        assert!(game.turn(executed).is_none());
+
+        assert_eq!(game.turns_since(0).len(), MAX_TURN_HISTORY);
+        assert_eq!(game.turns_since(executed - 2).len(), 2);
+        assert_eq!(game.turns_since(executed - 2)[0].index, executed - 1);
+        assert!(game.turns_since(executed).is_empty());
+
+        // Turns which are not newer than the last are not executed again
+        assert!(!game.execute_turn(&turn(executed)));
+        assert_eq!(game.turns_count(), executed);
+    }
+
+    #[test]
+    fn forgotten_surrender() {
+        let mut game = Game::default();
+
+        let mut surrendered = turn(1);
+        surrendered.surrendered = Some(Team::Blue);
+        game.execute_turn(&surrendered);
+
+        for index in 2..=MAX_TURN_HISTORY + 1 {
+            game.execute_turn(&turn(index));
+        }
+
+        assert!(game.turn(0).is_none());
+        assert_eq!(game.surrendered(), Some(Team::Blue));
     }
 }
@@ -0,0 +1,73 @@
+use nalgebra::Vector2;
+
+use crate::Team;
+
+/// Cells along each side of a [`CaptureHeatmap`], spanning the capture zone's bounding square.
+pub const HEATMAP_CELLS: usize = 16;
+
+/// Share of its heat a cell keeps from one scoring to the next.
+const HEATMAP_DECAY: f32 = 0.9;
+
+/// Binned history of where each team's bugs stood inside the capture zone, faded a little at
+/// every scoring so recent control weighs the most.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureHeatmap {
+    red: Vec<f32>,
+    blue: Vec<f32>,
+}
+
+impl Default for CaptureHeatmap {
+    fn default() -> Self {
+        CaptureHeatmap {
+            red: vec![0.0; HEATMAP_CELLS * HEATMAP_CELLS],
+            blue: vec![0.0; HEATMAP_CELLS * HEATMAP_CELLS],
+        }
+    }
+}
+
+impl CaptureHeatmap {
+    /// Fades all cells, then heats the cell under every given bug inside the capture zone.
+    pub fn record(
+        &mut self,
+        capture_radius: f32,
+        bugs: impl IntoIterator<Item = (Vector2<f32>, Team)>,
+    ) {
+        for heat in self.red.iter_mut().chain(self.blue.iter_mut()) {
+            *heat *= HEATMAP_DECAY;
+        }
+
+        for (position, team) in bugs {
+            if position.magnitude() >= capture_radius {
+                continue;
+            }
+
+            let cell = |coordinate: f32| {
+                (((coordinate + capture_radius) / (2.0 * capture_radius) * HEATMAP_CELLS as f32)
+                    as usize)
+                    .min(HEATMAP_CELLS - 1)
+            };
+            let index = cell(position.y) * HEATMAP_CELLS + cell(position.x);
+
+            match team {
+                Team::Red => self.red[index] += 1.0,
+                Team::Blue => self.blue[index] += 1.0,
+            }
+        }
+    }
+
+    /// Returns the Red and Blue heat of the cell at the given column and row.
+    pub fn cell(&self, column: usize, row: usize) -> (f32, f32) {
+        let index = row * HEATMAP_CELLS + column;
+
+        (self.red[index], self.blue[index])
+    }
+
+    /// Hottest cell of either team, used to normalise the map for display.
+    pub fn peak(&self) -> f32 {
+        self.red
+            .iter()
+            .chain(self.blue.iter())
+            .copied()
+            .fold(0.0, f32::max)
+    }
+}
@@ -0,0 +1,337 @@
+use std::f64::consts::TAU;
+
+use data_encoding::BASE64URL_NOPAD;
+use nalgebra::{vector, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ArenaGeometry, LobbyError, LobbyErrorKind, Personality, PropSort, TerrainData, TerrainSort,
+};
+
+/// Most props a shared map may place.
+pub const MAX_MAP_PROPS: usize = 48;
+
+/// Most terrain patches a shared map may place.
+pub const MAX_MAP_TERRAIN: usize = 16;
+
+/// Smallest and largest radius of a terrain patch on a shared map.
+const TERRAIN_RADIUS_RANGE: (f32, f32) = (0.5, 4.0);
+
+/// Strongest push a boost pad on a shared map may give.
+const MAX_BOOST: f32 = 16.0;
+
+/// Highest bug index a map may give a [`Personality`] to, the last of both teams' six bugs.
+const MAX_BUG_INDEX: usize = 12;
+
+/// Number of bugs on each team, and so of spawn slots per team in a formation.
+pub const TEAM_SIZE: usize = 6;
+
+/// Largest distance between a spawn slot and the mirror of its rival's for a formation to count
+/// as mirrored.
+const MIRROR_TOLERANCE: f32 = 0.05;
+
+/// Placement of the props and terrain of an arena, shareable as a code between players.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ArenaMap {
+    /// Position and sort of every prop.
+    pub props: Vec<(Vector2<f32>, PropSort)>,
+    /// Position and data of every terrain patch.
+    pub terrain: Vec<(Vector2<f32>, TerrainData)>,
+    /// [`Personality`] of the bugs controlled by the built-in bot, keyed by bug index.
+    #[serde(default)]
+    pub personalities: Vec<(usize, Personality)>,
+    /// Spawn slot of every bug in order of their index, the red team's six first, or none for
+    /// the default arcs, see [`ArenaMap::formation`].
+    #[serde(default)]
+    pub spawns: Vec<Vector2<f32>>,
+}
+
+impl Default for ArenaMap {
+    fn default() -> Self {
+        let mut props = Vec::new();
+
+        for i in 0..24 {
+            let offset = i;
+            let arc_size = TAU / 16.0;
+            let arc: f32 = arc_size as f32 * offset as f32;
+
+            props.push((
+                vector![
+                    0.0 + (arc * 1.0).cos() * 10.0,
+                    0.0 + (arc * 6.0).sin() * 10.0
+                ],
+                PropSort::Pillar,
+            ));
+        }
+
+        for i in 0..6 {
+            let offset = i;
+            let arc_size = TAU / 6.0;
+            let arc: f32 = arc_size as f32 * offset as f32 + std::f32::consts::PI / 6.0;
+
+            props.push((
+                vector![0.0 + (arc * 1.0).cos() * 6.0, 0.0 + (arc * 1.0).sin() * 6.0],
+                if i % 2 == 0 {
+                    PropSort::Rock
+                } else {
+                    PropSort::Pillar
+                },
+            ));
+        }
+
+        for i in 0..4 {
+            let offset = i;
+            let arc_size = TAU / 4.0;
+            let arc: f32 = arc_size as f32 * offset as f32 + std::f32::consts::PI / 8.0;
+
+            props.push((
+                vector![0.0 + (arc * 1.0).cos() * 3.0, 0.0 + (arc * 1.0).sin() * 3.0],
+                PropSort::Boulder,
+            ));
+        }
+
+        let terrain = vec![
+            (vector![-5.0, 5.5], TerrainData::new(TerrainSort::Mud, 1.5)),
+            (vector![5.0, -5.5], TerrainData::new(TerrainSort::Mud, 1.5)),
+            (vector![5.0, 5.5], TerrainData::new(TerrainSort::Ice, 1.5)),
+            (vector![-5.0, -5.5], TerrainData::new(TerrainSort::Ice, 1.5)),
+            (
+                vector![0.0, -9.0],
+                TerrainData::new(TerrainSort::Boost(vector![8.0, 0.0]), 1.0),
+            ),
+            (
+                vector![0.0, 9.0],
+                TerrainData::new(TerrainSort::Boost(vector![-8.0, 0.0]), 1.0),
+            ),
+        ];
+
+        ArenaMap {
+            props,
+            terrain,
+            personalities: Vec::new(),
+            spawns: Vec::new(),
+        }
+    }
+}
+
+/// Spawn slots of both teams in arcs facing each other across the arena, red on the right.
+fn default_formation() -> Vec<Vector2<f32>> {
+    let arc_size = 0.3;
+    let team_arc = arc_size * (TEAM_SIZE - 1) as f32;
+    let arc_offset = team_arc / 2.0;
+
+    (0..TEAM_SIZE * 2)
+        .map(|i| {
+            let offset = i % TEAM_SIZE;
+            let team_offset = if i < TEAM_SIZE {
+                -arc_offset
+            } else {
+                std::f32::consts::PI - arc_offset
+            };
+            let net_offset = team_offset + arc_size * offset as f32;
+
+            vector![
+                0.0 + (net_offset).cos() * 8.0,
+                0.0 + (net_offset).sin() * 8.0
+            ]
+        })
+        .collect()
+}
+
+impl ArenaMap {
+    /// Encodes the map into a URL-safe base64 code for sharing.
+    pub fn encode(&self) -> String {
+        BASE64URL_NOPAD.encode(serde_json::to_string(self).unwrap_or_default().as_bytes())
+    }
+
+    /// Decodes and validates a map code produced by [`ArenaMap::encode`].
+    pub fn decode(code: &str) -> Result<ArenaMap, LobbyError> {
+        let json = BASE64URL_NOPAD
+            .decode(code.trim().as_bytes())
+            .map_err(|err| {
+                LobbyError::new(
+                    LobbyErrorKind::InvalidMap,
+                    &format!("invalid map code: {err}"),
+                )
+            })?;
+
+        let map: ArenaMap = serde_json::from_slice(&json).map_err(|err| {
+            LobbyError::new(LobbyErrorKind::InvalidMap, &format!("invalid map: {err}"))
+        })?;
+
+        map.validate(&ArenaGeometry::default())?;
+
+        Ok(map)
+    }
+
+    /// Returns the spawn slot of every bug in order of their index, the red team's six first.
+    pub fn formation(&self) -> Vec<Vector2<f32>> {
+        if self.spawns.is_empty() {
+            default_formation()
+        } else {
+            self.spawns.clone()
+        }
+    }
+
+    /// Determines if every blue spawn slot mirrors its red counterpart through the centre of the
+    /// arena, so that neither team starts with an edge.
+    pub fn is_mirrored(&self) -> bool {
+        let formation = self.formation();
+        let (red, blue) = formation.split_at(TEAM_SIZE);
+
+        red.iter()
+            .zip(blue)
+            .all(|(red, blue)| (red + blue).magnitude() <= MIRROR_TOLERANCE)
+    }
+
+    /// Checks that the map fits within the arena and its limits.
+    pub fn validate(&self, geometry: &ArenaGeometry) -> Result<(), LobbyError> {
+        let invalid = |reason: &str| Err(LobbyError::new(LobbyErrorKind::InvalidMap, reason));
+
+        if self.props.len() > MAX_MAP_PROPS {
+            return invalid("too many props");
+        }
+
+        if self.terrain.len() > MAX_MAP_TERRAIN {
+            return invalid("too much terrain");
+        }
+
+        if !self
+            .personalities
+            .iter()
+            .all(|(bug_index, _)| (1..=MAX_BUG_INDEX).contains(bug_index))
+        {
+            return invalid("personality for an unknown bug");
+        }
+
+        let inside = |position: &Vector2<f32>| {
+            position.x.is_finite()
+                && position.y.is_finite()
+                && position.x.abs() < geometry.width / 2.0
+                && position.y.abs() < geometry.height / 2.0
+        };
+
+        if !self.props.iter().all(|(position, _)| inside(position)) {
+            return invalid("prop outside the arena");
+        }
+
+        if !self.spawns.is_empty() && self.spawns.len() != TEAM_SIZE * 2 {
+            return invalid("formation needs six spawn slots per team");
+        }
+
+        if !self.spawns.iter().all(inside) {
+            return invalid("spawn slot outside the arena");
+        }
+
+        for (position, terrain_data) in &self.terrain {
+            if !inside(position) {
+                return invalid("terrain outside the arena");
+            }
+
+            let radius = terrain_data.radius();
+
+            if !(TERRAIN_RADIUS_RANGE.0..=TERRAIN_RADIUS_RANGE.1).contains(&radius) {
+                return invalid("terrain radius out of range");
+            }
+
+            if let TerrainSort::Boost(push) = terrain_data.sort() {
+                if !push.magnitude().is_finite() || push.magnitude() > MAX_BOOST {
+                    return invalid("boost too strong");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(map: &ArenaMap) -> String {
+        map.validate(&ArenaGeometry::default()).unwrap_err().reason
+    }
+
+    #[test]
+    fn default_map_is_valid() {
+        let map = ArenaMap::default();
+
+        assert!(map.validate(&ArenaGeometry::default()).is_ok());
+        assert!(map.is_mirrored());
+        assert_eq!(
+            ArenaMap::decode(&map.encode()).unwrap().props.len(),
+            map.props.len()
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let map = ArenaMap {
+            props: vec![(vector![0.0, 0.0], PropSort::Rock); MAX_MAP_PROPS + 1],
+            ..Default::default()
+        };
+        assert_eq!(reason(&map), "too many props");
+
+        let map = ArenaMap {
+            terrain: vec![
+                (vector![0.0, 0.0], TerrainData::new(TerrainSort::Mud, 1.0));
+                MAX_MAP_TERRAIN + 1
+            ],
+            ..Default::default()
+        };
+        assert_eq!(reason(&map), "too much terrain");
+
+        let map = ArenaMap {
+            personalities: vec![(0, Personality::Camper)],
+            ..Default::default()
+        };
+        assert_eq!(reason(&map), "personality for an unknown bug");
+
+        let map = ArenaMap {
+            spawns: vec![vector![1.0, 0.0]; TEAM_SIZE],
+            ..Default::default()
+        };
+        assert_eq!(reason(&map), "formation needs six spawn slots per team");
+    }
+
+    #[test]
+    fn placement() {
+        let mut map = ArenaMap::default();
+        map.props.push((vector![f32::NAN, 0.0], PropSort::Rock));
+        assert_eq!(reason(&map), "prop outside the arena");
+
+        let map = ArenaMap {
+            spawns: vec![vector![1000.0, 0.0]; TEAM_SIZE * 2],
+            ..Default::default()
+        };
+        assert_eq!(reason(&map), "spawn slot outside the arena");
+
+        let mut map = ArenaMap::default();
+        map.terrain.push((
+            vector![0.0, 0.0],
+            TerrainData::new(TerrainSort::Ice, TERRAIN_RADIUS_RANGE.1 + 1.0),
+        ));
+        assert_eq!(reason(&map), "terrain radius out of range");
+
+        let mut map = ArenaMap::default();
+        map.terrain.push((
+            vector![0.0, 0.0],
+            TerrainData::new(TerrainSort::Boost(vector![MAX_BOOST, 1.0]), 1.0),
+        ));
+        assert_eq!(reason(&map), "boost too strong");
+    }
+
+    #[test]
+    fn invalid_codes() {
+        let mut map = ArenaMap::default();
+        map.props.push((vector![1000.0, 0.0], PropSort::Rock));
+
+        for code in ["not base64!".to_string(), "e30".to_string(), map.encode()] {
+            assert_eq!(
+                ArenaMap::decode(&code).unwrap_err().kind,
+                LobbyErrorKind::InvalidMap
+            );
+        }
+    }
+}
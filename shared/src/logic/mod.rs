@@ -1,13 +1,33 @@
+mod arena;
+mod bot;
 mod bug;
+mod event;
+mod field;
 mod game;
+mod heatmap;
+mod map;
+mod mode;
+mod mutator;
 mod physics;
+mod practice;
 mod prop;
 mod team;
+mod terrain;
 mod turn;
 
+pub use arena::*;
+pub use bot::*;
 pub use bug::*;
+pub use event::*;
+pub use field::*;
 pub use game::*;
+pub use heatmap::*;
+pub use map::*;
+pub use mode::*;
+pub use mutator::*;
 pub use physics::*;
+pub use practice::*;
 pub use prop::*;
 pub use team::*;
+pub use terrain::*;
 pub use turn::*;
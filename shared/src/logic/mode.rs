@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+/// Distance the walls close in by at the end of every turn under [`GameMode::Sumo`].
+pub const SUMO_RING_SHRINK: f32 = 0.75;
+
+/// Furthest the walls close in under [`GameMode::Sumo`], leaving room for a final standoff.
+pub const SUMO_MAX_INSET: f32 = 7.5;
+
+/// Goals that win a game under [`GameMode::Soccer`].
+pub const SOCCER_GOALS_TO_WIN: u32 = 3;
+
+/// Half the width of the mouth of each goal under [`GameMode::Soccer`].
+pub const SOCCER_GOAL_HALF_WIDTH: f32 = 3.0;
+
+/// Radius of the ball under [`GameMode::Soccer`].
+pub const SOCCER_BALL_RADIUS: f32 = 0.4;
+
+/// Mass of the ball, lighter than any bug so every flick sends it flying.
+pub const SOCCER_BALL_MASS: f32 = 0.3;
+
+/// Restitution of the ball.
+pub const SOCCER_BALL_RESTITUTION: f32 = 0.9;
+
+/// Objective a game is played for.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Copy, Clone, Hash, Default)]
+pub enum GameMode {
+    /// Hold the capture zone until the capture bar tips fully to one side.
+    #[default]
+    Capture,
+    /// There is no capture zone, the walls close in every turn crushing the bugs caught against
+    /// them, and the last team with bugs in the ring wins.
+    Sumo,
+    /// There is no capture zone, bugs knock a ball around instead and the first team to score
+    /// [`SOCCER_GOALS_TO_WIN`] goals wins.
+    Soccer,
+}
+
+impl GameMode {
+    /// All modes, in the order they are listed.
+    pub const ALL: [GameMode; 3] = [GameMode::Capture, GameMode::Sumo, GameMode::Soccer];
+
+    /// Name of the mode for menus.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Capture => "Capture",
+            GameMode::Sumo => "Sumo",
+            GameMode::Soccer => "Soccer",
+        }
+    }
+
+    /// The mode listed after this one, wrapping around.
+    pub fn next(&self) -> GameMode {
+        let index = GameMode::ALL
+            .iter()
+            .position(|mode| mode == self)
+            .unwrap_or_default();
+
+        GameMode::ALL[(index + 1) % GameMode::ALL.len()]
+    }
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+/// Pull towards the centre of the arena under [`Mutator::LowGravity`].
+pub const LOW_GRAVITY_PULL: f32 = 1.5;
+
+/// Distance within which a team sees rival bugs under [`Mutator::FogOfWar`].
+pub const FOG_OF_WAR_RADIUS: f32 = 6.0;
+
+/// Radius of the outer ring under [`Mutator::RingOut`], just inside the walls along the middle of
+/// each side so that bugs are mostly shoved out past it into the corners.
+pub const RING_OUT_RADIUS: f32 = 9.5;
+
+/// Turns a bug shoved out of the ring sits out under [`Mutator::RingOut`] before it returns.
+pub const RING_OUT_TURNS: usize = 1;
+
+/// A custom rule a lobby can be played under, on top of its arena and force field.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Copy, Clone, Hash)]
+pub enum Mutator {
+    /// Impulses launch bugs twice as hard.
+    DoubleImpulse,
+    /// A gentle pull towards the centre of the arena.
+    LowGravity,
+    /// Every bug is knocked out by a single hit.
+    OneHealth,
+    /// The arena has no props.
+    NoProps,
+    /// Rival bugs are hidden unless one of your own bugs is near.
+    FogOfWar,
+    /// Shoving a rival bug out of the outer ring scores a point, and the bug sits out a turn.
+    RingOut,
+}
+
+impl Mutator {
+    /// All mutators, in the order they are listed.
+    pub const ALL: [Mutator; 6] = [
+        Mutator::DoubleImpulse,
+        Mutator::LowGravity,
+        Mutator::OneHealth,
+        Mutator::NoProps,
+        Mutator::FogOfWar,
+        Mutator::RingOut,
+    ];
+
+    /// Name of the mutator for menus.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mutator::DoubleImpulse => "Double impulse",
+            Mutator::LowGravity => "Low gravity",
+            Mutator::OneHealth => "One health",
+            Mutator::NoProps => "No props",
+            Mutator::FogOfWar => "Fog of war",
+            Mutator::RingOut => "Ring out",
+        }
+    }
+
+    /// Short tag of the mutator for badges.
+    pub fn badge(&self) -> &'static str {
+        match self {
+            Mutator::DoubleImpulse => "2x",
+            Mutator::LowGravity => "Grav",
+            Mutator::OneHealth => "1HP",
+            Mutator::NoProps => "Bare",
+            Mutator::FogOfWar => "Fog",
+            Mutator::RingOut => "Out",
+        }
+    }
+}
@@ -1,16 +1,37 @@
+use std::collections::HashMap;
+
 use itertools::Itertools;
-use nalgebra::{vector, Point, Point2, Vector2};
+use nalgebra::{vector, Isometry2, Point, Point2, Vector2};
 use rapier2d::{
     dynamics::{
         CCDSolver, ImpulseJointSet, IntegrationParameters, IslandManager, MultibodyJointSet,
-        RigidBodyBuilder, RigidBodyHandle, RigidBodySet,
+        RigidBodyActivation, RigidBodyBuilder, RigidBodyHandle, RigidBodySet,
     },
-    geometry::{BroadPhase, ColliderBuilder, ColliderSet, ContactData, NarrowPhase},
+    geometry::{Ball, BroadPhase, ColliderBuilder, ColliderSet, ContactData, Cuboid, NarrowPhase},
     pipeline::PhysicsPipeline,
-    prelude::{ColliderHandle, PointProjection, QueryFilter, QueryPipeline},
+    prelude::{ColliderHandle, PointProjection, QueryFilter, QueryPipeline, Ray},
+};
+
+use crate::{
+    balance, ArenaGeometry, BugSort, ForceField, PropSort, TerrainSort, SOCCER_BALL_MASS,
+    SOCCER_BALL_RADIUS, SOCCER_BALL_RESTITUTION,
 };
 
-use crate::BugSort;
+/// User data of the ball's [`RigidBody`] and collider, clear of the ranges of bugs and props.
+pub const BALL_USER_DATA: u128 = 0x200;
+
+/// How soon bodies fall asleep, well within the resolution of a turn rather than rapier's default
+/// of two seconds.
+///
+/// Spin alone keeps no body awake, as bugs face along their velocity rather than their rotation.
+fn resting_activation() -> RigidBodyActivation {
+    RigidBodyActivation {
+        linear_threshold: balance().sleep_linear_threshold,
+        angular_threshold: f32::INFINITY,
+        time_until_sleep: balance().sleep_delay,
+        ..RigidBodyActivation::active()
+    }
+}
 
 /// Wrapper for rapier2d.
 pub struct Physics {
@@ -28,6 +49,11 @@ pub struct Physics {
     /// TODO docs
     pub collider_set: ColliderSet,
     query_pipeline: QueryPipeline,
+    terrain: Vec<(ColliderHandle, TerrainSort)>,
+    /// Top, bottom, right and left walls.
+    walls: Vec<ColliderHandle>,
+    force_field: ForceField,
+    central_pull: f32,
 }
 
 impl Physics {
@@ -38,24 +64,16 @@ impl Physics {
         index: usize,
         bug_sort: BugSort,
     ) -> RigidBodyHandle {
-        let mass = match bug_sort {
-            BugSort::Beetle => 1.0,
-            BugSort::Ladybug => 0.9,
-            BugSort::Ant => 0.6,
-        };
-
-        let restitution = match bug_sort {
-            BugSort::Beetle => 0.7,
-            BugSort::Ladybug => 0.75,
-            BugSort::Ant => 0.95,
-        };
+        let mass = bug_sort.mass();
+        let restitution = bug_sort.restitution();
 
-        let rigid_body = RigidBodyBuilder::dynamic()
+        let mut rigid_body = RigidBodyBuilder::dynamic()
             .ccd_enabled(true)
             .translation(translation)
-            .linear_damping(1.5)
+            .linear_damping(balance().base_linear_damping)
             .user_data(index as u128)
             .build();
+        *rigid_body.activation_mut() = resting_activation();
 
         let collider = ColliderBuilder::ball(0.5)
             .restitution(restitution)
@@ -70,16 +88,191 @@ impl Physics {
 
         ball_body_handle
     }
-    /// Inserts a new [`RigidBody`] for a [`Bug`].
-    pub fn insert_prop(&mut self, translation: Vector2<f32>, index: usize) -> ColliderHandle {
-        let collider = ColliderBuilder::ball(0.5)
-            .restitution(0.7)
+    /// Inserts the ball's [`RigidBody`].
+    pub fn insert_ball(&mut self, translation: Vector2<f32>) -> RigidBodyHandle {
+        let mut rigid_body = RigidBodyBuilder::dynamic()
+            .ccd_enabled(true)
+            .translation(translation)
+            .linear_damping(balance().base_linear_damping)
+            .user_data(BALL_USER_DATA)
+            .build();
+        *rigid_body.activation_mut() = resting_activation();
+
+        let collider = ColliderBuilder::ball(SOCCER_BALL_RADIUS)
+            .restitution(SOCCER_BALL_RESTITUTION)
+            .mass(SOCCER_BALL_MASS)
+            .user_data(BALL_USER_DATA)
+            .build();
+
+        let rigid_body_handle = self.rigid_body_set.insert(rigid_body);
+
+        self.collider_set
+            .insert_with_parent(collider, rigid_body_handle, &mut self.rigid_body_set);
+
+        rigid_body_handle
+    }
+
+    /// Inserts a new sensor [`Collider`] for a goal.
+    pub fn insert_goal(
+        &mut self,
+        translation: Vector2<f32>,
+        half_extents: Vector2<f32>,
+    ) -> ColliderHandle {
+        let collider = ColliderBuilder::cuboid(half_extents.x, half_extents.y)
+            .sensor(true)
+            .translation(translation)
+            .build();
+
+        self.collider_set.insert(collider)
+    }
+
+    /// Determines if any collider of a [`RigidBody`] overlaps a sensor [`Collider`].
+    pub fn overlaps(
+        &self,
+        sensor_handle: ColliderHandle,
+        rigid_body_handle: RigidBodyHandle,
+    ) -> bool {
+        self.rigid_body_set
+            .get(rigid_body_handle)
+            .is_some_and(|rigid_body| {
+                rigid_body.colliders().iter().any(|collider_handle| {
+                    self.narrow_phase
+                        .intersection_pair(sensor_handle, *collider_handle)
+                        == Some(true)
+                })
+            })
+    }
+
+    /// Puts a [`RigidBody`] back at rest at a position.
+    pub fn reset_body(&mut self, rigid_body_handle: RigidBodyHandle, translation: Vector2<f32>) {
+        if let Some(rigid_body) = self.rigid_body_set.get_mut(rigid_body_handle) {
+            rigid_body.set_translation(translation, true);
+            rigid_body.set_linvel(Vector2::zeros(), true);
+            rigid_body.set_angvel(0.0, true);
+        }
+    }
+
+    /// Removes a bug's [`RigidBody`] along with its collider.
+    pub fn remove_bug(&mut self, rigid_body_handle: RigidBodyHandle) {
+        self.rigid_body_set.remove(
+            rigid_body_handle,
+            &mut self.island_manager,
+            &mut self.collider_set,
+            &mut self.impulse_joint_set,
+            &mut self.multibody_joint_set,
+            true,
+        );
+    }
+
+    /// Inserts a new [`Collider`] for a prop, attached to a [`RigidBody`] if it can move.
+    pub fn insert_prop(
+        &mut self,
+        translation: Vector2<f32>,
+        index: usize,
+        prop_sort: PropSort,
+    ) -> ColliderHandle {
+        match prop_sort {
+            PropSort::Boulder => {
+                let mut rigid_body = RigidBodyBuilder::dynamic()
+                    .ccd_enabled(true)
+                    .translation(translation)
+                    .linear_damping(balance().base_linear_damping)
+                    .angular_damping(balance().boulder_angular_damping)
+                    .user_data(index as u128)
+                    .build();
+                *rigid_body.activation_mut() = resting_activation();
+
+                let collider = ColliderBuilder::ball(0.7)
+                    .restitution(balance().boulder_restitution)
+                    .mass(prop_sort.mass())
+                    .user_data(index as u128)
+                    .build();
+
+                let rigid_body_handle = self.rigid_body_set.insert(rigid_body);
+
+                self.collider_set.insert_with_parent(
+                    collider,
+                    rigid_body_handle,
+                    &mut self.rigid_body_set,
+                )
+            }
+            PropSort::Pillar | PropSort::Rock => {
+                let collider = ColliderBuilder::ball(0.5)
+                    .restitution(balance().prop_restitution)
+                    .user_data(index as u128)
+                    .translation(translation)
+                    .build();
+
+                self.collider_set.insert(collider)
+            }
+        }
+    }
+
+    /// Removes a prop [`Collider`] and its [`RigidBody`], if any.
+    pub fn remove_prop(&mut self, collider_handle: ColliderHandle) {
+        if let Some(collider) = self.collider_set.remove(
+            collider_handle,
+            &mut self.island_manager,
+            &mut self.rigid_body_set,
+            true,
+        ) {
+            if let Some(parent_handle) = collider.parent() {
+                self.rigid_body_set.remove(
+                    parent_handle,
+                    &mut self.island_manager,
+                    &mut self.collider_set,
+                    &mut self.impulse_joint_set,
+                    &mut self.multibody_joint_set,
+                    true,
+                );
+            }
+        }
+    }
+
+    /// Inserts a new sensor [`Collider`] for a terrain patch.
+    pub fn insert_terrain(
+        &mut self,
+        translation: Vector2<f32>,
+        index: usize,
+        radius: f32,
+        terrain_sort: TerrainSort,
+    ) -> ColliderHandle {
+        let collider = ColliderBuilder::ball(radius)
+            .sensor(true)
             .user_data(index as u128)
             .translation(translation)
             .build();
-        let ball_body_handle = self.collider_set.insert(collider);
+        let collider_handle = self.collider_set.insert(collider);
 
-        ball_body_handle
+        self.terrain.push((collider_handle, terrain_sort));
+
+        collider_handle
+    }
+
+    /// Moves the walls in from the edges of the given [`ArenaGeometry`] by an inset.
+    pub fn set_wall_inset(&mut self, geometry: &ArenaGeometry, inset: f32) {
+        let half_width = geometry.width / 2.0 - inset;
+        let half_height = geometry.height / 2.0 - inset;
+
+        let translations = [
+            vector![0.0, -half_height],
+            vector![0.0, half_height],
+            vector![half_width, 0.0],
+            vector![-half_width, 0.0],
+        ];
+
+        for (wall_handle, translation) in self.walls.iter().zip(translations) {
+            if let Some(collider) = self.collider_set.get_mut(*wall_handle) {
+                collider.set_translation(translation);
+            }
+        }
+
+        // Bodies resting against the old walls are pushed by the new ones on the next step
+        for (_, rigid_body) in self.rigid_body_set.iter_mut() {
+            if rigid_body.is_dynamic() {
+                rigid_body.wake_up(true);
+            }
+        }
     }
 
     /// TODO docs
@@ -100,6 +293,100 @@ impl Physics {
             &(),
             &(),
         );
+
+        self.apply_terrain();
+        self.apply_force_field();
+    }
+
+    /// Determines if every dynamic body is asleep, so stepping the simulation would change nothing.
+    pub fn all_asleep(&self) -> bool {
+        self.rigid_body_set
+            .iter()
+            .all(|(_, rigid_body)| !rigid_body.is_dynamic() || rigid_body.is_sleeping())
+    }
+
+    /// Sets the global [`ForceField`].
+    pub fn set_force_field(&mut self, force_field: ForceField) {
+        self.force_field = force_field;
+    }
+
+    /// Sets an extra pull towards the centre of the arena, on top of the [`ForceField`].
+    pub fn set_central_pull(&mut self, central_pull: f32) {
+        self.central_pull = central_pull;
+    }
+
+    /// Pushes all dynamic bodies along the [`ForceField`] and the central pull.
+    fn apply_force_field(&mut self) {
+        if self.force_field == ForceField::Calm && self.central_pull == 0.0 {
+            return;
+        }
+
+        let central_pull = ForceField::Current {
+            pull: self.central_pull,
+            swirl: 0.0,
+        };
+
+        let dt = self.integration_parameters.dt;
+
+        for (_, rigid_body) in self.rigid_body_set.iter_mut() {
+            if rigid_body.is_dynamic() {
+                let acceleration = self.force_field.acceleration_at(rigid_body.translation())
+                    + central_pull.acceleration_at(rigid_body.translation());
+
+                rigid_body.apply_impulse(acceleration * rigid_body.mass() * dt, true);
+            }
+        }
+    }
+
+    /// Applies terrain modifiers to all bodies overlapping a terrain patch.
+    fn apply_terrain(&mut self) {
+        let dt = self.integration_parameters.dt;
+
+        let mut dampings = HashMap::new();
+        let mut boosts = Vec::new();
+
+        for (terrain_handle, terrain_sort) in &self.terrain {
+            for (ch_a, ch_b, intersecting) in self.narrow_phase.intersections_with(*terrain_handle)
+            {
+                if !intersecting {
+                    continue;
+                }
+
+                let other_handle = if ch_a == *terrain_handle { ch_b } else { ch_a };
+
+                if let Some(parent_handle) = self
+                    .collider_set
+                    .get(other_handle)
+                    .and_then(|collider| collider.parent())
+                {
+                    match terrain_sort {
+                        TerrainSort::Boost(direction) => boosts.push((parent_handle, *direction)),
+                        _ => {
+                            dampings.insert(parent_handle, terrain_sort.linear_damping());
+                        }
+                    }
+                }
+            }
+        }
+
+        for (rigid_body_handle, rigid_body) in self.rigid_body_set.iter_mut() {
+            if rigid_body.is_dynamic() {
+                let damping = dampings
+                    .get(&rigid_body_handle)
+                    .copied()
+                    .unwrap_or(balance().base_linear_damping);
+
+                if rigid_body.linear_damping() != damping {
+                    rigid_body.set_linear_damping(damping);
+                }
+            }
+        }
+
+        for (rigid_body_handle, direction) in boosts {
+            if let Some(rigid_body) = self.rigid_body_set.get_mut(rigid_body_handle) {
+                rigid_body.apply_impulse(direction * dt, true);
+            }
+        }
     }
 
     /// Retrieves the closest [`ColliderHandle`] which intersects with a [`Point2`].
@@ -108,7 +395,7 @@ impl Physics {
         point: Point2<f32>,
     ) -> Option<(ColliderHandle, PointProjection)> {
         let solid = true;
-        let filter = QueryFilter::default();
+        let filter = QueryFilter::default().exclude_sensors();
 
         self.query_pipeline
             .project_point(
@@ -127,6 +414,85 @@ impl Physics {
             })
     }
 
+    /// Retrieves every solid [`ColliderHandle`] overlapping the rectangle spanned by two corners.
+    pub fn colliders_in_rect(&self, a: Point2<f32>, b: Point2<f32>) -> Vec<ColliderHandle> {
+        let filter = QueryFilter::default().exclude_sensors();
+        let centre = nalgebra::center(&a, &b);
+        let half_extents = (b - a).abs() / 2.0;
+
+        let mut collider_handles = Vec::new();
+
+        self.query_pipeline.intersections_with_shape(
+            &self.rigid_body_set,
+            &self.collider_set,
+            &Isometry2::translation(centre.x, centre.y),
+            &Cuboid::new(half_extents),
+            filter,
+            |collider_handle| {
+                collider_handles.push(collider_handle);
+                true
+            },
+        );
+
+        collider_handles
+    }
+
+    /// Sweeps a ball from a position along a direction, returning the first solid [`ColliderHandle`]
+    /// it would touch and the distance travelled until then.
+    pub fn first_hit(
+        &self,
+        position: Vector2<f32>,
+        direction: Vector2<f32>,
+        radius: f32,
+        max_distance: f32,
+        exclude: RigidBodyHandle,
+    ) -> Option<(ColliderHandle, f32)> {
+        let filter = QueryFilter::default()
+            .exclude_sensors()
+            .exclude_rigid_body(exclude);
+
+        self.query_pipeline
+            .cast_shape(
+                &self.rigid_body_set,
+                &self.collider_set,
+                &Isometry2::translation(position.x, position.y),
+                &direction.normalize(),
+                &Ball::new(radius),
+                max_distance,
+                true,
+                filter,
+            )
+            .map(|(collider_handle, toi)| (collider_handle, toi.toi))
+    }
+
+    /// Casts a ray from a point along a direction, returning the first solid [`ColliderHandle`] it
+    /// crosses and where, ignoring the colliders of the `exclude` body.
+    pub fn raycast_first(
+        &self,
+        point: Point2<f32>,
+        direction: Vector2<f32>,
+        exclude: Option<RigidBodyHandle>,
+    ) -> Option<(ColliderHandle, Point2<f32>)> {
+        let mut filter = QueryFilter::default().exclude_sensors();
+
+        if let Some(exclude) = exclude {
+            filter = filter.exclude_rigid_body(exclude);
+        }
+
+        let ray = Ray::new(point, direction.normalize());
+
+        self.query_pipeline
+            .cast_ray(
+                &self.rigid_body_set,
+                &self.collider_set,
+                &ray,
+                f32::MAX,
+                true,
+                filter,
+            )
+            .map(|(collider_handle, toi)| (collider_handle, ray.point_at(toi)))
+    }
+
     /// Returns the contact pairs for all bug colliders
     pub fn bug_collisions(&self) -> Vec<((u128, u128), Point2<f32>)> {
         let bug_colliders: Vec<_> = self
@@ -153,6 +519,77 @@ impl Physics {
 
         contacts
     }
+
+    /// Returns the contacts between bug colliders and the walls, by the user data of the bug.
+    pub fn wall_collisions(&self) -> Vec<(u128, Point2<f32>)> {
+        let mut contacts = Vec::new();
+
+        for wall_handle in &self.walls {
+            for contact_pair in self.narrow_phase.contacts_with(*wall_handle) {
+                if !contact_pair.has_any_active_contact {
+                    continue;
+                }
+
+                let other_handle = if contact_pair.collider1 == *wall_handle {
+                    contact_pair.collider2
+                } else {
+                    contact_pair.collider1
+                };
+
+                if let Some(other) = self.collider_set.get(other_handle) {
+                    if (0x01..0xff).contains(&other.user_data) {
+                        if let Some((contact_manifold, _)) = contact_pair.find_deepest_contact() {
+                            if let Some(solver_contact) =
+                                contact_manifold.data.solver_contacts.first()
+                            {
+                                contacts.push((other.user_data, solver_contact.point));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        contacts
+    }
+
+    /// Returns the contact pairs between bug and prop colliders, bug first.
+    pub fn prop_collisions(&self) -> Vec<((u128, u128), Point2<f32>)> {
+        let mut contacts = Vec::new();
+
+        for (collider_handle, collider) in self.collider_set.iter() {
+            if !(0x01..0xff).contains(&collider.user_data) {
+                continue;
+            }
+
+            for contact_pair in self.narrow_phase.contacts_with(collider_handle) {
+                if !contact_pair.has_any_active_contact {
+                    continue;
+                }
+
+                let other_handle = if contact_pair.collider1 == collider_handle {
+                    contact_pair.collider2
+                } else {
+                    contact_pair.collider1
+                };
+
+                if let Some(other) = self.collider_set.get(other_handle) {
+                    if (0xff..0x200).contains(&other.user_data) {
+                        if let Some((contact_manifold, _)) = contact_pair.find_deepest_contact() {
+                            for solver_contact in &contact_manifold.data.solver_contacts {
+                                contacts.push((
+                                    (collider.user_data, other.user_data),
+                                    solver_contact.point,
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        contacts
+    }
 }
 
 impl Clone for Physics {
@@ -170,12 +607,23 @@ impl Clone for Physics {
             rigid_body_set: self.rigid_body_set.clone(),
             collider_set: self.collider_set.clone(),
             query_pipeline: self.query_pipeline.clone(),
+            terrain: self.terrain.clone(),
+            walls: self.walls.clone(),
+            force_field: self.force_field,
+            central_pull: self.central_pull,
         }
     }
 }
 
 impl Default for Physics {
     fn default() -> Physics {
+        Physics::new(&ArenaGeometry::default())
+    }
+}
+
+impl Physics {
+    /// Creates an empty physics world walled in along the edges of the given [`ArenaGeometry`].
+    pub fn new(geometry: &ArenaGeometry) -> Physics {
         let rigid_body_set = RigidBodySet::new();
         let collider_set = ColliderSet::new();
         let gravity = vector![0.0, 0.0];
@@ -202,34 +650,38 @@ impl Default for Physics {
             rigid_body_set,
             collider_set,
             query_pipeline,
+            terrain: Vec::new(),
+            walls: Vec::new(),
+            force_field: ForceField::default(),
+            central_pull: 0.0,
         };
 
-        let map_width = 23.0;
-        let map_height = 23.0;
+        let map_width = geometry.width;
+        let map_height = geometry.height;
 
         /* Create the ground. */
         let collider = ColliderBuilder::cuboid(map_width / 2.0, 0.5)
             .translation(vector![0.0, -map_height / 2.0])
             .build();
-        physics.collider_set.insert(collider);
+        physics.walls.push(physics.collider_set.insert(collider));
 
         /* Create the ground. */
         let collider = ColliderBuilder::cuboid(map_width / 2.0, 0.5)
             .translation(vector![0.0, map_height / 2.0])
             .build();
-        physics.collider_set.insert(collider);
+        physics.walls.push(physics.collider_set.insert(collider));
 
         /* Create the ground. */
         let collider = ColliderBuilder::cuboid(0.5, map_height / 2.0)
             .translation(vector![map_width / 2.0, 0.0])
             .build();
-        physics.collider_set.insert(collider);
+        physics.walls.push(physics.collider_set.insert(collider));
 
         /* Create the ground. */
         let collider = ColliderBuilder::cuboid(0.5, map_height / 2.0)
             .translation(vector![-map_width / 2.0, 0.0])
             .build();
-        physics.collider_set.insert(collider);
+        physics.walls.push(physics.collider_set.insert(collider));
 
         physics
     }
@@ -0,0 +1,238 @@
+use nalgebra::Vector2;
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha8Rng,
+};
+
+use crate::{BugData, BugSort, Game, Team, VecMap};
+
+/// Seconds a round of [`TargetPractice`] lasts.
+pub const PRACTICE_SECONDS: u64 = 60;
+
+/// Flicks a round of [`TargetPractice`] starts with.
+pub const PRACTICE_FLICKS: u32 = 8;
+
+/// Flicks awarded for clearing a wave of [`TargetPractice`].
+pub const PRACTICE_WAVE_FLICKS: u32 = 3;
+
+/// Points for knocking over a training dummy.
+pub const DUMMY_POINTS: u32 = 100;
+
+/// Points for bringing a bug to rest on a [`Bullseye`].
+pub const BULLSEYE_POINTS: u32 = 200;
+
+/// Points for clearing a wave, multiplied by the number of the wave.
+pub const WAVE_POINTS: u32 = 150;
+
+/// Distance kept between spawned targets, the bugs and the walls.
+const SPAWN_CLEARANCE: f32 = 2.0;
+
+/// Attempts at finding a clear spot for a target before giving up on it.
+const SPAWN_ATTEMPTS: usize = 32;
+
+/// A zone scoring [`BULLSEYE_POINTS`] once one of the player's bugs comes to rest in it.
+#[derive(Debug, Clone, Copy)]
+pub struct Bullseye {
+    /// Centre of the zone.
+    pub position: Vector2<f32>,
+    /// Radius of the zone, smaller in later waves.
+    pub radius: f32,
+}
+
+/// A single-player round of flicking Red's bugs at stationary dummies and into bullseye zones,
+/// spawned in waves, with limited flicks and time.
+///
+/// The round plays out in a sandbox [`Game`] created by [`TargetPractice::arena`], which the
+/// round is ticked along with.
+#[derive(Clone)]
+pub struct TargetPractice {
+    rng: ChaCha8Rng,
+    wave: usize,
+    score: u32,
+    flicks_left: u32,
+    ticks: u64,
+    dummies: Vec<usize>,
+    bullseyes: Vec<Bullseye>,
+    finished: bool,
+}
+
+impl TargetPractice {
+    /// Starts a round whose targets are placed from the given seed.
+    pub fn new(seed: u64) -> TargetPractice {
+        TargetPractice {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            wave: 0,
+            score: 0,
+            flicks_left: PRACTICE_FLICKS,
+            ticks: 0,
+            dummies: Vec::new(),
+            bullseyes: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Creates the arena of the round with its first wave, an empty sandbox with only Red's bugs.
+    pub fn arena(&mut self) -> Game {
+        let mut game = Game::practice_arena();
+
+        self.spawn_wave(&mut game);
+
+        game
+    }
+
+    /// Launches bugs of the player at once for a single flick, if any are left.
+    pub fn flick(&mut self, game: &mut Game, impulse_intents: VecMap<usize, Vector2<f32>>) {
+        if self.finished || self.flicks_left == 0 || impulse_intents.is_empty() {
+            return;
+        }
+
+        self.flicks_left -= 1;
+
+        for (bug_index, impulse_intent) in impulse_intents {
+            game.flick(bug_index, impulse_intent);
+        }
+    }
+
+    /// Scores the dummies knocked over and the bullseyes reached in the game's last tick, spawning
+    /// the next wave once the current one is cleared.
+    pub fn tick(&mut self, game: &mut Game) {
+        if self.finished {
+            return;
+        }
+
+        self.ticks += 1;
+
+        let knocked: Vec<usize> = game
+            .bug_impacts()
+            .into_iter()
+            .map(|((_, defender), _)| defender as usize)
+            .filter(|defender| self.dummies.contains(defender))
+            .collect();
+
+        for dummy in knocked {
+            if let Some(position) = self
+                .dummies
+                .iter()
+                .position(|bug_index| *bug_index == dummy)
+            {
+                self.dummies.swap_remove(position);
+                self.score += DUMMY_POINTS;
+                game.remove_bug(dummy);
+            }
+        }
+
+        if game.at_rest() {
+            let resting: Vec<Vector2<f32>> = game
+                .iter_bugs()
+                .filter(|(_, bug_data)| *bug_data.team() == Team::Red)
+                .map(|(rigid_body, _)| *rigid_body.translation())
+                .collect();
+
+            let before = self.bullseyes.len();
+
+            self.bullseyes.retain(|bullseye| {
+                !resting
+                    .iter()
+                    .any(|position| (position - bullseye.position).magnitude() < bullseye.radius)
+            });
+
+            self.score += (before - self.bullseyes.len()) as u32 * BULLSEYE_POINTS;
+        }
+
+        if self.dummies.is_empty() && self.bullseyes.is_empty() {
+            self.score += WAVE_POINTS * self.wave as u32;
+            self.flicks_left += PRACTICE_WAVE_FLICKS;
+            self.spawn_wave(game);
+        }
+
+        // The last flick still scores once everything has come to rest
+        self.finished =
+            self.ticks >= PRACTICE_SECONDS * 60 || (self.flicks_left == 0 && game.at_rest());
+    }
+
+    /// Places the dummies and bullseyes of the next wave, more and smaller ones every wave.
+    fn spawn_wave(&mut self, game: &mut Game) {
+        self.wave += 1;
+
+        for _ in 0..(1 + self.wave).min(6) {
+            if let Some(position) = self.clear_spot(game) {
+                if let Ok((bug_index, _)) =
+                    game.insert_bug(position, BugData::new(BugSort::Beetle, Team::Blue))
+                {
+                    self.dummies.push(bug_index);
+                }
+            }
+        }
+
+        for _ in 0..(1 + self.wave / 2).min(3) {
+            if let Some(position) = self.clear_spot(game) {
+                self.bullseyes.push(Bullseye {
+                    position,
+                    radius: (2.0 - self.wave as f32 * 0.2).max(1.0),
+                });
+            }
+        }
+    }
+
+    /// Picks a spot inside the walls away from every bug and bullseye.
+    fn clear_spot(&mut self, game: &Game) -> Option<Vector2<f32>> {
+        let geometry = game.geometry();
+        let half_width = geometry.width / 2.0 - SPAWN_CLEARANCE;
+        let half_height = geometry.height / 2.0 - SPAWN_CLEARANCE;
+
+        (0..SPAWN_ATTEMPTS).find_map(|_| {
+            let position = Vector2::new(
+                (self.unit() * 2.0 - 1.0) * half_width,
+                (self.unit() * 2.0 - 1.0) * half_height,
+            );
+
+            let clear = game.iter_bugs().all(|(rigid_body, _)| {
+                (rigid_body.translation() - position).magnitude() > SPAWN_CLEARANCE
+            }) && self.bullseyes.iter().all(|bullseye| {
+                (bullseye.position - position).magnitude() > bullseye.radius + SPAWN_CLEARANCE
+            });
+
+            clear.then_some(position)
+        })
+    }
+
+    /// Draws a number between zero and one.
+    fn unit(&mut self) -> f32 {
+        self.rng.next_u32() as f32 / u32::MAX as f32
+    }
+
+    /// Returns the number of the current wave, starting at 1.
+    pub fn wave(&self) -> usize {
+        self.wave
+    }
+
+    /// Returns the points scored so far.
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// Returns the number of flicks left.
+    pub fn flicks_left(&self) -> u32 {
+        self.flicks_left
+    }
+
+    /// Returns the seconds left in the round.
+    pub fn seconds_left(&self) -> f32 {
+        (PRACTICE_SECONDS * 60).saturating_sub(self.ticks) as f32 / 60.0
+    }
+
+    /// Returns the bug indices of the dummies still standing.
+    pub fn dummies(&self) -> &[usize] {
+        &self.dummies
+    }
+
+    /// Returns the bullseyes yet to be reached.
+    pub fn bullseyes(&self) -> &[Bullseye] {
+        &self.bullseyes
+    }
+
+    /// Determines if the round is over, as time or flicks ran out.
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+}
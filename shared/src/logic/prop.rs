@@ -1,6 +1,81 @@
 use serde::{Deserialize, Serialize};
 
-/// new prop
+use crate::balance;
 
+/// Sort of a prop
+#[derive(PartialEq, Eq, Hash, Debug, Serialize, Deserialize, Copy, Clone, Default)]
+pub enum PropSort {
+    /// An indestructible pillar
+    #[default]
+    Pillar,
+    /// A rock which shatters after enough impacts
+    Rock,
+    /// A heavy boulder which can be pushed around
+    Boulder,
+}
+
+impl PropSort {
+    /// Mass of the prop, zero if immovable.
+    pub fn mass(&self) -> f32 {
+        match self {
+            PropSort::Pillar => 0.0,
+            PropSort::Rock => 0.0,
+            PropSort::Boulder => balance().boulder_mass,
+        }
+    }
+
+    /// Impact energy a prop absorbs before shattering, zero if unbreakable.
+    fn durability(&self) -> f32 {
+        match self {
+            PropSort::Pillar => 0.0,
+            PropSort::Rock => 60.0,
+            PropSort::Boulder => 0.0,
+        }
+    }
+}
+
+/// A prop
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, Default)]
-pub struct PropData {}
+pub struct PropData {
+    sort: PropSort,
+    absorbed: f32,
+}
+
+impl PropData {
+    /// Creates a new [`PropData`] entry.
+    pub fn new(sort: PropSort) -> PropData {
+        PropData {
+            sort,
+            absorbed: 0.0,
+        }
+    }
+
+    /// Returns the [`PropSort`] for this prop.
+    pub fn sort(&self) -> &PropSort {
+        &self.sort
+    }
+
+    /// Returns the fraction of durability used up, zero for unbreakable props.
+    pub fn damage(&self) -> f32 {
+        match self.sort.durability() {
+            durability if durability > 0.0 => (self.absorbed / durability).min(1.0),
+            _ => 0.0,
+        }
+    }
+
+    /// Returns `true` if a breakable prop has shattered.
+    pub fn broken(&self) -> bool {
+        self.damage() >= 1.0
+    }
+
+    /// Absorbs impact energy, returning `true` if the prop shattered from it.
+    pub fn absorb(&mut self, energy: f32) -> bool {
+        if self.sort.durability() <= 0.0 || self.broken() {
+            return false;
+        }
+
+        self.absorbed += energy;
+
+        self.broken()
+    }
+}
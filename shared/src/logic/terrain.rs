@@ -0,0 +1,50 @@
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::balance;
+
+/// Sort of a terrain patch
+#[derive(PartialEq, Debug, Serialize, Deserialize, Copy, Clone)]
+pub enum TerrainSort {
+    /// Mud, heavily damps bugs passing through
+    Mud,
+    /// Ice, bugs slide across with almost no damping
+    Ice,
+    /// Boost pad, pushes bugs along its direction
+    Boost(Vector2<f32>),
+}
+
+impl TerrainSort {
+    /// Linear damping applied to bugs inside the patch.
+    pub fn linear_damping(&self) -> f32 {
+        match self {
+            TerrainSort::Mud => balance().mud_linear_damping,
+            TerrainSort::Ice => balance().ice_linear_damping,
+            TerrainSort::Boost(_) => balance().base_linear_damping,
+        }
+    }
+}
+
+/// A terrain patch
+#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+pub struct TerrainData {
+    sort: TerrainSort,
+    radius: f32,
+}
+
+impl TerrainData {
+    /// Creates a new [`TerrainData`] entry.
+    pub fn new(sort: TerrainSort, radius: f32) -> TerrainData {
+        TerrainData { sort, radius }
+    }
+
+    /// Returns the [`TerrainSort`].
+    pub fn sort(&self) -> &TerrainSort {
+        &self.sort
+    }
+
+    /// Returns the radius of the patch.
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+}
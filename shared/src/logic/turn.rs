@@ -4,14 +4,101 @@ use nalgebra::Vector2;
 use serde::{Deserialize, Serialize};
 use serde_json_any_key::*;
 
+use crate::{sha256::sha256, Team, VecMap};
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 /// A turn
 pub struct Turn {
-    /// List of impulse intents
+    /// Impulse intents by bug index, applied in ascending order of the bugs on every client and
+    /// the server alike.
     #[serde(with = "any_key_map")]
-    pub impulse_intents: HashMap<usize, Vector2<f32>>,
+    pub impulse_intents: VecMap<usize, Vector2<f32>>,
     /// time stamp
     pub timestamp: f64,
     /// index
     pub index: usize,
+    /// Seconds each [`Team`] spent planning this turn.
+    #[serde(default, with = "any_key_map")]
+    pub think_times: HashMap<Team, f32>,
+    /// Team which surrendered during this turn, losing the game once it executes.
+    #[serde(default)]
+    pub surrendered: Option<Team>,
+}
+
+impl Turn {
+    /// Commits to the turn's index and impulse intents, hidden behind a salt which the player
+    /// keeps secret until revealing the turn.
+    pub fn commitment(&self, salt: u64) -> TurnCommitment {
+        let mut data = Vec::with_capacity(16 + self.impulse_intents.len() * 16);
+        data.extend_from_slice(&(self.index as u64).to_le_bytes());
+
+        // Intents are hashed in ascending order of the bugs, the same on every client
+        for (bug_index, impulse_intent) in &self.impulse_intents {
+            data.extend_from_slice(&(*bug_index as u64).to_le_bytes());
+            data.extend_from_slice(&impulse_intent.x.to_bits().to_le_bytes());
+            data.extend_from_slice(&impulse_intent.y.to_bits().to_le_bytes());
+        }
+
+        data.extend_from_slice(&salt.to_le_bytes());
+
+        TurnCommitment {
+            index: self.index,
+            hash: sha256(&data),
+        }
+    }
+}
+
+/// A hash of a [`Turn`] sent while planning, so its impulse intents stay hidden from everyone
+/// until they are revealed as the turn resolves.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TurnCommitment {
+    /// Index of the turn committed to.
+    pub index: usize,
+    /// SHA-256 of the turn's impulse intents and a secret salt, see [`Turn::commitment`].
+    pub hash: [u8; 32],
+}
+
+impl TurnCommitment {
+    /// Seconds before the turn deadline from which the server accepts reveals instead of
+    /// commitments.
+    pub const REVEAL_SECONDS: f64 = 3.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use data_encoding::HEXLOWER;
+    use nalgebra::vector;
+
+    use super::Turn;
+
+    #[test]
+    fn commitment_vectors() {
+        let turn = Turn {
+            index: 3,
+            impulse_intents: [(7, vector![2.0, 0.25]), (1, vector![0.5, -1.0])].into(),
+            ..Default::default()
+        };
+
+        let commitment = turn.commitment(42);
+
+        assert_eq!(commitment.index, 3);
+        assert_eq!(
+            HEXLOWER.encode(&commitment.hash),
+            "d5b8c7f18bfb72d348d0af0fefe40e46348555c98c9db65246282b3ec5e78b6f"
+        );
+
+        // Only the index and salt are hashed for a turn without intents
+        assert_eq!(
+            HEXLOWER.encode(&Turn::default().commitment(0).hash),
+            "374708fff7719dd5979ec875d56cd2286f6d3cf7ec317a3b25632aab28ec37bb"
+        );
+    }
+
+    #[test]
+    fn commitment_hides_the_salt() {
+        let turn = Turn::default();
+
+        assert_ne!(turn.commitment(1), turn.commitment(2));
+        assert_eq!(turn.commitment(1), turn.commitment(1));
+    }
 }
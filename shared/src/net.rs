@@ -1,24 +1,112 @@
 use std::collections::HashMap;
 
+use crate::{
+    Achievement, Bracket, ChatContent, Lobby, LobbyError, LobbySettings, LobbySummary, Replay,
+    SurrenderVote, Turn, TurnCommitment,
+};
 use serde::{Deserialize, Serialize};
 use serde_json_any_key::*;
-use crate::{Lobby, LobbyError, LobbySettings, Turn};
 
 /// A network message.
 #[derive(Serialize, Deserialize)]
 pub enum Message {
     /// Everything's zappin'!
     Ok,
-    /// A single [`Turn`].
+    /// A single [`Turn`], which players send as a [`Message::Commit`] and [`Message::Reveal`].
     Move(Turn),
-    /// A list of [`Turn`]s for synchronising observers who may be multiple turns behind.
-    TurnSync(Vec<Turn>),
+    /// New [`Turn`]s for synchronising observers who may be multiple turns behind.
+    TurnSync(TurnSync),
     /// An entire [`Lobby`] state for complete synchronisation.
     Lobby(Box<Lobby>),
-    /// List of lobbies
-    Lobbies(#[serde(with = "any_key_map")] HashMap<u16, Lobby>),
+    /// List of lobbies by ID, each as a [`LobbySummary`].
+    Lobbies(#[serde(with = "any_key_map")] HashMap<u16, LobbySummary>),
     /// A [`LobbyError`].
     LobbyError(LobbyError),
+    /// The server-side [`Profile`] of a session.
+    Profile(Profile),
+    /// The server's [`ServerStatus`].
+    Status(ServerStatus),
+    /// Confirms the [`SessionMessage`] with the given `message_id` was processed.
+    Ack(u64),
+    /// A page of the community map gallery.
+    Maps(MapGallery),
+    /// The state of a server-hosted tournament [`Bracket`].
+    Bracket(Box<Bracket>),
+    /// A line for the lobby chat, see [`crate::Lobby::chat`].
+    Chat(ChatContent),
+    /// Replaces the [`crate::Player::seat`]s whose chat lines the player does not want to see.
+    Mute(Vec<usize>),
+    /// A player's vote for their team to surrender, starting or joining a [`SurrenderVote`].
+    Surrender,
+    /// Marks the player ready for the game to begin, or not.
+    Ready(bool),
+    /// A [`TurnCommitment`] to a move, hiding its impulse intents while the turn is planned.
+    Commit(TurnCommitment),
+    /// A committed move along with its salt, accepted once the turn is about to resolve.
+    Reveal(Turn, u64),
+    /// Leaves the lobby on purpose, freeing the player's slot if the game has yet to start.
+    Leave,
+    /// Warns that the lobby is dropped in the given number of seconds unless a player sends a
+    /// heartbeat, see [`crate::Lobby::idle_seconds`].
+    LobbyExpiryWarning(f64),
+    /// A seed issued to a session for its next game against the built-in bot, see
+    /// [`crate::LobbySettings::level_seed`].
+    LevelSeed(u64),
+}
+
+/// Turns missed by a client, along with the server's state to detect drift against.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TurnSync {
+    /// Turns since the requested index, only referencing bugs which moved.
+    pub turns: Vec<Turn>,
+    /// Number of turns the server has executed.
+    pub turn_count: usize,
+    /// The server's capture progress after its last executed turn, withheld from spectators.
+    pub capture_progress: Option<f32>,
+    /// The server's [`crate::Game::checksum`] after its last executed turn, withheld from spectators.
+    #[serde(default)]
+    pub checksum: Option<u64>,
+    /// The server's clock in seconds since the epoch when the response was made.
+    #[serde(default)]
+    pub server_time: f64,
+    /// Server time at which the current turn is force-advanced, withheld from spectators.
+    #[serde(default)]
+    pub turn_deadline: Option<f64>,
+    /// Server time at which the warmup ends, if the lobby has one.
+    #[serde(default)]
+    pub warmup_until: Option<f64>,
+    /// The latest surrender vote of the requesting player's team, withheld from everyone else.
+    #[serde(default)]
+    pub surrender_vote: Option<SurrenderVote>,
+}
+
+/// Load of the server, reported so clients can tell when no new lobbies can be hosted.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug)]
+pub struct ServerStatus {
+    /// Number of lobbies currently hosted.
+    pub active_lobbies: usize,
+    /// Maximum number of lobbies the server hosts at once.
+    pub capacity: usize,
+    /// Hash of the server's [`crate::BalanceConfig`], clients with a different one cannot play online.
+    #[serde(default)]
+    pub balance_hash: u64,
+}
+
+impl ServerStatus {
+    /// Determines if the server is at capacity and refuses new lobbies.
+    pub fn busy(&self) -> bool {
+        self.active_lobbies >= self.capacity
+    }
+}
+
+/// Results of a session which the server has verified by re-simulating them.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Profile {
+    /// Number of wins against the built-in bot confirmed from their [`Replay`]s.
+    pub verified_wins: usize,
+    /// Achievements unlocked in those wins, in the order they were first verified.
+    #[serde(default)]
+    pub achievements: Vec<Achievement>,
 }
 
 /// An HTTP request made with a certain session ID.
@@ -28,6 +116,18 @@ pub struct SessionRequest {
     pub session_id: String,
 }
 
+/// A newly issued session, whose secret token authorizes requests made with its session ID.
+#[derive(Serialize, Deserialize)]
+pub struct SessionGrant {
+    /// The public session ID.
+    pub session_id: String,
+    /// The secret token, sent as a bearer token in the `Authorization` header.
+    pub token: String,
+    /// Hash of the server's [`crate::BalanceConfig`].
+    #[serde(default)]
+    pub balance_hash: u64,
+}
+
 /// An HTTP request made with a session ID, containing a [`Message`] payload.
 #[derive(Serialize, Deserialize)]
 pub struct SessionMessage {
@@ -35,6 +135,9 @@ pub struct SessionMessage {
     pub session_id: String,
     /// A [`Message`] payload.
     pub message: Message,
+    /// ID echoed back in a [`Message::Ack`] once processed, so the sender can stop retrying.
+    #[serde(default)]
+    pub message_id: Option<u64>,
 }
 
 /// An HTTP request made with a session ID, containing a [`Message`] payload.
@@ -45,3 +148,60 @@ pub struct SessionNewLobby {
     /// A [`Message`] payload.
     pub lobby_settings: LobbySettings,
 }
+
+/// An HTTP request made with a session ID, sharing an [`crate::ArenaMap`] with the community.
+#[derive(Serialize, Deserialize)]
+pub struct SessionSharedMap {
+    /// The session ID for this request.
+    pub session_id: String,
+    /// Title the map is listed under.
+    pub title: String,
+    /// Code of the map, see [`crate::ArenaMap::encode`].
+    pub code: String,
+}
+
+/// An HTTP request made with a session ID, reporting a bug with the recent client logs the
+/// player chose to attach.
+#[derive(Serialize, Deserialize)]
+pub struct SessionBugReport {
+    /// The session ID for this request.
+    pub session_id: String,
+    /// What the player says went wrong.
+    pub description: String,
+    /// Latest lines of the client's log, empty unless the player agreed to send them.
+    #[serde(default)]
+    pub logs: Vec<String>,
+}
+
+/// A map shared to the community gallery.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SharedMap {
+    /// ID of the map in the gallery.
+    pub id: u32,
+    /// Title the map is listed under.
+    pub title: String,
+    /// Code of the map, see [`crate::ArenaMap::encode`].
+    pub code: String,
+    /// Number of matches started on the map.
+    pub plays: u32,
+}
+
+/// A page of the community map gallery, most played first.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MapGallery {
+    /// Maps on this page.
+    pub maps: Vec<SharedMap>,
+    /// Index of this page.
+    pub page: usize,
+    /// Number of pages in the gallery.
+    pub pages: usize,
+}
+
+/// An HTTP request made with a session ID, claiming the win recorded in a [`Replay`].
+#[derive(Serialize, Deserialize)]
+pub struct SessionReplay {
+    /// The session ID for this request.
+    pub session_id: String,
+    /// The [`Replay`] of the claimed win.
+    pub replay: Replay,
+}
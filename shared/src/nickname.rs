@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// Adjectives a [`Nickname`] is drawn from, all of them friendly.
+const NICKNAME_ADJECTIVES: [&str; 32] = [
+    "Sneaky", "Brave", "Jolly", "Nimble", "Plucky", "Sunny", "Zippy", "Clever", "Dapper", "Fuzzy",
+    "Gentle", "Happy", "Humble", "Lucky", "Mighty", "Noble", "Peppy", "Quick", "Shiny", "Spry",
+    "Swift", "Tiny", "Witty", "Bouncy", "Cheery", "Cosmic", "Dizzy", "Frosty", "Giddy", "Mellow",
+    "Bold", "Curious",
+];
+
+/// Bugs a [`Nickname`] is drawn from.
+const NICKNAME_BUGS: [&str; 32] = [
+    "Weevil", "Beetle", "Ladybug", "Ant", "Moth", "Cricket", "Firefly", "Hornet", "Mantis",
+    "Termite", "Aphid", "Midge", "Gnat", "Locust", "Cicada", "Katydid", "Earwig", "Mayfly",
+    "Scarab", "Wasp", "Bee", "Flea", "Spider", "Mite", "Chafer", "Skipper", "Lacewing", "Stag",
+    "Bumble", "Glowworm", "Hopper", "Silkworm",
+];
+
+/// Name a player goes by, such as "Sneaky Weevil", derived from their session ID so that raw
+/// session IDs are never shown.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Copy, Clone, Default)]
+pub struct Nickname {
+    adjective: u8,
+    bug: u8,
+}
+
+impl Nickname {
+    /// Generates the nickname of a session.
+    ///
+    /// Nicknames only depend on the session ID, so a player keeps theirs from lobby to lobby.
+    pub fn from_session(session_id: &str) -> Nickname {
+        // FNV-1a, which unlike the standard library's hasher is the same on every platform
+        let hash = session_id
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+
+        Nickname {
+            adjective: (hash % NICKNAME_ADJECTIVES.len() as u64) as u8,
+            bug: ((hash >> 32) % NICKNAME_BUGS.len() as u64) as u8,
+        }
+    }
+}
+
+impl std::fmt::Display for Nickname {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            NICKNAME_ADJECTIVES[self.adjective as usize % NICKNAME_ADJECTIVES.len()],
+            NICKNAME_BUGS[self.bug as usize % NICKNAME_BUGS.len()]
+        )
+    }
+}
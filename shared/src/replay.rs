@@ -0,0 +1,233 @@
+use data_encoding::BASE64URL_NOPAD;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AchievementTracker, BotController, Game, Lobby, LobbyError, LobbyErrorKind, LobbySettings,
+    LobbySort, Team, Turn,
+};
+
+/// Largest difference tolerated between a recorded and a re-planned bot intent.
+const INTENT_TOLERANCE: f32 = 1e-3;
+
+/// A recorded match, containing everything needed to re-simulate it deterministically.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Replay {
+    /// Settings of the recorded lobby.
+    pub settings: LobbySettings,
+    /// Every executed [`Turn`] in order.
+    pub turns: Vec<Turn>,
+}
+
+impl Replay {
+    /// Records the settings and turns of a [`Lobby`], as a local lobby.
+    ///
+    /// Returns `None` once the game forgot its earliest turns, see [`crate::MAX_TURN_HISTORY`], as
+    /// the remembered ones would replay from the middle of the game.
+    pub fn new(lobby: &Lobby) -> Option<Replay> {
+        if lobby.turns().len() < lobby.game.turns_count() {
+            return None;
+        }
+
+        let mut settings = lobby.settings.clone();
+        settings.set_sort(LobbySort::Local);
+
+        Some(Replay {
+            settings,
+            turns: lobby.turns().clone(),
+        })
+    }
+
+    /// Encodes the replay into a URL-safe base64 code for sharing.
+    pub fn encode(&self) -> String {
+        BASE64URL_NOPAD.encode(serde_json::to_string(self).unwrap_or_default().as_bytes())
+    }
+
+    /// Decodes a replay code produced by [`Replay::encode`].
+    pub fn decode(code: &str) -> Result<Replay, LobbyError> {
+        let json = BASE64URL_NOPAD
+            .decode(code.trim().as_bytes())
+            .map_err(|err| {
+                LobbyError::new(
+                    LobbyErrorKind::InvalidReplay,
+                    &format!("invalid replay code: {err}"),
+                )
+            })?;
+
+        serde_json::from_slice(&json).map_err(|err| {
+            LobbyError::new(
+                LobbyErrorKind::InvalidReplay,
+                &format!("invalid replay: {err}"),
+            )
+        })
+    }
+
+    /// Re-simulates the replay headlessly, checking that every intent of `bot_team` matches what
+    /// `bot` plans itself. Returns the [`crate::Result`] reached, or `None` if the bot's moves were
+    /// tampered with or the game did not finish.
+    ///
+    /// The `tracker` observes every tick of the re-simulation, unlocking the achievements earned.
+    pub fn verify(
+        &self,
+        bot_team: Team,
+        bot: &mut dyn BotController,
+        tracker: &mut AchievementTracker,
+    ) -> Option<crate::Result> {
+        let mut game = Game::with_settings(&self.settings);
+
+        let mut tick = |game: &mut Game| {
+            game.tick();
+
+            let events = game.drain_events();
+            tracker.observe(game, &events);
+        };
+
+        for turn in &self.turns {
+            while !game.awaiting_turn() {
+                tick(&mut game);
+            }
+
+            game.plan_bot(bot_team, bot);
+
+            for bug_index in game.team_bug_indices(bot_team) {
+                let planned = game.get_bug(bug_index)?.1.impulse_intent();
+                let recorded = turn
+                    .impulse_intents
+                    .get(&bug_index)
+                    .copied()
+                    .unwrap_or_default();
+
+                if (planned - recorded).magnitude() > INTENT_TOLERANCE {
+                    return None;
+                }
+            }
+
+            game.queue_turns(vec![turn.clone()]).ok()?;
+            tick(&mut game);
+        }
+
+        while !game.awaiting_turn() {
+            tick(&mut game);
+        }
+
+        game.result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::vector;
+
+    use super::*;
+    use crate::ZoneBot;
+
+    #[test]
+    fn code_round_trip() {
+        let replay = Replay {
+            settings: LobbySettings::new(LobbySort::Local),
+            turns: vec![Turn {
+                index: 1,
+                impulse_intents: [(2, vector![0.5, -1.0])].into(),
+                surrendered: Some(Team::Blue),
+                ..Default::default()
+            }],
+        };
+
+        let code = replay.encode();
+        let decoded = Replay::decode(&format!("  {code}\n")).unwrap();
+
+        assert_eq!(decoded.encode(), code);
+        assert_eq!(
+            decoded.turns[0].impulse_intents.get(&2),
+            Some(&vector![0.5, -1.0])
+        );
+        assert_eq!(decoded.turns[0].surrendered, Some(Team::Blue));
+    }
+
+    #[test]
+    fn forgotten_turns() {
+        let mut lobby = Lobby::new(LobbySettings::new(LobbySort::Local), 0.0);
+
+        for index in 1..=crate::MAX_TURN_HISTORY {
+            lobby.game.execute_turn(&Turn {
+                index,
+                ..Default::default()
+            });
+        }
+
+        assert_eq!(
+            Replay::new(&lobby).map(|replay| replay.turns.len()),
+            Some(crate::MAX_TURN_HISTORY)
+        );
+
+        // Past the history, the first turns are missing from the replay
+        lobby.game.execute_turn(&Turn {
+            index: crate::MAX_TURN_HISTORY + 1,
+            ..Default::default()
+        });
+
+        assert!(Replay::new(&lobby).is_none());
+    }
+
+    /// Plays a short game between two bots, the blue one aiming by the given level seed.
+    fn record(level_seed: u64) -> Replay {
+        let mut settings = LobbySettings::new(LobbySort::Local);
+        settings.set_max_turns(Some(3));
+        settings.set_level_seed(level_seed);
+
+        let mut game = Game::with_settings(&settings);
+        let mut red = ZoneBot::default();
+        let mut blue = ZoneBot::for_settings(&settings);
+        let mut turns = Vec::new();
+
+        while game.result().is_none() {
+            while !game.awaiting_turn() {
+                game.tick();
+            }
+
+            game.plan_bot(Team::Red, &mut red);
+            game.plan_bot(Team::Blue, &mut blue);
+
+            let turn = game.aggregate_turn();
+            turns.push(turn.clone());
+            game.queue_turns(vec![turn]).unwrap();
+            game.tick();
+        }
+
+        Replay { settings, turns }
+    }
+
+    fn verify(replay: &Replay) -> Option<crate::Result> {
+        replay.verify(
+            Team::Blue,
+            &mut ZoneBot::for_settings(&replay.settings),
+            &mut AchievementTracker::new(Team::Red),
+        )
+    }
+
+    #[test]
+    fn bound_to_level_seed() {
+        let replay = record(0x5eed);
+        assert!(verify(&replay).is_some());
+
+        // The bot aims differently on any other level, so the recorded moves no longer match
+        for level_seed in [0, 0x5eee] {
+            let mut replay = replay.clone();
+            replay.settings.set_level_seed(level_seed);
+
+            assert!(verify(&replay).is_none());
+        }
+    }
+
+    #[test]
+    fn invalid_codes() {
+        for code in ["not base64!", "e30"] {
+            assert!(matches!(
+                Replay::decode(code),
+                Err(LobbyError {
+                    kind: LobbyErrorKind::InvalidReplay,
+                    ..
+                })
+            ));
+        }
+    }
+}
@@ -26,3 +26,242 @@ where
 
     Ok(T::from_iter(container))
 }
+
+/// A map kept as a [`Vec`] of entries sorted by key.
+///
+/// Entries always iterate in ascending key order, unlike a [`std::collections::HashMap`], so
+/// every client and the server walk the bugs, props and impulse intents of a [`crate::Game`]
+/// in the same order. The sync protocol relies on this: turns are resolved independently on
+/// each side and compared by [`crate::Game::checksum`]. Lookups are binary searches, which beat
+/// hashing for the handful of entries a game holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VecMap<K, V> {
+    entries: Vec<(K, V)>,
+}
+
+impl<K, V> Default for VecMap<K, V> {
+    fn default() -> Self {
+        VecMap {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<K: Ord, V> VecMap<K, V> {
+    /// Creates an empty map.
+    pub fn new() -> VecMap<K, V> {
+        VecMap::default()
+    }
+
+    fn search(&self, key: &K) -> Result<usize, usize> {
+        self.entries
+            .binary_search_by(|(entry_key, _)| entry_key.cmp(key))
+    }
+
+    /// Inserts a value under a key, returning the value it replaced.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.search(&key) {
+            Ok(index) => Some(std::mem::replace(&mut self.entries[index].1, value)),
+            Err(index) => {
+                self.entries.insert(index, (key, value));
+                None
+            }
+        }
+    }
+
+    /// Returns the value under a key.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.search(key).ok().map(|index| &self.entries[index].1)
+    }
+
+    /// Returns the value under a key mutably.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.search(key)
+            .ok()
+            .map(|index| &mut self.entries[index].1)
+    }
+
+    /// Determines if the map holds a key.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.search(key).is_ok()
+    }
+
+    /// Removes the value under a key, keeping the others in order.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.search(key)
+            .ok()
+            .map(|index| self.entries.remove(index).1)
+    }
+}
+
+impl<K, V> VecMap<K, V> {
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Determines if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the entries in ascending key order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    /// Iterates over the entries in ascending key order, the values mutably.
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = (&K, &mut V)> {
+        self.entries.iter_mut().map(|(key, value)| (&*key, value))
+    }
+
+    /// Iterates over the keys in ascending order.
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    /// Iterates over the values in ascending key order.
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+
+    /// Iterates over the values in ascending key order, mutably.
+    pub fn values_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut V> {
+        self.entries.iter_mut().map(|(_, value)| value)
+    }
+
+    /// Consumes the map into its keys in ascending order.
+    pub fn into_keys(self) -> impl DoubleEndedIterator<Item = K> {
+        self.entries.into_iter().map(|(key, _)| key)
+    }
+
+    /// Consumes the map into its values in ascending key order.
+    pub fn into_values(self) -> impl DoubleEndedIterator<Item = V> {
+        self.entries.into_iter().map(|(_, value)| value)
+    }
+
+    /// Keeps only the entries for which the predicate holds.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        self.entries.retain_mut(|(key, value)| f(key, value));
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for VecMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = VecMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for VecMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for VecMap<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        VecMap::from_iter(entries)
+    }
+}
+
+impl<K, V> IntoIterator for VecMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = std::vec::IntoIter<(K, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a VecMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = std::iter::Map<std::slice::Iter<'a, (K, V)>, fn(&'a (K, V)) -> (&'a K, &'a V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let pair: fn(&'a (K, V)) -> (&'a K, &'a V) = |(key, value)| (key, value);
+
+        self.entries.iter().map(pair)
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a mut VecMap<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter =
+        std::iter::Map<std::slice::IterMut<'a, (K, V)>, fn(&'a mut (K, V)) -> (&'a K, &'a mut V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let pair: fn(&'a mut (K, V)) -> (&'a K, &'a mut V) = |(key, value)| (&*key, value);
+
+        self.entries.iter_mut().map(pair)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json_any_key::any_key_map;
+
+    use super::VecMap;
+
+    #[test]
+    fn iterates_in_key_order() {
+        let map: VecMap<usize, char> = [(3, 'c'), (1, 'a'), (2, 'b')].into();
+
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(
+            map.values().copied().collect::<Vec<_>>(),
+            vec!['a', 'b', 'c']
+        );
+        assert_eq!(map.iter().next_back(), Some((&3, &'c')));
+    }
+
+    #[test]
+    fn insert_replace_remove() {
+        let mut map = VecMap::new();
+
+        assert_eq!(map.insert(2, "two"), None);
+        assert_eq!(map.insert(1, "one"), None);
+        assert_eq!(map.insert(2, "deux"), Some("two"));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&2), Some(&"deux"));
+
+        *map.get_mut(&1).unwrap() = "un";
+        assert_eq!(map.get(&1), Some(&"un"));
+
+        assert_eq!(map.remove(&1), Some("un"));
+        assert_eq!(map.remove(&1), None);
+        assert!(!map.contains_key(&1));
+        assert_eq!(map.into_iter().collect::<Vec<_>>(), vec![(2, "deux")]);
+    }
+
+    #[test]
+    fn later_duplicates_win() {
+        let map: VecMap<usize, char> = [(1, 'a'), (1, 'b')].into_iter().collect();
+
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&1), Some(&'b'));
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Intents {
+        #[serde(with = "any_key_map")]
+        intents: VecMap<usize, f32>,
+    }
+
+    #[test]
+    fn any_key_map_round_trip() {
+        let intents = Intents {
+            intents: [(10, 0.5), (2, -1.0)].into(),
+        };
+
+        let json = serde_json::to_string(&intents).unwrap();
+
+        // Written in key order, so every peer serializes the same intents the same way
+        assert_eq!(json, r#"{"intents":{"2":-1.0,"10":0.5}}"#);
+        assert_eq!(serde_json::from_str::<Intents>(&json).unwrap(), intents);
+    }
+}
@@ -0,0 +1,36 @@
+use shared::Achievement;
+
+use crate::app::App;
+
+/// Storage key of the comma-separated keys of unlocked achievements.
+const ACHIEVEMENTS_KEY: &str = "achievements";
+
+/// Returns the achievements unlocked on this device.
+pub fn unlocked_achievements() -> Vec<Achievement> {
+    App::kv_get(ACHIEVEMENTS_KEY)
+        .split(',')
+        .filter_map(Achievement::from_key)
+        .collect()
+}
+
+/// Persists an unlocked achievement, returns `false` if it had already been unlocked.
+pub fn unlock_achievement(achievement: Achievement) -> bool {
+    let mut achievements = unlocked_achievements();
+
+    if achievements.contains(&achievement) {
+        return false;
+    }
+
+    achievements.push(achievement);
+
+    App::kv_set(
+        ACHIEVEMENTS_KEY,
+        &achievements
+            .iter()
+            .map(Achievement::key)
+            .collect::<Vec<_>>()
+            .join(","),
+    );
+
+    true
+}
@@ -0,0 +1,355 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{self, Display},
+    future::Future,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    net::{api_url, authorize, fetch},
+    platform::{spawn, HttpRequest},
+    warn,
+};
+use serde::Serialize;
+use shared::{
+    Bracket, Lobby, LobbyError, LobbyID, LobbySettings, LobbySummary, MapGallery, Message, Profile,
+    Replay, ServerStatus, SessionBugReport, SessionGrant, SessionMessage, SessionNewLobby,
+    SessionReplay, SessionRequest, SessionSharedMap,
+};
+
+/// Errors of requests made through the [`ApiClient`].
+#[derive(Debug)]
+pub enum NetError {
+    /// The request could not be sent, or its response could not be read.
+    Fetch(String),
+    /// The response is not the JSON the request expects.
+    Decode(String),
+    /// The server refused the request.
+    Lobby(LobbyError),
+    /// The server answered with a different [`Message`] than the request expects.
+    Unexpected,
+}
+
+impl From<LobbyError> for NetError {
+    fn from(err: LobbyError) -> Self {
+        NetError::Lobby(err)
+    }
+}
+
+impl Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Fetch(reason) => write!(f, "request failed: {reason}"),
+            NetError::Decode(reason) => write!(f, "invalid response: {reason}"),
+            NetError::Lobby(err) => write!(f, "{}", err.reason),
+            NetError::Unexpected => write!(f, "unexpected response"),
+        }
+    }
+}
+
+/// Typed client of the server's API in the active region, authorizing its requests with the
+/// stored session token.
+///
+/// Requests take the client by value, so their futures can be handed to a [`Dispatcher`].
+#[derive(Clone, Default)]
+pub struct ApiClient {
+    /// Session the requests are made for, empty for anonymous requests.
+    session_id: String,
+}
+
+impl ApiClient {
+    /// Creates a client making requests for a session.
+    pub fn new(session_id: String) -> ApiClient {
+        ApiClient { session_id }
+    }
+
+    /// Fetches a request and decodes its [`Message`], turning a [`Message::LobbyError`] into an
+    /// error.
+    async fn fetch_message(request: HttpRequest) -> Result<Message, NetError> {
+        let response = fetch(request)
+            .await
+            .map_err(|err| NetError::Fetch(err.to_string()))?;
+
+        match serde_json::from_slice(&response.body) {
+            Ok(Message::LobbyError(err)) => Err(err.into()),
+            Ok(message) => Ok(message),
+            Err(err) => Err(NetError::Decode(err.to_string())),
+        }
+    }
+
+    /// Sends a `GET` request to a path of the API.
+    async fn get(&self, path: &str) -> Result<Message, NetError> {
+        let mut request = HttpRequest::get(format!("{}{path}", api_url()));
+
+        if !self.session_id.is_empty() {
+            request = authorize(request);
+        }
+
+        ApiClient::fetch_message(request).await
+    }
+
+    /// Sends a `POST` request with a JSON body to a path of the API.
+    async fn post(&self, path: &str, body: &impl Serialize) -> Result<Message, NetError> {
+        let json = serde_json::to_string(body).map_err(|err| NetError::Decode(err.to_string()))?;
+
+        let request = authorize(HttpRequest::post(format!("{}{path}", api_url()), json));
+
+        ApiClient::fetch_message(request).await
+    }
+
+    /// Sends a `POST` request carrying only the session ID.
+    async fn post_session(&self, path: &str) -> Result<Message, NetError> {
+        self.post(
+            path,
+            &SessionRequest {
+                session_id: self.session_id.clone(),
+            },
+        )
+        .await
+    }
+
+    /// Obtains a new session from the server.
+    pub async fn session(self) -> Result<SessionGrant, NetError> {
+        let response = fetch(HttpRequest::get(format!("{}/session", api_url())))
+            .await
+            .map_err(|err| NetError::Fetch(err.to_string()))?;
+
+        serde_json::from_slice(&response.body).map_err(|err| NetError::Decode(err.to_string()))
+    }
+
+    /// Lists the public lobbies by their ID.
+    pub async fn lobbies(self) -> Result<HashMap<LobbyID, LobbySummary>, NetError> {
+        match self.get("/lobbies/").await? {
+            Message::Lobbies(lobbies) => Ok(lobbies),
+            _ => Err(NetError::Unexpected),
+        }
+    }
+
+    /// Fetches the load of the server.
+    pub async fn status(self) -> Result<ServerStatus, NetError> {
+        match self.get("/status").await? {
+            Message::Status(server_status) => Ok(server_status),
+            _ => Err(NetError::Unexpected),
+        }
+    }
+
+    /// Hosts a new lobby, which the session joins right away.
+    pub async fn create_lobby(self, lobby_settings: LobbySettings) -> Result<Lobby, NetError> {
+        let session_new_lobby = SessionNewLobby {
+            session_id: self.session_id.clone(),
+            lobby_settings,
+        };
+
+        match self.post("/lobbies/create", &session_new_lobby).await? {
+            Message::Lobby(lobby) => Ok(*lobby),
+            _ => Err(NetError::Unexpected),
+        }
+    }
+
+    /// Takes a free player slot of a lobby.
+    pub async fn join_lobby(self, lobby_id: LobbyID) -> Result<Lobby, NetError> {
+        match self
+            .post_session(&format!("/lobbies/{lobby_id}/ready"))
+            .await?
+        {
+            Message::Lobby(lobby) => Ok(*lobby),
+            _ => Err(NetError::Unexpected),
+        }
+    }
+
+    /// Sends a message to a lobby, answered by [`Message::Ok`], or by [`Message::Ack`] when it
+    /// carries a `message_id`.
+    pub async fn act(
+        self,
+        lobby_id: LobbyID,
+        message: Message,
+        message_id: Option<u64>,
+    ) -> Result<Message, NetError> {
+        let session_message = SessionMessage {
+            session_id: self.session_id.clone(),
+            message,
+            message_id,
+        };
+
+        self.post(&format!("/lobbies/{lobby_id}/act"), &session_message)
+            .await
+    }
+
+    /// Fetches the turns of a lobby since an index, answered by a [`Message::TurnSync`] once the
+    /// game started and by a [`Message::Lobby`] before.
+    pub async fn turns_since(self, lobby_id: LobbyID, since: usize) -> Result<Message, NetError> {
+        if self.session_id.is_empty() {
+            self.get(&format!("/lobbies/{lobby_id}/turns/{since}"))
+                .await
+        } else {
+            self.get(&format!(
+                "/lobbies/{lobby_id}/turns/{since}?session_id={}",
+                self.session_id
+            ))
+            .await
+        }
+    }
+
+    /// Fetches a page of the community map gallery.
+    pub async fn maps(self, page: usize) -> Result<MapGallery, NetError> {
+        match self.get(&format!("/maps/{page}")).await? {
+            Message::Maps(gallery) => Ok(gallery),
+            _ => Err(NetError::Unexpected),
+        }
+    }
+
+    /// Shares a map to the gallery, answered by a gallery holding only the shared map.
+    pub async fn share_map(self, title: String, code: String) -> Result<MapGallery, NetError> {
+        let session_map = SessionSharedMap {
+            session_id: self.session_id.clone(),
+            title,
+            code,
+        };
+
+        match self.post("/maps", &session_map).await? {
+            Message::Maps(gallery) => Ok(gallery),
+            _ => Err(NetError::Unexpected),
+        }
+    }
+
+    /// Counts a play of a shared map.
+    pub async fn play_map(self, map_id: u32) -> Result<(), NetError> {
+        self.post_session(&format!("/maps/{map_id}/play"))
+            .await
+            .map(|_| ())
+    }
+
+    /// Reports a shared map for moderation.
+    pub async fn report_map(self, map_id: u32) -> Result<(), NetError> {
+        self.post_session(&format!("/maps/{map_id}/report"))
+            .await
+            .map(|_| ())
+    }
+
+    /// Sends a bug report along with the recent log lines.
+    pub async fn report_bug(self, description: String, logs: Vec<String>) -> Result<(), NetError> {
+        let bug_report = SessionBugReport {
+            session_id: self.session_id.clone(),
+            description,
+            logs,
+        };
+
+        self.post("/bugs", &bug_report).await.map(|_| ())
+    }
+
+    /// Has the server issue a level seed for the session's next game against the built-in bot,
+    /// which its win is only verified with.
+    pub async fn level_seed(self) -> Result<u64, NetError> {
+        match self.post_session("/verify/seed").await? {
+            Message::LevelSeed(level_seed) => Ok(level_seed),
+            _ => Err(NetError::Unexpected),
+        }
+    }
+
+    /// Has the server re-simulate a win against the built-in bot, answered by the session's
+    /// updated [`Profile`].
+    pub async fn submit_replay(self, replay: Replay) -> Result<Profile, NetError> {
+        let session_replay = SessionReplay {
+            session_id: self.session_id.clone(),
+            replay,
+        };
+
+        match self.post("/verify", &session_replay).await? {
+            Message::Profile(profile) => Ok(profile),
+            _ => Err(NetError::Unexpected),
+        }
+    }
+
+    /// Fetches a tournament bracket by its code.
+    pub async fn bracket(self, code: String) -> Result<Bracket, NetError> {
+        ApiClient::expect_bracket(self.get(&format!("/tournaments/{code}")).await?)
+    }
+
+    /// Hosts a new tournament bracket.
+    pub async fn create_bracket(self) -> Result<Bracket, NetError> {
+        ApiClient::expect_bracket(self.post_session("/tournaments/create").await?)
+    }
+
+    /// Registers the session for a tournament bracket.
+    pub async fn join_bracket(self, code: String) -> Result<Bracket, NetError> {
+        ApiClient::expect_bracket(
+            self.post_session(&format!("/tournaments/{code}/join"))
+                .await?,
+        )
+    }
+
+    /// Starts a tournament bracket hosted by the session.
+    pub async fn start_bracket(self, code: String) -> Result<Bracket, NetError> {
+        ApiClient::expect_bracket(
+            self.post_session(&format!("/tournaments/{code}/start"))
+                .await?,
+        )
+    }
+
+    fn expect_bracket(message: Message) -> Result<Bracket, NetError> {
+        match message {
+            Message::Bracket(bracket) => Ok(*bracket),
+            _ => Err(NetError::Unexpected),
+        }
+    }
+}
+
+/// Sends a request whose answer does not matter, only logging its failure.
+pub fn detach<T: 'static>(request: impl Future<Output = Result<T, NetError>> + 'static) {
+    spawn(async move {
+        if let Err(err) = request.await {
+            warn!("{err}");
+        }
+    });
+}
+
+/// Routes the answers to requests a state sends through it back to that state as [`Message`]s,
+/// dropping those which arrive after the state was left.
+#[derive(Default)]
+pub struct Dispatcher {
+    inbox: Rc<RefCell<Vec<Message>>>,
+}
+
+impl Dispatcher {
+    /// Sends a request in the background, wrapping its answer into a [`Message`].
+    ///
+    /// Refusals by the server arrive as a [`Message::LobbyError`], any other error is only logged.
+    pub fn dispatch<T: 'static>(
+        &self,
+        request: impl Future<Output = Result<T, NetError>> + 'static,
+        into_message: impl FnOnce(T) -> Message + 'static,
+    ) {
+        let inbox: Weak<RefCell<Vec<Message>>> = Rc::downgrade(&self.inbox);
+
+        spawn(async move {
+            let message = match request.await {
+                Ok(value) => into_message(value),
+                Err(NetError::Lobby(err)) => Message::LobbyError(err),
+                Err(err) => {
+                    warn!("{err}");
+                    return;
+                }
+            };
+
+            if let Some(inbox) = inbox.upgrade() {
+                inbox.borrow_mut().push(message);
+            }
+        });
+    }
+
+    /// Sends a request answered by a plain [`Message`].
+    pub fn send(&self, request: impl Future<Output = Result<Message, NetError>> + 'static) {
+        self.dispatch(request, |message| message);
+    }
+
+    /// Takes the messages which arrived since the last call.
+    pub fn take(&self) -> Vec<Message> {
+        std::mem::take(&mut self.inbox.borrow_mut())
+    }
+
+    /// Number of messages waiting to be taken.
+    pub fn waiting(&self) -> usize {
+        self.inbox.borrow().len()
+    }
+}
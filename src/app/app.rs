@@ -1,13 +1,24 @@
+use std::rc::Rc;
+
 use serde::{Deserialize, Serialize};
-use shared::{LobbyError, SessionRequest};
-use wasm_bindgen::JsValue;
-use web_sys::{
-    CanvasRenderingContext2d, DomRectReadOnly, FocusEvent, HtmlCanvasElement, HtmlInputElement,
-    KeyboardEvent, MouseEvent, TouchEvent,
-};
+use shared::{balance, LobbyError, SessionGrant};
 
-use super::{AudioSystem, GameState, MainMenuState, Pointer, SettingsMenuState};
-use crate::{app::State, draw::draw_image, net::get_session_id, storage, window};
+use super::{
+    now, AchievementsMenuState, AudioSystem, AutoPerformance, BracketState, CalibrationState,
+    CritterpediaMenuState, Frame, GameClock, GameState, Haptics, LobbySetupState, MainMenuState,
+    MapGalleryState, PerformanceHud, Pointer, ResultsState, RulesMenuState, Settings,
+    SettingsMenuState, TournamentState,
+};
+use crate::{
+    app::State,
+    draw::draw_image,
+    formation::Formation,
+    info,
+    net::{active_region, get_session_id, get_session_token},
+    platform::{Application, Canvas, InputEvent, InputHandler, MouseButton, PlatformError},
+    render::{create_renderer, Renderer},
+    storage, warn,
+};
 
 /// Errors concerning the [`App`].
 #[derive(Debug, Serialize, Deserialize)]
@@ -15,60 +26,116 @@ pub struct AppError(String);
 
 impl From<LobbyError> for AppError {
     fn from(lobby_error: LobbyError) -> Self {
-        AppError(format!("LobbyError: {0}", lobby_error.0))
+        AppError(format!("LobbyError: {0}", lobby_error.reason))
     }
 }
 
+/// Radius of a fingertip in CSS pixels, which touches may land off a button by.
+const TOUCH_REACH_CSS: f64 = 12.0;
+
+/// Frames after a touch during which mouse events are taken as the browser's emulated ones.
+const TOUCH_MOUSE_FRAMES: Frame = 60;
+
 pub enum StateSort {
     MainMenu(MainMenuState),
     Game(GameState),
     SettingsMenu(SettingsMenuState),
+    CritterpediaMenu(CritterpediaMenuState),
+    AchievementsMenu(AchievementsMenuState),
+    Results(ResultsState),
+    MapGallery(MapGalleryState),
+    RulesMenu(RulesMenuState),
+    Tournament(TournamentState),
+    Bracket(BracketState),
+    LobbySetup(LobbySetupState),
+    Calibration(CalibrationState),
 }
 
 pub struct AppContext {
     pub session_id: Option<String>,
     pub pointer: Pointer,
-    pub frame: usize,
+    pub clock: GameClock,
     pub canvas_settings: CanvasSettings,
     pub text_input: Option<(String, String)>,
     pub audio_system: AudioSystem,
-    pub atlas_context: CanvasRenderingContext2d,
+    pub atlas_context: Rc<dyn Canvas>,
+    pub render_options: RenderOptions,
+    pub renderer: Box<dyn Renderer>,
+    pub aim_options: AimOptions,
+    pub frame_limit: FrameLimit,
+    pub haptics: Haptics,
+    pub hidden: bool,
+    pub last_input_frame: Frame,
+}
+
+impl AppContext {
+    /// Frames elapsed since the last pointer, touch or key input.
+    pub fn idle_frames(&self) -> Frame {
+        self.clock.since(self.last_input_frame)
+    }
 }
 
 pub struct App {
     app_context: AppContext,
     state_sort: StateSort,
     atlas_complete: bool,
+    performance_hud: PerformanceHud,
+    auto_performance: AutoPerformance,
+    /// Frame of the last touch input, so emulated mouse events keep the touch reach.
+    last_touch_frame: Option<Frame>,
+    /// Size the game's canvas is shown at, in display pixels.
+    display_size: (f64, f64),
 }
 
 impl App {
     pub fn new(
         canvas_settings: &CanvasSettings,
-        atlas_context: CanvasRenderingContext2d,
+        atlas_context: Rc<dyn Canvas>,
         audio_system: AudioSystem,
     ) -> App {
+        let render_options = RenderOptions::load();
+
         App {
             app_context: AppContext {
-                session_id: get_session_id(),
+                session_id: get_session_id().filter(|_| get_session_token().is_some()),
                 pointer: Pointer::new(canvas_settings),
-                frame: 0,
+                clock: GameClock::default(),
                 canvas_settings: canvas_settings.clone(),
                 text_input: None,
                 audio_system,
                 atlas_context,
+                renderer: create_renderer(render_options.webgl),
+                render_options,
+                aim_options: AimOptions::load(),
+                frame_limit: FrameLimit::load(),
+                haptics: Haptics::load(),
+                hidden: false,
+                last_input_frame: 0,
             },
             // state_sort: StateSort::Game(GameState::new(LobbySettings::new(shared::LobbySort::Local))),
             state_sort: StateSort::MainMenu(MainMenuState::default()),
             atlas_complete: false,
+            performance_hud: PerformanceHud::default(),
+            auto_performance: AutoPerformance::new(canvas_settings.canvas_scale),
+            last_touch_frame: None,
+            display_size: (
+                canvas_settings.element_width() as f64,
+                canvas_settings.element_height() as f64,
+            ),
         }
     }
 
-    pub fn draw(
+    fn draw(
         &mut self,
-        context: &CanvasRenderingContext2d,
-        interface_context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
-    ) -> Result<(), JsValue> {
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+    ) -> Result<(), PlatformError> {
+        let draw_start = now();
+
+        self.performance_hud.record_frame(draw_start);
+        self.adapt_performance(context, interface_context, draw_start)?;
+
         context.clear_rect(
             0.0,
             0.0,
@@ -123,9 +190,60 @@ impl App {
                 StateSort::SettingsMenu(state) => {
                     state.draw(context, interface_context, atlas, &self.app_context)
                 }
+                StateSort::CritterpediaMenu(state) => {
+                    state.draw(context, interface_context, atlas, &self.app_context)
+                }
+                StateSort::AchievementsMenu(state) => {
+                    state.draw(context, interface_context, atlas, &self.app_context)
+                }
+                StateSort::Results(state) => {
+                    state.draw(context, interface_context, atlas, &self.app_context)
+                }
+                StateSort::MapGallery(state) => {
+                    state.draw(context, interface_context, atlas, &self.app_context)
+                }
+                StateSort::RulesMenu(state) => {
+                    state.draw(context, interface_context, atlas, &self.app_context)
+                }
+                StateSort::Tournament(state) => {
+                    state.draw(context, interface_context, atlas, &self.app_context)
+                }
+                StateSort::Bracket(state) => {
+                    state.draw(context, interface_context, atlas, &self.app_context)
+                }
+                StateSort::LobbySetup(state) => {
+                    state.draw(context, interface_context, atlas, &self.app_context)
+                }
+                StateSort::Calibration(state) => {
+                    state.draw(context, interface_context, atlas, &self.app_context)
+                }
             };
         }
 
+        if self.performance_hud.visible {
+            let mut state_lines = vec![format!("renderer {}", self.app_context.renderer.name())];
+
+            state_lines.extend(self.auto_performance.log().iter().cloned());
+
+            state_lines.extend(match &self.state_sort {
+                StateSort::Game(state) => state.performance_lines(),
+                StateSort::MainMenu(state) => state.performance_lines(),
+                StateSort::SettingsMenu(state) => state.performance_lines(),
+                StateSort::CritterpediaMenu(state) => state.performance_lines(),
+                StateSort::AchievementsMenu(state) => state.performance_lines(),
+                StateSort::Results(state) => state.performance_lines(),
+                StateSort::MapGallery(state) => state.performance_lines(),
+                StateSort::RulesMenu(state) => state.performance_lines(),
+                StateSort::Tournament(state) => state.performance_lines(),
+                StateSort::Bracket(state) => state.performance_lines(),
+                StateSort::LobbySetup(state) => state.performance_lines(),
+                StateSort::Calibration(state) => state.performance_lines(),
+            });
+
+            self.performance_hud
+                .draw(interface_context, atlas, &state_lines)?;
+        }
+
         // DRAW cursor
         draw_image(
             interface_context,
@@ -141,19 +259,65 @@ impl App {
         context.restore();
         interface_context.restore();
 
-        self.app_context.frame = (window().performance().unwrap().now() * 0.06) as usize;
+        self.app_context.clock.advance(now());
         self.app_context.pointer.swap();
         self.app_context.text_input = None;
 
+        self.performance_hud.record_draw(now() - draw_start);
+
         result
     }
 
-    pub fn tick(&mut self, text_input: &HtmlInputElement) {
+    /// Lowers or restores the canvas resolution and particle quality by the recent frame times,
+    /// resizing the canvases while keeping their size on the page.
+    fn adapt_performance(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        now: f64,
+    ) -> Result<(), PlatformError> {
+        let budget = self.app_context.frame_limit.interval().max(1000.0 / 60.0);
+
+        let changed = if self.app_context.render_options.auto_performance {
+            self.performance_hud.window_full()
+                && self
+                    .auto_performance
+                    .assess(self.performance_hud.frame_time(), budget, now)
+        } else {
+            self.auto_performance.reset(now)
+        };
+
+        self.app_context.render_options.particle_quality = self.auto_performance.particle_quality();
+
+        if !changed {
+            return Ok(());
+        }
+
+        // Measured at the new settings from here on
+        self.performance_hud.clear_window();
+
+        let canvas_settings = &mut self.app_context.canvas_settings;
+        canvas_settings.canvas_scale = self.auto_performance.canvas_scale();
+
+        for context in [context, interface_context] {
+            context.resize(
+                canvas_settings.element_width(),
+                canvas_settings.element_height(),
+                self.auto_performance.reduced(),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        let tick_start = now();
+
         let next_state = match &mut self.state_sort {
-            StateSort::Game(state) => state.tick(text_input, &self.app_context),
-            StateSort::MainMenu(state) => state.tick(text_input, &self.app_context),
+            StateSort::Game(state) => state.tick(&self.app_context),
+            StateSort::MainMenu(state) => state.tick(&self.app_context),
             StateSort::SettingsMenu(state) => {
-                let next_state = state.tick(text_input, &self.app_context);
+                let next_state = state.tick(&self.app_context);
 
                 self.app_context
                     .audio_system
@@ -161,14 +325,41 @@ impl App {
                 self.app_context
                     .audio_system
                     .set_clip_volume(state.clip_volume);
+                if state.render_options.webgl != self.app_context.render_options.webgl {
+                    self.app_context.renderer = create_renderer(state.render_options.webgl);
+                }
+
+                self.app_context.render_options = RenderOptions {
+                    particle_quality: self.app_context.render_options.particle_quality,
+                    ..state.render_options.clone()
+                };
+                self.app_context.aim_options = state.aim_options;
+                self.app_context.frame_limit = state.frame_limit;
+                self.app_context.haptics = state.haptics;
 
                 next_state
             }
+            StateSort::CritterpediaMenu(state) => state.tick(&self.app_context),
+            StateSort::AchievementsMenu(state) => state.tick(&self.app_context),
+            StateSort::Results(state) => state.tick(&self.app_context),
+            StateSort::MapGallery(state) => state.tick(&self.app_context),
+            StateSort::RulesMenu(state) => state.tick(&self.app_context),
+            StateSort::Tournament(state) => state.tick(&self.app_context),
+            StateSort::Bracket(state) => state.tick(&self.app_context),
+            StateSort::LobbySetup(state) => state.tick(&self.app_context),
+            StateSort::Calibration(state) => state.tick(&self.app_context),
         };
 
         if let Some(next_state) = next_state {
+            // The clips of games are only fetched once the first game is entered
+            if let StateSort::Game(_) = next_state {
+                self.app_context.audio_system.populate_game_audio();
+            }
+
             self.state_sort = next_state;
         }
+
+        self.performance_hud.record_tick(now() - tick_start);
     }
 
     pub fn session_id(&self) -> Option<&String> {
@@ -179,98 +370,124 @@ impl App {
         self.app_context.session_id = Some(session_id);
     }
 
-    pub fn on_blur(&mut self, _event: FocusEvent, text_input: &HtmlInputElement) {
-        if let Some(field) = text_input.dataset().get("field") {
-            self.app_context.text_input = Some((field, text_input.value()));
-            text_input.dataset().delete("field");
-        }
-    }
+    fn on_mouse_down(&mut self, button: MouseButton, shift: bool) {
+        self.mark_input();
 
-    pub fn on_mouse_down(&mut self, event: MouseEvent) {
-        match event.button() {
-            0 => self.app_context.pointer.button = true,
-            2 => self.app_context.pointer.alt_button = true,
-            _ => (),
+        self.app_context.pointer.modifier = shift;
+
+        match button {
+            MouseButton::Primary => self.app_context.pointer.button = true,
+            MouseButton::Secondary => self.app_context.pointer.alt_button = true,
+            MouseButton::Other => (),
         }
     }
 
-    pub fn on_mouse_up(&mut self, event: MouseEvent) {
-        match event.button() {
-            0 => self.app_context.pointer.button = false,
-            2 => self.app_context.pointer.alt_button = false,
-            _ => (),
+    fn on_mouse_up(&mut self, button: MouseButton) {
+        self.mark_input();
+
+        match button {
+            MouseButton::Primary => self.app_context.pointer.button = false,
+            MouseButton::Secondary => self.app_context.pointer.alt_button = false,
+            MouseButton::Other => (),
         }
     }
 
-    pub fn on_mouse_move(&mut self, bound: &DomRectReadOnly, event: MouseEvent) {
-        let x = event.page_x() - bound.left() as i32;
-        let y = event.page_y() - bound.top() as i32;
-        let pointer_location =
-            App::transform_pointer(&self.app_context.canvas_settings, bound, x, y);
+    fn on_mouse_move(&mut self, x: f64, y: f64, shift: bool) {
+        self.mark_input();
+
+        let pointer_location = App::transform_pointer(
+            &self.app_context.canvas_settings,
+            self.display_size,
+            x as i32,
+            y as i32,
+        );
 
         self.app_context.pointer.location = pointer_location;
+        self.app_context.pointer.modifier = shift;
 
-        event.prevent_default();
+        if self.last_touch_frame.is_none_or(|touch_frame| {
+            self.app_context.clock.since(touch_frame) > TOUCH_MOUSE_FRAMES
+        }) {
+            self.app_context.pointer.touch_reach = 0;
+        }
     }
 
-    pub fn on_touch_start(&mut self, bound: &DomRectReadOnly, event: TouchEvent) {
-        if let Some(touch) = event.target_touches().item(0) {
-            let x = touch.page_x() - bound.left() as i32;
-            let y = touch.page_y() - bound.top() as i32;
+    fn on_touch(&mut self, touch: Option<(f64, f64)>) {
+        self.mark_input();
 
-            let pointer_location =
-                App::transform_pointer(&self.app_context.canvas_settings, bound, x, y);
+        if let Some((x, y)) = touch {
+            let pointer_location = App::transform_pointer(
+                &self.app_context.canvas_settings,
+                self.display_size,
+                x as i32,
+                y as i32,
+            );
 
             self.app_context.pointer.location = pointer_location;
         }
-    }
 
-    pub fn on_touch_end(&mut self, bound: &DomRectReadOnly, event: TouchEvent) {
-        if let Some(touch) = event.target_touches().item(0) {
-            let x = touch.page_x() - bound.left() as i32;
-            let y = touch.page_y() - bound.top() as i32;
-
-            let pointer_location =
-                App::transform_pointer(&self.app_context.canvas_settings, bound, x, y);
-            self.app_context.pointer.location = pointer_location;
-        }
+        self.mark_touch();
+    }
 
-        self.app_context.pointer.button = false;
+    fn mark_input(&mut self) {
+        self.app_context.last_input_frame = self.app_context.clock.frame();
     }
 
-    pub fn on_touch_move(&mut self, bound: &DomRectReadOnly, event: TouchEvent) {
-        if let Some(touch) = event.target_touches().item(0) {
-            let x = touch.page_x() - bound.left() as i32;
-            let y = touch.page_y() - bound.top() as i32;
+    fn mark_touch(&mut self) {
+        self.last_touch_frame = Some(self.app_context.clock.frame());
+        self.app_context.pointer.touch_reach =
+            App::touch_reach(&self.app_context.canvas_settings, self.display_size.0);
+    }
 
-            let pointer_location =
-                App::transform_pointer(&self.app_context.canvas_settings, bound, x, y);
-            self.app_context.pointer.location = pointer_location;
-        }
+    /// Converts the fingertip radius into interface pixels at the canvas's current display size.
+    fn touch_reach(canvas_settings: &CanvasSettings, display_width: f64) -> i32 {
+        let interface_per_css = canvas_settings.element_width() as f64
+            / display_width.max(1.0)
+            / canvas_settings.canvas_scale;
 
-        event.prevent_default();
+        ((TOUCH_REACH_CSS * interface_per_css).round() as i32).clamp(4, 16)
     }
 
     fn transform_pointer(
         canvas_settings: &CanvasSettings,
-        bound: &DomRectReadOnly,
+        (display_width, display_height): (f64, f64),
         x: i32,
         y: i32,
     ) -> (i32, i32) {
-        let x = (x as f64 * (canvas_settings.element_width() as f64 / bound.width()))
+        let x = (x as f64 * (canvas_settings.element_width() as f64 / display_width))
             / canvas_settings.canvas_scale;
-        let y = (y as f64 * (canvas_settings.element_height() as f64 / bound.height()))
+        let y = (y as f64 * (canvas_settings.element_height() as f64 / display_height))
             / canvas_settings.canvas_scale;
 
         Pointer::location_from_real(canvas_settings, (x as i32, y as i32))
     }
 
+    /// Handles a key press, returning whether the platform should not act on it.
     #[allow(clippy::single_match)]
-    pub fn on_key_down(&mut self, event: KeyboardEvent) {
+    fn on_key_down(&mut self, code: &str, shift: bool) -> bool {
+        self.mark_input();
+
+        let mut consumed = false;
+
+        if let StateSort::Game(state) = &mut self.state_sort {
+            if let Some(formation) = Formation::from_key(code) {
+                state.apply_formation(&self.app_context, formation);
+            } else if code == "Tab" {
+                // Keeps the browser from moving the focus off the canvas
+                consumed = true;
+                state.cycle_bug(&self.app_context, shift);
+            }
+        }
+
+        #[cfg(not(feature = "deploy"))]
+        if code == "KeyP" {
+            self.performance_hud.toggle();
+        }
+
         #[cfg(not(feature = "deploy"))]
         match &mut self.state_sort {
             StateSort::Game(state) => {
-                match event.code().as_str() {
+                match code {
                     "KeyM" => {
                         state.print_turns();
                     }
@@ -279,28 +496,112 @@ impl App {
             }
             _ => (),
         }
+
+        consumed
     }
 
-    pub fn on_session_response(&mut self, value: JsValue) {
-        let session_request: SessionRequest = serde_wasm_bindgen::from_value(value).unwrap();
-        let session_id = session_request.session_id;
+    pub fn on_session_response(&mut self, session_grant: SessionGrant) {
+        let session_id = session_grant.session_id;
+
+        if session_grant.balance_hash != 0 && session_grant.balance_hash != balance().hash() {
+            warn!("balance config differs from the server's");
+        }
+
+        info!("session granted in region {}", active_region());
 
         self.set_session_id(session_id.clone());
 
-        storage().map(|storage| storage.set_item("session_id", session_id.as_str()));
+        if let Some(storage) = storage() {
+            storage.set("session_region", active_region());
+            storage.set("session_id", session_id.as_str());
+            storage.set("session_token", session_grant.token.as_str());
+        }
     }
 
     pub fn kv_set(key: &str, value: &str) {
-        storage().and_then(|storage| storage.set_item(key, value).ok());
+        if let Some(storage) = storage() {
+            storage.set(key, value);
+        }
+    }
+
+    pub fn kv_remove(key: &str) {
+        if let Some(storage) = storage() {
+            storage.remove(key);
+        }
     }
 
     pub fn kv_get(key: &str) -> String {
         storage()
-            .and_then(|storage| storage.get_item(key).unwrap_or_default())
+            .and_then(|storage| storage.get(key))
             .unwrap_or_default()
     }
 }
 
+impl InputHandler for App {
+    fn handle_input(&mut self, event: InputEvent) -> bool {
+        match event {
+            InputEvent::Resize { width, height } => self.display_size = (width, height),
+            InputEvent::MouseDown { button, shift } => self.on_mouse_down(button, shift),
+            InputEvent::MouseUp { button } => self.on_mouse_up(button),
+            InputEvent::MouseMove { x, y, shift } => self.on_mouse_move(x, y, shift),
+            InputEvent::TouchStart { touch } | InputEvent::TouchMove { touch } => {
+                self.on_touch(touch)
+            }
+            InputEvent::TouchEnd { touch } => {
+                self.on_touch(touch);
+                self.app_context.pointer.button = false;
+            }
+            InputEvent::KeyDown { code, shift } => return self.on_key_down(&code, shift),
+            InputEvent::TextInput { field, value } => {
+                self.app_context.text_input = Some((field, value))
+            }
+            InputEvent::VisibilityChange { hidden } => self.app_context.hidden = hidden,
+        }
+
+        false
+    }
+}
+
+impl Application for App {
+    fn frame(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+    ) -> Result<(), PlatformError> {
+        let atlas = self.app_context.atlas_context.clone();
+
+        self.tick();
+        self.draw(context, interface_context, atlas.as_ref())
+    }
+
+    fn poll(&mut self) {
+        self.app_context.clock.refresh(now());
+
+        match &mut self.state_sort {
+            StateSort::Game(state) => state.poll(&self.app_context),
+            StateSort::MainMenu(state) => state.poll(&self.app_context),
+            StateSort::SettingsMenu(state) => state.poll(&self.app_context),
+            StateSort::CritterpediaMenu(state) => state.poll(&self.app_context),
+            StateSort::AchievementsMenu(state) => state.poll(&self.app_context),
+            StateSort::Results(state) => state.poll(&self.app_context),
+            StateSort::MapGallery(state) => state.poll(&self.app_context),
+            StateSort::RulesMenu(state) => state.poll(&self.app_context),
+            StateSort::Tournament(state) => state.poll(&self.app_context),
+            StateSort::Bracket(state) => state.poll(&self.app_context),
+            StateSort::LobbySetup(state) => state.poll(&self.app_context),
+            StateSort::Calibration(state) => state.poll(&self.app_context),
+        }
+    }
+
+    fn hidden(&self) -> bool {
+        self.app_context.hidden
+    }
+
+    fn frame_interval(&self) -> f64 {
+        self.app_context.frame_limit.interval()
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct CanvasSettings {
     pub interface_width: u32,
@@ -358,3 +659,136 @@ impl CanvasSettings {
         }
     }
 }
+
+#[derive(Clone, Default)]
+pub struct RenderOptions {
+    pub high_contrast: bool,
+    pub reduced_effects: bool,
+    pub webgl: bool,
+    /// Whether spectators and replays show the impulses of the resolving turn.
+    pub intent_arrows: bool,
+    /// Whether online games show the latency and sync diagnostics panel.
+    pub diagnostics: bool,
+    /// Whether the resolution and particles are lowered automatically while frames run slow.
+    pub auto_performance: bool,
+    /// Share of particles kept, lowered by [`AutoPerformance`] under load.
+    pub particle_quality: f64,
+}
+
+impl RenderOptions {
+    pub fn load() -> RenderOptions {
+        let settings = Settings::load();
+
+        RenderOptions {
+            high_contrast: settings.high_contrast,
+            reduced_effects: settings.reduced_effects,
+            webgl: settings.webgl,
+            intent_arrows: settings.intent_arrows,
+            diagnostics: settings.diagnostics,
+            auto_performance: settings.auto_performance,
+            particle_quality: 1.0,
+        }
+    }
+
+    pub fn save(&self) {
+        Settings::update(|settings| {
+            settings.high_contrast = self.high_contrast;
+            settings.reduced_effects = self.reduced_effects;
+            settings.webgl = self.webgl;
+            settings.intent_arrows = self.intent_arrows;
+            settings.diagnostics = self.diagnostics;
+            settings.auto_performance = self.auto_performance;
+        });
+    }
+
+    pub fn particles(&self) -> bool {
+        !self.reduced_effects
+    }
+
+    pub fn screen_shake(&self) -> bool {
+        !self.reduced_effects
+    }
+
+    pub fn trails(&self) -> bool {
+        !self.reduced_effects
+    }
+
+    pub fn selection_ring_width(&self) -> f64 {
+        if self.high_contrast {
+            3.0
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Aim assist applied to the impulse intent while a bug is selected.
+#[derive(Clone, Copy, Default)]
+pub struct AimOptions {
+    /// Smooths the pointer with an exponential moving average.
+    pub smoothing: bool,
+    /// Snaps the intent to zero close to the bug.
+    pub dead_zone: bool,
+}
+
+impl AimOptions {
+    pub fn load() -> AimOptions {
+        let settings = Settings::load();
+
+        AimOptions {
+            smoothing: settings.aim_smoothing,
+            dead_zone: settings.aim_dead_zone,
+        }
+    }
+
+    pub fn save(&self) {
+        Settings::update(|settings| {
+            settings.aim_smoothing = self.smoothing;
+            settings.aim_dead_zone = self.dead_zone;
+        });
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum FrameLimit {
+    #[default]
+    Uncapped,
+    Sixty,
+    Thirty,
+}
+
+impl FrameLimit {
+    pub fn load() -> FrameLimit {
+        Settings::load().frame_limit
+    }
+
+    pub fn save(&self) {
+        let frame_limit = *self;
+
+        Settings::update(|settings| settings.frame_limit = frame_limit);
+    }
+
+    pub fn value(&self) -> usize {
+        match self {
+            FrameLimit::Uncapped => 0,
+            FrameLimit::Sixty => 60,
+            FrameLimit::Thirty => 30,
+        }
+    }
+
+    /// Minimum milliseconds between two drawn frames.
+    pub fn interval(&self) -> f64 {
+        match self {
+            FrameLimit::Uncapped => 0.0,
+            _ => 1000.0 / self.value() as f64,
+        }
+    }
+
+    /// Number of game ticks to run per drawn frame so the simulation stays at 60 ticks per second.
+    pub fn ticks_per_frame(&self) -> usize {
+        match self {
+            FrameLimit::Thirty => 2,
+            _ => 1,
+        }
+    }
+}
@@ -1,12 +1,19 @@
-use std::collections::HashMap;
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+};
 
-use js_sys::{ArrayBuffer, Math, Uint8Array};
-use wasm_bindgen::JsCast;
-use web_sys::{console, AudioBuffer, AudioContext, GainNode};
+use serde::Deserialize;
 
 use super::SettingsMenuState;
+use crate::{
+    net::{fetch, request_clip_manifest},
+    platform::{network, platform, random, spawn, AudioOutput, HttpRequest, Sound, Voice},
+    warn, RESOURCE_BASE_URL,
+};
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Deserialize)]
 pub enum ClipId {
     CrackleI,
     CrackleII,
@@ -32,51 +39,92 @@ pub enum ClipId {
     MapDecreaseSize,
     StarSparkle,
     MusicI,
+    Thud,
+    Knock,
 }
 
-#[derive(Clone, Debug)]
+/// What a bug collided with, which picks the sound of the impact.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ImpactMaterial {
+    Bug,
+    Rock,
+    Wall,
+}
+
+/// Impact speed at which collisions are heard at their quietest and highest.
+const IMPACT_SPEED_MIN: f32 = 2.0;
+
+/// Impact speed at which collisions are heard at their loudest and deepest.
+const IMPACT_SPEED_MAX: f32 = 12.0;
+
+/// Largest random change of the pitch of a collision, so repeated impacts differ.
+const IMPACT_PITCH_JITTER: f64 = 0.08;
+
+#[derive(Clone)]
 pub struct AudioClip {
-    buffer: AudioBuffer,
+    sound: Sound,
     volume: f32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct AudioSystem {
-    context: AudioContext,
-    audio_clips: HashMap<ClipId, AudioClip>,
-    music_gain: Option<GainNode>,
+    /// Decoded clips, shared by all clones so background decodes reach every one of them.
+    audio_clips: Rc<RefCell<HashMap<ClipId, AudioClip>>>,
+    /// Clips listed in the manifest, fetched once they are needed.
+    clip_sources: Rc<RefCell<Vec<ClipSource>>>,
+    game_audio_requested: Rc<Cell<bool>>,
+    loading: Rc<Cell<AudioLoading>>,
+    music_voice: Option<Rc<dyn Voice>>,
     base_volume: f32,
     music_volume: i8,
     clip_volume: i8,
 }
 
 impl AudioSystem {
-    pub async fn register_audio_clip(&mut self, clip_id: ClipId, data: &[u8], volume: f32) {
-        let promise = self
-            .context
-            .decode_audio_data(&u8_slice_to_array_buffer(data))
-            .ok();
-
-        if let Some(promise) = promise {
-            let buffer = wasm_bindgen_futures::JsFuture::from(promise)
-                .await
-                .unwrap()
-                .dyn_into::<AudioBuffer>()
-                .unwrap();
-
-            let audio_clip = AudioClip { buffer, volume };
-
-            console::log_1(&format!("{:?}", audio_clip).into());
-
-            self.audio_clips.insert(clip_id, audio_clip);
+    /// Fetches and decodes a [`ClipSource`], returns `false` if either failed.
+    async fn register_audio_clip(&self, clip_source: &ClipSource) -> bool {
+        let url = format!("{RESOURCE_BASE_URL}/static/wav/{}", clip_source.file);
+
+        let Some(data) = fetch_bytes(&url).await else {
+            warn!("failed to fetch {:?}", clip_source.clip_id);
+
+            return false;
+        };
+
+        let Some(output) = AudioSystem::output() else {
+            return false;
+        };
+
+        match output.decode(data).await {
+            Some(sound) => {
+                self.audio_clips.borrow_mut().insert(
+                    clip_source.clip_id.clone(),
+                    AudioClip {
+                        sound,
+                        volume: clip_source.volume,
+                    },
+                );
+
+                true
+            }
+            None => {
+                warn!("failed to decode {:?}", clip_source.clip_id);
+
+                false
+            }
         }
     }
 
+    /// The platform's audio output, unless the device has none.
+    fn output() -> Option<&'static dyn AudioOutput> {
+        platform().audio.as_deref()
+    }
+
     pub fn set_music_volume(&mut self, volume: i8) {
         self.music_volume = volume;
 
-        if let Some(gain_node) = &self.music_gain {
-            gain_node.gain().set_value(self.music_volume());
+        if let Some(music_voice) = &self.music_voice {
+            music_voice.set_volume(self.music_volume());
         }
     }
 
@@ -93,21 +141,19 @@ impl AudioSystem {
     }
 
     pub fn play_clip(&self, clip_id: ClipId) {
-        if let Some(audio_clip) = self.audio_clips.get(&clip_id) {
-            let real_volume = audio_clip.volume * self.base_volume * self.clip_volume();
-
-            let buffer_source = self.context.create_buffer_source().unwrap();
-            buffer_source.set_buffer(Some(&audio_clip.buffer));
-
-            let gain_node = self.context.create_gain().unwrap();
-            gain_node.gain().set_value(real_volume);
+        self.play_clip_varied(clip_id, 1.0, 1.0);
+    }
 
-            buffer_source.connect_with_audio_node(&gain_node).unwrap();
-            gain_node
-                .connect_with_audio_node(&self.context.destination())
-                .unwrap();
+    /// Plays a clip at a playback rate, which shifts its pitch and length, and a share of its
+    /// volume.
+    pub fn play_clip_varied(&self, clip_id: ClipId, playback_rate: f32, volume: f32) {
+        if let (Some(output), Some(audio_clip)) = (
+            AudioSystem::output(),
+            self.audio_clips.borrow().get(&clip_id),
+        ) {
+            let real_volume = audio_clip.volume * self.base_volume * self.clip_volume() * volume;
 
-            buffer_source.start_with_when(0.0).unwrap();
+            output.play(&audio_clip.sound, playback_rate, real_volume, false);
         }
     }
 
@@ -118,195 +164,193 @@ impl AudioSystem {
     }
 
     pub fn play_music(&mut self, clip_id: ClipId) {
-        if let Some(audio_clip) = self.audio_clips.get(&clip_id) {
+        if let (Some(output), Some(audio_clip)) = (
+            AudioSystem::output(),
+            self.audio_clips.borrow().get(&clip_id),
+        ) {
             let real_volume = audio_clip.volume * self.base_volume * self.music_volume();
 
-            let buffer_source = self.context.create_buffer_source().unwrap();
-            buffer_source.set_buffer(Some(&audio_clip.buffer));
-
-            let gain_node = self.context.create_gain().unwrap();
-            gain_node.gain().set_value(real_volume);
+            self.music_voice = output.play(&audio_clip.sound, 1.0, real_volume, true);
+        }
+    }
 
-            buffer_source.connect_with_audio_node(&gain_node).unwrap();
-            gain_node
-                .connect_with_audio_node(&self.context.destination())
-                .unwrap();
+    /// Plays the sound of a bug colliding with something. The clip follows the material struck,
+    /// and faster impacts sound louder and deeper, with a random nudge to the pitch.
+    pub fn play_impact(&self, material: ImpactMaterial, speed: f32) {
+        let intensity = ((speed - IMPACT_SPEED_MIN) / (IMPACT_SPEED_MAX - IMPACT_SPEED_MIN))
+            .clamp(0.0, 1.0) as f64;
+        let jitter = (random() * 2.0 - 1.0) * IMPACT_PITCH_JITTER;
+
+        let (clip_id, pitch, volume) = match material {
+            ImpactMaterial::Bug => {
+                let rand = random();
+                let clip_id = if rand < 0.33 {
+                    ClipId::ZapI
+                } else if rand < 0.66 {
+                    ClipId::ZapII
+                } else {
+                    ClipId::ZapIII
+                };
+
+                (clip_id, 1.15 - 0.3 * intensity, 0.6 + 0.4 * intensity)
+            }
+            ImpactMaterial::Rock => (ClipId::Knock, 1.1 - 0.3 * intensity, 0.5 + 0.5 * intensity),
+            ImpactMaterial::Wall => (ClipId::Thud, 1.0 - 0.25 * intensity, 0.3 + 0.5 * intensity),
+        };
+
+        self.play_clip_varied(clip_id, (pitch + jitter) as f32, volume as f32);
+
+        // The hardest hits between bugs crackle on top
+        if material == ImpactMaterial::Bug && intensity > 0.75 {
+            self.play_clip(ClipId::CrackleI);
+        }
+    }
 
-            buffer_source.set_loop(true);
+    /// Fetches the clip manifest, then the clips of the menus, so the menu can start without
+    /// waiting on the rest.
+    pub async fn populate_critical_audio(&self) {
+        let manifest = match fetch(request_clip_manifest()).await {
+            Ok(response) => {
+                serde_json::from_slice::<ClipManifest>(&response.body).unwrap_or_else(|err| {
+                    warn!("invalid clip manifest: {err}");
+                    ClipManifest::default()
+                })
+            }
+            Err(err) => {
+                warn!("could not fetch the clip manifest: {err}");
+                ClipManifest::default()
+            }
+        };
+
+        *self.clip_sources.borrow_mut() = manifest.clips;
+
+        let menu_clips = self.clip_sources_of(ClipGroup::Menu);
+
+        self.loading.set(AudioLoading {
+            requested: menu_clips.len(),
+            ..self.loading.get()
+        });
+
+        for clip_source in &menu_clips {
+            self.register_audio_clip(clip_source).await;
+        }
 
-            buffer_source.start_with_when(0.0).unwrap();
+        self.loading.set(AudioLoading {
+            loaded: self.audio_clips.borrow().len(),
+            failed: menu_clips.len() - self.audio_clips.borrow().len(),
+            ..self.loading.get()
+        });
+    }
 
-            self.music_gain = Some(gain_node);
+    /// Fetches and decodes the clips of games in the background on the first call, retrying
+    /// failed ones a few times.
+    ///
+    /// Clips play as soon as they are decoded, those still missing are silently skipped.
+    pub fn populate_game_audio(&self) {
+        if self.game_audio_requested.replace(true) {
+            return;
         }
-    }
 
-    pub fn play_random_zap(&self, hits: usize) {
-        let rand = Math::random();
+        let audio_system = self.clone();
+        let game_clips = self.clip_sources_of(ClipGroup::Game);
+
+        self.loading.set(AudioLoading {
+            requested: self.loading.get().requested + game_clips.len(),
+            ..self.loading.get()
+        });
+
+        spawn(async move {
+            let mut queue: VecDeque<(ClipSource, usize)> = game_clips
+                .into_iter()
+                .map(|clip_source| (clip_source, 1))
+                .collect();
+
+            while let Some((clip_source, attempt)) = queue.pop_front() {
+                let registered = audio_system.register_audio_clip(&clip_source).await;
+                let mut loading = audio_system.loading.get();
+
+                if registered {
+                    loading.loaded += 1;
+                } else if attempt < MAX_DECODE_ATTEMPTS {
+                    queue.push_back((clip_source, attempt + 1));
+                } else {
+                    loading.failed += 1;
+                }
+
+                audio_system.loading.set(loading);
+            }
+        });
+    }
 
-        if rand < 0.33 {
-            self.play_clip(ClipId::ZapI);
-        } else if rand < 0.66 {
-            self.play_clip(ClipId::ZapII);
-        } else {
-            self.play_clip(ClipId::ZapIII);
-        }
+    fn clip_sources_of(&self, group: ClipGroup) -> Vec<ClipSource> {
+        self.clip_sources
+            .borrow()
+            .iter()
+            .filter(|clip_source| clip_source.group == group)
+            .cloned()
+            .collect()
+    }
 
-        match hits {
-            0 => (),
-            1 => self.play_clip(ClipId::CrackleI),
-            2 => self.play_clip(ClipId::CrackleII),
-            _ => self.play_clip(ClipId::CrackleIII),
-        }
+    /// Returns the progress of decoding all clips.
+    pub fn loading(&self) -> AudioLoading {
+        self.loading.get()
     }
+}
 
-    pub async fn populate_audio(&mut self) {
-        {
-            // COMBAT Crackle Implemented
-            self.register_audio_clip(
-                ClipId::CrackleI,
-                include_bytes!("../../static/wav/COMBAT_Crackle_1.wav"),
-                1.0,
-            )
-            .await;
-            self.register_audio_clip(
-                ClipId::CrackleII,
-                include_bytes!("../../static/wav/COMBAT_Crackle_2.wav"),
-                1.0,
-            )
-            .await;
-            self.register_audio_clip(
-                ClipId::CrackleIII,
-                include_bytes!("../../static/wav/COMBAT_Crackle_3.wav"),
-                1.0,
-            )
-            .await;
-        }
+/// When a clip is needed, which decides when it is fetched.
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+enum ClipGroup {
+    /// Clips of the menus, fetched and decoded before the first frame.
+    Menu,
+    /// Clips of games, fetched in the background once the first game is entered.
+    Game,
+}
 
-        {
-            // COMBAT Hit Implemented
-            self.register_audio_clip(
-                ClipId::ZapI,
-                include_bytes!("../../static/wav/COMBAT_Hit_1.wav"),
-                1.0,
-            )
-            .await;
-            self.register_audio_clip(
-                ClipId::ZapII,
-                include_bytes!("../../static/wav/COMBAT_Hit_2.wav"),
-                1.0,
-            )
-            .await;
-            self.register_audio_clip(
-                ClipId::ZapII,
-                include_bytes!("../../static/wav/COMBAT_Hit_3.wav"),
-                1.0,
-            )
-            .await;
-        }
+/// A clip listed in the manifest, fetched from the static directory and decoded into an
+/// [`AudioClip`] on load.
+#[derive(Deserialize, Clone, Debug)]
+struct ClipSource {
+    #[serde(rename = "id")]
+    clip_id: ClipId,
+    /// File name in the `static/wav` directory.
+    file: String,
+    volume: f32,
+    group: ClipGroup,
+}
 
-        {
-            // POWERUP Implemented
-            self.register_audio_clip(
-                ClipId::Diagonal,
-                include_bytes!("../../static/wav/POWERUP_Diagonal.wav"),
-                1.0,
-            )
-            .await;
-            self.register_audio_clip(
-                ClipId::Beam,
-                include_bytes!("../../static/wav/POWERUP_BigLaser.wav"),
-                1.0,
-            )
-            .await;
-            self.register_audio_clip(
-                ClipId::Shield,
-                include_bytes!("../../static/wav/POWERUP_Shield.wav"),
-                1.0,
-            )
-            .await;
-        }
+/// The clips of the game, kept out of the binary so the first load stays small.
+#[derive(Deserialize, Default, Debug)]
+struct ClipManifest {
+    clips: Vec<ClipSource>,
+}
 
-        {
-            // UI Battle Implemented
-            self.register_audio_clip(
-                ClipId::MageDeselect,
-                include_bytes!("../../static/wav/UI_Battle_MageDeSelect.wav"),
-                1.0,
-            )
-            .await;
-            self.register_audio_clip(
-                ClipId::MageSelect,
-                include_bytes!("../../static/wav/UI_Battle_MageSelect.wav"),
-                1.0,
-            )
-            .await;
-            // self.register_audio_clip(
-            //     ClipId::MageMove,
-            //     include_bytes!("../../static/wav/UI_Battle_MageMoveToSquare_2.wav"),
-            //     1.0,
-            // )
-            // .await;
-        }
+/// Attempts at fetching and decoding a game clip before giving up on it.
+const MAX_DECODE_ATTEMPTS: usize = 3;
 
-        {
-            // UI Click Implemented
-            self.register_audio_clip(
-                ClipId::ClickBack,
-                include_bytes!("../../static/wav/UI_Click_Back.wav"),
-                1.0,
-            )
-            .await;
-            self.register_audio_clip(
-                ClipId::ClickForward,
-                include_bytes!("../../static/wav/UI_Click_Forward.wav"),
-                1.0,
-            )
-            .await;
-        }
+/// Progress of fetching and decoding the clips requested so far.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AudioLoading {
+    pub requested: usize,
+    pub loaded: usize,
+    pub failed: usize,
+}
 
-        {
-            // UI Level
-            self.register_audio_clip(
-                ClipId::LevelEnter,
-                include_bytes!("../../static/wav/UI_LevelChangeWhoosh.wav"),
-                1.0,
-            )
-            .await;
-            self.register_audio_clip(
-                ClipId::LevelSuccess,
-                include_bytes!("../../static/wav/UI_LevelFinish_Success.wav"),
-                1.0,
-            )
-            .await;
-            self.register_audio_clip(
-                ClipId::LevelFailure,
-                include_bytes!("../../static/wav/UI_LevelFinish_Failure.wav"),
-                1.0,
-            )
-            .await;
-            // self.register_audio_clip(
-            //     ClipId::StarSparkle,
-            //     include_bytes!("../../static/wav/UI_LevelCompleteCrystals.wav"),
-            //     1.0,
-            // )
-            // .await;
-        }
+impl AudioLoading {
+    pub fn total(&self) -> usize {
+        self.requested
+    }
 
-        // {
-        //     self.register_audio_clip(
-        //         ClipId::MusicI,
-        //         include_bytes!("../../static/wav/music_1.mp3"),
-        //         1.0,
-        //     )
-        //     .await;
-        // }
+    /// Determines if every clip has either been decoded or given up on.
+    pub fn done(&self) -> bool {
+        self.loaded + self.failed >= self.total()
     }
 }
 
-fn u8_slice_to_array_buffer(u8_slice: &[u8]) -> ArrayBuffer {
-    let uint8_array = Uint8Array::new_with_length(u8_slice.len() as u32);
-    uint8_array.set(&Uint8Array::from(u8_slice), 0);
-    ArrayBuffer::from(uint8_array.buffer())
+/// Fetches a static resource as raw bytes.
+async fn fetch_bytes(url: &str) -> Option<Vec<u8>> {
+    let response = network().fetch(HttpRequest::get(url)).await.ok()?;
+
+    response.ok().then_some(response.body)
 }
 
 impl Default for AudioSystem {
@@ -314,10 +358,12 @@ impl Default for AudioSystem {
         let (music_volume, clip_volume) = SettingsMenuState::load_volume();
 
         Self {
-            context: AudioContext::new().unwrap(),
             audio_clips: Default::default(),
+            clip_sources: Default::default(),
+            game_audio_requested: Default::default(),
+            loading: Default::default(),
             base_volume: 1.0,
-            music_gain: None,
+            music_voice: None,
             music_volume,
             clip_volume,
         }
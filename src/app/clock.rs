@@ -0,0 +1,91 @@
+use std::{cell::RefCell, collections::VecDeque};
+
+use crate::platform::system;
+
+/// Count of 60 Hz frames, the unit particles, animations and turn timing are all measured in,
+/// matching the width of the simulation's tick counter.
+pub type Frame = u64;
+
+/// Frames counted per second, the same rate the simulation ticks at.
+pub const FRAMES_PER_SECOND: f64 = 60.0;
+
+/// Server timestamps kept to estimate the offset of the server's clock.
+const CLOCK_SAMPLES: usize = 16;
+
+/// The client's single source of time: the frame counter, the time between frames and the
+/// estimated offset of the server's clock.
+#[derive(Default)]
+pub struct GameClock {
+    frame: Frame,
+    delta: f64,
+    last_now: Option<f64>,
+    server_samples: RefCell<VecDeque<f64>>,
+}
+
+impl GameClock {
+    /// Local wall-clock time in seconds since the epoch.
+    pub fn local_time() -> f64 {
+        system().time() / 1000.0
+    }
+
+    /// Moves the clock to `now`, a monotonic timestamp in milliseconds, once per drawn frame.
+    pub fn advance(&mut self, now: f64) {
+        self.delta = self
+            .last_now
+            .map(|last_now| ((now - last_now) / 1000.0).max(0.0))
+            .unwrap_or_default();
+        self.last_now = Some(now);
+        self.refresh(now);
+    }
+
+    /// Moves the frame counter to `now` without counting a drawn frame, for work scheduled
+    /// outside the animation loop such as network polling.
+    pub fn refresh(&mut self, now: f64) {
+        self.frame = (now / 1000.0 * FRAMES_PER_SECOND) as Frame;
+    }
+
+    /// The current frame.
+    pub fn frame(&self) -> Frame {
+        self.frame
+    }
+
+    /// Seconds between the last two advances of the clock.
+    pub fn delta(&self) -> f64 {
+        self.delta
+    }
+
+    /// Frames elapsed since an earlier frame, zero if it lies in the future.
+    pub fn since(&self, earlier: Frame) -> Frame {
+        self.frame.saturating_sub(earlier)
+    }
+
+    /// Records a server timestamp from a response which has just arrived.
+    pub fn observe_server_time(&self, server_time: f64) {
+        if server_time <= 0.0 {
+            return;
+        }
+
+        let mut samples = self.server_samples.borrow_mut();
+        samples.push_back(server_time - GameClock::local_time());
+
+        if samples.len() > CLOCK_SAMPLES {
+            samples.pop_front();
+        }
+    }
+
+    /// Seconds to add to the local clock to read the server's, if any response was observed.
+    ///
+    /// Every sample lags behind by the response's transit time, so the largest is the closest.
+    pub fn server_offset(&self) -> Option<f64> {
+        self.server_samples
+            .borrow()
+            .iter()
+            .copied()
+            .reduce(f64::max)
+    }
+
+    /// Converts a server timestamp to the local clock.
+    pub fn server_to_local(&self, server_time: f64) -> Option<f64> {
+        self.server_offset().map(|offset| server_time - offset)
+    }
+}
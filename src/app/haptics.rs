@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use super::Settings;
+use crate::platform::system;
+
+/// Strength of vibration feedback.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum HapticIntensity {
+    Off,
+    Light,
+    Strong,
+}
+
+impl HapticIntensity {
+    /// The intensity listed after this one, wrapping around.
+    pub fn next(&self) -> HapticIntensity {
+        match self {
+            HapticIntensity::Off => HapticIntensity::Light,
+            HapticIntensity::Light => HapticIntensity::Strong,
+            HapticIntensity::Strong => HapticIntensity::Off,
+        }
+    }
+
+    /// Name of the intensity for menus.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HapticIntensity::Off => "Off",
+            HapticIntensity::Light => "Light",
+            HapticIntensity::Strong => "Strong",
+        }
+    }
+
+    /// Scale applied to the length of every pulse.
+    fn scale(&self) -> f64 {
+        match self {
+            HapticIntensity::Off => 0.0,
+            HapticIntensity::Light => 0.5,
+            HapticIntensity::Strong => 1.0,
+        }
+    }
+}
+
+/// A moment of play felt through vibration.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Haptic {
+    /// One of your bugs was struck.
+    Hit,
+    /// A new turn opened for planning.
+    TurnStart,
+    /// Three seconds of planning are left.
+    Countdown,
+}
+
+impl Haptic {
+    /// Alternating vibration and pause lengths in milliseconds, at full intensity.
+    fn pattern(&self) -> &'static [u32] {
+        match self {
+            Haptic::Hit => &[40],
+            Haptic::TurnStart => &[60, 80, 60],
+            Haptic::Countdown => &[20, 120, 20, 120, 20],
+        }
+    }
+}
+
+/// Routes vibration feedback to the platform, on devices which support it.
+#[derive(Clone, Copy, Debug)]
+pub struct Haptics {
+    pub intensity: HapticIntensity,
+}
+
+impl Haptics {
+    pub fn load() -> Haptics {
+        Haptics {
+            intensity: Settings::load().haptics,
+        }
+    }
+
+    pub fn save(&self) {
+        let intensity = self.intensity;
+
+        Settings::update(|settings| settings.haptics = intensity);
+    }
+
+    /// Determines if the device can vibrate, which desktops mostly cannot.
+    pub fn supported() -> bool {
+        system().can_vibrate()
+    }
+
+    /// Vibrates the device with the pattern of a [`Haptic`], scaled to the chosen intensity.
+    pub fn play(&self, haptic: Haptic) {
+        if self.intensity == HapticIntensity::Off || !Haptics::supported() {
+            return;
+        }
+
+        let pattern: Vec<f64> = haptic
+            .pattern()
+            .iter()
+            .enumerate()
+            .map(|(i, duration)| {
+                // Only the vibrations are scaled, the pauses between them keep their length
+                if i % 2 == 0 {
+                    (*duration as f64 * self.intensity.scale()).round()
+                } else {
+                    *duration as f64
+                }
+            })
+            .collect();
+
+        system().vibrate(&pattern);
+    }
+}
@@ -1,13 +1,21 @@
 mod app;
 mod audio;
+mod clock;
+mod haptics;
 mod particle;
+mod performance;
 mod pointer;
+mod settings;
 mod state;
 mod ui;
 
 pub use app::*;
 pub use audio::*;
+pub use clock::*;
+pub use haptics::*;
 pub use particle::*;
+pub use performance::*;
 pub use pointer::*;
+pub use settings::*;
 pub use state::*;
 pub use ui::*;
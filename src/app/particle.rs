@@ -1,23 +1,32 @@
 use itertools::Itertools;
-use wasm_bindgen::JsValue;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
 
-use crate::draw::draw_particle;
+use super::{Frame, RenderOptions};
+use crate::{
+    platform::{Canvas, PlatformError},
+    render::Renderer,
+};
 
 #[derive(Clone, Default)]
 pub struct ParticleSystem {
     particles: Vec<Particle>,
-    last_tick_at: usize,
+    last_tick_at: Frame,
     last_index: usize,
 }
 
 impl ParticleSystem {
     pub fn tick_and_draw(
         &mut self,
-        context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
-        frame: usize,
-    ) -> Result<(), JsValue> {
+        renderer: &dyn Renderer,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        frame: Frame,
+        render_options: &RenderOptions,
+    ) -> Result<(), PlatformError> {
+        if !render_options.particles() {
+            self.particles.clear();
+            return Ok(());
+        }
+
         if self.last_tick_at != frame {
             self.last_tick_at = frame;
 
@@ -25,14 +34,18 @@ impl ParticleSystem {
                 particle.tick();
             }
 
-            self.particles.retain(|particle| particle.is_alive());
-        }
+            // Under load only every other or every fourth particle is kept
+            let stride = (1.0 / render_options.particle_quality.max(0.25)).round() as usize;
 
-        for particle in &self.particles {
-            draw_particle(context, atlas, particle, frame)?;
+            self.particles
+                .retain(|particle| particle.is_alive() && particle.index % stride == 0);
         }
 
-        Ok(())
+        renderer.draw_particles(context, atlas, &self.particles, frame)
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
     }
 
     pub fn add(&mut self, particle: Particle) {
@@ -74,7 +87,7 @@ pub struct Particle {
     pub index: usize,
     pub position: (f64, f64),
     velocity: (f64, f64),
-    pub lifetime: usize,
+    pub lifetime: Frame,
     pub sort: ParticleSort,
 }
 
@@ -82,7 +95,7 @@ impl Particle {
     pub fn new(
         position: (f64, f64),
         velocity: (f64, f64),
-        lifetime: usize,
+        lifetime: Frame,
         sort: ParticleSort,
     ) -> Particle {
         Particle {
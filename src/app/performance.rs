@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+
+use crate::{
+    draw::draw_text,
+    platform::{system, Canvas, PlatformError},
+};
+
+pub fn now() -> f64 {
+    system().now()
+}
+
+#[derive(Default)]
+pub struct PerformanceHud {
+    pub visible: bool,
+    frame_times: VecDeque<f64>,
+    last_frame_at: f64,
+    tick_time: f64,
+    draw_time: f64,
+}
+
+impl PerformanceHud {
+    const WINDOW: usize = 60;
+    const PAUSE: f64 = 1000.0;
+
+    pub fn toggle(&mut self) {
+        self.visible ^= true;
+    }
+
+    pub fn record_frame(&mut self, now: f64) {
+        // Gaps from a hidden tab are pauses rather than slow frames
+        if self.last_frame_at > 0.0 && now - self.last_frame_at < Self::PAUSE {
+            self.frame_times.push_back(now - self.last_frame_at);
+
+            if self.frame_times.len() > Self::WINDOW {
+                self.frame_times.pop_front();
+            }
+        }
+
+        self.last_frame_at = now;
+    }
+
+    /// Determines if the rolling window holds enough frames to judge the frame rate by.
+    pub fn window_full(&self) -> bool {
+        self.frame_times.len() >= Self::WINDOW
+    }
+
+    /// Forgets the frame times measured so far.
+    pub fn clear_window(&mut self) {
+        self.frame_times.clear();
+    }
+
+    pub fn record_tick(&mut self, duration: f64) {
+        self.tick_time = duration;
+    }
+
+    pub fn record_draw(&mut self, duration: f64) {
+        self.draw_time = duration;
+    }
+
+    /// Average frame time in milliseconds over the rolling window.
+    pub fn frame_time(&self) -> f64 {
+        if self.frame_times.is_empty() {
+            0.0
+        } else {
+            self.frame_times.iter().sum::<f64>() / self.frame_times.len() as f64
+        }
+    }
+
+    pub fn fps(&self) -> f64 {
+        let frame_time = self.frame_time();
+
+        if frame_time > 0.0 {
+            1000.0 / frame_time
+        } else {
+            0.0
+        }
+    }
+
+    pub fn draw(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        state_lines: &[String],
+    ) -> Result<(), PlatformError> {
+        let mut lines = vec![
+            format!("fps {:.0}", self.fps()),
+            format!("tick {:.2}ms", self.tick_time),
+            format!("draw {:.2}ms", self.draw_time),
+        ];
+
+        lines.extend_from_slice(state_lines);
+
+        context.save();
+
+        context.set_fill_style("#000000bf");
+        context.fill_rect(0.0, 0.0, 128.0, 4.0 + lines.len() as f64 * 10.0);
+
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(context, atlas, 4.0, 2.0 + i as f64 * 10.0, line)?;
+        }
+
+        context.restore();
+
+        Ok(())
+    }
+}
+
+/// Canvas resolutions, as a share of the full one, stepped through while frames run slow.
+const RESOLUTION_LEVELS: [f64; 3] = [1.0, 0.75, 0.5];
+
+/// Particles kept at each step of [`RESOLUTION_LEVELS`].
+const PARTICLE_LEVELS: [f64; 3] = [1.0, 0.5, 0.25];
+
+/// Number of transitions kept for the performance HUD.
+const LOG_LENGTH: usize = 4;
+
+/// Lowers the canvas resolution and particle quality while the average frame time exceeds the
+/// frame budget, and restores them once frames have kept within it for a while.
+pub struct AutoPerformance {
+    full_scale: f64,
+    level: usize,
+    last_change_at: f64,
+    log: VecDeque<String>,
+}
+
+impl AutoPerformance {
+    /// Share of the budget the average frame time may exceed it by before quality drops.
+    const OVER_BUDGET: f64 = 1.25;
+    /// Share of the budget the average frame time must keep under for quality to return.
+    const UNDER_BUDGET: f64 = 1.1;
+    /// Milliseconds to wait after a change before lowering quality further.
+    const LOWER_DELAY: f64 = 2000.0;
+    /// Milliseconds to wait after a change before restoring quality, longer than for lowering so
+    /// the levels do not flap.
+    const RAISE_DELAY: f64 = 10000.0;
+
+    pub fn new(full_scale: f64) -> AutoPerformance {
+        AutoPerformance {
+            full_scale,
+            level: 0,
+            last_change_at: 0.0,
+            log: VecDeque::new(),
+        }
+    }
+
+    /// Judges the average frame time against the budget, both in milliseconds, returning whether
+    /// the level changed.
+    pub fn assess(&mut self, frame_time: f64, budget: f64, now: f64) -> bool {
+        let since_change = now - self.last_change_at;
+
+        if frame_time > budget * Self::OVER_BUDGET
+            && since_change > Self::LOWER_DELAY
+            && self.level + 1 < RESOLUTION_LEVELS.len()
+        {
+            self.change(self.level + 1, now, &format!("slow {frame_time:.1}ms"))
+        } else if frame_time < budget * Self::UNDER_BUDGET
+            && since_change > Self::RAISE_DELAY
+            && self.level > 0
+        {
+            self.change(self.level - 1, now, "recovered")
+        } else {
+            false
+        }
+    }
+
+    /// Restores full quality, returning whether it had been lowered.
+    pub fn reset(&mut self, now: f64) -> bool {
+        self.level > 0 && self.change(0, now, "disabled")
+    }
+
+    fn change(&mut self, level: usize, now: f64, reason: &str) -> bool {
+        self.level = level;
+        self.last_change_at = now;
+
+        self.log.push_back(format!(
+            "res {:.0}% {reason}",
+            RESOLUTION_LEVELS[level] * 100.0
+        ));
+
+        if self.log.len() > LOG_LENGTH {
+            self.log.pop_front();
+        }
+
+        true
+    }
+
+    /// Determines if the quality is currently lowered.
+    pub fn reduced(&self) -> bool {
+        self.level > 0
+    }
+
+    pub fn canvas_scale(&self) -> f64 {
+        self.full_scale * RESOLUTION_LEVELS[self.level]
+    }
+
+    pub fn particle_quality(&self) -> f64 {
+        PARTICLE_LEVELS[self.level]
+    }
+
+    /// The latest transitions, oldest first.
+    pub fn log(&self) -> &VecDeque<String> {
+        &self.log
+    }
+}
@@ -7,6 +7,10 @@ pub struct Pointer {
     pub location: (i32, i32),
     pub button: bool,
     pub alt_button: bool,
+    /// Whether the modifier key (shift) was held during the last mouse event.
+    pub modifier: bool,
+    /// Distance in interface pixels a touch reaches beyond where it landed, zero for a mouse.
+    pub touch_reach: i32,
 }
 
 impl Pointer {
@@ -71,4 +75,32 @@ impl Pointer {
             && self.location.1 >= position.1
             && self.location.1 < position.1 + size.1
     }
+
+    /// Determines if the pointer is a finger which has left the screen, so nothing is hovered.
+    pub fn touch_lifted(&self) -> bool {
+        self.touch_reach > 0 && !self.button
+    }
+
+    /// Distance from the pointer to the nearest edge of a region, zero when inside it.
+    pub fn region_distance(&self, position: (i32, i32), size: (i32, i32)) -> i32 {
+        let dx = (position.0 - self.location.0).max(self.location.0 - (position.0 + size.0 - 1));
+        let dy = (position.1 - self.location.1).max(self.location.1 - (position.1 + size.1 - 1));
+
+        dx.max(dy).max(0)
+    }
+
+    /// Returns a copy of this pointer moved onto the closest point of a region.
+    pub fn snap_to_region(&self, position: (i32, i32), size: (i32, i32)) -> Pointer {
+        let mut returned = self.clone();
+
+        returned.location.0 = returned
+            .location
+            .0
+            .clamp(position.0, position.0 + size.0 - 1);
+        returned.location.1 = returned
+            .location
+            .1
+            .clamp(position.1, position.1 + size.1 - 1);
+        returned
+    }
 }
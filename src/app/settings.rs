@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use shared::BotDifficulty;
+
+use super::{App, FrameLimit, HapticIntensity};
+use crate::warn;
+
+/// Storage key of the settings, kept as a single JSON blob.
+pub const SETTINGS_KEY: &str = "settings";
+
+/// Version of the settings schema, bumped with a new migration whenever a setting is renamed,
+/// removed or stored differently. Settings added with a default need no new version.
+const SETTINGS_VERSION: u64 = 1;
+
+/// Upgrades stored settings of each version to the next, index 0 upgrading the separate storage
+/// keys used before the blob.
+const MIGRATIONS: [fn(Value) -> Value; SETTINGS_VERSION as usize] = [migrate_legacy_keys];
+
+/// Storage keys the settings were kept under before the blob, removed once migrated.
+const LEGACY_KEYS: [&str; 13] = [
+    "music_volume",
+    "clip_volume",
+    "high_contrast",
+    "reduced_effects",
+    "webgl",
+    "intent_arrows",
+    "diagnostics",
+    "auto_performance",
+    "aim_smoothing",
+    "aim_dead_zone",
+    "frame_limit",
+    "haptics",
+    "hints",
+];
+
+/// Every option of the settings menu, stored on this device.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    version: u64,
+    pub music_volume: i8,
+    pub clip_volume: i8,
+    pub high_contrast: bool,
+    pub reduced_effects: bool,
+    pub webgl: bool,
+    pub intent_arrows: bool,
+    pub diagnostics: bool,
+    pub auto_performance: bool,
+    pub aim_smoothing: bool,
+    pub aim_dead_zone: bool,
+    pub frame_limit: FrameLimit,
+    pub haptics: HapticIntensity,
+    pub hints: bool,
+    /// Difficulty of games versus AI, recommended by the last calibration or skipped to the
+    /// default, none until either.
+    pub bot_difficulty: Option<BotDifficulty>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: SETTINGS_VERSION,
+            music_volume: 10,
+            clip_volume: 8,
+            high_contrast: false,
+            reduced_effects: false,
+            webgl: false,
+            intent_arrows: true,
+            diagnostics: false,
+            auto_performance: true,
+            aim_smoothing: false,
+            aim_dead_zone: true,
+            frame_limit: FrameLimit::Uncapped,
+            haptics: HapticIntensity::Strong,
+            hints: true,
+            bot_difficulty: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Loads the stored settings, migrating them from older versions, or the defaults if there are
+    /// none or they cannot be read.
+    pub fn load() -> Settings {
+        let stored = App::kv_get(SETTINGS_KEY);
+
+        let mut value = if stored.is_empty() {
+            legacy_keys()
+        } else {
+            match serde_json::from_str::<Value>(&stored) {
+                Ok(value) => value,
+                Err(err) => {
+                    warn!("unreadable settings, using defaults: {err}");
+                    return Settings::default();
+                }
+            }
+        };
+
+        let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+
+        for migration in MIGRATIONS.iter().skip(version as usize) {
+            value = migration(value);
+        }
+
+        let settings = match serde_json::from_value::<Settings>(value) {
+            Ok(settings) => settings,
+            Err(err) => {
+                warn!("invalid settings, using defaults: {err}");
+                Settings::default()
+            }
+        };
+
+        if version < SETTINGS_VERSION {
+            settings.save();
+
+            if version == 0 {
+                for key in LEGACY_KEYS {
+                    App::kv_remove(key);
+                }
+            }
+        }
+
+        settings
+    }
+
+    /// Stores the settings under the current version.
+    pub fn save(&self) {
+        let settings = Settings {
+            version: SETTINGS_VERSION,
+            ..self.clone()
+        };
+
+        App::kv_set(
+            SETTINGS_KEY,
+            &serde_json::to_string(&settings).unwrap_or_default(),
+        );
+    }
+
+    /// Loads the settings, changes them and stores them again.
+    pub fn update(change: impl FnOnce(&mut Settings)) {
+        let mut settings = Settings::load();
+
+        change(&mut settings);
+
+        settings.save();
+    }
+}
+
+/// Collects the settings stored under their separate keys, as version 0 of the schema.
+fn legacy_keys() -> Value {
+    Value::Object(
+        LEGACY_KEYS
+            .into_iter()
+            .map(|key| (key.to_string(), Value::String(App::kv_get(key))))
+            .collect(),
+    )
+}
+
+/// Parses the separate keys, whose values were all strings, with the defaults they were read with.
+fn migrate_legacy_keys(keys: Value) -> Value {
+    let key = |key: &str| {
+        keys.get(key)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    json!({
+        "version": 1,
+        "music_volume": key("music_volume").parse::<i8>().unwrap_or(10),
+        "clip_volume": key("clip_volume").parse::<i8>().unwrap_or(8),
+        "high_contrast": key("high_contrast") == "true",
+        "reduced_effects": key("reduced_effects") == "true",
+        "webgl": key("webgl") == "true",
+        "intent_arrows": key("intent_arrows") != "false",
+        "diagnostics": key("diagnostics") == "true",
+        "auto_performance": key("auto_performance") != "false",
+        "aim_smoothing": key("aim_smoothing") == "true",
+        "aim_dead_zone": key("aim_dead_zone") != "false",
+        "frame_limit": match key("frame_limit").as_str() {
+            "60" => "Sixty",
+            "30" => "Thirty",
+            _ => "Uncapped",
+        },
+        "haptics": match key("haptics").as_str() {
+            "off" => "Off",
+            "light" => "Light",
+            _ => "Strong",
+        },
+        "hints": key("hints") != "false",
+    })
+}
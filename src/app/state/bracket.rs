@@ -0,0 +1,445 @@
+use shared::{
+    Bracket, BracketPhase, LobbyErrorKind, LobbySettings, LobbySort, Message, Nickname,
+    BRACKET_CODE_LENGTH,
+};
+
+use super::{GameState, State, TournamentState};
+use crate::{
+    api::{ApiClient, Dispatcher},
+    app::{
+        Alignment, App, AppContext, ButtonElement, ConfirmButtonElement, ContentElement, Frame,
+        Interface, LabelTheme, LabelTrim, StateSort, UIElement, UIEvent,
+    },
+    draw::{draw_label, draw_text, draw_text_centered},
+    platform::{dialogs, Canvas, PlatformError},
+};
+
+const BUTTON_BACK: usize = 0;
+const BUTTON_HOST: usize = 1;
+const BUTTON_JOIN: usize = 2;
+const BUTTON_START: usize = 3;
+const BUTTON_PLAY: usize = 4;
+const BUTTON_LEAVE: usize = 5;
+
+/// Storage key of the code of the bracket this device takes part in.
+const BRACKET_CODE_KEY: &str = "bracket_code";
+
+/// Frames between refreshes of the bracket, picking up registrations and results.
+const REFRESH_FRAMES: Frame = 60;
+
+/// Height of each match in the first round of the bracket.
+const ROW_HEIGHT: f64 = 14.0;
+
+/// Wraps a bracket answered by the server for the [`Dispatcher`].
+fn bracket_message(bracket: Bracket) -> Message {
+    Message::Bracket(Box::new(bracket))
+}
+
+/// Registration, pairings and results of a single-elimination bracket hosted by the server.
+pub struct BracketState {
+    interface: Interface,
+    dispatcher: Dispatcher,
+    /// Code of the bracket taken part in, if any.
+    code: Option<String>,
+    bracket: Option<Bracket>,
+    next_refresh: Frame,
+    status: Option<String>,
+}
+
+impl BracketState {
+    /// Builds the buttons for hosting or joining a bracket, or for playing through the one taken
+    /// part in.
+    fn create_interface(&self, session_id: Option<&String>) -> Interface {
+        let button_back = ButtonElement::new(
+            (8, 360 - 28),
+            (88, 16),
+            BUTTON_BACK,
+            LabelTrim::Return,
+            LabelTheme::Default,
+            ContentElement::Text("Back".to_string(), Alignment::Center),
+        );
+
+        let mut elements = vec![button_back.boxed()];
+
+        let action = |value: usize, text: &str| {
+            ButtonElement::new(
+                (384 - 120, 360 - 32),
+                (112, 24),
+                value,
+                LabelTrim::Glorious,
+                LabelTheme::Action,
+                ContentElement::Text(text.to_string(), Alignment::Center),
+            )
+            .boxed()
+        };
+
+        let button_leave = ConfirmButtonElement::new(
+            ((384 - 88) / 2, 360 - 28),
+            (88, 16),
+            BUTTON_LEAVE,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            ContentElement::Text("Leave".to_string(), Alignment::Center),
+        );
+
+        match (&self.code, &self.bracket) {
+            (None, _) => {
+                elements.push(action(BUTTON_HOST, "Host"));
+                elements.push(
+                    ButtonElement::new(
+                        ((384 - 88) / 2, 360 - 28),
+                        (88, 16),
+                        BUTTON_JOIN,
+                        LabelTrim::Round,
+                        LabelTheme::Default,
+                        ContentElement::Text("Join".to_string(), Alignment::Center),
+                    )
+                    .boxed(),
+                );
+            }
+            (Some(_), Some(bracket)) => {
+                let is_host = session_id == Some(&bracket.host);
+
+                match bracket.phase {
+                    BracketPhase::Registering if is_host && bracket.players.len() >= 2 => {
+                        elements.push(action(BUTTON_START, "Start"));
+                    }
+                    BracketPhase::Running => {
+                        let lobby_id = session_id
+                            .and_then(|session_id| bracket.current_match(session_id))
+                            .and_then(|bracket_match| bracket_match.lobby_id);
+
+                        if lobby_id.is_some() {
+                            elements.push(action(BUTTON_PLAY, "Play match"));
+                        }
+                    }
+                    _ => (),
+                }
+
+                elements.push(button_leave.boxed());
+            }
+            (Some(_), None) => elements.push(button_leave.boxed()),
+        }
+
+        Interface::new(elements)
+    }
+
+    fn refresh_interface(&mut self, app_context: &AppContext) {
+        self.interface = self.create_interface(app_context.session_id.as_ref());
+    }
+
+    /// Remembers the bracket taken part in, so it can be returned to after each match.
+    fn set_code(&mut self, code: Option<String>) {
+        App::kv_set(BRACKET_CODE_KEY, code.as_deref().unwrap_or_default());
+
+        if code.is_none() {
+            self.bracket = None;
+        }
+
+        self.code = code;
+        self.next_refresh = 0;
+    }
+
+    /// Asks for the code of a bracket to join.
+    fn join(&mut self, session_id: &str) {
+        let Some(code) = dialogs().prompt("Code of the bracket:", "") else {
+            return;
+        };
+
+        let code = code.trim().to_uppercase();
+
+        if code.len() != BRACKET_CODE_LENGTH || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            self.status = Some("Invalid code".to_string());
+            return;
+        }
+
+        self.dispatcher.dispatch(
+            ApiClient::new(session_id.to_string()).join_bracket(code),
+            bracket_message,
+        );
+    }
+
+    /// Names a player by seed, calling out this device's player.
+    fn player_label(
+        bracket: &Bracket,
+        player: Option<&String>,
+        session_id: Option<&String>,
+    ) -> String {
+        match player {
+            Some(player) if Some(player) == session_id => "You".to_string(),
+            Some(player) => bracket
+                .seed(player)
+                .map(|seed| format!("#{seed}"))
+                .unwrap_or_default(),
+            None => "-".to_string(),
+        }
+    }
+
+    /// Names a player by [`Nickname`], calling out this device's player.
+    fn player_name(player: &str, session_id: Option<&String>) -> String {
+        if session_id.is_some_and(|session_id| session_id == player) {
+            "You".to_string()
+        } else {
+            Nickname::from_session(player).to_string()
+        }
+    }
+
+    fn draw_rounds(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        bracket: &Bracket,
+        session_id: Option<&String>,
+        dy: f64,
+    ) -> Result<(), PlatformError> {
+        let column_width = 368.0 / bracket.rounds.len().max(1) as f64;
+
+        for (round, matches) in bracket.rounds.iter().enumerate() {
+            // Each match sits between the two it is fed by
+            let spacing = ROW_HEIGHT * (1 << round) as f64;
+            let x = 8.0 + round as f64 * column_width;
+
+            for (index, bracket_match) in matches.iter().enumerate() {
+                let y = dy + index as f64 * spacing + (spacing - ROW_HEIGHT) / 2.0;
+
+                let [red, blue] = bracket_match.players.each_ref().map(|player| {
+                    let label = BracketState::player_label(bracket, player.as_ref(), session_id);
+
+                    match (&bracket_match.winner, player) {
+                        (Some(winner), Some(player)) if winner == player => format!("{label}*"),
+                        _ => label,
+                    }
+                });
+
+                draw_text(context, atlas, x, y, &format!("{red} v {blue}"))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl State for BracketState {
+    fn draw(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+        let session_id = app_context.session_id.as_ref();
+
+        draw_label(
+            context,
+            atlas,
+            ((384 - 136) / 2, 16),
+            (136, 24),
+            "#7f3faa",
+            &ContentElement::Text("Bracket".to_string(), Alignment::Center),
+            pointer,
+            frame,
+            &LabelTrim::Glorious,
+            false,
+        )?;
+
+        match (&self.code, &self.bracket) {
+            (None, _) => {
+                draw_text_centered(
+                    context,
+                    atlas,
+                    384.0 / 2.0,
+                    52.0,
+                    "Host a bracket, or join one with its code",
+                )?;
+            }
+            (Some(code), None) => {
+                draw_text_centered(context, atlas, 384.0 / 2.0, 52.0, &format!("Code {code}"))?;
+            }
+            (Some(code), Some(bracket)) => {
+                draw_text_centered(context, atlas, 384.0 / 2.0, 52.0, &format!("Code {code}"))?;
+
+                match bracket.phase {
+                    BracketPhase::Registering => {
+                        draw_text_centered(
+                            context,
+                            atlas,
+                            384.0 / 2.0,
+                            68.0,
+                            &format!("{} players registered", bracket.players.len()),
+                        )?;
+
+                        // Seeds are listed with nicknames, as the rounds only show seeds
+                        for (i, player) in bracket.players.iter().enumerate() {
+                            draw_text(
+                                context,
+                                atlas,
+                                (384.0 - 160.0) / 2.0,
+                                88.0 + i as f64 * 12.0,
+                                &format!(
+                                    "#{} {}",
+                                    i + 1,
+                                    BracketState::player_name(player, session_id)
+                                ),
+                            )?;
+                        }
+                    }
+                    BracketPhase::Running | BracketPhase::Finished => {
+                        self.draw_rounds(context, atlas, bracket, session_id, 72.0)?;
+
+                        let footer_y = 360.0 - 72.0;
+
+                        if let Some(champion) = bracket.champion() {
+                            draw_label(
+                                context,
+                                atlas,
+                                ((384 - 240) / 2, footer_y as i32),
+                                (240, 24),
+                                "#2a9f55",
+                                &ContentElement::Text(
+                                    format!(
+                                        "{} is champion!",
+                                        BracketState::player_name(champion, session_id)
+                                    ),
+                                    Alignment::Center,
+                                ),
+                                pointer,
+                                frame,
+                                &LabelTrim::Glorious,
+                                false,
+                            )?;
+                        } else {
+                            let status = match session_id
+                                .and_then(|session_id| bracket.current_match(session_id))
+                            {
+                                Some(bracket_match) if bracket_match.lobby_id.is_some() => {
+                                    "Your match is ready"
+                                }
+                                Some(_) => "Waiting for your opponent",
+                                None => "Out of the running",
+                            };
+
+                            draw_text_centered(context, atlas, 384.0 / 2.0, footer_y, status)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(status) = &self.status {
+            draw_text_centered(context, atlas, 384.0 / 2.0, 360.0 - 48.0, status)?;
+        }
+
+        self.interface
+            .draw(interface_context, atlas, pointer, frame)?;
+
+        Ok(())
+    }
+
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
+        if let Some(UIEvent::ButtonClick(value, clip_id)) =
+            self.interface.tick(&app_context.pointer)
+        {
+            app_context.audio_system.play_clip_option(clip_id);
+            self.status = None;
+
+            match (value, &app_context.session_id) {
+                (BUTTON_BACK, _) => return Some(StateSort::Tournament(TournamentState::default())),
+                (BUTTON_HOST, Some(session_id)) => {
+                    self.dispatcher.dispatch(
+                        ApiClient::new(session_id.clone()).create_bracket(),
+                        bracket_message,
+                    );
+                }
+                (BUTTON_JOIN, Some(session_id)) => self.join(session_id),
+                (BUTTON_START, Some(session_id)) => {
+                    if let Some(code) = self.code.clone() {
+                        self.dispatcher.dispatch(
+                            ApiClient::new(session_id.clone()).start_bracket(code),
+                            bracket_message,
+                        );
+                    }
+                }
+                (BUTTON_PLAY, Some(session_id)) => {
+                    if let Some(lobby_id) = self
+                        .bracket
+                        .as_ref()
+                        .and_then(|bracket| bracket.current_match(session_id))
+                        .and_then(|bracket_match| bracket_match.lobby_id)
+                    {
+                        return Some(StateSort::Game(GameState::new(
+                            LobbySettings::new(LobbySort::Online(lobby_id)),
+                            session_id.clone(),
+                        )));
+                    }
+                }
+                (BUTTON_LEAVE, _) => {
+                    self.set_code(None);
+                    self.refresh_interface(app_context);
+                }
+                (_, None) => self.status = Some("Not connected to the server".to_string()),
+                _ => (),
+            }
+        }
+
+        self.poll(app_context);
+
+        for message in self.dispatcher.take() {
+            match message {
+                Message::Bracket(bracket) => {
+                    if self.code.as_ref() != Some(&bracket.code) {
+                        self.set_code(Some(bracket.code.clone()));
+                    }
+
+                    self.bracket = Some(*bracket);
+                    self.refresh_interface(app_context);
+                }
+                Message::LobbyError(err) => {
+                    // The server dropped the bracket, or restarted since
+                    if matches!(err.kind, LobbyErrorKind::NotFound) {
+                        self.set_code(None);
+                        self.refresh_interface(app_context);
+                    }
+
+                    self.status = Some(err.reason);
+                }
+                _ => (),
+            }
+        }
+
+        None
+    }
+
+    fn poll(&mut self, app_context: &AppContext) {
+        let frame = app_context.clock.frame();
+
+        if let Some(code) = &self.code {
+            if frame >= self.next_refresh {
+                self.next_refresh = frame + REFRESH_FRAMES;
+
+                self.dispatcher
+                    .dispatch(ApiClient::default().bracket(code.clone()), bracket_message);
+            }
+        }
+    }
+}
+
+impl Default for BracketState {
+    fn default() -> Self {
+        let code = Some(App::kv_get(BRACKET_CODE_KEY)).filter(|code| !code.is_empty());
+
+        let mut state = BracketState {
+            interface: Interface::new(Vec::new()),
+            dispatcher: Dispatcher::default(),
+            code,
+            bracket: None,
+            next_refresh: 0,
+            status: None,
+        };
+
+        state.interface = state.create_interface(None);
+
+        state
+    }
+}
@@ -0,0 +1,276 @@
+use shared::{BotDifficulty, LobbySettings, LobbySort};
+
+use super::{custom_settings, GameState, MainMenuState, State};
+use crate::{
+    app::{
+        Alignment, AppContext, ButtonElement, ConfirmButtonElement, ContentElement, Interface,
+        LabelTheme, LabelTrim, Settings, StateSort, UIElement, UIEvent,
+    },
+    calibration::{versus_ai_settings, Calibration, CALIBRATION_TURNS},
+    draw::{draw_label, draw_text, draw_text_centered},
+    platform::{Canvas, PlatformError},
+};
+
+const BUTTON_BACK: usize = 0;
+const BUTTON_START: usize = 1;
+const BUTTON_PLAY: usize = 2;
+const BUTTON_ABANDON: usize = 3;
+const BUTTON_SKIP: usize = 4;
+const BUTTON_VERSUS_AI: usize = 5;
+
+/// Height of each row of the results.
+const ROW_HEIGHT: f64 = 16.0;
+
+/// Quick matches against the AI at every difficulty, recommending the one games versus AI are
+/// played at.
+pub struct CalibrationState {
+    interface: Interface,
+    calibration: Option<Calibration>,
+    /// Difficulty stored in the settings, none until calibrated or skipped.
+    bot_difficulty: Option<BotDifficulty>,
+}
+
+impl CalibrationState {
+    fn create_interface(&self) -> Interface {
+        let action_button = |value: usize, text: &str| {
+            ButtonElement::new(
+                (384 - 120, 360 - 32),
+                (112, 24),
+                value,
+                LabelTrim::Glorious,
+                LabelTheme::Action,
+                ContentElement::Text(text.to_string(), Alignment::Center),
+            )
+            .boxed()
+        };
+
+        let mut elements = vec![ButtonElement::new(
+            (8, 360 - 28),
+            (88, 16),
+            BUTTON_BACK,
+            LabelTrim::Return,
+            LabelTheme::Default,
+            ContentElement::Text("Back".to_string(), Alignment::Center),
+        )
+        .boxed()];
+
+        match &self.calibration {
+            Some(calibration) if calibration.next_difficulty().is_some() => {
+                elements.push(action_button(BUTTON_PLAY, "Play match"));
+                elements.push(
+                    ConfirmButtonElement::new(
+                        ((384 - 88) / 2, 360 - 28),
+                        (88, 16),
+                        BUTTON_ABANDON,
+                        LabelTrim::Round,
+                        LabelTheme::Default,
+                        ContentElement::Text("Abandon".to_string(), Alignment::Center),
+                    )
+                    .boxed(),
+                );
+            }
+            Some(_) => {
+                elements.push(action_button(BUTTON_VERSUS_AI, "Play AI"));
+                elements.push(
+                    ButtonElement::new(
+                        ((384 - 88) / 2, 360 - 28),
+                        (88, 16),
+                        BUTTON_START,
+                        LabelTrim::Round,
+                        LabelTheme::Default,
+                        ContentElement::Text("Recalibrate".to_string(), Alignment::Center),
+                    )
+                    .boxed(),
+                );
+            }
+            None => {
+                elements.push(action_button(BUTTON_START, "Start"));
+
+                // Players who never calibrated may go straight to a game at the default
+                if self.bot_difficulty.is_none() {
+                    elements.push(
+                        ButtonElement::new(
+                            ((384 - 88) / 2, 360 - 28),
+                            (88, 16),
+                            BUTTON_SKIP,
+                            LabelTrim::Round,
+                            LabelTheme::Default,
+                            ContentElement::Text("Skip".to_string(), Alignment::Center),
+                        )
+                        .boxed(),
+                    );
+                }
+            }
+        }
+
+        Interface::new(elements)
+    }
+
+    /// Starts the next match of the calibration, if any is left.
+    fn play_match(&mut self, session_id: String) -> Option<StateSort> {
+        let calibration = self.calibration.as_mut()?;
+        let difficulty = calibration.next_difficulty()?;
+
+        calibration.start_match();
+
+        // Played by the default rules, so the device's custom rules do not skew the results
+        let mut lobby_settings = LobbySettings::new(LobbySort::LocalAI);
+        lobby_settings.set_max_turns(Some(CALIBRATION_TURNS));
+        lobby_settings.set_bot_difficulty(difficulty);
+
+        Some(StateSort::Game(GameState::new(lobby_settings, session_id)))
+    }
+}
+
+impl State for CalibrationState {
+    fn draw(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+
+        draw_label(
+            context,
+            atlas,
+            ((384 - 136) / 2, 16),
+            (136, 24),
+            "#7f3faa",
+            &ContentElement::Text("AI calibration".to_string(), Alignment::Center),
+            pointer,
+            frame,
+            &LabelTrim::Glorious,
+            false,
+        )?;
+
+        draw_text_centered(
+            context,
+            atlas,
+            384.0 / 2.0,
+            52.0,
+            "Play a quick match as Red against each AI",
+        )?;
+        draw_text_centered(
+            context,
+            atlas,
+            384.0 / 2.0,
+            64.0,
+            "to find the difficulty that suits you",
+        )?;
+
+        let current = match self.bot_difficulty {
+            Some(difficulty) => format!("AI difficulty: {}", difficulty.name()),
+            None => format!(
+                "AI difficulty: {} (not calibrated)",
+                BotDifficulty::default().name()
+            ),
+        };
+
+        draw_text_centered(context, atlas, 384.0 / 2.0, 88.0, &current)?;
+
+        if let Some(calibration) = &self.calibration {
+            let results: Vec<_> = calibration.results().collect();
+
+            for (i, difficulty) in BotDifficulty::ALL.iter().enumerate() {
+                let outcome = match results.get(i) {
+                    Some((_, true)) => "Won",
+                    Some((_, false)) => "Lost",
+                    None if i == results.len() => "Next",
+                    None => "-",
+                };
+
+                let y = 120.0 + i as f64 * ROW_HEIGHT;
+
+                draw_text(context, atlas, 120.0, y, difficulty.name())?;
+                draw_text(context, atlas, 216.0, y, outcome)?;
+            }
+
+            if let Some(recommendation) = calibration.recommendation() {
+                draw_label(
+                    context,
+                    atlas,
+                    ((384 - 192) / 2, 360 - 112),
+                    (192, 24),
+                    "#2a9f55",
+                    &ContentElement::Text(
+                        format!("Recommended: {}", recommendation.name()),
+                        Alignment::Center,
+                    ),
+                    pointer,
+                    frame,
+                    &LabelTrim::Glorious,
+                    false,
+                )?;
+            } else {
+                draw_text_centered(
+                    context,
+                    atlas,
+                    384.0 / 2.0,
+                    360.0 - 100.0,
+                    &format!("Each match lasts {CALIBRATION_TURNS} turns"),
+                )?;
+            }
+        }
+
+        self.interface
+            .draw(interface_context, atlas, pointer, frame)?;
+
+        Ok(())
+    }
+
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
+        if let Some(UIEvent::ButtonClick(value, clip_id)) =
+            self.interface.tick(&app_context.pointer)
+        {
+            app_context.audio_system.play_clip_option(clip_id);
+
+            let session_id = app_context.session_id.clone().unwrap_or_default();
+
+            match value {
+                BUTTON_BACK => return Some(StateSort::MainMenu(MainMenuState::default())),
+                BUTTON_START => {
+                    self.calibration = Some(Calibration::default());
+
+                    return self.play_match(session_id);
+                }
+                BUTTON_PLAY => return self.play_match(session_id),
+                BUTTON_ABANDON => {
+                    Calibration::clear();
+                    self.calibration = None;
+                }
+                BUTTON_SKIP | BUTTON_VERSUS_AI => {
+                    Settings::update(|settings| {
+                        settings.bot_difficulty.get_or_insert_with(Default::default);
+                    });
+
+                    return Some(StateSort::Game(GameState::new(
+                        versus_ai_settings(custom_settings(LobbySettings::new(LobbySort::LocalAI))),
+                        session_id,
+                    )));
+                }
+                _ => (),
+            }
+
+            self.interface = self.create_interface();
+        }
+
+        None
+    }
+}
+
+impl Default for CalibrationState {
+    fn default() -> Self {
+        let mut state = CalibrationState {
+            interface: Interface::new(Vec::new()),
+            calibration: Calibration::load(),
+            bot_difficulty: Settings::load().bot_difficulty,
+        };
+
+        state.interface = state.create_interface();
+
+        state
+    }
+}
@@ -0,0 +1,264 @@
+use shared::{ChatContent, Emote, Lobby, Message, Player, Team};
+
+use crate::{
+    app::{
+        Alignment, AppContext, ButtonElement, ContentElement, Interface, LabelTheme, LabelTrim,
+        UIElement, UIEvent,
+    },
+    draw::{draw_text, draw_text_centered, text_length},
+    platform::{dialogs, Canvas, PlatformError},
+};
+
+const BUTTON_SAY: usize = 81;
+const BUTTON_MUTE: usize = 84;
+const BUTTON_EMOTE: usize = 90;
+
+/// Height of a chat line.
+const LINE_HEIGHT: i32 = 14;
+
+/// The lobby chat of an online game: its latest lines, and emote, say and mute buttons for the
+/// lobby's players.
+pub struct ChatBox {
+    interface: Interface,
+    /// Top-left corner of the chat lines, the buttons lining up below them.
+    position: (i32, i32),
+    /// Whether the interface was built for a player rather than a spectator, and whether they
+    /// muted their opponents.
+    built_for: Option<bool>,
+    /// Muted players sent to the server which the lobby state has yet to reflect.
+    sent_muted: Option<Vec<usize>>,
+}
+
+impl ChatBox {
+    /// Creates a chat box whose lines start at the given position.
+    pub fn new(position: (i32, i32)) -> ChatBox {
+        ChatBox {
+            interface: ChatBox::create_interface(position, None),
+            position,
+            built_for: None,
+            sent_muted: None,
+        }
+    }
+
+    /// Builds the emote, say and mute buttons, none of them for spectators.
+    fn create_interface((x, y): (i32, i32), built_for: Option<bool>) -> Interface {
+        let Some(muted) = built_for else {
+            return Interface::new(Vec::new());
+        };
+
+        let y = y + Lobby::CHAT_LINES as i32 * LINE_HEIGHT + 4;
+
+        let mut children: Vec<Box<dyn UIElement>> = Emote::ALL
+            .into_iter()
+            .enumerate()
+            .map(|(i, emote)| {
+                ButtonElement::new(
+                    (x - 2 + i as i32 * 70, y),
+                    (66, 16),
+                    BUTTON_EMOTE + i,
+                    LabelTrim::Round,
+                    LabelTheme::Default,
+                    ContentElement::Text(emote.text().to_string(), Alignment::Center),
+                )
+                .boxed()
+            })
+            .collect();
+
+        children.push(
+            ButtonElement::new(
+                (x + 64, y + 24),
+                (56, 16),
+                BUTTON_SAY,
+                LabelTrim::Round,
+                LabelTheme::Default,
+                ContentElement::Text("Say...".to_string(), Alignment::Center),
+            )
+            .boxed(),
+        );
+
+        children.push(
+            ButtonElement::new(
+                (x + 124, y + 24),
+                (48, 16),
+                BUTTON_MUTE,
+                LabelTrim::Round,
+                LabelTheme::Default,
+                ContentElement::Text(
+                    if muted { "Unmute" } else { "Mute" }.to_string(),
+                    Alignment::Center,
+                ),
+            )
+            .boxed(),
+        );
+
+        Interface::new(children)
+    }
+
+    /// Players muted by the player as shown, preferring what was sent over the last lobby state.
+    fn muted<'a>(&'a self, player: &'a Player) -> &'a [usize] {
+        self.sent_muted.as_deref().unwrap_or(&player.muted)
+    }
+
+    /// [`Player::seat`]s of the players on the other team than the given player's.
+    fn opponents(lobby: &Lobby, player: &Player) -> Vec<usize> {
+        lobby
+            .players()
+            .values()
+            .filter(|other| other.team != player.team)
+            .map(|other| other.seat)
+            .collect()
+    }
+
+    /// Determines whether the player muted every opponent, none having joined counting as not.
+    fn muted_opponents(&self, lobby: &Lobby, player: &Player) -> bool {
+        let opponents = ChatBox::opponents(lobby, player);
+        let muted = self.muted(player);
+
+        !opponents.is_empty() && opponents.iter().all(|opponent| muted.contains(opponent))
+    }
+
+    /// Handles the buttons of the chat, returning the message to send to the lobby.
+    pub fn tick(&mut self, lobby: &Lobby, app_context: &AppContext) -> Option<Message> {
+        let player = app_context
+            .session_id
+            .as_ref()
+            .and_then(|session_id| lobby.players().get(session_id));
+
+        if self.sent_muted.as_ref() == player.map(|player| &player.muted) {
+            self.sent_muted = None;
+        }
+
+        let built_for = player.map(|player| self.muted_opponents(lobby, player));
+
+        if self.built_for != built_for {
+            self.built_for = built_for;
+            self.interface = ChatBox::create_interface(self.position, built_for);
+        }
+
+        let Some(UIEvent::ButtonClick(value, clip_id)) = self.interface.tick(&app_context.pointer)
+        else {
+            return None;
+        };
+
+        app_context.audio_system.play_clip_option(clip_id);
+
+        match value {
+            BUTTON_SAY => {
+                let text = dialogs().prompt("Say something:", "")?;
+
+                ChatContent::Text(text).sanitized().map(Message::Chat)
+            }
+            BUTTON_MUTE => {
+                let player = player?;
+                let opponents = ChatBox::opponents(lobby, player);
+
+                // Toggles all opponents at once, keeping whoever else was muted
+                let mut muted: Vec<usize> = self
+                    .muted(player)
+                    .iter()
+                    .filter(|seat| !opponents.contains(seat))
+                    .copied()
+                    .collect();
+
+                if !self.muted_opponents(lobby, player) {
+                    muted.extend(opponents);
+                }
+
+                // Sorted like the server keeps it, so the lobby state is seen to reflect it
+                muted.sort();
+                muted.dedup();
+                self.sent_muted = Some(muted.clone());
+
+                Some(Message::Mute(muted))
+            }
+            value => Emote::ALL
+                .get(value.checked_sub(BUTTON_EMOTE)?)
+                .map(|emote| Message::Chat(ChatContent::Emote(*emote))),
+        }
+    }
+
+    /// Names the author of a chat line by [`shared::Nickname`], or by team once they left.
+    fn author(lobby: &Lobby, seat: usize, team: Team) -> String {
+        lobby
+            .players()
+            .values()
+            .find(|player| player.seat == seat)
+            .map(|player| player.nickname.to_string())
+            .unwrap_or_else(|| format!("{team:?}"))
+    }
+
+    /// Draws the chat lines, each behind a swatch of its author's team, and the buttons.
+    pub fn draw(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        lobby: &Lobby,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        // Lines start further left than the buttons, leaving room for the author's nickname
+        let (x, y) = (self.position.0 as f64 - 40.0, self.position.1 as f64);
+
+        let player = app_context
+            .session_id
+            .as_ref()
+            .and_then(|session_id| lobby.players().get(session_id));
+
+        // Muted players' lines are hidden right away, before the server stops relaying them
+        let muted = player.map(|player| self.muted(player)).unwrap_or_default();
+        let chat: Vec<_> = lobby
+            .chat
+            .iter()
+            .filter(|line| !muted.contains(&line.seat))
+            .collect();
+
+        for (i, line) in chat.iter().enumerate() {
+            let line_y = y + i as f64 * LINE_HEIGHT as f64;
+            let author = format!("{}:", ChatBox::author(lobby, line.seat, line.team));
+
+            draw_team_swatch(context, line.team, x, line_y)?;
+            draw_text(context, atlas, x + 12.0, line_y, &author)?;
+            draw_text(
+                context,
+                atlas,
+                x + 12.0 + text_length(&author) as f64 + 6.0,
+                line_y,
+                line.content.text(),
+            )?;
+        }
+
+        if chat.is_empty() {
+            draw_text_centered(
+                context,
+                atlas,
+                384.0 / 2.0,
+                y + (Lobby::CHAT_LINES as i32 * LINE_HEIGHT) as f64 / 2.0 - 4.0,
+                "Say hello!",
+            )?;
+        }
+
+        self.interface.draw(
+            context,
+            atlas,
+            &app_context.pointer,
+            app_context.clock.frame(),
+        )
+    }
+}
+
+/// Draws a square in the colour of the team in front of a line of text.
+pub fn draw_team_swatch(
+    context: &dyn Canvas,
+    team: Team,
+    x: f64,
+    y: f64,
+) -> Result<(), PlatformError> {
+    let color = match team {
+        Team::Red => "#C20005",
+        Team::Blue => "#00C2BD",
+    };
+
+    context.set_fill_style(color);
+    context.fill_rect(x, y + 1.0, 8.0, 8.0);
+
+    Ok(())
+}
@@ -1,66 +1,466 @@
-use std::{cell::RefCell, collections::HashMap, f32::consts::TAU, f64::consts::PI, rc::Rc};
+use std::{
+    collections::{HashMap, VecDeque},
+    f32::consts::TAU,
+};
 
-use js_sys::Math;
-use nalgebra::{vector, ComplexField};
-use rapier2d::prelude::point;
-use shared::{Lobby, LobbySettings, LobbySort, Message, Team, Turn};
-use wasm_bindgen::{prelude::Closure, JsValue};
-use web_sys::{console, CanvasRenderingContext2d, HtmlCanvasElement, HtmlInputElement};
+use nalgebra::{vector, Point2, Vector2};
+use rapier2d::prelude::{point, RigidBody};
+use shared::{
+    Achievement, AchievementTracker, BotController, BugData, BugSort, CaptureHeatmap, ForceField,
+    Game, GameEvent, GameMode, GamePhase, GameView, Lobby, LobbyErrorKind, LobbyID, LobbySettings,
+    LobbySort, Message, Mutator, Obstruction, Player, PropSort, Replay, Result as GameResult,
+    SurrenderVote, TargetPractice, Team, Turn, TurnCommitment, VecMap, ZoneBot, CORPSE_TURNS,
+    MAX_IMPULSE_INTENT,
+};
 
-use super::{MainMenuState, State};
+use super::{MainMenuState, ResultsState, State, WaitingRoom, WaitingRoomAction};
 use crate::{
+    achievements::unlock_achievement,
+    api::{ApiClient, Dispatcher},
     app::{
-        Alignment, AppContext, ButtonElement, ConfirmButtonElement, Interface, LabelTheme,
-        LabelTrim, Particle, ParticleSort, ParticleSystem, StateSort, ToggleButtonElement,
-        UIElement,
+        now, AimOptions, Alignment, AppContext, ButtonElement, ButtonGroupElement, ClipId,
+        ConfirmButtonElement, Frame, GameClock, Haptic, ImpactMaterial, Interface, LabelTheme,
+        LabelTrim, Particle, ParticleSort, ParticleSystem, Pointer, StateSort, ToggleButtonElement,
+        UIElement, UIEvent, FRAMES_PER_SECOND,
     },
+    bot::active_bot,
+    debug,
     draw::{
-        draw_bug, draw_bug_impulse, draw_image_centered, draw_label, draw_prop, draw_sand_circle,
-        draw_text, local_to_screen, screen_to_local,
+        draw_aim_tooltip, draw_ball, draw_bug, draw_bug_icon, draw_bug_impulse, draw_bug_tooltip,
+        draw_bug_trail, draw_bullseye, draw_capture_heatmap, draw_corpse, draw_focus_ring,
+        draw_force_field, draw_goal, draw_image_centered, draw_info_card, draw_intent_arrow,
+        draw_label, draw_obstruction_marker, draw_prop, draw_ring, draw_ring_out,
+        draw_selection_box, draw_selection_pulse, draw_selection_ring, draw_solid_arena,
+        draw_terrain, draw_text, draw_text_centered, text_length,
     },
-    net::{create_new_lobby, fetch, request_turns_since, send_message, send_ready, MessagePool},
-    tuple_as,
+    formation::Formation,
+    hints::{dismiss_hint, hints_enabled, seen_hints, Hint},
+    log::{self, Level},
+    net::Outbox,
+    platform::{dialogs, random, system, Canvas, PlatformError},
+    practice::{high_scores, record_high_score},
+    tuple_as, warn,
 };
 
 const BUTTON_REMATCH: usize = 1;
 const BUTTON_LEAVE: usize = 2;
 const BUTTON_MENU: usize = 10;
 const BUTTON_UNDO: usize = 20;
+const BUTTON_SPEED_HALF: usize = 30;
+const BUTTON_SPEED_NORMAL: usize = 31;
+const BUTTON_SPEED_DOUBLE: usize = 32;
+const BUTTON_SPEED_QUADRUPLE: usize = 33;
+const BUTTON_EXPORT: usize = 40;
+const BUTTON_REPLAY_TURN: usize = 41;
+const BUTTON_SURRENDER: usize = 42;
+const BUTTON_FORMATION: usize = 50;
+const BUTTON_HINT: usize = 60;
+const BUTTON_SUGGEST: usize = 70;
+const BUTTON_PRACTICE_RETRY: usize = 75;
+const BUTTON_PRACTICE_LEAVE: usize = 76;
+
+/// Most moves the suggest button shows at once, the bot's most valuable ones.
+const SUGGESTED_MOVES: usize = 2;
+
+/// Ticks before the turn deadline at which players are reminded to commit.
+const REMINDER_TICKS: u64 = 5 * 60;
+/// Ticks before the turn deadline at which the current intents are sent automatically, before the
+/// server stops accepting commitments.
+const AUTO_COMMIT_TICKS: u64 = ((TurnCommitment::REVEAL_SECONDS + 0.5) * 60.0) as u64;
+/// Ticks before the turn deadline at which committed moves are revealed, once the server surely
+/// accepts them despite the clocks drifting apart.
+const REVEAL_TICKS: u64 = ((TurnCommitment::REVEAL_SECONDS - 0.5) * 60.0) as u64;
+
+/// Frames between requests for the turns of an online lobby.
+const POLL_FRAMES: Frame = 60;
+
+/// Seconds without an answer from the server after which the connection is shown at risk.
+const CONNECTION_AT_RISK_SECONDS: f64 = 5.0;
+
+/// Frames an achievement toast stays on screen.
+const ACHIEVEMENT_TOAST_FRAMES: Frame = 180;
+
+/// Number of recent pointer positions drawn as a trail while aiming.
+const AIM_TRAIL_LENGTH: usize = 12;
+/// Number of recent positions drawn as a trail behind each bug while the turn resolves.
+const BUG_TRAIL_LENGTH: usize = 20;
+/// Distance in arena units a bug has to move for its trail to grow rather than shrink.
+const BUG_TRAIL_STEP: f32 = 0.02;
+
+/// Weight of the newest pointer position in the smoothed aim.
+const AIM_SMOOTHING: f32 = 0.35;
+/// Distance from the bug's centre, in arena units, within which the intent snaps to zero.
+const AIM_DEAD_ZONE: f32 = 0.6;
+/// Increment the intent's angle snaps to while the modifier key is held.
+const AIM_SNAP_ANGLE: f32 = TAU / 24.0;
+
+/// Applies the dead-zone and angle snapping of the [`AimOptions`] to a raw impulse intent.
+fn aim_intent(impulse_intent: Vector2<f32>, aim_options: &AimOptions, snap: bool) -> Vector2<f32> {
+    let magnitude = impulse_intent.magnitude();
+
+    if aim_options.dead_zone && magnitude < AIM_DEAD_ZONE {
+        return Vector2::zeros();
+    }
+
+    if snap && magnitude > 0.0 {
+        let angle = impulse_intent.y.atan2(impulse_intent.x);
+        let angle = (angle / AIM_SNAP_ANGLE).round() * AIM_SNAP_ANGLE;
+
+        return vector![angle.cos(), angle.sin()] * magnitude;
+    }
+
+    impulse_intent
+}
+
+/// Pixels a drag over the arena has to span before it selects the bugs in its box.
+const SELECTION_BOX_MIN: i32 = 4;
+/// Frames the ring marking a bug picked with the next-bug hotkey takes to close in on it.
+const CYCLE_FOCUS_FRAMES: Frame = 20;
+/// Frames the pointer has to be held still to inspect what is under it.
+const LONG_PRESS_FRAMES: Frame = 30;
+/// Frames an inspection card stays open.
+const INSPECTION_FRAMES: Frame = 300;
+/// Frames a zone scoring popup floats above the zone.
+const SCORE_POPUP_FRAMES: Frame = 60;
+/// Frames a line stays in the event feed.
+const EVENT_FEED_FRAMES: Frame = 240;
+/// Frames over which an event feed line fades out at the end of its life.
+const EVENT_FEED_FADE_FRAMES: Frame = 60;
+/// Height of an event feed line, fitting a bug icon.
+const EVENT_FEED_LINE_HEIGHT: f64 = 16.0;
+/// Most lines shown in the event feed at once.
+const EVENT_FEED_LINES: usize = 4;
+/// Latest log lines shown in the diagnostics panel.
+const DIAGNOSTICS_LOG_LINES: usize = 4;
+/// Characters of a log line shown in the diagnostics panel.
+const DIAGNOSTICS_LOG_WIDTH: usize = 40;
+
+/// Frames the end of a game is celebrated for before its results are shown.
+const RESULTS_DELAY_FRAMES: Frame = 180;
+
+/// Starting position, impulse and team of a bug moved by the resolving turn.
+type IntentArrow = (Vector2<f32>, Vector2<f32>, Team);
+
+/// Ticks the impulses of a resolving turn stay on screen for spectators, fading out over them.
+const INTENT_ARROW_TICKS: u64 = 90;
+
+/// Piece of an event feed line, laid out from left to right.
+enum FeedSegment {
+    /// Icon of a bug sort in its team's colours.
+    Bug(BugSort, Team),
+    /// Plain text.
+    Text(String),
+}
+
+impl FeedSegment {
+    fn width(&self) -> f64 {
+        match self {
+            FeedSegment::Bug(..) => 16.0,
+            FeedSegment::Text(text) => text_length(text) as f64,
+        }
+    }
+}
+
+/// Something in the arena opened in an info card.
+#[derive(Clone, Copy)]
+enum Inspected {
+    Bug(usize),
+    Prop(usize),
+}
+
+/// Formats seconds as a `m:ss` clock.
+fn format_clock(seconds: f32) -> String {
+    let seconds = seconds.max(0.0).round() as usize;
+
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Playback speed of local games.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum GameSpeed {
+    Half,
+    #[default]
+    Normal,
+    Double,
+    Quadruple,
+}
+
+impl GameSpeed {
+    pub fn from_value(value: usize) -> GameSpeed {
+        match value {
+            BUTTON_SPEED_HALF => GameSpeed::Half,
+            BUTTON_SPEED_DOUBLE => GameSpeed::Double,
+            BUTTON_SPEED_QUADRUPLE => GameSpeed::Quadruple,
+            _ => GameSpeed::Normal,
+        }
+    }
+
+    /// Value of the speed button which selects this speed.
+    pub fn value(&self) -> usize {
+        match self {
+            GameSpeed::Half => BUTTON_SPEED_HALF,
+            GameSpeed::Normal => BUTTON_SPEED_NORMAL,
+            GameSpeed::Double => BUTTON_SPEED_DOUBLE,
+            GameSpeed::Quadruple => BUTTON_SPEED_QUADRUPLE,
+        }
+    }
+
+    /// Number of game ticks to run on the given frame, skipping every other frame at half speed.
+    pub fn ticks_for_frame(&self, frame: Frame) -> usize {
+        match self {
+            GameSpeed::Half => (frame % 2) as usize,
+            GameSpeed::Normal => 1,
+            GameSpeed::Double => 2,
+            GameSpeed::Quadruple => 4,
+        }
+    }
+}
+
+/// Whether two turns move the same bugs with the same intents.
+fn same_intents(turn: &Turn, other: &Turn) -> bool {
+    let moving = |turn: &Turn| -> HashMap<usize, Vector2<f32>> {
+        turn.impulse_intents
+            .iter()
+            .filter(|(_, impulse_intent)| impulse_intent.magnitude() > 0.0)
+            .map(|(bug_index, impulse_intent)| (*bug_index, *impulse_intent))
+            .collect()
+    };
+
+    moving(turn) == moving(other)
+}
+
+/// A turn executed locally ahead of the server, until its authoritative copy arrives.
+struct Prediction {
+    /// The game right before the predicted turn, re-simulated from on a misprediction.
+    snapshot: Game,
+    turn: Turn,
+}
+
+/// Offscreen canvas holding the painted capture heatmap, only repainted when the heatmap changes.
+struct HeatmapLayer {
+    canvas: Box<dyn Canvas>,
+    painted: Option<CaptureHeatmap>,
+}
+
+impl HeatmapLayer {
+    fn new(size: u32) -> Result<HeatmapLayer, PlatformError> {
+        Ok(HeatmapLayer {
+            canvas: system().create_canvas(size, size)?,
+            painted: None,
+        })
+    }
+
+    fn update(&mut self, heatmap: &CaptureHeatmap) -> Result<(), PlatformError> {
+        if self.painted.as_ref() != Some(heatmap) {
+            draw_capture_heatmap(self.canvas.as_ref(), heatmap, self.canvas.width() as f64)?;
+            self.painted = Some(heatmap.clone());
+        }
+
+        Ok(())
+    }
+}
+
+/// Timings of the turn polls of an online game, shown in the diagnostics panel.
+#[derive(Default)]
+struct SyncDiagnostics {
+    /// When the outstanding poll was sent, in milliseconds.
+    requested_at: Option<f64>,
+    /// Round trip of the last answered poll, in milliseconds.
+    round_trip: Option<f64>,
+    /// Local time of the last [`Message::TurnSync`], in seconds.
+    last_sync: Option<f64>,
+    /// Turns the server had executed as of the last sync.
+    server_turn_count: Option<usize>,
+}
+
+/// Placement of the turn timer and capture bars. In landscape they run along the top and bottom
+/// edges; on portrait screens they run down the left and right edges, which become the top and
+/// bottom once the canvas is rotated, clear of the arena.
+#[derive(Clone, Copy)]
+struct HudLayout {
+    /// Centre of the turn timer bar.
+    timer: (i32, i32),
+    /// Centre of the capture bar.
+    capture: (i32, i32),
+    vertical: bool,
+}
+
+impl HudLayout {
+    /// Length of both bars.
+    const BAR_LENGTH: i32 = 7 * 24;
+
+    fn new(portrait: bool) -> HudLayout {
+        if portrait {
+            HudLayout {
+                timer: (2, 360 / 2),
+                capture: (384 - 10, 360 / 2),
+                vertical: true,
+            }
+        } else {
+            HudLayout {
+                timer: (384 / 2, 8),
+                capture: (384 / 2, 360 - 16),
+                vertical: false,
+            }
+        }
+    }
+
+    /// Position and size of the part of a bar centred at `centre` which spans `length` pixels
+    /// from `start`, relative to the middle of the bar.
+    fn segment(&self, centre: (i32, i32), start: i32, length: i32) -> ((i32, i32), (i32, i32)) {
+        if self.vertical {
+            ((centre.0, centre.1 + start), (8, length))
+        } else {
+            ((centre.0 + start, centre.1), (length, 8))
+        }
+    }
+
+    /// Point on the middle line of a bar centred at `centre`, `offset` pixels from its middle.
+    fn point(&self, centre: (i32, i32), offset: f64) -> (f64, f64) {
+        if self.vertical {
+            (centre.0 as f64 + 4.0, centre.1 as f64 + offset)
+        } else {
+            (centre.0 as f64 + offset, centre.1 as f64 + 4.0)
+        }
+    }
+}
+
+/// Where the player's team stands on surrendering, from the vote the server last relayed.
+#[derive(Clone, Copy, PartialEq)]
+enum SurrenderStatus {
+    /// The team may start a vote.
+    Available,
+    /// A teammate started a vote the player has yet to confirm, with the seconds left.
+    Asked(f64),
+    /// The player voted and waits for their teammates, with the seconds left.
+    Voted(f64),
+    /// The last vote expired and another may not be started yet.
+    CoolingDown,
+}
 
 pub struct GameState {
     interface: Interface,
+    speed_interface: Interface,
+    speed: GameSpeed,
     lobby: Lobby,
     particle_system: ParticleSystem,
-    message_pool: Rc<RefCell<MessagePool>>,
-    message_closure: Closure<dyn FnMut(JsValue)>,
-    shake_frame: (u64, usize),
+    dispatcher: Dispatcher,
+    /// Frame from which the turns of an online lobby are requested again.
+    next_poll: Frame,
+    shake_frame: (u64, Frame),
     selected_bug_index: Option<usize>,
+    /// Other friendly bugs aimed along with the selected one, each given the same impulse.
+    selected_group: Vec<usize>,
+    /// Where the pointer went down to drag a box selecting several bugs.
+    selection_box: Option<(i32, i32)>,
+    /// Bug picked with the next-bug hotkey and the impulse intent it had before it was aimed,
+    /// given back when cycling on so passing over a bug does not plan a move for it.
+    cycled_bug: Option<(usize, Vector2<f32>)>,
+    /// Frame at which the next-bug hotkey last picked a bug.
+    cycle_frame: Frame,
     animated_capture_progress: f32,
-    capture_frame: usize,
+    capture_frame: Frame,
+    layer_timings: Vec<(&'static str, f64)>,
+    physics_time: f64,
+    /// Milliseconds spent in [`Game::tick`] during the last frame, without the bookkeeping
+    /// around it.
+    tick_time: f64,
+    /// Ticks run during the last frame, and how many of them skipped the physics as every body
+    /// was asleep.
+    physics_ticks: (u64, u64),
+    bots: Vec<(Team, Box<dyn BotController>)>,
+    attract_input_frame: Option<Frame>,
+    replay_interface: Interface,
+    replay_turns: Option<VecDeque<Turn>>,
+    replay_end_frame: Frame,
+    phase_banner: Option<(&'static str, Frame)>,
+    reminded_turn: Option<usize>,
+    committed_turn: Option<usize>,
+    server_sync: Option<(usize, f32, Option<u64>)>,
+    sync_diagnostics: SyncDiagnostics,
+    server_deadline: Option<(usize, f64)>,
+    /// Local time at which the warmup ends, once the server reported one.
+    warmup_until: Option<f64>,
+    smoothed_aim: Option<Vector2<f32>>,
+    press: Option<(Frame, (i32, i32))>,
+    inspection: Option<(Inspected, (i32, i32), Frame)>,
+    return_to_menu: bool,
+    /// Whether the last slot of the lobby was taken just before the player could join it.
+    slot_taken: bool,
+    score_popups: Vec<(String, Frame)>,
+    formation_interface: Interface,
+    suggest_interface: Interface,
+    /// Moves the built-in bot suggests for the player's bugs, and the turn they were suggested for.
+    suggestion: Option<(usize, VecMap<usize, Vector2<f32>>)>,
+    prediction: Option<Prediction>,
+    aim_trail: VecDeque<(i32, i32)>,
+    /// The game as it was when the current turn was first awaited.
+    turn_snapshot: Option<Game>,
+    /// The game as it was when the last executed turn was awaited.
+    last_turn_snapshot: Option<Game>,
+    /// Slowed-down rerun of the last turn being shown over the live game.
+    turn_replay: Option<Game>,
+    turn_replay_interface: Interface,
+    surrender_interface: Interface,
+    /// Latest surrender vote of the player's team, as relayed by the server.
+    surrender_vote: Option<SurrenderVote>,
+    /// Recent positions of each bug while the turn resolves, by bug index.
+    bug_trails: HashMap<usize, VecDeque<Vector2<f32>>>,
+    achievement_tracker: Option<AchievementTracker>,
+    achievement_toasts: Vec<(Achievement, Frame)>,
+    /// Achievements unlocked for the first time during this game.
+    unlocked_achievements: Vec<Achievement>,
+    heatmap_layer: Option<HeatmapLayer>,
+    event_feed: VecDeque<(Vec<FeedSegment>, Frame)>,
+    bug_hits: HashMap<usize, usize>,
+    /// Whether the HUD is laid out for a portrait screen.
+    portrait: bool,
+    /// Moves sent to the server which it has yet to acknowledge.
+    outbox: Outbox,
+    /// Index and first tick of the last executed turn, with the arrows of its moved bugs.
+    resolved_intents: Option<(usize, u64, Vec<IntentArrow>)>,
+    /// Hint currently shown, if any.
+    hint: Option<Hint>,
+    hint_interface: Interface,
+    /// Hints already dismissed, or all of them when hints are turned off.
+    seen_hints: Vec<Hint>,
+    /// Shown over the arena of online games until the server starts the game.
+    waiting_room: Option<WaitingRoom>,
+    /// Local time of the last answer from the server.
+    last_heard: Option<f64>,
+    /// Local time at which the server warned it drops the lobby, unless a heartbeat reaches it.
+    lobby_expires_at: Option<f64>,
+    /// Round of target practice played in the sandbox, if any.
+    practice: Option<TargetPractice>,
+    /// Buttons shown once the round of target practice is over and its score recorded.
+    practice_interface: Option<Interface>,
+    /// Rank of the finished round in the high score table, if it made the table.
+    practice_rank: Option<usize>,
 }
 
 impl GameState {
     pub fn new(lobby_settings: LobbySettings, session_id: String) -> GameState {
-        let message_pool = Rc::new(RefCell::new(MessagePool::new()));
-
-        let message_closure = {
-            let message_pool = message_pool.clone();
-
-            Closure::<dyn FnMut(JsValue)>::new(move |value| {
-                let mut message_pool = message_pool.borrow_mut();
-                let message: Message = serde_wasm_bindgen::from_value(value).unwrap();
-                message_pool.push(message);
-            })
-        };
+        let dispatcher = Dispatcher::default();
 
         if let shared::LobbySort::Online(0) = lobby_settings.sort() {
-            let _ = create_new_lobby(lobby_settings.clone(), session_id)
-                .unwrap()
-                .then(&message_closure);
+            dispatcher.dispatch(
+                ApiClient::new(session_id).create_lobby(lobby_settings.clone()),
+                |lobby| Message::Lobby(Box::new(lobby)),
+            );
+        } else if session_id.is_empty() {
+            // Spectators only follow the lobby, see GameState::spectate
         } else if let shared::LobbySort::Online(lobby_id) = lobby_settings.sort() {
-            let _ = send_ready(*lobby_id, session_id)
-                .unwrap()
-                .then(&message_closure);
+            dispatcher.dispatch(ApiClient::new(session_id).join_lobby(*lobby_id), |lobby| {
+                Message::Lobby(Box::new(lobby))
+            });
+        } else if let LobbySort::LocalAI = lobby_settings.sort() {
+            // Without a level seed the win is not verified, which is no reason to leave the game
+            let request = ApiClient::new(session_id).level_seed();
+
+            dispatcher.dispatch(
+                async move { Ok(request.await.inspect_err(|err| warn!("{err}")).ok()) },
+                |level_seed| level_seed.map_or(Message::Ok, Message::LevelSeed),
+            );
         }
 
         let _button_menu = ToggleButtonElement::new(
@@ -101,209 +501,2777 @@ impl GameState {
 
         let root_element = Interface::new(vec![button_rematch.boxed(), button_leave.boxed()]);
 
+        let speed_interface = GameState::create_speed_interface(false, GameSpeed::default());
+
+        let button_export = ButtonElement::new(
+            (384 - 56, 360 - 20),
+            (48, 16),
+            BUTTON_EXPORT,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Export".to_string(), Alignment::Center),
+        );
+
+        let replay_interface = Interface::new(vec![button_export.boxed()]);
+
+        let button_replay_turn = ButtonElement::new(
+            (384 - 56 - 88, 360 - 20),
+            (80, 16),
+            BUTTON_REPLAY_TURN,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Replay turn".to_string(), Alignment::Center),
+        );
+
+        let turn_replay_interface = Interface::new(vec![button_replay_turn.boxed()]);
+
+        let button_surrender = ButtonElement::new(
+            (384 - 80, 4),
+            (72, 16),
+            BUTTON_SURRENDER,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Surrender".to_string(), Alignment::Center),
+        );
+
+        let surrender_interface = Interface::new(vec![button_surrender.boxed()]);
+
+        let formation_interface = GameState::create_formation_interface(false);
+        let suggest_interface = GameState::create_suggest_interface(false);
+
+        let waiting_room =
+            matches!(lobby_settings.sort(), LobbySort::Online(_)).then(WaitingRoom::default);
+
+        let bots = if let LobbySort::LocalAI = lobby_settings.sort() {
+            vec![(
+                Team::Blue,
+                active_bot(ZoneBot::for_settings(&lobby_settings)),
+            )]
+        } else {
+            Vec::new()
+        };
+
         GameState {
             interface: root_element,
+            speed_interface,
+            speed: GameSpeed::default(),
             lobby: Lobby::new(lobby_settings, 0.0),
             particle_system: ParticleSystem::default(),
-            message_pool,
-            message_closure,
+            dispatcher,
+            next_poll: 0,
             shake_frame: (0, 0),
             selected_bug_index: None,
+            selected_group: Vec::new(),
+            selection_box: None,
+            cycled_bug: None,
+            cycle_frame: 0,
             animated_capture_progress: 0.0,
             capture_frame: 0,
+            layer_timings: Vec::new(),
+            physics_time: 0.0,
+            tick_time: 0.0,
+            physics_ticks: (0, 0),
+            bots,
+            attract_input_frame: None,
+            replay_interface,
+            replay_turns: None,
+            replay_end_frame: 0,
+            phase_banner: None,
+            reminded_turn: None,
+            committed_turn: None,
+            server_sync: None,
+            sync_diagnostics: SyncDiagnostics::default(),
+            server_deadline: None,
+            warmup_until: None,
+            smoothed_aim: None,
+            press: None,
+            inspection: None,
+            return_to_menu: false,
+            slot_taken: false,
+            score_popups: Vec::new(),
+            formation_interface,
+            suggest_interface,
+            suggestion: None,
+            prediction: None,
+            aim_trail: VecDeque::new(),
+            turn_snapshot: None,
+            last_turn_snapshot: None,
+            turn_replay: None,
+            turn_replay_interface,
+            surrender_interface,
+            surrender_vote: None,
+            bug_trails: HashMap::new(),
+            achievement_tracker: None,
+            achievement_toasts: Vec::new(),
+            unlocked_achievements: Vec::new(),
+            heatmap_layer: None,
+            event_feed: VecDeque::new(),
+            bug_hits: HashMap::new(),
+            portrait: false,
+            outbox: Outbox::default(),
+            resolved_intents: None,
+            hint: None,
+            hint_interface: Interface::new(Vec::new()),
+            seen_hints: if hints_enabled() {
+                seen_hints()
+            } else {
+                Hint::ALL.to_vec()
+            },
+            waiting_room,
+            last_heard: None,
+            lobby_expires_at: None,
+            practice: None,
+            practice_interface: None,
+            practice_rank: None,
         }
     }
 
-    pub fn particle_system(&mut self) -> &mut ParticleSystem {
-        &mut self.particle_system
+    /// Builds the local speed picker, moved from the top edge down to the bottom-right corner on
+    /// portrait screens.
+    fn create_speed_interface(portrait: bool, speed: GameSpeed) -> Interface {
+        let speed_buttons = [
+            (BUTTON_SPEED_HALF, ".5"),
+            (BUTTON_SPEED_NORMAL, "1"),
+            (BUTTON_SPEED_DOUBLE, "2"),
+            (BUTTON_SPEED_QUADRUPLE, "4"),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (value, text))| {
+            ButtonElement::new(
+                (i as i32 * 20, 0),
+                (18, 16),
+                value,
+                LabelTrim::Round,
+                LabelTheme::Default,
+                crate::app::ContentElement::Text(text.to_string(), Alignment::Center),
+            )
+        })
+        .collect();
+
+        let position = if portrait { (288, 360 - 40) } else { (288, 4) };
+
+        Interface::new(vec![ButtonGroupElement::new(
+            position,
+            speed_buttons,
+            speed.value(),
+        )
+        .boxed()])
     }
 
-    pub fn team_for(&self, session_id: &Option<String>) -> Option<Team> {
-        if let Some(session_id) = session_id {
-            self.lobby
-                .players()
-                .get(session_id)
-                .map(|player| player.team)
+    /// Builds the formation buttons, stacked up from the bottom-left corner on portrait screens.
+    fn create_formation_interface(portrait: bool) -> Interface {
+        let formation_count = Formation::ALL.len() as i32;
+
+        Interface::new(
+            Formation::ALL
+                .iter()
+                .enumerate()
+                .map(|(i, formation)| {
+                    let y = if portrait {
+                        360 - 28 - (formation_count - 1 - i as i32) * 20
+                    } else {
+                        24 + i as i32 * 20
+                    };
+
+                    ButtonElement::new(
+                        (8, y),
+                        (56, 16),
+                        BUTTON_FORMATION + i,
+                        LabelTrim::Round,
+                        LabelTheme::Default,
+                        crate::app::ContentElement::Text(
+                            formation.label().to_string(),
+                            Alignment::Center,
+                        ),
+                    )
+                    .boxed()
+                })
+                .collect(),
+        )
+    }
+
+    /// Lays out the suggest button next to the formation buttons, below them in landscape and above
+    /// them in portrait.
+    fn create_suggest_interface(portrait: bool) -> Interface {
+        let formation_count = Formation::ALL.len() as i32;
+
+        let y = if portrait {
+            360 - 28 - formation_count * 20
         } else {
-            None
+            24 + formation_count * 20
+        };
+
+        Interface::new(vec![ButtonElement::new(
+            (8, y),
+            (56, 16),
+            BUTTON_SUGGEST,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Suggest".to_string(), Alignment::Center),
+        )
+        .boxed()])
+    }
+
+    /// Remembers where each bug stood and which impulse it was given once a new turn executes.
+    /// Keeps a snapshot of the game whenever a new turn is awaited, which becomes the snapshot of
+    /// the last turn once that turn is executed.
+    fn record_snapshot(&mut self) {
+        let turns_count = self.lobby.game.turns_count();
+
+        if let Some(snapshot) = self
+            .turn_snapshot
+            .take_if(|snapshot| snapshot.turns_count() != turns_count)
+        {
+            // Several turns executed at once while catching up leave nothing to replay
+            self.last_turn_snapshot =
+                Some(snapshot).filter(|snapshot| snapshot.turns_count() + 1 == turns_count);
+        }
+
+        if self.turn_snapshot.is_none()
+            && self.lobby.game.awaiting_turn()
+            && !self.lobby.game.is_sandbox()
+        {
+            self.turn_snapshot = Some(self.lobby.game.clone());
         }
     }
 
-    pub(crate) fn print_turns(&self) {
-        let indexes: Vec<_> = self.lobby.turns().iter().map(|v| v.index).collect();
-        console::log_1(&format!("{indexes:#?}").into());
+    /// Determines if the last turn can be replayed, only while planning the next one.
+    fn can_replay_turn(&self) -> bool {
+        self.last_turn_snapshot.is_some()
+            && self.turn_replay.is_none()
+            && self.attract_input_frame.is_none()
+            && self.replay_turns.is_none()
+            && self.lobby.game.phase() == GamePhase::Planning
     }
-}
 
-impl State for GameState {
-    fn draw(
-        &mut self,
-        context: &CanvasRenderingContext2d,
-        _interface_context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
-        app_context: &AppContext,
-    ) -> Result<(), JsValue> {
-        let frame = app_context.frame;
-        let pointer = &app_context.pointer;
+    /// Starts rerunning the last turn from its snapshot, with the turn as finally recorded rather
+    /// than as predicted.
+    fn start_turn_replay(&mut self) {
+        let Some(snapshot) = &self.last_turn_snapshot else {
+            return;
+        };
 
-        let my_team = self.team_for(&app_context.session_id);
+        if let Some(turn) = self.lobby.game.turn(snapshot.turns_count()) {
+            let mut game = snapshot.clone();
 
-        let point = tuple_as!(screen_to_local(tuple_as!(pointer.location, f64)), f32);
-        let point = point![point.0, point.1];
+            if game.queue_turns(vec![turn.clone()]).is_ok() {
+                self.turn_replay = Some(game);
+                self.deselect();
+            }
+        }
+    }
 
-        draw_image_centered(
-            context,
-            atlas,
-            360.0,
-            0.0,
-            360.0,
-            360.0,
-            384.0 / 2.0,
-            360.0 / 2.0,
-        )?;
+    /// Advances the turn replay at half speed, ending it once its bugs stop resolving, on a tap, or
+    /// when the live game moves on from planning.
+    ///
+    /// Returns whether the replay was shown, in which case input is not passed on to the game.
+    fn tick_turn_replay(&mut self, app_context: &AppContext) -> bool {
+        if self.turn_replay.is_none() {
+            return false;
+        }
 
-        self.animated_capture_progress +=
-            (self.lobby.game.capture_progress() - self.animated_capture_progress) * 0.05;
+        if app_context.pointer.clicked() || self.lobby.game.phase() != GamePhase::Planning {
+            self.turn_replay = None;
+            return true;
+        }
 
-        draw_sand_circle(
-            &app_context.atlas_context,
-            self.animated_capture_progress,
-            self.lobby.game.capture_radius() * 16.0,
-        )?;
+        let frame = app_context.clock.frame();
+        let ticks =
+            app_context.frame_limit.ticks_per_frame() * GameSpeed::Half.ticks_for_frame(frame);
+
+        let resolved = self.with_replay_game(|state| {
+            for _ in 0..ticks {
+                state.lobby.game.tick();
+                state.record_trails();
+            }
+
+            // Effects are only for the live game
+            state.lobby.game.drain_events();
+
+            state.lobby.game.all_turns_count() == state.lobby.game.turns_count()
+                && state.lobby.game.phase() != GamePhase::Resolving
+        });
+
+        if resolved == Some(true) {
+            self.turn_replay = None;
+        }
 
-        draw_image_centered(
+        true
+    }
+
+    /// Runs a closure with the turn replay standing in for the live game, if one is shown.
+    fn with_replay_game<T>(&mut self, f: impl FnOnce(&mut GameState) -> T) -> Option<T> {
+        let mut game = self.turn_replay.take()?;
+
+        std::mem::swap(&mut self.lobby.game, &mut game);
+        let result = f(self);
+        std::mem::swap(&mut self.lobby.game, &mut game);
+
+        self.turn_replay = Some(game);
+
+        Some(result)
+    }
+
+    /// Marks the turn replay over the live game.
+    fn draw_turn_replay(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        draw_label(
             context,
             atlas,
-            360.0,
-            360.0,
-            360.0,
-            360.0,
-            384.0 / 2.0,
-            360.0 / 2.0,
+            ((384 - 128) / 2, 40),
+            (128, 20),
+            "#7f3faa",
+            &crate::app::ContentElement::Text("Last turn x0.5".to_string(), Alignment::Center),
+            &app_context.pointer,
+            app_context.clock.frame(),
+            &LabelTrim::Glorious,
+            false,
         )?;
 
+        draw_text_centered(context, atlas, 384.0 / 2.0, 64.0, "Tap to skip")
+    }
+
+    /// Extends the trails of moving bugs while the turn resolves, shrinking them away otherwise.
+    fn record_trails(&mut self) {
+        let positions: HashMap<usize, Vector2<f32>> = if self.lobby.game.phase()
+            == GamePhase::Resolving
         {
-            let bar_width = 7 * 24;
-            let length = bar_width as f64
-                - (self.lobby.game.turn_percentage_time() * bar_width as f64)
-                    .floor()
-                    .clamp(0.0, bar_width as f64);
-            let label_length = (length as i32 / 2) * 2;
+            self.lobby
+                .game
+                .iter_bugs()
+                .map(|(rigid_body, _)| (rigid_body.user_data as usize, *rigid_body.translation()))
+                .collect()
+        } else {
+            HashMap::new()
+        };
 
-            draw_label(
-                context,
-                atlas,
-                ((384 - bar_width) / 2, 8),
-                (bar_width, 8),
-                "#002a2a",
-                &crate::app::ContentElement::None,
-                pointer,
-                frame,
-                &LabelTrim::Round,
-                false,
-            )?;
+        for (bug_index, position) in &positions {
+            let trail = self.bug_trails.entry(*bug_index).or_default();
 
-            draw_label(
-                context,
-                atlas,
-                ((384 - label_length) / 2, 8),
-                (label_length, 8),
-                "#CA891B",
-                &crate::app::ContentElement::None,
-                pointer,
-                frame,
-                &LabelTrim::Round,
-                false,
-            )?;
+            if trail
+                .back()
+                .is_none_or(|last| (last - position).norm() > BUG_TRAIL_STEP)
+            {
+                trail.push_back(*position);
 
-            let simulation_portion_length =
-                (1.0 - self.lobby.game.turn_percentage_time_half()) * bar_width as f64;
-            let simulation_portion_label_length = (simulation_portion_length as i32 / 2) * 2;
+                if trail.len() > BUG_TRAIL_LENGTH {
+                    trail.pop_front();
+                }
+            } else {
+                trail.pop_front();
+            }
+        }
 
-            draw_label(
-                context,
-                atlas,
-                (
-                    (384 - (simulation_portion_label_length).min(label_length)) / 2,
-                    8,
-                ),
-                ((simulation_portion_label_length).min(label_length), 8),
-                "#fff",
-                &crate::app::ContentElement::None,
-                pointer,
-                frame,
-                &LabelTrim::Round,
-                false,
-            )?;
+        for (bug_index, trail) in self.bug_trails.iter_mut() {
+            if !positions.contains_key(bug_index) {
+                trail.pop_front();
+            }
         }
 
-        {
-            let capture_progress = self.animated_capture_progress;
-            let length = (capture_progress * 7.0 * 12.0)
-                .abs()
-                .floor()
-                .clamp(0.0, 7.0 * 12.0);
-            let length = (length as i32 / 2) * 2;
+        self.bug_trails.retain(|_, trail| !trail.is_empty());
+    }
 
-            draw_label(
-                context,
-                atlas,
-                ((384 - 7 * 24) / 2, 360 - 16),
-                (7 * 24, 8),
-                "#002a2a",
-                &crate::app::ContentElement::None,
-                pointer,
-                frame,
-                &LabelTrim::Round,
-                false,
-            )?;
+    fn record_intents(&mut self) {
+        if let Some(turn) = self.lobby.game.last_turn() {
+            if self
+                .resolved_intents
+                .as_ref()
+                .is_some_and(|(index, _, _)| *index == turn.index)
+            {
+                return;
+            }
 
-            draw_label(
-                context,
-                atlas,
-                ((384 / 2) + length.min(0), 360 - 16),
-                (length, 8),
-                if capture_progress > 0.0 {
-                    "#C20005"
-                } else {
-                    "#00C2BD"
-                },
-                &crate::app::ContentElement::None,
-                pointer,
-                frame,
-                &LabelTrim::Round,
-                false,
-            )?;
-        }
+            let arrows = turn
+                .impulse_intents
+                .iter()
+                .filter_map(|(bug_index, intent)| {
+                    self.lobby
+                        .game
+                        .get_bug(*bug_index)
+                        .map(|(rigid_body, bug_data)| {
+                            (*rigid_body.translation(), *intent, *bug_data.team())
+                        })
+                })
+                .collect();
 
-        {
-            context.save();
-            context.translate(384.0 / 2.0, 360.0 / 2.0)?;
-            self.particle_system()
-                .tick_and_draw(context, atlas, frame)?;
-            context.restore();
+            self.resolved_intents = Some((turn.index, self.lobby.game.ticks(), arrows));
         }
+    }
 
-        if let Some((_, rigid_body, _bug_data)) = self.lobby.game.intersecting_bug(point) {
-            let (dx, dy) = local_to_screen(rigid_body.translation());
+    /// Follows an online lobby as a spectator, without trying to take one of its slots.
+    pub fn spectate(lobby_id: LobbyID) -> GameState {
+        GameState::new(
+            LobbySettings::new(LobbySort::Online(lobby_id)),
+            String::new(),
+        )
+    }
+
+    /// Creates a viewer which plays back the turns of a [`Replay`] one by one.
+    pub fn replay(replay: Replay) -> GameState {
+        let mut game_state = GameState::new(replay.settings, String::new());
+
+        game_state.replay_turns = Some(VecDeque::from(replay.turns));
+
+        game_state
+    }
+
+    /// Where the player's team stands on surrendering, if they play an online team game.
+    fn surrender_status(&self, app_context: &AppContext) -> Option<SurrenderStatus> {
+        let session_id = app_context.session_id.as_ref()?;
+
+        if !matches!(self.lobby.settings.sort(), LobbySort::Online(_))
+            || self.lobby.settings.players_per_team() <= 1
+            || self.lobby.game.is_sandbox()
+            || self.lobby.game.result().is_some()
+            || self.team_for(&app_context.session_id).is_none()
+        {
+            return None;
+        }
+
+        let Some(vote) = &self.surrender_vote else {
+            return Some(SurrenderStatus::Available);
+        };
+
+        let now = GameClock::local_time();
+        let expires_at = app_context
+            .clock
+            .server_to_local(vote.expires_at)
+            .unwrap_or(vote.expires_at);
+
+        Some(if now < expires_at && vote.voters.contains(session_id) {
+            SurrenderStatus::Voted(expires_at - now)
+        } else if now < expires_at {
+            SurrenderStatus::Asked(expires_at - now)
+        } else if now < expires_at + SurrenderVote::COOLDOWN {
+            SurrenderStatus::CoolingDown
+        } else {
+            SurrenderStatus::Available
+        })
+    }
+
+    /// Starts or confirms the surrender vote of the player's team.
+    fn vote_surrender(&self, app_context: &AppContext) {
+        self.send_to_lobby(app_context, Message::Surrender);
+    }
+
+    /// Sends a message to the online lobby on behalf of the player, without retrying.
+    fn send_to_lobby(&self, app_context: &AppContext, message: Message) {
+        if let (LobbySort::Online(lobby_id), Some(session_id)) =
+            (self.lobby.settings.sort(), app_context.session_id.clone())
+        {
+            self.dispatcher
+                .send(ApiClient::new(session_id).act(*lobby_id, message, None));
+        }
+    }
+
+    /// Determines whether the connection to the server of an unfinished online game is at risk,
+    /// returning the seconds left before the server drops the lobby if it warned of it.
+    fn connection_at_risk(&self) -> Option<Option<f64>> {
+        if !matches!(self.lobby.settings.sort(), LobbySort::Online(_))
+            || self.lobby.game.result().is_some()
+        {
+            return None;
+        }
+
+        let now = GameClock::local_time();
+
+        match (self.lobby_expires_at, self.last_heard) {
+            (Some(expires_at), _) if expires_at > now => Some(Some(expires_at - now)),
+            (_, Some(last_heard)) if now - last_heard > CONNECTION_AT_RISK_SECONDS => Some(None),
+            _ => None,
+        }
+    }
+
+    /// Draws a banner while the connection is at risk, counting down to the lobby being dropped
+    /// once the server warned of it.
+    fn draw_connection_warning(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let Some(seconds_left) = self.connection_at_risk() else {
+            return Ok(());
+        };
+
+        let text = match seconds_left {
+            Some(seconds_left) => {
+                format!("Connection at risk, closing in {:.0}s", seconds_left.ceil())
+            }
+            None => "Connection at risk, reconnecting...".to_string(),
+        };
+
+        draw_label(
+            context,
+            atlas,
+            ((384 - 272) / 2, 24),
+            (272, 16),
+            "#aa3f3f",
+            &crate::app::ContentElement::Text(text, Alignment::Center),
+            &app_context.pointer,
+            app_context.clock.frame(),
+            &LabelTrim::Round,
+            false,
+        )
+    }
+
+    /// Draws the surrender button, and the vote in progress for the player's team.
+    fn draw_surrender(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+
+        let (headline, prompt, seconds) = match self.surrender_status(app_context) {
+            Some(SurrenderStatus::Available) => {
+                return self
+                    .surrender_interface
+                    .draw(context, atlas, pointer, frame);
+            }
+            Some(SurrenderStatus::Asked(seconds)) => {
+                self.surrender_interface
+                    .draw(context, atlas, pointer, frame)?;
+
+                ("Ally votes to surrender", "Tap Surrender to agree", seconds)
+            }
+            Some(SurrenderStatus::Voted(seconds)) => {
+                ("Surrender vote", "Waiting for your ally", seconds)
+            }
+            Some(SurrenderStatus::CoolingDown) | None => return Ok(()),
+        };
+
+        draw_label(
+            context,
+            atlas,
+            ((384 - 200) / 2, 28),
+            (200, 20),
+            "#7f0000",
+            &crate::app::ContentElement::Text(
+                format!("{headline} {:.0}s", seconds.ceil()),
+                Alignment::Center,
+            ),
+            pointer,
+            frame,
+            &LabelTrim::Round,
+            false,
+        )?;
+
+        draw_text_centered(context, atlas, 384.0 / 2.0, 52.0, prompt)
+    }
+
+    /// Shows the replay code of this game so it can be copied and shared.
+    fn export_replay(&self) {
+        let Some(replay) = Replay::new(&self.lobby) else {
+            dialogs().alert("This match is too long to be shared as a replay.");
+            return;
+        };
+
+        let _ = dialogs().prompt(
+            "Copy this replay code to share the match:",
+            &replay.encode(),
+        );
+    }
+
+    /// Creates an AI versus AI exhibition which ends on the first input after `last_input_frame`.
+    pub fn exhibition(last_input_frame: Frame) -> GameState {
+        let mut game_state = GameState::new(LobbySettings::new(LobbySort::LocalAI), String::new());
+
+        game_state
+            .bots
+            .push((Team::Red, Box::new(ZoneBot::default())));
+        game_state.attract_input_frame = Some(last_input_frame);
+
+        game_state
+    }
+
+    /// Starts a round of target practice, flicking Red's bugs at dummies and bullseyes.
+    pub fn target_practice() -> GameState {
+        let mut game_state = GameState::new(LobbySettings::new(LobbySort::LocalAI), String::new());
+        let mut practice = TargetPractice::new((random() * u32::MAX as f64) as u64);
+
+        game_state.lobby.game = practice.arena();
+        game_state.bots.clear();
+        game_state.practice = Some(practice);
+
+        game_state
+    }
+
+    /// Records the score of a finished round of target practice and offers another round.
+    fn finish_practice(&mut self, score: u32) {
+        self.practice_rank = record_high_score(score);
+
+        let button_retry = ButtonElement::new(
+            (384 / 2 - 88, 248),
+            (80, 20),
+            BUTTON_PRACTICE_RETRY,
+            LabelTrim::Glorious,
+            LabelTheme::Action,
+            crate::app::ContentElement::Text("Again!".to_string(), Alignment::Center),
+        );
+
+        let button_leave = ButtonElement::new(
+            (384 / 2 + 8, 250),
+            (80, 16),
+            BUTTON_PRACTICE_LEAVE,
+            LabelTrim::Return,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Leave".to_string(), Alignment::Center),
+        );
+
+        self.practice_interface = Some(Interface::new(vec![
+            button_retry.boxed(),
+            button_leave.boxed(),
+        ]));
+    }
+
+    /// Draws the score, flicks and time left of target practice, and the high score table once
+    /// the round is over.
+    fn draw_practice(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        practice: &TargetPractice,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        draw_text_centered(
+            context,
+            atlas,
+            384.0 / 2.0,
+            20.0,
+            &format!(
+                "Wave {}  Score {}  Flicks {}  Time {:.0}s",
+                practice.wave(),
+                practice.score(),
+                practice.flicks_left(),
+                practice.seconds_left().ceil(),
+            ),
+        )?;
+
+        let Some(practice_interface) = &self.practice_interface else {
+            return Ok(());
+        };
+
+        context.set_fill_style("rgba(0, 0, 0, 0.75)");
+        context.fill_rect(96.0, 64.0, 192.0, 216.0);
+
+        let headline = if practice.seconds_left() > 0.0 {
+            "Out of flicks!"
+        } else {
+            "Time's up!"
+        };
+
+        draw_text_centered(context, atlas, 384.0 / 2.0, 76.0, headline)?;
+        draw_text_centered(
+            context,
+            atlas,
+            384.0 / 2.0,
+            92.0,
+            &format!("Score {}", practice.score()),
+        )?;
+        draw_text_centered(context, atlas, 384.0 / 2.0, 116.0, "High scores")?;
+
+        for (i, score) in high_scores().into_iter().enumerate() {
+            // The new entry blinks
+            if self.practice_rank == Some(i) && app_context.clock.frame() % 60 >= 40 {
+                continue;
+            }
+
+            draw_text_centered(
+                context,
+                atlas,
+                384.0 / 2.0,
+                132.0 + i as f64 * 14.0,
+                &format!("{}. {score}", i + 1),
+            )?;
+        }
+
+        practice_interface.draw(
+            context,
+            atlas,
+            &app_context.pointer,
+            app_context.clock.frame(),
+        )
+    }
+
+    pub fn particle_system(&mut self) -> &mut ParticleSystem {
+        &mut self.particle_system
+    }
+
+    pub fn team_for(&self, session_id: &Option<String>) -> Option<Team> {
+        if self.attract_input_frame.is_some() {
+            None
+        } else if self.lobby.has_ai() {
+            Some(Team::Red)
+        } else if let Some(session_id) = session_id {
+            self.lobby
+                .players()
+                .get(session_id)
+                .map(|player| player.team)
+        } else {
+            None
+        }
+    }
+
+    /// Determines whether the session controls a bug, as 2v2 players only command their own squad.
+    /// Drops the selected bug and the bugs grouped with it.
+    fn deselect(&mut self) {
+        self.selected_bug_index = None;
+        self.selected_group.clear();
+        self.cycled_bug = None;
+    }
+
+    /// Returns the player's selectable bugs in the order the next-bug hotkey cycles through them:
+    /// those without a planned move first, then those nearest to the capture zone.
+    fn cycle_order(&self, my_team: Team, session_id: &Option<String>) -> Vec<usize> {
+        let mut bugs: Vec<(bool, f32, usize)> = self
+            .lobby
+            .game
+            .team_bug_indices(my_team)
+            .into_iter()
+            .filter(|bug_index| self.selectable(*bug_index, Some(my_team), session_id))
+            .filter_map(|bug_index| {
+                let (rigid_body, bug_data) = self.lobby.game.get_bug(bug_index)?;
+
+                Some((
+                    bug_data.impulse_intent().magnitude() > 0.0,
+                    rigid_body.translation().magnitude(),
+                    bug_index,
+                ))
+            })
+            .collect();
+
+        bugs.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+        bugs.into_iter()
+            .map(|(_, _, bug_index)| bug_index)
+            .collect()
+    }
+
+    /// Selects the next of the player's bugs for aiming, or the previous one when `reverse`, so
+    /// moves can be planned without picking out every bug with the pointer.
+    pub fn cycle_bug(&mut self, app_context: &AppContext, reverse: bool) {
+        let my_team = self.team_for(&app_context.session_id);
+
+        let Some(team) = my_team.filter(|_| self.can_plan(my_team)) else {
+            return;
+        };
+
+        if let Some((bug_index, impulse_intent)) = self.cycled_bug.take() {
+            if self.selected_bug_index == Some(bug_index) {
+                if let Some((_, bug_data)) = self.lobby.game.get_bug_mut(bug_index) {
+                    bug_data.set_impulse_intent(impulse_intent);
+                }
+            }
+        }
+
+        let order = self.cycle_order(team, &app_context.session_id);
+
+        let position = self
+            .selected_bug_index
+            .and_then(|selected| order.iter().position(|bug_index| *bug_index == selected));
+
+        let next = match (position, reverse) {
+            (Some(position), false) => order.get((position + 1) % order.len()),
+            (Some(position), true) => order.get((position + order.len() - 1) % order.len()),
+            (None, false) => order.first(),
+            (None, true) => order.last(),
+        };
+
+        let Some(&bug_index) = next else {
+            return;
+        };
+
+        self.deselect();
+        self.selected_bug_index = Some(bug_index);
+        self.smoothed_aim = None;
+        self.cycled_bug = self
+            .lobby
+            .game
+            .get_bug(bug_index)
+            .map(|(_, bug_data)| (bug_index, *bug_data.impulse_intent()));
+        self.cycle_frame = app_context.clock.frame();
+
+        app_context.audio_system.play_clip(ClipId::MageSelect);
+    }
+
+    /// Determines if a bug can be picked for aiming, being a friendly bug still able to move.
+    fn selectable(
+        &self,
+        bug_index: usize,
+        my_team: Option<Team>,
+        session_id: &Option<String>,
+    ) -> bool {
+        self.lobby
+            .game
+            .get_bug(bug_index)
+            .is_some_and(|(_, bug_data)| Some(*bug_data.team()) == my_team && bug_data.health() > 1)
+            && self.controls(session_id, bug_index)
+    }
+
+    /// Returns the bug under a point which can be picked for aiming.
+    fn selectable_bug(
+        &self,
+        point: Point2<f32>,
+        my_team: Option<Team>,
+        session_id: &Option<String>,
+    ) -> Option<usize> {
+        self.lobby
+            .game
+            .intersecting_bug(point)
+            .map(|(bug_index, _, _)| bug_index)
+            .filter(|bug_index| self.selectable(*bug_index, my_team, session_id))
+    }
+
+    /// Adds the friendly bug under a point to the group aimed along with the selected bug, or takes
+    /// it out again, returning whether a bug was added or taken out.
+    fn toggle_grouped(
+        &mut self,
+        point: Point2<f32>,
+        my_team: Option<Team>,
+        session_id: &Option<String>,
+    ) -> bool {
+        let Some(selected_bug_index) = self.selected_bug_index else {
+            return false;
+        };
+
+        let Some(bug_index) = self
+            .selectable_bug(point, my_team, session_id)
+            .filter(|bug_index| *bug_index != selected_bug_index)
+        else {
+            return false;
+        };
+
+        match self.selected_group.iter().position(|i| *i == bug_index) {
+            Some(position) => {
+                self.selected_group.remove(position);
+
+                if let Some((_, bug_data)) = self.lobby.game.get_bug_mut(bug_index) {
+                    bug_data.set_impulse_intent(Vector2::zeros());
+                }
+            }
+            None => self.selected_group.push(bug_index),
+        }
+
+        true
+    }
+
+    /// Selects the friendly bugs inside the dragged box once the pointer is released, the one
+    /// closest to where the drag ended leading the group so aiming carries on from there.
+    fn tick_selection_box(
+        &mut self,
+        pointer: &Pointer,
+        my_team: Option<Team>,
+        session_id: &Option<String>,
+    ) {
+        let Some(start) = self.selection_box else {
+            return;
+        };
+
+        if pointer.button {
+            return;
+        }
+
+        self.selection_box = None;
+
+        if (start.0 - pointer.location.0).abs() < SELECTION_BOX_MIN
+            && (start.1 - pointer.location.1).abs() < SELECTION_BOX_MIN
+            || !self.can_plan(my_team)
+        {
+            return;
+        }
+
+        let geometry = *self.lobby.game.geometry();
+        let to_local = |location: (i32, i32)| {
+            let (x, y) = tuple_as!(geometry.screen_to_local(tuple_as!(location, f64)), f32);
+            point![x, y]
+        };
+        let end = to_local(pointer.location);
+
+        let mut bug_indices: Vec<(usize, f32)> = self
+            .lobby
+            .game
+            .bugs_in_rect(to_local(start), end)
+            .into_iter()
+            .filter(|bug_index| self.selectable(*bug_index, my_team, session_id))
+            .filter_map(|bug_index| {
+                let (rigid_body, _) = self.lobby.game.get_bug(bug_index)?;
+
+                Some((
+                    bug_index,
+                    (rigid_body.translation() - end.coords).magnitude(),
+                ))
+            })
+            .collect();
+
+        bug_indices.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some(((lead, _), group)) = bug_indices.split_first() {
+            self.selected_bug_index = Some(*lead);
+            self.selected_group = group.iter().map(|(bug_index, _)| *bug_index).collect();
+            self.smoothed_aim = None;
+        }
+    }
+
+    pub fn controls(&self, session_id: &Option<String>, bug_index: usize) -> bool {
+        if self.lobby.is_local() {
+            true
+        } else if let Some(session_id) = session_id {
+            self.lobby
+                .players()
+                .get(session_id)
+                .is_some_and(|player| player.controls(bug_index))
+        } else {
+            false
+        }
+    }
+
+    /// Determines if the player can currently plan moves for their team.
+    /// First unseen hint whose mechanic the player is running into, for players of live games only.
+    fn due_hint(&self, my_team: Option<Team>) -> Option<Hint> {
+        if !self.can_plan(my_team) {
+            return None;
+        }
+
+        let turns_count = self.lobby.game.turns_count();
+
+        Hint::ALL.into_iter().find(|hint| {
+            !self.seen_hints.contains(hint)
+                && match hint {
+                    Hint::Aim => true,
+                    Hint::Zone => turns_count >= 1,
+                    Hint::Terrain => {
+                        turns_count >= 2 && self.lobby.game.iter_terrain().next().is_some()
+                    }
+                    Hint::Inspect => turns_count >= 3,
+                }
+        })
+    }
+
+    /// Shows the due hint as a button which dismisses it for good.
+    fn tick_hints(&mut self, app_context: &AppContext, my_team: Option<Team>) {
+        if let Some(UIEvent::ButtonClick(BUTTON_HINT, clip_id)) =
+            self.hint_interface.tick(&app_context.pointer)
+        {
+            app_context.audio_system.play_clip_option(clip_id);
+
+            if let Some(hint) = self.hint {
+                self.dismiss_hint(hint);
+            }
+        }
+
+        let due_hint = self.due_hint(my_team);
+
+        if due_hint != self.hint {
+            self.hint = due_hint;
+            self.hint_interface = Interface::new(
+                due_hint
+                    .map(|hint| {
+                        ButtonElement::new(
+                            ((384 - 256) / 2, 68),
+                            (256, 16),
+                            BUTTON_HINT,
+                            LabelTrim::Round,
+                            LabelTheme::Default,
+                            crate::app::ContentElement::Text(
+                                format!("{} (x)", hint.text()),
+                                Alignment::Center,
+                            ),
+                        )
+                        .boxed()
+                    })
+                    .into_iter()
+                    .collect(),
+            );
+        }
+    }
+
+    fn dismiss_hint(&mut self, hint: Hint) {
+        if !self.seen_hints.contains(&hint) {
+            self.seen_hints.push(hint);
+            dismiss_hint(hint);
+        }
+    }
+
+    fn can_plan(&self, my_team: Option<Team>) -> bool {
+        my_team.is_some()
+            && self.attract_input_frame.is_none()
+            && self.replay_turns.is_none()
+            && self.lobby.game.phase() == GamePhase::Planning
+    }
+
+    /// Applies a [`Formation`] to all of the player's healthy bugs, sending it right away online.
+    /// Shows ghost arrows of the moves the built-in bot would make for the player's bugs in local
+    /// games, without committing them, or hides them again.
+    fn toggle_suggestion(&mut self, app_context: &AppContext) {
+        if self.suggestion.take().is_some() {
+            return;
+        }
+
+        let my_team = self.team_for(&app_context.session_id);
+
+        let Some(team) = my_team.filter(|_| self.lobby.is_local() && self.can_plan(my_team)) else {
+            return;
+        };
+
+        let suggestion = ZoneBot::for_settings(&self.lobby.settings)
+            .suggest(&GameView::new(&self.lobby.game, team), SUGGESTED_MOVES)
+            .into_iter()
+            .filter(|(bug_index, _)| self.controls(&app_context.session_id, *bug_index))
+            .collect();
+
+        self.suggestion = Some((self.lobby.game.turns_count(), suggestion));
+    }
+
+    pub fn apply_formation(&mut self, app_context: &AppContext, formation: Formation) {
+        let my_team = self.team_for(&app_context.session_id);
+
+        let Some(team) = my_team.filter(|_| self.can_plan(my_team)) else {
+            return;
+        };
+
+        let impulse_intents: VecMap<usize, _> = formation
+            .plan(&GameView::new(&self.lobby.game, team))
+            .into_iter()
+            .filter(|(bug_index, _)| self.controls(&app_context.session_id, *bug_index))
+            .collect();
+
+        self.lobby
+            .game
+            .set_impulse_intents(team, impulse_intents.clone());
+        self.deselect();
+
+        app_context.audio_system.play_clip(ClipId::ClickForward);
+
+        if let LobbySort::Online(lobby_id) = *self.lobby.settings.sort() {
+            // Sends the whole plan as clamped by the bugs' stamina, replacing the commitment
+            let impulse_intents = self.planned_intents(app_context, team);
+
+            self.outbox.send(
+                &self.dispatcher,
+                lobby_id,
+                app_context.session_id.clone().unwrap_or_default(),
+                Turn {
+                    impulse_intents,
+                    timestamp: 0.0,
+                    index: self.lobby.game.turns_count(),
+                    ..Default::default()
+                },
+                app_context.clock.frame(),
+            );
+        }
+    }
+
+    /// Triggers particles, sounds and screen shake for the events the game emitted since the last frame.
+    fn consume_events(&mut self, app_context: &AppContext, my_team: Option<Team>) {
+        let frame = app_context.clock.frame();
+
+        let events = self.lobby.game.drain_events();
+
+        // Nothing done while warming up counts towards achievements
+        if let (Some(tracker), false) =
+            (&mut self.achievement_tracker, self.lobby.game.is_sandbox())
+        {
+            for achievement in tracker.observe(&self.lobby.game, &events) {
+                self.unlock(app_context, achievement);
+            }
+        }
+
+        let geometry = *self.lobby.game.geometry();
+
+        for event in events {
+            match event {
+                GameEvent::BugImpact {
+                    attacker,
+                    defender,
+                    position,
+                    speed,
+                } => {
+                    *self.bug_hits.entry(attacker).or_default() += 1;
+                    let mut line = self.bug_segments(attacker);
+                    line.push(FeedSegment::Text("bounced".to_string()));
+                    line.extend(self.bug_segments(defender));
+                    self.push_feed(line, frame);
+
+                    self.shake_frame = (self.lobby.game.ticks(), frame);
+                    app_context
+                        .audio_system
+                        .play_impact(ImpactMaterial::Bug, speed);
+
+                    if my_team.is_some()
+                        && my_team == self.lobby.game.get_bug(defender).map(|bug| *bug.1.team())
+                    {
+                        app_context.haptics.play(Haptic::Hit);
+                    }
+
+                    self.particle_system().spawn(10, |_| {
+                        let round = std::f64::consts::TAU * random();
+                        let x = geometry.to_screen_length(position.x);
+                        let y = geometry.to_screen_length(position.y);
+
+                        Particle::new(
+                            (x, y),
+                            (
+                                (random()) * round.cos() * 5.0,
+                                (random()) * round.sin() * 5.0,
+                            ),
+                            20 + (random() * 10.0) as Frame,
+                            crate::app::ParticleSort::Missile,
+                        )
+                    });
+                }
+                GameEvent::BugKnockedOut { bug, attacker } => {
+                    let line = match attacker {
+                        Some(attacker) => {
+                            let mut line = self.bug_segments(attacker);
+                            line.push(FeedSegment::Text("squashed".to_string()));
+                            line.extend(self.bug_segments(bug));
+                            line
+                        }
+                        None => {
+                            let mut line = self.bug_segments(bug);
+                            line.push(FeedSegment::Text("got squashed".to_string()));
+                            line
+                        }
+                    };
+
+                    self.push_feed(line, frame);
+                }
+                GameEvent::PropImpact { speed, .. } => {
+                    app_context
+                        .audio_system
+                        .play_impact(ImpactMaterial::Rock, speed);
+                }
+                GameEvent::WallImpact { speed, .. } => {
+                    app_context
+                        .audio_system
+                        .play_impact(ImpactMaterial::Wall, speed);
+                }
+                GameEvent::PropBreak { position, .. } => {
+                    self.shake_frame = (self.lobby.game.ticks(), frame);
+                    app_context.audio_system.play_clip(ClipId::CrackleIII);
+
+                    self.particle_system().spawn(24, |_| {
+                        let round = std::f64::consts::TAU * random();
+                        let x = geometry.to_screen_length(position.x);
+                        let y = geometry.to_screen_length(position.y);
+
+                        Particle::new(
+                            (x, y),
+                            (
+                                (random()) * round.cos() * 6.0,
+                                (random()) * round.sin() * 6.0,
+                            ),
+                            30 + (random() * 20.0) as Frame,
+                            ParticleSort::Diagonals,
+                        )
+                    });
+                }
+                GameEvent::ZoneScored { team, amount, .. } => {
+                    if let Some(team) = team {
+                        self.score_popups.push((
+                            format!(
+                                "+{amount} {}",
+                                match team {
+                                    Team::Red => "RED",
+                                    Team::Blue => "BLUE",
+                                }
+                            ),
+                            frame,
+                        ));
+
+                        app_context.audio_system.play_clip(
+                            if my_team.is_none() || my_team == Some(team) {
+                                ClipId::StarSparkle
+                            } else {
+                                ClipId::Shield
+                            },
+                        );
+                    }
+
+                    self.particle_system().spawn(100, |_| {
+                        let round = std::f64::consts::TAU * random();
+                        let x = round.cos() * geometry.to_screen_length(geometry.capture_radius);
+                        let y = round.sin() * geometry.to_screen_length(geometry.capture_radius);
+
+                        Particle::new(
+                            (x, y),
+                            (
+                                (random()) * round.cos() * 7.0,
+                                (random()) * round.sin() * 7.0,
+                            ),
+                            20 + (random() * 40.0) as Frame,
+                            crate::app::ParticleSort::Missile,
+                        )
+                    });
+                }
+                GameEvent::RingShrunk { inset } => {
+                    self.score_popups
+                        .push(("WALLS CLOSE IN".to_string(), frame));
+
+                    app_context.audio_system.play_clip(ClipId::Shield);
+
+                    let half_width = geometry.to_screen_length(geometry.width / 2.0 - inset);
+                    let half_height = geometry.to_screen_length(geometry.height / 2.0 - inset);
+
+                    // Dust is kicked up along the walls, blown into the ring
+                    self.particle_system().spawn(80, |i| {
+                        let along = random() * 2.0 - 1.0;
+                        let ((x, y), (vx, vy)) = match i % 4 {
+                            0 => ((along * half_width, -half_height), (0.0, 1.0)),
+                            1 => ((along * half_width, half_height), (0.0, -1.0)),
+                            2 => ((half_width, along * half_height), (-1.0, 0.0)),
+                            _ => ((-half_width, along * half_height), (1.0, 0.0)),
+                        };
+
+                        Particle::new(
+                            (x, y),
+                            (vx * random() * 3.0, vy * random() * 3.0),
+                            20 + (random() * 20.0) as Frame,
+                            ParticleSort::Diagonals,
+                        )
+                    });
+                }
+                GameEvent::RingOut { bug, team, points } => {
+                    self.score_popups.push((
+                        format!(
+                            "RING OUT {} {points}",
+                            match team {
+                                Team::Red => "RED",
+                                Team::Blue => "BLUE",
+                            }
+                        ),
+                        frame,
+                    ));
+
+                    app_context.audio_system.play_clip(
+                        if my_team.is_none() || my_team == Some(team) {
+                            ClipId::StarSparkle
+                        } else {
+                            ClipId::Shield
+                        },
+                    );
+
+                    let mut line = self.bug_segments(bug);
+                    line.push(FeedSegment::Text("was shoved out".to_string()));
+                    self.push_feed(line, frame);
+                }
+                GameEvent::GoalScored { team, goals } => {
+                    self.score_popups.push((
+                        format!(
+                            "GOAL {} {goals}",
+                            match team {
+                                Team::Red => "RED",
+                                Team::Blue => "BLUE",
+                            }
+                        ),
+                        frame,
+                    ));
+
+                    app_context.audio_system.play_clip(
+                        if my_team.is_none() || my_team == Some(team) {
+                            ClipId::StarSparkle
+                        } else {
+                            ClipId::Shield
+                        },
+                    );
+
+                    if let Some(centre) = self.lobby.game.goal(team) {
+                        let (x, y) = (
+                            geometry.to_screen_length(centre.x),
+                            geometry.to_screen_length(centre.y),
+                        );
+
+                        self.particle_system().spawn(60, |_| {
+                            let round = std::f64::consts::TAU * random();
+
+                            Particle::new(
+                                (x, y + (random() - 0.5) * 32.0),
+                                (round.cos() * 4.0, round.sin() * 4.0),
+                                20 + (random() * 40.0) as Frame,
+                                ParticleSort::Missile,
+                            )
+                        });
+                    }
+                }
+                GameEvent::PhaseChanged(phase) => {
+                    self.announce_phase(app_context, my_team, phase);
+
+                    if phase == GamePhase::Finished {
+                        self.submit_win(app_context);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Persists an achievement and, if it is new to this device, celebrates it with a toast.
+    fn unlock(&mut self, app_context: &AppContext, achievement: Achievement) {
+        if !unlock_achievement(achievement) {
+            return;
+        }
+
+        self.achievement_toasts
+            .push((achievement, app_context.clock.frame()));
+        self.unlocked_achievements.push(achievement);
+        app_context.audio_system.play_clip(ClipId::StarSparkle);
+
+        self.particle_system().spawn(32, |_| {
+            let round = std::f64::consts::TAU * random();
+
+            Particle::new(
+                ((random() - 0.5) * 128.0, -164.0),
+                (round.cos() * 2.0, round.sin() * 2.0),
+                30 + (random() * 30.0) as Frame,
+                ParticleSort::Shield,
+            )
+        });
+    }
+
+    /// Announces a change of the game's [`GamePhase`] with a banner and an audio cue.
+    fn announce_phase(
+        &mut self,
+        app_context: &AppContext,
+        my_team: Option<Team>,
+        phase: GamePhase,
+    ) {
+        let (text, clip_id) = match phase {
+            GamePhase::Planning if self.lobby.game.final_turn() => {
+                ("Final turn!", ClipId::ClickBack)
+            }
+            GamePhase::Planning => ("Plan your moves!", ClipId::ClickForward),
+            GamePhase::Resolving => ("Go!", ClipId::MageMove),
+            // Scoring is announced by the zone's own popup
+            GamePhase::Scoring => return,
+            GamePhase::Finished => match self.lobby.game.result() {
+                Some(GameResult::Win(winner)) => (
+                    match winner {
+                        Team::Red => "Red wins!",
+                        Team::Blue => "Blue wins!",
+                    },
+                    if my_team.is_none() || my_team == Some(winner) {
+                        ClipId::LevelSuccess
+                    } else {
+                        ClipId::LevelFailure
+                    },
+                ),
+                _ => ("Draw!", ClipId::LevelSuccess),
+            },
+        };
+
+        self.phase_banner = Some((text, app_context.clock.frame()));
+        app_context.audio_system.play_clip(clip_id);
+
+        if phase == GamePhase::Planning && my_team.is_some() {
+            app_context.haptics.play(Haptic::TurnStart);
+        }
+    }
+
+    /// Sends the replay of a win against the built-in bot to the server for verification.
+    fn submit_win(&self, app_context: &AppContext) {
+        let verifiable = self.lobby.has_ai()
+            && self.lobby.settings.level_seed() != 0
+            && self.attract_input_frame.is_none()
+            && self.replay_turns.is_none()
+            && self.lobby.game.result() == Some(GameResult::Win(Team::Red));
+
+        if !verifiable {
+            return;
+        }
+
+        if let (Some(session_id), Some(replay)) =
+            (&app_context.session_id, Replay::new(&self.lobby))
+        {
+            self.dispatcher.dispatch(
+                ApiClient::new(session_id.clone()).submit_replay(replay),
+                Message::Profile,
+            );
+        }
+    }
+
+    /// Draws the latency and sync state of an online game, for players to screenshot when their
+    /// moves do not seem to register.
+    fn draw_diagnostics(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        clock: &GameClock,
+    ) -> Result<(), PlatformError> {
+        let format_optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+
+        let mut lines =
+            vec![
+                format!(
+                    "RTT {}",
+                    format_optional(
+                        self.sync_diagnostics
+                            .round_trip
+                            .map(|round_trip| format!("{round_trip:.0}ms"))
+                    )
+                ),
+                format!(
+                    "Sync {}",
+                    format_optional(self.sync_diagnostics.last_sync.map(|last_sync| format!(
+                        "{:.1}s ago",
+                        GameClock::local_time() - last_sync
+                    )))
+                ),
+                format!(
+                    "Queued {} unsent {}",
+                    self.lobby.game.all_turns_count() - self.lobby.game.turns_count(),
+                    self.outbox.pending()
+                ),
+                format!(
+                    "Turn {} server {}",
+                    self.lobby.game.turns_count(),
+                    format_optional(
+                        self.sync_diagnostics
+                            .server_turn_count
+                            .map(|turn_count| turn_count.to_string())
+                    )
+                ),
+                format!(
+                    "Offset {}",
+                    format_optional(clock.server_offset().map(|offset| format!("{offset:+.3}s")))
+                ),
+            ];
+
+        // Cut so the panel stays within the arena
+        lines.extend(
+            log::recent(DIAGNOSTICS_LOG_LINES, Level::Debug)
+                .into_iter()
+                .map(|line| line.chars().take(DIAGNOSTICS_LOG_WIDTH).collect()),
+        );
+
+        let width = lines
+            .iter()
+            .map(|line| text_length(line))
+            .max()
+            .unwrap_or_default() as f64
+            + 8.0;
+
+        context.save();
+        context.set_global_alpha(0.7);
+        context.set_fill_style("#000000");
+        context.fill_rect(16.0, 120.0, width, lines.len() as f64 * 12.0 + 6.0);
+        context.restore();
+
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(context, atlas, 20.0, 124.0 + i as f64 * 12.0, line)?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws the capture gained in each scored turn as a strip of bars, marking the current turn.
+    fn draw_scoring_timeline(
+        &self,
+        context: &dyn Canvas,
+        dy: f64,
+        turn_count: usize,
+    ) -> Result<(), PlatformError> {
+        let scoring_history = self.lobby.game.scoring_history();
+        let turn_count = turn_count.max(scoring_history.len()).max(1);
+        let bar_width = (160.0 / turn_count as f64).clamp(1.0, 8.0).floor();
+        let dx = ((384.0 - bar_width * turn_count as f64) / 2.0).floor();
+
+        context.save();
+        context.set_fill_style("#002a2a");
+        context.fill_rect(
+            dx - 1.0,
+            dy - 9.0,
+            bar_width * turn_count as f64 + 2.0,
+            18.0,
+        );
+
+        for (i, tip) in scoring_history.iter().enumerate() {
+            let height = (tip.unsigned_abs() as f64 * 2.0).min(8.0);
+            let x = dx + i as f64 * bar_width;
+
+            if *tip > 0 {
+                context.set_fill_style("#C20005");
+                context.fill_rect(x, dy - height, (bar_width - 1.0).max(1.0), height);
+            } else if *tip < 0 {
+                context.set_fill_style("#00C2BD");
+                context.fill_rect(x, dy, (bar_width - 1.0).max(1.0), height);
+            }
+        }
+
+        context.set_fill_style("#ffffff");
+        context.fill_rect(
+            dx + scoring_history.len().min(turn_count) as f64 * bar_width - 1.0,
+            dy - 9.0,
+            1.0,
+            18.0,
+        );
+        context.restore();
+
+        Ok(())
+    }
+
+    /// Draws the capture bar filled in the colour of the leading team, with tick marks at every
+    /// quarter, an arrow spanning the capture gained at the last scoring, and a pulsing glow at the
+    /// end of each team which could capture the zone with its best turn so far.
+    fn draw_capture_bar(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        hud_layout: HudLayout,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+
+        let capture_progress = self.animated_capture_progress;
+        let half_length = HudLayout::BAR_LENGTH as f32 / 2.0;
+        let length = (capture_progress * half_length)
+            .abs()
+            .floor()
+            .clamp(0.0, half_length);
+        let length = (length as i32 / 2) * 2;
+        let length = if capture_progress < 0.0 {
+            -length
+        } else {
+            length
+        };
+
+        let (position, size) = hud_layout.segment(
+            hud_layout.capture,
+            -HudLayout::BAR_LENGTH / 2,
+            HudLayout::BAR_LENGTH,
+        );
+
+        draw_label(
+            context,
+            atlas,
+            position,
+            size,
+            "#002a2a",
+            &crate::app::ContentElement::None,
+            pointer,
+            frame,
+            &LabelTrim::Round,
+            false,
+        )?;
+
+        let (position, size) = hud_layout.segment(hud_layout.capture, length.min(0), length.abs());
+
+        draw_label(
+            context,
+            atlas,
+            position,
+            size,
+            if capture_progress > 0.0 {
+                "#C20005"
+            } else {
+                "#00C2BD"
+            },
+            &crate::app::ContentElement::None,
+            pointer,
+            frame,
+            &LabelTrim::Round,
+            false,
+        )?;
+
+        let half_length = half_length as f64;
+
+        context.save();
+
+        // Red fills towards the positive end of the bar, Blue towards the negative one
+        for (team, sign, color) in [(Team::Red, 1.0, "#ff5040"), (Team::Blue, -1.0, "#40c8ff")] {
+            if !self.lobby.game.capture_within_reach(team) {
+                continue;
+            }
+
+            let (x, y) = hud_layout.point(hud_layout.capture, sign * (half_length - 6.0));
+            let (width, height) = if hud_layout.vertical {
+                (12.0, 16.0)
+            } else {
+                (16.0, 12.0)
+            };
+
+            context.set_global_alpha(0.35 + 0.25 * (frame as f64 / 8.0).sin());
+            context.set_fill_style(color);
+            context.fill_rect(
+                (x - width / 2.0).floor(),
+                (y - height / 2.0).floor(),
+                width,
+                height,
+            );
+        }
+
+        context.set_global_alpha(0.6);
+        context.set_fill_style("#ffffff");
+
+        for quarter in [-3, -2, -1, 1, 2, 3] {
+            let (x, y) = hud_layout.point(hud_layout.capture, quarter as f64 * half_length / 4.0);
+            // The halfway marks reach across the whole bar, the others only halfway
+            let reach = if quarter % 2 == 0 { 4.0 } else { 2.0 };
+            let (width, height) = if hud_layout.vertical {
+                (reach * 2.0, 1.0)
+            } else {
+                (1.0, reach * 2.0)
+            };
+
+            context.fill_rect(
+                (x - width / 2.0).floor(),
+                (y - height / 2.0).floor(),
+                width,
+                height,
+            );
+        }
+
+        let momentum = self.lobby.game.capture_momentum() as f64;
+
+        if momentum != 0.0 {
+            let end = capture_progress.clamp(-1.0, 1.0) as f64 * half_length;
+            let start = (end - momentum * half_length).clamp(-half_length, half_length);
+            let (start_x, start_y) = hud_layout.point(hud_layout.capture, start);
+            let (end_x, end_y) = hud_layout.point(hud_layout.capture, end);
+            let head = if momentum > 0.0 { -3.0 } else { 3.0 };
+
+            context.set_global_alpha(1.0);
+            context.set_stroke_style("#ffffff");
+            context.set_line_width(1.0);
+            context.begin_path();
+            context.move_to(start_x, start_y);
+            context.line_to(end_x, end_y);
+
+            if hud_layout.vertical {
+                context.move_to(end_x - 3.0, end_y + head);
+                context.line_to(end_x, end_y);
+                context.line_to(end_x + 3.0, end_y + head);
+            } else {
+                context.move_to(end_x + head, end_y - 3.0);
+                context.line_to(end_x, end_y);
+                context.line_to(end_x + head, end_y + 3.0);
+            }
+
+            context.stroke();
+        }
+
+        context.restore();
+
+        Ok(())
+    }
+
+    /// Eases the displayed capture progress towards the game's, streaming particles while it catches up.
+    ///
+    /// The easing covers 5% of the distance every 60th of a second, whatever the frame rate.
+    fn animate_capture_progress(&mut self, clock: &GameClock) {
+        let ease = 1.0 - 0.95f64.powf(clock.delta() * FRAMES_PER_SECOND);
+
+        self.animated_capture_progress +=
+            (self.lobby.game.capture_progress() - self.animated_capture_progress) * ease as f32;
+
+        let capture_progress_unsigned_distance =
+            (self.animated_capture_progress - self.lobby.game.capture_progress()).abs() as f64;
+
+        if capture_progress_unsigned_distance > 0.05 || self.animated_capture_progress.abs() > 1.0 {
+            let particle_sort =
+                if self.animated_capture_progress < self.lobby.game.capture_progress() {
+                    ParticleSort::RedWin
+                } else {
+                    ParticleSort::BlueWin
+                };
+
+            let geometry = *self.lobby.game.geometry();
+
+            self.particle_system().spawn(
+                2 + (capture_progress_unsigned_distance * 6.0).round() as usize,
+                |_| {
+                    let round = std::f64::consts::TAU * random();
+                    let x = round.cos() * geometry.to_screen_length(geometry.capture_radius);
+                    let y = round.sin() * geometry.to_screen_length(geometry.capture_radius);
+
+                    Particle::new(
+                        (x, y),
+                        (
+                            (random())
+                                * round.cos()
+                                * 6.0
+                                * (1.0 + capture_progress_unsigned_distance * 4.0),
+                            (random())
+                                * round.sin()
+                                * 6.0
+                                * (1.0 + capture_progress_unsigned_distance * 4.0),
+                        ),
+                        20 + (random() * 40.0) as Frame,
+                        particle_sort,
+                    )
+                },
+            );
+        }
+    }
+
+    /// Display name of a bug, falling back to a generic one if it is not in the arena.
+    fn bug_name(&self, bug_index: usize) -> String {
+        self.lobby
+            .game
+            .get_bug(bug_index)
+            .map_or("A bug".to_string(), |(_, bug_data)| {
+                bug_data.name().to_string()
+            })
+    }
+
+    /// Icon and name of a bug for an event feed line.
+    /// Names the player controlling a team's first squad by [`Nickname`], if the team has any
+    /// players, leaving no more room between the clocks.
+    fn team_name(&self, team: Team) -> Option<String> {
+        let mut players: Vec<&Player> = self
+            .lobby
+            .players()
+            .values()
+            .filter(|player| player.team == team)
+            .collect();
+        players.sort_by_key(|player| player.squad.first().copied());
+
+        players.first().map(|player| player.nickname.to_string())
+    }
+
+    fn bug_segments(&self, bug_index: usize) -> Vec<FeedSegment> {
+        // Bugs shoved out of the ring are still named in the feed while they sit out
+        let bug_data = self
+            .lobby
+            .game
+            .get_bug(bug_index)
+            .map(|(_, bug_data)| bug_data)
+            .or_else(|| self.lobby.game.get_benched_bug(bug_index));
+
+        match bug_data {
+            Some(bug_data) => vec![
+                FeedSegment::Bug(*bug_data.sort(), *bug_data.team()),
+                FeedSegment::Text(bug_data.name().to_string()),
+            ],
+            None => vec![FeedSegment::Text(self.bug_name(bug_index))],
+        }
+    }
+
+    /// Adds a line to the event feed, dropping the oldest beyond [`EVENT_FEED_LINES`].
+    ///
+    /// Lines come from the game's events, so replays show the same feed as the original match.
+    fn push_feed(&mut self, line: Vec<FeedSegment>, frame: Frame) {
+        self.event_feed.push_back((line, frame));
+
+        if self.event_feed.len() > EVENT_FEED_LINES {
+            self.event_feed.pop_front();
+        }
+    }
+
+    /// Ticks left until the current turn's deadline, following the server's clock when it reported
+    /// the deadline of the turn being planned.
+    fn remaining_ticks(&self, clock: &GameClock) -> u64 {
+        match self.server_remaining_seconds(clock) {
+            Some(remaining) => (remaining * FRAMES_PER_SECOND).ceil() as Frame,
+            None => self.lobby.game.turn_tick_count() - self.lobby.game.turn_ticks(),
+        }
+    }
+
+    /// Seconds left until the server force-advances the turn being planned, if known.
+    fn server_remaining_seconds(&self, clock: &GameClock) -> Option<f64> {
+        let (turn_count, deadline) = self.server_deadline?;
+
+        if turn_count != self.lobby.game.turns_count()
+            || self.lobby.game.phase() != GamePhase::Planning
+        {
+            return None;
+        }
+
+        let deadline = clock.server_to_local(deadline)?;
+
+        Some((deadline - GameClock::local_time()).max(0.0))
+    }
+
+    /// Whether the turn deadline is close enough to warrant a reminder.
+    fn deadline_near(&self, clock: &GameClock) -> bool {
+        self.lobby.game.phase() == GamePhase::Planning
+            && self.remaining_ticks(clock) <= REMINDER_TICKS
+    }
+
+    /// Swaps the game for a sandbox until the server's warmup ends, unless it already has.
+    fn start_warmup(&mut self, app_context: &AppContext, turn_count: usize, warmup_until: f64) {
+        let Some(warmup_until) = app_context.clock.server_to_local(warmup_until) else {
+            return;
+        };
+
+        self.warmup_until = Some(warmup_until);
+
+        if turn_count == 0 && GameClock::local_time() < warmup_until {
+            self.lobby.game = Game::sandbox(&self.lobby.settings);
+            self.deselect();
+            self.phase_banner = Some(("Warmup!", app_context.clock.frame()));
+        }
+    }
+
+    /// Resets into the authoritative game once the warmup is over.
+    fn tick_warmup(&mut self, frame: Frame) {
+        let over = self
+            .warmup_until
+            .is_none_or(|warmup_until| GameClock::local_time() >= warmup_until);
+
+        if self.lobby.game.is_sandbox() && self.practice.is_none() && over {
+            self.lobby.reset_game();
+            self.deselect();
+            self.event_feed.clear();
+            self.bug_hits.clear();
+            self.phase_banner = Some(("Fight!", frame));
+        }
+    }
+
+    /// Reminds the player of the approaching deadline and, in online games, sends the currently
+    /// planned intents right before it so they are not lost to the server advancing the turn.
+    fn tick_deadline(&mut self, app_context: &AppContext, my_team: Team) {
+        let turn_index = self.lobby.game.turns_count();
+
+        if self.deadline_near(&app_context.clock) && self.reminded_turn != Some(turn_index) {
+            self.reminded_turn = Some(turn_index);
+            app_context.audio_system.play_clip(ClipId::ClickBack);
+            app_context.haptics.play(Haptic::Countdown);
+        }
+
+        let LobbySort::Online(lobby_id) = *self.lobby.settings.sort() else {
+            return;
+        };
+
+        if self.lobby.game.phase() == GamePhase::Planning
+            && self.remaining_ticks(&app_context.clock) <= REVEAL_TICKS
+        {
+            self.outbox
+                .reveal(&self.dispatcher, turn_index, app_context.clock.frame());
+        }
+
+        if self.remaining_ticks(&app_context.clock) > AUTO_COMMIT_TICKS
+            || self.committed_turn == Some(turn_index)
+        {
+            return;
+        }
+
+        self.committed_turn = Some(turn_index);
+
+        let impulse_intents = self.planned_intents(app_context, my_team);
+
+        if impulse_intents.is_empty() {
+            return;
+        }
+
+        if let Some(session_id) = &app_context.session_id {
+            self.outbox.send(
+                &self.dispatcher,
+                lobby_id,
+                session_id.clone(),
+                Turn {
+                    impulse_intents,
+                    timestamp: 0.0,
+                    index: turn_index,
+                    ..Default::default()
+                },
+                app_context.clock.frame(),
+            );
+        }
+    }
+
+    /// Returns the non-zero impulse intents of the bugs the local player controls.
+    fn planned_intents(
+        &self,
+        app_context: &AppContext,
+        my_team: Team,
+    ) -> VecMap<usize, Vector2<f32>> {
+        self.lobby
+            .game
+            .team_bug_indices(my_team)
+            .into_iter()
+            .filter(|bug_index| self.controls(&app_context.session_id, *bug_index))
+            .filter_map(|bug_index| {
+                self.lobby
+                    .game
+                    .get_bug(bug_index)
+                    .map(|(_, bug_data)| (bug_index, *bug_data.impulse_intent()))
+            })
+            .filter(|(_, impulse_intent)| impulse_intent.magnitude() > 0.0)
+            .collect()
+    }
+
+    /// Executes the local player's committed intents as soon as an online turn is due, instead of
+    /// idling until the server's [`Turn`] arrives. Other players' bugs are predicted to stand still.
+    fn predict_turn(&mut self, app_context: &AppContext, my_team: Team) {
+        let turn_index = self.lobby.game.turns_count();
+
+        if self.lobby.is_local()
+            || self.prediction.is_some()
+            || self.committed_turn != Some(turn_index)
+            || !self.lobby.game.awaiting_turn()
+        {
+            return;
+        }
+
+        let turn = Turn {
+            impulse_intents: self.planned_intents(app_context, my_team),
+            timestamp: 0.0,
+            index: turn_index,
+            ..Default::default()
+        };
+
+        self.prediction = Some(Prediction {
+            snapshot: self.lobby.game.clone(),
+            turn: turn.clone(),
+        });
+
+        // Nothing is queued while a turn is awaited
+        let _ = self.lobby.game.queue_turns(vec![turn]);
+    }
+
+    /// Checks server turns against a pending prediction, keeping the predicted simulation if it
+    /// matches and otherwise re-simulating from the snapshot up to the current tick.
+    ///
+    /// Returns the turns which follow the predicted one, still to be queued.
+    fn reconcile(&mut self, mut turns: Vec<Turn>) -> Vec<Turn> {
+        let Some(prediction) = self.prediction.take() else {
+            return turns;
+        };
+
+        let Some(position) = turns
+            .iter()
+            .position(|turn| turn.index == prediction.turn.index)
+        else {
+            self.prediction = Some(prediction);
+            return turns;
+        };
+
+        let mut turns = turns.split_off(position);
+        let turn = turns.remove(0);
+
+        if same_intents(&prediction.turn, &turn) {
+            self.lobby.game.amend_turn(turn);
+        } else {
+            let ticks = self.lobby.game.ticks();
+
+            // The snapshot was taken while the turn was awaited, with nothing queued
+            self.lobby.game = prediction.snapshot;
+            let _ = self.lobby.game.queue_turns(vec![turn]);
+
+            loop {
+                self.lobby.game.tick();
+
+                if self.lobby.game.ticks() >= ticks || self.lobby.game.awaiting_turn() {
+                    break;
+                }
+            }
+
+            // Effects of the mispredicted simulation have already played out
+            self.lobby.game.drain_events();
+        }
+
+        turns
+    }
+
+    /// Compares the capture progress and checksum with the server's once the same turn has been
+    /// resolved locally, adopting the server's capture progress on mismatch.
+    fn correct_drift(&mut self) {
+        let Some((turn_count, capture_progress, checksum)) = self.server_sync else {
+            return;
+        };
+
+        if self.lobby.game.is_sandbox() {
+            return;
+        }
+
+        if self.lobby.game.turns_count() != turn_count || !self.lobby.game.awaiting_turn() {
+            return;
+        }
+
+        self.server_sync = None;
+
+        if checksum.is_some_and(|checksum| checksum != self.lobby.game.checksum()) {
+            warn!("simulation diverged from the server at turn {turn_count}");
+        }
+
+        if (self.lobby.game.capture_progress() - capture_progress).abs() > 1e-3 {
+            warn!(
+                "capture progress drifted from {} to {} at turn {}",
+                capture_progress,
+                self.lobby.game.capture_progress(),
+                turn_count
+            );
+
+            self.lobby.game.sync_capture_progress(capture_progress);
+        }
+    }
+
+    /// Returns the aimed point in arena units, smoothed over frames if enabled.
+    fn aim_point(&mut self, point: Point2<f32>, aim_options: &AimOptions) -> Vector2<f32> {
+        let point = point.coords;
+
+        if !aim_options.smoothing {
+            return point;
+        }
+
+        let smoothed_aim = match self.smoothed_aim {
+            Some(smoothed_aim) => smoothed_aim + (point - smoothed_aim) * AIM_SMOOTHING,
+            None => point,
+        };
+
+        self.smoothed_aim = Some(smoothed_aim);
+
+        smoothed_aim
+    }
+
+    /// Finds the bug or prop under a point.
+    fn inspect(&self, point: Point2<f32>) -> Option<Inspected> {
+        if let Some((bug_index, _, _)) = self.lobby.game.intersecting_bug(point) {
+            Some(Inspected::Bug(bug_index))
+        } else {
+            self.lobby
+                .game
+                .intersecting_prop(point)
+                .map(|(prop_index, _, _)| Inspected::Prop(prop_index))
+        }
+    }
+
+    /// Lines of the info card of an inspected bug or prop, if it is still in the arena.
+    /// Describes the selected bug's aim: its strength relative to the cap and what it would hit first.
+    /// Lines of the aim tooltip of a bug for its current intent, and whether it runs into a wall
+    /// before coming to rest.
+    fn aim_tooltip(&self, bug_index: usize) -> Option<(Vec<String>, bool)> {
+        let (rigid_body, bug_data) = self.lobby.game.get_bug(bug_index)?;
+        let impulse_intent = *bug_data.impulse_intent();
+
+        let power = impulse_intent.magnitude() / MAX_IMPULSE_INTENT * 100.0;
+        let travel = self
+            .lobby
+            .game
+            .estimated_travel(bug_index, impulse_intent)
+            .unwrap_or_default();
+
+        let hits_wall = matches!(
+            self.lobby.game.raycast_obstruction(bug_index, impulse_intent),
+            Some((Obstruction::Wall, point))
+                if (point.coords - rigid_body.translation()).magnitude() < travel
+        );
+
+        let target = match self.lobby.game.first_hit(bug_index, impulse_intent) {
+            Some((index, _)) => match (
+                self.lobby.game.get_bug(index),
+                self.lobby.game.get_prop(index),
+            ) {
+                (Some((_, bug_data)), _) => format!("Hits {}", bug_data.name()),
+                (_, Some((_, prop_data))) => format!("Hits {:?}", prop_data.sort()),
+                _ => "Hits nothing".to_string(),
+            },
+            None => "Hits nothing".to_string(),
+        };
+
+        Some((
+            vec![
+                format!("Power {power:.0}%"),
+                format!("Travel {travel:.1}"),
+                target,
+            ],
+            hits_wall,
+        ))
+    }
+
+    fn inspection_lines(&self, inspected: Inspected) -> Option<Vec<String>> {
+        match inspected {
+            Inspected::Bug(bug_index) => {
+                let (_, bug_data) = self.lobby.game.get_bug(bug_index)?;
+                let sort = bug_data.sort();
+
+                let last_impulse = self
+                    .lobby
+                    .game
+                    .last_turn()
+                    .and_then(|turn| turn.impulse_intents.get(&bug_index))
+                    .map_or(0.0, |impulse_intent| impulse_intent.magnitude());
+
+                Some(vec![
+                    format!("{} No. {}", bug_data.name(), bug_data.name().number()),
+                    format!("{:?} ({:?})", sort, bug_data.team()),
+                    format!("Health {}/{}", bug_data.health(), sort.max_health()),
+                    format!("Mass {:.1}", sort.mass()),
+                    format!("Last move {:.1}", last_impulse),
+                ])
+            }
+            Inspected::Prop(prop_index) => {
+                let (_, prop_data) = self.lobby.game.get_prop(prop_index)?;
+                let sort = prop_data.sort();
+
+                let mut lines = vec![format!("{:?}", sort)];
+
+                if sort.mass() > 0.0 {
+                    lines.push(format!("Mass {:.1}", sort.mass()));
+                } else {
+                    lines.push("Immovable".to_string());
+                }
+
+                if *sort == PropSort::Rock {
+                    lines.push(format!("Cracked {:.0}%", prop_data.damage() * 100.0));
+                }
+
+                Some(lines)
+            }
+        }
+    }
+
+    /// Opens an info card on right-click or long-press, closing it on the next click.
+    fn tick_inspection(&mut self, point: Point2<f32>, pointer: &Pointer, clock: &GameClock) {
+        let frame = clock.frame();
+
+        if pointer.clicked() {
+            self.press = Some((frame, pointer.location));
+            self.inspection = None;
+        } else if !pointer.button {
+            self.press = None;
+        }
+
+        let long_pressed = self.press.is_some_and(|(press_frame, location)| {
+            clock.since(press_frame) >= LONG_PRESS_FRAMES
+                && (location.0 - pointer.location.0).abs() <= 4
+                && (location.1 - pointer.location.1).abs() <= 4
+        });
+
+        if pointer.alt_clicked() || long_pressed {
+            self.press = None;
+            self.inspection = self
+                .inspect(point)
+                .map(|inspected| (inspected, pointer.location, frame));
+        }
+    }
+
+    fn record_layer(&mut self, layer: &'static str, layer_start: &mut f64) {
+        let layer_end = now();
+        self.layer_timings.push((layer, layer_end - *layer_start));
+        *layer_start = layer_end;
+    }
+
+    pub(crate) fn print_turns(&self) {
+        let indexes: Vec<_> = self.lobby.turns().iter().map(|v| v.index).collect();
+        debug!("turns {indexes:?}");
+    }
+
+    /// Draws the arena, bugs and HUD of whichever game is current.
+    fn draw_scene(
+        &mut self,
+        context: &dyn Canvas,
+        _interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+
+        let my_team = self.team_for(&app_context.session_id);
+
+        let geometry = *self.lobby.game.geometry();
+        let point = tuple_as!(
+            geometry.screen_to_local(tuple_as!(pointer.location, f64)),
+            f32
+        );
+        let point = point![point.0, point.1];
+
+        let render_options = &app_context.render_options;
+
+        self.layer_timings.clear();
+        let mut layer_start = now();
+
+        if render_options.screen_shake() && self.shake_frame.1 > 0 {
+            let shake = app_context.clock.since(self.shake_frame.1);
+
+            if shake < 12 {
+                let magnitude = (12 - shake) as f64 / 6.0;
+
+                context.translate(
+                    ((frame % 3) as f64 - 1.0) * magnitude,
+                    ((frame / 3 % 3) as f64 - 1.0) * magnitude,
+                )?;
+            }
+        }
+
+        if render_options.high_contrast {
+            draw_solid_arena(context, &geometry, self.animated_capture_progress)?;
+        } else {
+            draw_image_centered(
+                context,
+                atlas,
+                360.0,
+                0.0,
+                360.0,
+                360.0,
+                geometry.view_centre().0,
+                geometry.view_centre().1,
+            )?;
+
+            app_context.renderer.draw_sand_circle(
+                app_context.atlas_context.as_ref(),
+                self.animated_capture_progress,
+                geometry.capture_radius * geometry.scale,
+            )?;
+
+            draw_image_centered(
+                context,
+                atlas,
+                360.0,
+                360.0,
+                360.0,
+                360.0,
+                geometry.view_centre().0,
+                geometry.view_centre().1,
+            )?;
+        }
+
+        self.record_layer("floor", &mut layer_start);
+
+        let zone_size = geometry.to_screen_length(geometry.capture_radius * 2.0) as u32;
+
+        if self.heatmap_layer.is_none() {
+            self.heatmap_layer = Some(HeatmapLayer::new(zone_size)?);
+        }
+
+        if let Some(heatmap_layer) = &mut self.heatmap_layer {
+            heatmap_layer.update(self.lobby.game.capture_heatmap())?;
+
+            context.draw_image_at(
+                heatmap_layer.canvas.as_ref(),
+                (geometry.view_centre().0 - zone_size as f64 / 2.0).floor(),
+                (geometry.view_centre().1 - zone_size as f64 / 2.0).floor(),
+            )?;
+        }
+
+        self.record_layer("heatmap", &mut layer_start);
+
+        for terrain in self.lobby.game.iter_terrain() {
+            draw_terrain(context, &geometry, terrain, render_options.high_contrast)?;
+        }
+
+        if self.lobby.game.mode() == GameMode::Sumo {
+            draw_ring(context, &geometry, self.lobby.game.ring_inset())?;
+        }
+
+        if self.lobby.game.has_mutator(Mutator::RingOut) {
+            draw_ring_out(context, &geometry)?;
+        }
+
+        for (scorer, defender) in [(Team::Red, Team::Blue), (Team::Blue, Team::Red)] {
+            if let Some(centre) = self.lobby.game.goal(scorer) {
+                draw_goal(context, &geometry, &centre, defender)?;
+            }
+        }
+
+        for bullseye in self
+            .practice
+            .iter()
+            .flat_map(|practice| practice.bullseyes())
+        {
+            draw_bullseye(context, &geometry, bullseye, frame)?;
+        }
+
+        self.record_layer("terrain", &mut layer_start);
+
+        let hud_layout = HudLayout::new(self.portrait);
+
+        {
+            let bar_width = HudLayout::BAR_LENGTH;
+            let length = bar_width as f64
+                - (self.lobby.game.turn_percentage_time() * bar_width as f64)
+                    .floor()
+                    .clamp(0.0, bar_width as f64);
+            let label_length = (length as i32 / 2) * 2;
+
+            let (position, size) = hud_layout.segment(hud_layout.timer, -bar_width / 2, bar_width);
+
+            draw_label(
+                context,
+                atlas,
+                position,
+                size,
+                "#002a2a",
+                &crate::app::ContentElement::None,
+                pointer,
+                frame,
+                &LabelTrim::Round,
+                false,
+            )?;
+
+            let (position, size) =
+                hud_layout.segment(hud_layout.timer, -label_length / 2, label_length);
+
+            draw_label(
+                context,
+                atlas,
+                position,
+                size,
+                if self.deadline_near(&app_context.clock) && frame % 16 < 8 {
+                    "#C20005"
+                } else {
+                    "#CA891B"
+                },
+                &crate::app::ContentElement::None,
+                pointer,
+                frame,
+                &LabelTrim::Round,
+                false,
+            )?;
+
+            let simulation_portion_length =
+                (1.0 - self.lobby.game.turn_percentage_time_half()) * bar_width as f64;
+            let simulation_portion_label_length =
+                ((simulation_portion_length as i32 / 2) * 2).min(label_length);
+
+            let (position, size) = hud_layout.segment(
+                hud_layout.timer,
+                -simulation_portion_label_length / 2,
+                simulation_portion_label_length,
+            );
+
+            draw_label(
+                context,
+                atlas,
+                position,
+                size,
+                "#fff",
+                &crate::app::ContentElement::None,
+                pointer,
+                frame,
+                &LabelTrim::Round,
+                false,
+            )?;
+        }
+
+        if self.attract_input_frame.is_none() && self.lobby.game.turns_count() > 0 {
+            self.replay_interface.draw(context, atlas, pointer, frame)?;
+        }
+
+        if let Some(replay_turns) = &self.replay_turns {
+            self.draw_scoring_timeline(
+                context,
+                48.0,
+                self.lobby.game.all_turns_count() + replay_turns.len(),
+            )?;
+        }
+
+        if self.replay_end_frame > 0 {
+            draw_text_centered(context, atlas, 384.0 / 2.0, 32.0, "End of replay")?;
+        }
+
+        if self.can_plan(my_team) {
+            self.formation_interface
+                .draw(context, atlas, pointer, frame)?;
+
+            if self.lobby.is_local() {
+                self.suggest_interface
+                    .draw(context, atlas, pointer, frame)?;
+            }
+        }
+
+        self.hint_interface.draw(context, atlas, pointer, frame)?;
+
+        if self.attract_input_frame.is_some() {
+            if frame % 60 < 40 {
+                draw_text_centered(context, atlas, 384.0 / 2.0, 348.0, "Demo - click to play")?;
+            }
+        } else if self.lobby.is_local() {
+            self.speed_interface.draw(context, atlas, pointer, frame)?;
+        }
+
+        self.draw_surrender(context, atlas, app_context)?;
+
+        let force_field = *self.lobby.game.force_field();
+
+        if force_field != ForceField::Calm {
+            let turn_ticks = self.lobby.game.turn_ticks();
+            let announcing = self.lobby.game.turns_count() > 0 && turn_ticks < 90;
+
+            let emphasis = if announcing {
+                ((turn_ticks as f64 / 6.0).sin() + 1.0) / 2.0
+            } else {
+                0.0
+            };
+
+            draw_force_field(context, &force_field, 92.0, 12.0, emphasis)?;
+
+            if announcing && frame % 16 < 8 {
+                draw_text_centered(
+                    context,
+                    atlas,
+                    384.0 / 2.0,
+                    32.0,
+                    match force_field {
+                        ForceField::Wind(_) => "Wind!",
+                        _ => "Current!",
+                    },
+                )?;
+            }
+        }
+
+        // The lobby's custom rules are shown until the first turn is played
+        if self.lobby.game.turns_count() == 0 && !self.lobby.game.mutators().is_empty() {
+            let badges: Vec<&str> = self
+                .lobby
+                .game
+                .mutators()
+                .iter()
+                .map(|mutator| mutator.badge())
+                .collect();
+            let badges = badges.join("  ");
+            let width = text_length(&badges) as i32 + 16;
+
+            draw_label(
+                context,
+                atlas,
+                ((384 - width) / 2, 28),
+                (width, 16),
+                "#7f3faa",
+                &crate::app::ContentElement::Text(badges, Alignment::Center),
+                pointer,
+                frame,
+                &LabelTrim::Round,
+                false,
+            )?;
+        }
+
+        if let Some((text, banner_frame)) = self.phase_banner {
+            if app_context.clock.since(banner_frame) < 90 {
+                draw_label(
+                    context,
+                    atlas,
+                    ((384 - 128) / 2, 48),
+                    (128, 16),
+                    "#002a2a",
+                    &crate::app::ContentElement::Text(text.to_string(), Alignment::Center),
+                    pointer,
+                    frame,
+                    &LabelTrim::Round,
+                    false,
+                )?;
+            }
+        }
+
+        if self.lobby.game.mode() == GameMode::Soccer {
+            // The score takes the place of the capture bar, along the top on portrait screens
+            let centre = if hud_layout.vertical {
+                (384 / 2, 8)
+            } else {
+                hud_layout.capture
+            };
+
+            draw_label(
+                context,
+                atlas,
+                (centre.0 - 56, centre.1 - 8),
+                (112, 16),
+                "#002a2a",
+                &crate::app::ContentElement::Text(
+                    format!(
+                        "RED {} - {} BLUE",
+                        self.lobby.game.goals(Team::Red),
+                        self.lobby.game.goals(Team::Blue)
+                    ),
+                    Alignment::Center,
+                ),
+                pointer,
+                frame,
+                &LabelTrim::Round,
+                false,
+            )?;
+        } else {
+            self.draw_capture_bar(context, atlas, hud_layout, app_context)?;
+        }
+
+        if let Some(max_turns) = self.lobby.game.max_turns() {
+            let turn = match self.lobby.game.phase() {
+                GamePhase::Planning => self.lobby.game.turns_count() + 1,
+                _ => self.lobby.game.turns_count(),
+            };
+
+            draw_text_centered(
+                context,
+                atlas,
+                384.0 / 2.0,
+                360.0 - 28.0,
+                &format!("Turn {}/{}", turn.min(max_turns), max_turns),
+            )?;
+        }
+
+        // Blue's clock is kept clear of the export button in the corner
+        for (team, x) in [(Team::Red, 8.0), (Team::Blue, 384.0 - 64.0)] {
+            let clock = match self.lobby.game.think_time_left(team) {
+                Some(think_time_left) => format_clock(think_time_left),
+                None => format_clock(self.lobby.game.think_time(team)),
+            };
+            let x = if team == Team::Blue {
+                x - text_length(&clock) as f64
+            } else {
+                x
+            };
+
+            draw_text(context, atlas, x, 360.0 - 20.0, &clock)?;
+
+            // Players are named beside their team's clock, towards the middle
+            if let Some(name) = self.team_name(team) {
+                let x = if team == Team::Blue {
+                    x - text_length(&name) as f64 - 8.0
+                } else {
+                    x + text_length(&clock) as f64 + 8.0
+                };
+
+                draw_text(context, atlas, x, 360.0 - 20.0, &name)?;
+            }
+
+            // Points for shoving rivals out of the ring sit above each team's clock
+            if self.lobby.game.has_mutator(Mutator::RingOut) {
+                let points = format!("OUT {}", self.lobby.game.ring_out_points(team));
+                let x = if team == Team::Blue {
+                    384.0 - 64.0 - text_length(&points) as f64
+                } else {
+                    8.0
+                };
+
+                draw_text(context, atlas, x, 360.0 - 32.0, &points)?;
+            }
+        }
+
+        if let (Some(warmup_until), true) = (self.warmup_until, self.lobby.game.is_sandbox()) {
+            let seconds = (warmup_until - GameClock::local_time()).max(0.0).ceil();
+
+            draw_text_centered(
+                context,
+                atlas,
+                384.0 / 2.0,
+                360.0 - 36.0,
+                &format!("Warmup {seconds}s"),
+            )?;
+        }
+
+        if self.outbox.unconfirmed(frame) && frame % 60 < 40 {
+            draw_text_centered(context, atlas, 384.0 / 2.0, 360.0 - 36.0, "Sending move...")?;
+        }
+
+        self.achievement_toasts.retain(|(_, toast_frame)| {
+            app_context.clock.since(*toast_frame) < ACHIEVEMENT_TOAST_FRAMES
+        });
+
+        if let Some((achievement, _)) = self.achievement_toasts.first() {
+            draw_label(
+                context,
+                atlas,
+                ((384 - 160) / 2, 4),
+                (160, 16),
+                "#7f3faa",
+                &crate::app::ContentElement::Text(
+                    format!("{} unlocked!", achievement.name()),
+                    Alignment::Center,
+                ),
+                pointer,
+                frame,
+                &LabelTrim::Glorious,
+                false,
+            )?;
+        }
+
+        self.score_popups
+            .retain(|(_, popup_frame)| app_context.clock.since(*popup_frame) < SCORE_POPUP_FRAMES);
+
+        for (text, popup_frame) in &self.score_popups {
+            let rise = app_context.clock.since(*popup_frame) as f64 / 2.0;
+
+            if app_context.clock.since(*popup_frame) < SCORE_POPUP_FRAMES - 12 || frame % 4 < 2 {
+                draw_text_centered(context, atlas, 384.0 / 2.0, 360.0 / 2.0 - 24.0 - rise, text)?;
+            }
+        }
+
+        self.event_feed
+            .retain(|(_, feed_frame)| app_context.clock.since(*feed_frame) < EVENT_FEED_FRAMES);
+
+        for (i, (line, feed_frame)) in self.event_feed.iter().enumerate() {
+            let remaining = EVENT_FEED_FRAMES - app_context.clock.since(*feed_frame);
+            let y = 24.0 + i as f64 * EVENT_FEED_LINE_HEIGHT;
+            let width = line
+                .iter()
+                .map(|segment| segment.width() + 3.0)
+                .sum::<f64>();
+            let mut x = 384.0 - 4.0 - width;
+
+            context.save();
+            context.set_global_alpha((remaining as f64 / EVENT_FEED_FADE_FRAMES as f64).min(1.0));
+
+            for segment in line {
+                match segment {
+                    FeedSegment::Bug(sort, team) => {
+                        draw_bug_icon(context, atlas, *sort, *team, x + 8.0, y + 4.0)?
+                    }
+                    FeedSegment::Text(text) => draw_text(context, atlas, x, y, text)?,
+                }
+
+                x += segment.width() + 3.0;
+            }
 
-            draw_image_centered(context, atlas, 0.0, 176.0, 32.0, 32.0, dx, dy)?;
+            context.restore();
+        }
+
+        if render_options.diagnostics && !self.lobby.is_local() {
+            self.draw_diagnostics(context, atlas, &app_context.clock)?;
+        }
+
+        if self.lobby.game.phase() == GamePhase::Finished {
+            self.draw_scoring_timeline(context, 360.0 / 2.0 + 84.0, self.lobby.game.turns_count())?;
+
+            draw_text_centered(
+                context,
+                atlas,
+                384.0 / 2.0,
+                360.0 / 2.0 + 48.0,
+                "Think time",
+            )?;
+            draw_text_centered(
+                context,
+                atlas,
+                384.0 / 2.0,
+                360.0 / 2.0 + 62.0,
+                &format!(
+                    "Red {}  Blue {}",
+                    format_clock(self.lobby.game.think_time(Team::Red)),
+                    format_clock(self.lobby.game.think_time(Team::Blue))
+                ),
+            )?;
+        }
+
+        self.record_layer("hud", &mut layer_start);
+
+        {
+            context.save();
+            context.translate(geometry.view_centre().0, geometry.view_centre().1)?;
+            self.particle_system().tick_and_draw(
+                app_context.renderer.as_ref(),
+                context,
+                atlas,
+                frame,
+                render_options,
+            )?;
+            context.restore();
+        }
+
+        self.record_layer("particles", &mut layer_start);
+
+        // Under fog of war, players only see rival bugs near their own
+        let hidden = |rigid_body: &RigidBody, bug_data: &BugData| match my_team {
+            Some(my_team) => {
+                *bug_data.team() != my_team
+                    && !self
+                        .lobby
+                        .game
+                        .visible_to(my_team, rigid_body.translation())
+            }
+            None => false,
+        };
+
+        let hovered_bug = self
+            .lobby
+            .game
+            .intersecting_bug(point)
+            .filter(|(_, rigid_body, bug_data)| !hidden(rigid_body, bug_data));
+
+        if let Some((_, rigid_body, _bug_data)) = hovered_bug {
+            let (dx, dy) = geometry.local_to_screen(rigid_body.translation());
+
+            if render_options.high_contrast {
+                draw_selection_ring(
+                    context,
+                    dx,
+                    dy,
+                    render_options.selection_ring_width(),
+                    "#ffff00",
+                )?;
+            } else {
+                draw_image_centered(context, atlas, 0.0, 176.0, 32.0, 32.0, dx, dy)?;
+            }
+        }
+
+        let corpse_lifetime = (CORPSE_TURNS as u64 * self.lobby.game.turn_tick_count()) as f64;
+
+        for (index, corpse) in self.lobby.game.corpses().iter().enumerate() {
+            let age_ticks = self.lobby.game.ticks().saturating_sub(corpse.ticks);
+
+            draw_corpse(
+                context,
+                atlas,
+                &geometry,
+                corpse,
+                age_ticks,
+                age_ticks as f64 / corpse_lifetime,
+                index,
+            )?;
         }
 
         for (index, prop) in self.lobby.game.iter_props().enumerate() {
-            draw_prop(context, atlas, prop, index, frame)?;
+            draw_prop(context, atlas, &geometry, prop, index, frame)?;
+        }
+
+        if let Some(ball) = self.lobby.game.ball() {
+            draw_ball(context, &geometry, ball)?;
+        }
+
+        if render_options.trails() {
+            for (bug_index, trail) in &self.bug_trails {
+                if let Some((rigid_body, bug_data)) = self.lobby.game.get_bug(*bug_index) {
+                    if !hidden(rigid_body, bug_data) {
+                        draw_bug_trail(context, &geometry, trail, *bug_data.team())?;
+                    }
+                }
+            }
+        }
+
+        for (index, bug) in self.lobby.game.iter_bugs().enumerate() {
+            if hidden(bug.0, bug.1) {
+                continue;
+            }
+
+            draw_bug(context, atlas, &geometry, bug, index, frame)?;
+
+            if my_team == Some(*bug.1.team()) {
+                draw_bug_impulse(context, atlas, &geometry, bug, index, frame)?;
+            }
+        }
+
+        if render_options.intent_arrows && (my_team.is_none() || self.replay_turns.is_some()) {
+            if let Some((_, start_tick, arrows)) = &self.resolved_intents {
+                let age = self.lobby.game.ticks().saturating_sub(*start_tick);
+
+                if age < INTENT_ARROW_TICKS {
+                    let alpha = 1.0 - age as f64 / INTENT_ARROW_TICKS as f64;
+
+                    for (origin, intent, team) in arrows {
+                        draw_intent_arrow(context, &geometry, origin, intent, *team, alpha)?;
+                    }
+                }
+            }
         }
 
-        for (index, bug) in self.lobby.game.iter_bugs().enumerate() {
-            draw_bug(context, atlas, bug, index, frame)?;
+        if let Some((_, suggestion)) = &self.suggestion {
+            // Pulses so a suggestion does not read as a committed move
+            let alpha = 0.5 + 0.25 * (frame as f64 / 8.0).sin();
 
-            if my_team == Some(*bug.1.team()) {
-                draw_bug_impulse(context, atlas, bug, index, frame)?;
+            for (bug_index, impulse_intent) in suggestion {
+                if let Some((rigid_body, bug_data)) = self.lobby.game.get_bug(*bug_index) {
+                    draw_intent_arrow(
+                        context,
+                        &geometry,
+                        rigid_body.translation(),
+                        impulse_intent,
+                        *bug_data.team(),
+                        alpha,
+                    )?;
+                }
             }
         }
 
         for (_index, (rigid_body, bug_data)) in self.lobby.game.iter_bugs().enumerate() {
-            let (dx, dy) = local_to_screen(rigid_body.translation());
+            let (dx, dy) = geometry.local_to_screen(rigid_body.translation());
 
             if my_team == Some(*bug_data.team()) {
                 match bug_data.team() {
@@ -317,16 +3285,114 @@ impl State for GameState {
             }
         }
 
+        let selected_bugs = self
+            .selected_bug_index
+            .into_iter()
+            .chain(self.selected_group.iter().copied());
+
+        for selected_bug_index in selected_bugs {
+            if let Some((rigid_body, bug_data)) = self.lobby.game.get_bug(selected_bug_index) {
+                let (dx, dy) = geometry.local_to_screen(rigid_body.translation());
+
+                if render_options.high_contrast {
+                    draw_selection_ring(
+                        context,
+                        dx,
+                        dy,
+                        render_options.selection_ring_width(),
+                        "#ffffff",
+                    )?;
+                } else {
+                    draw_image_centered(context, atlas, 0.0, 176.0, 32.0, 32.0, dx, dy)?;
+                }
+
+                let color = match bug_data.team() {
+                    Team::Red => "#ff5040",
+                    Team::Blue => "#40c8ff",
+                };
+
+                draw_selection_pulse(context, dx, dy, frame, color)?;
+
+                let since_cycle = app_context.clock.since(self.cycle_frame);
+
+                if self.cycled_bug.is_some() && since_cycle < CYCLE_FOCUS_FRAMES {
+                    draw_focus_ring(
+                        context,
+                        dx,
+                        dy,
+                        since_cycle as f64 / CYCLE_FOCUS_FRAMES as f64,
+                        color,
+                    )?;
+                }
+            }
+        }
+
+        if let Some(start) = self.selection_box.filter(|_| pointer.button) {
+            draw_selection_box(context, start, pointer.location)?;
+        }
+
         if let Some(selected_bug_index) = self.selected_bug_index {
-            if let Some((rigid_body, _bug_data)) = self.lobby.game.get_bug(selected_bug_index) {
-                let (dx, dy) = local_to_screen(rigid_body.translation());
+            context.save();
+            context.set_fill_style("#ffffff");
 
-                draw_image_centered(context, atlas, 0.0, 176.0, 32.0, 32.0, dx, dy)?;
+            for (i, (x, y)) in self.aim_trail.iter().enumerate() {
+                context.set_global_alpha((i + 1) as f64 / (AIM_TRAIL_LENGTH + 1) as f64 * 0.5);
+                context.fill_rect(*x as f64 - 1.0, *y as f64 - 1.0, 2.0, 2.0);
+            }
+
+            context.restore();
+
+            if let Some((obstruction, point)) = self
+                .lobby
+                .game
+                .get_bug(selected_bug_index)
+                .and_then(|(_, bug_data)| {
+                    self.lobby
+                        .game
+                        .raycast_obstruction(selected_bug_index, *bug_data.impulse_intent())
+                })
+            {
+                let (dx, dy) = geometry.local_to_screen(&point.coords);
+
+                draw_obstruction_marker(context, obstruction, dx, dy)?;
             }
         }
 
-        match (self.lobby.game.turn_tick_count() as i64 - self.lobby.game.turn_ticks() as i64) / 60
-        {
+        // Aiming shows the tooltip of the selected bug, planning that of any hovered friendly one
+        let aim_tooltip_bug = self.selected_bug_index.or(hovered_bug
+            .filter(|(_, _, bug_data)| self.can_plan(my_team) && my_team == Some(*bug_data.team()))
+            .map(|(bug_index, _, _)| bug_index));
+
+        if let Some(bug_index) = aim_tooltip_bug {
+            if let (Some((rigid_body, _)), Some((lines, hits_wall))) = (
+                self.lobby.game.get_bug(bug_index),
+                self.aim_tooltip(bug_index),
+            ) {
+                let (dx, dy) = geometry.local_to_screen(rigid_body.translation());
+
+                draw_aim_tooltip(context, atlas, &lines, hits_wall, dx, dy, pointer, frame)?;
+            }
+        }
+
+        if let Some((_, rigid_body, bug_data)) = hovered_bug {
+            if my_team != Some(*bug_data.team()) {
+                let (dx, dy) = geometry.local_to_screen(rigid_body.translation());
+
+                draw_bug_tooltip(context, atlas, bug_data, dx, dy, pointer, frame)?;
+            }
+        }
+
+        if let Some((inspected, anchor, inspection_frame)) = self.inspection {
+            if app_context.clock.since(inspection_frame) < INSPECTION_FRAMES {
+                if let Some(lines) = self.inspection_lines(inspected) {
+                    draw_info_card(context, atlas, &lines, anchor, pointer, frame)?;
+                }
+            }
+        }
+
+        self.record_layer("bugs", &mut layer_start);
+
+        match self.remaining_ticks(&app_context.clock) / 60 {
             2 => draw_image_centered(
                 context,
                 atlas,
@@ -439,178 +3505,333 @@ impl State for GameState {
         //     }
         // }
 
-        // console::log_1(&format!("{:?}", self.lobby.game.get_bug(0)).into());
+        Ok(())
+    }
+}
 
-        if self.lobby.game.turn_ticks() == self.lobby.game.turn_tick_count_half() {
-            self.particle_system().spawn(100, |_| {
-                let round = std::f64::consts::TAU * Math::random();
-                let x = round.cos() * 4.0 * 16.0;
-                let y = round.sin() * 4.0 * 16.0;
+impl State for GameState {
+    fn draw(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        match self.with_replay_game(|state| {
+            state.draw_scene(context, interface_context, atlas, app_context)
+        }) {
+            Some(result) => {
+                result?;
+                self.draw_turn_replay(context, atlas, app_context)?;
+            }
+            None => {
+                self.draw_scene(context, interface_context, atlas, app_context)?;
 
-                Particle::new(
-                    (x, y),
-                    (
-                        (Math::random()) * round.cos() * 7.0,
-                        (Math::random()) * round.sin() * 7.0,
-                    ),
-                    20 + (Math::random() * 40.0) as usize,
-                    crate::app::ParticleSort::Missile,
-                )
-            });
+                if self.can_replay_turn() {
+                    self.turn_replay_interface.draw(
+                        context,
+                        atlas,
+                        &app_context.pointer,
+                        app_context.clock.frame(),
+                    )?;
+                }
+            }
         }
 
-        let capture_progress_unsigned_distance =
-            (self.animated_capture_progress - self.lobby.game.capture_progress()).abs() as f64;
-
-        if capture_progress_unsigned_distance > 0.05 || self.animated_capture_progress.abs() > 1.0 {
-            let particle_sort =
-                if self.animated_capture_progress < self.lobby.game.capture_progress() {
-                    ParticleSort::RedWin
-                } else {
-                    ParticleSort::BlueWin
-                };
-
-            self.particle_system().spawn(
-                2 + (capture_progress_unsigned_distance * 6.0).round() as usize,
-                |_| {
-                    let round = std::f64::consts::TAU * Math::random();
-                    let x = round.cos() * 4.0 * 16.0;
-                    let y = round.sin() * 4.0 * 16.0;
-
-                    Particle::new(
-                        (x, y),
-                        (
-                            (Math::random())
-                                * round.cos()
-                                * 6.0
-                                * (1.0 + capture_progress_unsigned_distance * 4.0),
-                            (Math::random())
-                                * round.sin()
-                                * 6.0
-                                * (1.0 + capture_progress_unsigned_distance * 4.0),
-                        ),
-                        20 + (Math::random() * 40.0) as usize,
-                        particle_sort,
-                    )
-                },
-            );
+        if let Some(waiting_room) = &self.waiting_room {
+            waiting_room.draw(context, atlas, &self.lobby, app_context)?;
         }
 
-        for ((a, b), data) in self.lobby.game.bug_impacts() {
-            self.particle_system().spawn(10, |_| {
-                let round = std::f64::consts::TAU * Math::random();
-                let x = data.x as f64 * 16.0;
-                let y = data.y as f64 * 16.0;
-
-                Particle::new(
-                    (x, y),
-                    (
-                        (Math::random()) * round.cos() * 5.0,
-                        (Math::random()) * round.sin() * 5.0,
-                    ),
-                    20 + (Math::random() * 10.0) as usize,
-                    crate::app::ParticleSort::Missile,
-                )
-            });
+        if let Some(practice) = &self.practice {
+            self.draw_practice(context, atlas, practice, app_context)?;
         }
 
-        Ok(())
+        self.draw_connection_warning(context, atlas, app_context)
     }
 
-    fn tick(
-        &mut self,
-        _text_input: &HtmlInputElement,
-        app_context: &AppContext,
-    ) -> Option<StateSort> {
-        let frame = app_context.frame;
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
+        let frame = app_context.clock.frame();
         let pointer = &app_context.pointer;
 
-        let point = tuple_as!(screen_to_local(tuple_as!(pointer.location, f64)), f32);
+        let geometry = *self.lobby.game.geometry();
+        let point = tuple_as!(
+            geometry.screen_to_local(tuple_as!(pointer.location, f64)),
+            f32
+        );
         let point = point![point.0, point.1];
 
         let my_team = self.team_for(&app_context.session_id);
 
-        let mut message_pool = self.message_pool.borrow_mut();
+        if self.portrait != app_context.canvas_settings.orientation {
+            self.portrait = app_context.canvas_settings.orientation;
+            self.speed_interface = GameState::create_speed_interface(self.portrait, self.speed);
+            self.formation_interface = GameState::create_formation_interface(self.portrait);
+            self.suggest_interface = GameState::create_suggest_interface(self.portrait);
+        }
 
-        for message in &message_pool.messages {
-            match message {
-                Message::Ok => (),
-                Message::Lobby(lobby) => {
-                    self.lobby = *lobby.clone();
-                }
-                Message::Lobbies(_lobbies) => (),
-                Message::LobbyError(_) => (),
-                Message::Move(_) => (),
-                Message::TurnSync(turns) => {
-                    self.lobby.game.queue_turns(turns.clone());
+        self.poll(app_context);
+
+        if self.return_to_menu {
+            return Some(StateSort::MainMenu(
+                match (self.slot_taken, self.lobby.settings.sort()) {
+                    (true, LobbySort::Online(lobby_id)) => MainMenuState::missed_lobby(*lobby_id),
+                    _ => MainMenuState::default(),
+                },
+            ));
+        }
+
+        // Nothing happens in the arena until everyone joined and readied up
+        if let Some(waiting_room) = &mut self.waiting_room {
+            match waiting_room.tick(&self.lobby, app_context) {
+                Some(WaitingRoomAction::Send(message)) => self.send_to_lobby(app_context, message),
+                Some(WaitingRoomAction::Leave) => {
+                    // Frees the slot right away, rather than once the server misses heartbeats
+                    self.send_to_lobby(app_context, Message::Leave);
+
+                    return Some(StateSort::MainMenu(MainMenuState::default()));
                 }
+                None => (),
             }
+
+            return None;
         }
 
-        message_pool.clear();
+        self.tick_warmup(frame);
 
-        if message_pool.available(frame) {
-            if let LobbySort::Online(lobby_id) = self.lobby.settings.sort() {
-                let _ = fetch(&request_turns_since(
-                    *lobby_id,
-                    self.lobby.game.all_turns_count(),
-                ))
-                .then(&self.message_closure);
+        if let Some(attract_input_frame) = self.attract_input_frame {
+            if app_context.last_input_frame != attract_input_frame {
+                return Some(StateSort::MainMenu(MainMenuState::default()));
+            }
+        }
+
+        let replaying = self.tick_turn_replay(app_context);
+
+        if self.can_replay_turn() {
+            if let Some(UIEvent::ButtonClick(BUTTON_REPLAY_TURN, clip_id)) =
+                self.turn_replay_interface.tick(pointer)
+            {
+                app_context.audio_system.play_clip_option(clip_id);
+                self.start_turn_replay();
+            }
+        }
+
+        if let Some(SurrenderStatus::Available | SurrenderStatus::Asked(_)) =
+            self.surrender_status(app_context)
+        {
+            if let Some(UIEvent::ButtonClick(BUTTON_SURRENDER, clip_id)) =
+                self.surrender_interface.tick(pointer)
+            {
+                app_context.audio_system.play_clip_option(clip_id);
+                self.vote_surrender(app_context);
+            }
+        }
+
+        if self.lobby.is_local() && self.attract_input_frame.is_none() {
+            if let Some(UIEvent::ButtonClick(value, clip_id)) = self.speed_interface.tick(pointer) {
+                app_context.audio_system.play_clip_option(clip_id);
+                self.speed = GameSpeed::from_value(value);
+            }
+        }
+
+        if self.attract_input_frame.is_none() && self.lobby.game.turns_count() > 0 {
+            if let Some(UIEvent::ButtonClick(BUTTON_EXPORT, clip_id)) =
+                self.replay_interface.tick(pointer)
+            {
+                app_context.audio_system.play_clip_option(clip_id);
+                self.export_replay();
             }
+        }
 
-            message_pool.block(frame);
+        if let Some(practice_interface) = &mut self.practice_interface {
+            if let Some(UIEvent::ButtonClick(value, clip_id)) = practice_interface.tick(pointer) {
+                app_context.audio_system.play_clip_option(clip_id);
+
+                match value {
+                    BUTTON_PRACTICE_RETRY => {
+                        return Some(StateSort::Game(GameState::target_practice()))
+                    }
+                    BUTTON_PRACTICE_LEAVE => {
+                        return Some(StateSort::MainMenu(MainMenuState::default()))
+                    }
+                    _ => (),
+                }
+            }
         }
 
-        if self.animated_capture_progress.abs() > 1.0 {
+        if self.lobby.game.result().is_some() {
             if self.capture_frame == 0 {
                 self.capture_frame = frame;
-            } else if frame - self.capture_frame > 180 {
-                return Some(StateSort::MainMenu(MainMenuState::default()));
+            } else if app_context.clock.since(self.capture_frame) > RESULTS_DELAY_FRAMES {
+                if self.attract_input_frame.is_some() {
+                    return Some(StateSort::MainMenu(MainMenuState::default()));
+                }
+
+                return Some(StateSort::Results(ResultsState::new(
+                    self.lobby.clone(),
+                    my_team,
+                    std::mem::take(&mut self.bug_hits),
+                    std::mem::take(&mut self.unlocked_achievements),
+                )));
+            }
+        }
+
+        if self.can_plan(my_team) && !replaying {
+            if let Some(UIEvent::ButtonClick(value, _)) = self.formation_interface.tick(pointer) {
+                if let Some(formation) = value
+                    .checked_sub(BUTTON_FORMATION)
+                    .and_then(|i| Formation::ALL.get(i))
+                {
+                    self.apply_formation(app_context, *formation);
+                }
+            }
+
+            if self.lobby.is_local() {
+                if let Some(UIEvent::ButtonClick(BUTTON_SUGGEST, clip_id)) =
+                    self.suggest_interface.tick(pointer)
+                {
+                    app_context.audio_system.play_clip_option(clip_id);
+                    self.toggle_suggestion(app_context);
+                }
+            }
+        }
+
+        // Suggestions are for the turn they were asked in only
+        if self
+            .suggestion
+            .as_ref()
+            .is_some_and(|(turn, _)| *turn != self.lobby.game.turns_count())
+        {
+            self.suggestion = None;
+        }
+
+        if self.selected_bug_index.is_some() {
+            if self.aim_trail.back() != Some(&pointer.location) {
+                self.aim_trail.push_back(pointer.location);
+            }
+
+            if self.aim_trail.len() > AIM_TRAIL_LENGTH {
+                self.aim_trail.pop_front();
             }
+        } else {
+            self.aim_trail.clear();
         }
 
         if let Some(bug_index) = self.selected_bug_index {
-            if let Some((rigid_body, bug_data)) = self.lobby.game.get_bug_mut(bug_index) {
-                if Some(*bug_data.team()) == my_team {
-                    let impulse_intent = vector![point.x, point.y] - rigid_body.translation();
-                    bug_data.set_impulse_intent(impulse_intent);
+            let aim_point = self.aim_point(point, &app_context.aim_options);
+
+            let impulse_intent = self
+                .lobby
+                .game
+                .get_bug(bug_index)
+                .filter(|(_, bug_data)| Some(*bug_data.team()) == my_team)
+                .map(|(rigid_body, _)| {
+                    aim_intent(
+                        aim_point - rigid_body.translation(),
+                        &app_context.aim_options,
+                        pointer.modifier,
+                    )
+                });
+
+            // The group shares the selected bug's impulse, each bug flicked from where it stands
+            if let Some(impulse_intent) = impulse_intent {
+                for bug_index in
+                    std::iter::once(bug_index).chain(self.selected_group.iter().copied())
+                {
+                    if let Some((_, bug_data)) = self.lobby.game.get_bug_mut(bug_index) {
+                        bug_data.set_impulse_intent(impulse_intent);
+                    }
                 }
             }
         }
 
-        if pointer.clicked() {
+        if let Some(my_team) = my_team {
+            if self.attract_input_frame.is_none()
+                && self.replay_turns.is_none()
+                && !self.lobby.game.is_sandbox()
+            {
+                if self.achievement_tracker.is_none() {
+                    self.achievement_tracker = Some(AchievementTracker::new(my_team));
+                }
+
+                self.tick_deadline(app_context, my_team);
+                self.predict_turn(app_context, my_team);
+            }
+        }
+
+        self.tick_hints(app_context, my_team);
+
+        // Shift-clicking another friendly bug while aiming groups it instead of launching
+        let grouped = pointer.clicked()
+            && pointer.modifier
+            && !replaying
+            && self.toggle_grouped(point, my_team, &app_context.session_id);
+
+        if pointer.clicked() && !replaying && !grouped {
+            let aiming = self.selected_bug_index.is_some();
+
             if let Some(bug_index) = self.selected_bug_index {
-                if let Some((_rigid_body, bug_data)) = self.lobby.game.get_bug_mut(bug_index) {
-                    if let LobbySort::Online(lobby_id) = self.lobby.settings.sort() {
-                        send_message(
-                            *lobby_id,
+                // Launching a bug is what the aiming hint teaches
+                self.dismiss_hint(Hint::Aim);
+
+                let think_time = self.lobby.game.turn_ticks() as f32 / 60.0;
+
+                let impulse_intents: VecMap<usize, Vector2<f32>> = std::iter::once(bug_index)
+                    .chain(self.selected_group.iter().copied())
+                    .filter_map(|bug_index| {
+                        self.lobby
+                            .game
+                            .get_bug(bug_index)
+                            .map(|(_, bug_data)| (bug_index, *bug_data.impulse_intent()))
+                    })
+                    .collect();
+
+                if let Some(practice) = &mut self.practice {
+                    practice.flick(&mut self.lobby.game, impulse_intents);
+                } else if self.lobby.game.is_sandbox() {
+                    for (bug_index, impulse_intent) in impulse_intents {
+                        self.lobby.game.flick(bug_index, impulse_intent);
+                    }
+                } else if let Some((_rigid_body, bug_data)) = self.lobby.game.get_bug(bug_index) {
+                    let team = *bug_data.team();
+
+                    if let LobbySort::Online(lobby_id) = *self.lobby.settings.sort() {
+                        // The whole plan so far replaces the commitment of the turn
+                        let impulse_intents = self.planned_intents(app_context, team);
+
+                        self.outbox.send(
+                            &self.dispatcher,
+                            lobby_id,
                             app_context.session_id.clone().unwrap(),
-                            Message::Move(Turn {
-                                impulse_intents: HashMap::from([(
-                                    bug_index,
-                                    *bug_data.impulse_intent(),
-                                )]),
+                            Turn {
+                                impulse_intents,
                                 timestamp: 0.0,
                                 index: self.lobby.game.turns_count(),
-                            }),
+                                ..Default::default()
+                            },
+                            frame,
                         );
+                    } else {
+                        // The server measures think time of online games itself
+                        self.lobby.game.record_think_time(team, think_time);
                     }
                 }
             }
 
-            if let Some((rigid_body_handle, _rigid_body, bug_data)) =
-                self.lobby.game.intersecting_bug_mut(point)
-            {
-                if Some(*bug_data.team()) == my_team && bug_data.health() > 1 {
-                    self.selected_bug_index = Some(rigid_body_handle);
-                } else {
-                    self.selected_bug_index = None
-                }
-            } else {
-                self.selected_bug_index = None;
+            self.deselect();
+            self.selected_bug_index = self.selectable_bug(point, my_team, &app_context.session_id);
+
+            if self.selected_bug_index.is_some() {
+                self.smoothed_aim = None;
+            } else if !aiming && self.can_plan(my_team) {
+                // Dragging over the arena selects every friendly bug in the box
+                self.selection_box = Some(pointer.location);
             }
         }
 
+        self.tick_selection_box(pointer, my_team, &app_context.session_id);
+
+        self.tick_inspection(point, pointer, &app_context.clock);
+
         // if pointer.alt_clicked() {
         //     self.lobby.game.execute_turn();
         // }
@@ -620,17 +3841,265 @@ impl State for GameState {
 
         // self.server_target_tick = self.server_target_tick.max(self.lobby.target_tick());
 
-        self.lobby.game.tick();
+        if let Some(replay_turns) = &mut self.replay_turns {
+            if self.lobby.game.awaiting_turn() {
+                if let Some(turn) = replay_turns.pop_front() {
+                    let _ = self.lobby.game.queue_turns(vec![turn]);
+                } else if self.replay_end_frame == 0 {
+                    self.replay_end_frame = frame;
+                } else if app_context.clock.since(self.replay_end_frame) > 180 {
+                    return Some(StateSort::MainMenu(MainMenuState::default()));
+                }
+            }
+        } else if self.lobby.is_local() && self.lobby.game.awaiting_turn() {
+            for (team, bot) in &mut self.bots {
+                self.lobby.game.plan_bot(*team, bot.as_mut());
+            }
 
-        // console::log_1(
-        //     &format!(
-        //         "{:?} {:?}",
-        //         self.lobby.game.target_tick(),
-        //         self.lobby.game.ticks()
-        //     )
-        //     .into(),
-        // );
+            let turn = self.lobby.game.aggregate_turn();
+            let _ = self.lobby.game.queue_turns(vec![turn]);
+        }
+
+        let physics_start = now();
+
+        let speed = if self.lobby.is_local() {
+            self.speed
+        } else {
+            GameSpeed::Normal
+        };
+
+        let ticks_before = (self.lobby.game.ticks(), self.lobby.game.resting_ticks());
+        self.tick_time = 0.0;
+
+        for _ in 0..app_context.frame_limit.ticks_per_frame() * speed.ticks_for_frame(frame) {
+            let tick_start = now();
+            self.lobby.game.tick();
+            self.tick_time += now() - tick_start;
+
+            if let Some(practice) = &mut self.practice {
+                practice.tick(&mut self.lobby.game);
+            }
+
+            self.record_intents();
+            self.record_snapshot();
+
+            if self.turn_replay.is_none() {
+                self.record_trails();
+            }
+        }
+
+        self.physics_time = now() - physics_start;
+        self.physics_ticks = (
+            self.lobby.game.ticks().saturating_sub(ticks_before.0),
+            self.lobby
+                .game
+                .resting_ticks()
+                .saturating_sub(ticks_before.1),
+        );
+
+        if let (Some(practice), None) = (&self.practice, &self.practice_interface) {
+            if practice.finished() {
+                self.finish_practice(practice.score());
+            }
+        }
+
+        self.consume_events(app_context, my_team);
+        self.correct_drift();
+        self.animate_capture_progress(&app_context.clock);
 
         None
     }
+
+    fn performance_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("game {:.2}ms", self.physics_time),
+            format!(
+                "physics {:.3}ms/tick asleep {}/{}",
+                self.tick_time / self.physics_ticks.0.max(1) as f64,
+                self.physics_ticks.1,
+                self.physics_ticks.0
+            ),
+            format!(
+                "turn {} tick {}",
+                self.lobby.game.turns_count(),
+                self.lobby.game.ticks()
+            ),
+            format!(
+                "queued {} pool {}",
+                self.lobby.game.all_turns_count() - self.lobby.game.turns_count(),
+                self.dispatcher.waiting()
+            ),
+            format!("particles {}", self.particle_system.len()),
+        ];
+
+        for (layer, duration) in &self.layer_timings {
+            lines.push(format!("{layer} {duration:.2}ms"));
+        }
+
+        lines
+    }
+
+    fn poll(&mut self, app_context: &AppContext) {
+        let frame = app_context.clock.frame();
+
+        for message in self.dispatcher.take() {
+            self.last_heard = Some(GameClock::local_time());
+
+            match message {
+                Message::Ok => (),
+                Message::Lobby(lobby) => {
+                    self.lobby_expires_at = None;
+                    self.lobby = *lobby;
+                    self.lobby.reset_game();
+
+                    if self.lobby.all_ready() {
+                        self.waiting_room = None;
+                    }
+
+                    self.prediction = None;
+                    self.event_feed.clear();
+                    self.bug_hits.clear();
+                }
+                Message::Lobbies(_lobbies) => (),
+                Message::LobbyError(lobby_error) => match lobby_error.kind {
+                    LobbyErrorKind::NotFound
+                    | LobbyErrorKind::Unauthorized
+                    | LobbyErrorKind::RateLimited => {
+                        self.return_to_menu = true;
+                    }
+                    LobbyErrorKind::Full => {
+                        self.phase_banner = Some(("Spectating", frame));
+                    }
+                    // The main menu offers other lobbies, or to spectate this one instead
+                    LobbyErrorKind::SlotTaken => {
+                        self.slot_taken = true;
+                        self.return_to_menu = true;
+                    }
+                    LobbyErrorKind::NotStarted | LobbyErrorKind::NotJoined => {
+                        if let (LobbySort::Online(lobby_id), Some(session_id)) =
+                            (self.lobby.settings.sort(), app_context.session_id.clone())
+                        {
+                            self.dispatcher.dispatch(
+                                ApiClient::new(session_id).join_lobby(*lobby_id),
+                                |lobby| Message::Lobby(Box::new(lobby)),
+                            );
+                        }
+
+                        self.phase_banner = Some(("Joining...", frame));
+                    }
+                    LobbyErrorKind::InvalidTurn => {
+                        self.phase_banner = Some(("Move too late!", frame));
+                    }
+                    LobbyErrorKind::InvalidReplay => {
+                        self.phase_banner = Some(("Win not verified", frame));
+                    }
+                    LobbyErrorKind::InvalidMap => {
+                        self.return_to_menu = true;
+                    }
+                    LobbyErrorKind::AlreadyJoined => (),
+                    LobbyErrorKind::SurrenderUnavailable => {
+                        self.phase_banner = Some(("Can't surrender yet", frame));
+                    }
+                    // Revealed again by the outbox once the server's window opens
+                    LobbyErrorKind::NotRevealing => (),
+                    LobbyErrorKind::OverBudget => {
+                        warn!("{}", lobby_error.reason);
+                    }
+                },
+                Message::Profile(profile) => {
+                    self.phase_banner = Some(("Win verified!", frame));
+
+                    // Achievements verified by the server also count on this device
+                    for achievement in &profile.achievements {
+                        unlock_achievement(*achievement);
+                    }
+                }
+                Message::Status(_) => (),
+                Message::Ack(message_id) => self.outbox.acknowledge(message_id),
+                Message::Maps(_) => (),
+                Message::Bracket(_) => (),
+                Message::Chat(_) => (),
+                Message::Mute(_) => (),
+                Message::Surrender => (),
+                Message::Ready(_) => (),
+                Message::Commit(_) => (),
+                Message::Reveal(..) => (),
+                Message::Move(_) => (),
+                Message::Leave => (),
+                Message::LobbyExpiryWarning(seconds_left) => {
+                    self.lobby_expires_at = Some(GameClock::local_time() + seconds_left);
+                }
+                // The bot only aims by the seed from its first move on, later ones are ignored
+                Message::LevelSeed(level_seed) => {
+                    if self.lobby.turns().is_empty() {
+                        self.lobby.settings.set_level_seed(level_seed);
+                        self.bots = vec![(
+                            Team::Blue,
+                            active_bot(ZoneBot::for_settings(&self.lobby.settings)),
+                        )];
+                    }
+                }
+                Message::TurnSync(turn_sync) => {
+                    // Turns are only synchronised once the game started
+                    self.waiting_room = None;
+                    self.lobby_expires_at = None;
+                    app_context.clock.observe_server_time(turn_sync.server_time);
+
+                    if let Some(requested_at) = self.sync_diagnostics.requested_at.take() {
+                        self.sync_diagnostics.round_trip = Some(now() - requested_at);
+                    }
+                    self.sync_diagnostics.last_sync = Some(GameClock::local_time());
+                    self.sync_diagnostics.server_turn_count = Some(turn_sync.turn_count);
+
+                    if let (None, Some(warmup_until), Some(_)) = (
+                        self.warmup_until,
+                        turn_sync.warmup_until,
+                        self.team_for(&app_context.session_id),
+                    ) {
+                        self.start_warmup(app_context, turn_sync.turn_count, warmup_until);
+                    }
+
+                    if !self.lobby.game.is_sandbox() {
+                        let turns = self.reconcile(turn_sync.turns.clone());
+
+                        // Refused turns are requested again once the queue drains
+                        if let Err(err) = self.lobby.game.queue_turns(turns) {
+                            warn!("{}", err.reason);
+                        }
+                    }
+                    self.surrender_vote = turn_sync.surrender_vote.clone();
+                    self.server_deadline = turn_sync
+                        .turn_deadline
+                        .map(|turn_deadline| (turn_sync.turn_count, turn_deadline));
+
+                    if let Some(capture_progress) = turn_sync.capture_progress {
+                        self.server_sync =
+                            Some((turn_sync.turn_count, capture_progress, turn_sync.checksum));
+                    }
+                }
+            }
+        }
+
+        self.outbox
+            .retry(&self.dispatcher, self.lobby.game.all_turns_count(), frame);
+
+        if frame >= self.next_poll {
+            if let LobbySort::Online(lobby_id) = self.lobby.settings.sort() {
+                // A predicted turn is still owed by the server
+                let since = match &self.prediction {
+                    Some(prediction) => prediction.turn.index,
+                    None => self.lobby.game.all_turns_count(),
+                };
+
+                self.dispatcher.send(
+                    ApiClient::new(app_context.session_id.clone().unwrap_or_default())
+                        .turns_since(*lobby_id, since),
+                );
+
+                self.sync_diagnostics.requested_at = Some(now());
+            }
+
+            self.next_poll = frame + POLL_FRAMES;
+        }
+    }
 }
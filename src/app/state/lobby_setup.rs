@@ -0,0 +1,403 @@
+use serde::{Deserialize, Serialize};
+use shared::{ArenaMap, Game, GamePhase, LobbySettings, LobbySort, Mutator, Team, ZoneBot};
+
+use super::{
+    custom_settings, lobby_mode, lobby_mutators, set_lobby_mutators, GameState, MainMenuState,
+    State,
+};
+use crate::{
+    app::{
+        Alignment, App, AppContext, ButtonElement, ContentElement, Interface, LabelTheme,
+        LabelTrim, StateSort, ToggleButtonElement, UIElement, UIEvent,
+    },
+    draw::{draw_game_preview, draw_label, draw_text_centered},
+    platform::{dialogs, Canvas, PlatformError},
+};
+
+const BUTTON_BACK: usize = 0;
+const BUTTON_CREATE: usize = 1;
+const BUTTON_MAP: usize = 2;
+const BUTTON_TURN_DURATION: usize = 3;
+const BUTTON_PRIVATE: usize = 4;
+const BUTTON_BEST_OF: usize = 5;
+const BUTTON_JOIN_PRIVATE: usize = 6;
+
+/// Mutator toggles are numbered from here, in the order of [`Mutator::ALL`].
+const BUTTON_MUTATOR_BASE: usize = 10;
+
+/// Storage key of the options of the last lobby created on this device.
+const LOBBY_OPTIONS_KEY: &str = "lobby_options";
+
+/// Turn durations in seconds the turn button cycles through.
+const TURN_DURATIONS: [u64; 4] = [12, 16, 20, 30];
+
+/// Series lengths the best-of button cycles through.
+const BEST_OF: [usize; 3] = [1, 3, 5];
+
+/// Turns the preview plays before starting over.
+const PREVIEW_TURNS: usize = 8;
+
+/// Fraction of the bots' impulses the preview flicks with, so its bugs drift around slowly.
+const PREVIEW_IMPULSE_SCALE: f32 = 0.4;
+
+/// Side in pixels of the square the preview is drawn in.
+const PREVIEW_SIZE: f64 = 96.0;
+
+/// Options of an online lobby picked on the setup screen, remembered for the next one. Mutators,
+/// warmup and the mode are shared with the rules menu and stored by it.
+#[derive(Serialize, Deserialize, Clone)]
+struct LobbyOptions {
+    map: Option<ArenaMap>,
+    turn_duration: u64,
+    private: bool,
+    best_of: usize,
+}
+
+impl LobbyOptions {
+    fn load() -> LobbyOptions {
+        serde_json::from_str(&App::kv_get(LOBBY_OPTIONS_KEY)).unwrap_or_default()
+    }
+
+    fn save(&self) {
+        App::kv_set(
+            LOBBY_OPTIONS_KEY,
+            &serde_json::to_string(self).unwrap_or_default(),
+        );
+    }
+}
+
+impl Default for LobbyOptions {
+    fn default() -> Self {
+        LobbyOptions {
+            map: None,
+            turn_duration: LobbySettings::DEFAULT_TURN_DURATION,
+            private: false,
+            best_of: 1,
+        }
+    }
+}
+
+/// Picks the map, turn duration, mutators, privacy and series length of a new online lobby
+/// before creating it.
+pub struct LobbySetupState {
+    interface: Interface,
+    options: LobbyOptions,
+    mutators: Vec<Mutator>,
+    players_per_team: usize,
+    /// Bots playing each other in the picked settings, previewing them.
+    preview: Game,
+    preview_bot: ZoneBot,
+}
+
+impl LobbySetupState {
+    pub fn new(players_per_team: usize) -> LobbySetupState {
+        let mut state = LobbySetupState {
+            interface: Interface::new(Vec::new()),
+            options: LobbyOptions::load(),
+            mutators: lobby_mutators(),
+            players_per_team,
+            preview: Game::default(),
+            preview_bot: ZoneBot::default(),
+        };
+
+        state.interface = state.create_interface();
+        state.reset_preview();
+
+        state
+    }
+
+    fn create_interface(&self) -> Interface {
+        let option_button = |y: i32, value: usize, text: String| {
+            ButtonElement::new(
+                ((384 - 160) / 2, y),
+                (160, 20),
+                value,
+                LabelTrim::Round,
+                LabelTheme::Default,
+                ContentElement::Text(text, Alignment::Center),
+            )
+            .boxed()
+        };
+
+        let map_name = match self.options.map {
+            Some(_) => "Custom",
+            None => "Default",
+        };
+
+        let mut elements = vec![
+            ButtonElement::new(
+                (8, 360 - 28),
+                (88, 16),
+                BUTTON_BACK,
+                LabelTrim::Return,
+                LabelTheme::Default,
+                ContentElement::Text("Back".to_string(), Alignment::Center),
+            )
+            .boxed(),
+            ButtonElement::new(
+                ((384 - 88) / 2, 360 - 28),
+                (88, 16),
+                BUTTON_JOIN_PRIVATE,
+                LabelTrim::Round,
+                LabelTheme::Default,
+                ContentElement::Text("Join by ID".to_string(), Alignment::Center),
+            )
+            .boxed(),
+            ButtonElement::new(
+                (384 - 120, 360 - 32),
+                (112, 24),
+                BUTTON_CREATE,
+                LabelTrim::Glorious,
+                LabelTheme::Action,
+                ContentElement::Text("Create".to_string(), Alignment::Center),
+            )
+            .boxed(),
+            option_button(56, BUTTON_MAP, format!("Map: {map_name}")),
+            option_button(
+                80,
+                BUTTON_TURN_DURATION,
+                format!("Turn: {}s", self.options.turn_duration),
+            ),
+            option_button(
+                128,
+                BUTTON_BEST_OF,
+                match self.options.best_of {
+                    1 => "Single game".to_string(),
+                    best_of => format!("Best of {best_of}"),
+                },
+            ),
+        ];
+
+        let mut button_private = ToggleButtonElement::new(
+            ((384 - 160) / 2, 104),
+            (160, 20),
+            BUTTON_PRIVATE,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            ContentElement::Text("Private".to_string(), Alignment::Center),
+        );
+        button_private.set_selected(self.options.private);
+
+        elements.push(button_private.boxed());
+
+        for (i, mutator) in Mutator::ALL.iter().enumerate() {
+            let mut button_mutator = ToggleButtonElement::new(
+                ((384 - 160) / 2, 160 + i as i32 * 24),
+                (160, 20),
+                BUTTON_MUTATOR_BASE + i,
+                LabelTrim::Round,
+                LabelTheme::Default,
+                ContentElement::Text(mutator.name().to_string(), Alignment::Center),
+            );
+            button_mutator.set_selected(self.mutators.contains(mutator));
+
+            elements.push(button_mutator.boxed());
+        }
+
+        Interface::new(elements)
+    }
+
+    /// Composes the settings of the lobby from the picked options and the rules of this device.
+    fn lobby_settings(&self) -> LobbySettings {
+        let mut settings = custom_settings(LobbySettings::new(LobbySort::Online(0)));
+
+        settings.set_players_per_team(self.players_per_team);
+        settings.set_max_turns(Some(LobbySettings::DEFAULT_MAX_TURNS));
+        settings.set_map(self.options.map.clone());
+        settings.set_turn_duration(self.options.turn_duration);
+        settings.set_private(self.options.private);
+        settings.set_best_of(self.options.best_of);
+
+        settings
+    }
+
+    /// Starts the preview over in the picked settings.
+    fn reset_preview(&mut self) {
+        let settings = self.lobby_settings();
+
+        self.preview = Game::with_settings(&settings);
+        self.preview_bot = ZoneBot::for_settings(&settings);
+    }
+
+    /// Advances the preview by a tick, letting the bots flick gently whenever a turn is awaited and
+    /// skipping the idle planning phase in between.
+    fn tick_preview(&mut self) {
+        if self.preview.result().is_some() || self.preview.turns_count() >= PREVIEW_TURNS {
+            self.reset_preview();
+        }
+
+        if self.preview.phase() == GamePhase::Planning {
+            while !self.preview.awaiting_turn() {
+                self.preview.tick();
+            }
+        }
+
+        if self.preview.awaiting_turn() {
+            for team in [Team::Red, Team::Blue] {
+                self.preview.plan_bot(team, &mut self.preview_bot);
+            }
+
+            let mut turn = self.preview.aggregate_turn();
+
+            for impulse_intent in turn.impulse_intents.values_mut() {
+                *impulse_intent *= PREVIEW_IMPULSE_SCALE;
+            }
+
+            let _ = self.preview.queue_turns(vec![turn]);
+        }
+
+        self.preview.tick();
+    }
+
+    /// Asks for a map code, an empty one going back to the default arena.
+    fn pick_map(&mut self) {
+        let Some(code) = dialogs().prompt(
+            "Paste a map code, or leave empty for the default arena:",
+            "",
+        ) else {
+            return;
+        };
+
+        if code.trim().is_empty() {
+            self.options.map = None;
+        } else {
+            match ArenaMap::decode(&code) {
+                Ok(map) => self.options.map = Some(map),
+                Err(err) => {
+                    dialogs().alert(&err.reason);
+                }
+            }
+        }
+    }
+
+    /// Asks for the ID of a private lobby to join.
+    fn join_private(&self, session_id: &str) -> Option<StateSort> {
+        let lobby_id = dialogs().prompt("ID of the lobby:", "")?;
+
+        let lobby_id = lobby_id.trim().parse().ok()?;
+
+        Some(StateSort::Game(GameState::new(
+            LobbySettings::new(LobbySort::Online(lobby_id)),
+            session_id.to_string(),
+        )))
+    }
+}
+
+impl State for LobbySetupState {
+    fn draw(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+
+        let title = match self.players_per_team {
+            1 => "New lobby".to_string(),
+            players_per_team => format!("New {players_per_team}v{players_per_team} lobby"),
+        };
+
+        draw_label(
+            context,
+            atlas,
+            ((384 - 136) / 2, 16),
+            (136, 24),
+            "#7f3faa",
+            &ContentElement::Text(title, Alignment::Center),
+            pointer,
+            frame,
+            &LabelTrim::Glorious,
+            false,
+        )?;
+
+        draw_text_centered(
+            context,
+            atlas,
+            384.0 / 2.0,
+            160.0 + Mutator::ALL.len() as f64 * 24.0 + 4.0,
+            &format!("Mode: {} (see Rules)", lobby_mode().name()),
+        )?;
+
+        draw_game_preview(
+            context,
+            &self.preview,
+            384.0 - 8.0 - PREVIEW_SIZE,
+            56.0,
+            PREVIEW_SIZE,
+        )?;
+
+        self.interface
+            .draw(interface_context, atlas, pointer, frame)?;
+
+        Ok(())
+    }
+
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
+        self.tick_preview();
+
+        if let Some(UIEvent::ButtonClick(value, clip_id)) =
+            self.interface.tick(&app_context.pointer)
+        {
+            app_context.audio_system.play_clip_option(clip_id);
+
+            match value {
+                BUTTON_BACK => return Some(StateSort::MainMenu(MainMenuState::default())),
+                BUTTON_CREATE => {
+                    if let Some(session_id) = &app_context.session_id {
+                        self.options.save();
+
+                        return Some(StateSort::Game(GameState::new(
+                            self.lobby_settings(),
+                            session_id.clone(),
+                        )));
+                    }
+                }
+                BUTTON_JOIN_PRIVATE => {
+                    if let Some(session_id) = &app_context.session_id {
+                        if let Some(next_state) = self.join_private(session_id) {
+                            return Some(next_state);
+                        }
+                    }
+                }
+                BUTTON_MAP => self.pick_map(),
+                BUTTON_TURN_DURATION => {
+                    let index = TURN_DURATIONS
+                        .iter()
+                        .position(|turn_duration| *turn_duration == self.options.turn_duration)
+                        .map_or(0, |index| (index + 1) % TURN_DURATIONS.len());
+
+                    self.options.turn_duration = TURN_DURATIONS[index];
+                }
+                BUTTON_PRIVATE => self.options.private ^= true,
+                BUTTON_BEST_OF => {
+                    let index = BEST_OF
+                        .iter()
+                        .position(|best_of| *best_of == self.options.best_of)
+                        .map_or(0, |index| (index + 1) % BEST_OF.len());
+
+                    self.options.best_of = BEST_OF[index];
+                }
+                value if value >= BUTTON_MUTATOR_BASE => {
+                    if let Some(mutator) = Mutator::ALL.get(value - BUTTON_MUTATOR_BASE) {
+                        if let Some(position) = self.mutators.iter().position(|m| m == mutator) {
+                            self.mutators.remove(position);
+                        } else {
+                            self.mutators.push(*mutator);
+                        }
+
+                        set_lobby_mutators(&self.mutators);
+                    }
+                }
+                _ => (),
+            }
+
+            // Rebuilt so the buttons name the picked options, and the preview plays them
+            self.interface = self.create_interface();
+            self.reset_preview();
+        }
+
+        None
+    }
+}
@@ -0,0 +1,126 @@
+use shared::Achievement;
+
+use super::{MainMenuState, State};
+use crate::{
+    achievements::unlocked_achievements,
+    app::{
+        Alignment, AppContext, ButtonElement, ContentElement, Interface, LabelTheme, LabelTrim,
+        StateSort, UIElement, UIEvent,
+    },
+    draw::{draw_label, draw_text, draw_text_centered},
+    platform::{Canvas, PlatformError},
+};
+
+const BUTTON_BACK: usize = 0;
+
+/// Gallery of all achievements, revealing which have been unlocked on this device.
+pub struct AchievementsMenuState {
+    interface: Interface,
+    unlocked: Vec<Achievement>,
+}
+
+impl State for AchievementsMenuState {
+    fn draw(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+
+        draw_label(
+            context,
+            atlas,
+            ((384 - 136) / 2, 24),
+            (136, 24),
+            "#7f3faa",
+            &ContentElement::Text("Achievements".to_string(), Alignment::Center),
+            pointer,
+            frame,
+            &LabelTrim::Glorious,
+            false,
+        )?;
+
+        for (i, achievement) in Achievement::ALL.iter().enumerate() {
+            let y = 72 + i as i32 * 48;
+            let unlocked = self.unlocked.contains(achievement);
+
+            draw_label(
+                context,
+                atlas,
+                (48, y),
+                (112, 16),
+                if unlocked { "#2a9f55" } else { "#2a1f00" },
+                &ContentElement::Text(
+                    if unlocked {
+                        achievement.name().to_string()
+                    } else {
+                        "Locked".to_string()
+                    },
+                    Alignment::Center,
+                ),
+                pointer,
+                frame,
+                &LabelTrim::Round,
+                false,
+            )?;
+
+            draw_text(
+                context,
+                atlas,
+                48.0,
+                y as f64 + 24.0,
+                achievement.description(),
+            )?;
+        }
+
+        draw_text_centered(
+            context,
+            atlas,
+            384.0 / 2.0,
+            360.0 - 52.0,
+            &format!(
+                "{}/{} unlocked",
+                self.unlocked.len(),
+                Achievement::ALL.len()
+            ),
+        )?;
+
+        self.interface
+            .draw(interface_context, atlas, pointer, frame)?;
+
+        Ok(())
+    }
+
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
+        if let Some(UIEvent::ButtonClick(BUTTON_BACK, clip_id)) =
+            self.interface.tick(&app_context.pointer)
+        {
+            app_context.audio_system.play_clip_option(clip_id);
+
+            return Some(StateSort::MainMenu(MainMenuState::default()));
+        }
+
+        None
+    }
+}
+
+impl Default for AchievementsMenuState {
+    fn default() -> Self {
+        let button_back = ButtonElement::new(
+            ((384 - 88) / 2, 360 - 28),
+            (88, 16),
+            BUTTON_BACK,
+            LabelTrim::Return,
+            LabelTheme::Default,
+            ContentElement::Text("Back".to_string(), Alignment::Center),
+        );
+
+        AchievementsMenuState {
+            interface: Interface::new(vec![button_back.boxed()]),
+            unlocked: unlocked_achievements(),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use shared::{BugData, BugSort, Game, Team};
+
+use super::{MainMenuState, State};
+use crate::{
+    app::{
+        Alignment, AppContext, ButtonElement, ContentElement, Interface, LabelTheme, LabelTrim,
+        StateSort, UIElement, UIEvent,
+    },
+    draw::{draw_bugdata, draw_label, draw_text},
+    platform::{Canvas, PlatformError},
+};
+
+const BUTTON_BACK: usize = 0;
+const BUTTON_PAGE_PREVIOUS: usize = 10;
+const BUTTON_PAGE_NEXT: usize = 11;
+
+/// Pages following the bug entries.
+const EXTRA_PAGES: usize = 2;
+
+/// Reference of all bugs, the rules and the controls, built from the game's own balance values.
+pub struct CritterpediaMenuState {
+    interface: Interface,
+    page: usize,
+}
+
+impl CritterpediaMenuState {
+    fn page_count() -> usize {
+        BugSort::ALL.len() + EXTRA_PAGES
+    }
+
+    fn bug_name(bug_sort: &BugSort) -> &'static str {
+        match bug_sort {
+            BugSort::Beetle => "Beetle",
+            BugSort::Ladybug => "Ladybug",
+            BugSort::Ant => "Ant",
+        }
+    }
+
+    fn bug_lines(bug_sort: &BugSort) -> Vec<String> {
+        let mut lines = vec![
+            format!("Health   {}", bug_sort.max_health()),
+            format!("Weight   {}", bug_sort.weight_class()),
+            format!("Mass     {:.1}", bug_sort.mass()),
+            format!("Bounce   {:.2}", bug_sort.restitution()),
+            format!(
+                "Stamina  {:.1} (+{:.1}/turn)",
+                bug_sort.max_stamina(),
+                bug_sort.stamina_regen()
+            ),
+            String::new(),
+            format!("Deals {} damage when ramming.", 1 + bug_sort.bonus_damage()),
+        ];
+
+        let lighter: Vec<&str> = BugSort::ALL
+            .iter()
+            .filter(|other| other.weight_class() < bug_sort.weight_class())
+            .map(Self::bug_name)
+            .collect();
+
+        if !lighter.is_empty() {
+            lines.push(format!("Shrugs off rams by {}.", lighter.join(", ")));
+        }
+
+        if bug_sort.brace_knockback() < 1.0 {
+            lines.push(format!(
+                "Braces when still: {:.0}% knockback.",
+                bug_sort.brace_knockback() * 100.0
+            ));
+        }
+
+        lines
+    }
+
+    fn rules_lines() -> Vec<String> {
+        let game = Game::default();
+
+        vec![
+            format!("Each turn lasts {} seconds.", game.turn_duration()),
+            "Plan moves, then all bugs launch".to_string(),
+            "at the same time.".to_string(),
+            String::new(),
+            "When rivals collide, the faster".to_string(),
+            "bug rams: both lose 1 health,".to_string(),
+            "unless the target is heavier.".to_string(),
+            String::new(),
+            "Moves cost stamina, which".to_string(),
+            "recovers every turn.".to_string(),
+            String::new(),
+            "Outnumber the rival team in the".to_string(),
+            "center zone to tip the bar.".to_string(),
+            "Fill it to win!".to_string(),
+        ]
+    }
+
+    fn controls_lines() -> Vec<String> {
+        vec![
+            "Click one of your bugs to".to_string(),
+            "select it.".to_string(),
+            String::new(),
+            "Aim with the pointer: the".to_string(),
+            "farther, the stronger.".to_string(),
+            String::new(),
+            "Click again to commit the move".to_string(),
+            "before the timer runs out.".to_string(),
+        ]
+    }
+}
+
+impl State for CritterpediaMenuState {
+    fn draw(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+
+        let (title, lines) = match BugSort::ALL.get(self.page) {
+            Some(bug_sort) => (Self::bug_name(bug_sort), Self::bug_lines(bug_sort)),
+            None if self.page == BugSort::ALL.len() => ("Rules", Self::rules_lines()),
+            None => ("Controls", Self::controls_lines()),
+        };
+
+        draw_label(
+            context,
+            atlas,
+            ((384 - 136) / 2, 24),
+            (136, 24),
+            "#7f3faa",
+            &ContentElement::Text(title.to_string(), Alignment::Center),
+            pointer,
+            frame,
+            &LabelTrim::Glorious,
+            false,
+        )?;
+
+        let mut text_y = 64.0;
+
+        if let Some(bug_sort) = BugSort::ALL.get(self.page) {
+            context.save();
+            context.translate(384.0 / 2.0, 80.0)?;
+            context.scale(2.0, 2.0)?;
+            draw_bugdata(
+                context,
+                atlas,
+                &BugData::new(*bug_sort, Team::Red),
+                self.page,
+                frame,
+            )?;
+            context.restore();
+
+            text_y = 112.0;
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(context, atlas, 48.0, text_y + i as f64 * 12.0, line)?;
+        }
+
+        draw_text(
+            context,
+            atlas,
+            (384 - 40) as f64 / 2.0,
+            360.0 - 44.0,
+            &format!("{}/{}", self.page + 1, Self::page_count()),
+        )?;
+
+        self.interface
+            .draw(interface_context, atlas, pointer, frame)?;
+
+        Ok(())
+    }
+
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
+        if let Some(UIEvent::ButtonClick(value, clip_id)) =
+            self.interface.tick(&app_context.pointer)
+        {
+            app_context.audio_system.play_clip_option(clip_id);
+
+            match value {
+                BUTTON_BACK => {
+                    return Some(StateSort::MainMenu(MainMenuState::default()));
+                }
+                BUTTON_PAGE_PREVIOUS => {
+                    self.page = (self.page + Self::page_count() - 1) % Self::page_count();
+                }
+                BUTTON_PAGE_NEXT => {
+                    self.page = (self.page + 1) % Self::page_count();
+                }
+                _ => (),
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for CritterpediaMenuState {
+    fn default() -> Self {
+        let button_back = ButtonElement::new(
+            ((384 - 88) / 2, 360 - 28),
+            (88, 16),
+            BUTTON_BACK,
+            LabelTrim::Return,
+            LabelTheme::Default,
+            ContentElement::Text("Back".to_string(), Alignment::Center),
+        );
+
+        let button_page_previous = ButtonElement::new(
+            (8, 360 - 28),
+            (20, 16),
+            BUTTON_PAGE_PREVIOUS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            ContentElement::Sprite((48, 176), (8, 8)),
+        );
+
+        let button_page_next = ButtonElement::new(
+            (384 - 28, 360 - 28),
+            (20, 16),
+            BUTTON_PAGE_NEXT,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            ContentElement::Sprite((56, 176), (8, 8)),
+        );
+
+        let interface = Interface::new(vec![
+            button_back.boxed(),
+            button_page_previous.boxed(),
+            button_page_next.boxed(),
+        ]);
+
+        CritterpediaMenuState { interface, page: 0 }
+    }
+}
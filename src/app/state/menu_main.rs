@@ -1,49 +1,371 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::collections::HashMap;
 
-use shared::{Lobby, LobbySettings, LobbySort, Message};
-use wasm_bindgen::{closure::Closure, JsValue};
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlInputElement};
+use shared::{
+    balance, BugData, BugSummary, LobbySettings, LobbySort, LobbySummary, Message, Replay,
+    ServerStatus,
+};
 
-use super::{GameState, State, SettingsMenuState};
+use super::{
+    custom_settings, lobby_mutators, lobby_warmup, AchievementsMenuState, CalibrationState,
+    CritterpediaMenuState, GameState, LobbySetupState, MapGalleryState, RulesMenuState,
+    SettingsMenuState, State, TournamentState,
+};
 use crate::{
+    api::{ApiClient, Dispatcher},
     app::{
-        Alignment, AppContext, ButtonElement, Interface, LabelTheme, LabelTrim, StateSort,
-        UIElement, UIEvent,
+        Alignment, AppContext, ButtonElement, Frame, Interface, LabelTheme, LabelTrim, Settings,
+        StateSort, UIElement, UIEvent,
     },
-    draw::{draw_bugdata, draw_label, draw_text, draw_text_centered},
-    net::{fetch, request_lobbies, MessagePool},
+    calibration::versus_ai_settings,
+    debug,
+    draw::{draw_bugdata, draw_label, draw_text, draw_text_centered, text_length},
+    net::{region_ping, take_invited_lobby},
+    platform::{dialogs, Canvas, PlatformError},
 };
 
 pub struct MainMenuState {
     interface: Interface,
     lobby_list_interface: Interface,
-    last_lobby_refresh: usize,
-    message_pool: Rc<RefCell<MessagePool>>,
-    message_closure: Closure<dyn FnMut(JsValue)>,
-    lobbies: HashMap<u16, Lobby>,
-    displayed_lobbies: Vec<(usize, (u16, Lobby))>,
+    last_lobby_refresh: Frame,
+    dispatcher: Dispatcher,
+    lobbies: HashMap<u16, LobbySummary>,
+    displayed_lobbies: Vec<(usize, (u16, LobbySummary))>,
+    /// Summaries of the previous refresh, which previews animate from.
+    previous_summaries: HashMap<u16, LobbySummary>,
+    /// Frame at which the current summaries arrived.
+    summaries_frame: Frame,
     lobby_page: usize,
     lobby_list_dirty: bool,
+    server_status: ServerStatus,
+    /// Whether the interface is laid out for a portrait screen.
+    portrait: bool,
+    /// Lobby whose last slot another player took just before this one could join it.
+    missed_lobby: Option<u16>,
+    /// Offers to spectate the missed lobby instead.
+    missed_interface: Interface,
 }
 
-impl MainMenuState {}
+impl MainMenuState {
+    /// Returns to the main menu after losing the race for a lobby's last slot, refreshing the
+    /// lobby list right away to highlight lobbies which still have room.
+    pub fn missed_lobby(lobby_id: u16) -> MainMenuState {
+        let button_spectate = ButtonElement::new(
+            (384 / 2 - 84, 360 / 2 + 4),
+            (80, 20),
+            BUTTON_SPECTATE,
+            LabelTrim::Glorious,
+            LabelTheme::Action,
+            crate::app::ContentElement::Text("Spectate".to_string(), Alignment::Center),
+        );
+
+        let button_dismiss = ButtonElement::new(
+            (384 / 2 + 4, 360 / 2 + 4),
+            (80, 20),
+            BUTTON_DISMISS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Dismiss".to_string(), Alignment::Center),
+        );
+
+        MainMenuState {
+            missed_lobby: Some(lobby_id),
+            missed_interface: Interface::new(vec![button_spectate.boxed(), button_dismiss.boxed()]),
+            ..MainMenuState::default()
+        }
+    }
+
+    /// Determines if a lobby from the list still has a free player slot.
+    fn has_room(summary: &LobbySummary) -> bool {
+        summary.players < summary.players_per_team * 2
+    }
+
+    /// Determines if the server reported it cannot host any more lobbies.
+    fn server_busy(&self) -> bool {
+        self.server_status.capacity > 0 && self.server_status.busy()
+    }
+
+    /// Determines if the server runs a different balance config, which would desync online games.
+    fn balance_outdated(&self) -> bool {
+        self.server_status.balance_hash != 0 && self.server_status.balance_hash != balance().hash()
+    }
+
+    /// Centre of the page number, between the paging buttons.
+    fn page_label_position(&self) -> (f64, f64) {
+        if self.portrait {
+            (384.0 - 64.0, 360.0 - 94.0)
+        } else {
+            (384.0 / 2.0, 360.0 - 20.0)
+        }
+    }
+
+    /// Builds the menu buttons, moving those normally along the top and middle of the screen
+    /// into the right-hand column on portrait screens, where thumbs reach when held sideways.
+    fn create_interface(portrait: bool) -> Interface {
+        let button_new_lobby = ButtonElement::new(
+            (8, 360 - 32),
+            (112, 24),
+            BUTTON_ARENA,
+            LabelTrim::Glorious,
+            LabelTheme::Action,
+            crate::app::ContentElement::Text("New Lobby".to_string(), Alignment::Center),
+        );
+
+        let button_versus_ai = ButtonElement::new(
+            (8, 360 - 56),
+            (112, 20),
+            BUTTON_VERSUS_AI,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Versus AI".to_string(), Alignment::Center),
+        );
+
+        let button_teams = ButtonElement::new(
+            (8, 360 - 80),
+            (112, 20),
+            BUTTON_TEAMS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("New 2v2".to_string(), Alignment::Center),
+        );
+
+        let button_import_replay = ButtonElement::new(
+            (8, 360 - 104),
+            (112, 20),
+            BUTTON_IMPORT_REPLAY,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Import Replay".to_string(), Alignment::Center),
+        );
+
+        let button_maps = ButtonElement::new(
+            (8, 360 - 128),
+            (54, 20),
+            BUTTON_MAPS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Maps".to_string(), Alignment::Center),
+        );
+
+        let button_rules = ButtonElement::new(
+            (66, 360 - 128),
+            (54, 20),
+            BUTTON_RULES,
+            LabelTrim::Round,
+            if lobby_mutators().is_empty() && !lobby_warmup() {
+                LabelTheme::Default
+            } else {
+                LabelTheme::Action
+            },
+            crate::app::ContentElement::Text("Rules".to_string(), Alignment::Center),
+        );
+
+        let button_tournament = ButtonElement::new(
+            (8, 360 - 152),
+            (112, 20),
+            BUTTON_TOURNAMENT,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Tournament".to_string(), Alignment::Center),
+        );
+
+        let button_practice = ButtonElement::new(
+            (8, 360 - 176),
+            (112, 20),
+            BUTTON_PRACTICE,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Target Practice".to_string(), Alignment::Center),
+        );
+
+        let button_settings: ButtonElement = ButtonElement::new(
+            (384 - 120, 360 - 32),
+            (112, 24),
+            BUTTON_SETTINGS,
+            LabelTrim::Return,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Settings".to_string(), Alignment::Center),
+        );
+
+        let button_critterpedia = ButtonElement::new(
+            (384 - 120, 360 - 56),
+            (112, 20),
+            BUTTON_CRITTERPEDIA,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Critterpedia".to_string(), Alignment::Center),
+        );
+
+        let (achievements_position, achievements_size) = if portrait {
+            ((384 - 120, 360 - 80), (112, 20))
+        } else {
+            ((384 - 120, 4), (112, 16))
+        };
+
+        let button_achievements = ButtonElement::new(
+            achievements_position,
+            achievements_size,
+            BUTTON_ACHIEVEMENTS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Achievements".to_string(), Alignment::Center),
+        );
+
+        let (page_previous_position, page_next_position, page_size) = if portrait {
+            ((384 - 120, 360 - 104), (384 - 48, 360 - 104), (40, 20))
+        } else {
+            (
+                ((384 - 64) / 2, 360 - 28),
+                ((384 - 64) / 2 + 44, 360 - 28),
+                (20, 16),
+            )
+        };
+
+        let button_page_previous: ButtonElement = ButtonElement::new(
+            page_previous_position,
+            page_size,
+            BUTTON_PAGE_PREVIOUS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Sprite((48, 176), (8, 8)),
+        );
+
+        let button_page_next: ButtonElement = ButtonElement::new(
+            page_next_position,
+            page_size,
+            BUTTON_PAGE_NEXT,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Sprite((56, 176), (8, 8)),
+        );
+
+        Interface::new(vec![
+            button_new_lobby.boxed(),
+            button_versus_ai.boxed(),
+            button_teams.boxed(),
+            button_import_replay.boxed(),
+            button_maps.boxed(),
+            button_rules.boxed(),
+            button_tournament.boxed(),
+            button_practice.boxed(),
+            button_settings.boxed(),
+            button_critterpedia.boxed(),
+            button_achievements.boxed(),
+            button_page_previous.boxed(),
+            button_page_next.boxed(),
+        ])
+    }
+}
 
 const BUTTON_PAGE_PREVIOUS: usize = 10;
 const BUTTON_PAGE_NEXT: usize = 11;
 const BUTTON_ARENA: usize = 20;
 const BUTTON_SETTINGS: usize = 21;
+const BUTTON_VERSUS_AI: usize = 22;
+const BUTTON_TEAMS: usize = 23;
+const BUTTON_IMPORT_REPLAY: usize = 24;
+const BUTTON_CRITTERPEDIA: usize = 25;
+const BUTTON_ACHIEVEMENTS: usize = 26;
+const BUTTON_MAPS: usize = 27;
+const BUTTON_RULES: usize = 28;
+const BUTTON_TOURNAMENT: usize = 29;
+const BUTTON_PRACTICE: usize = 30;
+const BUTTON_SPECTATE: usize = 31;
+const BUTTON_DISMISS: usize = 32;
+
+/// Frames without input before the main menu starts an exhibition match.
+const ATTRACT_IDLE_FRAMES: Frame = 2 * 60 * 60;
 
 const LOBBY_PAGE_SIZE: usize = 6;
 
+/// Frames over which lobby previews animate between two refreshes.
+const PREVIEW_FRAMES: Frame = 60;
+
+/// Draws a lobby's game in miniature: its bugs on a squashed arena, its capture progress and turn.
+fn draw_lobby_preview(
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
+    summary: &LobbySummary,
+    previous_summary: Option<&LobbySummary>,
+    t: f32,
+    frame: Frame,
+) -> Result<(), PlatformError> {
+    let mut bugs: Vec<(usize, &BugSummary, (f64, f64))> = summary
+        .bugs
+        .iter()
+        .enumerate()
+        .map(|(j, bug)| {
+            let position = match previous_summary.and_then(|previous| previous.bugs.get(j)) {
+                Some(previous_bug) => previous_bug.position().lerp(&bug.position(), t),
+                None => bug.position(),
+            };
+
+            (
+                j,
+                bug,
+                (
+                    80.0 + position.x as f64 * 6.0,
+                    27.0 + position.y as f64 * 0.8,
+                ),
+            )
+        })
+        .collect();
+
+    // Bugs further up the arena are drawn first, so nearer ones overlap them
+    bugs.sort_by(|a, b| a.2 .1.total_cmp(&b.2 .1));
+
+    for (j, bug, (x, y)) in bugs {
+        let mut bug_data = BugData::new(bug.sort, bug.team);
+        bug_data.add_health(bug.health as isize - bug.sort.max_health() as isize);
+
+        context.save();
+        context.translate(x.floor(), y.floor())?;
+        context.scale(0.5, 0.5)?;
+        draw_bugdata(context, atlas, &bug_data, j, frame)?;
+        context.restore();
+    }
+
+    let capture_progress = summary.capture_progress.clamp(-1.0, 1.0) as f64;
+
+    context.save();
+    context.set_fill_style("#002a2a");
+    context.fill_rect(160.0, 19.0, 56.0, 4.0);
+
+    if capture_progress > 0.0 {
+        context.set_fill_style("#C20005");
+        context.fill_rect(
+            188.0 - 28.0 * capture_progress,
+            19.0,
+            28.0 * capture_progress,
+            4.0,
+        );
+    } else if capture_progress < 0.0 {
+        context.set_fill_style("#00C2BD");
+        context.fill_rect(188.0, 19.0, -28.0 * capture_progress, 4.0);
+    }
+
+    context.set_fill_style("#ffffff");
+    context.fill_rect(188.0, 18.0, 1.0, 6.0);
+    context.restore();
+
+    let turn = match summary.max_turns {
+        Some(max_turns) => format!("Turn {}/{}", summary.turn, max_turns),
+        None => format!("Turn {}", summary.turn),
+    };
+
+    draw_text(context, atlas, 160.0, 27.0, &turn)?;
+
+    Ok(())
+}
+
 impl State for MainMenuState {
     fn draw(
         &mut self,
-        context: &CanvasRenderingContext2d,
-        interface_context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
         app_context: &AppContext,
-    ) -> Result<(), JsValue> {
-        let frame = app_context.frame;
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
         let pointer = &app_context.pointer;
 
         context.save();
@@ -57,20 +379,46 @@ impl State for MainMenuState {
         self.lobby_list_interface
             .draw(interface_context, atlas, pointer, frame)?;
 
+        let (page_x, page_y) = self.page_label_position();
+
         draw_text_centered(
             context,
             atlas,
-            (384.0) / 2.0,
-            360.0 - 20.0,
+            page_x,
+            page_y,
             format!("{}", self.lobby_page + 1).as_str(),
         )?;
 
-        // let a: Vec<f64> = self
-        //     .displayed_lobbies
-        //     .iter()
-        //     .map(|(_, (_, lobby))| lobby.first_heartbeat)
-        //     .collect();
-        // console::log_1(&format!("{:?}", a).into());
+        if self.balance_outdated() {
+            draw_label(
+                context,
+                atlas,
+                (8, 360 - 152),
+                (112, 16),
+                "#7f0000",
+                &crate::app::ContentElement::Text(
+                    "Reload to update!".to_string(),
+                    Alignment::Center,
+                ),
+                pointer,
+                frame,
+                &LabelTrim::Round,
+                false,
+            )?;
+        } else if self.server_busy() {
+            draw_label(
+                context,
+                atlas,
+                (8, 360 - 152),
+                (112, 16),
+                "#7f0000",
+                &crate::app::ContentElement::Text("Server busy!".to_string(), Alignment::Center),
+                pointer,
+                frame,
+                &LabelTrim::Round,
+                false,
+            )?;
+        }
 
         if self.displayed_lobbies.is_empty() {
             draw_label(
@@ -106,9 +454,16 @@ impl State for MainMenuState {
                 false,
             )?;
         } else {
-            for (i, (lobby_id, lobby)) in &self.displayed_lobbies {
+            let t = (app_context.clock.since(self.summaries_frame) as f32 / PREVIEW_FRAMES as f32)
+                .min(1.0);
+
+            for (i, (lobby_id, summary)) in &self.displayed_lobbies {
                 let ir: usize = i - self.lobby_page * LOBBY_PAGE_SIZE;
                 let pointer = pointer.teleport((-(384 - 256) / 2, -(12 + ir as i32 * 48)));
+                // Lobbies with room are the alternatives to the one just missed
+                let highlighted = self.missed_lobby.is_some_and(|missed_lobby| {
+                    missed_lobby != *lobby_id && MainMenuState::has_room(summary)
+                });
                 context.save();
                 context.translate((384.0 - 256.0) / 2.0, 12.0 + ir as f64 * 48.0)?;
                 draw_label(
@@ -116,7 +471,7 @@ impl State for MainMenuState {
                     atlas,
                     (0, 15),
                     (224, 24),
-                    "#2a1f00",
+                    if highlighted { "#1f5f00" } else { "#2a1f00" },
                     &crate::app::ContentElement::None,
                     &pointer,
                     frame,
@@ -155,50 +510,147 @@ impl State for MainMenuState {
                     )?;
                 }
 
-                draw_text(context, atlas, 72.0, 4.0, "King of the Hill")?;
+                let mode = match (summary.players_per_team > 1, summary.custom_map) {
+                    (true, true) => "2v2 Custom map",
+                    (true, false) => "King of the Hill 2v2",
+                    (false, true) => "Custom map",
+                    (false, false) => "King of the Hill",
+                };
 
-                context.save();
-                if (i) % 2 == 1 {
-                    context.translate(12.0, 36.0)?;
-                } else {
-                    context.translate(12.0, 32.0)?;
+                draw_text(
+                    context,
+                    atlas,
+                    72.0,
+                    4.0,
+                    &format!(
+                        "{mode} {}/{}",
+                        summary.players,
+                        summary.players_per_team * 2
+                    ),
+                )?;
+
+                let region = match region_ping(&summary.region) {
+                    Some(ping) => format!("{} {:.0}ms", summary.region, ping),
+                    None => summary.region.clone(),
+                };
+
+                draw_text(
+                    context,
+                    atlas,
+                    224.0 - text_length(&region) as f64,
+                    4.0,
+                    &region,
+                )?;
+
+                if !summary.mutators.is_empty() {
+                    let badges: Vec<&str> = summary
+                        .mutators
+                        .iter()
+                        .map(|mutator| mutator.badge())
+                        .collect();
+
+                    draw_text(context, atlas, 0.0, 39.0, &badges.join(" "))?;
                 }
-                for (j, bug) in lobby.game.iter_bugdata().enumerate() {
-                    if (j + i) % 2 == 0 {
-                        context.translate(0.0, 4.0)?;
-                    } else {
-                        context.translate(0.0, -4.0)?;
-                    }
 
-                    draw_bugdata(context, atlas, bug, i * j + j, frame)?;
-                    context.translate(12.0, 0.0)?;
+                if let Some(name) = summary.names.first() {
+                    let names = match summary.names.len() {
+                        1 => name.clone(),
+                        len => format!("{name} +{}", len - 1),
+                    };
+
+                    draw_text(
+                        context,
+                        atlas,
+                        224.0 - text_length(&names) as f64,
+                        39.0,
+                        &names,
+                    )?;
                 }
 
-                context.restore();
+                draw_lobby_preview(
+                    context,
+                    atlas,
+                    summary,
+                    self.previous_summaries.get(lobby_id),
+                    t,
+                    frame,
+                )?;
+
                 context.restore();
             }
         }
 
+        if let Some(missed_lobby) = self.missed_lobby {
+            draw_label(
+                context,
+                atlas,
+                ((384 - 240) / 2, 360 / 2 - 20),
+                (240, 20),
+                "#7f0000",
+                &crate::app::ContentElement::Text(
+                    format!("Lobby {missed_lobby} just filled up!"),
+                    Alignment::Center,
+                ),
+                pointer,
+                frame,
+                &LabelTrim::Round,
+                false,
+            )?;
+
+            self.missed_interface
+                .draw(interface_context, atlas, pointer, frame)?;
+        }
+
         Ok(())
     }
 
-    fn tick(
-        &mut self,
-        _text_input: &HtmlInputElement,
-        app_context: &AppContext,
-    ) -> Option<StateSort> {
-        let frame = app_context.frame;
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
+        let frame = app_context.clock.frame();
         let pointer = &app_context.pointer;
 
+        if self.portrait != app_context.canvas_settings.orientation {
+            self.portrait = app_context.canvas_settings.orientation;
+            self.interface = MainMenuState::create_interface(self.portrait);
+        }
+
+        if app_context.idle_frames() > ATTRACT_IDLE_FRAMES {
+            return Some(StateSort::Game(GameState::exhibition(
+                app_context.last_input_frame,
+            )));
+        }
+
+        if let (Some(session_id), false) = (&app_context.session_id, self.balance_outdated()) {
+            if let Some(lobby_id) = take_invited_lobby() {
+                debug!("joining invited lobby {lobby_id}");
+                return Some(StateSort::Game(GameState::new(
+                    LobbySettings::new(LobbySort::Online(lobby_id)),
+                    session_id.clone(),
+                )));
+            }
+        }
+
+        if let Some(UIEvent::ButtonClick(value, clip_id)) = self.missed_interface.tick(pointer) {
+            app_context.audio_system.play_clip_option(clip_id);
+
+            match (value, self.missed_lobby.take()) {
+                (BUTTON_SPECTATE, Some(lobby_id)) => {
+                    return Some(StateSort::Game(GameState::spectate(lobby_id)));
+                }
+                _ => self.missed_interface = Interface::new(Vec::new()),
+            }
+        }
+
         if let Some(UIEvent::ButtonClick(value, clip_id)) = self.interface.tick(pointer) {
             app_context.audio_system.play_clip_option(clip_id);
 
-            if let BUTTON_ARENA = value {
-                if let Some(session_id) = &app_context.session_id {
-                    return Some(StateSort::Game(GameState::new(
-                        LobbySettings::new(LobbySort::Online(0)),
-                        session_id.clone(),
-                    )));
+            if (value == BUTTON_ARENA || value == BUTTON_TEAMS) && self.balance_outdated() {
+                // Online games would desync until the page is reloaded with the new balance config
+            } else if (value == BUTTON_ARENA || value == BUTTON_TEAMS) && self.server_busy() {
+                // Check again right away whether a lobby has freed up
+                self.last_lobby_refresh = 0;
+            } else if let BUTTON_ARENA = value {
+                if app_context.session_id.is_some() {
+                    return Some(StateSort::LobbySetup(LobbySetupState::new(1)));
                 }
             } else if let BUTTON_PAGE_PREVIOUS = value {
                 self.lobby_page = self.lobby_page.saturating_sub(1);
@@ -208,15 +660,51 @@ impl State for MainMenuState {
                 self.lobby_list_dirty = true;
             } else if let BUTTON_SETTINGS = value {
                 return Some(StateSort::SettingsMenu(SettingsMenuState::default()));
+            } else if let BUTTON_CRITTERPEDIA = value {
+                return Some(StateSort::CritterpediaMenu(CritterpediaMenuState::default()));
+            } else if let BUTTON_ACHIEVEMENTS = value {
+                return Some(StateSort::AchievementsMenu(AchievementsMenuState::default()));
+            } else if let BUTTON_MAPS = value {
+                return Some(StateSort::MapGallery(MapGalleryState::default()));
+            } else if let BUTTON_RULES = value {
+                return Some(StateSort::RulesMenu(RulesMenuState::default()));
+            } else if let BUTTON_TOURNAMENT = value {
+                return Some(StateSort::Tournament(TournamentState::default()));
+            } else if let BUTTON_PRACTICE = value {
+                return Some(StateSort::Game(GameState::target_practice()));
+            } else if let BUTTON_TEAMS = value {
+                if app_context.session_id.is_some() {
+                    return Some(StateSort::LobbySetup(LobbySetupState::new(2)));
+                }
+            } else if let BUTTON_IMPORT_REPLAY = value {
+                if let Some(code) = dialogs().prompt("Paste a replay code to watch the match:", "")
+                {
+                    match Replay::decode(&code) {
+                        Ok(replay) => return Some(StateSort::Game(GameState::replay(replay))),
+                        Err(err) => {
+                            dialogs().alert(&err.reason);
+                        }
+                    }
+                }
+            } else if let BUTTON_VERSUS_AI = value {
+                // The first game versus AI is offered a calibration of its difficulty
+                if Settings::load().bot_difficulty.is_none() {
+                    return Some(StateSort::Calibration(CalibrationState::default()));
+                }
+
+                return Some(StateSort::Game(GameState::new(
+                    versus_ai_settings(custom_settings(LobbySettings::new(LobbySort::LocalAI))),
+                    app_context.session_id.clone().unwrap_or_default(),
+                )));
             }
         }
 
         if let Some(UIEvent::ButtonClick(value, clip_id)) = self.lobby_list_interface.tick(pointer)
         {
-            if let Some(session_id) = &app_context.session_id {
+            if let (Some(session_id), false) = (&app_context.session_id, self.balance_outdated()) {
                 app_context.audio_system.play_clip_option(clip_id);
 
-                // console::log_1(&format!("{}", value).into());
+                debug!("joining lobby {value}");
                 return Some(StateSort::Game(GameState::new(
                     LobbySettings::new(LobbySort::Online(value as u16)),
                     session_id.clone(),
@@ -228,38 +716,54 @@ impl State for MainMenuState {
             .lobby_page
             .min(self.lobbies.len().saturating_sub(1) / LOBBY_PAGE_SIZE);
 
-        if (frame - self.last_lobby_refresh) > 60 {
+        if app_context.clock.since(self.last_lobby_refresh) > 60 {
             self.last_lobby_refresh = frame;
-            let _ = fetch(&request_lobbies()).then(&self.message_closure);
+            self.dispatcher
+                .dispatch(ApiClient::default().lobbies(), Message::Lobbies);
+            self.dispatcher
+                .dispatch(ApiClient::default().status(), Message::Status);
         }
 
-        let mut message_pool = self.message_pool.borrow_mut();
-
-        for message in &message_pool.messages {
+        for message in self.dispatcher.take() {
             match message {
                 Message::Ok => (),
                 Message::Lobby(_lobby) => {
                     // self.lobbies.insert(0, *lobby.clone());
                 }
                 Message::Lobbies(lobbies) => {
-                    self.lobbies = lobbies.clone();
+                    self.previous_summaries = std::mem::replace(&mut self.lobbies, lobbies);
+                    self.summaries_frame = frame;
                     self.lobby_list_dirty = true;
                 }
                 Message::LobbyError(_) => (),
                 Message::Move(_) => (),
                 Message::TurnSync(_) => (),
+                Message::Profile(_) => (),
+                Message::Status(server_status) => {
+                    self.server_status = server_status;
+                }
+                Message::Ack(_) => (),
+                Message::Maps(_) => (),
+                Message::Bracket(_) => (),
+                Message::Chat(_) => (),
+                Message::Mute(_) => (),
+                Message::Surrender => (),
+                Message::Ready(_) => (),
+                Message::Commit(_) => (),
+                Message::Reveal(..) => (),
+                Message::Leave => (),
+                Message::LobbyExpiryWarning(_) => (),
+                Message::LevelSeed(_) => (),
             }
         }
 
-        message_pool.clear();
-
         if self.lobby_list_dirty {
             self.lobby_list_dirty = false;
 
-            let mut displayed_lobbies: Vec<(u16, Lobby)> =
+            let mut displayed_lobbies: Vec<(u16, LobbySummary)> =
                 self.lobbies.clone().into_iter().collect();
 
-            displayed_lobbies.sort_by(|a, b| a.1.first_heartbeat.total_cmp(&b.1.first_heartbeat));
+            displayed_lobbies.sort_by(|a, b| a.1.created_at.total_cmp(&b.1.created_at));
 
             self.displayed_lobbies = displayed_lobbies
                 .into_iter()
@@ -272,7 +776,6 @@ impl State for MainMenuState {
                 self.displayed_lobbies
                     .iter()
                     .map(|(i, (key, _lobby))| {
-                        // console::log_1(&format!("INTERP {}", key).into());
                         ButtonElement::new(
                             (384 - 88, 27 + *i as i32 * 48),
                             (24, 24),
@@ -293,60 +796,7 @@ impl State for MainMenuState {
 
 impl Default for MainMenuState {
     fn default() -> Self {
-        let button_new_lobby = ButtonElement::new(
-            (8, 360 - 32),
-            (112, 24),
-            BUTTON_ARENA,
-            LabelTrim::Glorious,
-            LabelTheme::Action,
-            crate::app::ContentElement::Text("New Lobby".to_string(), Alignment::Center),
-        );
-
-        let button_settings: ButtonElement = ButtonElement::new(
-            (384 - 120, 360 - 32),
-            (112, 24),
-            BUTTON_SETTINGS,
-            LabelTrim::Return,
-            LabelTheme::Default,
-            crate::app::ContentElement::Text("Settings".to_string(), Alignment::Center),
-        );
-
-        let button_page_previous: ButtonElement = ButtonElement::new(
-            ((384 - 64) / 2, 360 - 28),
-            (20, 16),
-            BUTTON_PAGE_PREVIOUS,
-            LabelTrim::Round,
-            LabelTheme::Default,
-            crate::app::ContentElement::Sprite((48, 176), (8, 8)),
-        );
-
-        let button_page_next: ButtonElement = ButtonElement::new(
-            ((384 - 64) / 2 + 44, 360 - 28),
-            (20, 16),
-            BUTTON_PAGE_NEXT,
-            LabelTrim::Round,
-            LabelTheme::Default,
-            crate::app::ContentElement::Sprite((56, 176), (8, 8)),
-        );
-
-        let interface = Interface::new(vec![
-            button_new_lobby.boxed(),
-            button_settings.boxed(),
-            button_page_previous.boxed(),
-            button_page_next.boxed(),
-        ]);
-
-        let message_pool = Rc::new(RefCell::new(MessagePool::new()));
-
-        let message_closure = {
-            let message_pool = message_pool.clone();
-
-            Closure::<dyn FnMut(JsValue)>::new(move |value| {
-                let mut message_pool = message_pool.borrow_mut();
-                let message: Message = serde_wasm_bindgen::from_value(value).unwrap();
-                message_pool.push(message);
-            })
-        };
+        let interface = MainMenuState::create_interface(false);
 
         let lobbies = HashMap::new();
 
@@ -356,9 +806,14 @@ impl Default for MainMenuState {
             last_lobby_refresh: 0,
             lobby_page: 0,
             lobby_list_dirty: false,
+            server_status: ServerStatus::default(),
+            portrait: false,
+            missed_lobby: None,
+            missed_interface: Interface::new(Vec::new()),
             displayed_lobbies: Vec::new(),
-            message_closure,
-            message_pool,
+            previous_summaries: HashMap::new(),
+            summaries_frame: 0,
+            dispatcher: Dispatcher::default(),
             lobbies,
         }
     }
@@ -0,0 +1,366 @@
+use shared::{ArenaMap, LobbySettings, LobbySort, MapGallery, Message, SharedMap};
+
+use super::{GameState, MainMenuState, State};
+use crate::{
+    api::{detach, ApiClient, Dispatcher},
+    app::{
+        Alignment, AppContext, ButtonElement, ContentElement, Interface, LabelTheme, LabelTrim,
+        StateSort, UIElement, UIEvent,
+    },
+    calibration::versus_ai_settings,
+    draw::{draw_label, draw_map_preview, draw_text, draw_text_centered},
+    platform::{dialogs, Canvas, PlatformError},
+};
+
+const BUTTON_BACK: usize = 0;
+const BUTTON_SHARE: usize = 1;
+const BUTTON_PAGE_PREVIOUS: usize = 10;
+const BUTTON_PAGE_NEXT: usize = 11;
+
+/// Row buttons are numbered from here, [`ROW_ACTIONS`] values per row.
+const BUTTON_ROW_BASE: usize = 100;
+const ROW_ACTIONS: usize = 4;
+const ROW_VERSUS_AI: usize = 0;
+const ROW_HOST: usize = 1;
+const ROW_REPORT: usize = 2;
+
+/// Height of each map row, preview included.
+const ROW_HEIGHT: i32 = 64;
+
+/// Side of each map's preview square.
+const PREVIEW_SIZE: f64 = 56.0;
+
+/// Community gallery of shared maps, most played first, to play against the AI or host online.
+pub struct MapGalleryState {
+    interface: Interface,
+    row_interface: Interface,
+    dispatcher: Dispatcher,
+    gallery: MapGallery,
+    /// Decoded maps of the current page, `None` where a code no longer decodes.
+    maps: Vec<Option<ArenaMap>>,
+    page: usize,
+    /// Whether the page needs fetching again.
+    page_dirty: bool,
+    /// Whether the next gallery received is the listing of a map just shared.
+    awaiting_share: bool,
+    status: Option<String>,
+}
+
+impl MapGalleryState {
+    fn create_row_interface(maps: &[SharedMap]) -> Interface {
+        let mut elements = Vec::new();
+
+        for (row, _) in maps.iter().enumerate() {
+            let y = 48 + row as i32 * ROW_HEIGHT;
+            let base = BUTTON_ROW_BASE + row * ROW_ACTIONS;
+
+            for (action, label, x, width) in [
+                (ROW_VERSUS_AI, "Versus AI", 88, 80),
+                (ROW_HOST, "Host", 176, 56),
+                (ROW_REPORT, "Report", 384 - 72, 56),
+            ] {
+                elements.push(
+                    ButtonElement::new(
+                        (x, y + 36),
+                        (width, 16),
+                        base + action,
+                        LabelTrim::Round,
+                        if action == ROW_HOST {
+                            LabelTheme::Action
+                        } else {
+                            LabelTheme::Default
+                        },
+                        ContentElement::Text(label.to_string(), Alignment::Center),
+                    )
+                    .boxed(),
+                );
+            }
+        }
+
+        Interface::new(elements)
+    }
+
+    /// Settings for a game on the given map, counting the play towards its ranking.
+    fn map_settings(
+        &self,
+        row: usize,
+        sort: LobbySort,
+        session_id: &Option<String>,
+    ) -> Option<LobbySettings> {
+        let shared_map = self.gallery.maps.get(row)?;
+        let map = self.maps.get(row)?.clone()?;
+
+        if let Some(session_id) = session_id {
+            detach(ApiClient::new(session_id.clone()).play_map(shared_map.id));
+        }
+
+        let mut settings = LobbySettings::new(sort);
+        settings.set_map(Some(map));
+
+        Some(settings)
+    }
+
+    /// Asks for a title and a map code, and submits them to the gallery.
+    fn share(&mut self, session_id: &Option<String>) {
+        let Some(session_id) = session_id else {
+            self.status = Some("Not connected".to_string());
+            return;
+        };
+
+        let Some(code) = dialogs().prompt("Paste the code of the map to share:", "") else {
+            return;
+        };
+
+        match ArenaMap::decode(&code) {
+            Err(err) => {
+                dialogs().alert(&err.reason);
+                return;
+            }
+            Ok(map) if !map.is_mirrored() => {
+                if !dialogs().confirm(
+                    "The spawn formation is not mirrored, so one team starts with an edge. \
+                         Share anyway?",
+                ) {
+                    return;
+                }
+            }
+            Ok(_) => (),
+        }
+
+        let Some(title) = dialogs().prompt("Title of the map:", "") else {
+            return;
+        };
+
+        self.dispatcher.dispatch(
+            ApiClient::new(session_id.clone()).share_map(title, code),
+            Message::Maps,
+        );
+        self.awaiting_share = true;
+    }
+}
+
+impl State for MapGalleryState {
+    fn draw(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+
+        draw_label(
+            context,
+            atlas,
+            ((384 - 136) / 2, 12),
+            (136, 24),
+            "#7f3faa",
+            &ContentElement::Text("Maps".to_string(), Alignment::Center),
+            pointer,
+            frame,
+            &LabelTrim::Glorious,
+            false,
+        )?;
+
+        for (row, shared_map) in self.gallery.maps.iter().enumerate() {
+            let y = 48 + row as i32 * ROW_HEIGHT;
+
+            if let Some(Some(map)) = self.maps.get(row) {
+                draw_map_preview(context, map, 16.0, y as f64, PREVIEW_SIZE)?;
+            }
+
+            draw_text(context, atlas, 88.0, y as f64 + 4.0, &shared_map.title)?;
+            draw_text(
+                context,
+                atlas,
+                88.0,
+                y as f64 + 18.0,
+                &format!("{} plays", shared_map.plays),
+            )?;
+        }
+
+        if self.gallery.maps.is_empty() {
+            draw_text_centered(context, atlas, 384.0 / 2.0, 160.0, "No maps shared yet")?;
+        }
+
+        draw_text_centered(
+            context,
+            atlas,
+            384.0 / 2.0,
+            360.0 - 20.0,
+            &format!("{}/{}", self.gallery.page + 1, self.gallery.pages.max(1)),
+        )?;
+
+        if let Some(status) = &self.status {
+            draw_text_centered(context, atlas, 384.0 / 2.0, 360.0 - 44.0, status)?;
+        }
+
+        self.interface
+            .draw(interface_context, atlas, pointer, frame)?;
+        self.row_interface
+            .draw(interface_context, atlas, pointer, frame)?;
+
+        Ok(())
+    }
+
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
+        let pointer = &app_context.pointer;
+
+        if let Some(UIEvent::ButtonClick(value, clip_id)) = self.interface.tick(pointer) {
+            app_context.audio_system.play_clip_option(clip_id);
+
+            match value {
+                BUTTON_BACK => return Some(StateSort::MainMenu(MainMenuState::default())),
+                BUTTON_SHARE => self.share(&app_context.session_id),
+                BUTTON_PAGE_PREVIOUS => {
+                    self.page = self.page.saturating_sub(1);
+                    self.page_dirty = true;
+                }
+                BUTTON_PAGE_NEXT => {
+                    self.page = (self.page + 1).min(self.gallery.pages.saturating_sub(1));
+                    self.page_dirty = true;
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(UIEvent::ButtonClick(value, clip_id)) = self.row_interface.tick(pointer) {
+            app_context.audio_system.play_clip_option(clip_id);
+
+            let row = (value - BUTTON_ROW_BASE) / ROW_ACTIONS;
+
+            match (value - BUTTON_ROW_BASE) % ROW_ACTIONS {
+                ROW_VERSUS_AI => {
+                    if let Some(settings) =
+                        self.map_settings(row, LobbySort::LocalAI, &app_context.session_id)
+                    {
+                        return Some(StateSort::Game(GameState::new(
+                            versus_ai_settings(settings),
+                            app_context.session_id.clone().unwrap_or_default(),
+                        )));
+                    }
+                }
+                ROW_HOST => match &app_context.session_id {
+                    Some(session_id) => {
+                        if let Some(mut settings) =
+                            self.map_settings(row, LobbySort::Online(0), &app_context.session_id)
+                        {
+                            settings.set_max_turns(Some(LobbySettings::DEFAULT_MAX_TURNS));
+
+                            return Some(StateSort::Game(GameState::new(
+                                settings,
+                                session_id.clone(),
+                            )));
+                        }
+                    }
+                    None => self.status = Some("Not connected".to_string()),
+                },
+                ROW_REPORT => {
+                    if let (Some(session_id), Some(shared_map)) =
+                        (&app_context.session_id, self.gallery.maps.get(row))
+                    {
+                        if dialogs().confirm(&format!("Report \"{}\"?", shared_map.title)) {
+                            detach(ApiClient::new(session_id.clone()).report_map(shared_map.id));
+                            self.status = Some("Thanks, the map was reported".to_string());
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if self.page_dirty {
+            self.page_dirty = false;
+            self.dispatcher
+                .dispatch(ApiClient::default().maps(self.page), Message::Maps);
+        }
+
+        for message in self.dispatcher.take() {
+            match message {
+                Message::Maps(gallery) if self.awaiting_share => {
+                    self.awaiting_share = false;
+                    self.status = gallery
+                        .maps
+                        .first()
+                        .map(|shared_map| format!("Shared \"{}\"", shared_map.title));
+                    self.page_dirty = true;
+                }
+                Message::Maps(gallery) => {
+                    self.page = gallery.page;
+                    self.maps = gallery
+                        .maps
+                        .iter()
+                        .map(|shared_map| ArenaMap::decode(&shared_map.code).ok())
+                        .collect();
+                    self.row_interface = MapGalleryState::create_row_interface(&gallery.maps);
+                    self.gallery = gallery.clone();
+                }
+                Message::LobbyError(err) => {
+                    self.awaiting_share = false;
+                    self.status = Some(err.reason.clone());
+                }
+                _ => (),
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for MapGalleryState {
+    fn default() -> Self {
+        let button_back = ButtonElement::new(
+            (384 - 120, 360 - 32),
+            (112, 24),
+            BUTTON_BACK,
+            LabelTrim::Return,
+            LabelTheme::Default,
+            ContentElement::Text("Back".to_string(), Alignment::Center),
+        );
+
+        let button_share = ButtonElement::new(
+            (8, 360 - 32),
+            (112, 24),
+            BUTTON_SHARE,
+            LabelTrim::Glorious,
+            LabelTheme::Action,
+            ContentElement::Text("Share".to_string(), Alignment::Center),
+        );
+
+        let button_page_previous = ButtonElement::new(
+            ((384 - 64) / 2, 360 - 28),
+            (20, 16),
+            BUTTON_PAGE_PREVIOUS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            ContentElement::Sprite((48, 176), (8, 8)),
+        );
+
+        let button_page_next = ButtonElement::new(
+            ((384 - 64) / 2 + 44, 360 - 28),
+            (20, 16),
+            BUTTON_PAGE_NEXT,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            ContentElement::Sprite((56, 176), (8, 8)),
+        );
+
+        MapGalleryState {
+            interface: Interface::new(vec![
+                button_back.boxed(),
+                button_share.boxed(),
+                button_page_previous.boxed(),
+                button_page_next.boxed(),
+            ]),
+            row_interface: Interface::new(Vec::new()),
+            dispatcher: Dispatcher::default(),
+            gallery: MapGallery::default(),
+            maps: Vec::new(),
+            page: 0,
+            page_dirty: true,
+            awaiting_share: false,
+            status: None,
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use shared::{GameMode, LobbySettings, Mutator};
+
+use super::{MainMenuState, State};
+use crate::{
+    app::{
+        Alignment, App, AppContext, ButtonElement, ContentElement, Interface, LabelTheme,
+        LabelTrim, StateSort, ToggleButtonElement, UIElement, UIEvent,
+    },
+    draw::{draw_label, draw_text_centered},
+    platform::{Canvas, PlatformError},
+};
+
+const BUTTON_BACK: usize = 0;
+const BUTTON_WARMUP: usize = 1;
+const BUTTON_MODE: usize = 2;
+
+/// Mutator toggles are numbered from here, in the order of [`Mutator::ALL`].
+const BUTTON_MUTATOR_BASE: usize = 10;
+
+/// Storage key of the mutators applied to lobbies created on this device.
+const MUTATORS_KEY: &str = "mutators";
+
+/// Storage key of whether lobbies created on this device begin with a warmup.
+const WARMUP_KEY: &str = "warmup";
+
+/// Storage key of the mode lobbies created on this device are played in.
+const MODE_KEY: &str = "mode";
+
+/// Returns the [`GameMode`] lobbies created on this device are played in.
+pub fn lobby_mode() -> GameMode {
+    serde_json::from_str(&App::kv_get(MODE_KEY)).unwrap_or_default()
+}
+
+/// Returns the [`Mutator`]s applied to lobbies created on this device.
+pub fn lobby_mutators() -> Vec<Mutator> {
+    serde_json::from_str(&App::kv_get(MUTATORS_KEY)).unwrap_or_default()
+}
+
+/// Stores the [`Mutator`]s applied to lobbies created on this device.
+pub fn set_lobby_mutators(mutators: &[Mutator]) {
+    App::kv_set(
+        MUTATORS_KEY,
+        &serde_json::to_string(mutators).unwrap_or_default(),
+    );
+}
+
+/// Determines if lobbies created on this device begin with a warmup.
+pub fn lobby_warmup() -> bool {
+    App::kv_get(WARMUP_KEY) == "true"
+}
+
+/// Creates [`LobbySettings`] with the rules chosen on this device.
+pub fn custom_settings(mut settings: LobbySettings) -> LobbySettings {
+    for mutator in lobby_mutators() {
+        settings.set_mutator(mutator, true);
+    }
+
+    settings.set_warmup(lobby_warmup());
+    settings.set_mode(lobby_mode());
+
+    settings
+}
+
+/// Custom rules for the lobbies and AI games created from the main menu.
+pub struct RulesMenuState {
+    interface: Interface,
+    mutators: Vec<Mutator>,
+}
+
+impl State for RulesMenuState {
+    fn draw(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+
+        draw_label(
+            context,
+            atlas,
+            ((384 - 136) / 2, 24),
+            (136, 24),
+            "#7f3faa",
+            &ContentElement::Text("Rules".to_string(), Alignment::Center),
+            pointer,
+            frame,
+            &LabelTrim::Glorious,
+            false,
+        )?;
+
+        draw_text_centered(
+            context,
+            atlas,
+            384.0 / 2.0,
+            60.0,
+            "Applied to new lobbies and AI games",
+        )?;
+
+        self.interface
+            .draw(interface_context, atlas, pointer, frame)?;
+
+        Ok(())
+    }
+
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
+        if let Some(UIEvent::ButtonClick(value, clip_id)) =
+            self.interface.tick(&app_context.pointer)
+        {
+            app_context.audio_system.play_clip_option(clip_id);
+
+            match value {
+                BUTTON_BACK => return Some(StateSort::MainMenu(MainMenuState::default())),
+                BUTTON_WARMUP => App::kv_set(WARMUP_KEY, &(!lobby_warmup()).to_string()),
+                BUTTON_MODE => {
+                    App::kv_set(
+                        MODE_KEY,
+                        &serde_json::to_string(&lobby_mode().next()).unwrap_or_default(),
+                    );
+
+                    // Rebuilt so the button names the new mode
+                    *self = RulesMenuState::default();
+                }
+                value if value >= BUTTON_MUTATOR_BASE => {
+                    if let Some(mutator) = Mutator::ALL.get(value - BUTTON_MUTATOR_BASE) {
+                        if let Some(position) = self.mutators.iter().position(|m| m == mutator) {
+                            self.mutators.remove(position);
+                        } else {
+                            self.mutators.push(*mutator);
+                        }
+
+                        set_lobby_mutators(&self.mutators);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for RulesMenuState {
+    fn default() -> Self {
+        let mutators = lobby_mutators();
+
+        let mut elements = vec![ButtonElement::new(
+            ((384 - 88) / 2, 360 - 28),
+            (88, 16),
+            BUTTON_BACK,
+            LabelTrim::Return,
+            LabelTheme::Default,
+            ContentElement::Text("Back".to_string(), Alignment::Center),
+        )
+        .boxed()];
+
+        for (i, mutator) in Mutator::ALL.iter().enumerate() {
+            let mut button_mutator = ToggleButtonElement::new(
+                ((384 - 160) / 2, 88 + i as i32 * 28),
+                (160, 20),
+                BUTTON_MUTATOR_BASE + i,
+                LabelTrim::Round,
+                LabelTheme::Default,
+                ContentElement::Text(mutator.name().to_string(), Alignment::Center),
+            );
+            button_mutator.set_selected(mutators.contains(mutator));
+
+            elements.push(button_mutator.boxed());
+        }
+
+        let mut button_warmup = ToggleButtonElement::new(
+            ((384 - 160) / 2, 88 + Mutator::ALL.len() as i32 * 28 + 8),
+            (160, 20),
+            BUTTON_WARMUP,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            ContentElement::Text("Online warmup".to_string(), Alignment::Center),
+        );
+        button_warmup.set_selected(lobby_warmup());
+
+        elements.push(button_warmup.boxed());
+
+        let button_mode = ButtonElement::new(
+            ((384 - 160) / 2, 88 + Mutator::ALL.len() as i32 * 28 + 36),
+            (160, 20),
+            BUTTON_MODE,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            ContentElement::Text(format!("Mode: {}", lobby_mode().name()), Alignment::Center),
+        );
+
+        elements.push(button_mode.boxed());
+
+        RulesMenuState {
+            interface: Interface::new(elements),
+            mutators,
+        }
+    }
+}
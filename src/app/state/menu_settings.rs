@@ -1,50 +1,82 @@
-use wasm_bindgen::JsValue;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlInputElement};
-
-use super::{MainMenuState, State};
+use super::{CalibrationState, MainMenuState, State};
 use crate::{
+    api::{detach, ApiClient},
     app::{
-        Alignment, App, AppContext, ButtonElement, ContentElement, Interface, LabelTheme,
-        LabelTrim, StateSort, UIElement, UIEvent,
+        AimOptions, Alignment, AppContext, ButtonElement, ButtonGroupElement, ContentElement,
+        FrameLimit, Haptic, Haptics, Interface, LabelTheme, LabelTrim, RenderOptions, Settings,
+        StateSort, ToggleButtonElement, UIElement, UIEvent,
     },
+    backup::{download_backup, export_backup, paste_backup, pick_backup_file},
     draw::{draw_image, draw_label, draw_text},
+    hints::{hints_enabled, set_hints_enabled},
+    log::{self, Level},
+    platform::{dialogs, Canvas, PlatformError},
 };
 
 pub struct SettingsMenuState {
     interface: Interface,
     pub music_volume: i8,
     pub clip_volume: i8,
+    pub render_options: RenderOptions,
+    pub aim_options: AimOptions,
+    pub frame_limit: FrameLimit,
+    pub haptics: Haptics,
+    hints: bool,
 }
 
+/// Latest log lines attached to a bug report.
+const BUG_REPORT_LOG_LINES: usize = 128;
+
 const BUTTON_BACK: usize = 0;
 const BUTTON_MUSIC_MINUS: usize = 10;
 const BUTTON_MUSIC_PLUS: usize = 11;
 const BUTTON_SOUND_MINUS: usize = 12;
 const BUTTON_SOUND_PLUS: usize = 13;
+const BUTTON_HIGH_CONTRAST: usize = 20;
+const BUTTON_REDUCED_EFFECTS: usize = 21;
+#[cfg(feature = "webgl")]
+const BUTTON_WEBGL: usize = 22;
+const BUTTON_INTENT_ARROWS: usize = 23;
+const BUTTON_HINTS: usize = 24;
+const BUTTON_DIAGNOSTICS: usize = 25;
+const BUTTON_HAPTICS: usize = 26;
+const BUTTON_AUTO_PERFORMANCE: usize = 27;
+const BUTTON_REPORT_BUG: usize = 28;
+const BUTTON_FRAME_LIMIT_UNCAPPED: usize = 30;
+const BUTTON_FRAME_LIMIT_SIXTY: usize = 31;
+const BUTTON_FRAME_LIMIT_THIRTY: usize = 32;
+const BUTTON_AIM_SMOOTHING: usize = 40;
+const BUTTON_AIM_DEAD_ZONE: usize = 41;
+const BUTTON_EXPORT_DATA: usize = 50;
+const BUTTON_IMPORT_DATA: usize = 51;
+const BUTTON_PASTE_DATA: usize = 52;
+const BUTTON_RESET: usize = 60;
+const BUTTON_CALIBRATE: usize = 61;
 
 impl SettingsMenuState {
     fn save_volume(&self) {
-        App::kv_set("music_volume", self.music_volume.to_string().as_str());
-        App::kv_set("clip_volume", self.clip_volume.to_string().as_str());
+        Settings::update(|settings| {
+            settings.music_volume = self.music_volume;
+            settings.clip_volume = self.clip_volume;
+        });
     }
 
     pub fn load_volume() -> (i8, i8) {
-        let music_volume = App::kv_get("music_volume").parse::<i8>().unwrap_or(10);
-        let clip_volume = App::kv_get("clip_volume").parse::<i8>().unwrap_or(8);
+        let settings = Settings::load();
 
-        (music_volume, clip_volume)
+        (settings.music_volume, settings.clip_volume)
     }
 }
 
 impl State for SettingsMenuState {
     fn draw(
         &mut self,
-        context: &CanvasRenderingContext2d,
-        interface_context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
         app_context: &AppContext,
-    ) -> Result<(), JsValue> {
-        let frame = app_context.frame;
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
         let pointer = &app_context
             .pointer
             .teleport((-(360 - 256) / 2, -(360 - 256) / 2));
@@ -60,7 +92,7 @@ impl State for SettingsMenuState {
             "#7f3faa",
             &ContentElement::Text("Settings".to_string(), Alignment::Center),
             &app_context.pointer,
-            app_context.frame,
+            app_context.clock.frame(),
             &LabelTrim::Glorious,
             false,
         )?;
@@ -95,6 +127,26 @@ impl State for SettingsMenuState {
 
         draw_text(context, atlas, 0.0, 100.0, "Sound Volume")?;
 
+        let audio_loading = app_context.audio_system.loading();
+
+        let audio_status = if !audio_loading.done() {
+            format!(
+                "Loading sounds {}/{}",
+                audio_loading.loaded,
+                audio_loading.total()
+            )
+        } else if audio_loading.failed > 0 {
+            format!("{} sounds failed to load", audio_loading.failed)
+        } else {
+            String::new()
+        };
+
+        draw_text(context, atlas, 0.0, 134.0, &audio_status)?;
+
+        draw_text(context, atlas, 0.0, 184.0, "Frame Rate")?;
+
+        draw_text(context, atlas, 148.0, 148.0, "Aim")?;
+
         for i in (0..10).rev() {
             if self.clip_volume > i {
                 draw_image(
@@ -133,7 +185,7 @@ impl State for SettingsMenuState {
             "#7f0055",
             &ContentElement::Text("Credits".to_string(), Alignment::Center),
             &app_context.pointer,
-            app_context.frame,
+            app_context.clock.frame(),
             &LabelTrim::Glorious,
             false,
         )?;
@@ -160,12 +212,7 @@ impl State for SettingsMenuState {
         Ok(())
     }
 
-    fn tick(
-        &mut self,
-        _text_input: &HtmlInputElement,
-        app_context: &AppContext,
-    ) -> Option<StateSort> {
-        let frame = app_context.frame;
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
         let pointer = &app_context
             .pointer
             .teleport((-(360 - 256) / 2, -(360 - 256) / 2));
@@ -193,6 +240,83 @@ impl State for SettingsMenuState {
                     self.clip_volume = (self.clip_volume + 1).max(0).min(10);
                     self.save_volume();
                 }
+                BUTTON_HIGH_CONTRAST => {
+                    self.render_options.high_contrast ^= true;
+                    self.render_options.save();
+                }
+                BUTTON_REDUCED_EFFECTS => {
+                    self.render_options.reduced_effects ^= true;
+                    self.render_options.save();
+                }
+                BUTTON_INTENT_ARROWS => {
+                    self.render_options.intent_arrows ^= true;
+                    self.render_options.save();
+                }
+                BUTTON_HINTS => {
+                    self.hints ^= true;
+                    set_hints_enabled(self.hints);
+                }
+                BUTTON_DIAGNOSTICS => {
+                    self.render_options.diagnostics ^= true;
+                    self.render_options.save();
+                }
+                BUTTON_AUTO_PERFORMANCE => {
+                    self.render_options.auto_performance ^= true;
+                    self.render_options.save();
+                }
+                BUTTON_HAPTICS => {
+                    self.haptics.intensity = self.haptics.intensity.next();
+                    self.haptics.save();
+                    self.haptics.play(Haptic::Hit);
+
+                    // Rebuilt so the button names the new intensity
+                    self.interface = SettingsMenuState::default().interface;
+                }
+                #[cfg(feature = "webgl")]
+                BUTTON_WEBGL => {
+                    self.render_options.webgl ^= true;
+                    self.render_options.save();
+                }
+                BUTTON_AIM_SMOOTHING => {
+                    self.aim_options.smoothing ^= true;
+                    self.aim_options.save();
+                }
+                BUTTON_AIM_DEAD_ZONE => {
+                    self.aim_options.dead_zone ^= true;
+                    self.aim_options.save();
+                }
+                BUTTON_FRAME_LIMIT_UNCAPPED => {
+                    self.frame_limit = FrameLimit::Uncapped;
+                    self.frame_limit.save();
+                }
+                BUTTON_FRAME_LIMIT_SIXTY => {
+                    self.frame_limit = FrameLimit::Sixty;
+                    self.frame_limit.save();
+                }
+                BUTTON_FRAME_LIMIT_THIRTY => {
+                    self.frame_limit = FrameLimit::Thirty;
+                    self.frame_limit.save();
+                }
+                // Fall back to the paste box where downloads or file pickers are unavailable
+                BUTTON_EXPORT_DATA if download_backup().is_err() => {
+                    let _ = dialogs().prompt("Copy this backup data:", &export_backup());
+                }
+                BUTTON_REPORT_BUG => {
+                    if let Some(session_id) = &app_context.session_id {
+                        report_bug(session_id.clone());
+                    }
+                }
+                BUTTON_RESET if dialogs().confirm("Reset every setting to its default?") => {
+                    Settings::default().save();
+
+                    // Reloaded so the buttons show the defaults, which the app picks up from here
+                    *self = SettingsMenuState::default();
+                }
+                BUTTON_CALIBRATE => {
+                    return Some(StateSort::Calibration(CalibrationState::default()));
+                }
+                BUTTON_IMPORT_DATA if pick_backup_file().is_err() => paste_backup(),
+                BUTTON_PASTE_DATA => paste_backup(),
                 _ => (),
             }
         }
@@ -201,6 +325,25 @@ impl State for SettingsMenuState {
     }
 }
 
+/// Asks the player what went wrong and whether to attach the recent logs, then sends the report.
+fn report_bug(session_id: String) {
+    let Some(description) = dialogs().prompt("What went wrong?", "") else {
+        return;
+    };
+
+    if description.trim().is_empty() {
+        return;
+    }
+
+    let logs = if dialogs().confirm("Attach the recent logs to help find the bug?") {
+        log::recent(BUG_REPORT_LOG_LINES, Level::Debug)
+    } else {
+        Vec::new()
+    };
+
+    detach(ApiClient::new(session_id).report_bug(description, logs));
+}
+
 impl Default for SettingsMenuState {
     fn default() -> Self {
         let button_back = ButtonElement::new(
@@ -248,13 +391,238 @@ impl Default for SettingsMenuState {
             crate::app::ContentElement::Sprite((56, 184), (8, 8)),
         );
 
-        let interface = Interface::new(vec![
+        let render_options = RenderOptions::load();
+
+        let mut button_high_contrast = ToggleButtonElement::new(
+            (0, 144),
+            (136, 16),
+            BUTTON_HIGH_CONTRAST,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("High contrast".to_string(), Alignment::Center),
+        );
+        button_high_contrast.set_selected(render_options.high_contrast);
+
+        let mut button_reduced_effects = ToggleButtonElement::new(
+            (0, 164),
+            (136, 16),
+            BUTTON_REDUCED_EFFECTS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Reduced effects".to_string(), Alignment::Center),
+        );
+        button_reduced_effects.set_selected(render_options.reduced_effects);
+
+        let mut button_intent_arrows = ToggleButtonElement::new(
+            (0, 224),
+            (80, 16),
+            BUTTON_INTENT_ARROWS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Intents".to_string(), Alignment::Center),
+        );
+        button_intent_arrows.set_selected(render_options.intent_arrows);
+
+        let hints = hints_enabled();
+
+        let mut button_hints = ToggleButtonElement::new(
+            (176, 224),
+            (80, 16),
+            BUTTON_HINTS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Hints".to_string(), Alignment::Center),
+        );
+        button_hints.set_selected(hints);
+
+        let mut button_diagnostics = ToggleButtonElement::new(
+            (0, 268),
+            (124, 16),
+            BUTTON_DIAGNOSTICS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Diagnostics".to_string(), Alignment::Center),
+        );
+        button_diagnostics.set_selected(render_options.diagnostics);
+
+        let mut button_auto_performance = ToggleButtonElement::new(
+            (132, 268),
+            (124, 16),
+            BUTTON_AUTO_PERFORMANCE,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Auto performance".to_string(), Alignment::Center),
+        );
+        button_auto_performance.set_selected(render_options.auto_performance);
+
+        let haptics = Haptics::load();
+
+        let button_haptics = ButtonElement::new(
+            (0, 288),
+            (124, 16),
+            BUTTON_HAPTICS,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text(
+                format!("Vibration: {}", haptics.intensity.name()),
+                Alignment::Center,
+            ),
+        );
+
+        let button_report_bug = ButtonElement::new(
+            (132, 288),
+            (124, 16),
+            BUTTON_REPORT_BUG,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Report a bug".to_string(), Alignment::Center),
+        );
+
+        let aim_options = AimOptions::load();
+
+        let mut button_aim_smoothing = ToggleButtonElement::new(
+            (148, 164),
+            (108, 16),
+            BUTTON_AIM_SMOOTHING,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Smoothing".to_string(), Alignment::Center),
+        );
+        button_aim_smoothing.set_selected(aim_options.smoothing);
+
+        let mut button_aim_dead_zone = ToggleButtonElement::new(
+            (148, 184),
+            (108, 16),
+            BUTTON_AIM_DEAD_ZONE,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Dead-zone".to_string(), Alignment::Center),
+        );
+        button_aim_dead_zone.set_selected(aim_options.dead_zone);
+
+        let frame_limit = FrameLimit::load();
+
+        let button_frame_limit = ButtonGroupElement::new(
+            (0, 196),
+            vec![
+                ButtonElement::new(
+                    (0, 0),
+                    (40, 16),
+                    BUTTON_FRAME_LIMIT_THIRTY,
+                    LabelTrim::Round,
+                    LabelTheme::Default,
+                    crate::app::ContentElement::Text("30".to_string(), Alignment::Center),
+                ),
+                ButtonElement::new(
+                    (44, 0),
+                    (40, 16),
+                    BUTTON_FRAME_LIMIT_SIXTY,
+                    LabelTrim::Round,
+                    LabelTheme::Default,
+                    crate::app::ContentElement::Text("60".to_string(), Alignment::Center),
+                ),
+                ButtonElement::new(
+                    (88, 0),
+                    (48, 16),
+                    BUTTON_FRAME_LIMIT_UNCAPPED,
+                    LabelTrim::Round,
+                    LabelTheme::Default,
+                    crate::app::ContentElement::Text("Max".to_string(), Alignment::Center),
+                ),
+            ],
+            match frame_limit {
+                FrameLimit::Uncapped => BUTTON_FRAME_LIMIT_UNCAPPED,
+                FrameLimit::Sixty => BUTTON_FRAME_LIMIT_SIXTY,
+                FrameLimit::Thirty => BUTTON_FRAME_LIMIT_THIRTY,
+            },
+        );
+
+        let button_reset = ButtonElement::new(
+            (148, 0),
+            (108, 16),
+            BUTTON_RESET,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Reset settings".to_string(), Alignment::Center),
+        );
+
+        let button_calibrate = ButtonElement::new(
+            (0, 0),
+            (136, 16),
+            BUTTON_CALIBRATE,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Calibrate AI".to_string(), Alignment::Center),
+        );
+
+        let button_export_data = ButtonElement::new(
+            (0, 248),
+            (80, 16),
+            BUTTON_EXPORT_DATA,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Export data".to_string(), Alignment::Center),
+        );
+
+        let button_import_data = ButtonElement::new(
+            (88, 248),
+            (80, 16),
+            BUTTON_IMPORT_DATA,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Import file".to_string(), Alignment::Center),
+        );
+
+        let button_paste_data = ButtonElement::new(
+            (176, 248),
+            (80, 16),
+            BUTTON_PASTE_DATA,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            crate::app::ContentElement::Text("Paste data".to_string(), Alignment::Center),
+        );
+
+        #[allow(unused_mut)]
+        let mut elements = vec![
             button_back.boxed(),
             button_music_minus.boxed(),
             button_music_plus.boxed(),
             button_sound_minus.boxed(),
             button_sound_plus.boxed(),
-        ]);
+            button_high_contrast.boxed(),
+            button_reduced_effects.boxed(),
+            button_intent_arrows.boxed(),
+            button_hints.boxed(),
+            button_frame_limit.boxed(),
+            button_aim_smoothing.boxed(),
+            button_aim_dead_zone.boxed(),
+            button_export_data.boxed(),
+            button_import_data.boxed(),
+            button_paste_data.boxed(),
+            button_diagnostics.boxed(),
+            button_auto_performance.boxed(),
+            button_haptics.boxed(),
+            button_report_bug.boxed(),
+            button_reset.boxed(),
+            button_calibrate.boxed(),
+        ];
+
+        #[cfg(feature = "webgl")]
+        {
+            let mut button_webgl = ToggleButtonElement::new(
+                (148, 204),
+                (108, 16),
+                BUTTON_WEBGL,
+                LabelTrim::Round,
+                LabelTheme::Default,
+                crate::app::ContentElement::Text("WebGL".to_string(), Alignment::Center),
+            );
+            button_webgl.set_selected(render_options.webgl);
+
+            elements.push(button_webgl.boxed());
+        }
+
+        let interface = Interface::new(elements);
 
         let (music_volume, clip_volume) = SettingsMenuState::load_volume();
 
@@ -262,6 +630,11 @@ impl Default for SettingsMenuState {
             interface,
             music_volume,
             clip_volume,
+            render_options,
+            aim_options,
+            frame_limit,
+            haptics,
+            hints,
         }
     }
 }
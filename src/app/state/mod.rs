@@ -1,9 +1,31 @@
+mod bracket;
+mod calibration;
+mod chat;
 mod game;
+mod lobby_setup;
+mod menu_achievements;
+mod menu_critterpedia;
 mod menu_main;
+mod menu_maps;
+mod menu_rules;
 mod menu_settings;
+mod results;
 mod state;
+mod tournament;
+mod waiting_room;
 
+pub use bracket::*;
+pub use calibration::*;
+pub use chat::*;
 pub use game::*;
+pub use lobby_setup::*;
+pub use menu_achievements::*;
+pub use menu_critterpedia::*;
 pub use menu_main::*;
+pub use menu_maps::*;
+pub use menu_rules::*;
 pub use menu_settings::*;
+pub use results::*;
 pub use state::*;
+pub use tournament::*;
+pub use waiting_room::*;
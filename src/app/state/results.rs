@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+
+use shared::{Achievement, Lobby, LobbySettings, LobbySort, Replay, Result as GameResult, Team};
+
+use super::{CalibrationState, GameState, MainMenuState, State, TournamentState};
+use crate::{
+    app::{
+        Alignment, AppContext, ButtonElement, ContentElement, Interface, LabelTheme, LabelTrim,
+        Settings, StateSort, UIElement, UIEvent,
+    },
+    calibration::Calibration,
+    draw::{draw_bug_icon, draw_label, draw_text, draw_text_centered, text_length},
+    platform::{Canvas, PlatformError},
+    tournament::Tournament,
+};
+
+const BUTTON_REMATCH: usize = 1;
+const BUTTON_REPLAY: usize = 2;
+const BUTTON_LEAVE: usize = 3;
+const BUTTON_STANDINGS: usize = 4;
+const BUTTON_CALIBRATION: usize = 5;
+
+/// Height of each row of the per-bug breakdown.
+const BUG_ROW_HEIGHT: f64 = 14.0;
+
+/// Half the height of the capture graph, the distance of a full capture from its midline.
+const GRAPH_HALF_HEIGHT: f64 = 20.0;
+
+/// Width of the capture graph.
+const GRAPH_WIDTH: f64 = 240.0;
+
+/// Breakdown of a finished game, with the way back into another one.
+pub struct ResultsState {
+    interface: Interface,
+    lobby: Lobby,
+    my_team: Option<Team>,
+    bug_hits: HashMap<usize, usize>,
+    unlocked: Vec<Achievement>,
+    rematch_settings: LobbySettings,
+}
+
+impl ResultsState {
+    pub fn new(
+        lobby: Lobby,
+        my_team: Option<Team>,
+        bug_hits: HashMap<usize, usize>,
+        unlocked: Vec<Achievement>,
+    ) -> ResultsState {
+        // Hotseat games started from the tournament screen count towards its standings
+        let tournament_match = match Tournament::load() {
+            Some(mut tournament)
+                if tournament.in_progress() && lobby.is_local() && !lobby.has_ai() =>
+            {
+                let capture = lobby.game.scoring_history().iter().sum();
+                tournament.record(lobby.game.result(), capture);
+
+                true
+            }
+            _ => false,
+        };
+
+        // Games versus AI started from the calibration screen count towards it, the last one
+        // storing the recommended difficulty
+        let calibration_match = match Calibration::load() {
+            Some(mut calibration) if calibration.in_progress() && lobby.has_ai() => {
+                calibration.record(lobby.game.result());
+
+                if let Some(recommendation) = calibration.recommendation() {
+                    Settings::update(|settings| settings.bot_difficulty = Some(recommendation));
+                }
+
+                true
+            }
+            _ => false,
+        };
+
+        let rematch_settings = ResultsState::rematch_settings(&lobby);
+        let rematch_text = if rematch_settings.best_of() > 1 && !rematch_settings.series_over() {
+            "Next game"
+        } else {
+            "Rematch"
+        };
+
+        let button_rematch = if calibration_match {
+            ButtonElement::new(
+                (8, 360 - 32),
+                (112, 24),
+                BUTTON_CALIBRATION,
+                LabelTrim::Glorious,
+                LabelTheme::Action,
+                ContentElement::Text("Calibration".to_string(), Alignment::Center),
+            )
+        } else if tournament_match {
+            ButtonElement::new(
+                (8, 360 - 32),
+                (112, 24),
+                BUTTON_STANDINGS,
+                LabelTrim::Glorious,
+                LabelTheme::Action,
+                ContentElement::Text("Standings".to_string(), Alignment::Center),
+            )
+        } else {
+            ButtonElement::new(
+                (8, 360 - 32),
+                (112, 24),
+                BUTTON_REMATCH,
+                LabelTrim::Glorious,
+                LabelTheme::Action,
+                ContentElement::Text(rematch_text.to_string(), Alignment::Center),
+            )
+        };
+
+        let button_replay = ButtonElement::new(
+            ((384 - 88) / 2, 360 - 28),
+            (88, 16),
+            BUTTON_REPLAY,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            ContentElement::Text("Replay".to_string(), Alignment::Center),
+        );
+
+        let button_leave = ButtonElement::new(
+            (384 - 120, 360 - 32),
+            (112, 24),
+            BUTTON_LEAVE,
+            LabelTrim::Return,
+            LabelTheme::Default,
+            ContentElement::Text("Leave".to_string(), Alignment::Center),
+        );
+
+        let mut buttons = vec![button_rematch.boxed()];
+
+        // Games which outlasted their turn history cannot be replayed from the start
+        if Replay::new(&lobby).is_some() {
+            buttons.push(button_replay.boxed());
+        }
+
+        buttons.push(button_leave.boxed());
+
+        ResultsState {
+            interface: Interface::new(buttons),
+            lobby,
+            my_team,
+            bug_hits,
+            unlocked,
+            rematch_settings,
+        }
+    }
+
+    /// Headline and colour of the winner's banner, personalised for players.
+    fn headline(&self) -> (String, &'static str) {
+        match (self.lobby.game.result(), self.my_team) {
+            (Some(GameResult::Win(winner)), Some(my_team)) if winner == my_team => {
+                ("Victory!".to_string(), "#2a9f55")
+            }
+            (Some(GameResult::Win(_)), Some(_)) => ("Defeat".to_string(), "#7f0000"),
+            (Some(GameResult::Win(Team::Red)), None) => ("Red wins!".to_string(), "#C20005"),
+            (Some(GameResult::Win(Team::Blue)), None) => ("Blue wins!".to_string(), "#008080"),
+            _ => ("Draw!".to_string(), "#7f3faa"),
+        }
+    }
+
+    /// Settings for a fresh game like this one, online games getting a new lobby, and series
+    /// carrying on with the result of this game.
+    fn rematch_settings(lobby: &Lobby) -> LobbySettings {
+        let mut settings = lobby.settings.clone();
+
+        if let LobbySort::Online(_) = settings.sort() {
+            settings.set_sort(LobbySort::Online(0));
+        }
+
+        if let (Some(result), true) = (lobby.game.result(), settings.best_of() > 1) {
+            settings.record_series_game(result);
+        }
+
+        settings
+    }
+
+    /// Score of the series this game is part of, and its winner once over.
+    fn series_line(&self) -> Option<String> {
+        let settings = &self.rematch_settings;
+
+        if settings.best_of() <= 1 {
+            return None;
+        }
+
+        let (red, blue) = settings.series_wins();
+
+        Some(match settings.series_winner() {
+            Some(team) => format!("{team:?} takes the series {red}-{blue}"),
+            None if settings.series_over() => format!("Series drawn {red}-{blue}"),
+            None => format!("Best of {}: Red {red}-{blue} Blue", settings.best_of()),
+        })
+    }
+
+    /// Bug with the most hits landed, and its hit count.
+    fn most_valuable_bug(&self) -> Option<(usize, usize)> {
+        self.bug_hits
+            .iter()
+            .max_by_key(|(bug_index, hits)| (**hits, std::cmp::Reverse(**bug_index)))
+            .map(|(bug_index, hits)| (*bug_index, *hits))
+    }
+
+    /// Draws the capture progress after every scored turn as a line around a midline, Red above.
+    fn draw_capture_graph(&self, context: &dyn Canvas, dy: f64) -> Result<(), PlatformError> {
+        let scoring_history = self.lobby.game.scoring_history();
+        let bug_count = self.lobby.game.iter_bugs().count().max(1) as f64;
+        let dx = (384.0 - GRAPH_WIDTH) / 2.0;
+        let step = GRAPH_WIDTH / scoring_history.len().max(1) as f64;
+
+        context.save();
+        context.set_fill_style("#002a2a");
+        context.fill_rect(
+            dx - 1.0,
+            dy - GRAPH_HALF_HEIGHT - 1.0,
+            GRAPH_WIDTH + 2.0,
+            GRAPH_HALF_HEIGHT * 2.0 + 2.0,
+        );
+        context.set_fill_style("#3f3f3f");
+        context.fill_rect(dx, dy, GRAPH_WIDTH, 1.0);
+
+        context.set_line_width(1.0);
+        context.set_stroke_style("#ffffff");
+        context.begin_path();
+        context.move_to(dx, dy + 0.5);
+
+        let mut capture = 0;
+
+        for (i, tip) in scoring_history.iter().enumerate() {
+            capture += tip;
+
+            let progress = (capture as f64 / bug_count).clamp(-1.0, 1.0);
+            context.line_to(
+                dx + (i + 1) as f64 * step,
+                dy + 0.5 - progress * GRAPH_HALF_HEIGHT,
+            );
+        }
+
+        context.stroke();
+        context.restore();
+
+        Ok(())
+    }
+
+    /// Draws a column of each of a team's bugs with its name, hits and whether it finished standing.
+    fn draw_team_column(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        team: Team,
+        dx: f64,
+        dy: f64,
+    ) -> Result<(), PlatformError> {
+        let bugs = self
+            .lobby
+            .game
+            .team_bug_indices(team)
+            .into_iter()
+            .filter_map(|bug_index| {
+                self.lobby
+                    .game
+                    .get_bug(bug_index)
+                    .map(|(_, bug_data)| (bug_index, bug_data))
+            });
+
+        for (row, (bug_index, bug_data)) in bugs.enumerate() {
+            let y = dy + row as f64 * BUG_ROW_HEIGHT;
+            let hits = self.bug_hits.get(&bug_index).copied().unwrap_or_default();
+            let stats = if bug_data.health() > 1 {
+                format!("{hits}")
+            } else {
+                format!("{hits} KO")
+            };
+
+            draw_bug_icon(context, atlas, *bug_data.sort(), team, dx + 4.0, y + 4.0)?;
+            draw_text(context, atlas, dx + 12.0, y, &bug_data.name().to_string())?;
+            draw_text(
+                context,
+                atlas,
+                dx + 152.0 - text_length(&stats) as f64,
+                y,
+                &stats,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl State for ResultsState {
+    fn draw(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+
+        let (headline, color) = self.headline();
+
+        draw_label(
+            context,
+            atlas,
+            ((384 - 136) / 2, 16),
+            (136, 24),
+            color,
+            &ContentElement::Text(headline, Alignment::Center),
+            pointer,
+            frame,
+            &LabelTrim::Glorious,
+            false,
+        )?;
+
+        draw_text_centered(
+            context,
+            atlas,
+            384.0 / 2.0,
+            52.0,
+            &match self.lobby.game.surrendered() {
+                Some(team) => format!(
+                    "{} turns, {team:?} surrendered",
+                    self.lobby.game.turns_count()
+                ),
+                None => format!("{} turns", self.lobby.game.turns_count()),
+            },
+        )?;
+
+        if let Some(series_line) = self.series_line() {
+            draw_text_centered(context, atlas, 384.0 / 2.0, 360.0 - 96.0, &series_line)?;
+        }
+
+        self.draw_capture_graph(context, 88.0)?;
+
+        draw_text(context, atlas, 24.0, 120.0, "Red")?;
+        draw_text(context, atlas, 208.0, 120.0, "Blue")?;
+        self.draw_team_column(context, atlas, Team::Red, 16.0, 136.0)?;
+        self.draw_team_column(context, atlas, Team::Blue, 200.0, 136.0)?;
+
+        if let Some((bug_index, hits)) = self.most_valuable_bug() {
+            if let Some((_, bug_data)) = self.lobby.game.get_bug(bug_index) {
+                draw_text_centered(
+                    context,
+                    atlas,
+                    384.0 / 2.0,
+                    360.0 - 80.0,
+                    &format!("MVP {} ({hits} hits)", bug_data.name()),
+                )?;
+            }
+        }
+
+        if !self.unlocked.is_empty() {
+            let names: Vec<&str> = self
+                .unlocked
+                .iter()
+                .map(|achievement| achievement.name())
+                .collect();
+
+            draw_text_centered(
+                context,
+                atlas,
+                384.0 / 2.0,
+                360.0 - 60.0,
+                &format!("Unlocked {}", names.join(", ")),
+            )?;
+        }
+
+        self.interface
+            .draw(interface_context, atlas, pointer, frame)?;
+
+        Ok(())
+    }
+
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
+        if let Some(UIEvent::ButtonClick(value, clip_id)) =
+            self.interface.tick(&app_context.pointer)
+        {
+            app_context.audio_system.play_clip_option(clip_id);
+
+            match value {
+                BUTTON_REMATCH => {
+                    let settings = self.rematch_settings.clone();
+
+                    // Online games need a session to host the new lobby
+                    if let LobbySort::Online(_) = settings.sort() {
+                        if let Some(session_id) = &app_context.session_id {
+                            return Some(StateSort::Game(GameState::new(
+                                settings,
+                                session_id.clone(),
+                            )));
+                        }
+                    } else {
+                        return Some(StateSort::Game(GameState::new(
+                            settings,
+                            app_context.session_id.clone().unwrap_or_default(),
+                        )));
+                    }
+                }
+                BUTTON_REPLAY => {
+                    if let Some(replay) = Replay::new(&self.lobby) {
+                        return Some(StateSort::Game(GameState::replay(replay)));
+                    }
+                }
+                BUTTON_STANDINGS => return Some(StateSort::Tournament(TournamentState::default())),
+                BUTTON_CALIBRATION => {
+                    return Some(StateSort::Calibration(CalibrationState::default()))
+                }
+                BUTTON_LEAVE => return Some(StateSort::MainMenu(MainMenuState::default())),
+                _ => (),
+            }
+        }
+
+        None
+    }
+}
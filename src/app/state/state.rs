@@ -1,20 +1,24 @@
-use wasm_bindgen::JsValue;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlInputElement};
-
-use crate::app::{AppContext, StateSort};
+use crate::{
+    app::{AppContext, StateSort},
+    platform::{Canvas, PlatformError},
+};
 
 pub trait State {
     fn draw(
         &mut self,
-        context: &CanvasRenderingContext2d,
-        interface_context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
         app_context: &AppContext,
-    ) -> Result<(), JsValue>;
+    ) -> Result<(), PlatformError>;
 
-    fn tick(
-        &mut self,
-        text_input: &HtmlInputElement,
-        app_context: &AppContext,
-    ) -> Option<StateSort>;
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort>;
+
+    /// Handles network traffic only, called instead of [`State::tick`] while the page is hidden.
+    fn poll(&mut self, _app_context: &AppContext) {}
+
+    /// Extra lines for the performance HUD.
+    fn performance_lines(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
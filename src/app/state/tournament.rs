@@ -0,0 +1,343 @@
+use shared::{LobbySettings, LobbySort};
+
+use super::{custom_settings, BracketState, GameState, MainMenuState, State};
+use crate::{
+    app::{
+        Alignment, AppContext, ButtonElement, ConfirmButtonElement, ContentElement, Interface,
+        LabelTheme, LabelTrim, StateSort, UIElement, UIEvent,
+    },
+    draw::{draw_label, draw_text, draw_text_centered},
+    platform::{dialogs, Canvas, PlatformError},
+    tournament::{Tournament, MAX_ENTRANTS, MIN_ENTRANTS},
+};
+
+const BUTTON_BACK: usize = 0;
+const BUTTON_ADD_PLAYER: usize = 1;
+const BUTTON_CLEAR: usize = 2;
+const BUTTON_START: usize = 3;
+const BUTTON_PLAY: usize = 4;
+const BUTTON_ABANDON: usize = 5;
+const BUTTON_NEW: usize = 6;
+const BUTTON_ONLINE: usize = 7;
+
+/// Longest name a tournament player may enter.
+const MAX_NAME_LENGTH: usize = 12;
+
+/// Height of each row of the standings.
+const ROW_HEIGHT: f64 = 14.0;
+
+/// Sign-up, standings and champion of a round-robin of hotseat matches on this device.
+pub struct TournamentState {
+    interface: Interface,
+    tournament: Option<Tournament>,
+    entrants: Vec<String>,
+}
+
+impl TournamentState {
+    /// Builds the buttons for signing up players, or for playing through the tournament once it
+    /// has started.
+    fn create_interface(&self) -> Interface {
+        let button_back = ButtonElement::new(
+            (8, 360 - 28),
+            (88, 16),
+            BUTTON_BACK,
+            LabelTrim::Return,
+            LabelTheme::Default,
+            ContentElement::Text("Back".to_string(), Alignment::Center),
+        );
+
+        let mut elements = vec![button_back.boxed()];
+
+        match &self.tournament {
+            None => {
+                elements.push(
+                    ButtonElement::new(
+                        ((384 - 112) / 2, 360 - 80),
+                        (112, 20),
+                        BUTTON_ADD_PLAYER,
+                        LabelTrim::Round,
+                        LabelTheme::Default,
+                        ContentElement::Text("Add player".to_string(), Alignment::Center),
+                    )
+                    .boxed(),
+                );
+                elements.push(
+                    ButtonElement::new(
+                        ((384 - 112) / 2, 360 - 56),
+                        (112, 20),
+                        BUTTON_CLEAR,
+                        LabelTrim::Round,
+                        LabelTheme::Default,
+                        ContentElement::Text("Clear".to_string(), Alignment::Center),
+                    )
+                    .boxed(),
+                );
+                elements.push(
+                    ButtonElement::new(
+                        ((384 - 112) / 2, 360 - 32),
+                        (112, 20),
+                        BUTTON_ONLINE,
+                        LabelTrim::Round,
+                        LabelTheme::Default,
+                        ContentElement::Text("Online bracket".to_string(), Alignment::Center),
+                    )
+                    .boxed(),
+                );
+
+                if self.entrants.len() >= MIN_ENTRANTS {
+                    elements.push(
+                        ButtonElement::new(
+                            (384 - 120, 360 - 32),
+                            (112, 24),
+                            BUTTON_START,
+                            LabelTrim::Glorious,
+                            LabelTheme::Action,
+                            ContentElement::Text("Start".to_string(), Alignment::Center),
+                        )
+                        .boxed(),
+                    );
+                }
+            }
+            Some(tournament) if tournament.champion().is_some() => {
+                elements.push(
+                    ButtonElement::new(
+                        (384 - 120, 360 - 32),
+                        (112, 24),
+                        BUTTON_NEW,
+                        LabelTrim::Glorious,
+                        LabelTheme::Action,
+                        ContentElement::Text("New tournament".to_string(), Alignment::Center),
+                    )
+                    .boxed(),
+                );
+            }
+            Some(_) => {
+                elements.push(
+                    ButtonElement::new(
+                        (384 - 120, 360 - 32),
+                        (112, 24),
+                        BUTTON_PLAY,
+                        LabelTrim::Glorious,
+                        LabelTheme::Action,
+                        ContentElement::Text("Play match".to_string(), Alignment::Center),
+                    )
+                    .boxed(),
+                );
+                elements.push(
+                    ConfirmButtonElement::new(
+                        ((384 - 88) / 2, 360 - 28),
+                        (88, 16),
+                        BUTTON_ABANDON,
+                        LabelTrim::Round,
+                        LabelTheme::Default,
+                        ContentElement::Text("Abandon".to_string(), Alignment::Center),
+                    )
+                    .boxed(),
+                );
+            }
+        }
+
+        Interface::new(elements)
+    }
+
+    /// Asks for the name of another player.
+    fn add_player(&mut self) {
+        if self.entrants.len() >= MAX_ENTRANTS {
+            return;
+        }
+
+        let Some(name) = dialogs().prompt("Name of the player:", "") else {
+            return;
+        };
+
+        let name: String = name.trim().chars().take(MAX_NAME_LENGTH).collect();
+
+        if !name.is_empty() && !self.entrants.contains(&name) {
+            self.entrants.push(name);
+        }
+    }
+
+    fn draw_standings(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        tournament: &Tournament,
+        dy: f64,
+    ) -> Result<(), PlatformError> {
+        for (x, header) in [(40.0, "Player"), (184.0, "W"), (208.0, "D"), (232.0, "L")] {
+            draw_text(context, atlas, x, dy, header)?;
+        }
+        draw_text(context, atlas, 256.0, dy, "+/-")?;
+        draw_text(context, atlas, 304.0, dy, "Pts")?;
+
+        for (i, standing) in tournament.ranking().iter().enumerate() {
+            let y = dy + (i + 1) as f64 * ROW_HEIGHT;
+
+            draw_text(context, atlas, 24.0, y, &format!("{}", i + 1))?;
+            draw_text(context, atlas, 40.0, y, &standing.name)?;
+            draw_text(context, atlas, 184.0, y, &standing.wins.to_string())?;
+            draw_text(context, atlas, 208.0, y, &standing.draws.to_string())?;
+            draw_text(context, atlas, 232.0, y, &standing.losses.to_string())?;
+            draw_text(
+                context,
+                atlas,
+                256.0,
+                y,
+                &format!("{:+}", standing.capture_differential),
+            )?;
+            draw_text(context, atlas, 304.0, y, &standing.points().to_string())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl State for TournamentState {
+    fn draw(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        let frame = app_context.clock.frame();
+        let pointer = &app_context.pointer;
+
+        draw_label(
+            context,
+            atlas,
+            ((384 - 136) / 2, 16),
+            (136, 24),
+            "#7f3faa",
+            &ContentElement::Text("Tournament".to_string(), Alignment::Center),
+            pointer,
+            frame,
+            &LabelTrim::Glorious,
+            false,
+        )?;
+
+        match &self.tournament {
+            None => {
+                draw_text_centered(
+                    context,
+                    atlas,
+                    384.0 / 2.0,
+                    52.0,
+                    &format!("Add {MIN_ENTRANTS} to {MAX_ENTRANTS} players to play everyone once"),
+                )?;
+
+                for (i, name) in self.entrants.iter().enumerate() {
+                    draw_text(
+                        context,
+                        atlas,
+                        (384.0 - 112.0) / 2.0,
+                        76.0 + i as f64 * ROW_HEIGHT,
+                        &format!("{}. {name}", i + 1),
+                    )?;
+                }
+            }
+            Some(tournament) => {
+                self.draw_standings(context, atlas, tournament, 56.0)?;
+
+                let (played, total) = tournament.progress();
+                let footer_y = 360.0 - 112.0;
+
+                if let Some(champion) = tournament.champion() {
+                    draw_label(
+                        context,
+                        atlas,
+                        ((384 - 192) / 2, footer_y as i32),
+                        (192, 24),
+                        "#2a9f55",
+                        &ContentElement::Text(
+                            format!("{} is champion!", champion.name),
+                            Alignment::Center,
+                        ),
+                        pointer,
+                        frame,
+                        &LabelTrim::Glorious,
+                        false,
+                    )?;
+                } else if let Some((red, blue)) = tournament.next_match() {
+                    draw_text_centered(
+                        context,
+                        atlas,
+                        384.0 / 2.0,
+                        footer_y,
+                        &format!("Match {}/{total}", played + 1),
+                    )?;
+                    draw_text_centered(
+                        context,
+                        atlas,
+                        384.0 / 2.0,
+                        footer_y + 16.0,
+                        &format!("{red} (Red) vs {blue} (Blue)"),
+                    )?;
+                }
+            }
+        }
+
+        self.interface
+            .draw(interface_context, atlas, pointer, frame)?;
+
+        Ok(())
+    }
+
+    fn tick(&mut self, app_context: &AppContext) -> Option<StateSort> {
+        if let Some(UIEvent::ButtonClick(value, clip_id)) =
+            self.interface.tick(&app_context.pointer)
+        {
+            app_context.audio_system.play_clip_option(clip_id);
+
+            match value {
+                BUTTON_BACK => return Some(StateSort::MainMenu(MainMenuState::default())),
+                BUTTON_ONLINE => return Some(StateSort::Bracket(BracketState::default())),
+                BUTTON_ADD_PLAYER => self.add_player(),
+                BUTTON_CLEAR => self.entrants.clear(),
+                BUTTON_START if self.entrants.len() >= MIN_ENTRANTS => {
+                    let tournament = Tournament::new(std::mem::take(&mut self.entrants));
+                    tournament.save();
+
+                    self.tournament = Some(tournament);
+                }
+                BUTTON_PLAY => {
+                    if let Some(tournament) = &mut self.tournament {
+                        tournament.start_match();
+
+                        let mut lobby_settings =
+                            custom_settings(LobbySettings::new(LobbySort::Local));
+                        lobby_settings.set_max_turns(Some(LobbySettings::DEFAULT_MAX_TURNS));
+
+                        return Some(StateSort::Game(GameState::new(
+                            lobby_settings,
+                            app_context.session_id.clone().unwrap_or_default(),
+                        )));
+                    }
+                }
+                BUTTON_ABANDON | BUTTON_NEW => {
+                    Tournament::clear();
+                    self.tournament = None;
+                }
+                _ => (),
+            }
+
+            self.interface = self.create_interface();
+        }
+
+        None
+    }
+}
+
+impl Default for TournamentState {
+    fn default() -> Self {
+        let mut state = TournamentState {
+            interface: Interface::new(Vec::new()),
+            tournament: Tournament::load(),
+            entrants: Vec::new(),
+        };
+
+        state.interface = state.create_interface();
+
+        state
+    }
+}
@@ -0,0 +1,247 @@
+use shared::{Lobby, LobbySort, Message, Player, Team};
+
+use crate::{
+    app::{
+        Alignment, AppContext, ButtonElement, ContentElement, Interface, LabelTheme, LabelTrim,
+        UIElement, UIEvent,
+    },
+    draw::{draw_text, draw_text_centered},
+    net::invite_link,
+    platform::{dialogs, Canvas, PlatformError},
+};
+
+use super::{draw_team_swatch, ChatBox};
+
+const BUTTON_READY: usize = 80;
+const BUTTON_INVITE: usize = 82;
+const BUTTON_LEAVE: usize = 83;
+
+/// What the player asked for in the waiting room.
+pub enum WaitingRoomAction {
+    /// Sends a message to the lobby.
+    Send(Message),
+    /// Leaves the lobby for the main menu.
+    Leave,
+}
+
+/// The lobby of an online game while players join and ready up, drawn over the empty arena until
+/// the server starts the game.
+pub struct WaitingRoom {
+    interface: Interface,
+    /// Whether the interface was built for a player rather than a spectator, and their readiness.
+    built_for: Option<bool>,
+    /// Readiness sent to the server which the lobby state has yet to reflect.
+    sent_ready: Option<bool>,
+    /// Lobby chat below the teams.
+    chat_box: ChatBox,
+}
+
+impl Default for WaitingRoom {
+    fn default() -> Self {
+        WaitingRoom {
+            interface: WaitingRoom::create_interface(None),
+            built_for: None,
+            sent_ready: None,
+            chat_box: ChatBox::new((56, 156)),
+        }
+    }
+}
+
+impl WaitingRoom {
+    /// Builds the buttons of the waiting room, only the invite and leave buttons for spectators.
+    fn create_interface(ready: Option<bool>) -> Interface {
+        let button_invite = ButtonElement::new(
+            (384 / 2 - 40, 66),
+            (80, 16),
+            BUTTON_INVITE,
+            LabelTrim::Round,
+            LabelTheme::Default,
+            ContentElement::Text("Invite link".to_string(), Alignment::Center),
+        );
+
+        let button_leave = ButtonElement::new(
+            (56, 296),
+            (56, 16),
+            BUTTON_LEAVE,
+            LabelTrim::Return,
+            LabelTheme::Default,
+            ContentElement::Text("Leave".to_string(), Alignment::Center),
+        );
+
+        let mut children = vec![button_invite.boxed(), button_leave.boxed()];
+
+        let Some(ready) = ready else {
+            return Interface::new(children);
+        };
+
+        let (label, theme) = if ready {
+            ("Not ready", LabelTheme::Default)
+        } else {
+            ("Ready!", LabelTheme::Action)
+        };
+
+        children.push(
+            ButtonElement::new(
+                (232, 294),
+                (96, 20),
+                BUTTON_READY,
+                LabelTrim::Glorious,
+                theme,
+                ContentElement::Text(label.to_string(), Alignment::Center),
+            )
+            .boxed(),
+        );
+
+        Interface::new(children)
+    }
+
+    /// Readiness of the player as shown, preferring what was sent over the last lobby state.
+    fn ready(&self, player: &Player) -> bool {
+        self.sent_ready.unwrap_or(player.ready)
+    }
+
+    /// Handles the buttons of the waiting room, returning what the player asked for.
+    pub fn tick(&mut self, lobby: &Lobby, app_context: &AppContext) -> Option<WaitingRoomAction> {
+        let player = app_context
+            .session_id
+            .as_ref()
+            .and_then(|session_id| lobby.players().get(session_id));
+
+        if self.sent_ready == player.map(|player| player.ready) {
+            self.sent_ready = None;
+        }
+
+        let built_for = player.map(|player| self.ready(player));
+
+        if self.built_for != built_for {
+            self.built_for = built_for;
+            self.interface = WaitingRoom::create_interface(built_for);
+        }
+
+        if let Some(message) = self.chat_box.tick(lobby, app_context) {
+            return Some(WaitingRoomAction::Send(message));
+        }
+
+        let Some(UIEvent::ButtonClick(value, clip_id)) = self.interface.tick(&app_context.pointer)
+        else {
+            return None;
+        };
+
+        app_context.audio_system.play_clip_option(clip_id);
+
+        match value {
+            BUTTON_LEAVE => Some(WaitingRoomAction::Leave),
+            BUTTON_INVITE => {
+                if let LobbySort::Online(lobby_id) = lobby.settings.sort() {
+                    let _ = dialogs().prompt(
+                        "Share this link to invite players:",
+                        &invite_link(*lobby_id),
+                    );
+                }
+
+                None
+            }
+            BUTTON_READY => {
+                let ready = !self.ready(player?);
+                self.sent_ready = Some(ready);
+
+                Some(WaitingRoomAction::Send(Message::Ready(ready)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Draws the lobby ID, the players of each team with their readiness, the chat and the
+    /// buttons.
+    pub fn draw(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        lobby: &Lobby,
+        app_context: &AppContext,
+    ) -> Result<(), PlatformError> {
+        context.set_fill_style("rgba(0, 0, 0, 0.75)");
+        context.fill_rect(48.0, 32.0, 288.0, 288.0);
+
+        let headline = if lobby.all_joined() {
+            "Waiting for everyone to ready up"
+        } else {
+            "Waiting for players"
+        };
+
+        draw_text_centered(context, atlas, 384.0 / 2.0, 40.0, headline)?;
+
+        if let LobbySort::Online(lobby_id) = lobby.settings.sort() {
+            draw_text_centered(
+                context,
+                atlas,
+                384.0 / 2.0,
+                54.0,
+                &format!("Lobby ID {lobby_id}"),
+            )?;
+        }
+
+        let session_id = app_context.session_id.as_ref();
+        let my_team = session_id
+            .and_then(|session_id| lobby.players().get(session_id))
+            .map(|player| player.team);
+        // Heartbeats are compared against each other, lacking the server's clock
+        let latest_heartbeat = lobby.latest_heartbeat();
+
+        for (team, x) in [(Team::Red, 56.0), (Team::Blue, 200.0)] {
+            let mut players: Vec<(&String, &Player)> = lobby
+                .players()
+                .iter()
+                .filter(|(_, player)| player.team == team)
+                .collect();
+            players.sort_by_key(|(_, player)| player.squad.first().copied());
+
+            draw_team_swatch(context, team, x, 92.0)?;
+            draw_text(context, atlas, x + 12.0, 92.0, &format!("{team:?}"))?;
+
+            for slot in 0..lobby.settings.players_per_team() {
+                let y = 106.0 + slot as f64 * 24.0;
+
+                match players.get(slot) {
+                    Some((player_session_id, player)) => {
+                        draw_text(context, atlas, x, y, &player.nickname.to_string())?;
+
+                        let role = if Some(*player_session_id) == session_id {
+                            "You"
+                        } else if Some(team) == my_team {
+                            "Ally"
+                        } else {
+                            "Opponent"
+                        };
+
+                        let status = if Some(*player_session_id) == session_id {
+                            if self.ready(player) {
+                                "ready"
+                            } else {
+                                "not ready"
+                            }
+                        } else if !player.connected(latest_heartbeat) {
+                            "away"
+                        } else if player.ready {
+                            "ready"
+                        } else {
+                            "not ready"
+                        };
+
+                        draw_text(context, atlas, x, y + 12.0, &format!("{role} - {status}"))?;
+                    }
+                    None => draw_text(context, atlas, x, y, "Open slot")?,
+                }
+            }
+        }
+
+        self.chat_box.draw(context, atlas, lobby, app_context)?;
+
+        self.interface.draw(
+            context,
+            atlas,
+            &app_context.pointer,
+            app_context.clock.frame(),
+        )
+    }
+}
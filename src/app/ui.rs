@@ -1,27 +1,35 @@
-use wasm_bindgen::JsValue;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
-
-use super::{ClipId, Pointer};
-use crate::draw::{draw_image, draw_label, draw_text, draw_text_centered};
+use super::{ClipId, Frame, Pointer};
+use crate::{
+    draw::{draw_image, draw_label, draw_text, draw_text_centered},
+    platform::{Canvas, PlatformError},
+};
 
 pub enum UIEvent {
     ButtonClick(usize, Option<ClipId>),
 }
 
+/// Drawn position and size of a region which responds to the pointer.
+pub type HitRegion = ((i32, i32), (i32, i32));
+
 pub trait UIElement {
     fn boxed(self) -> Box<dyn UIElement>;
 
+    /// Regions of this element which respond to the pointer, for touches to snap onto.
+    fn hit_regions(&self) -> Vec<HitRegion> {
+        Vec::new()
+    }
+
     fn tick(&mut self, _pointer: &Pointer) -> Option<UIEvent> {
         None
     }
 
     fn draw(
         &self,
-        context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
         pointer: &Pointer,
-        frame: usize,
-    ) -> Result<(), JsValue>;
+        frame: Frame,
+    ) -> Result<(), PlatformError>;
 }
 
 #[derive(Clone)]
@@ -46,11 +54,11 @@ impl UIElement for ContentElement {
 
     fn draw(
         &self,
-        context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
         _pointer: &Pointer,
-        _frame: usize,
-    ) -> Result<(), JsValue> {
+        _frame: Frame,
+    ) -> Result<(), PlatformError> {
         context.save();
 
         match self {
@@ -70,7 +78,7 @@ impl UIElement for ContentElement {
                 -size.0 as f64 / 2.0,
                 -size.1 as f64 / 2.0,
             ),
-            ContentElement::None => Ok(())
+            ContentElement::None => Ok(()),
         }?;
 
         context.restore();
@@ -126,7 +134,7 @@ impl ButtonElement {
     }
 
     fn hovered(&self, pointer: &Pointer) -> bool {
-        pointer.in_region(self.position, self.size)
+        !pointer.touch_lifted() && pointer.in_region(self.position, self.size)
     }
 
     fn clicked(&self, pointer: &Pointer) -> bool {
@@ -147,13 +155,17 @@ impl UIElement for ButtonElement {
         Box::new(self)
     }
 
+    fn hit_regions(&self) -> Vec<HitRegion> {
+        vec![(self.position, self.size)]
+    }
+
     fn draw(
         &self,
-        context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
         pointer: &Pointer,
-        frame: usize,
-    ) -> Result<(), JsValue> {
+        frame: Frame,
+    ) -> Result<(), PlatformError> {
         let color = match self.class {
             LabelTheme::Default => {
                 if self.selected {
@@ -253,13 +265,17 @@ impl UIElement for ConfirmButtonElement {
         Box::new(self)
     }
 
+    fn hit_regions(&self) -> Vec<HitRegion> {
+        self.button.hit_regions()
+    }
+
     fn draw(
         &self,
-        context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
         pointer: &Pointer,
-        frame: usize,
-    ) -> Result<(), JsValue> {
+        frame: Frame,
+    ) -> Result<(), PlatformError> {
         context.save();
 
         if self.button.selected {
@@ -335,13 +351,17 @@ impl UIElement for ToggleButtonElement {
         Box::new(self)
     }
 
+    fn hit_regions(&self) -> Vec<HitRegion> {
+        self.button.hit_regions()
+    }
+
     fn draw(
         &self,
-        context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
         pointer: &Pointer,
-        frame: usize,
-    ) -> Result<(), JsValue> {
+        frame: Frame,
+    ) -> Result<(), PlatformError> {
         self.button.draw(context, atlas, pointer, frame)
     }
 
@@ -385,6 +405,14 @@ impl UIElement for ButtonGroupElement {
         Box::new(self)
     }
 
+    fn hit_regions(&self) -> Vec<HitRegion> {
+        self.buttons
+            .iter()
+            .flat_map(|button| button.hit_regions())
+            .map(|((x, y), size)| ((x + self.position.0, y + self.position.1), size))
+            .collect()
+    }
+
     fn tick(&mut self, pointer: &Pointer) -> Option<UIEvent> {
         let pointer = pointer.teleport((-self.position.0, -self.position.1));
         let mut event = None;
@@ -403,11 +431,11 @@ impl UIElement for ButtonGroupElement {
 
     fn draw(
         &self,
-        context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
         pointer: &Pointer,
-        frame: usize,
-    ) -> Result<(), JsValue> {
+        frame: Frame,
+    ) -> Result<(), PlatformError> {
         context.save();
 
         context.translate(self.position.0 as f64, self.position.1 as f64)?;
@@ -432,6 +460,24 @@ impl Interface {
     pub fn new(children: Vec<Box<dyn UIElement>>) -> Interface {
         Interface { children }
     }
+
+    /// Moves a touch onto the nearest region within its reach, so small buttons have hitboxes
+    /// larger than drawn without overlapping their neighbours.
+    fn touch_pointer(&self, pointer: &Pointer) -> Pointer {
+        if pointer.touch_reach == 0 {
+            return pointer.clone();
+        }
+
+        self.hit_regions()
+            .into_iter()
+            .map(|(position, size)| (pointer.region_distance(position, size), position, size))
+            .filter(|(distance, _, _)| *distance <= pointer.touch_reach)
+            .min_by_key(|(distance, _, _)| *distance)
+            .map_or_else(
+                || pointer.clone(),
+                |(_, position, size)| pointer.snap_to_region(position, size),
+            )
+    }
 }
 
 impl UIElement for Interface {
@@ -439,7 +485,15 @@ impl UIElement for Interface {
         Box::new(self)
     }
 
+    fn hit_regions(&self) -> Vec<HitRegion> {
+        self.children
+            .iter()
+            .flat_map(|child| child.hit_regions())
+            .collect()
+    }
+
     fn tick(&mut self, pointer: &Pointer) -> Option<UIEvent> {
+        let pointer = &self.touch_pointer(pointer);
         let mut event = None;
 
         for child in &mut self.children {
@@ -453,11 +507,13 @@ impl UIElement for Interface {
 
     fn draw(
         &self,
-        context: &CanvasRenderingContext2d,
-        atlas: &HtmlCanvasElement,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
         pointer: &Pointer,
-        frame: usize,
-    ) -> Result<(), JsValue> {
+        frame: Frame,
+    ) -> Result<(), PlatformError> {
+        let pointer = &self.touch_pointer(pointer);
+
         for child in &self.children {
             child.draw(context, atlas, pointer, frame)?;
         }
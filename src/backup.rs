@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    app::SETTINGS_KEY,
+    platform::{dialogs, spawn, system, PlatformError},
+    storage,
+};
+use serde::{Deserialize, Serialize};
+
+/// Version of the backup format, bumped whenever stored values change incompatibly.
+const BACKUP_VERSION: u32 = 1;
+
+/// Storage keys tied to this device's session, never exported or overwritten by an import.
+const SESSION_KEYS: [&str; 3] = ["session_id", "session_token", "session_region"];
+
+/// Name of the file a backup is downloaded as.
+const BACKUP_FILENAME: &str = "crittershowdown-backup.json";
+
+/// All locally stored player data, portable between browsers and devices.
+#[derive(Serialize, Deserialize)]
+struct Backup {
+    version: u32,
+    entries: BTreeMap<String, String>,
+}
+
+/// Serializes every stored setting, achievement and other local value into a JSON blob.
+pub fn export_backup() -> String {
+    let mut entries = BTreeMap::new();
+
+    if let Some(storage) = storage() {
+        for key in storage.keys() {
+            if SESSION_KEYS.contains(&key.as_str()) {
+                continue;
+            }
+
+            if let Some(value) = storage.get(&key) {
+                entries.insert(key, value);
+            }
+        }
+    }
+
+    serde_json::to_string(&Backup {
+        version: BACKUP_VERSION,
+        entries,
+    })
+    .unwrap_or_default()
+}
+
+/// Restores a JSON blob made by [`export_backup`], returning the number of values written.
+pub fn import_backup(json: &str) -> Result<usize, String> {
+    let backup: Backup =
+        serde_json::from_str(json.trim()).map_err(|err| format!("Invalid backup: {err}"))?;
+
+    if backup.version > BACKUP_VERSION {
+        return Err("This backup was made by a newer version of the game.".to_string());
+    }
+
+    let storage = storage().ok_or("Local storage is unavailable.")?;
+    let mut written = 0;
+
+    // Backups made before the settings blob carry the separate keys, migrated on the next load
+    if !backup.entries.contains_key(SETTINGS_KEY) {
+        storage.remove(SETTINGS_KEY);
+    }
+
+    for (key, value) in backup.entries {
+        if SESSION_KEYS.contains(&key.as_str()) {
+            continue;
+        }
+
+        if !storage.set(&key, &value) {
+            return Err("Local storage is full or unavailable.".to_string());
+        }
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+/// Downloads the [`export_backup`] blob as a JSON file.
+pub fn download_backup() -> Result<(), PlatformError> {
+    dialogs().save_file(BACKUP_FILENAME, &export_backup())
+}
+
+/// Opens a file picker and imports the chosen backup file, reloading the page to apply it.
+pub fn pick_backup_file() -> Result<(), PlatformError> {
+    let file = dialogs().open_file(&["json"])?;
+
+    spawn(async move {
+        match file.await {
+            Some(Ok(text)) => finish_import(&text),
+            Some(Err(_)) => {
+                dialogs().alert("The file could not be read.");
+            }
+            None => {}
+        }
+    });
+
+    Ok(())
+}
+
+/// Asks for a pasted backup and imports it, reloading the page to apply it.
+pub fn paste_backup() {
+    if let Some(json) = dialogs().prompt("Paste your backup data:", "") {
+        if !json.trim().is_empty() {
+            finish_import(&json);
+        }
+    }
+}
+
+/// Imports a backup and reports the outcome, reloading on success so every setting is re-read.
+fn finish_import(json: &str) {
+    match import_backup(json) {
+        Ok(written) => {
+            dialogs().alert(&format!("Imported {written} saved values."));
+            system().reload();
+        }
+        Err(reason) => {
+            dialogs().alert(&reason);
+        }
+    }
+}
@@ -0,0 +1,8 @@
+//! Runs Critter Showdown in a desktop window.
+
+fn main() {
+    if let Err(err) = crittershowdown::run_desktop() {
+        eprintln!("crittershowdown: {err}");
+        std::process::exit(1);
+    }
+}
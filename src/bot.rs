@@ -0,0 +1,86 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use js_sys::Function;
+use nalgebra::{vector, Vector2};
+use shared::{BotController, GameView, VecMap, ZoneBot};
+use wasm_bindgen::{prelude::*, JsValue};
+
+use crate::warn;
+
+thread_local! {
+    static BOT_REGISTRY: RefCell<BotRegistry> = RefCell::new(BotRegistry::default());
+}
+
+#[derive(Default)]
+struct BotRegistry {
+    bots: HashMap<String, Function>,
+    active: Option<String>,
+}
+
+/// A [`BotController`] backed by a JavaScript function.
+///
+/// The function receives the [`GameView`] as a plain object and returns an array of `[index, x, y]` impulse intents.
+pub struct JsBot {
+    name: String,
+    plan: Function,
+}
+
+impl BotController for JsBot {
+    fn plan(&mut self, view: &GameView) -> VecMap<usize, Vector2<f32>> {
+        let result = serde_wasm_bindgen::to_value(view)
+            .map_err(JsValue::from)
+            .and_then(|view| self.plan.call1(&JsValue::NULL, &view))
+            .and_then(|value| {
+                serde_wasm_bindgen::from_value::<Vec<(usize, f32, f32)>>(value)
+                    .map_err(JsValue::from)
+            });
+
+        match result {
+            Ok(intents) => intents
+                .into_iter()
+                .map(|(index, x, y)| (index, vector![x, y]))
+                .collect(),
+            Err(err) => {
+                warn!("bot `{}` failed: {err:?}", self.name);
+                VecMap::new()
+            }
+        }
+    }
+}
+
+/// Registers a scripted bot under a name and makes it the active opponent for games versus AI.
+#[wasm_bindgen]
+pub fn register_bot(name: String, plan: Function) {
+    BOT_REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        registry.bots.insert(name.clone(), plan);
+        registry.active = Some(name);
+    });
+}
+
+/// Selects a previously registered bot, or the built-in bot when the name is unknown.
+#[wasm_bindgen]
+pub fn select_bot(name: String) {
+    BOT_REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        registry.active = registry.bots.contains_key(&name).then_some(name);
+    });
+}
+
+/// Returns the active bot, falling back to the given built-in [`ZoneBot`].
+pub fn active_bot(zone_bot: ZoneBot) -> Box<dyn BotController> {
+    BOT_REGISTRY.with(|registry| {
+        let registry = registry.borrow();
+
+        match &registry.active {
+            Some(name) => match registry.bots.get(name) {
+                Some(plan) => Box::new(JsBot {
+                    name: name.clone(),
+                    plan: plan.clone(),
+                }) as Box<dyn BotController>,
+                None => Box::new(zone_bot),
+            },
+            None => Box::new(zone_bot),
+        }
+    })
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use shared::{BotDifficulty, LobbySettings, Result as GameResult, Team};
+
+use crate::app::{App, Settings};
+
+/// Storage key of the calibration in progress on this device.
+const CALIBRATION_KEY: &str = "calibration";
+
+/// Turn limit of each calibration match, short enough to play all of them in a few minutes.
+pub const CALIBRATION_TURNS: usize = 8;
+
+/// A run of quick matches as Red against the AI at every [`BotDifficulty`], from the easiest,
+/// whose results recommend the difficulty of later games versus AI.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Calibration {
+    /// Whether the player won each match played so far, in the order of [`BotDifficulty::ALL`].
+    wins: Vec<bool>,
+    /// Whether the next match has been started and awaits its result.
+    in_progress: bool,
+}
+
+impl Calibration {
+    /// Returns the calibration in progress on this device, if any.
+    pub fn load() -> Option<Calibration> {
+        serde_json::from_str(&App::kv_get(CALIBRATION_KEY)).ok()
+    }
+
+    /// Persists the calibration, so it survives reloads between matches.
+    pub fn save(&self) {
+        App::kv_set(
+            CALIBRATION_KEY,
+            &serde_json::to_string(self).unwrap_or_default(),
+        );
+    }
+
+    /// Abandons the calibration in progress on this device.
+    pub fn clear() {
+        App::kv_remove(CALIBRATION_KEY);
+    }
+
+    /// Difficulty of the AI in the next match, if any is left.
+    pub fn next_difficulty(&self) -> Option<BotDifficulty> {
+        BotDifficulty::ALL.get(self.wins.len()).copied()
+    }
+
+    /// Whether the player won the match against each difficulty, for those played so far.
+    pub fn results(&self) -> impl Iterator<Item = (BotDifficulty, bool)> + '_ {
+        BotDifficulty::ALL
+            .into_iter()
+            .zip(self.wins.iter().copied())
+    }
+
+    /// Marks the next match as started, so the result of the next game versus AI counts for it.
+    pub fn start_match(&mut self) {
+        self.in_progress = self.next_difficulty().is_some();
+        self.save();
+    }
+
+    /// Determines if a started match awaits its result.
+    pub fn in_progress(&self) -> bool {
+        self.in_progress
+    }
+
+    /// Records the result of the started match, which the player played as Red.
+    pub fn record(&mut self, result: Option<GameResult>) {
+        if !self.in_progress || self.next_difficulty().is_none() {
+            return;
+        }
+
+        self.wins.push(result == Some(GameResult::Win(Team::Red)));
+        self.in_progress = false;
+        self.save();
+    }
+
+    /// The difficulty to play at once every match is played: the easiest one the player did not
+    /// beat, or the hardest if they beat them all.
+    pub fn recommendation(&self) -> Option<BotDifficulty> {
+        if self.next_difficulty().is_some() {
+            return None;
+        }
+
+        Some(
+            self.results()
+                .find(|(_, won)| !won)
+                .map_or(BotDifficulty::Hard, |(difficulty, _)| difficulty),
+        )
+    }
+}
+
+/// Sets the AI of a game versus AI to the difficulty stored in the settings, the default until a
+/// calibration recommends one.
+pub fn versus_ai_settings(mut settings: LobbySettings) -> LobbySettings {
+    settings.set_bot_difficulty(Settings::load().bot_difficulty.unwrap_or_default());
+
+    settings
+}
@@ -1,88 +1,129 @@
+use std::collections::VecDeque;
+
 use nalgebra::Vector2;
 use rapier2d::{dynamics::RigidBody, geometry::Collider};
-use shared::{BugData, PropData};
-use wasm_bindgen::{Clamped, JsValue};
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+use shared::{
+    ArenaGeometry, ArenaMap, BugData, BugSort, Bullseye, CaptureHeatmap, Corpse, ForceField, Game,
+    Obstruction, PropData, PropSort, Team, TerrainData, TerrainSort, HEATMAP_CELLS,
+    RING_OUT_RADIUS, SOCCER_BALL_RADIUS, SOCCER_GOAL_HALF_WIDTH, SUMO_MAX_INSET, SUMO_RING_SHRINK,
+    TEAM_SIZE,
+};
 
-use crate::app::{ContentElement, LabelTrim, Particle, ParticleSort, Pointer, UIElement};
+use crate::{
+    app::{ContentElement, Frame, LabelTrim, Particle, ParticleSort, Pointer, UIElement},
+    platform::{Canvas, PlatformError},
+};
 
 pub fn draw_image(
-    context: &CanvasRenderingContext2d,
-    atlas: &HtmlCanvasElement,
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
     sx: f64,
     sy: f64,
     sw: f64,
     sh: f64,
     dx: f64,
     dy: f64,
-) -> Result<(), JsValue> {
-    context.draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-        atlas,
-        sx,
-        sy,
-        sw,
-        sh,
-        dx.floor(),
-        dy.floor(),
-        sw,
-        sh,
-    )?;
+) -> Result<(), PlatformError> {
+    context.draw_image(atlas, (sx, sy, sw, sh), (dx.floor(), dy.floor(), sw, sh))?;
 
     Ok(())
 }
 
 pub fn draw_image_centered(
-    context: &CanvasRenderingContext2d,
-    atlas: &HtmlCanvasElement,
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
     sx: f64,
     sy: f64,
     sw: f64,
     sh: f64,
     dx: f64,
     dy: f64,
-) -> Result<(), JsValue> {
-    context.draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+) -> Result<(), PlatformError> {
+    context.draw_image(
         atlas,
-        sx,
-        sy,
-        sw,
-        sh,
-        (dx - sw / 2.0).floor(),
-        (dy - sh / 2.0).floor(),
-        sw,
-        sh,
+        (sx, sy, sw, sh),
+        ((dx - sw / 2.0).floor(), (dy - sh / 2.0).floor(), sw, sh),
     )?;
 
     Ok(())
 }
 
-const LOCAL_SCALE: f64 = 16.0;
+/// Atlas rows holding the team-tinted copies of the bug sprites, Red then Blue.
+const TEAM_SPRITES_Y: [f64; 2] = [360.0, 392.0];
 
-pub fn local_to_screen(local: &Vector2<f32>) -> (f64, f64) {
-    (
-        local.x as f64 * LOCAL_SCALE + 384.0 / 2.0,
-        local.y as f64 * LOCAL_SCALE + 360.0 / 2.0,
-    )
+/// Copies an atlas region to another location, mapping every pixel through `swap`.
+pub fn palette_swap(
+    context: &dyn Canvas,
+    (sx, sy, sw, sh): (f64, f64, f64, f64),
+    (dx, dy): (f64, f64),
+    swap: impl Fn([u8; 4]) -> [u8; 4],
+) -> Result<(), PlatformError> {
+    let source = context.get_image_data(sx, sy, sw, sh)?;
+
+    let swapped: Vec<u8> = source
+        .chunks_exact(4)
+        .flat_map(|pixel| swap([pixel[0], pixel[1], pixel[2], pixel[3]]))
+        .collect();
+
+    context.put_image_data(&swapped, sw as u32, sh as u32, dx, dy)?;
+
+    Ok(())
 }
-pub fn screen_to_local(screen: (f64, f64)) -> (f64, f64) {
-    (
-        (screen.0 - 384.0 / 2.0) / LOCAL_SCALE,
-        (screen.1 - 360.0 / 2.0) / LOCAL_SCALE,
-    )
+
+/// Generates the team-tinted bug sprites from the shared ones.
+pub fn generate_team_sprites(atlas_context: &dyn Canvas) -> Result<(), PlatformError> {
+    for (tint, dy) in [[255.0, 80.0, 64.0], [64.0, 200.0, 255.0]]
+        .iter()
+        .zip(TEAM_SPRITES_Y)
+    {
+        palette_swap(atlas_context, (0.0, 0.0, 48.0, 32.0), (0.0, dy), |pixel| {
+            if pixel[3] == 0 {
+                return pixel;
+            }
+
+            let luminance =
+                (pixel[0] as f64 * 0.3 + pixel[1] as f64 * 0.59 + pixel[2] as f64 * 0.11) / 255.0;
+            let channel = |i: usize| {
+                (pixel[i] as f64 * 0.45 + tint[i] * luminance * 0.7).clamp(0.0, 255.0) as u8
+            };
+
+            [channel(0), channel(1), channel(2), pixel[3]]
+        })?;
+    }
+
+    Ok(())
 }
 
 pub fn draw_bug(
-    context: &CanvasRenderingContext2d,
-    atlas: &HtmlCanvasElement,
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
+    geometry: &ArenaGeometry,
     (rigid_body, bug_data): (&RigidBody, &BugData),
     index: usize,
-    frame: usize,
-) -> Result<(), JsValue> {
-    let (dx, dy) = local_to_screen(rigid_body.translation());
+    frame: Frame,
+) -> Result<(), PlatformError> {
+    let (dx, dy) = geometry.local_to_screen(rigid_body.translation());
     let direction = rigid_body.linvel().x.signum() as f64;
 
     context.save();
     context.translate(dx.round(), dy.round())?;
+
+    // A faint notch marks the front of the shell, where impacts do less damage
+    let facing = bug_data.facing();
+
+    if *facing != Vector2::zeros() {
+        context.save();
+        context.set_global_alpha(0.5);
+        context.set_fill_style("#ffffff");
+        context.fill_rect(
+            (facing.x as f64 * 10.0).round() - 1.0,
+            (facing.y as f64 * 10.0).round() - 1.0,
+            2.0,
+            2.0,
+        );
+        context.restore();
+    }
+
     context.scale(direction, 1.0)?;
     draw_bugdata(context, atlas, bug_data, index, frame)?;
     context.restore();
@@ -90,25 +131,97 @@ pub fn draw_bug(
     Ok(())
 }
 
+/// Ticks over which a corpse squashes flat after its bug is knocked out.
+const CORPSE_SQUASH_TICKS: f64 = 30.0;
+
+/// Draws the decal of a knocked out bug, squashing it flat at first and fading it out as it ages.
+///
+/// `age` is the fraction of the corpse's lifetime which has passed.
+pub fn draw_corpse(
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
+    geometry: &ArenaGeometry,
+    corpse: &Corpse,
+    age_ticks: u64,
+    age: f64,
+    index: usize,
+) -> Result<(), PlatformError> {
+    let (dx, dy) = geometry.local_to_screen(&corpse.position);
+    let squash = 1.0 - (age_ticks as f64 / CORPSE_SQUASH_TICKS).min(1.0) * 0.6;
+
+    let mut bug_data = BugData::new(corpse.sort, corpse.team);
+    bug_data.add_health(-(corpse.sort.max_health() as isize));
+
+    context.save();
+    context.set_global_alpha(0.6 * (1.0 - age.clamp(0.0, 1.0)));
+    context.translate(dx.round(), dy.round() + 4.0 * (1.0 - squash))?;
+    context.scale(1.0 + (1.0 - squash) * 0.5, squash)?;
+    // Corpses do not animate, so always draw the same frame
+    draw_bugdata(context, atlas, &bug_data, index, 0)?;
+    context.restore();
+
+    Ok(())
+}
+
+/// Draws the still sprite of a bug sort in its team's colours, centred on a point.
+pub fn draw_bug_icon(
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
+    sort: BugSort,
+    team: Team,
+    dx: f64,
+    dy: f64,
+) -> Result<(), PlatformError> {
+    let bug_offset = match sort {
+        BugSort::Beetle => 0.0,
+        BugSort::Ladybug => 1.0,
+        BugSort::Ant => 2.0,
+    };
+
+    let team_y = match team {
+        Team::Red => TEAM_SPRITES_Y[0],
+        Team::Blue => TEAM_SPRITES_Y[1],
+    };
+
+    draw_image_centered(
+        context,
+        atlas,
+        16.0 * bug_offset,
+        team_y,
+        16.0,
+        16.0,
+        dx,
+        dy,
+    )
+}
+
 pub fn draw_bugdata(
-    context: &CanvasRenderingContext2d,
-    atlas: &HtmlCanvasElement,
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
     bug_data: &BugData,
     index: usize,
-    frame: usize,
-) -> Result<(), JsValue> {
+    frame: Frame,
+) -> Result<(), PlatformError> {
     let bug_offset = match bug_data.sort() {
         shared::BugSort::Beetle => 0.0,
         shared::BugSort::Ladybug => 1.0,
         shared::BugSort::Ant => 2.0,
     };
 
+    let team_y = match bug_data.team() {
+        shared::Team::Red => TEAM_SPRITES_Y[0],
+        shared::Team::Blue => TEAM_SPRITES_Y[1],
+    };
+
+    // Bugs step out of sync with each other, on one of three cadences
+    let phase = (index % 3) as Frame;
+
     if bug_data.health() > 1 {
         draw_image_centered(
             context,
             atlas,
             16.0 * bug_offset,
-            16.0 * (((frame / (6 + (index % 3)) + (index % 3)) % 2) as f64),
+            team_y + 16.0 * (((frame / (6 + phase) + phase) % 2) as f64),
             16.0,
             16.0,
             0.0,
@@ -139,6 +252,8 @@ pub fn draw_bugdata(
                 10.0,
             )?,
         }
+
+        draw_stamina_pips(context, bug_data)?;
     } else {
         context.save();
         context.scale(1.0, -1.0)?;
@@ -146,7 +261,7 @@ pub fn draw_bugdata(
             context,
             atlas,
             16.0 * bug_offset,
-            16.0 * (((frame / (6 + (index % 3)) + (index % 3)) % 2) as f64),
+            team_y + 16.0 * (((frame / (6 + phase) + phase) % 2) as f64),
             16.0,
             16.0,
             0.0,
@@ -159,14 +274,30 @@ pub fn draw_bugdata(
     Ok(())
 }
 
+fn draw_stamina_pips(context: &dyn Canvas, bug_data: &BugData) -> Result<(), PlatformError> {
+    const PIP_STAMINA: f32 = 2.0;
+
+    let pips = (bug_data.sort().max_stamina() / PIP_STAMINA).ceil() as usize;
+    let filled = (bug_data.stamina() / PIP_STAMINA).floor() as usize;
+    let left = -((pips * 3 - 1) as f64) / 2.0;
+
+    for pip in 0..pips {
+        context.set_fill_style(if pip < filled { "#ffd020" } else { "#3a3a3a" });
+        context.fill_rect((left + pip as f64 * 3.0).floor(), 14.0, 2.0, 2.0);
+    }
+
+    Ok(())
+}
+
 pub fn draw_prop(
-    context: &CanvasRenderingContext2d,
-    atlas: &HtmlCanvasElement,
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
+    geometry: &ArenaGeometry,
     (collider, prop_data): (&Collider, &PropData),
     index: usize,
-    frame: usize,
-) -> Result<(), JsValue> {
-    let (dx, dy) = local_to_screen(collider.translation());
+    frame: Frame,
+) -> Result<(), PlatformError> {
+    let (dx, dy) = geometry.local_to_screen(collider.translation());
 
     context.save();
     context.translate(dx.round(), dy.round())?;
@@ -176,31 +307,327 @@ pub fn draw_prop(
     Ok(())
 }
 
+/// Draws the mouth of a goal under [`shared::GameMode::Soccer`], netted in the colour of the
+/// team defending it.
+pub fn draw_goal(
+    context: &dyn Canvas,
+    geometry: &ArenaGeometry,
+    centre: &Vector2<f32>,
+    defender: Team,
+) -> Result<(), PlatformError> {
+    let (dx, dy) = geometry.local_to_screen(centre);
+    let width = geometry.to_screen_length(1.0);
+    let height = geometry.to_screen_length(SOCCER_GOAL_HALF_WIDTH * 2.0);
+    let (left, top) = ((dx - width / 2.0).floor(), (dy - height / 2.0).floor());
+
+    context.save();
+
+    context.set_fill_style(match defender {
+        Team::Red => "#c200055f",
+        Team::Blue => "#00c2bd5f",
+    });
+    context.fill_rect(left, top, width, height);
+
+    context.begin_path();
+
+    for i in (0..height as i32).step_by(4) {
+        context.move_to(left, top + i as f64 + 0.5);
+        context.line_to(left + width, top + i as f64 + 0.5);
+    }
+
+    context.set_line_width(1.0);
+    context.set_stroke_style("#ffffff3f");
+    context.stroke();
+
+    context.restore();
+
+    Ok(())
+}
+
+/// Draws a bullseye of target practice as rings which pulse while it waits to be reached.
+pub fn draw_bullseye(
+    context: &dyn Canvas,
+    geometry: &ArenaGeometry,
+    bullseye: &Bullseye,
+    frame: Frame,
+) -> Result<(), PlatformError> {
+    let (dx, dy) = geometry.local_to_screen(&bullseye.position);
+    let radius = geometry.to_screen_length(bullseye.radius);
+    let pulse = ((frame % 60) as f64 / 60.0 * std::f64::consts::TAU).sin() * 0.5 + 0.5;
+
+    context.save();
+    context.translate(dx.round(), dy.round())?;
+
+    for (i, color) in ["#c200055f", "#ffffff5f", "#c200059f"]
+        .into_iter()
+        .enumerate()
+    {
+        context.begin_path();
+        context.arc(
+            0.0,
+            0.0,
+            radius * (3 - i) as f64 / 3.0,
+            0.0,
+            std::f64::consts::TAU,
+        )?;
+        context.set_fill_style(color);
+        context.fill();
+    }
+
+    context.begin_path();
+    context.arc(0.0, 0.0, radius + pulse * 2.0, 0.0, std::f64::consts::TAU)?;
+    context.set_line_width(1.0);
+    context.set_stroke_style("#ffffff");
+    context.stroke();
+
+    context.restore();
+
+    Ok(())
+}
+
+/// Draws the ball under [`shared::GameMode::Soccer`], its seam turning as it rolls.
+pub fn draw_ball(
+    context: &dyn Canvas,
+    geometry: &ArenaGeometry,
+    rigid_body: &RigidBody,
+) -> Result<(), PlatformError> {
+    let (dx, dy) = geometry.local_to_screen(rigid_body.translation());
+    let radius = geometry.to_screen_length(SOCCER_BALL_RADIUS);
+    let angle = rigid_body.rotation().angle() as f64;
+
+    context.save();
+    context.translate(dx.round(), dy.round())?;
+
+    context.begin_path();
+    context.arc(0.0, 0.0, radius, 0.0, std::f64::consts::TAU)?;
+    context.set_fill_style("#ffffff");
+    context.fill();
+    context.set_line_width(1.0);
+    context.set_stroke_style("#000000");
+    context.stroke();
+
+    context.begin_path();
+    context.move_to(angle.cos() * radius, angle.sin() * radius);
+    context.line_to(-angle.cos() * radius, -angle.sin() * radius);
+    context.stroke();
+
+    context.restore();
+
+    Ok(())
+}
+
 pub fn draw_propdata(
-    context: &CanvasRenderingContext2d,
-    atlas: &HtmlCanvasElement,
-    _prop_data: &PropData,
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
+    prop_data: &PropData,
     index: usize,
-    frame: usize,
-) -> Result<(), JsValue> {
-    if index % 2 == 0 {
-        draw_image_centered(context, atlas, 0.0, 144.0, 16.0, 16.0, 0.0, 0.0)?;
-    } else {
-        draw_image_centered(context, atlas, 16.0, 144.0, 16.0, 16.0, 0.0, 0.0)?;
+    _frame: Frame,
+) -> Result<(), PlatformError> {
+    match prop_data.sort() {
+        PropSort::Pillar => {
+            if index % 2 == 0 {
+                draw_image_centered(context, atlas, 0.0, 144.0, 16.0, 16.0, 0.0, 0.0)?;
+            } else {
+                draw_image_centered(context, atlas, 16.0, 144.0, 16.0, 16.0, 0.0, 0.0)?;
+            }
+        }
+        PropSort::Rock => {
+            draw_image_centered(context, atlas, 16.0, 144.0, 16.0, 16.0, 0.0, 0.0)?;
+
+            let cracks = (prop_data.damage() * 4.0).floor() as usize;
+
+            if cracks > 0 {
+                context.begin_path();
+
+                for crack in 0..cracks {
+                    let angle = crack as f64 * 2.2 + index as f64;
+
+                    context.move_to(0.0, 0.0);
+                    context.line_to(angle.cos() * 7.0, angle.sin() * 7.0);
+                }
+
+                context.set_line_width(1.0);
+                context.set_stroke_style("#000000");
+                context.stroke();
+            }
+        }
+        PropSort::Boulder => {
+            context.begin_path();
+            context.arc(0.0, 0.0, 11.0, 0.0, std::f64::consts::TAU)?;
+            context.set_fill_style("#6a6a6a");
+            context.fill();
+            context.set_line_width(2.0);
+            context.set_stroke_style("#2a2a2a");
+            context.stroke();
+
+            context.begin_path();
+            context.arc(-3.0, -3.0, 4.0, 0.0, std::f64::consts::TAU)?;
+            context.set_fill_style("#8a8a8a");
+            context.fill();
+        }
     }
 
     Ok(())
 }
 
+pub fn draw_terrain(
+    context: &dyn Canvas,
+    geometry: &ArenaGeometry,
+    (collider, terrain_data): (&Collider, &TerrainData),
+    high_contrast: bool,
+) -> Result<(), PlatformError> {
+    let (dx, dy) = geometry.local_to_screen(collider.translation());
+    let radius = geometry.to_screen_length(terrain_data.radius());
+
+    let color = match terrain_data.sort() {
+        TerrainSort::Mud => "#5a3a1a",
+        TerrainSort::Ice => "#b0e8ff",
+        TerrainSort::Boost(_) => "#ffd020",
+    };
+
+    context.save();
+    context.translate(dx.round(), dy.round())?;
+
+    context.begin_path();
+    context.arc(0.0, 0.0, radius, 0.0, std::f64::consts::TAU)?;
+    context.set_global_alpha(if high_contrast { 1.0 } else { 0.5 });
+    context.set_fill_style(color);
+    context.fill();
+
+    if let TerrainSort::Boost(direction) = terrain_data.sort() {
+        let direction = direction.normalize();
+        let (ux, uy) = (direction.x as f64, direction.y as f64);
+        let length = radius * 0.6;
+
+        context.begin_path();
+        context.move_to(-ux * length, -uy * length);
+        context.line_to(ux * length, uy * length);
+        context.move_to(ux * length - (ux + uy) * 4.0, uy * length - (uy - ux) * 4.0);
+        context.line_to(ux * length, uy * length);
+        context.line_to(ux * length - (ux - uy) * 4.0, uy * length - (uy + ux) * 4.0);
+        context.set_global_alpha(1.0);
+        context.set_line_width(2.0);
+        context.set_stroke_style("#000000");
+        context.stroke();
+    }
+
+    context.restore();
+
+    Ok(())
+}
+
+/// Paints a [`CaptureHeatmap`] onto its offscreen canvas of `size` pixels, clipped to the zone.
+///
+/// Each team's heat is tinted in its colour and scaled against the hottest cell of the map.
+pub fn draw_capture_heatmap(
+    context: &dyn Canvas,
+    heatmap: &CaptureHeatmap,
+    size: f64,
+) -> Result<(), PlatformError> {
+    let cell_size = size / HEATMAP_CELLS as f64;
+    let peak = heatmap.peak().max(1.0) as f64;
+
+    context.clear_rect(0.0, 0.0, size, size);
+
+    context.save();
+    context.begin_path();
+    context.arc(
+        size / 2.0,
+        size / 2.0,
+        size / 2.0,
+        0.0,
+        std::f64::consts::TAU,
+    )?;
+    context.clip();
+
+    for row in 0..HEATMAP_CELLS {
+        for column in 0..HEATMAP_CELLS {
+            let (red, blue) = heatmap.cell(column, row);
+
+            for (heat, color) in [(red, "#ff5040"), (blue, "#40c8ff")] {
+                if heat > 0.0 {
+                    context.set_global_alpha((heat as f64 / peak).min(1.0) * 0.4);
+                    context.set_fill_style(color);
+                    context.fill_rect(
+                        column as f64 * cell_size,
+                        row as f64 * cell_size,
+                        cell_size,
+                        cell_size,
+                    );
+                }
+            }
+        }
+    }
+
+    context.restore();
+
+    Ok(())
+}
+
+pub fn draw_force_field(
+    context: &dyn Canvas,
+    force_field: &ForceField,
+    dx: f64,
+    dy: f64,
+    emphasis: f64,
+) -> Result<(), PlatformError> {
+    context.save();
+    context.translate(dx, dy)?;
+
+    context.begin_path();
+    context.arc(0.0, 0.0, 8.0 + emphasis * 2.0, 0.0, std::f64::consts::TAU)?;
+    context.set_fill_style("#002a2a");
+    context.fill();
+
+    context.begin_path();
+
+    match force_field {
+        ForceField::Calm => (),
+        ForceField::Wind(wind) => {
+            let angle = (wind.y as f64).atan2(wind.x as f64);
+
+            context.rotate(angle)?;
+            context.move_to(-5.0, 0.0);
+            context.line_to(5.0, 0.0);
+            context.move_to(2.0, -3.0);
+            context.line_to(5.0, 0.0);
+            context.line_to(2.0, 3.0);
+        }
+        ForceField::Current { pull, swirl } => {
+            let clockwise = *swirl < 0.0;
+            let (start, end) = (0.4, std::f64::consts::TAU - 0.4);
+
+            context.arc_with_anticlockwise(0.0, 0.0, 5.0, start, end, clockwise)?;
+
+            if *pull != 0.0 {
+                let tip = if *pull > 0.0 { 0.0 } else { 3.0 };
+
+                context.move_to(3.0 - tip, 0.0);
+                context.line_to(tip, 0.0);
+            }
+        }
+    }
+
+    context.set_line_width(1.5);
+    context.set_stroke_style("#ffffff");
+    context.stroke();
+
+    context.restore();
+
+    Ok(())
+}
+
 pub fn draw_bug_impulse(
-    context: &CanvasRenderingContext2d,
-    atlas: &HtmlCanvasElement,
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
+    geometry: &ArenaGeometry,
     (rigid_body, bug_data): (&RigidBody, &BugData),
     _index: usize,
-    _frame: usize,
-) -> Result<(), JsValue> {
-    let (ox, oy) = local_to_screen(rigid_body.translation());
-    let (dx, dy) = local_to_screen(&(rigid_body.translation() + bug_data.impulse_intent()));
+    _frame: Frame,
+) -> Result<(), PlatformError> {
+    let (ox, oy) = geometry.local_to_screen(rigid_body.translation());
+    let (dx, dy) =
+        geometry.local_to_screen(&(rigid_body.translation() + bug_data.impulse_intent()));
 
     let length = (dy - oy).hypot(dx - ox);
 
@@ -230,10 +657,10 @@ pub fn draw_bug_impulse(
 // }
 
 // pub fn draw_sprite(
-//     context: &CanvasRenderingContext2d,
-//     atlas: &HtmlCanvasElement,
+//     context: &dyn Canvas,
+//     atlas: &dyn Canvas,
 //     sprite: &Sprite,
-// ) -> Result<(), JsValue> {
+// ) -> Result<(), PlatformError> {
 //     draw_image(
 //         context,
 //         atlas,
@@ -272,12 +699,12 @@ pub fn text_length(text: &str) -> isize {
 }
 
 pub fn draw_text(
-    context: &CanvasRenderingContext2d,
-    atlas: &HtmlCanvasElement,
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
     dx: f64,
     dy: f64,
     text: &str,
-) -> Result<(), JsValue> {
+) -> Result<(), PlatformError> {
     let mut kerning_acc: isize = 0;
 
     for (i, char) in text.chars().enumerate() {
@@ -303,12 +730,12 @@ pub fn draw_text(
 }
 
 pub fn draw_text_centered(
-    context: &CanvasRenderingContext2d,
-    atlas: &HtmlCanvasElement,
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
     dx: f64,
     dy: f64,
     text: &str,
-) -> Result<(), JsValue> {
+) -> Result<(), PlatformError> {
     draw_text(
         context,
         atlas,
@@ -318,62 +745,62 @@ pub fn draw_text_centered(
     )
 }
 
+/// Atlas X coordinate and the number of quarter turns of a particle's current sprite.
+pub fn particle_sprite(particle: &Particle, frame: Frame) -> (f64, usize) {
+    let spin = particle.lifetime;
+    let cycle = frame
+        + (particle.position.0 * 16.0) as Frame
+        + (particle.position.1 * 16.0) as Frame
+        + spin
+        + particle.index as Frame;
+
+    let sx = {
+        let t = cycle % 24;
+        if t > 16 {
+            16.0
+        } else if t > 8 {
+            8.0
+        } else {
+            0.0
+        }
+    } + {
+        match particle.sort {
+            ParticleSort::Missile => 0.0,
+            ParticleSort::Diagonals => 24.0,
+            ParticleSort::BlueWin => 48.0,
+            ParticleSort::RedWin => 72.0,
+            ParticleSort::Shield => 96.0,
+            ParticleSort::Beam => 120.0,
+        }
+    };
+
+    (sx, (spin / 5) as usize)
+}
+
 pub fn draw_particle(
-    context: &CanvasRenderingContext2d,
-    atlas: &HtmlCanvasElement,
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
     particle: &Particle,
-    frame: usize,
-) -> Result<(), JsValue> {
+    frame: Frame,
+) -> Result<(), PlatformError> {
     context.save();
     context.translate(particle.position.0.round(), particle.position.1.round())?;
 
-    let spin = particle.lifetime;
-    let cycle = frame
-        + (particle.position.0 * 16.0) as usize
-        + (particle.position.1 * 16.0) as usize
-        + spin
-        + particle.index;
+    let (sx, quarter_turns) = particle_sprite(particle, frame);
 
-    context.rotate((spin / 5) as f64 * std::f64::consts::PI / 2.0)?;
+    context.rotate(quarter_turns as f64 * std::f64::consts::PI / 2.0)?;
     // context.rotate(frame as f64 * 0.1)?;
-    draw_image(
-        context,
-        atlas,
-        {
-            let t = cycle % 24;
-            if t > 16 {
-                16.0
-            } else if t > 8 {
-                8.0
-            } else {
-                0.0
-            }
-        } + {
-            match particle.sort {
-                ParticleSort::Missile => 0.0,
-                ParticleSort::Diagonals => 24.0,
-                ParticleSort::BlueWin => 48.0,
-                ParticleSort::RedWin => 72.0,
-                ParticleSort::Shield => 96.0,
-                ParticleSort::Beam => 120.0,
-            }
-        },
-        248.0,
-        8.0,
-        8.0,
-        -4.0,
-        -4.0,
-    )?;
+    draw_image(context, atlas, sx, 248.0, 8.0, 8.0, -4.0, -4.0)?;
     context.restore();
 
     Ok(())
 }
 
 pub fn draw_sand_circle(
-    context: &CanvasRenderingContext2d,
+    context: &dyn Canvas,
     capture_progress: f32,
     radius: f32,
-) -> Result<(), JsValue> {
+) -> Result<(), PlatformError> {
     context.clear_rect(360.0, 360.0, 360.0, 360.0);
 
     let capture_radius = (capture_progress * radius).abs();
@@ -408,9 +835,464 @@ pub fn draw_sand_circle(
         })
         .collect();
 
-    let image_data = ImageData::new_with_u8_clamped_array_and_sh(Clamped(&a), 360, 360).unwrap();
+    context.put_image_data(&a, 360, 360, 360.0, 360.0)?;
+
+    Ok(())
+}
+
+/// Shades the floor the walls have closed over under [`shared::GameMode::Sumo`], and dashes the
+/// line beyond which bugs are crushed when the walls next close in.
+pub fn draw_ring(
+    context: &dyn Canvas,
+    geometry: &ArenaGeometry,
+    inset: f32,
+) -> Result<(), PlatformError> {
+    let (cx, cy) = geometry.view_centre();
+    // The walls are a unit thick, half of which reaches into the arena
+    let outer_width = geometry.to_screen_length(geometry.width - 1.0);
+    let outer_height = geometry.to_screen_length(geometry.height - 1.0);
+    let inner_width = geometry.to_screen_length(geometry.width - 1.0 - inset * 2.0);
+    let inner_height = geometry.to_screen_length(geometry.height - 1.0 - inset * 2.0);
+
+    context.save();
+
+    if inset > 0.0 {
+        context.begin_path();
+        context.rect(
+            cx - outer_width / 2.0,
+            cy - outer_height / 2.0,
+            outer_width,
+            outer_height,
+        );
+        // Drawn the other way round, so the ring is cut out of the shading
+        context.rect(
+            cx + inner_width / 2.0,
+            cy - inner_height / 2.0,
+            -inner_width,
+            inner_height,
+        );
+        context.set_fill_style("#3f0f0f9f");
+        context.fill();
+
+        context.set_stroke_style("#df3f3f");
+        context.stroke_rect(
+            (cx - inner_width / 2.0).floor() + 0.5,
+            (cy - inner_height / 2.0).floor() + 0.5,
+            inner_width.floor(),
+            inner_height.floor(),
+        );
+    }
+
+    if inset < SUMO_MAX_INSET {
+        let next_inset = (inset + SUMO_RING_SHRINK).min(SUMO_MAX_INSET);
+        // Bugs whose centres lie within a unit of the closed-in walls' centre lines are crushed
+        let crush_width = geometry.to_screen_length(geometry.width - 2.0 - next_inset * 2.0);
+        let crush_height = geometry.to_screen_length(geometry.height - 2.0 - next_inset * 2.0);
+
+        context.set_line_dash(&[4.0, 4.0])?;
+        context.set_stroke_style("#df3f3f7f");
+        context.stroke_rect(
+            (cx - crush_width / 2.0).floor() + 0.5,
+            (cy - crush_height / 2.0).floor() + 0.5,
+            crush_width.floor(),
+            crush_height.floor(),
+        );
+    }
+
+    context.restore();
+
+    Ok(())
+}
+
+/// Shades the floor beyond the outer ring under [`shared::Mutator::RingOut`], and dashes the ring
+/// bugs are shoved out past.
+pub fn draw_ring_out(context: &dyn Canvas, geometry: &ArenaGeometry) -> Result<(), PlatformError> {
+    let (cx, cy) = geometry.view_centre();
+    let width = geometry.to_screen_length(geometry.width - 1.0);
+    let height = geometry.to_screen_length(geometry.height - 1.0);
+    let radius = geometry.to_screen_length(RING_OUT_RADIUS);
+
+    context.save();
+
+    context.begin_path();
+    context.rect(cx - width / 2.0, cy - height / 2.0, width, height);
+    // Drawn the other way round, so the ring is cut out of the shading
+    context.arc_with_anticlockwise(cx, cy, radius, 0.0, std::f64::consts::TAU, true)?;
+    context.set_fill_style("#3f2f0f5f");
+    context.fill();
+
+    context.begin_path();
+    context.arc(cx, cy, radius, 0.0, std::f64::consts::TAU)?;
+    context.set_line_dash(&[4.0, 4.0])?;
+    context.set_stroke_style("#dfaf3f");
+    context.stroke();
+
+    context.restore();
+
+    Ok(())
+}
+
+pub fn draw_solid_arena(
+    context: &dyn Canvas,
+    geometry: &ArenaGeometry,
+    capture_progress: f32,
+) -> Result<(), PlatformError> {
+    let (cx, cy) = geometry.view_centre();
+    // The walls are a unit thick, half of which reaches into the arena
+    let floor_width = geometry.to_screen_length(geometry.width - 1.0);
+    let floor_height = geometry.to_screen_length(geometry.height - 1.0);
+    let radius = geometry.capture_radius * geometry.scale;
+
+    context.save();
+
+    context.set_fill_style("#000000");
+    context.fill_rect(
+        0.0,
+        0.0,
+        geometry.view_width as f64,
+        geometry.view_height as f64,
+    );
+
+    context.set_fill_style("#1f1f1f");
+    context.fill_rect(
+        cx - floor_width / 2.0,
+        cy - floor_height / 2.0,
+        floor_width,
+        floor_height,
+    );
+
+    context.begin_path();
+    context.arc(cx, cy, radius as f64, 0.0, std::f64::consts::TAU)?;
+    context.set_fill_style("#3f3f3f");
+    context.fill();
+
+    let capture_radius = (capture_progress * radius).abs().min(radius) as f64;
+
+    if capture_radius > 0.0 {
+        context.begin_path();
+        context.arc(cx, cy, capture_radius, 0.0, std::f64::consts::TAU)?;
+        context.set_fill_style(if capture_progress > 0.0 {
+            "#ff2020"
+        } else {
+            "#20ffff"
+        });
+        context.fill();
+    }
+
+    context.begin_path();
+    context.arc(
+        384.0 / 2.0,
+        360.0 / 2.0,
+        radius as f64,
+        0.0,
+        std::f64::consts::TAU,
+    )?;
+    context.set_line_width(2.0);
+    context.set_stroke_style("#ffffff");
+    context.stroke();
+
+    context.restore();
+
+    Ok(())
+}
+
+pub fn draw_selection_ring(
+    context: &dyn Canvas,
+    dx: f64,
+    dy: f64,
+    width: f64,
+    color: &str,
+) -> Result<(), PlatformError> {
+    context.save();
+    context.begin_path();
+    context.arc(dx.round(), dy.round(), 11.0, 0.0, std::f64::consts::TAU)?;
+    context.set_line_width(width);
+    context.set_stroke_style(color);
+    context.stroke();
+    context.restore();
+
+    Ok(())
+}
+
+/// Draws a ring closing in on a bug picked with the next-bug hotkey, `t` going from 0 to 1 as it
+/// lands on the bug.
+pub fn draw_focus_ring(
+    context: &dyn Canvas,
+    dx: f64,
+    dy: f64,
+    t: f64,
+    color: &str,
+) -> Result<(), PlatformError> {
+    context.save();
+    context.begin_path();
+    context.arc(
+        dx.round(),
+        dy.round(),
+        12.0 + (1.0 - t) * 36.0,
+        0.0,
+        std::f64::consts::TAU,
+    )?;
+    context.set_global_alpha(t);
+    context.set_line_width(1.0 + (1.0 - t) * 2.0);
+    context.set_stroke_style(color);
+    context.stroke();
+    context.restore();
+
+    Ok(())
+}
+
+/// Outlines the box being dragged to select several bugs at once.
+pub fn draw_selection_box(
+    context: &dyn Canvas,
+    start: (i32, i32),
+    end: (i32, i32),
+) -> Result<(), PlatformError> {
+    let (x, y) = (start.0.min(end.0) as f64, start.1.min(end.1) as f64);
+    let (width, height) = (
+        (start.0 - end.0).abs() as f64,
+        (start.1 - end.1).abs() as f64,
+    );
+
+    context.save();
+    context.set_global_alpha(0.15);
+    context.set_fill_style("#ffffff");
+    context.fill_rect(x, y, width, height);
+    context.set_global_alpha(0.8);
+    context.set_line_width(1.0);
+    context.set_stroke_style("#ffffff");
+    context.stroke_rect(x + 0.5, y + 0.5, width, height);
+    context.restore();
+
+    Ok(())
+}
+
+pub fn draw_selection_pulse(
+    context: &dyn Canvas,
+    dx: f64,
+    dy: f64,
+    frame: Frame,
+    color: &str,
+) -> Result<(), PlatformError> {
+    let pulse = (frame as f64 / 8.0).sin();
+
+    context.save();
+    context.begin_path();
+    context.arc(
+        dx.round(),
+        dy.round(),
+        12.0 + pulse * 1.5,
+        0.0,
+        std::f64::consts::TAU,
+    )?;
+    context.set_global_alpha(0.6 + pulse * 0.3);
+    context.set_line_width(1.0);
+    context.set_stroke_style(color);
+    context.stroke();
+    context.restore();
+
+    Ok(())
+}
+
+/// Marks where an aim would first be obstructed: a ring on allies, a cross on enemies and a
+/// diamond on props or walls.
+pub fn draw_obstruction_marker(
+    context: &dyn Canvas,
+    obstruction: Obstruction,
+    dx: f64,
+    dy: f64,
+) -> Result<(), PlatformError> {
+    let (dx, dy) = (dx.round() + 0.5, dy.round() + 0.5);
+
+    context.save();
+    context.set_global_alpha(0.7);
+    context.set_line_width(1.0);
+    context.begin_path();
+
+    match obstruction {
+        Obstruction::Ally(_) => {
+            context.set_stroke_style("#80ff80");
+            context.arc(dx, dy, 3.0, 0.0, std::f64::consts::TAU)?;
+        }
+        Obstruction::Ball => {
+            context.set_stroke_style("#ffffff");
+            context.arc(dx, dy, 3.0, 0.0, std::f64::consts::TAU)?;
+        }
+        Obstruction::Enemy(_) => {
+            context.set_stroke_style("#ff5040");
+            context.move_to(dx - 3.0, dy - 3.0);
+            context.line_to(dx + 3.0, dy + 3.0);
+            context.move_to(dx + 3.0, dy - 3.0);
+            context.line_to(dx - 3.0, dy + 3.0);
+        }
+        Obstruction::Prop(_) | Obstruction::Wall => {
+            context.set_stroke_style("#ffd020");
+            context.move_to(dx, dy - 3.0);
+            context.line_to(dx + 3.0, dy);
+            context.line_to(dx, dy + 3.0);
+            context.line_to(dx - 3.0, dy);
+            context.close_path();
+        }
+    }
+
+    context.stroke();
+    context.restore();
+
+    Ok(())
+}
+
+pub fn draw_bug_tooltip(
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
+    bug_data: &BugData,
+    dx: f64,
+    dy: f64,
+    pointer: &Pointer,
+    frame: Frame,
+) -> Result<(), PlatformError> {
+    let sort = match bug_data.sort() {
+        shared::BugSort::Beetle => "Beetle",
+        shared::BugSort::Ladybug => "Ladybug",
+        shared::BugSort::Ant => "Ant",
+    };
+    let text = format!(
+        "{} ({sort}) {}/{}",
+        bug_data.name(),
+        bug_data.health(),
+        bug_data.sort().max_health()
+    );
+    let width = text_length(&text) as i32 + 12;
+    let x = (dx as i32 - width / 2).clamp(0, 384 - width);
+
+    draw_label(
+        context,
+        atlas,
+        (x, dy as i32 - 28),
+        (width, 12),
+        "#2a1f00",
+        &ContentElement::Text(text, crate::app::Alignment::Center),
+        pointer,
+        frame,
+        &LabelTrim::Round,
+        false,
+    )
+}
+
+/// Draws the projected stats of a bug's aim in a compact card above it, or below it near the top
+/// edge, with a warning mark when the shot runs into a wall.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_aim_tooltip(
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
+    lines: &[String],
+    hits_wall: bool,
+    dx: f64,
+    dy: f64,
+    pointer: &Pointer,
+    frame: Frame,
+) -> Result<(), PlatformError> {
+    const WARNING_WIDTH: i32 = 12;
+
+    let width = lines
+        .iter()
+        .map(|line| text_length(line) as i32)
+        .max()
+        .unwrap_or_default()
+        + 12
+        + if hits_wall { WARNING_WIDTH } else { 0 };
+    let height = lines.len() as i32 * 10 + 6;
+
+    let x = (dx as i32 - width / 2).clamp(0, 384 - width);
+    let y = if dy as i32 - 20 - height >= 0 {
+        dy as i32 - 20 - height
+    } else {
+        dy as i32 + 20
+    };
+
+    draw_label(
+        context,
+        atlas,
+        (x, y),
+        (width, height),
+        "#002a2a",
+        &ContentElement::None,
+        pointer,
+        frame,
+        &LabelTrim::Round,
+        false,
+    )?;
+
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(
+            context,
+            atlas,
+            (x + 6) as f64,
+            (y + 3 + i as i32 * 10) as f64,
+            line,
+        )?;
+    }
+
+    if hits_wall {
+        let (wx, wy) = ((x + width - WARNING_WIDTH) as f64, (y + 4) as f64);
+
+        context.save();
+        context.set_fill_style("#ffd020");
+        context.begin_path();
+        context.move_to(wx + 4.0, wy);
+        context.line_to(wx + 8.0, wy + 7.0);
+        context.line_to(wx, wy + 7.0);
+        context.close_path();
+        context.fill();
+        context.set_fill_style("#2a1f00");
+        context.fill_rect(wx + 3.5, wy + 2.0, 1.0, 3.0);
+        context.fill_rect(wx + 3.5, wy + 6.0, 1.0, 1.0);
+        context.restore();
+    }
+
+    Ok(())
+}
+
+/// Draws a small card of text lines next to an anchor point, kept within the screen.
+pub fn draw_info_card(
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
+    lines: &[String],
+    anchor: (i32, i32),
+    pointer: &Pointer,
+    frame: Frame,
+) -> Result<(), PlatformError> {
+    let width = lines
+        .iter()
+        .map(|line| text_length(line) as i32)
+        .max()
+        .unwrap_or_default()
+        + 16;
+    let height = lines.len() as i32 * 12 + 8;
 
-    context.put_image_data(&image_data, 360.0, 360.0)?;
+    let x = (anchor.0 + 8).clamp(0, 384 - width);
+    let y = (anchor.1 + 8).clamp(0, 360 - height);
+
+    draw_label(
+        context,
+        atlas,
+        (x, y),
+        (width, height),
+        "#002a2a",
+        &ContentElement::None,
+        pointer,
+        frame,
+        &LabelTrim::Round,
+        false,
+    )?;
+
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(
+            context,
+            atlas,
+            (x + 8) as f64,
+            (y + 4 + i as i32 * 12) as f64,
+            line,
+        )?;
+    }
 
     Ok(())
 }
@@ -425,22 +1307,22 @@ fn quadrant_to_xy(corner: u8) -> (u8, u8) {
 }
 
 pub fn draw_label(
-    context: &CanvasRenderingContext2d,
-    atlas: &HtmlCanvasElement,
+    context: &dyn Canvas,
+    atlas: &dyn Canvas,
     position: (i32, i32),
     size: (i32, i32),
     color: &str,
     content: &ContentElement,
     pointer: &Pointer,
-    frame: usize,
+    frame: Frame,
     trim: &LabelTrim,
     snip_content: bool,
-) -> Result<(), JsValue> {
+) -> Result<(), PlatformError> {
     context.save();
 
     context.translate(position.0 as f64, position.1 as f64)?;
 
-    context.set_fill_style(&color.into());
+    context.set_fill_style(color);
     context.fill_rect(0.0, 0.0, size.0 as f64, size.1 as f64);
 
     context.translate(size.0 as f64 / 2.0, size.1 as f64 / 2.0)?;
@@ -519,3 +1401,260 @@ pub fn draw_label(
 
     Ok(())
 }
+
+/// Draws the recent positions of a bug as a line tapering and fading towards its oldest end.
+pub fn draw_bug_trail(
+    context: &dyn Canvas,
+    geometry: &ArenaGeometry,
+    trail: &VecDeque<Vector2<f32>>,
+    team: Team,
+) -> Result<(), PlatformError> {
+    context.save();
+    context.set_line_cap("round");
+    context.set_stroke_style(match team {
+        Team::Red => "#ff5040",
+        Team::Blue => "#40c8ff",
+    });
+
+    for (i, (from, to)) in trail.iter().zip(trail.iter().skip(1)).enumerate() {
+        let freshness = (i + 1) as f64 / trail.len() as f64;
+        let (fx, fy) = geometry.local_to_screen(from);
+        let (tx, ty) = geometry.local_to_screen(to);
+
+        context.set_global_alpha(freshness * 0.6);
+        context.set_line_width(1.0 + freshness * 3.0);
+        context.begin_path();
+        context.move_to(fx, fy);
+        context.line_to(tx, ty);
+        context.stroke();
+    }
+
+    context.restore();
+
+    Ok(())
+}
+
+/// Draws the impulse a bug was given at the start of the resolving turn as an arrow in its team's
+/// colour, from where the bug stood when the turn executed.
+pub fn draw_intent_arrow(
+    context: &dyn Canvas,
+    geometry: &ArenaGeometry,
+    origin: &Vector2<f32>,
+    intent: &Vector2<f32>,
+    team: Team,
+    alpha: f64,
+) -> Result<(), PlatformError> {
+    let (ox, oy) = geometry.local_to_screen(origin);
+    let (dx, dy) = geometry.local_to_screen(&(origin + intent));
+
+    let length = (dy - oy).hypot(dx - ox);
+
+    if length < 4.0 {
+        return Ok(());
+    }
+
+    let (nx, ny) = ((dx - ox) / length, (dy - oy) / length);
+    const HEAD: f64 = 4.0;
+
+    context.save();
+    context.set_global_alpha(alpha.clamp(0.0, 1.0));
+    context.set_line_width(1.0);
+    context.set_stroke_style(match team {
+        Team::Red => "#ff5040",
+        Team::Blue => "#40c8ff",
+    });
+
+    context.begin_path();
+    context.move_to(ox, oy);
+    context.line_to(dx, dy);
+    context.move_to(dx - HEAD * (nx - ny), dy - HEAD * (ny + nx));
+    context.line_to(dx, dy);
+    context.line_to(dx - HEAD * (nx + ny), dy - HEAD * (ny - nx));
+    context.stroke();
+
+    context.restore();
+
+    Ok(())
+}
+
+/// Draws a miniature of an [`ArenaMap`] into a square of `size` pixels, terrain as tinted circles,
+/// props as dots and spawn slots as dots in their team colours.
+pub fn draw_map_preview(
+    context: &dyn Canvas,
+    map: &ArenaMap,
+    dx: f64,
+    dy: f64,
+    size: f64,
+) -> Result<(), PlatformError> {
+    let geometry = ArenaGeometry::default();
+    let scale = size / geometry.width.max(geometry.height) as f64;
+    let to_preview = |position: &Vector2<f32>| {
+        (
+            dx + size / 2.0 + position.x as f64 * scale,
+            dy + size / 2.0 + position.y as f64 * scale,
+        )
+    };
+
+    context.save();
+    context.set_fill_style("#2a1f00");
+    context.fill_rect(dx, dy, size, size);
+
+    context.begin_path();
+    context.arc(
+        dx + size / 2.0,
+        dy + size / 2.0,
+        geometry.capture_radius as f64 * scale,
+        0.0,
+        std::f64::consts::TAU,
+    )?;
+    context.set_line_width(1.0);
+    context.set_stroke_style("#7f7f7f");
+    context.stroke();
+
+    context.set_global_alpha(0.6);
+
+    for (position, terrain_data) in &map.terrain {
+        let (x, y) = to_preview(position);
+
+        context.begin_path();
+        context.arc(
+            x,
+            y,
+            terrain_data.radius() as f64 * scale,
+            0.0,
+            std::f64::consts::TAU,
+        )?;
+        context.set_fill_style(preview_terrain_color(terrain_data.sort()));
+        context.fill();
+    }
+
+    context.set_global_alpha(1.0);
+
+    for (position, prop_sort) in &map.props {
+        let (x, y) = to_preview(position);
+
+        context.set_fill_style(preview_prop_color(prop_sort));
+        context.fill_rect(x.round() - 1.0, y.round() - 1.0, 2.0, 2.0);
+    }
+
+    for (i, position) in map.formation().iter().enumerate() {
+        let (x, y) = to_preview(position);
+
+        context.set_fill_style(if i < TEAM_SIZE { "#ff5040" } else { "#40c8ff" });
+        context.fill_rect(x.round() - 1.0, y.round() - 1.0, 2.0, 2.0);
+    }
+
+    context.restore();
+
+    Ok(())
+}
+
+/// Draws a miniature of a running [`Game`] into a square of `size` pixels, in the colours of
+/// [`draw_map_preview`] with bugs as circles in their team colours.
+pub fn draw_game_preview(
+    context: &dyn Canvas,
+    game: &Game,
+    dx: f64,
+    dy: f64,
+    size: f64,
+) -> Result<(), PlatformError> {
+    let geometry = game.geometry();
+    let scale = size / geometry.width.max(geometry.height) as f64;
+    let to_preview = |position: &Vector2<f32>| {
+        (
+            dx + size / 2.0 + position.x as f64 * scale,
+            dy + size / 2.0 + position.y as f64 * scale,
+        )
+    };
+
+    context.save();
+    context.set_fill_style("#2a1f00");
+    context.fill_rect(dx, dy, size, size);
+
+    context.begin_path();
+    context.arc(
+        dx + size / 2.0,
+        dy + size / 2.0,
+        game.capture_radius() as f64 * scale,
+        0.0,
+        std::f64::consts::TAU,
+    )?;
+    context.set_line_width(1.0);
+    context.set_stroke_style("#7f7f7f");
+    context.stroke();
+
+    context.set_global_alpha(0.6);
+
+    for (collider, terrain_data) in game.iter_terrain() {
+        let (x, y) = to_preview(collider.translation());
+
+        context.begin_path();
+        context.arc(
+            x,
+            y,
+            terrain_data.radius() as f64 * scale,
+            0.0,
+            std::f64::consts::TAU,
+        )?;
+        context.set_fill_style(preview_terrain_color(terrain_data.sort()));
+        context.fill();
+    }
+
+    context.set_global_alpha(1.0);
+
+    for (collider, prop_data) in game.iter_props() {
+        let (x, y) = to_preview(collider.translation());
+
+        context.set_fill_style(preview_prop_color(prop_data.sort()));
+        context.fill_rect(x.round() - 1.0, y.round() - 1.0, 2.0, 2.0);
+    }
+
+    for (rigid_body, bug_data) in game.iter_bugs() {
+        let (x, y) = to_preview(rigid_body.translation());
+
+        context.begin_path();
+        context.arc(x, y, (0.5 * scale).max(1.5), 0.0, std::f64::consts::TAU)?;
+        context.set_fill_style(match bug_data.team() {
+            Team::Red => "#ff5040",
+            Team::Blue => "#40c8ff",
+        });
+        context.fill();
+    }
+
+    if let Some(ball) = game.ball() {
+        let (x, y) = to_preview(ball.translation());
+
+        context.begin_path();
+        context.arc(
+            x,
+            y,
+            (SOCCER_BALL_RADIUS as f64 * scale).max(1.0),
+            0.0,
+            std::f64::consts::TAU,
+        )?;
+        context.set_fill_style("#ffffff");
+        context.fill();
+    }
+
+    context.restore();
+
+    Ok(())
+}
+
+/// Colour of a terrain patch in the miniatures of [`draw_map_preview`] and [`draw_game_preview`].
+fn preview_terrain_color(terrain_sort: &TerrainSort) -> &'static str {
+    match terrain_sort {
+        TerrainSort::Mud => "#5a3a1a",
+        TerrainSort::Ice => "#b0e8ff",
+        TerrainSort::Boost(_) => "#ffd020",
+    }
+}
+
+/// Colour of a prop in the miniatures of [`draw_map_preview`] and [`draw_game_preview`].
+fn preview_prop_color(prop_sort: &PropSort) -> &'static str {
+    match prop_sort {
+        PropSort::Pillar => "#c0c0c0",
+        PropSort::Rock => "#8a8a8a",
+        PropSort::Boulder => "#6a6a6a",
+    }
+}
@@ -0,0 +1,100 @@
+use nalgebra::{vector, Vector2};
+use shared::{GameView, VecMap};
+
+/// Strongest impulse intent a bug can be given.
+const MAX_INTENT: f32 = 4.0;
+
+/// Planning presets which give every healthy bug of a team an impulse intent in one go.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Formation {
+    /// Every bug charges towards the capture zone.
+    Advance,
+    /// Bugs outside the zone regroup on its inner ring, bugs inside hold still.
+    DefendZone,
+    /// Bugs move apart from the team's centre, avoiding pile-ups.
+    SpreadOut,
+}
+
+impl Formation {
+    /// All formations, in the order of their buttons and hotkeys.
+    pub const ALL: [Formation; 3] = [
+        Formation::Advance,
+        Formation::DefendZone,
+        Formation::SpreadOut,
+    ];
+
+    /// Short label of the formation's button.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Formation::Advance => "Advance",
+            Formation::DefendZone => "Defend",
+            Formation::SpreadOut => "Spread",
+        }
+    }
+
+    /// Returns the formation bound to a keyboard code, `Digit1` to `Digit3`.
+    pub fn from_key(code: &str) -> Option<Formation> {
+        match code {
+            "Digit1" => Some(Formation::Advance),
+            "Digit2" => Some(Formation::DefendZone),
+            "Digit3" => Some(Formation::SpreadOut),
+            _ => None,
+        }
+    }
+
+    /// Computes impulse intents for all of the view's team's bugs which can still act.
+    pub fn plan(&self, view: &GameView) -> VecMap<usize, Vector2<f32>> {
+        match self {
+            Formation::Advance => view
+                .own_bugs()
+                .map(|bug| (bug.index, towards(bug.position, Vector2::zeros())))
+                .collect(),
+            Formation::DefendZone => view
+                .own_bugs()
+                .map(|bug| {
+                    let ring = view.capture_radius * 0.5;
+
+                    if bug.position.magnitude() <= ring {
+                        (bug.index, Vector2::zeros())
+                    } else {
+                        let target = bug.position.normalize() * ring;
+
+                        (bug.index, towards(bug.position, target))
+                    }
+                })
+                .collect(),
+            Formation::SpreadOut => {
+                let positions: Vec<Vector2<f32>> =
+                    view.own_bugs().map(|bug| bug.position).collect();
+                let centre = positions.iter().sum::<Vector2<f32>>() / positions.len().max(1) as f32;
+
+                view.own_bugs()
+                    .map(|bug| {
+                        let away = bug.position - centre;
+
+                        // Bugs sitting on the centre fan out by index instead
+                        let direction = if away.magnitude() > 0.1 {
+                            away.normalize()
+                        } else {
+                            let angle = bug.index as f32 * std::f32::consts::TAU / 6.0;
+                            vector![angle.cos(), angle.sin()]
+                        };
+
+                        (bug.index, direction * MAX_INTENT * 0.5)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Intent moving a bug from a position towards a target, stronger the farther it is.
+fn towards(position: Vector2<f32>, target: Vector2<f32>) -> Vector2<f32> {
+    let delta = target - position;
+
+    if delta.magnitude() > 0.05 {
+        delta.normalize() * (delta.magnitude() * 0.5).min(MAX_INTENT)
+    } else {
+        Vector2::zeros()
+    }
+}
@@ -0,0 +1,79 @@
+use crate::app::{App, Settings};
+
+/// Storage key of the comma-separated keys of dismissed hints.
+const HINTS_SEEN_KEY: &str = "hints_seen";
+
+/// A one-time tip about a mechanic, shown the first time a player runs into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hint {
+    Aim,
+    Zone,
+    Terrain,
+    Inspect,
+}
+
+impl Hint {
+    pub const ALL: [Hint; 4] = [Hint::Aim, Hint::Zone, Hint::Terrain, Hint::Inspect];
+
+    /// Stable key under which the hint is stored.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Hint::Aim => "aim",
+            Hint::Zone => "zone",
+            Hint::Terrain => "terrain",
+            Hint::Inspect => "inspect",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Hint> {
+        Hint::ALL.into_iter().find(|hint| hint.key() == key)
+    }
+
+    /// The tip itself, short enough for a single label.
+    pub fn text(&self) -> &'static str {
+        match self {
+            Hint::Aim => "Pick a bug, aim, then click",
+            Hint::Zone => "Hold the zone to score",
+            Hint::Terrain => "Mud slows, ice slides, pads push",
+            Hint::Inspect => "Right-click or hold to inspect",
+        }
+    }
+}
+
+/// Determines if hints are shown, which they are unless turned off in the settings.
+pub fn hints_enabled() -> bool {
+    Settings::load().hints
+}
+
+/// Turns hints on or off, turning them on again showing every hint anew.
+pub fn set_hints_enabled(enabled: bool) {
+    Settings::update(|settings| settings.hints = enabled);
+
+    if enabled {
+        App::kv_set(HINTS_SEEN_KEY, "");
+    }
+}
+
+/// Returns the hints dismissed on this device.
+pub fn seen_hints() -> Vec<Hint> {
+    App::kv_get(HINTS_SEEN_KEY)
+        .split(',')
+        .filter_map(Hint::from_key)
+        .collect()
+}
+
+/// Persists a dismissed hint so it is not shown again.
+pub fn dismiss_hint(hint: Hint) {
+    let mut hints = seen_hints();
+
+    if hints.contains(&hint) {
+        return;
+    }
+
+    hints.push(hint);
+
+    App::kv_set(
+        HINTS_SEEN_KEY,
+        &hints.iter().map(Hint::key).collect::<Vec<_>>().join(","),
+    );
+}
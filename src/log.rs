@@ -0,0 +1,137 @@
+use std::{cell::RefCell, collections::VecDeque};
+
+use crate::{app::now, platform::try_platform};
+
+/// Number of entries kept in memory for the diagnostics panel and bug reports.
+const LOG_CAPACITY: usize = 256;
+
+thread_local! {
+    static LOG: RefCell<VecDeque<Entry>> = const { RefCell::new(VecDeque::new()) };
+}
+
+/// Severity of a log [`Entry`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    pub(crate) fn letter(&self) -> char {
+        match self {
+            Level::Error => 'E',
+            Level::Warn => 'W',
+            Level::Info => 'I',
+            Level::Debug => 'D',
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    level: Level,
+    /// Milliseconds since the page loaded.
+    at: f64,
+    message: String,
+}
+
+impl Entry {
+    fn line(&self) -> String {
+        format!(
+            "{:.1}s {} {}",
+            self.at / 1000.0,
+            self.level.letter(),
+            self.message
+        )
+    }
+}
+
+/// Writes a message to the console and keeps it in the ring buffer. Used through the [`error!`],
+/// [`warn!`], [`info!`] and [`debug!`] macros.
+///
+/// Messages logged before the platform is installed are dropped.
+pub fn record(level: Level, message: String) {
+    let Some(platform) = try_platform() else {
+        return;
+    };
+
+    platform.system.log(level, &message);
+
+    LOG.with(|log| {
+        let mut log = log.borrow_mut();
+
+        if log.len() >= LOG_CAPACITY {
+            log.pop_front();
+        }
+
+        log.push_back(Entry {
+            level,
+            at: now(),
+            message,
+        });
+    });
+}
+
+/// The latest entries at or above a level, oldest first.
+pub fn recent(count: usize, level: Level) -> Vec<String> {
+    LOG.with(|log| {
+        let log = log.borrow();
+        let mut lines: Vec<String> = log
+            .iter()
+            .rev()
+            .filter(|entry| entry.level <= level)
+            .take(count)
+            .map(Entry::line)
+            .collect();
+
+        lines.reverse();
+        lines
+    })
+}
+
+/// Logs an error, always kept.
+#[macro_export]
+macro_rules! error {
+    ($($arg: tt)*) => {
+        $crate::log::record($crate::log::Level::Error, format!($($arg)*))
+    };
+}
+
+/// Logs a warning, always kept.
+#[macro_export]
+macro_rules! warn {
+    ($($arg: tt)*) => {
+        $crate::log::record($crate::log::Level::Warn, format!($($arg)*))
+    };
+}
+
+/// Logs an informational message, always kept.
+#[macro_export]
+macro_rules! info {
+    ($($arg: tt)*) => {
+        $crate::log::record($crate::log::Level::Info, format!($($arg)*))
+    };
+}
+
+/// Logs a debugging message, compiled out of deploy builds.
+#[cfg(not(feature = "deploy"))]
+#[macro_export]
+macro_rules! debug {
+    ($($arg: tt)*) => {
+        $crate::log::record($crate::log::Level::Debug, format!($($arg)*))
+    };
+}
+
+/// Logs a debugging message, compiled out of deploy builds.
+#[cfg(feature = "deploy")]
+#[macro_export]
+macro_rules! debug {
+    ($($arg: tt)*) => {
+        // Still type-checked, so the arguments do not turn into unused warnings
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
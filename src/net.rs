@@ -1,163 +1,418 @@
-use futures::TryFutureExt;
-use js_sys::Promise;
-use shared::{LobbySettings, Message, SessionMessage, SessionNewLobby, SessionRequest, LobbyID};
-use wasm_bindgen::{JsCast, JsValue};
-use wasm_bindgen_futures::{future_to_promise, JsFuture};
-use web_sys::{Request, RequestInit, Response};
-
-use crate::storage;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+
+use futures::future::{join_all, select, Either, LocalBoxFuture};
+use shared::{LobbyID, Message, Turn};
+
+use crate::{
+    api::{ApiClient, Dispatcher},
+    app::{now, Frame},
+    platform::{network, system, HttpRequest, HttpResponse, PlatformError},
+    storage, RESOURCE_BASE_URL,
+};
+
+/// A deployment of the game server the client can play on.
+pub struct Region {
+    /// Short name shown next to lobbies, matching the server's `REGION`.
+    pub name: &'static str,
+    /// Base URL of the region's API.
+    pub url: &'static str,
+}
 
 #[cfg(feature = "deploy")]
-const API_URL: &str = "https://crittershowdown.evrim.zone";
+const REGIONS: &[Region] = &[Region {
+    name: "eu",
+    url: "https://crittershowdown.evrim.zone",
+}];
 #[cfg(not(feature = "deploy"))]
-const API_URL: &str = "https://tunnel.evrim.zone";
-
-pub struct MessagePool {
-    pub messages: Vec<Message>,
-    block_frame: usize,
+const REGIONS: &[Region] = &[Region {
+    name: "dev",
+    url: "https://tunnel.evrim.zone",
+}];
+
+/// Milliseconds a region may take to answer a ping before it is considered unreachable.
+const PING_TIMEOUT_MS: f64 = 2000.0;
+
+thread_local! {
+    static ACTIVE_REGION: Cell<usize> = const { Cell::new(0) };
+    static REGION_PINGS: RefCell<HashMap<&'static str, f64>> = RefCell::new(HashMap::new());
+    static INVITED_LOBBY: Cell<Option<LobbyID>> = Cell::new(invite().map(|(lobby_id, _)| lobby_id));
 }
 
-impl MessagePool {
-    const BLOCK_FRAMES: usize = 60;
-
-    pub fn new() -> MessagePool {
-        MessagePool {
-            messages: Vec::new(),
-            block_frame: 0,
+/// Lobby ID and region of the invite link the game was opened with, see [`invite_link`].
+fn invite() -> Option<(LobbyID, String)> {
+    let parameters = system().launch_parameters();
+    let mut lobby_id = None;
+    let mut region = String::new();
+
+    for (key, value) in parameters
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+    {
+        match key {
+            "lobby" => lobby_id = value.parse::<LobbyID>().ok(),
+            "region" => region = value.to_string(),
+            _ => (),
         }
     }
 
-    pub fn available(&self, frame: usize) -> bool {
-        frame >= self.block_frame
-    }
+    lobby_id.map(|lobby_id| (lobby_id, region))
+}
 
-    pub fn block(&mut self, frame: usize) {
-        self.block_frame = frame + Self::BLOCK_FRAMES;
-    }
+/// Link which opens the game straight into a lobby of the active region.
+///
+/// Builds without a page of their own, e.g. the desktop one, link to the page the region's server
+/// hosts.
+pub fn invite_link(lobby_id: LobbyID) -> String {
+    let page_url = system()
+        .page_url()
+        .unwrap_or_else(|| format!("{}/", api_url()));
 
-    pub fn push(&mut self, message: Message) {
-        self.messages.push(message);
-    }
+    format!("{page_url}?lobby={lobby_id}&region={}", active_region())
+}
 
-    pub fn clear(&mut self) {
-        self.messages.clear();
-    }
+/// Takes the lobby of the invite link the page was opened with, so it is only joined once.
+pub fn take_invited_lobby() -> Option<LobbyID> {
+    INVITED_LOBBY.with(Cell::take)
 }
 
-fn wrap_response_into_json(value: JsValue) -> JsFuture {
-    assert!(value.is_instance_of::<Response>());
-    let resp: Response = value.dyn_into().unwrap();
-    JsFuture::from(resp.json().unwrap())
+pub fn api_url() -> &'static str {
+    REGIONS[ACTIVE_REGION.with(Cell::get)].url
 }
 
-pub fn fetch(request: &Request) -> Promise {
-    let resp_value = JsFuture::from(web_sys::window().unwrap().fetch_with_request(request))
-        .and_then(wrap_response_into_json);
+/// Name of the region all requests are sent to.
+pub fn active_region() -> &'static str {
+    REGIONS[ACTIVE_REGION.with(Cell::get)].name
+}
 
-    future_to_promise(resp_value)
+/// Last measured round trip to a region in milliseconds, if it answered.
+pub fn region_ping(name: &str) -> Option<f64> {
+    REGION_PINGS.with(|pings| pings.borrow().get(name).copied())
 }
 
-fn request_url(method: &str, url: &str) -> Request {
-    let mut opts = RequestInit::new();
-    opts.method(method);
+/// Measures the round trip to every region and makes the fastest one active, so lobbies are
+/// created and browsed on it.
+pub async fn select_region() {
+    let pings = join_all(REGIONS.iter().map(|region| measure_ping(region.url))).await;
+    let mut fastest: Option<(usize, f64)> = None;
 
-    Request::new_with_str_and_init(url, &opts).unwrap()
-}
+    for (index, (region, ping)) in REGIONS.iter().zip(pings).enumerate() {
+        let Some(ping) = ping else {
+            continue;
+        };
+
+        REGION_PINGS.with(|pings| pings.borrow_mut().insert(region.name, ping));
+
+        if fastest.is_none_or(|(_, fastest_ping)| ping < fastest_ping) {
+            fastest = Some((index, ping));
+        }
+    }
 
-pub fn request_session() -> Request {
-    request_url("GET", &format!("{API_URL}/session"))
+    // The lobby of an invite link is hosted in the region it was shared from, if reachable
+    let invited = invite().and_then(|(_, name)| {
+        REGIONS
+            .iter()
+            .position(|region| region.name == name && region_ping(region.name).is_some())
+    });
+
+    if let Some(index) = invited.or(fastest.map(|(index, _)| index)) {
+        ACTIVE_REGION.with(|active_region| active_region.set(index));
+    }
 }
 
-pub fn request_state(lobby_id: LobbyID) -> Request {
-    request_url("GET", &format!("{API_URL}/lobbies/{lobby_id}/state"))
+/// Times a request to a region's ping endpoint, `None` if it failed or timed out.
+async fn measure_ping(url: &str) -> Option<f64> {
+    let start = now();
+    let ping = fetch(HttpRequest::get(format!("{url}/ping")));
+    let timeout = system().sleep(PING_TIMEOUT_MS);
+
+    match select(ping, timeout).await {
+        Either::Left((Ok(response), _)) if response.ok() => Some(now() - start),
+        _ => None,
+    }
 }
 
-pub fn request_turns_since(lobby_id: LobbyID, since: usize) -> Request {
-    request_url("GET", &format!("{API_URL}/lobbies/{lobby_id}/turns/{since}"))
+/// A move committed to the server, awaiting the [`Message::Ack`] of its commitment and, once the
+/// turn is about to resolve, of its reveal.
+struct PendingMove {
+    message_id: u64,
+    lobby_id: LobbyID,
+    session_id: String,
+    turn: Turn,
+    salt: u64,
+    sent_frame: Frame,
+    /// Whether the move was revealed rather than only committed.
+    revealed: bool,
+    /// Whether the server acknowledged the last message sent for the move.
+    acknowledged: bool,
 }
 
-pub fn request_lobbies() -> Request {
-    request_url("GET", &format!("{API_URL}/lobbies/"))
+impl PendingMove {
+    fn send(&self, dispatcher: &Dispatcher) {
+        let message = if self.revealed {
+            Message::Reveal(self.turn.clone(), self.salt)
+        } else {
+            Message::Commit(self.turn.commitment(self.salt))
+        };
+
+        dispatcher.send(ApiClient::new(self.session_id.clone()).act(
+            self.lobby_id,
+            message,
+            Some(self.message_id),
+        ));
+    }
 }
 
-pub fn create_new_lobby(lobby_settings: LobbySettings, session_id: String) -> Option<Promise> {
-    let session_request = SessionNewLobby { lobby_settings, session_id };
+/// Outgoing moves, committed while planning and revealed once the turn is about to resolve, each
+/// sent again until the server acknowledges it or its turn has passed.
+#[derive(Default)]
+pub struct Outbox {
+    pending: Vec<PendingMove>,
+    next_id: u64,
+}
 
-    if let Ok(json) = serde_json::to_string(&session_request) {
-        let mut opts = RequestInit::new();
-        opts.method("POST");
-        opts.body(Some(&json.into()));
+impl Outbox {
+    /// Frames a move waits for its acknowledgement before it is sent again.
+    const RETRY_FRAMES: Frame = 90;
+
+    /// Frames a move may go unacknowledged before the player is told.
+    const UNCONFIRMED_FRAMES: Frame = 30;
+
+    /// Commits to a move and keeps it until it is revealed, replacing the unrevealed move of the
+    /// same turn as the server keeps a single commitment per player and turn.
+    pub fn send(
+        &mut self,
+        dispatcher: &Dispatcher,
+        lobby_id: LobbyID,
+        session_id: String,
+        turn: Turn,
+        frame: Frame,
+    ) {
+        self.next_id += 1;
+
+        let pending = PendingMove {
+            message_id: self.next_id,
+            lobby_id,
+            session_id,
+            turn,
+            salt: random_salt(),
+            sent_frame: frame,
+            revealed: false,
+            acknowledged: false,
+        };
+        pending.send(dispatcher);
+
+        self.pending
+            .retain(|earlier| earlier.turn.index != pending.turn.index || earlier.revealed);
+        self.pending.push(pending);
+    }
 
-        let url = format!("{API_URL}/lobbies/create");
+    /// Stops retrying the commitment or reveal with the given ID, dropping revealed moves.
+    pub fn acknowledge(&mut self, message_id: u64) {
+        self.pending
+            .retain(|pending| !(pending.message_id == message_id && pending.revealed));
 
-        let request = &Request::new_with_str_and_init(&url, &opts).unwrap();
+        for pending in &mut self.pending {
+            if pending.message_id == message_id {
+                pending.acknowledged = true;
+            }
+        }
+    }
 
-        request
-            .headers()
-            .set("Content-Type", "application/json")
-            .unwrap();
+    /// Reveals the moves committed for the given turn which are not yet revealed.
+    pub fn reveal(&mut self, dispatcher: &Dispatcher, turn_index: usize, frame: Frame) {
+        for pending in &mut self.pending {
+            if pending.turn.index != turn_index || pending.revealed {
+                continue;
+            }
 
-        Some(fetch(request))
-    } else {
-        None
-    }
-}
+            self.next_id += 1;
 
-pub fn post_probe(url: String, session_id: String) -> Option<Promise> {
-    let session_request = SessionRequest { session_id };
+            pending.message_id = self.next_id;
+            pending.revealed = true;
+            pending.acknowledged = false;
+            pending.sent_frame = frame;
 
-    if let Ok(json) = serde_json::to_string(&session_request) {
-        let mut opts = RequestInit::new();
-        opts.method("POST");
-        opts.body(Some(&json.into()));
+            pending.send(dispatcher);
+        }
+    }
 
-        let request = &Request::new_with_str_and_init(&url, &opts).unwrap();
+    /// Drops moves for turns which have passed and sends the overdue ones again.
+    pub fn retry(&mut self, dispatcher: &Dispatcher, turns_count: usize, frame: Frame) {
+        self.pending
+            .retain(|pending| pending.turn.index >= turns_count);
+
+        for pending in self
+            .pending
+            .iter_mut()
+            .filter(|pending| !pending.acknowledged)
+            .filter(|pending| frame.saturating_sub(pending.sent_frame) >= Self::RETRY_FRAMES)
+        {
+            pending.sent_frame = frame;
+            pending.send(dispatcher);
+        }
+    }
 
-        request
-            .headers()
-            .set("Content-Type", "application/json")
-            .unwrap();
+    /// Number of moves awaiting their acknowledgement.
+    pub fn pending(&self) -> usize {
+        self.pending
+            .iter()
+            .filter(|pending| !pending.acknowledged)
+            .count()
+    }
 
-        Some(fetch(request))
-    } else {
-        None
+    /// Determines if a move has gone unacknowledged for long enough to tell the player.
+    pub fn unconfirmed(&self, frame: Frame) -> bool {
+        self.pending.iter().any(|pending| {
+            !pending.acknowledged
+                && frame.saturating_sub(pending.sent_frame) >= Self::UNCONFIRMED_FRAMES
+        })
     }
 }
 
-pub fn send_ready(lobby_id: LobbyID, session_id: String) -> Option<Promise> {
-    post_probe(format!("{API_URL}/lobbies/{lobby_id}/ready"), session_id)
+/// Draws the salt of a move's commitment from the platform's cryptographic random number
+/// generator, as opponents could otherwise predict it and brute force the committed move.
+fn random_salt() -> u64 {
+    let mut bytes = [0u8; 8];
+
+    system()
+        .fill_random(&mut bytes)
+        .expect("no cryptographic random number generator");
+
+    u64::from_le_bytes(bytes)
 }
 
-pub fn send_rematch(lobby_id: LobbyID, session_id: String) -> Option<Promise> {
-    post_probe(format!("{API_URL}/lobbies/{lobby_id}/rematch"), session_id)
+/// Sends a request through the platform's network, answering with the response whatever its
+/// status.
+pub fn fetch(request: HttpRequest) -> LocalBoxFuture<'static, Result<HttpResponse, PlatformError>> {
+    let response = network().fetch(request);
+
+    #[cfg(not(feature = "deploy"))]
+    let response = simulation::degrade(response);
+
+    response
 }
 
-pub fn send_message(lobby_id: LobbyID, session_id: String, message: Message) -> Option<Promise> {
-    let session_message = SessionMessage {
-        session_id,
-        message,
-    };
+/// Dev-only network simulation, degrading responses to test netcode against bad connections.
+///
+/// Configured through the launch parameters, e.g. the query `?latency=200&jitter=100&drop=0.05&reorder=0.1`
+/// of the page: `latency` and `jitter` are in milliseconds, `drop` and `reorder` are probabilities.
+#[cfg(not(feature = "deploy"))]
+mod simulation {
+    use futures::{future::LocalBoxFuture, FutureExt};
+
+    use crate::platform::{random, system, HttpResponse, PlatformError};
+
+    /// Extra delay of reordered responses, so they arrive after ones sent later.
+    const REORDER_DELAY: f64 = 500.0;
+
+    #[derive(Default)]
+    struct NetworkConditions {
+        latency: f64,
+        jitter: f64,
+        drop: f64,
+        reorder: f64,
+    }
+
+    impl NetworkConditions {
+        fn from_parameters(parameters: &str) -> NetworkConditions {
+            let mut conditions = NetworkConditions::default();
+
+            for (key, value) in parameters
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+            {
+                let value = value.parse::<f64>().unwrap_or_default().max(0.0);
+
+                match key {
+                    "latency" => conditions.latency = value,
+                    "jitter" => conditions.jitter = value,
+                    "drop" => conditions.drop = value.min(1.0),
+                    "reorder" => conditions.reorder = value.min(1.0),
+                    _ => (),
+                }
+            }
+
+            conditions
+        }
+
+        fn active(&self) -> bool {
+            self.latency > 0.0 || self.jitter > 0.0 || self.drop > 0.0 || self.reorder > 0.0
+        }
+    }
+
+    thread_local! {
+        static CONDITIONS: NetworkConditions =
+            NetworkConditions::from_parameters(&system().launch_parameters());
+    }
+
+    /// Delays, drops or reorders a response according to the launch's [`NetworkConditions`].
+    pub fn degrade(
+        response: LocalBoxFuture<'static, Result<HttpResponse, PlatformError>>,
+    ) -> LocalBoxFuture<'static, Result<HttpResponse, PlatformError>> {
+        let (active, delay, dropped) = CONDITIONS.with(|conditions| {
+            let mut delay = conditions.latency + conditions.jitter * random();
+
+            if random() < conditions.reorder {
+                delay += REORDER_DELAY;
+            }
 
-    if let Ok(json) = serde_json::to_string(&session_message) {
-        let mut opts = RequestInit::new();
-        opts.method("POST");
-        opts.body(Some(&json.into()));
+            (conditions.active(), delay, random() < conditions.drop)
+        });
 
-        let url = format!("{API_URL}/lobbies/{lobby_id}/act");
+        async move {
+            let value = response.await;
 
-        let request = &Request::new_with_str_and_init(&url, &opts).unwrap();
+            if !active {
+                return value;
+            }
 
-        request
-            .headers()
-            .set("Content-Type", "application/json")
-            .unwrap();
+            if dropped {
+                // A lost response never arrives
+                futures::future::pending::<()>().await;
+            }
 
-        Some(fetch(request))
-    } else {
-        None
+            system().sleep(delay).await;
+
+            value
+        }
+        .boxed_local()
     }
 }
 
+/// Adds the stored session token to a request, if any.
+pub fn authorize(request: HttpRequest) -> HttpRequest {
+    match get_session_token() {
+        Some(token) => request.header("Authorization", format!("Bearer {token}")),
+        None => request,
+    }
+}
+
+pub fn request_balance() -> HttpRequest {
+    // Cache-busted so balance patches are picked up on the next page load
+    HttpRequest::get(format!(
+        "{RESOURCE_BASE_URL}/static/balance.json?t={}",
+        system().time() as u64
+    ))
+}
+
+pub fn request_clip_manifest() -> HttpRequest {
+    // Cache-busted so the clips listed match the binary fetching them
+    HttpRequest::get(format!(
+        "{RESOURCE_BASE_URL}/static/wav/manifest.json?t={}",
+        system().time() as u64
+    ))
+}
+
+/// Returns the stored session ID, if it was issued by the active region.
 pub fn get_session_id() -> Option<String> {
-    storage().and_then(|storage| storage.get_item("session_id").unwrap_or_default())
+    storage()
+        .filter(|storage| storage.get("session_region").as_deref() == Some(active_region()))
+        .and_then(|storage| storage.get("session_id"))
+}
+
+pub fn get_session_token() -> Option<String> {
+    storage().and_then(|storage| storage.get("session_token"))
 }
@@ -0,0 +1,490 @@
+//! The seam between the game and the platform it runs on.
+//!
+//! Game code draws, listens, plays sounds, stores data and talks to the server only through the
+//! traits of this module. The browser backend in [`web`] implements them on top of `web_sys`, the
+//! desktop backend in [`native`] on top of a window, a software rasterizer and the file system.
+//! Each entry point installs its backend with [`install`] before anything else runs.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native;
+mod web;
+
+use std::{
+    any::Any,
+    cell::OnceCell,
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+use futures::future::LocalBoxFuture;
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+pub use web::*;
+
+use crate::log::Level;
+
+/// Errors reported by a platform backend.
+#[derive(Clone, Debug)]
+pub struct PlatformError(pub String);
+
+impl Display for PlatformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&str> for PlatformError {
+    fn from(reason: &str) -> Self {
+        PlatformError(reason.to_string())
+    }
+}
+
+impl From<String> for PlatformError {
+    fn from(reason: String) -> Self {
+        PlatformError(reason)
+    }
+}
+
+/// Which backend the game runs on, for the few features only one of them offers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlatformKind {
+    Web,
+    Native,
+}
+
+/// A 2D drawing surface with the state machine and coordinate conventions of the HTML canvas.
+///
+/// Colours are CSS colour strings, `#rgb`, `#rrggbb`, `#rrggbbaa` or `rgba(r, g, b, a)`. Images
+/// are always scaled without smoothing, keeping the pixel art crisp.
+pub trait Canvas {
+    /// The backend's own canvas, so a backend can reach its native handle.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Width of the backing store in pixels.
+    fn width(&self) -> u32;
+
+    /// Height of the backing store in pixels.
+    fn height(&self) -> u32;
+
+    /// Resizes the backing store, which clears it and resets the drawing state.
+    ///
+    /// With `keep_display_size`, the canvas keeps the size it is shown at rather than following
+    /// its new backing size.
+    fn resize(&self, width: u32, height: u32, keep_display_size: bool);
+
+    fn save(&self);
+
+    fn restore(&self);
+
+    fn translate(&self, x: f64, y: f64) -> Result<(), PlatformError>;
+
+    fn rotate(&self, angle: f64) -> Result<(), PlatformError>;
+
+    fn scale(&self, x: f64, y: f64) -> Result<(), PlatformError>;
+
+    fn set_fill_style(&self, color: &str);
+
+    fn set_stroke_style(&self, color: &str);
+
+    fn set_line_width(&self, width: f64);
+
+    /// Sets the cap of line ends, `butt`, `round` or `square`.
+    fn set_line_cap(&self, cap: &str);
+
+    /// Sets the alternating lengths of dashes and gaps, solid lines when empty.
+    fn set_line_dash(&self, segments: &[f64]) -> Result<(), PlatformError>;
+
+    fn set_global_alpha(&self, alpha: f64);
+
+    /// Sets how drawing blends with the pixels underneath, `source-over` or `destination-out`.
+    fn set_global_composite_operation(&self, operation: &str) -> Result<(), PlatformError>;
+
+    fn begin_path(&self);
+
+    fn close_path(&self);
+
+    fn move_to(&self, x: f64, y: f64);
+
+    fn line_to(&self, x: f64, y: f64);
+
+    fn rect(&self, x: f64, y: f64, width: f64, height: f64);
+
+    fn arc(
+        &self,
+        x: f64,
+        y: f64,
+        radius: f64,
+        start_angle: f64,
+        end_angle: f64,
+    ) -> Result<(), PlatformError> {
+        self.arc_with_anticlockwise(x, y, radius, start_angle, end_angle, false)
+    }
+
+    fn arc_with_anticlockwise(
+        &self,
+        x: f64,
+        y: f64,
+        radius: f64,
+        start_angle: f64,
+        end_angle: f64,
+        anticlockwise: bool,
+    ) -> Result<(), PlatformError>;
+
+    fn fill(&self);
+
+    fn stroke(&self);
+
+    /// Intersects the clipping region with the current path.
+    fn clip(&self);
+
+    fn fill_rect(&self, x: f64, y: f64, width: f64, height: f64);
+
+    fn stroke_rect(&self, x: f64, y: f64, width: f64, height: f64);
+
+    fn clear_rect(&self, x: f64, y: f64, width: f64, height: f64);
+
+    /// Draws the `source` region of another canvas of the same backend into the `destination`
+    /// region, both given as `(x, y, width, height)`.
+    fn draw_image(
+        &self,
+        source: &dyn Canvas,
+        source_rect: (f64, f64, f64, f64),
+        destination_rect: (f64, f64, f64, f64),
+    ) -> Result<(), PlatformError>;
+
+    /// Draws all of another canvas at its own size.
+    fn draw_image_at(&self, source: &dyn Canvas, dx: f64, dy: f64) -> Result<(), PlatformError> {
+        let (width, height) = (source.width() as f64, source.height() as f64);
+
+        self.draw_image(source, (0.0, 0.0, width, height), (dx, dy, width, height))
+    }
+
+    /// Reads the unpremultiplied RGBA pixels of a region, ignoring the transform.
+    fn get_image_data(
+        &self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) -> Result<Vec<u8>, PlatformError>;
+
+    /// Writes unpremultiplied RGBA pixels to a region, ignoring the transform and clip.
+    fn put_image_data(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        dx: f64,
+        dy: f64,
+    ) -> Result<(), PlatformError>;
+}
+
+/// Persistent key-value store holding the session, settings, achievements and other local data.
+///
+/// Game code only talks to the store through this trait, so that a backend other than the browser
+/// can keep the data elsewhere, e.g. in a file next to a desktop build.
+pub trait KeyValueStore {
+    /// Returns the value stored under the key, if any.
+    fn get(&self, key: &str) -> Option<String>;
+
+    /// Stores the value under the key, returning whether it was written.
+    fn set(&self, key: &str, value: &str) -> bool;
+
+    /// Removes the value stored under the key, if any.
+    fn remove(&self, key: &str);
+
+    /// Returns every key holding a value.
+    fn keys(&self) -> Vec<String>;
+}
+
+/// A decoded clip, in whatever form the [`AudioOutput`] which decoded it plays it from.
+#[derive(Clone)]
+pub struct Sound(pub Rc<dyn Any>);
+
+/// A playing sound, which plays out when dropped.
+pub trait Voice {
+    fn set_volume(&self, volume: f32);
+}
+
+/// Decodes and plays sounds.
+pub trait AudioOutput {
+    /// Decodes the bytes of an audio file, `None` if the format is not understood.
+    fn decode(&self, data: Vec<u8>) -> LocalBoxFuture<'static, Option<Sound>>;
+
+    /// Plays a sound at a playback rate, which shifts its pitch and length, and a volume, on repeat
+    /// if `looped`. The [`Voice`] adjusts the sound while it plays, `None` if it could not start.
+    fn play(
+        &self,
+        sound: &Sound,
+        playback_rate: f32,
+        volume: f32,
+        looped: bool,
+    ) -> Option<Rc<dyn Voice>>;
+}
+
+/// An HTTP request sent through the [`Network`].
+pub struct HttpRequest {
+    pub method: &'static str,
+    /// Absolute URL of the server, or a path of the game's own resources.
+    pub url: String,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Option<String>,
+}
+
+impl HttpRequest {
+    pub fn get(url: impl Into<String>) -> HttpRequest {
+        HttpRequest {
+            method: "GET",
+            url: url.into(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn post(url: impl Into<String>, body: String) -> HttpRequest {
+        HttpRequest {
+            method: "POST",
+            url: url.into(),
+            headers: vec![("Content-Type", "application/json".to_string())],
+            body: Some(body),
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: String) -> HttpRequest {
+        self.headers.push((name, value));
+        self
+    }
+}
+
+/// The answer to an [`HttpRequest`], whatever its status.
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// Determines if the status reports success.
+    pub fn ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Sends requests to the server and loads the game's own resources.
+pub trait Network {
+    /// Sends a request, failing only if no response arrived.
+    fn fetch(
+        &self,
+        request: HttpRequest,
+    ) -> LocalBoxFuture<'static, Result<HttpResponse, PlatformError>>;
+}
+
+/// Modal dialogs and files picked by the player.
+pub trait Dialogs {
+    fn alert(&self, message: &str);
+
+    fn confirm(&self, message: &str) -> bool;
+
+    /// Asks for a line of text, pre-filled with `default`, `None` if cancelled.
+    fn prompt(&self, message: &str, default: &str) -> Option<String>;
+
+    /// Offers text as a file download, or saves it where the player picks.
+    fn save_file(&self, name: &str, contents: &str) -> Result<(), PlatformError>;
+
+    /// Lets the player pick a text file matching the `accept` extensions and reads it, resolving
+    /// to `None` if the picker was dismissed. Fails if no picker can be opened.
+    fn open_file(
+        &self,
+        accept: &[&str],
+    ) -> Result<LocalBoxFuture<'static, Option<Result<String, PlatformError>>>, PlatformError>;
+}
+
+/// Clocks, randomness, tasks and the rest of the runtime the game runs in.
+pub trait System {
+    /// Milliseconds on a monotonic clock, starting around the launch of the game.
+    fn now(&self) -> f64;
+
+    /// Milliseconds since the Unix epoch.
+    fn time(&self) -> f64;
+
+    /// A uniformly random number in `[0, 1)`, not suited for secrets.
+    fn random(&self) -> f64;
+
+    /// Fills the buffer from a cryptographic random number generator.
+    fn fill_random(&self, bytes: &mut [u8]) -> Result<(), PlatformError>;
+
+    /// Runs a future in the background on the game's thread.
+    fn spawn(&self, future: LocalBoxFuture<'static, ()>);
+
+    /// Resolves after a number of milliseconds.
+    fn sleep(&self, milliseconds: f64) -> LocalBoxFuture<'static, ()>;
+
+    /// Creates an offscreen [`Canvas`].
+    fn create_canvas(&self, width: u32, height: u32) -> Result<Box<dyn Canvas>, PlatformError>;
+
+    /// Loads a PNG image of the game's resources into a new [`Canvas`].
+    fn load_image(
+        &self,
+        path: &str,
+    ) -> LocalBoxFuture<'static, Result<Box<dyn Canvas>, PlatformError>>;
+
+    /// Writes a log line to the platform's console.
+    fn log(&self, level: Level, message: &str);
+
+    /// Vibrates the device with alternating vibration and pause milliseconds, returning whether
+    /// the device can vibrate at all.
+    fn vibrate(&self, pattern: &[f64]) -> bool;
+
+    /// Determines if the device can vibrate.
+    fn can_vibrate(&self) -> bool;
+
+    /// The `key=value` parameters the game was launched with, e.g. of an invite link.
+    fn launch_parameters(&self) -> String;
+
+    /// Address the game can be opened at, for links shared with other players.
+    fn page_url(&self) -> Option<String>;
+
+    /// Restarts the game, so stored data is read anew.
+    fn reload(&self);
+}
+
+/// Whether a mouse button is the one which selects, the one which cancels or any other.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MouseButton {
+    Primary,
+    Secondary,
+    Other,
+}
+
+/// Input delivered by the backend, with positions in display pixels from the top left corner of
+/// the game's canvas.
+#[derive(Clone, Debug)]
+pub enum InputEvent {
+    /// The canvas is now shown at another size.
+    Resize {
+        width: f64,
+        height: f64,
+    },
+    MouseDown {
+        button: MouseButton,
+        shift: bool,
+    },
+    MouseUp {
+        button: MouseButton,
+    },
+    MouseMove {
+        x: f64,
+        y: f64,
+        shift: bool,
+    },
+    /// A touch began, at the first finger still on the canvas.
+    TouchStart {
+        touch: Option<(f64, f64)>,
+    },
+    TouchMove {
+        touch: Option<(f64, f64)>,
+    },
+    TouchEnd {
+        touch: Option<(f64, f64)>,
+    },
+    /// A key was pressed, by its physical `code` as the browser names it, e.g. `KeyP` or `Tab`.
+    KeyDown {
+        code: String,
+        shift: bool,
+    },
+    /// A text field of the page was edited and left.
+    TextInput {
+        field: String,
+        value: String,
+    },
+    /// The game was hidden or shown, e.g. by switching tabs or minimising the window.
+    VisibilityChange {
+        hidden: bool,
+    },
+}
+
+/// Receives the [`InputEvent`]s of the backend.
+pub trait InputHandler {
+    /// Handles an event, returning whether it was consumed so the platform should not act on it,
+    /// e.g. moving the focus on `Tab`.
+    fn handle_input(&mut self, event: InputEvent) -> bool;
+}
+
+/// What a backend drives: input as it arrives, frames at the pace of the display.
+pub trait Application: InputHandler {
+    /// Advances and draws a frame onto the game and interface canvases.
+    fn frame(
+        &mut self,
+        context: &dyn Canvas,
+        interface_context: &dyn Canvas,
+    ) -> Result<(), PlatformError>;
+
+    /// Handles network traffic only, called about every second instead of frames while hidden.
+    fn poll(&mut self);
+
+    /// Determines if the game is hidden, which pauses frames.
+    fn hidden(&self) -> bool;
+
+    /// Minimum milliseconds between two frames.
+    fn frame_interval(&self) -> f64;
+}
+
+/// The backend the game runs on, installed once by its entry point.
+pub struct Platform {
+    pub kind: PlatformKind,
+    pub system: Box<dyn System>,
+    pub network: Box<dyn Network>,
+    pub dialogs: Box<dyn Dialogs>,
+    /// Audio output, unless the device has none.
+    pub audio: Option<Box<dyn AudioOutput>>,
+    /// Persistent storage, unless it is withheld, e.g. in some private browsing modes.
+    pub storage: Option<Box<dyn KeyValueStore>>,
+}
+
+thread_local! {
+    static PLATFORM: OnceCell<&'static Platform> = const { OnceCell::new() };
+}
+
+/// Installs the backend, for the rest of the program's life. Later calls are ignored.
+pub fn install(platform: Platform) {
+    PLATFORM.with(|cell| {
+        if cell.get().is_none() {
+            let _ = cell.set(Box::leak(Box::new(platform)));
+        }
+    });
+}
+
+/// The installed backend, if any.
+pub fn try_platform() -> Option<&'static Platform> {
+    PLATFORM.with(|cell| cell.get().copied())
+}
+
+/// The installed backend.
+///
+/// # Panics
+///
+/// Panics if no backend was installed yet.
+pub fn platform() -> &'static Platform {
+    try_platform().expect("no platform installed")
+}
+
+pub fn system() -> &'static dyn System {
+    platform().system.as_ref()
+}
+
+pub fn network() -> &'static dyn Network {
+    platform().network.as_ref()
+}
+
+pub fn dialogs() -> &'static dyn Dialogs {
+    platform().dialogs.as_ref()
+}
+
+/// Runs a future in the background on the game's thread.
+pub fn spawn(future: impl std::future::Future<Output = ()> + 'static) {
+    system().spawn(Box::pin(future));
+}
+
+/// A uniformly random number in `[0, 1)`, not suited for secrets.
+pub fn random() -> f64 {
+    system().random()
+}
@@ -0,0 +1,81 @@
+use std::{io::Cursor, rc::Rc};
+
+use futures::{future::LocalBoxFuture, FutureExt};
+use rodio::{source::Buffered, Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+
+use crate::platform::{AudioOutput, PlatformError, Sound, Voice};
+
+type Clip = Buffered<Decoder<Cursor<Vec<u8>>>>;
+
+/// The desktop's [`AudioOutput`], playing clips on the default output device.
+pub struct NativeAudio {
+    /// Playback ends when the stream is dropped, so it lives as long as the output.
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+}
+
+impl NativeAudio {
+    /// Opens the default output device.
+    pub fn open() -> Result<NativeAudio, PlatformError> {
+        let (stream, handle) = OutputStream::try_default().map_err(|err| err.to_string())?;
+
+        Ok(NativeAudio {
+            _stream: stream,
+            handle,
+        })
+    }
+}
+
+impl AudioOutput for NativeAudio {
+    fn decode(&self, data: Vec<u8>) -> LocalBoxFuture<'static, Option<Sound>> {
+        // Decoded as it first plays, then kept for the next time
+        let clip = Decoder::new(Cursor::new(data))
+            .ok()
+            .map(|decoder| Sound(Rc::new(decoder.buffered())));
+
+        async move { clip }.boxed_local()
+    }
+
+    fn play(
+        &self,
+        sound: &Sound,
+        playback_rate: f32,
+        volume: f32,
+        looped: bool,
+    ) -> Option<Rc<dyn Voice>> {
+        let clip = sound.0.downcast_ref::<Clip>()?.clone();
+        let sink = Sink::try_new(&self.handle).ok()?;
+
+        sink.set_speed(playback_rate);
+        sink.set_volume(volume);
+
+        if looped {
+            sink.append(clip.repeat_infinite());
+        } else {
+            sink.append(clip);
+        }
+
+        Some(Rc::new(NativeVoice { sink: Some(sink) }))
+    }
+}
+
+struct NativeVoice {
+    sink: Option<Sink>,
+}
+
+impl Voice for NativeVoice {
+    fn set_volume(&self, volume: f32) {
+        if let Some(sink) = &self.sink {
+            sink.set_volume(volume);
+        }
+    }
+}
+
+impl Drop for NativeVoice {
+    fn drop(&mut self) {
+        // A sink stops its sound when dropped, detached it plays out
+        if let Some(sink) = self.sink.take() {
+            sink.detach();
+        }
+    }
+}
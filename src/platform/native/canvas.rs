@@ -0,0 +1,660 @@
+use std::{
+    any::Any,
+    cell::{Ref, RefCell},
+    f64::consts::{FRAC_PI_2, TAU},
+    ptr,
+    rc::Rc,
+};
+
+use tiny_skia::{
+    BlendMode, Color, FillRule, FilterQuality, LineCap, Mask, Paint, Path, PathBuilder, Pattern,
+    Pixmap, Point, Rect, Shader, SpreadMode, Stroke, StrokeDash, Transform,
+};
+
+use crate::platform::{Canvas, PlatformError};
+
+/// The part of a canvas's state kept by `save` and `restore`.
+#[derive(Clone)]
+struct DrawState {
+    transform: Transform,
+    fill_color: Color,
+    stroke_color: Color,
+    global_alpha: f32,
+    line_width: f32,
+    line_cap: LineCap,
+    line_dash: Vec<f32>,
+    blend_mode: BlendMode,
+    /// Pixels outside of the clipping region are left alone, everything is drawn when `None`.
+    clip: Option<Rc<Mask>>,
+}
+
+impl Default for DrawState {
+    fn default() -> Self {
+        DrawState {
+            transform: Transform::identity(),
+            fill_color: Color::BLACK,
+            stroke_color: Color::BLACK,
+            global_alpha: 1.0,
+            line_width: 1.0,
+            line_cap: LineCap::Butt,
+            line_dash: Vec::new(),
+            blend_mode: BlendMode::SourceOver,
+            clip: None,
+        }
+    }
+}
+
+/// A [`Canvas`] rasterized in memory, following the HTML canvas's drawing model.
+///
+/// Paths are kept in pixel coordinates, transformed as they are built like the HTML canvas does.
+pub struct NativeCanvas {
+    pixmap: RefCell<Pixmap>,
+    state: RefCell<DrawState>,
+    saved_states: RefCell<Vec<DrawState>>,
+    path: RefCell<PathBuilder>,
+}
+
+impl NativeCanvas {
+    /// Creates a transparent canvas.
+    pub fn new(width: u32, height: u32) -> Result<NativeCanvas, PlatformError> {
+        let pixmap = Pixmap::new(width.max(1), height.max(1)).ok_or("canvas too large")?;
+
+        Ok(NativeCanvas::from_pixmap(pixmap))
+    }
+
+    /// Creates a canvas holding an image.
+    pub fn from_pixmap(pixmap: Pixmap) -> NativeCanvas {
+        NativeCanvas {
+            pixmap: RefCell::new(pixmap),
+            state: RefCell::new(DrawState::default()),
+            saved_states: RefCell::new(Vec::new()),
+            path: RefCell::new(PathBuilder::new()),
+        }
+    }
+
+    /// The premultiplied pixels of the canvas.
+    pub fn pixmap(&self) -> Ref<'_, Pixmap> {
+        self.pixmap.borrow()
+    }
+
+    fn map_point(&self, x: f64, y: f64) -> Point {
+        let mut point = Point::from_xy(x as f32, y as f32);
+        self.state.borrow().transform.map_point(&mut point);
+        point
+    }
+
+    /// Builds a closed rectangle in pixel coordinates.
+    fn rect_path(&self, x: f64, y: f64, width: f64, height: f64) -> Option<Path> {
+        let mut path = PathBuilder::new();
+
+        let corners = [
+            (x, y),
+            (x + width, y),
+            (x + width, y + height),
+            (x, y + height),
+        ];
+
+        for (index, (cx, cy)) in corners.into_iter().enumerate() {
+            let point = self.map_point(cx, cy);
+
+            if index == 0 {
+                path.move_to(point.x, point.y);
+            } else {
+                path.line_to(point.x, point.y);
+            }
+        }
+
+        path.close();
+        path.finish()
+    }
+
+    fn fill_path(&self, path: &Path, color: Color, blend_mode: Option<BlendMode>) {
+        let state = self.state.borrow();
+
+        let mut color = color;
+        color.apply_opacity(state.global_alpha);
+
+        let paint = Paint {
+            shader: Shader::SolidColor(color),
+            blend_mode: blend_mode.unwrap_or(state.blend_mode),
+            anti_alias: true,
+            ..Paint::default()
+        };
+
+        self.pixmap.borrow_mut().fill_path(
+            path,
+            &paint,
+            FillRule::Winding,
+            Transform::identity(),
+            state.clip.as_deref(),
+        );
+    }
+
+    fn stroke_path(&self, path: &Path) {
+        let state = self.state.borrow();
+
+        // Line widths and dashes are in user space, so the path is stroked before transforming
+        let Some(path) = state
+            .transform
+            .invert()
+            .and_then(|inverse| path.clone().transform(inverse))
+        else {
+            return;
+        };
+
+        let mut color = state.stroke_color;
+        color.apply_opacity(state.global_alpha);
+
+        let paint = Paint {
+            shader: Shader::SolidColor(color),
+            blend_mode: state.blend_mode,
+            anti_alias: true,
+            ..Paint::default()
+        };
+
+        let stroke = Stroke {
+            width: state.line_width,
+            line_cap: state.line_cap,
+            dash: StrokeDash::new(state.line_dash.clone(), 0.0),
+            ..Stroke::default()
+        };
+
+        self.pixmap.borrow_mut().stroke_path(
+            &path,
+            &paint,
+            &stroke,
+            state.transform,
+            state.clip.as_deref(),
+        );
+    }
+
+    fn current_path(&self) -> Option<Path> {
+        self.path.borrow().clone().finish()
+    }
+}
+
+impl Canvas for NativeCanvas {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn width(&self) -> u32 {
+        self.pixmap.borrow().width()
+    }
+
+    fn height(&self) -> u32 {
+        self.pixmap.borrow().height()
+    }
+
+    fn resize(&self, width: u32, height: u32, _keep_display_size: bool) {
+        // The window scales the canvas to fit, whatever its backing size
+        if let Some(pixmap) = Pixmap::new(width.max(1), height.max(1)) {
+            *self.pixmap.borrow_mut() = pixmap;
+        }
+
+        *self.state.borrow_mut() = DrawState::default();
+        self.saved_states.borrow_mut().clear();
+        self.path.borrow_mut().clear();
+    }
+
+    fn save(&self) {
+        let state = self.state.borrow().clone();
+        self.saved_states.borrow_mut().push(state);
+    }
+
+    fn restore(&self) {
+        if let Some(state) = self.saved_states.borrow_mut().pop() {
+            *self.state.borrow_mut() = state;
+        }
+    }
+
+    fn translate(&self, x: f64, y: f64) -> Result<(), PlatformError> {
+        let mut state = self.state.borrow_mut();
+        state.transform = state.transform.pre_translate(x as f32, y as f32);
+
+        Ok(())
+    }
+
+    fn rotate(&self, angle: f64) -> Result<(), PlatformError> {
+        let mut state = self.state.borrow_mut();
+        state.transform = state.transform.pre_rotate(angle.to_degrees() as f32);
+
+        Ok(())
+    }
+
+    fn scale(&self, x: f64, y: f64) -> Result<(), PlatformError> {
+        let mut state = self.state.borrow_mut();
+        state.transform = state.transform.pre_scale(x as f32, y as f32);
+
+        Ok(())
+    }
+
+    fn set_fill_style(&self, color: &str) {
+        // Invalid colours are ignored, like the HTML canvas does
+        if let Some(color) = parse_color(color) {
+            self.state.borrow_mut().fill_color = color;
+        }
+    }
+
+    fn set_stroke_style(&self, color: &str) {
+        if let Some(color) = parse_color(color) {
+            self.state.borrow_mut().stroke_color = color;
+        }
+    }
+
+    fn set_line_width(&self, width: f64) {
+        if width.is_finite() && width > 0.0 {
+            self.state.borrow_mut().line_width = width as f32;
+        }
+    }
+
+    fn set_line_cap(&self, cap: &str) {
+        let line_cap = match cap {
+            "butt" => LineCap::Butt,
+            "round" => LineCap::Round,
+            "square" => LineCap::Square,
+            _ => return,
+        };
+
+        self.state.borrow_mut().line_cap = line_cap;
+    }
+
+    fn set_line_dash(&self, segments: &[f64]) -> Result<(), PlatformError> {
+        if segments
+            .iter()
+            .any(|segment| !segment.is_finite() || *segment < 0.0)
+        {
+            return Ok(());
+        }
+
+        let mut line_dash: Vec<f32> = segments.iter().map(|segment| *segment as f32).collect();
+
+        // Odd lists repeat, so dashes and gaps alternate
+        if line_dash.len() % 2 == 1 {
+            line_dash.extend_from_within(..);
+        }
+
+        self.state.borrow_mut().line_dash = line_dash;
+
+        Ok(())
+    }
+
+    fn set_global_alpha(&self, alpha: f64) {
+        if (0.0..=1.0).contains(&alpha) {
+            self.state.borrow_mut().global_alpha = alpha as f32;
+        }
+    }
+
+    fn set_global_composite_operation(&self, operation: &str) -> Result<(), PlatformError> {
+        let blend_mode = match operation {
+            "source-over" => BlendMode::SourceOver,
+            "destination-out" => BlendMode::DestinationOut,
+            _ => return Err(format!("unsupported composite operation {operation}").into()),
+        };
+
+        self.state.borrow_mut().blend_mode = blend_mode;
+
+        Ok(())
+    }
+
+    fn begin_path(&self) {
+        self.path.borrow_mut().clear();
+    }
+
+    fn close_path(&self) {
+        self.path.borrow_mut().close();
+    }
+
+    fn move_to(&self, x: f64, y: f64) {
+        let point = self.map_point(x, y);
+        self.path.borrow_mut().move_to(point.x, point.y);
+    }
+
+    fn line_to(&self, x: f64, y: f64) {
+        let point = self.map_point(x, y);
+        let mut path = self.path.borrow_mut();
+
+        if path.last_point().is_some() {
+            path.line_to(point.x, point.y);
+        } else {
+            path.move_to(point.x, point.y);
+        }
+    }
+
+    fn rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        self.move_to(x, y);
+        self.line_to(x + width, y);
+        self.line_to(x + width, y + height);
+        self.line_to(x, y + height);
+        self.close_path();
+        self.move_to(x, y);
+    }
+
+    fn arc_with_anticlockwise(
+        &self,
+        x: f64,
+        y: f64,
+        radius: f64,
+        start_angle: f64,
+        end_angle: f64,
+        anticlockwise: bool,
+    ) -> Result<(), PlatformError> {
+        if radius < 0.0 {
+            return Err("negative arc radius".into());
+        }
+
+        let sweep = arc_sweep(start_angle, end_angle, anticlockwise);
+        let point_at = |angle: f64| (x + radius * angle.cos(), y + radius * angle.sin());
+
+        let (start_x, start_y) = point_at(start_angle);
+        self.line_to(start_x, start_y);
+
+        // Cubic curves follow a circle closely for up to a quarter turn each
+        let segments = (sweep.abs() / FRAC_PI_2).ceil().max(1.0) as usize;
+        let step = sweep / segments as f64;
+        let handle = 4.0 / 3.0 * (step / 4.0).tan() * radius;
+
+        let mut path = self.path.borrow_mut();
+
+        for segment in 0..segments {
+            let from = start_angle + step * segment as f64;
+            let to = from + step;
+
+            let (from_x, from_y) = point_at(from);
+            let (to_x, to_y) = point_at(to);
+
+            let control_a =
+                self.map_point(from_x - handle * from.sin(), from_y + handle * from.cos());
+            let control_b = self.map_point(to_x + handle * to.sin(), to_y - handle * to.cos());
+            let end = self.map_point(to_x, to_y);
+
+            path.cubic_to(
+                control_a.x,
+                control_a.y,
+                control_b.x,
+                control_b.y,
+                end.x,
+                end.y,
+            );
+        }
+
+        Ok(())
+    }
+
+    fn fill(&self) {
+        if let Some(path) = self.current_path() {
+            let fill_color = self.state.borrow().fill_color;
+            self.fill_path(&path, fill_color, None);
+        }
+    }
+
+    fn stroke(&self) {
+        if let Some(path) = self.current_path() {
+            self.stroke_path(&path);
+        }
+    }
+
+    fn clip(&self) {
+        let Some(path) = self.current_path() else {
+            return;
+        };
+
+        let mut state = self.state.borrow_mut();
+
+        let mask = match state.clip.take() {
+            Some(clip) => {
+                let mut mask = Rc::unwrap_or_clone(clip);
+                mask.intersect_path(&path, FillRule::Winding, true, Transform::identity());
+                mask
+            }
+            None => {
+                let pixmap = self.pixmap.borrow();
+                let Some(mut mask) = Mask::new(pixmap.width(), pixmap.height()) else {
+                    return;
+                };
+
+                mask.fill_path(&path, FillRule::Winding, true, Transform::identity());
+                mask
+            }
+        };
+
+        state.clip = Some(Rc::new(mask));
+    }
+
+    fn fill_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        if let Some(path) = self.rect_path(x, y, width, height) {
+            let fill_color = self.state.borrow().fill_color;
+            self.fill_path(&path, fill_color, None);
+        }
+    }
+
+    fn stroke_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        if let Some(path) = self.rect_path(x, y, width, height) {
+            self.stroke_path(&path);
+        }
+    }
+
+    fn clear_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        if let Some(path) = self.rect_path(x, y, width, height) {
+            self.fill_path(&path, Color::BLACK, Some(BlendMode::Clear));
+        }
+    }
+
+    fn draw_image(
+        &self,
+        source: &dyn Canvas,
+        (sx, sy, sw, sh): (f64, f64, f64, f64),
+        (dx, dy, dw, dh): (f64, f64, f64, f64),
+    ) -> Result<(), PlatformError> {
+        let source = source
+            .as_any()
+            .downcast_ref::<NativeCanvas>()
+            .ok_or("not a native canvas")?;
+
+        if sw == 0.0 || sh == 0.0 {
+            return Ok(());
+        }
+
+        // Drawing a canvas onto itself reads the pixels from before the draw
+        let copy;
+        let source_pixmap = if ptr::eq(source, self) {
+            copy = self.pixmap.borrow().clone();
+            &copy
+        } else {
+            &*source.pixmap.borrow()
+        };
+
+        let Some(destination) = Rect::from_xywh(dx as f32, dy as f32, dw as f32, dh as f32) else {
+            return Ok(());
+        };
+
+        let state = self.state.borrow();
+
+        let pattern_transform = Transform::from_translate(dx as f32, dy as f32)
+            .pre_scale((dw / sw) as f32, (dh / sh) as f32)
+            .pre_translate(-sx as f32, -sy as f32);
+
+        let paint = Paint {
+            shader: Pattern::new(
+                source_pixmap.as_ref(),
+                SpreadMode::Pad,
+                FilterQuality::Nearest,
+                state.global_alpha,
+                pattern_transform,
+            ),
+            blend_mode: state.blend_mode,
+            anti_alias: false,
+            ..Paint::default()
+        };
+
+        self.pixmap.borrow_mut().fill_rect(
+            destination,
+            &paint,
+            state.transform,
+            state.clip.as_deref(),
+        );
+
+        Ok(())
+    }
+
+    fn get_image_data(
+        &self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) -> Result<Vec<u8>, PlatformError> {
+        let pixmap = self.pixmap.borrow();
+        let (x, y) = (x.round() as i64, y.round() as i64);
+        let (width, height) = (
+            width.round().max(0.0) as i64,
+            height.round().max(0.0) as i64,
+        );
+
+        let mut data = vec![0; (width * height * 4) as usize];
+
+        for row in 0..height {
+            for column in 0..width {
+                let (px, py) = (x + column, y + row);
+
+                if px < 0 || py < 0 {
+                    continue;
+                }
+
+                // Pixels outside of the canvas read as transparent black
+                if let Some(pixel) = pixmap.pixel(px as u32, py as u32) {
+                    let color = pixel.demultiply();
+                    let offset = ((row * width + column) * 4) as usize;
+
+                    data[offset..offset + 4].copy_from_slice(&[
+                        color.red(),
+                        color.green(),
+                        color.blue(),
+                        color.alpha(),
+                    ]);
+                }
+            }
+        }
+
+        Ok(data)
+    }
+
+    fn put_image_data(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        dx: f64,
+        dy: f64,
+    ) -> Result<(), PlatformError> {
+        if data.len() != (width * height * 4) as usize {
+            return Err("image data does not match its size".into());
+        }
+
+        let mut pixmap = self.pixmap.borrow_mut();
+        let (pixmap_width, pixmap_height) = (pixmap.width() as i64, pixmap.height() as i64);
+        let (dx, dy) = (dx.round() as i64, dy.round() as i64);
+        let pixels = pixmap.pixels_mut();
+
+        for row in 0..height as i64 {
+            for column in 0..width as i64 {
+                let (px, py) = (dx + column, dy + row);
+
+                if px < 0 || py < 0 || px >= pixmap_width || py >= pixmap_height {
+                    continue;
+                }
+
+                let offset = ((row * width as i64 + column) * 4) as usize;
+                let [red, green, blue, alpha] = data[offset..offset + 4] else {
+                    continue;
+                };
+
+                pixels[(py * pixmap_width + px) as usize] =
+                    tiny_skia::ColorU8::from_rgba(red, green, blue, alpha).premultiply();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Signed angle an arc turns through, following the HTML canvas's rules.
+fn arc_sweep(start_angle: f64, end_angle: f64, anticlockwise: bool) -> f64 {
+    let sweep = end_angle - start_angle;
+
+    if anticlockwise {
+        if sweep <= -TAU {
+            -TAU
+        } else if sweep > 0.0 {
+            let remainder = sweep.rem_euclid(TAU);
+
+            if remainder == 0.0 {
+                0.0
+            } else {
+                remainder - TAU
+            }
+        } else {
+            sweep
+        }
+    } else if sweep >= TAU {
+        TAU
+    } else if sweep < 0.0 {
+        sweep.rem_euclid(TAU)
+    } else {
+        sweep
+    }
+}
+
+/// Parses the CSS colours the game uses: `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`, `rgb(r, g, b)`
+/// and `rgba(r, g, b, a)`.
+fn parse_color(color: &str) -> Option<Color> {
+    let color = color.trim();
+
+    if let Some(hex) = color.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|digit| digit.to_digit(16).map(|digit| digit as u8))
+            .collect::<Option<_>>()?;
+
+        let channels: Vec<u8> = match digits.len() {
+            3 | 4 => digits.iter().map(|digit| digit * 17).collect(),
+            6 | 8 => digits
+                .chunks(2)
+                .map(|pair| pair[0] * 16 + pair[1])
+                .collect(),
+            _ => return None,
+        };
+
+        let alpha = channels.get(3).copied().unwrap_or(255);
+
+        return Some(Color::from_rgba8(
+            channels[0],
+            channels[1],
+            channels[2],
+            alpha,
+        ));
+    }
+
+    let arguments = color
+        .strip_prefix("rgba(")
+        .or_else(|| color.strip_prefix("rgb("))?
+        .strip_suffix(')')?;
+
+    let values: Vec<f32> = arguments
+        .split(',')
+        .map(|value| value.trim().parse().ok())
+        .collect::<Option<_>>()?;
+
+    let (red, green, blue, alpha) = match values[..] {
+        [red, green, blue] => (red, green, blue, 1.0),
+        [red, green, blue, alpha] => (red, green, blue, alpha),
+        _ => return None,
+    };
+
+    Color::from_rgba(
+        red.clamp(0.0, 255.0) / 255.0,
+        green.clamp(0.0, 255.0) / 255.0,
+        blue.clamp(0.0, 255.0) / 255.0,
+        alpha.clamp(0.0, 1.0),
+    )
+}
@@ -0,0 +1,467 @@
+//! Desktop backend: a window drawn through a software rasterizer, requests sent from worker
+//! threads and data kept in the user's configuration directory.
+
+#[cfg(feature = "native-audio")]
+mod audio;
+mod canvas;
+mod window;
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    env, fs,
+    future::Future,
+    io::{ErrorKind, Read},
+    path::PathBuf,
+    process::Command,
+    rc::Rc,
+    sync::OnceLock,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{
+    channel::oneshot,
+    executor::{LocalPool, LocalSpawner},
+    future::LocalBoxFuture,
+    task::LocalSpawnExt,
+    FutureExt,
+};
+use tiny_skia::Pixmap;
+
+pub use self::canvas::NativeCanvas;
+use super::{
+    install, Application, AudioOutput, Canvas, Dialogs, HttpRequest, HttpResponse, KeyValueStore,
+    Network, Platform, PlatformError, PlatformKind, System,
+};
+use crate::log::Level;
+
+/// Title of the window and of its dialogs.
+const TITLE: &str = "Critter Showdown";
+
+/// Seconds a request may take before it is given up on.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Drives the tasks of the desktop backend, on the thread which installed it.
+pub struct NativeRuntime {
+    pool: RefCell<LocalPool>,
+}
+
+impl NativeRuntime {
+    /// Installs the desktop backend.
+    pub fn install() -> NativeRuntime {
+        let pool = LocalPool::new();
+
+        install(Platform {
+            kind: PlatformKind::Native,
+            system: Box::new(NativeSystem::new(pool.spawner())),
+            network: Box::new(NativeNetwork::new()),
+            dialogs: Box::new(NativeDialogs),
+            audio: audio_output(),
+            storage: FileStore::open().map(|store| Box::new(store) as Box<dyn KeyValueStore>),
+        });
+
+        NativeRuntime {
+            pool: RefCell::new(pool),
+        }
+    }
+
+    /// Runs a future and the tasks it spawns until it completes.
+    pub fn block_on<T>(&self, future: impl Future<Output = T>) -> T {
+        self.pool.borrow_mut().run_until(future)
+    }
+
+    /// Opens the window and runs the application until it is closed.
+    pub fn run(
+        self,
+        app: Rc<RefCell<dyn Application>>,
+        size: (u32, u32),
+    ) -> Result<(), PlatformError> {
+        window::run(self.pool.into_inner(), app, size)
+    }
+}
+
+#[cfg(feature = "native-audio")]
+fn audio_output() -> Option<Box<dyn AudioOutput>> {
+    match audio::NativeAudio::open() {
+        Ok(audio) => Some(Box::new(audio)),
+        Err(err) => {
+            // The log is not installed yet
+            eprintln!("no audio output: {err}");
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "native-audio"))]
+fn audio_output() -> Option<Box<dyn AudioOutput>> {
+    None
+}
+
+/// Directory holding the game's `static` resources: the working directory when run from the
+/// repository, otherwise the one of the executable or above it.
+fn resource_root() -> &'static PathBuf {
+    static RESOURCE_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+    RESOURCE_ROOT.get_or_init(|| {
+        let working_directory = env::current_dir().unwrap_or_default();
+        let executable = env::current_exe().unwrap_or_default();
+
+        std::iter::once(working_directory.as_path())
+            .chain(executable.ancestors().skip(1))
+            .find(|directory| directory.join("static").is_dir())
+            .unwrap_or(&working_directory)
+            .to_path_buf()
+    })
+}
+
+/// Reads a resource by the path the web build fetches it from, e.g. `/static/balance.json?t=1`,
+/// `None` if there is no such resource.
+fn read_resource(path: &str) -> Result<Option<Vec<u8>>, PlatformError> {
+    let path = path.split('?').next().unwrap_or_default();
+    let path = path.trim_start_matches("./").trim_start_matches('/');
+
+    match fs::read(resource_root().join(path)) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("cannot read {path}: {err}").into()),
+    }
+}
+
+/// Resolves once a worker thread has finished, without blocking the game's thread.
+fn run_in_thread<T: Send + 'static>(
+    work: impl FnOnce() -> T + Send + 'static,
+) -> LocalBoxFuture<'static, Option<T>> {
+    let (sender, receiver) = oneshot::channel();
+
+    thread::spawn(move || {
+        let _ = sender.send(work());
+    });
+
+    async move { receiver.await.ok() }.boxed_local()
+}
+
+/// The desktop's [`Network`], sending requests from worker threads and reading resources from the
+/// disk.
+pub struct NativeNetwork {
+    agent: ureq::Agent,
+}
+
+impl NativeNetwork {
+    fn new() -> NativeNetwork {
+        NativeNetwork {
+            agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build(),
+        }
+    }
+}
+
+impl Network for NativeNetwork {
+    fn fetch(
+        &self,
+        request: HttpRequest,
+    ) -> LocalBoxFuture<'static, Result<HttpResponse, PlatformError>> {
+        // Paths without a scheme are the game's own resources, which ship next to it
+        if !request.url.contains("://") {
+            let response = read_resource(&request.url).map(|body| match body {
+                Some(body) => HttpResponse { status: 200, body },
+                None => HttpResponse {
+                    status: 404,
+                    body: Vec::new(),
+                },
+            });
+
+            return async move { response }.boxed_local();
+        }
+
+        let agent = self.agent.clone();
+        let response = run_in_thread(move || send(&agent, request));
+
+        async move { response.await.unwrap_or(Err("request abandoned".into())) }.boxed_local()
+    }
+}
+
+/// Sends a request, answering with the response whatever its status.
+fn send(agent: &ureq::Agent, request: HttpRequest) -> Result<HttpResponse, PlatformError> {
+    let mut call = agent.request(request.method, &request.url);
+
+    for (name, value) in &request.headers {
+        call = call.set(name, value);
+    }
+
+    let response = match request.body {
+        Some(body) => call.send_string(&body),
+        None => call.call(),
+    };
+
+    let response = match response {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+        Err(err) => return Err(err.to_string().into()),
+    };
+
+    let status = response.status();
+    let mut body = Vec::new();
+
+    response
+        .into_reader()
+        .read_to_end(&mut body)
+        .map_err(|err| err.to_string())?;
+
+    Ok(HttpResponse { status, body })
+}
+
+/// The desktop's [`KeyValueStore`], a JSON file in the user's configuration directory.
+pub struct FileStore {
+    path: PathBuf,
+    entries: RefCell<BTreeMap<String, String>>,
+}
+
+impl FileStore {
+    /// Opens the store, `None` if the system has no configuration directory.
+    fn open() -> Option<FileStore> {
+        let path = dirs::config_dir()?
+            .join("crittershowdown")
+            .join("storage.json");
+
+        // A missing or damaged file starts an empty store, as a new browser profile would
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+
+        Some(FileStore {
+            path,
+            entries: RefCell::new(entries),
+        })
+    }
+
+    /// Writes the entries to a temporary file first, so a crash never leaves half a store.
+    fn write(&self) -> bool {
+        let Ok(json) = serde_json::to_vec(&*self.entries.borrow()) else {
+            return false;
+        };
+
+        let temporary = self.path.with_extension("json.tmp");
+
+        self.path
+            .parent()
+            .is_some_and(|directory| fs::create_dir_all(directory).is_ok())
+            && fs::write(&temporary, json).is_ok()
+            && fs::rename(&temporary, &self.path).is_ok()
+    }
+}
+
+impl KeyValueStore for FileStore {
+    fn get(&self, key: &str) -> Option<String> {
+        self.entries.borrow().get(key).cloned()
+    }
+
+    fn set(&self, key: &str, value: &str) -> bool {
+        let previous = self
+            .entries
+            .borrow_mut()
+            .insert(key.to_string(), value.to_string());
+
+        if self.write() {
+            return true;
+        }
+
+        // Unwritten values are not kept, as with a full local storage
+        let mut entries = self.entries.borrow_mut();
+
+        match previous {
+            Some(previous) => entries.insert(key.to_string(), previous),
+            None => entries.remove(key),
+        };
+
+        false
+    }
+
+    fn remove(&self, key: &str) {
+        if self.entries.borrow_mut().remove(key).is_some() {
+            self.write();
+        }
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.entries.borrow().keys().cloned().collect()
+    }
+}
+
+/// The desktop's [`Dialogs`], shown by the operating system.
+pub struct NativeDialogs;
+
+impl Dialogs for NativeDialogs {
+    fn alert(&self, message: &str) {
+        tinyfiledialogs::message_box_ok(TITLE, message, tinyfiledialogs::MessageBoxIcon::Info);
+    }
+
+    fn confirm(&self, message: &str) -> bool {
+        matches!(
+            tinyfiledialogs::message_box_ok_cancel(
+                TITLE,
+                message,
+                tinyfiledialogs::MessageBoxIcon::Question,
+                tinyfiledialogs::OkCancel::Cancel,
+            ),
+            tinyfiledialogs::OkCancel::Ok
+        )
+    }
+
+    fn prompt(&self, message: &str, default: &str) -> Option<String> {
+        tinyfiledialogs::input_box(TITLE, message, default)
+    }
+
+    fn save_file(&self, name: &str, contents: &str) -> Result<(), PlatformError> {
+        let Some(path) = tinyfiledialogs::save_file_dialog(TITLE, name) else {
+            return Ok(());
+        };
+
+        fs::write(&path, contents).map_err(|err| format!("cannot write {path}: {err}").into())
+    }
+
+    fn open_file(
+        &self,
+        accept: &[&str],
+    ) -> Result<LocalBoxFuture<'static, Option<Result<String, PlatformError>>>, PlatformError> {
+        let patterns: Vec<String> = accept
+            .iter()
+            .map(|extension| format!("*.{extension}"))
+            .collect();
+        let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+        let description = patterns.join(", ");
+
+        let text = tinyfiledialogs::open_file_dialog(TITLE, "", Some((&patterns, &description)))
+            .map(|path| {
+                fs::read_to_string(&path).map_err(|err| format!("cannot read {path}: {err}").into())
+            });
+
+        Ok(async move { text }.boxed_local())
+    }
+}
+
+/// The desktop's [`System`].
+pub struct NativeSystem {
+    started_at: Instant,
+    spawner: LocalSpawner,
+    /// State of the xorshift generator behind [`System::random`].
+    random_state: Cell<u64>,
+}
+
+impl NativeSystem {
+    fn new(spawner: LocalSpawner) -> NativeSystem {
+        let mut seed = [0; 8];
+        let _ = getrandom::getrandom(&mut seed);
+
+        NativeSystem {
+            started_at: Instant::now(),
+            spawner,
+            // The generator never leaves zero, so that seed is avoided
+            random_state: Cell::new(u64::from_le_bytes(seed) | 1),
+        }
+    }
+}
+
+impl System for NativeSystem {
+    fn now(&self) -> f64 {
+        self.started_at.elapsed().as_secs_f64() * 1000.0
+    }
+
+    fn time(&self) -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1000.0
+    }
+
+    fn random(&self) -> f64 {
+        let mut state = self.random_state.get();
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        self.random_state.set(state);
+
+        // The top 53 bits fill the mantissa evenly
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn fill_random(&self, bytes: &mut [u8]) -> Result<(), PlatformError> {
+        getrandom::getrandom(bytes).map_err(|err| err.to_string().into())
+    }
+
+    fn spawn(&self, future: LocalBoxFuture<'static, ()>) {
+        // Fails only once the pool is gone, when nothing runs anymore
+        let _ = self.spawner.spawn_local(future);
+    }
+
+    fn sleep(&self, milliseconds: f64) -> LocalBoxFuture<'static, ()> {
+        let duration = Duration::from_secs_f64(milliseconds.max(0.0) / 1000.0);
+        let woken = run_in_thread(move || thread::sleep(duration));
+
+        async move {
+            woken.await;
+        }
+        .boxed_local()
+    }
+
+    fn create_canvas(&self, width: u32, height: u32) -> Result<Box<dyn Canvas>, PlatformError> {
+        Ok(Box::new(NativeCanvas::new(width, height)?))
+    }
+
+    fn load_image(
+        &self,
+        path: &str,
+    ) -> LocalBoxFuture<'static, Result<Box<dyn Canvas>, PlatformError>> {
+        let image = read_resource(path)
+            .and_then(|data| data.ok_or_else(|| format!("no image at {path}").into()))
+            .and_then(|data| {
+                Pixmap::decode_png(&data)
+                    .map_err(|err| format!("invalid image {path}: {err}").into())
+            })
+            .map(|pixmap| Box::new(NativeCanvas::from_pixmap(pixmap)) as Box<dyn Canvas>);
+
+        async move { image }.boxed_local()
+    }
+
+    fn log(&self, level: Level, message: &str) {
+        eprintln!("{} {message}", level.letter());
+    }
+
+    fn vibrate(&self, _pattern: &[f64]) -> bool {
+        false
+    }
+
+    fn can_vibrate(&self) -> bool {
+        false
+    }
+
+    /// Parameters are passed as arguments, either as `key=value` pairs or as a whole invite link.
+    fn launch_parameters(&self) -> String {
+        env::args()
+            .skip(1)
+            .map(|argument| match argument.split_once('?') {
+                Some((_, query)) => query.to_string(),
+                None => argument,
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    fn page_url(&self) -> Option<String> {
+        None
+    }
+
+    fn reload(&self) {
+        let Ok(executable) = env::current_exe() else {
+            return;
+        };
+
+        if Command::new(executable)
+            .args(env::args().skip(1))
+            .spawn()
+            .is_ok()
+        {
+            std::process::exit(0);
+        }
+    }
+}
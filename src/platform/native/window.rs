@@ -0,0 +1,448 @@
+use std::{
+    cell::RefCell,
+    num::NonZeroU32,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use futures::executor::LocalPool;
+use softbuffer::{Context, Surface};
+use tiny_skia::{Pixmap, PremultipliedColorU8};
+use winit::{
+    application::ApplicationHandler,
+    dpi::{LogicalSize, PhysicalSize},
+    event::{ElementState, MouseButton as WinitMouseButton, TouchPhase, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::PhysicalKey,
+    window::{Window, WindowId},
+};
+
+use super::{NativeCanvas, TITLE};
+use crate::platform::{Application, InputEvent, MouseButton, PlatformError};
+
+/// Colour around the canvases, as behind them on the page.
+const BACKGROUND: [u8; 3] = [0x2a, 0x5f, 0x55];
+
+/// Time between polls while the window is hidden and draws no frames.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Milliseconds between frames of displays which do not report their refresh rate.
+const DEFAULT_DISPLAY_INTERVAL: f64 = 1000.0 / 60.0;
+
+/// Opens the window and runs the application until the window is closed.
+pub fn run(
+    pool: LocalPool,
+    app: Rc<RefCell<dyn Application>>,
+    (width, height): (u32, u32),
+) -> Result<(), PlatformError> {
+    let event_loop = EventLoop::new().map_err(|err| err.to_string())?;
+
+    let mut desktop = Desktop {
+        pool,
+        app,
+        canvas: NativeCanvas::new(width, height)?,
+        interface_canvas: NativeCanvas::new(width, height)?,
+        size: (width, height),
+        window: None,
+        placement: None,
+        minimised: false,
+        occluded: false,
+        shift: false,
+        next_frame: Instant::now(),
+        next_poll: Instant::now(),
+        error: None,
+    };
+
+    event_loop
+        .run_app(&mut desktop)
+        .map_err(|err| err.to_string())?;
+
+    desktop.error.map_or(Ok(()), Err)
+}
+
+/// A window and the surface its canvases are shown on.
+struct DesktopWindow {
+    window: Rc<Window>,
+    surface: Surface<Rc<Window>, Rc<Window>>,
+}
+
+/// Where the canvases are shown in the window, in physical pixels.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Placement {
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Placement {
+    /// Fits the canvases into the window at their aspect ratio, centred between bars.
+    fn fit(window_size: PhysicalSize<u32>, (width, height): (u32, u32)) -> Placement {
+        let (window_width, window_height) = (window_size.width as f64, window_size.height as f64);
+        let scale = (window_width / width as f64).min(window_height / height as f64);
+
+        let (display_width, display_height) = (width as f64 * scale, height as f64 * scale);
+
+        Placement {
+            left: ((window_width - display_width) / 2.0).floor(),
+            top: ((window_height - display_height) / 2.0).floor(),
+            width: display_width,
+            height: display_height,
+        }
+    }
+}
+
+struct Desktop {
+    pool: LocalPool,
+    app: Rc<RefCell<dyn Application>>,
+    canvas: NativeCanvas,
+    interface_canvas: NativeCanvas,
+    /// Size the canvases are created at, which sets the aspect ratio they are shown at.
+    size: (u32, u32),
+    window: Option<DesktopWindow>,
+    placement: Option<Placement>,
+    minimised: bool,
+    occluded: bool,
+    shift: bool,
+    next_frame: Instant,
+    next_poll: Instant,
+    /// Error which closed the window early.
+    error: Option<PlatformError>,
+}
+
+impl Desktop {
+    fn open_window(&mut self, event_loop: &ActiveEventLoop) -> Result<(), PlatformError> {
+        let attributes = Window::default_attributes()
+            .with_title(TITLE)
+            .with_inner_size(LogicalSize::new(self.size.0, self.size.1))
+            .with_min_inner_size(LogicalSize::new(self.size.0 / 4, self.size.1 / 4));
+
+        let window = Rc::new(
+            event_loop
+                .create_window(attributes)
+                .map_err(|err| err.to_string())?,
+        );
+
+        // The game draws its own cursor
+        window.set_cursor_visible(false);
+
+        let context = Context::new(window.clone()).map_err(|err| err.to_string())?;
+        let surface = Surface::new(&context, window.clone()).map_err(|err| err.to_string())?;
+
+        self.window = Some(DesktopWindow { window, surface });
+        self.resize();
+
+        Ok(())
+    }
+
+    /// Places the canvases in the window anew, telling the application their new display size.
+    fn resize(&mut self) {
+        let Some(window) = &self.window else {
+            return;
+        };
+
+        let window_size = window.window.inner_size();
+        self.minimised = window_size.width == 0 || window_size.height == 0;
+
+        if self.minimised {
+            return self.update_visibility();
+        }
+
+        let placement = Placement::fit(window_size, self.size);
+
+        if self.placement != Some(placement) {
+            self.placement = Some(placement);
+            self.app.borrow_mut().handle_input(InputEvent::Resize {
+                width: placement.width,
+                height: placement.height,
+            });
+        }
+
+        self.update_visibility();
+    }
+
+    fn update_visibility(&mut self) {
+        let hidden = self.minimised || self.occluded;
+
+        if hidden != self.app.borrow().hidden() {
+            self.app
+                .borrow_mut()
+                .handle_input(InputEvent::VisibilityChange { hidden });
+        }
+    }
+
+    /// Minimum milliseconds between frames the display can show.
+    fn display_interval(&self) -> f64 {
+        self.window
+            .as_ref()
+            .and_then(|window| window.window.current_monitor())
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .map_or(DEFAULT_DISPLAY_INTERVAL, |millihertz| {
+                1_000_000.0 / millihertz as f64
+            })
+    }
+
+    fn frame(&mut self) -> Result<(), PlatformError> {
+        self.app
+            .borrow_mut()
+            .frame(&self.canvas, &self.interface_canvas)?;
+
+        self.present()
+    }
+
+    /// Shows the canvases, the interface over the game, scaled without smoothing.
+    fn present(&mut self) -> Result<(), PlatformError> {
+        let (Some(window), Some(placement)) = (&mut self.window, self.placement) else {
+            return Ok(());
+        };
+
+        let window_size = window.window.inner_size();
+        let (Some(width), Some(height)) = (
+            NonZeroU32::new(window_size.width),
+            NonZeroU32::new(window_size.height),
+        ) else {
+            return Ok(());
+        };
+
+        window
+            .surface
+            .resize(width, height)
+            .map_err(|err| err.to_string())?;
+
+        let mut buffer = window.surface.buffer_mut().map_err(|err| err.to_string())?;
+
+        composite(
+            &mut buffer,
+            window_size.width as usize,
+            placement,
+            &self.canvas.pixmap(),
+            &self.interface_canvas.pixmap(),
+        );
+
+        buffer.present().map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+
+    fn fail(&mut self, event_loop: &ActiveEventLoop, err: PlatformError) {
+        self.error = Some(err);
+        event_loop.exit();
+    }
+}
+
+impl ApplicationHandler for Desktop {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_none() {
+            if let Err(err) = self.open_window(event_loop) {
+                self.fail(event_loop, err);
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        let placement = self.placement.unwrap_or(Placement {
+            left: 0.0,
+            top: 0.0,
+            width: 1.0,
+            height: 1.0,
+        });
+
+        let input_event = match event {
+            WindowEvent::CloseRequested => {
+                event_loop.exit();
+                return;
+            }
+            WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                self.resize();
+                return;
+            }
+            WindowEvent::Occluded(occluded) => {
+                self.occluded = occluded;
+                self.update_visibility();
+                return;
+            }
+            WindowEvent::RedrawRequested => {
+                if let Err(err) = self.present() {
+                    self.fail(event_loop, err);
+                }
+                return;
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.shift = modifiers.state().shift_key();
+                return;
+            }
+            WindowEvent::CursorMoved { position, .. } => InputEvent::MouseMove {
+                x: position.x - placement.left,
+                y: position.y - placement.top,
+                shift: self.shift,
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    WinitMouseButton::Left => MouseButton::Primary,
+                    WinitMouseButton::Right => MouseButton::Secondary,
+                    _ => MouseButton::Other,
+                };
+
+                match state {
+                    ElementState::Pressed => InputEvent::MouseDown {
+                        button,
+                        shift: self.shift,
+                    },
+                    ElementState::Released => InputEvent::MouseUp { button },
+                }
+            }
+            WindowEvent::Touch(touch) => {
+                let position = Some((
+                    touch.location.x - placement.left,
+                    touch.location.y - placement.top,
+                ));
+
+                match touch.phase {
+                    TouchPhase::Started => InputEvent::TouchStart { touch: position },
+                    TouchPhase::Moved => InputEvent::TouchMove { touch: position },
+                    // The finger which left is no longer on the canvas
+                    TouchPhase::Ended | TouchPhase::Cancelled => {
+                        InputEvent::TouchEnd { touch: None }
+                    }
+                }
+            }
+            WindowEvent::KeyboardInput { event, .. } => match event.physical_key {
+                // Key codes are named after the ones of the browser's keyboard events
+                PhysicalKey::Code(code) if event.state == ElementState::Pressed => {
+                    InputEvent::KeyDown {
+                        code: format!("{code:?}"),
+                        shift: self.shift,
+                    }
+                }
+                _ => return,
+            },
+            _ => return,
+        };
+
+        self.app.borrow_mut().handle_input(input_event);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.pool.run_until_stalled();
+
+        let now = Instant::now();
+
+        if self.app.borrow().hidden() {
+            // Frames pause while hidden, network traffic is kept alive separately
+            if now >= self.next_poll {
+                self.app.borrow_mut().poll();
+                self.pool.run_until_stalled();
+                self.next_poll = now + POLL_INTERVAL;
+            }
+
+            event_loop.set_control_flow(ControlFlow::WaitUntil(self.next_poll));
+            return;
+        }
+
+        if now >= self.next_frame {
+            let interval = self
+                .app
+                .borrow()
+                .frame_interval()
+                .max(self.display_interval());
+            let interval = Duration::from_secs_f64(interval / 1000.0);
+
+            // Keeps a steady pace, unless frames fell behind by more than one
+            self.next_frame += interval;
+            if self.next_frame < now {
+                self.next_frame = now + interval;
+            }
+
+            if let Err(err) = self.frame() {
+                return self.fail(event_loop, err);
+            }
+
+            self.pool.run_until_stalled();
+        }
+
+        event_loop.set_control_flow(ControlFlow::WaitUntil(self.next_frame));
+    }
+}
+
+/// Composites the canvases over the background into a buffer of `0RGB` pixels, with nearest
+/// neighbour scaling into the placement.
+fn composite(
+    buffer: &mut [u32],
+    buffer_width: usize,
+    placement: Placement,
+    game: &Pixmap,
+    interface: &Pixmap,
+) {
+    let background = BACKGROUND.map(u32::from);
+    let background_pixel = pack(background);
+
+    // Source pixel of every column and row, `None` outside of the placement
+    let sources = |offset: f64, display: f64, count: usize, source: u32| -> Vec<Option<usize>> {
+        (0..count)
+            .map(|index| {
+                let position = (index as f64 + 0.5 - offset) / display;
+
+                (0.0..1.0)
+                    .contains(&position)
+                    .then_some((position * source as f64) as usize)
+            })
+            .collect()
+    };
+
+    let buffer_height = buffer.len() / buffer_width.max(1);
+
+    let game_columns = sources(placement.left, placement.width, buffer_width, game.width());
+    let game_rows = sources(
+        placement.top,
+        placement.height,
+        buffer_height,
+        game.height(),
+    );
+    let interface_columns = sources(
+        placement.left,
+        placement.width,
+        buffer_width,
+        interface.width(),
+    );
+    let interface_rows = sources(
+        placement.top,
+        placement.height,
+        buffer_height,
+        interface.height(),
+    );
+
+    for (y, row) in buffer.chunks_exact_mut(buffer_width).enumerate() {
+        let (Some(game_row), Some(interface_row)) = (game_rows[y], interface_rows[y]) else {
+            row.fill(background_pixel);
+            continue;
+        };
+
+        let game_pixels = &game.pixels()[game_row * game.width() as usize..];
+        let interface_pixels = &interface.pixels()[interface_row * interface.width() as usize..];
+
+        for (x, pixel) in row.iter_mut().enumerate() {
+            *pixel = match (game_columns[x], interface_columns[x]) {
+                (Some(game_column), Some(interface_column)) => {
+                    let color = over(game_pixels[game_column], background);
+                    pack(over(interface_pixels[interface_column], color))
+                }
+                _ => background_pixel,
+            };
+        }
+    }
+}
+
+/// Blends a premultiplied pixel over an opaque colour.
+fn over(pixel: PremultipliedColorU8, [red, green, blue]: [u32; 3]) -> [u32; 3] {
+    let transparency = 255 - pixel.alpha() as u32;
+
+    [
+        pixel.red() as u32 + (red * transparency + 127) / 255,
+        pixel.green() as u32 + (green * transparency + 127) / 255,
+        pixel.blue() as u32 + (blue * transparency + 127) / 255,
+    ]
+}
+
+fn pack([red, green, blue]: [u32; 3]) -> u32 {
+    (red << 16) | (green << 8) | blue
+}
@@ -0,0 +1,973 @@
+//! The browser backend, built on `web_sys`.
+
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use futures::{channel::oneshot, future::LocalBoxFuture, FutureExt};
+use js_sys::{Array, Math, Promise, Reflect, Uint8Array};
+use wasm_bindgen::{prelude::*, Clamped, JsCast};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    console, AudioBuffer, AudioContext, Blob, BlobPropertyBag, CanvasRenderingContext2d, Document,
+    FocusEvent, GainNode, HtmlAnchorElement, HtmlCanvasElement, HtmlImageElement, HtmlInputElement,
+    ImageData, KeyboardEvent, MouseEvent, Request, RequestInit, Response, Storage, TouchEvent, Url,
+    Window,
+};
+
+use super::{
+    Application, AudioOutput, Canvas, Dialogs, HttpRequest, HttpResponse, InputEvent,
+    KeyValueStore, MouseButton, Network, Platform, PlatformError, PlatformKind, Sound, System,
+    Voice,
+};
+use crate::log::Level;
+
+pub fn window() -> Window {
+    web_sys::window().expect("no global `window` exists")
+}
+
+pub fn document() -> Document {
+    window()
+        .document()
+        .expect("should have a document on window")
+}
+
+fn request_animation_frame(f: &Closure<dyn FnMut()>) {
+    window()
+        .request_animation_frame(f.as_ref().unchecked_ref())
+        .expect("should register `requestAnimationFrame` OK");
+}
+
+impl From<JsValue> for PlatformError {
+    fn from(value: JsValue) -> Self {
+        PlatformError(value.as_string().unwrap_or_else(|| format!("{value:?}")))
+    }
+}
+
+/// Creates the browser's [`Platform`].
+pub fn web_platform() -> Platform {
+    Platform {
+        kind: PlatformKind::Web,
+        system: Box::new(WebSystem),
+        network: Box::new(WebNetwork),
+        dialogs: Box::new(WebDialogs),
+        audio: AudioContext::new()
+            .ok()
+            .map(|context| Box::new(WebAudio { context }) as Box<dyn AudioOutput>),
+        storage: LocalStorage::open().map(|storage| Box::new(storage) as Box<dyn KeyValueStore>),
+    }
+}
+
+/// The device pixel ratio and whether the page is taller than it is wide.
+pub fn web_display() -> (f64, bool) {
+    let window = window();
+    let dimension = |value: Result<JsValue, JsValue>| {
+        value
+            .ok()
+            .and_then(|value| value.as_f64())
+            .unwrap_or_default()
+    };
+
+    (
+        window.device_pixel_ratio(),
+        dimension(window.inner_width()) < dimension(window.inner_height()),
+    )
+}
+
+/// A `<canvas>` element and its 2D context.
+pub struct WebCanvas {
+    element: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+}
+
+impl WebCanvas {
+    /// Creates a canvas outside of the page.
+    pub fn new(width: u32, height: u32) -> Result<WebCanvas, PlatformError> {
+        let element = document()
+            .create_element("canvas")?
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(JsValue::from)?;
+
+        element.set_width(width);
+        element.set_height(height);
+
+        let context = element
+            .get_context("2d")?
+            .ok_or("no 2D context")?
+            .dyn_into::<CanvasRenderingContext2d>()
+            .map_err(JsValue::from)?;
+
+        context.set_image_smoothing_enabled(false);
+
+        Ok(WebCanvas { element, context })
+    }
+
+    #[cfg(feature = "webgl")]
+    pub fn element(&self) -> &HtmlCanvasElement {
+        &self.element
+    }
+
+    #[cfg(feature = "webgl")]
+    pub fn context(&self) -> &CanvasRenderingContext2d {
+        &self.context
+    }
+
+    /// The canvas behind a [`Canvas`] of the web platform.
+    #[cfg(feature = "webgl")]
+    pub fn of(canvas: &dyn Canvas) -> Result<&WebCanvas, PlatformError> {
+        canvas
+            .as_any()
+            .downcast_ref::<WebCanvas>()
+            .ok_or_else(|| "not a web canvas".into())
+    }
+}
+
+impl Canvas for WebCanvas {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn width(&self) -> u32 {
+        self.element.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.element.height()
+    }
+
+    fn resize(&self, width: u32, height: u32, keep_display_size: bool) {
+        let style = self.element.style();
+
+        // Pinned to its current size on the page, which would otherwise follow the backing size
+        if keep_display_size {
+            if style
+                .get_property_value("width")
+                .unwrap_or_default()
+                .is_empty()
+            {
+                let bound = self.element.get_bounding_client_rect();
+
+                let _ = style.set_property("width", &format!("{}px", bound.width()));
+                let _ = style.set_property("height", &format!("{}px", bound.height()));
+            }
+        } else {
+            let _ = style.remove_property("width");
+            let _ = style.remove_property("height");
+        }
+
+        self.element.set_width(width);
+        self.element.set_height(height);
+
+        // Resizing resets the context's state
+        self.context.set_image_smoothing_enabled(false);
+    }
+
+    fn save(&self) {
+        self.context.save();
+    }
+
+    fn restore(&self) {
+        self.context.restore();
+    }
+
+    fn translate(&self, x: f64, y: f64) -> Result<(), PlatformError> {
+        Ok(self.context.translate(x, y)?)
+    }
+
+    fn rotate(&self, angle: f64) -> Result<(), PlatformError> {
+        Ok(self.context.rotate(angle)?)
+    }
+
+    fn scale(&self, x: f64, y: f64) -> Result<(), PlatformError> {
+        Ok(self.context.scale(x, y)?)
+    }
+
+    fn set_fill_style(&self, color: &str) {
+        self.context.set_fill_style_str(color);
+    }
+
+    fn set_stroke_style(&self, color: &str) {
+        self.context.set_stroke_style_str(color);
+    }
+
+    fn set_line_width(&self, width: f64) {
+        self.context.set_line_width(width);
+    }
+
+    fn set_line_cap(&self, cap: &str) {
+        self.context.set_line_cap(cap);
+    }
+
+    fn set_line_dash(&self, segments: &[f64]) -> Result<(), PlatformError> {
+        let segments: Array = segments.iter().copied().map(JsValue::from).collect();
+
+        Ok(self.context.set_line_dash(&segments)?)
+    }
+
+    fn set_global_alpha(&self, alpha: f64) {
+        self.context.set_global_alpha(alpha);
+    }
+
+    fn set_global_composite_operation(&self, operation: &str) -> Result<(), PlatformError> {
+        Ok(self.context.set_global_composite_operation(operation)?)
+    }
+
+    fn begin_path(&self) {
+        self.context.begin_path();
+    }
+
+    fn close_path(&self) {
+        self.context.close_path();
+    }
+
+    fn move_to(&self, x: f64, y: f64) {
+        self.context.move_to(x, y);
+    }
+
+    fn line_to(&self, x: f64, y: f64) {
+        self.context.line_to(x, y);
+    }
+
+    fn rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        self.context.rect(x, y, width, height);
+    }
+
+    fn arc_with_anticlockwise(
+        &self,
+        x: f64,
+        y: f64,
+        radius: f64,
+        start_angle: f64,
+        end_angle: f64,
+        anticlockwise: bool,
+    ) -> Result<(), PlatformError> {
+        Ok(self.context.arc_with_anticlockwise(
+            x,
+            y,
+            radius,
+            start_angle,
+            end_angle,
+            anticlockwise,
+        )?)
+    }
+
+    fn fill(&self) {
+        self.context.fill();
+    }
+
+    fn stroke(&self) {
+        self.context.stroke();
+    }
+
+    fn clip(&self) {
+        self.context.clip();
+    }
+
+    fn fill_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        self.context.fill_rect(x, y, width, height);
+    }
+
+    fn stroke_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        self.context.stroke_rect(x, y, width, height);
+    }
+
+    fn clear_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        self.context.clear_rect(x, y, width, height);
+    }
+
+    fn draw_image(
+        &self,
+        source: &dyn Canvas,
+        (sx, sy, sw, sh): (f64, f64, f64, f64),
+        (dx, dy, dw, dh): (f64, f64, f64, f64),
+    ) -> Result<(), PlatformError> {
+        let source = source
+            .as_any()
+            .downcast_ref::<WebCanvas>()
+            .ok_or("not a web canvas")?;
+
+        Ok(self
+            .context
+            .draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                &source.element,
+                sx,
+                sy,
+                sw,
+                sh,
+                dx,
+                dy,
+                dw,
+                dh,
+            )?)
+    }
+
+    fn get_image_data(
+        &self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) -> Result<Vec<u8>, PlatformError> {
+        Ok(self.context.get_image_data(x, y, width, height)?.data().0)
+    }
+
+    fn put_image_data(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        dx: f64,
+        dy: f64,
+    ) -> Result<(), PlatformError> {
+        let image_data = ImageData::new_with_u8_clamped_array_and_sh(Clamped(data), width, height)?;
+
+        Ok(self.context.put_image_data(&image_data, dx, dy)?)
+    }
+}
+
+/// The browser's [`KeyValueStore`], backed by its local storage.
+pub struct LocalStorage(Storage);
+
+impl LocalStorage {
+    /// Opens the local storage, unless the browser withholds it, e.g. in some private modes.
+    pub fn open() -> Option<LocalStorage> {
+        window().local_storage().ok().flatten().map(LocalStorage)
+    }
+}
+
+impl KeyValueStore for LocalStorage {
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.get_item(key).unwrap_or_default()
+    }
+
+    fn set(&self, key: &str, value: &str) -> bool {
+        self.0.set_item(key, value).is_ok()
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = self.0.remove_item(key);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        (0..self.0.length().unwrap_or_default())
+            .filter_map(|index| self.0.key(index).ok().flatten())
+            .collect()
+    }
+}
+
+/// The browser's [`AudioOutput`], playing [`AudioBuffer`]s through Web Audio.
+pub struct WebAudio {
+    context: AudioContext,
+}
+
+impl WebAudio {
+    /// Starts a buffer through a gain node of its own.
+    fn start(
+        &self,
+        sound: &Sound,
+        playback_rate: f32,
+        volume: f32,
+        looped: bool,
+    ) -> Result<GainNode, PlatformError> {
+        let buffer = sound
+            .0
+            .downcast_ref::<AudioBuffer>()
+            .ok_or("not a web sound")?;
+
+        let buffer_source = self.context.create_buffer_source()?;
+        buffer_source.set_buffer(Some(buffer));
+        buffer_source.playback_rate().set_value(playback_rate);
+        buffer_source.set_loop(looped);
+
+        let gain_node = self.context.create_gain()?;
+        gain_node.gain().set_value(volume);
+
+        buffer_source.connect_with_audio_node(&gain_node)?;
+        gain_node.connect_with_audio_node(&self.context.destination())?;
+
+        buffer_source.start_with_when(0.0)?;
+
+        Ok(gain_node)
+    }
+}
+
+impl AudioOutput for WebAudio {
+    fn decode(&self, data: Vec<u8>) -> LocalBoxFuture<'static, Option<Sound>> {
+        let promise = self
+            .context
+            .decode_audio_data(&Uint8Array::from(data.as_slice()).buffer());
+
+        async move {
+            let buffer = JsFuture::from(promise.ok()?)
+                .await
+                .ok()?
+                .dyn_into::<AudioBuffer>()
+                .ok()?;
+
+            Some(Sound(Rc::new(buffer)))
+        }
+        .boxed_local()
+    }
+
+    fn play(
+        &self,
+        sound: &Sound,
+        playback_rate: f32,
+        volume: f32,
+        looped: bool,
+    ) -> Option<Rc<dyn Voice>> {
+        let gain_node = self.start(sound, playback_rate, volume, looped).ok()?;
+
+        Some(Rc::new(WebVoice { gain_node }))
+    }
+}
+
+struct WebVoice {
+    gain_node: GainNode,
+}
+
+impl Voice for WebVoice {
+    fn set_volume(&self, volume: f32) {
+        self.gain_node.gain().set_value(volume);
+    }
+}
+
+/// The browser's [`Network`], sending requests through `fetch`.
+pub struct WebNetwork;
+
+impl Network for WebNetwork {
+    fn fetch(
+        &self,
+        request: HttpRequest,
+    ) -> LocalBoxFuture<'static, Result<HttpResponse, PlatformError>> {
+        async move {
+            let opts = RequestInit::new();
+            opts.set_method(request.method);
+
+            if let Some(body) = &request.body {
+                opts.set_body(&JsValue::from_str(body));
+            }
+
+            let web_request = Request::new_with_str_and_init(&request.url, &opts)?;
+
+            for (name, value) in &request.headers {
+                web_request.headers().set(name, value)?;
+            }
+
+            let response = JsFuture::from(window().fetch_with_request(&web_request))
+                .await?
+                .dyn_into::<Response>()?;
+            let body = JsFuture::from(response.array_buffer()?).await?;
+
+            Ok(HttpResponse {
+                status: response.status(),
+                body: Uint8Array::new(&body).to_vec(),
+            })
+        }
+        .boxed_local()
+    }
+}
+
+/// The browser's [`Dialogs`], its built-in prompts, downloads and file inputs.
+pub struct WebDialogs;
+
+impl Dialogs for WebDialogs {
+    fn alert(&self, message: &str) {
+        let _ = window().alert_with_message(message);
+    }
+
+    fn confirm(&self, message: &str) -> bool {
+        window().confirm_with_message(message).unwrap_or_default()
+    }
+
+    fn prompt(&self, message: &str, default: &str) -> Option<String> {
+        window()
+            .prompt_with_message_and_default(message, default)
+            .ok()
+            .flatten()
+    }
+
+    fn save_file(&self, name: &str, contents: &str) -> Result<(), PlatformError> {
+        let parts = Array::of1(&contents.into());
+        let options = BlobPropertyBag::new();
+        options.set_type("application/json");
+
+        let blob = Blob::new_with_str_sequence_and_options(&parts, &options)?;
+        let url = Url::create_object_url_with_blob(&blob)?;
+
+        let anchor = document()
+            .create_element("a")?
+            .dyn_into::<HtmlAnchorElement>()
+            .map_err(JsValue::from)?;
+        anchor.set_href(&url);
+        anchor.set_download(name);
+        anchor.click();
+
+        Ok(Url::revoke_object_url(&url)?)
+    }
+
+    fn open_file(
+        &self,
+        accept: &[&str],
+    ) -> Result<LocalBoxFuture<'static, Option<Result<String, PlatformError>>>, PlatformError> {
+        let (sender, receiver) = oneshot::channel();
+
+        let input = document()
+            .create_element("input")?
+            .dyn_into::<HtmlInputElement>()
+            .map_err(JsValue::from)?;
+
+        input.set_type("file");
+        input.set_accept(
+            &accept
+                .iter()
+                .map(|extension| format!(".{extension}"))
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+
+        let closure = {
+            let input = input.clone();
+            let sender = Cell::new(Some(sender));
+
+            Closure::<dyn FnMut(JsValue)>::new(move |_| {
+                let (Some(file), Some(sender)) =
+                    (input.files().and_then(|files| files.get(0)), sender.take())
+                else {
+                    return;
+                };
+
+                spawn_local(async move {
+                    let text = JsFuture::from(file.text())
+                        .await
+                        .map(|text| text.as_string().unwrap_or_default())
+                        .map_err(PlatformError::from);
+
+                    let _ = sender.send(text);
+                });
+            })
+        };
+
+        input.set_onchange(Some(closure.as_ref().unchecked_ref()));
+        closure.forget();
+
+        input.click();
+
+        // Browsers do not tell when the picker is dismissed, the future then never resolves
+        Ok(async move { receiver.await.ok() }.boxed_local())
+    }
+}
+
+/// The browser's [`System`].
+pub struct WebSystem;
+
+impl System for WebSystem {
+    fn now(&self) -> f64 {
+        window()
+            .performance()
+            .map(|performance| performance.now())
+            .unwrap_or_default()
+    }
+
+    fn time(&self) -> f64 {
+        js_sys::Date::now()
+    }
+
+    fn random(&self) -> f64 {
+        Math::random()
+    }
+
+    fn fill_random(&self, bytes: &mut [u8]) -> Result<(), PlatformError> {
+        window().crypto()?.get_random_values_with_u8_array(bytes)?;
+
+        Ok(())
+    }
+
+    fn spawn(&self, future: LocalBoxFuture<'static, ()>) {
+        spawn_local(future);
+    }
+
+    fn sleep(&self, milliseconds: f64) -> LocalBoxFuture<'static, ()> {
+        let promise = Promise::new(&mut |resolve, _| {
+            let _ = window().set_timeout_with_callback_and_timeout_and_arguments_0(
+                &resolve,
+                milliseconds as i32,
+            );
+        });
+
+        async move {
+            let _ = JsFuture::from(promise).await;
+        }
+        .boxed_local()
+    }
+
+    fn create_canvas(&self, width: u32, height: u32) -> Result<Box<dyn Canvas>, PlatformError> {
+        Ok(Box::new(WebCanvas::new(width, height)?))
+    }
+
+    fn load_image(
+        &self,
+        path: &str,
+    ) -> LocalBoxFuture<'static, Result<Box<dyn Canvas>, PlatformError>> {
+        let image_future = ImageFuture::new(path);
+        let path = path.to_string();
+
+        async move {
+            let image = image_future
+                .await
+                .map_err(|_| format!("could not load {path}"))?;
+
+            let canvas = WebCanvas::new(image.width(), image.height())?;
+            canvas
+                .context
+                .draw_image_with_html_image_element(&image, 0.0, 0.0)?;
+
+            Ok(Box::new(canvas) as Box<dyn Canvas>)
+        }
+        .boxed_local()
+    }
+
+    fn log(&self, level: Level, message: &str) {
+        let value = message.into();
+
+        match level {
+            Level::Error => console::error_1(&value),
+            Level::Warn => console::warn_1(&value),
+            Level::Info => console::info_1(&value),
+            Level::Debug => console::debug_1(&value),
+        }
+    }
+
+    fn vibrate(&self, pattern: &[f64]) -> bool {
+        if !self.can_vibrate() {
+            return false;
+        }
+
+        let pattern: Array = pattern.iter().copied().map(JsValue::from).collect();
+
+        window().navigator().vibrate_with_pattern(&pattern)
+    }
+
+    /// Determines if the browser exposes the vibration API, which desktops mostly do not.
+    fn can_vibrate(&self) -> bool {
+        Reflect::has(&window().navigator(), &"vibrate".into()).unwrap_or_default()
+    }
+
+    fn launch_parameters(&self) -> String {
+        window()
+            .location()
+            .search()
+            .unwrap_or_default()
+            .trim_start_matches('?')
+            .to_string()
+    }
+
+    fn page_url(&self) -> Option<String> {
+        let location = window().location();
+
+        Some(format!(
+            "{}{}",
+            location.origin().ok()?,
+            location.pathname().ok()?
+        ))
+    }
+
+    fn reload(&self) {
+        let _ = window().location().reload();
+    }
+}
+
+/// Adds the game's canvases to the page and drives an [`Application`] by animation frames and the
+/// page's events.
+pub fn run_web(
+    app: Rc<RefCell<dyn Application>>,
+    (width, height): (u32, u32),
+) -> Result<(), PlatformError> {
+    let container_element = document()
+        .query_selector("#canvas-container")?
+        .ok_or("no canvas container")?;
+
+    let canvas = Rc::new(WebCanvas::new(width, height)?);
+    let interface_canvas = WebCanvas::new(width, height)?;
+
+    interface_canvas.element.set_id("interface-canvas");
+
+    container_element.append_child(&canvas.element)?;
+    container_element.append_child(&interface_canvas.element)?;
+
+    let text_input = document()
+        .query_selector("#text-input")?
+        .ok_or("no text input")?
+        .dyn_into::<HtmlInputElement>()
+        .map_err(JsValue::from)?;
+
+    {
+        let f = Rc::new(RefCell::new(None));
+        let g = f.clone();
+
+        let app = app.clone();
+        let canvas = canvas.clone();
+        let last_frame_at = Cell::new(0.0);
+
+        *g.borrow_mut() = Some(Closure::new(move || {
+            let mut app = app.borrow_mut();
+
+            let now = WebSystem.now();
+
+            // Small tolerance so a 60 FPS limit isn't halved by jitter on 60 Hz displays
+            if !app.hidden() && now - last_frame_at.get() >= app.frame_interval() - 2.0 {
+                last_frame_at.set(now);
+
+                app.frame(canvas.as_ref(), &interface_canvas).unwrap();
+            }
+
+            request_animation_frame(f.borrow().as_ref().unwrap());
+        }));
+
+        request_animation_frame(g.borrow().as_ref().unwrap());
+    }
+
+    // Top left corner of the canvas on the page, which pointer positions are taken from
+    let origin = Rc::new(Cell::new((0.0, 0.0)));
+
+    let resize = {
+        let app = app.clone();
+        let canvas = canvas.clone();
+        let origin = origin.clone();
+
+        move || {
+            let bound = canvas.element.get_bounding_client_rect();
+
+            origin.set((bound.left(), bound.top()));
+            app.borrow_mut().handle_input(InputEvent::Resize {
+                width: bound.width(),
+                height: bound.height(),
+            });
+        }
+    };
+
+    resize();
+
+    {
+        let closure = Closure::<dyn FnMut(_)>::new(move |_: JsValue| resize());
+        window().add_event_listener_with_callback("resize", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    {
+        let app = app.clone();
+        let closure = Closure::<dyn FnMut(_)>::new(move |_: FocusEvent| {
+            if let Some(field) = text_input.dataset().get("field") {
+                app.borrow_mut().handle_input(InputEvent::TextInput {
+                    field,
+                    value: text_input.value(),
+                });
+                text_input.dataset().delete("field");
+            }
+        });
+
+        document()
+            .add_event_listener_with_callback("focusout", closure.as_ref().unchecked_ref())?;
+
+        closure.forget();
+    }
+
+    {
+        let app = app.clone();
+        let closure = Closure::<dyn FnMut(_)>::new(move |_: JsValue| {
+            app.borrow_mut().handle_input(InputEvent::VisibilityChange {
+                hidden: document().hidden(),
+            });
+        });
+        document().add_event_listener_with_callback(
+            "visibilitychange",
+            closure.as_ref().unchecked_ref(),
+        )?;
+        closure.forget();
+    }
+
+    {
+        // Animation frames are suspended in hidden tabs, keep the network alive separately
+        let app = app.clone();
+        let closure = Closure::<dyn FnMut()>::new(move || {
+            let mut app = app.borrow_mut();
+            if app.hidden() {
+                app.poll();
+            }
+        });
+        window().set_interval_with_callback_and_timeout_and_arguments_0(
+            closure.as_ref().unchecked_ref(),
+            1000,
+        )?;
+        closure.forget();
+    }
+
+    let mouse_button = |event: &MouseEvent| match event.button() {
+        0 => MouseButton::Primary,
+        2 => MouseButton::Secondary,
+        _ => MouseButton::Other,
+    };
+
+    {
+        let app = app.clone();
+        let closure = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| {
+            app.borrow_mut().handle_input(InputEvent::MouseDown {
+                button: mouse_button(&event),
+                shift: event.shift_key(),
+            });
+        });
+        document()
+            .add_event_listener_with_callback("mousedown", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    {
+        let app = app.clone();
+        let closure = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| {
+            app.borrow_mut().handle_input(InputEvent::MouseUp {
+                button: mouse_button(&event),
+            });
+        });
+        document().add_event_listener_with_callback("mouseup", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    {
+        let app = app.clone();
+        let origin = origin.clone();
+        let closure = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| {
+            let (left, top) = origin.get();
+
+            app.borrow_mut().handle_input(InputEvent::MouseMove {
+                x: event.page_x() as f64 - left.trunc(),
+                y: event.page_y() as f64 - top.trunc(),
+                shift: event.shift_key(),
+            });
+
+            event.prevent_default();
+        });
+        document()
+            .add_event_listener_with_callback("mousemove", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    let first_touch = move |event: &TouchEvent| {
+        let (left, top) = origin.get();
+
+        event.target_touches().item(0).map(|touch| {
+            (
+                touch.page_x() as f64 - left.trunc(),
+                touch.page_y() as f64 - top.trunc(),
+            )
+        })
+    };
+
+    for (name, into_event, prevent_default) in [
+        (
+            "touchstart",
+            (|touch| InputEvent::TouchStart { touch }) as fn(Option<(f64, f64)>) -> InputEvent,
+            false,
+        ),
+        ("touchmove", |touch| InputEvent::TouchMove { touch }, true),
+        ("touchend", |touch| InputEvent::TouchEnd { touch }, false),
+    ] {
+        let app = app.clone();
+        let first_touch = first_touch.clone();
+        let closure = Closure::<dyn FnMut(_)>::new(move |event: TouchEvent| {
+            app.borrow_mut()
+                .handle_input(into_event(first_touch(&event)));
+
+            if prevent_default {
+                event.prevent_default();
+            }
+        });
+        document().add_event_listener_with_callback(name, closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    {
+        let closure = Closure::<dyn FnMut(_)>::new(move |event: KeyboardEvent| {
+            let consumed = app.borrow_mut().handle_input(InputEvent::KeyDown {
+                code: event.code(),
+                shift: event.shift_key(),
+            });
+
+            if consumed {
+                event.prevent_default();
+            }
+        });
+        document().add_event_listener_with_callback("keydown", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    {
+        let closure = Closure::<dyn FnMut(_)>::new(move |event: MouseEvent| {
+            event.prevent_default();
+        });
+        document()
+            .add_event_listener_with_callback("contextmenu", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    Ok(())
+}
+
+struct ImageFuture {
+    image: Option<HtmlImageElement>,
+    load_failed: Rc<Cell<bool>>,
+}
+
+impl ImageFuture {
+    fn new(path: &str) -> Self {
+        let image = HtmlImageElement::new().unwrap();
+        image.set_src(path);
+        ImageFuture {
+            image: Some(image),
+            load_failed: Rc::new(Cell::new(false)),
+        }
+    }
+}
+
+impl Future for ImageFuture {
+    type Output = Result<HtmlImageElement, ()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &self.image {
+            Some(image) if image.complete() => {
+                let image = self.image.take().unwrap();
+                let failed = self.load_failed.get();
+
+                if failed {
+                    Poll::Ready(Err(()))
+                } else {
+                    Poll::Ready(Ok(image))
+                }
+            }
+            Some(image) => {
+                let waker = cx.waker().clone();
+                let on_load_closure = Closure::wrap(Box::new(move || {
+                    waker.wake_by_ref();
+                }) as Box<dyn FnMut()>);
+                image.set_onload(Some(on_load_closure.as_ref().unchecked_ref()));
+                on_load_closure.forget();
+
+                let waker = cx.waker().clone();
+                let failed_flag = self.load_failed.clone();
+                let on_error_closure = Closure::wrap(Box::new(move || {
+                    failed_flag.set(true);
+                    waker.wake_by_ref();
+                }) as Box<dyn FnMut()>);
+                image.set_onerror(Some(on_error_closure.as_ref().unchecked_ref()));
+                on_error_closure.forget();
+
+                Poll::Pending
+            }
+            _ => Poll::Ready(Err(())),
+        }
+    }
+}
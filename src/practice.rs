@@ -0,0 +1,39 @@
+use crate::app::App;
+
+/// Storage key of the comma-separated best scores of target practice.
+const PRACTICE_SCORES_KEY: &str = "practice_scores";
+
+/// Number of scores kept in the high score table.
+pub const HIGH_SCORES: usize = 5;
+
+/// Returns the best target practice scores on this device, highest first.
+pub fn high_scores() -> Vec<u32> {
+    App::kv_get(PRACTICE_SCORES_KEY)
+        .split(',')
+        .filter_map(|score| score.parse().ok())
+        .collect()
+}
+
+/// Enters a score into the high score table, returning its rank if it made the table.
+pub fn record_high_score(score: u32) -> Option<usize> {
+    let mut scores = high_scores();
+    let rank = scores.iter().take_while(|high| **high >= score).count();
+
+    if rank >= HIGH_SCORES {
+        return None;
+    }
+
+    scores.insert(rank, score);
+    scores.truncate(HIGH_SCORES);
+
+    App::kv_set(
+        PRACTICE_SCORES_KEY,
+        &scores
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+    );
+
+    Some(rank)
+}
@@ -0,0 +1,457 @@
+use crate::{
+    app::{Frame, Particle},
+    draw::{draw_particle, draw_sand_circle},
+    platform::{platform, Canvas, PlatformError, PlatformKind},
+};
+
+/// Backend for the draw calls that dominate frame time, the particles and the sand capture zone.
+///
+/// Everything else is drawn through the Canvas2D helpers in [`crate::draw`].
+pub trait Renderer {
+    /// Short name of the backend, shown in the performance overlay.
+    fn name(&self) -> &'static str;
+
+    /// Renders the sand capture zone into the atlas region at (360, 360).
+    fn draw_sand_circle(
+        &self,
+        atlas_context: &dyn Canvas,
+        capture_progress: f32,
+        radius: f32,
+    ) -> Result<(), PlatformError>;
+
+    /// Draws all particles onto the context, whose origin is the centre of the arena.
+    fn draw_particles(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        particles: &[Particle],
+        frame: Frame,
+    ) -> Result<(), PlatformError>;
+}
+
+/// The default [`Renderer`], drawing straight onto the 2D canvas.
+pub struct Canvas2dRenderer;
+
+impl Renderer for Canvas2dRenderer {
+    fn name(&self) -> &'static str {
+        "canvas2d"
+    }
+
+    fn draw_sand_circle(
+        &self,
+        atlas_context: &dyn Canvas,
+        capture_progress: f32,
+        radius: f32,
+    ) -> Result<(), PlatformError> {
+        draw_sand_circle(atlas_context, capture_progress, radius)
+    }
+
+    fn draw_particles(
+        &self,
+        context: &dyn Canvas,
+        atlas: &dyn Canvas,
+        particles: &[Particle],
+        frame: Frame,
+    ) -> Result<(), PlatformError> {
+        for particle in particles {
+            draw_particle(context, atlas, particle, frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Creates the WebGL2 renderer if requested and supported, falling back to Canvas2D otherwise.
+pub fn create_renderer(webgl: bool) -> Box<dyn Renderer> {
+    // WebGL2 is a browser API, other platforms always draw through their canvas
+    let webgl = webgl && platform().kind == PlatformKind::Web;
+
+    #[cfg(feature = "webgl")]
+    if webgl {
+        match webgl::WebGl2Renderer::new() {
+            Ok(renderer) => return Box::new(renderer),
+            Err(err) => crate::warn!("WebGL2 unavailable: {err:?}"),
+        }
+    }
+
+    #[cfg(not(feature = "webgl"))]
+    let _ = webgl;
+
+    Box::new(Canvas2dRenderer)
+}
+
+#[cfg(feature = "webgl")]
+mod webgl {
+    use std::cell::{Cell, RefCell};
+
+    use js_sys::Float32Array;
+    use wasm_bindgen::{JsCast, JsValue};
+    use web_sys::{
+        HtmlCanvasElement, WebGl2RenderingContext as GL, WebGlBuffer, WebGlProgram, WebGlShader,
+        WebGlTexture, WebGlUniformLocation, WebGlVertexArrayObject,
+    };
+
+    use super::Renderer;
+    use crate::{
+        app::{Frame, Particle},
+        draw::particle_sprite,
+        platform::{document, Canvas, PlatformError, WebCanvas},
+    };
+
+    const WIDTH: u32 = 384;
+    const HEIGHT: u32 = 360;
+    const SAND_SIZE: i32 = 360;
+
+    /// Floats per sprite vertex: position and atlas coordinates.
+    const VERTEX_FLOATS: usize = 4;
+
+    const SPRITE_VERTEX: &str = r#"#version 300 es
+in vec2 a_position;
+in vec2 a_uv;
+uniform vec2 u_resolution;
+uniform vec2 u_atlas_size;
+out vec2 v_uv;
+
+void main() {
+    vec2 clip = a_position / u_resolution * 2.0 - 1.0;
+    gl_Position = vec4(clip.x, -clip.y, 0.0, 1.0);
+    v_uv = a_uv / u_atlas_size;
+}
+"#;
+
+    const SPRITE_FRAGMENT: &str = r#"#version 300 es
+precision mediump float;
+in vec2 v_uv;
+uniform sampler2D u_atlas;
+out vec4 color;
+
+void main() {
+    color = texture(u_atlas, v_uv);
+}
+"#;
+
+    const SAND_VERTEX: &str = r#"#version 300 es
+void main() {
+    vec2 position = vec2(float((gl_VertexID & 1) << 2), float((gl_VertexID & 2) << 1)) - 1.0;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+"#;
+
+    // Mirrors `draw_sand_circle`, including its dithered edges.
+    const SAND_FRAGMENT: &str = r#"#version 300 es
+precision highp float;
+uniform float u_capture_progress;
+uniform float u_radius;
+out vec4 color;
+
+const vec4 SAND = vec4(202.0, 137.0, 27.0, 127.0) / 255.0;
+const vec4 RED = vec4(194.0, 0.0, 5.0, 127.0) / 255.0;
+const vec4 BLUE = vec4(0.0, 194.0, 183.0, 127.0) / 255.0;
+
+void main() {
+    float x = floor(gl_FragCoord.x) - 180.0;
+    float y = 359.0 - floor(gl_FragCoord.y) - 180.0;
+    float q = length(vec2(x, y));
+    float capture_radius = abs(u_capture_progress * u_radius);
+    bool dither = sin(x) + cos(y) < 0.0;
+    vec4 team = u_capture_progress > 0.0 ? RED : BLUE;
+
+    vec4 result = vec4(0.0);
+
+    if (q < capture_radius - 1.5) {
+        result = team;
+    } else if (q < capture_radius) {
+        result = dither ? SAND : team;
+    } else if (q < u_radius - 1.5 || (q < u_radius && dither)) {
+        result = SAND;
+    }
+
+    color = vec4(result.rgb * result.a, result.a);
+}
+"#;
+
+    /// Renders into an offscreen WebGL2 canvas which is then composited onto the 2D canvases.
+    pub struct WebGl2Renderer {
+        canvas: HtmlCanvasElement,
+        gl: GL,
+        sprite_program: WebGlProgram,
+        sprite_vao: WebGlVertexArrayObject,
+        sprite_buffer: WebGlBuffer,
+        atlas_texture: WebGlTexture,
+        atlas_size: Cell<Option<(f32, f32)>>,
+        sand_program: WebGlProgram,
+        sand_vao: WebGlVertexArrayObject,
+        vertices: RefCell<Vec<f32>>,
+    }
+
+    impl WebGl2Renderer {
+        pub fn new() -> Result<WebGl2Renderer, JsValue> {
+            let canvas = document()
+                .create_element("canvas")?
+                .dyn_into::<HtmlCanvasElement>()?;
+
+            canvas.set_width(WIDTH);
+            canvas.set_height(HEIGHT);
+
+            let gl = canvas
+                .get_context("webgl2")?
+                .ok_or("no WebGL2 context")?
+                .dyn_into::<GL>()?;
+
+            let sprite_program = link_program(&gl, SPRITE_VERTEX, SPRITE_FRAGMENT)?;
+            let sand_program = link_program(&gl, SAND_VERTEX, SAND_FRAGMENT)?;
+
+            let sprite_vao = gl.create_vertex_array().ok_or("cannot create VAO")?;
+            let sprite_buffer = gl.create_buffer().ok_or("cannot create buffer")?;
+
+            gl.bind_vertex_array(Some(&sprite_vao));
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&sprite_buffer));
+
+            let stride = (VERTEX_FLOATS * 4) as i32;
+
+            for (name, offset) in [("a_position", 0), ("a_uv", 8)] {
+                let location = gl.get_attrib_location(&sprite_program, name) as u32;
+                gl.enable_vertex_attrib_array(location);
+                gl.vertex_attrib_pointer_with_i32(location, 2, GL::FLOAT, false, stride, offset);
+            }
+
+            let sand_vao = gl.create_vertex_array().ok_or("cannot create VAO")?;
+
+            let atlas_texture = gl.create_texture().ok_or("cannot create texture")?;
+
+            gl.bind_texture(GL::TEXTURE_2D, Some(&atlas_texture));
+
+            for (parameter, value) in [
+                (GL::TEXTURE_MIN_FILTER, GL::NEAREST),
+                (GL::TEXTURE_MAG_FILTER, GL::NEAREST),
+                (GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE),
+                (GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE),
+            ] {
+                gl.tex_parameteri(GL::TEXTURE_2D, parameter, value as i32);
+            }
+
+            gl.bind_vertex_array(None);
+
+            gl.enable(GL::BLEND);
+            gl.blend_func(GL::ONE, GL::ONE_MINUS_SRC_ALPHA);
+            gl.pixel_storei(GL::UNPACK_PREMULTIPLY_ALPHA_WEBGL, 1);
+
+            Ok(WebGl2Renderer {
+                canvas,
+                gl,
+                sprite_program,
+                sprite_vao,
+                sprite_buffer,
+                atlas_texture,
+                atlas_size: Cell::new(None),
+                sand_program,
+                sand_vao,
+                vertices: RefCell::new(Vec::new()),
+            })
+        }
+
+        fn uniform(&self, program: &WebGlProgram, name: &str) -> Option<WebGlUniformLocation> {
+            self.gl.get_uniform_location(program, name)
+        }
+
+        /// Uploads the atlas on first use; its sprites do not change after startup.
+        fn atlas_size(&self, atlas: &dyn Canvas) -> Result<(f32, f32), PlatformError> {
+            if let Some(atlas_size) = self.atlas_size.get() {
+                return Ok(atlas_size);
+            }
+
+            self.gl
+                .bind_texture(GL::TEXTURE_2D, Some(&self.atlas_texture));
+            self.gl
+                .tex_image_2d_with_u32_and_u32_and_html_canvas_element(
+                    GL::TEXTURE_2D,
+                    0,
+                    GL::RGBA as i32,
+                    GL::RGBA,
+                    GL::UNSIGNED_BYTE,
+                    WebCanvas::of(atlas)?.element(),
+                )?;
+
+            let atlas_size = (atlas.width() as f32, atlas.height() as f32);
+            self.atlas_size.set(Some(atlas_size));
+
+            Ok(atlas_size)
+        }
+
+        fn clear(&self, width: i32, height: i32) {
+            self.gl.viewport(0, HEIGHT as i32 - height, width, height);
+            self.gl.clear_color(0.0, 0.0, 0.0, 0.0);
+            self.gl.clear(GL::COLOR_BUFFER_BIT);
+        }
+    }
+
+    impl Renderer for WebGl2Renderer {
+        fn name(&self) -> &'static str {
+            "webgl2"
+        }
+
+        fn draw_sand_circle(
+            &self,
+            atlas_context: &dyn Canvas,
+            capture_progress: f32,
+            radius: f32,
+        ) -> Result<(), PlatformError> {
+            let gl = &self.gl;
+
+            self.clear(SAND_SIZE, SAND_SIZE);
+
+            gl.use_program(Some(&self.sand_program));
+            gl.uniform1f(
+                self.uniform(&self.sand_program, "u_capture_progress")
+                    .as_ref(),
+                capture_progress,
+            );
+            gl.uniform1f(
+                self.uniform(&self.sand_program, "u_radius").as_ref(),
+                radius,
+            );
+            gl.bind_vertex_array(Some(&self.sand_vao));
+            gl.draw_arrays(GL::TRIANGLES, 0, 3);
+            gl.bind_vertex_array(None);
+
+            let size = SAND_SIZE as f64;
+
+            atlas_context.clear_rect(360.0, 360.0, size, size);
+            WebCanvas::of(atlas_context)?
+                .context()
+                .draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                    &self.canvas,
+                    0.0,
+                    0.0,
+                    size,
+                    size,
+                    360.0,
+                    360.0,
+                    size,
+                    size,
+                )?;
+
+            Ok(())
+        }
+
+        fn draw_particles(
+            &self,
+            context: &dyn Canvas,
+            atlas: &dyn Canvas,
+            particles: &[Particle],
+            frame: Frame,
+        ) -> Result<(), PlatformError> {
+            if particles.is_empty() {
+                return Ok(());
+            }
+
+            let gl = &self.gl;
+            let (atlas_width, atlas_height) = self.atlas_size(atlas)?;
+
+            let mut vertices = self.vertices.borrow_mut();
+            vertices.clear();
+
+            for particle in particles {
+                let (sx, quarter_turns) = particle_sprite(particle, frame);
+                let (x, y) = (
+                    particle.position.0.round() as f32 + (WIDTH / 2) as f32,
+                    particle.position.1.round() as f32 + (HEIGHT / 2) as f32,
+                );
+                let (sx, sy) = (sx as f32, 248.0);
+
+                let corners = [(-4.0, -4.0), (4.0, -4.0), (4.0, 4.0), (-4.0, 4.0)].map(
+                    |(cx, cy): (f32, f32)| {
+                        let (rx, ry) = match quarter_turns % 4 {
+                            0 => (cx, cy),
+                            1 => (-cy, cx),
+                            2 => (-cx, -cy),
+                            _ => (cy, -cx),
+                        };
+
+                        (x + rx, y + ry, sx + cx + 4.0, sy + cy + 4.0)
+                    },
+                );
+
+                for index in [0, 1, 2, 0, 2, 3] {
+                    let (px, py, u, v) = corners[index];
+                    vertices.extend_from_slice(&[px, py, u, v]);
+                }
+            }
+
+            self.clear(WIDTH as i32, HEIGHT as i32);
+
+            gl.use_program(Some(&self.sprite_program));
+            gl.uniform2f(
+                self.uniform(&self.sprite_program, "u_resolution").as_ref(),
+                WIDTH as f32,
+                HEIGHT as f32,
+            );
+            gl.uniform2f(
+                self.uniform(&self.sprite_program, "u_atlas_size").as_ref(),
+                atlas_width,
+                atlas_height,
+            );
+            gl.active_texture(GL::TEXTURE0);
+            gl.bind_texture(GL::TEXTURE_2D, Some(&self.atlas_texture));
+            gl.uniform1i(self.uniform(&self.sprite_program, "u_atlas").as_ref(), 0);
+
+            gl.bind_vertex_array(Some(&self.sprite_vao));
+            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.sprite_buffer));
+            gl.buffer_data_with_array_buffer_view(
+                GL::ARRAY_BUFFER,
+                &Float32Array::from(vertices.as_slice()),
+                GL::STREAM_DRAW,
+            );
+            gl.draw_arrays(GL::TRIANGLES, 0, (vertices.len() / VERTEX_FLOATS) as i32);
+            gl.bind_vertex_array(None);
+
+            WebCanvas::of(context)?
+                .context()
+                .draw_image_with_html_canvas_element(
+                    &self.canvas,
+                    -((WIDTH / 2) as f64),
+                    -((HEIGHT / 2) as f64),
+                )?;
+
+            Ok(())
+        }
+    }
+
+    fn compile_shader(gl: &GL, sort: u32, source: &str) -> Result<WebGlShader, JsValue> {
+        let shader = gl.create_shader(sort).ok_or("cannot create shader")?;
+
+        gl.shader_source(&shader, source);
+        gl.compile_shader(&shader);
+
+        if gl
+            .get_shader_parameter(&shader, GL::COMPILE_STATUS)
+            .as_bool()
+            .unwrap_or(false)
+        {
+            Ok(shader)
+        } else {
+            Err(gl.get_shader_info_log(&shader).unwrap_or_default().into())
+        }
+    }
+
+    fn link_program(gl: &GL, vertex: &str, fragment: &str) -> Result<WebGlProgram, JsValue> {
+        let program = gl.create_program().ok_or("cannot create program")?;
+
+        gl.attach_shader(&program, &compile_shader(gl, GL::VERTEX_SHADER, vertex)?);
+        gl.attach_shader(
+            &program,
+            &compile_shader(gl, GL::FRAGMENT_SHADER, fragment)?,
+        );
+        gl.link_program(&program);
+
+        if gl
+            .get_program_parameter(&program, GL::LINK_STATUS)
+            .as_bool()
+            .unwrap_or(false)
+        {
+            Ok(program)
+        } else {
+            Err(gl.get_program_info_log(&program).unwrap_or_default().into())
+        }
+    }
+}
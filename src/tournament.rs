@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use shared::{Result as GameResult, Team};
+
+use crate::app::App;
+
+/// Storage key of the tournament in progress on this device.
+const TOURNAMENT_KEY: &str = "tournament";
+
+/// Fewest players a tournament is played between.
+pub const MIN_ENTRANTS: usize = 3;
+
+/// Most players a tournament is played between.
+pub const MAX_ENTRANTS: usize = 8;
+
+/// Record of a player over the matches of a tournament.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Standing {
+    pub name: String,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    /// Capture gained minus capture conceded over all matches.
+    pub capture_differential: i32,
+}
+
+impl Standing {
+    fn new(name: String) -> Standing {
+        Standing {
+            name,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            capture_differential: 0,
+        }
+    }
+
+    /// Three points for a win and one for a draw.
+    pub fn points(&self) -> usize {
+        self.wins * 3 + self.draws
+    }
+}
+
+/// A round-robin of hotseat matches between players sharing this device, every player meeting
+/// every other once.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Tournament {
+    standings: Vec<Standing>,
+    /// Indices into the standings of the Red and Blue player of every match, in playing order.
+    schedule: Vec<(usize, usize)>,
+    played: usize,
+    /// Whether the next match has been started and awaits its result.
+    in_progress: bool,
+}
+
+impl Tournament {
+    /// Schedules a round-robin between the named players, in rounds where nobody plays twice.
+    pub fn new(names: Vec<String>) -> Tournament {
+        let count = names.len();
+
+        // Circle method: the first slot stays put while the others rotate, an odd count of
+        // players getting a bye slot
+        let mut slots: Vec<Option<usize>> = (0..count).map(Some).collect();
+
+        if count % 2 == 1 {
+            slots.push(None);
+        }
+
+        let mut schedule = Vec::new();
+
+        for round in 0..slots.len().saturating_sub(1) {
+            for i in 0..slots.len() / 2 {
+                if let (Some(a), Some(b)) = (slots[i], slots[slots.len() - 1 - i]) {
+                    // Sides alternate between rounds, so nobody is always Red
+                    schedule.push(if round % 2 == 0 { (a, b) } else { (b, a) });
+                }
+            }
+
+            slots[1..].rotate_right(1);
+        }
+
+        Tournament {
+            standings: names.into_iter().map(Standing::new).collect(),
+            schedule,
+            played: 0,
+            in_progress: false,
+        }
+    }
+
+    /// Returns the tournament in progress on this device, if any.
+    pub fn load() -> Option<Tournament> {
+        serde_json::from_str(&App::kv_get(TOURNAMENT_KEY)).ok()
+    }
+
+    /// Persists the tournament, so it survives reloads between matches.
+    pub fn save(&self) {
+        App::kv_set(
+            TOURNAMENT_KEY,
+            &serde_json::to_string(self).unwrap_or_default(),
+        );
+    }
+
+    /// Abandons the tournament in progress on this device.
+    pub fn clear() {
+        App::kv_set(TOURNAMENT_KEY, "");
+    }
+
+    /// Names of the Red and Blue player of the next match, if any is left.
+    pub fn next_match(&self) -> Option<(&str, &str)> {
+        self.schedule.get(self.played).map(|(red, blue)| {
+            (
+                self.standings[*red].name.as_str(),
+                self.standings[*blue].name.as_str(),
+            )
+        })
+    }
+
+    /// Number of matches played and scheduled.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.played, self.schedule.len())
+    }
+
+    /// Marks the next match as started, so the result of the next hotseat game counts for it.
+    pub fn start_match(&mut self) {
+        self.in_progress = self.next_match().is_some();
+        self.save();
+    }
+
+    /// Determines if a started match awaits its result.
+    pub fn in_progress(&self) -> bool {
+        self.in_progress
+    }
+
+    /// Records the result of the started match, `capture` being the capture Red ended up with.
+    pub fn record(&mut self, result: Option<GameResult>, capture: i32) {
+        let Some((red, blue)) = self.schedule.get(self.played).copied() else {
+            return;
+        };
+
+        if !self.in_progress {
+            return;
+        }
+
+        for (index, team, differential) in [(red, Team::Red, capture), (blue, Team::Blue, -capture)]
+        {
+            let standing = &mut self.standings[index];
+
+            match result {
+                Some(GameResult::Win(winner)) if winner == team => standing.wins += 1,
+                Some(GameResult::Win(_)) => standing.losses += 1,
+                _ => standing.draws += 1,
+            }
+
+            standing.capture_differential += differential;
+        }
+
+        self.played += 1;
+        self.in_progress = false;
+        self.save();
+    }
+
+    /// Standings ordered by points, then by capture differential.
+    pub fn ranking(&self) -> Vec<&Standing> {
+        let mut ranking: Vec<&Standing> = self.standings.iter().collect();
+
+        ranking.sort_by_key(|standing| {
+            (
+                std::cmp::Reverse(standing.points()),
+                std::cmp::Reverse(standing.capture_differential),
+            )
+        });
+
+        ranking
+    }
+
+    /// The player atop the standings once every match is played.
+    pub fn champion(&self) -> Option<&Standing> {
+        if self.played < self.schedule.len() {
+            return None;
+        }
+
+        self.ranking().first().copied()
+    }
+}